@@ -0,0 +1,41 @@
+#import "shaders/lighting/shadow_segments.wgsl"::{
+    ShadowSegment, ShadowLight, MAX_LIGHT_SEGMENTS, SHADOW_LIST_STRIDE, closest_point_on_segment
+}
+
+@group(0) @binding(0) var<storage, read> segments: array<ShadowSegment>;
+@group(0) @binding(1) var<storage, read> lights: array<ShadowLight>;
+@group(0) @binding(2) var<storage, read_write> lists: array<u32>;
+
+// Finds the edges that can shadow each light, one light per invocation
+@compute @workgroup_size(64)
+fn cull(@builtin(global_invocation_id) id: vec3<u32>) {
+    let light_index = id.x;
+    if light_index >= arrayLength(&lights) {
+        return;
+    }
+    let light = lights[light_index];
+    let light_center = (light.a + light.b) * 0.5;
+    let reach = light.radius + distance(light.a, light.b) * 0.5;
+    let base = light_index * SHADOW_LIST_STRIDE;
+
+    var count = 0u;
+    for (var i = 0u; i < arrayLength(&segments); i++) {
+        let segment = segments[i];
+        // same as occluder_2d_occludes
+        if segment.depth != light.depth || (segment.groups & light.groups) == 0u {
+            continue;
+        }
+        let closest = closest_point_on_segment(segment.a, segment.b, light_center);
+        if distance(closest, light_center) > reach {
+            continue;
+        }
+        // lights with more edges than this are drawn with stencil shadows instead, see
+        // `prepare_shadow_lights`
+        if count == MAX_LIGHT_SEGMENTS {
+            break;
+        }
+        lists[base + 1u + count] = i;
+        count++;
+    }
+    lists[base] = count;
+}
@@ -23,3 +23,34 @@ fn normal_local_to_world(
         local_from_world_transpose_b,
     ) * vertex_normal;
 }
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+// How far into max_length a shadow starts fading, must match SHADOW_FADE_START in occluder.rs
+const SHADOW_FADE_START: f32 = 0.5;
+
+// How dark a point distance past the occluder casting a shadow is, from 1 in full shadow to 0
+// where the shadow has faded out. Mirrors shadow_fade in occluder.rs.
+fn shadow_fade(distance: f32, max_length: f32) -> f32 {
+    if max_length <= 0.0 {
+        return 0.0;
+    }
+    let start = max_length * SHADOW_FADE_START;
+    return 1.0 - clamp((distance - start) / (max_length - start), 0.0, 1.0);
+}
+
+// How far out from its center a lens keeps its full strength, must match LENS_EDGE_START in lens.rs
+const LENS_EDGE_START: f32 = 0.7;
+
+// How much the light at p is multiplied by when seen through lens, which holds the lens' center,
+// radius and gain. Mirrors LensFocus::gain_at in lens.rs.
+fn lens_gain(p: vec2<f32>, lens: vec4<f32>) -> f32 {
+    if lens.z <= 0.0 {
+        return 1.0;
+    }
+    let distance = length(p - lens.xy) / lens.z;
+    let edge = smoothstep(LENS_EDGE_START, 1.0, distance);
+    return 1.0 + (lens.w - 1.0) * (1.0 - edge);
+}
@@ -0,0 +1,12 @@
+#import bevy_sprite::mesh2d_vertex_output::VertexOutput
+
+@group(2) @binding(0) var<uniform> glow_color: vec4<f32>;
+@group(2) @binding(1) var glow_texture: texture_2d<f32>;
+@group(2) @binding(2) var glow_sampler: sampler;
+
+// added onto the scene by the blend state of `GlowSpriteMaterial`, so the alpha is unused
+@fragment
+fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
+    let fall_off = textureSample(glow_texture, glow_sampler, mesh.uv).a;
+    return vec4(glow_color.rgb * fall_off, 0.0);
+}
@@ -0,0 +1,22 @@
+#import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+#import "shaders/lighting/functions.wgsl" as light_functions
+
+@group(0) @binding(0) var unlit_image: texture_2d<f32>;
+@group(0) @binding(1) var unlit_sampler: sampler;
+@group(1) @binding(0) var light_buffer: texture_2d<f32>;
+@group(1) @binding(1) var light_buffer_sampler: sampler;
+
+// Lights the full resolution image with the upsampled light buffer. The light buffer only holds
+// the light reaching each pixel, so sprite edges come from the full resolution image and stay
+// sharp, only the light itself is filtered.
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    let base_color = textureSample(unlit_image, unlit_sampler, in.uv);
+    let light = textureSample(light_buffer, light_buffer_sampler, in.uv);
+
+    // the alpha holds the volumetric intensities of the lights weighted by their brightness
+    let volumetric_intensity = light.a / max(light_functions::luminance(light.rgb), 0.0001);
+    let shaded_color = base_color.rgb * light.rgb + light.rgb * volumetric_intensity;
+
+    return vec4<f32>(shaded_color, 0.0);
+}
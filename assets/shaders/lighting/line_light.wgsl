@@ -1,6 +1,11 @@
 #import bevy_render::view::View
 #import bevy_render::globals::Globals
 #import "shaders/lighting/functions.wgsl" as light_functions
+#ifdef COMPUTE_SHADOWS
+#import "shaders/lighting/shadow_segments.wgsl"::{
+    ShadowSegment, SHADOW_LIST_STRIDE, closest_point_on_segment, segment_shadow_distance
+}
+#endif
 
 struct Vertex {
     @location(0) position: vec3<f32>,
@@ -8,10 +13,22 @@ struct Vertex {
     @location(2) variant: u32,
 }
 
+// How far above the sprites lights are, so that normals facing the camera are still lit
+const LIGHT_HEIGHT: f32 = 16.0;
+
+// How many times the volumetric glow tests for shadows at each pixel, must match
+// VOLUMETRIC_MARCH_STEPS in compute_shadows.rs
+const VOLUMETRIC_MARCH_STEPS: u32 = 8u;
+
+// must match MAX_LIGHT_ROOM_RECTS in room_mask.rs
+const MAX_LIGHT_ROOM_RECTS: u32 = 16u;
+
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
     @location(0) world_position: vec4<f32>,
     @location(1) uv: vec2<f32>,
+    // computed from the clip position so it doesn't depend on the size of the render target
+    @location(2) screen_uv: vec2<f32>,
 }
 
 struct LineLight2d {
@@ -22,14 +39,41 @@ struct LineLight2d {
     half_length: f32,
     radius: f32,
     volumetric_intensity: f32,
+    // this light's list in shadow_lists
+    shadow_index: u32,
+    // the color shadowed parts of the light's range are lit with instead of black
+    shadow_tint: vec4<f32>,
+    // set if the light falls off with falloff_texture instead of the usual curve
+    falloff_texture: u32,
+    // how far out the light stays at full intensity, from 0 at the center line to 1 at radius
+    inner_fraction: f32,
+    // the min and max corners of the rooms the light is allowed to light, see LightRoomMask
+    room_rects: array<vec4<f32>, MAX_LIGHT_ROOM_RECTS>,
+    // how many of room_rects are used, 0 if the light lights everything
+    room_rect_count: u32,
+    // how far shadows reach past their occluder before they have faded out, see MaxShadowLength
+    max_shadow_length: f32,
+    // the center, radius and gain of the lens the light is seen through, see LensFocus
+    lens: vec4<f32>,
 }
 
 
 @group(0) @binding(0) var unlit_image: texture_2d<f32>;
 @group(0) @binding(1) var unlit_sampler: sampler;
+@group(0) @binding(2) var normal_image: texture_2d<f32>;
 @group(1) @binding(0) var<uniform> view: View;
 @group(1) @binding(1) var<uniform> globals: Globals;
 @group(2) @binding(0) var<uniform> light: LineLight2d;
+@group(2) @binding(1) var falloff_texture: texture_2d<f32>;
+@group(2) @binding(2) var falloff_sampler: sampler;
+#ifdef SOFT_SHADOWS
+@group(3) @binding(0) var shadow_mask: texture_2d<f32>;
+@group(3) @binding(1) var shadow_mask_sampler: sampler;
+#endif
+#ifdef COMPUTE_SHADOWS
+@group(3) @binding(0) var<storage, read> shadow_segments: array<ShadowSegment>;
+@group(3) @binding(1) var<storage, read> shadow_lists: array<u32>;
+#endif
 
 @vertex
 fn vertex(vertex: Vertex) -> VertexOutput {
@@ -49,33 +93,231 @@ fn vertex(vertex: Vertex) -> VertexOutput {
         vec4<f32>(new_position, 1.0)
     );
     out.position = light_functions::position_world_to_clip(out.world_position, view);
+    out.screen_uv = out.position.xy / out.position.w * vec2<f32>(0.5, -0.5) + vec2<f32>(0.5);
 
     return out;
 }
 
-fn line_light_color(uv: vec2<f32>, screen_uv: vec2<f32>) -> vec4<f32> {
+// How much the normal map drawn at screen_uv faces the closest point on the light
+fn line_light_normal_fall_off(world_position: vec2<f32>, screen_uv: vec2<f32>) -> f32 {
+    let normal_sample = textureSample(normal_image, unlit_sampler, screen_uv);
+    // nothing normal mapped was drawn here
+    if normal_sample.a == 0.0 {
+        return 1.0;
+    }
+    let normal = normalize(normal_sample.rgb * 2.0 - vec3<f32>(1.0));
+
+    let world_from_local = light_functions::get_world_from_local(light.world_from_local);
+    let light_center = (world_from_local * vec4<f32>(0.0, 0.0, 0.0, 1.0)).xy;
+    let light_axis = normalize((world_from_local * vec4<f32>(1.0, 0.0, 0.0, 0.0)).xy);
+    let along_light = clamp(
+        dot(world_position - light_center, light_axis),
+        -light.half_length,
+        light.half_length
+    );
+    let closest_point = light_center + light_axis * along_light;
+
+    let to_light = normalize(vec3<f32>(closest_point - world_position, LIGHT_HEIGHT));
+    return max(dot(normal, to_light), 0.0);
+}
+
+#ifdef COMPUTE_SHADOWS
+// How dark p is in the shadows of the edges found by compute_shadows.wgsl from light_point, from
+// 0 lit to 1 in full shadow. Mirrors shadow_amount in compute_shadows.rs.
+fn compute_shadow_from(light_point: vec2<f32>, p: vec2<f32>) -> f32 {
+    let base = light.shadow_index * SHADOW_LIST_STRIDE;
+    let count = shadow_lists[base];
+    var shadow = 0.0;
+    for (var i = 0u; i < count; i++) {
+        let segment = shadow_segments[shadow_lists[base + 1u + i]];
+        let distance = segment_shadow_distance(segment, light_point, p);
+        if distance >= 0.0 {
+            shadow = max(shadow, light_functions::shadow_fade(distance, light.max_shadow_length));
+            if shadow >= 1.0 {
+                break;
+            }
+        }
+    }
+    return shadow;
+}
+
+// How dark world_position is in the shadows from the closest point on this light
+fn compute_shadow(world_position: vec2<f32>) -> f32 {
+    let world_from_local = light_functions::get_world_from_local(light.world_from_local);
+    let light_a = (world_from_local * vec4<f32>(-light.half_length, 0.0, 0.0, 1.0)).xy;
+    let light_b = (world_from_local * vec4<f32>(light.half_length, 0.0, 0.0, 1.0)).xy;
+    let light_point = closest_point_on_segment(light_a, light_b, world_position);
+    return compute_shadow_from(light_point, world_position);
+}
+
+// How much of this light's volumetric glow reaches world_position, a pixel footprint world units
+// wide. Each step tests the shadows between a different point along the light and a different
+// point across the pixel, so gaps in occluders cast sharp shafts, and gaps thinner than a pixel
+// dim their shaft instead of flickering. Mirrors volumetric_visibility in compute_shadows.rs.
+fn compute_volumetric_visibility(world_position: vec2<f32>, footprint: f32) -> f32 {
+    let world_from_local = light_functions::get_world_from_local(light.world_from_local);
+    let light_a = (world_from_local * vec4<f32>(-light.half_length, 0.0, 0.0, 1.0)).xy;
+    let light_b = (world_from_local * vec4<f32>(light.half_length, 0.0, 0.0, 1.0)).xy;
+
+    let steps = f32(VOLUMETRIC_MARCH_STEPS);
+    var visible = 0.0;
+    for (var i = 0u; i < VOLUMETRIC_MARCH_STEPS; i++) {
+        let along_light = (f32(i) + 0.5) / steps;
+        // shuffled so that the points across the pixel aren't lined up with the light's
+        let across_pixel = (f32(i * 3u % VOLUMETRIC_MARCH_STEPS) + 0.5) / steps - 0.5;
+        let light_point = mix(light_a, light_b, along_light);
+        let to_pixel = world_position - light_point;
+        var across = vec2<f32>(0.0);
+        if dot(to_pixel, to_pixel) > 0.0 {
+            let dir = normalize(to_pixel);
+            across = vec2<f32>(-dir.y, dir.x);
+        }
+        let p = world_position + across * across_pixel * footprint;
+        visible += 1.0 - compute_shadow_from(light_point, p);
+    }
+    return visible / steps;
+}
+#endif
+
+// The light added at world_position by a light with light_rgba, which is either the light's color or
+// its shadow tint. The volumetric glow is scaled by volumetric_visibility.
+fn line_light_color(
+    uv: vec2<f32>,
+    screen_uv: vec2<f32>,
+    world_position: vec2<f32>,
+    light_rgba: vec4<f32>,
+    volumetric_visibility: f32
+) -> vec4<f32> {
     let one_tex_uv = uv * 2.0 - vec2<f32>(1.0); // -1 to 1
-    let base_color = textureSample(unlit_image, unlit_sampler, screen_uv);
 
-    let distance = min(length(one_tex_uv), 1.0);
+    // the inner vertices are on the center line and the outer ones at the edge of the range, so
+    // the fall off starts at inner_fraction from there
+    var distance = min(length(one_tex_uv), 1.0);
+    if distance < light.inner_fraction {
+        distance = 0.0;
+    } else if light.inner_fraction >= 1.0 {
+        distance = 1.0;
+    } else {
+        distance = (distance - light.inner_fraction) / (1.0 - light.inner_fraction);
+    }
     // let angle = abs(atan2(one_tex_uv.y, one_tex_uv.x));
 
-    let radial_fall_off = pow(1.0 - distance, 2.0);
+    var radial_fall_off = pow(1.0 - distance, 2.0);
+    if light.falloff_texture != 0u {
+        // from the center line on the left to the edge of the light's range on the right, along
+        // the middle of the first row
+        let first_row = 0.5 / f32(textureDimensions(falloff_texture).y);
+        radial_fall_off = textureSampleLevel(
+            falloff_texture,
+            falloff_sampler,
+            vec2<f32>(distance, first_row),
+            0.0
+        ).r;
+    }
     // let angular_fall_off = smoothstep(-3.14159, 3.14159, angle);
-    let normal_fall_off = 1.0;
-    let intensity = light.color.a;
+    let normal_fall_off = line_light_normal_fall_off(world_position, screen_uv);
+    let intensity = light_rgba.a;
 
-    let final_intensity = intensity * radial_fall_off * normal_fall_off;
-    let light_color = final_intensity * light.color.rgb;
-    let shaded_color = base_color.rgb * light_color + light_color * light.volumetric_intensity;
+    let lens_gain = light_functions::lens_gain(world_position, light.lens);
+
+    let final_intensity = intensity * radial_fall_off * normal_fall_off * lens_gain;
+    let light_color = final_intensity * light_rgba.rgb;
+#ifdef LIGHT_BUFFER
+    // the sprites are lit when the light buffer is composited, see light_buffer.wgsl
+    let volumetric = light_functions::luminance(light_color) * light.volumetric_intensity
+        * volumetric_visibility;
+    return vec4<f32>(light_color, volumetric);
+#else
+    let base_color = textureSample(unlit_image, unlit_sampler, screen_uv);
+    let volumetric = light.volumetric_intensity * volumetric_visibility;
+    let shaded_color = base_color.rgb * light_color + light_color * volumetric;
 
     return vec4<f32>(shaded_color, 1.0);
+#endif
+}
+
+// Darkens color towards the light's shadow tint by shadow, from 0 lit to 1 in full shadow
+fn shade(
+    uv: vec2<f32>,
+    screen_uv: vec2<f32>,
+    world_position: vec2<f32>,
+    color: vec4<f32>,
+    shadow: f32
+) -> vec4<f32> {
+    var tint = vec4<f32>(0.0);
+    if light.shadow_tint.a > 0.0 {
+        tint = line_light_color(uv, screen_uv, world_position, light.shadow_tint, 1.0);
+    }
+#ifdef LIGHT_BUFFER
+    return mix(color, tint, shadow);
+#else
+    return vec4<f32>(mix(color.rgb, tint.rgb, shadow), color.a);
+#endif
+}
+
+// Whether the light is allowed to light world_position, mirrors LightRoomMask::contains
+fn in_light_room(world_position: vec2<f32>) -> bool {
+    if light.room_rect_count == 0u {
+        return true;
+    }
+    for (var i = 0u; i < min(light.room_rect_count, MAX_LIGHT_ROOM_RECTS); i++) {
+        let rect = light.room_rects[i];
+        if all(world_position >= rect.xy) && all(world_position <= rect.zw) {
+            return true;
+        }
+    }
+    return false;
 }
 
 @fragment
 fn fragment(
     in: VertexOutput
 ) -> @location(0) vec4<f32> {
-    let screen_uv = in.position.xy / view.viewport.zw;
-    return line_light_color(in.uv, screen_uv);
+#ifdef COMPUTE_SHADOWS
+    // taken before branching, derivatives need uniform control flow
+    let footprint = length(dpdx(in.world_position.xy));
+#endif
+    if !in_light_room(in.world_position.xy) {
+        discard;
+    }
+#ifdef SHADOW_TINT
+    // only drawn where the stencil culled the light
+    return line_light_color(in.uv, in.screen_uv, in.world_position.xy, light.shadow_tint, 1.0);
+#else
+#ifdef COMPUTE_SHADOWS
+    let shadow = compute_shadow(in.world_position.xy);
+    if shadow >= 1.0 {
+        if light.shadow_tint.a == 0.0 {
+            discard;
+        }
+        return line_light_color(in.uv, in.screen_uv, in.world_position.xy, light.shadow_tint, 1.0);
+    }
+    // the march is only worth its cost for lights with a volumetric glow
+    var volumetric_visibility = 1.0;
+    if light.volumetric_intensity > 0.0 {
+        volumetric_visibility = compute_volumetric_visibility(in.world_position.xy, footprint);
+    }
+#else
+    let volumetric_visibility = 1.0;
+#endif
+    let color = line_light_color(
+        in.uv,
+        in.screen_uv,
+        in.world_position.xy,
+        light.color,
+        volumetric_visibility
+    );
+#ifdef SOFT_SHADOWS
+    let shadow = textureSample(shadow_mask, shadow_mask_sampler, in.screen_uv).r;
+    return shade(in.uv, in.screen_uv, in.world_position.xy, color, shadow);
+#else
+#ifdef COMPUTE_SHADOWS
+    // the faded out end of a shadow
+    if shadow > 0.0 {
+        return shade(in.uv, in.screen_uv, in.world_position.xy, color, shadow);
+    }
+#endif
+    return color;
+#endif
+#endif
 }
@@ -0,0 +1,72 @@
+#import bevy_render::view::View
+#import "shaders/lighting/functions.wgsl" as light_functions
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+struct NormalMap2d {
+    world_from_local: mat3x4<f32>,
+    world_from_local_normal: vec4<f32>,
+    size: vec2<f32>,
+    anchor: vec2<f32>,
+    flip: vec2<f32>,
+    _wasm_padding: vec2<f32>,
+}
+
+@group(0) @binding(0) var<uniform> view: View;
+@group(1) @binding(0) var<uniform> normal_map: NormalMap2d;
+@group(2) @binding(0) var normal_texture: texture_2d<f32>;
+@group(2) @binding(1) var normal_sampler: sampler;
+
+@vertex
+fn vertex(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(0.0, 0.0),
+    );
+    let corner = corners[index];
+
+    var out: VertexOutput;
+
+    // images are stored top to bottom, while world space y points up
+    out.uv = vec2<f32>(corner.x, 1.0 - corner.y);
+    if normal_map.flip.x < 0.0 {
+        out.uv.x = 1.0 - out.uv.x;
+    }
+    if normal_map.flip.y < 0.0 {
+        out.uv.y = 1.0 - out.uv.y;
+    }
+
+    let local_position = (corner - vec2<f32>(0.5) - normal_map.anchor) * normal_map.size;
+    let world_from_local = light_functions::get_world_from_local(normal_map.world_from_local);
+    let world_position = light_functions::position_local_to_world(
+        world_from_local,
+        vec4<f32>(local_position, 0.0, 1.0)
+    );
+    out.position = light_functions::position_world_to_clip(world_position, view);
+
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = textureSample(normal_texture, normal_sampler, in.uv);
+    if texel.a < 0.5 {
+        discard;
+    }
+
+    let local_normal = texel.rgb * 2.0 - vec3<f32>(1.0);
+    let world_from_local_normal = mat2x2<f32>(
+        normal_map.world_from_local_normal.xy,
+        normal_map.world_from_local_normal.zw,
+    );
+    let normal = normalize(vec3<f32>(world_from_local_normal * local_normal.xy, local_normal.z));
+
+    return vec4<f32>(normal * 0.5 + vec3<f32>(0.5), 1.0);
+}
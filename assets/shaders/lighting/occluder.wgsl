@@ -1,5 +1,6 @@
 #import bevy_render::view::View
 #import bevy_render::globals::Globals
+#import bevy_render::maths::mat2x4_f32_to_mat3x3_unpack
 #import "shaders/lighting/functions.wgsl" as light_functions
 #import "shaders/lighting/line_light.wgsl"::LineLight2d
 
@@ -10,19 +11,51 @@ struct Vertex {
 
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
+    @location(0) world_position: vec4<f32>,
+    // how far the shadow has reached past the occluder here
+    @location(1) shadow_distance: f32,
 }
 
+// How far the edges of an occluder facing away from the light are pushed to cast its shadow
+const SHADOW_PUSH: f32 = 400.0;
+
 struct Occluder2d {
     world_from_local: mat3x4<f32>,
     local_from_world_transpose_a: mat2x4<f32>,
     local_from_world_transpose_b: f32,
     half_size: vec2<f32>,
+    // set for occluders with a CookieAnimation
+    animated: u32,
+    rotation_speed: f32,
+    scroll: vec2<f32>,
 }
 
 @group(1) @binding(0) var<uniform> view: View;
 @group(1) @binding(1) var<uniform> globals: Globals;
 @group(2) @binding(0) var<uniform> light: LineLight2d;
+#ifndef OCCLUDER_BATCHED
 @group(3) @binding(0) var<uniform> occluder: Occluder2d;
+#endif
+
+#ifdef OCCLUDER_ALPHA_MASK
+@group(4) @binding(0) var alpha_mask_texture: texture_2d<f32>;
+@group(4) @binding(1) var alpha_mask_sampler: sampler;
+
+// Number of points sampled along the part of a light ray that passes through the occluder
+const ALPHA_MASK_SAMPLES: i32 = 16;
+#endif
+
+// Returns a threshold from a 4x4 ordered dither pattern for the pixel at position, between 0 and 1
+fn dither_threshold(position: vec2<f32>) -> f32 {
+    var bayer = array<u32, 16>(
+        0u, 8u, 2u, 10u,
+        12u, 4u, 14u, 6u,
+        3u, 11u, 1u, 9u,
+        15u, 7u, 13u, 5u,
+    );
+    let pixel = vec2<u32>(position) % vec2<u32>(4u);
+    return (f32(bayer[pixel.y * 4u + pixel.x]) + 0.5) / 16.0;
+}
 
 // Returns the point on the infinite line (through a and b) that is closest to p
 fn closest_point_on_line(a: vec2<f32>, b: vec2<f32>, p: vec2<f32>) -> vec2<f32> {
@@ -32,13 +65,8 @@ fn closest_point_on_line(a: vec2<f32>, b: vec2<f32>, p: vec2<f32>) -> vec2<f32>
     return a + t * d;
 }
 
-@vertex
-fn vertex(vertex: Vertex) -> VertexOutput {
+fn light_endpoints() -> array<vec2<f32>, 2> {
     let light_world_from_local = light_functions::get_world_from_local(light.world_from_local);
-    let light_center = light_functions::position_local_to_world(
-        light_world_from_local,
-        vec4<f32>(0.0, 0.0, 0.0, 1.0),
-    );
     let light_a = light_functions::position_local_to_world(
         light_world_from_local,
         vec4<f32>(-light.half_length, 0.0, 0.0, 1.0),
@@ -47,32 +75,134 @@ fn vertex(vertex: Vertex) -> VertexOutput {
         light_world_from_local,
         vec4<f32>(light.half_length, 0.0, 0.0, 1.0),
     );
+    return array<vec2<f32>, 2>(light_a.xy, light_b.xy);
+}
 
+#ifdef OCCLUDER_ALPHA_MASK
+fn position_world_to_occluder_local(world_position: vec2<f32>) -> vec2<f32> {
+    // occluders are never scaled, so the inverse of the rotation is its transpose
+    let world_from_local = light_functions::get_world_from_local(occluder.world_from_local);
+    let rotation = mat2x4_f32_to_mat3x3_unpack(
+        occluder.local_from_world_transpose_a,
+        occluder.local_from_world_transpose_b,
+    );
+    return (transpose(rotation) * (vec3<f32>(world_position, 0.0) - world_from_local[3].xyz)).xy;
+}
+
+// Returns the alpha of the mask at uv, turned and slid by the occluder's CookieAnimation
+fn sample_alpha_mask(uv: vec2<f32>) -> f32 {
+    if occluder.animated == 0u {
+        return textureSampleLevel(alpha_mask_texture, alpha_mask_sampler, uv, 0.0).a;
+    }
+    // uv is flipped vertically, so turning it clockwise turns the mask counterclockwise
+    let angle = occluder.rotation_speed * globals.time;
+    let rotation = mat2x2<f32>(cos(angle), sin(angle), -sin(angle), cos(angle));
+    let turned = rotation * (uv - vec2<f32>(0.5));
+    var animated_uv = turned + vec2<f32>(0.5);
+    // the corners of a turned mask are outside of the image, and let light through
+    if any(animated_uv < vec2<f32>(0.0)) || any(animated_uv > vec2<f32>(1.0)) {
+        return 0.0;
+    }
+    animated_uv = fract(animated_uv + occluder.scroll * globals.time);
+    return textureSampleLevel(alpha_mask_texture, alpha_mask_sampler, animated_uv, 0.0).a;
+}
+
+// Returns the highest alpha of the mask along the segment from the light to p, only considering
+// the part of the segment that is inside of the occluder
+fn alpha_mask_along_ray(light_point: vec2<f32>, p: vec2<f32>) -> f32 {
+    let ray_start = position_world_to_occluder_local(light_point);
+    let ray_end = position_world_to_occluder_local(p);
+    var dir = ray_end - ray_start;
+    dir = select(dir, vec2<f32>(0.0001), abs(dir) < vec2<f32>(0.0001));
+
+    // clip the segment to the occluder's rectangle
+    let t0 = (-occluder.half_size - ray_start) / dir;
+    let t1 = (occluder.half_size - ray_start) / dir;
+    let t_enter = max(max(min(t0.x, t1.x), min(t0.y, t1.y)), 0.0);
+    let t_exit = min(min(max(t0.x, t1.x), max(t0.y, t1.y)), 1.0);
+    if t_enter > t_exit {
+        return 0.0;
+    }
+
+    var alpha = 0.0;
+    for (var i = 0; i < ALPHA_MASK_SAMPLES; i++) {
+        let t = mix(t_enter, t_exit, (f32(i) + 0.5) / f32(ALPHA_MASK_SAMPLES));
+        let local = ray_start + dir * t;
+        // uv (0, 0) is the top left of the image
+        var uv = local / (2.0 * occluder.half_size) + vec2<f32>(0.5);
+        uv.y = 1.0 - uv.y;
+        alpha = max(alpha, sample_alpha_mask(uv));
+    }
+    return alpha;
+}
+#endif
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    let light_world_from_local = light_functions::get_world_from_local(light.world_from_local);
+    let light_center = light_functions::position_local_to_world(
+        light_world_from_local,
+        vec4<f32>(0.0, 0.0, 0.0, 1.0),
+    );
+    let light_ends = light_endpoints();
+    let light_a = light_ends[0];
+    let light_b = light_ends[1];
+
+#ifdef OCCLUDER_BATCHED
+    // batched occluder vertices are already in world space
+    var world_position = vec4<f32>(vertex.position, 1.0);
+#else
     let world_from_local = light_functions::get_world_from_local(occluder.world_from_local);
     let new_position = vertex.position * vec3<f32>(occluder.half_size, 1.0);
     var world_position = light_functions::position_local_to_world(
         world_from_local,
         vec4<f32>(new_position, 1.0)
     );
+#endif
 
+    var shadow_distance = 0.0;
 #ifndef OCCLUDER_CUTOUT
-    let closest_point = closest_point_on_line(light_a.xy, light_b.xy, world_position.xy);
+    let closest_point = closest_point_on_line(light_a, light_b, world_position.xy);
     if distance(closest_point, world_position.xy) < light.radius {
         let point_to_light = normalize(closest_point - world_position.xy);
         let dot_product = dot(point_to_light, vertex.normal.xy);
         if dot_product < 0.0 {
-            world_position += vec4<f32>(-400.0 * point_to_light, 0.0, 0.0);
+            world_position += vec4<f32>(-SHADOW_PUSH * point_to_light, 0.0, 0.0);
+            shadow_distance = SHADOW_PUSH;
         }
     }
 #endif
 
     var output: VertexOutput;
     output.position = light_functions::position_world_to_clip(world_position, view);
+    output.world_position = world_position;
+    output.shadow_distance = shadow_distance;
     return output;
 }
 
 @fragment
 fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+#ifdef OCCLUDER_ALPHA_MASK
+    // let light through where the ray from the light to this fragment only crosses the
+    // transparent parts of the occluder
+    let light_ends = light_endpoints();
+    let light_point = closest_point_on_line(light_ends[0], light_ends[1], in.world_position.xy);
+    if alpha_mask_along_ray(light_point, in.world_position.xy) < 0.5 {
+        discard;
+    }
+#endif
+#ifndef OCCLUDER_CUTOUT
+    let fade = light_functions::shadow_fade(in.shadow_distance, light.max_shadow_length);
+#ifdef OCCLUDER_SHADOW_MASK
+    // overlapping shadows are combined with max blending
+    return vec4<f32>(fade);
+#else
+    // the stencil is either shadowed or not, so the fade is dithered
+    if fade < dither_threshold(in.position.xy) {
+        discard;
+    }
+#endif
+#endif
     return vec4<f32>(0.0, 0.0, 0.0, 0.0);
     // return vec4<f32>(1.0, 0.0, 0.0, 0.05);
 }
@@ -0,0 +1,42 @@
+#import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+
+struct ShadowBlur {
+    radius: f32,
+    _wasm_padding_a: f32,
+    _wasm_padding_b: vec2<f32>,
+}
+
+@group(0) @binding(0) var shadow_mask: texture_2d<f32>;
+@group(0) @binding(1) var shadow_mask_sampler: sampler;
+@group(0) @binding(2) var<uniform> blur: ShadowBlur;
+
+// Keeps the number of samples bounded no matter what radius is configured
+const MAX_BLUR_RADIUS: i32 = 16;
+
+// One direction of a separable gaussian blur, horizontal unless BLUR_VERTICAL is defined
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    let texel_size = 1.0 / vec2<f32>(textureDimensions(shadow_mask));
+#ifdef BLUR_VERTICAL
+    let texel_step = vec2<f32>(0.0, texel_size.y);
+#else
+    let texel_step = vec2<f32>(texel_size.x, 0.0);
+#endif
+
+    let radius = min(i32(ceil(blur.radius)), MAX_BLUR_RADIUS);
+    if radius <= 0 {
+        return textureSampleLevel(shadow_mask, shadow_mask_sampler, in.uv, 0.0);
+    }
+
+    let sigma = blur.radius / 2.0;
+    var shadow = 0.0;
+    var total_weight = 0.0;
+    for (var i = -radius; i <= radius; i++) {
+        let offset = f32(i);
+        let weight = exp(-offset * offset / (2.0 * sigma * sigma));
+        let uv = in.uv + texel_step * offset;
+        shadow += weight * textureSampleLevel(shadow_mask, shadow_mask_sampler, uv, 0.0).r;
+        total_weight += weight;
+    }
+    return vec4<f32>(shadow / total_weight, 0.0, 0.0, 1.0);
+}
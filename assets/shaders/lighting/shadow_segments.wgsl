@@ -0,0 +1,58 @@
+// Shared between compute_shadows.wgsl and the COMPUTE_SHADOWS lights, see compute_shadows.rs
+
+// The most occluder edges that can shadow a single light, must match MAX_LIGHT_SEGMENTS
+const MAX_LIGHT_SEGMENTS: u32 = 255u;
+// Each light's list is its number of edges followed by their indices
+const SHADOW_LIST_STRIDE: u32 = 256u;
+
+// An edge of an occluder in world space, counterclockwise around the occluder
+struct ShadowSegment {
+    a: vec2<f32>,
+    b: vec2<f32>,
+    groups: u32,
+    depth: u32,
+}
+
+struct ShadowLight {
+    a: vec2<f32>,
+    b: vec2<f32>,
+    radius: f32,
+    groups: u32,
+    depth: u32,
+}
+
+fn closest_point_on_segment(a: vec2<f32>, b: vec2<f32>, p: vec2<f32>) -> vec2<f32> {
+    let ab = b - a;
+    let length_squared = dot(ab, ab);
+    if length_squared == 0.0 {
+        return a;
+    }
+    return a + ab * clamp(dot(p - a, ab) / length_squared, 0.0, 1.0);
+}
+
+fn cross_2d(v: vec2<f32>, w: vec2<f32>) -> f32 {
+    return v.x * w.y - v.y * w.x;
+}
+
+// How far past segment p is, if the ray from light_point to p leaves an occluder through
+// segment, which puts p in its shadow. -1 if it doesn't. Only edges facing away from the light
+// count, so the occluder's own body stays lit like with the stencil cutout.
+fn segment_shadow_distance(segment: ShadowSegment, light_point: vec2<f32>, p: vec2<f32>) -> f32 {
+    let edge = segment.b - segment.a;
+    let ray = p - light_point;
+    let outward = vec2<f32>(edge.y, -edge.x);
+    if dot(outward, ray) <= 0.0 {
+        return -1.0;
+    }
+    let denom = cross_2d(ray, edge);
+    if abs(denom) < 1e-6 {
+        return -1.0;
+    }
+    let to_a = segment.a - light_point;
+    let t = cross_2d(to_a, edge) / denom;
+    let u = cross_2d(to_a, ray) / denom;
+    if t < 0.0 || t > 1.0 || u < 0.0 || u > 1.0 {
+        return -1.0;
+    }
+    return (1.0 - t) * length(ray);
+}
@@ -0,0 +1,19 @@
+#import bevy_sprite::mesh2d_vertex_output::VertexOutput
+
+struct Vignette {
+    color: vec4<f32>,
+    strength: f32,
+}
+
+@group(2) @binding(0) var<uniform> vignette: Vignette;
+
+// how far from the center of the screen the vignette starts, where 1 is a corner
+const VIGNETTE_START: f32 = 0.4;
+
+@fragment
+fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
+    let distance = length((mesh.uv - 0.5) * 2.0) / sqrt(2.0);
+    let falloff = smoothstep(VIGNETTE_START, 1.0, distance);
+    let amount = clamp(falloff * vignette.strength, 0.0, 1.0);
+    return vec4(vignette.color.rgb, vignette.color.a * amount);
+}
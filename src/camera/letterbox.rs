@@ -0,0 +1,175 @@
+use bevy::{prelude::*, render::camera::Viewport, window::PrimaryWindow};
+
+use super::{TransitionCamera, UpscaleCamera};
+
+/// [`Plugin`] that keeps the game at a fixed aspect ratio, see [`Letterbox`].
+pub struct LetterboxPlugin;
+
+impl Plugin for LetterboxPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Letterbox>()
+            .add_systems(Update, apply_letterbox);
+    }
+}
+
+/// [`Resource`] for the aspect ratio, width over height, the game is locked to. Windows with a
+/// different aspect ratio get black bars above and below or on the sides, instead of stretching
+/// the scene. The [`UpscaleCamera`] only draws the scene inside of the active region in the
+/// middle, and the [`TransitionCamera`], which the UI is drawn by, is limited to the same region
+/// so the UI stays inside of it. `None` stretches the scene over the whole window. See
+/// `aspect_ratio` in the `window_config` section of `Lightborne.toml`.
+#[derive(Resource, Clone, Copy, Default, Debug, PartialEq)]
+pub struct Letterbox {
+    pub aspect_ratio: Option<f32>,
+}
+
+impl Letterbox {
+    /// The part of a window of `window_size` that the game is drawn in, which is the largest
+    /// rectangle in the middle of the window with the aspect ratio.
+    pub fn active_rect(&self, window_size: Vec2) -> Rect {
+        let full = Rect::from_corners(Vec2::ZERO, window_size);
+        let Some(aspect) = self
+            .aspect_ratio
+            .filter(|aspect| aspect.is_finite() && *aspect > 0.0)
+        else {
+            return full;
+        };
+        if window_size.cmple(Vec2::ZERO).any() {
+            return full;
+        }
+        let size = if window_size.x / window_size.y > aspect {
+            Vec2::new(window_size.y * aspect, window_size.y)
+        } else {
+            Vec2::new(window_size.x, window_size.x / aspect)
+        };
+        Rect::from_center_size(window_size * 0.5, size)
+    }
+
+    /// The [`Viewport`] of the active region in a window of `window_size` physical pixels, or
+    /// `None` if the game isn't letterboxed.
+    pub fn viewport(&self, window_size: UVec2) -> Option<Viewport> {
+        self.aspect_ratio?;
+        let rect = self.active_rect(window_size.as_vec2());
+        Some(Viewport {
+            physical_position: rect.min.round().as_uvec2(),
+            physical_size: rect.size().round().as_uvec2().max(UVec2::ONE),
+            ..default()
+        })
+    }
+
+    /// The size of the active region in a window of `window_size` physical pixels.
+    pub fn active_size(&self, window_size: UVec2) -> UVec2 {
+        self.viewport(window_size)
+            .map_or(window_size, |viewport| viewport.physical_size)
+    }
+}
+
+/// [`System`] that limits the cameras drawing to the window to the active region of the
+/// [`Letterbox`].
+pub fn apply_letterbox(
+    letterbox: Res<Letterbox>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    mut q_cameras: Query<&mut Camera, Or<(With<UpscaleCamera>, With<TransitionCamera>)>>,
+) {
+    let Ok(window) = q_window.get_single() else {
+        return;
+    };
+    let viewport = letterbox.viewport(window.physical_size());
+    for mut camera in q_cameras.iter_mut() {
+        let unchanged = match (&camera.viewport, &viewport) {
+            (None, None) => true,
+            (Some(current), Some(viewport)) => {
+                current.physical_position == viewport.physical_position
+                    && current.physical_size == viewport.physical_size
+            }
+            _ => false,
+        };
+        if !unchanged {
+            camera.viewport = viewport.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::window::WindowResolution;
+
+    use super::*;
+
+    #[test]
+    fn letterbox_keeps_aspect_ratio_with_bars() {
+        let letterbox = Letterbox {
+            aspect_ratio: Some(16.0 / 9.0),
+        };
+
+        // ultrawide windows get bars on the sides
+        let viewport = letterbox.viewport(UVec2::new(2560, 1080)).unwrap();
+        assert_eq!(viewport.physical_size, UVec2::new(1920, 1080));
+        assert_eq!(viewport.physical_position, UVec2::new(320, 0));
+
+        // tall windows get bars above and below
+        let viewport = letterbox.viewport(UVec2::new(1280, 1024)).unwrap();
+        assert_eq!(viewport.physical_size, UVec2::new(1280, 720));
+        assert_eq!(viewport.physical_position, UVec2::new(0, 152));
+
+        // and windows with the right aspect ratio get none
+        let viewport = letterbox.viewport(UVec2::new(1920, 1080)).unwrap();
+        assert_eq!(viewport.physical_size, UVec2::new(1920, 1080));
+        assert_eq!(viewport.physical_position, UVec2::ZERO);
+
+        let stretched = Letterbox::default();
+        assert!(stretched.viewport(UVec2::new(2560, 1080)).is_none());
+        assert_eq!(
+            stretched.active_size(UVec2::new(2560, 1080)),
+            UVec2::new(2560, 1080)
+        );
+    }
+
+    #[test]
+    fn cameras_drawing_to_the_window_are_letterboxed() {
+        let mut app = App::new();
+        app.insert_resource(Letterbox {
+            aspect_ratio: Some(16.0 / 9.0),
+        })
+        .add_systems(Update, apply_letterbox);
+        app.world_mut().spawn((
+            Window {
+                resolution: WindowResolution::new(2560.0, 1080.0).with_scale_factor_override(1.0),
+                ..default()
+            },
+            PrimaryWindow,
+        ));
+        let upscale = app
+            .world_mut()
+            .spawn((Camera::default(), UpscaleCamera))
+            .id();
+        let transition = app
+            .world_mut()
+            .spawn((Camera::default(), TransitionCamera))
+            .id();
+        let other = app.world_mut().spawn(Camera::default()).id();
+        app.update();
+
+        for camera in [upscale, transition] {
+            let viewport = app
+                .world()
+                .get::<Camera>(camera)
+                .unwrap()
+                .viewport
+                .clone()
+                .unwrap();
+            assert_eq!(viewport.physical_position, UVec2::new(320, 0));
+            assert_eq!(viewport.physical_size, UVec2::new(1920, 1080));
+        }
+        assert!(app.world().get::<Camera>(other).unwrap().viewport.is_none());
+
+        app.world_mut().resource_mut::<Letterbox>().aspect_ratio = None;
+        app.update();
+        assert!(app
+            .world()
+            .get::<Camera>(upscale)
+            .unwrap()
+            .viewport
+            .is_none());
+    }
+}
@@ -4,31 +4,55 @@ use bevy::{
     core_pipeline::tonemapping::Tonemapping,
     ecs::system::SystemId,
     prelude::*,
-    render::{camera::ScalingMode, view::RenderLayers},
+    render::{
+        camera::{RenderTarget, ScalingMode},
+        view::RenderLayers,
+    },
 };
+use bevy_ecs_ldtk::LevelIid;
 use bevy_rapier2d::plugin::PhysicsSet;
+use letterbox::LetterboxPlugin;
+use resolution::{DynamicResolutionPlugin, SceneRenderTarget};
+use shake::CameraShakePlugin;
+use thumbnail::LevelThumbnailPlugin;
+use vignette::VignettePlugin;
 
 use crate::{
+    config::Config,
     level::{switch_level, CurrentLevel, LevelSystems},
     lighting::AmbientLight2d,
     player::PlayerMarker,
 };
 
+pub mod letterbox;
+pub mod resolution;
+pub mod shake;
+pub mod thumbnail;
+pub mod vignette;
+
 /// The [`Plugin`] responsible for handling anything Camera related.
 pub struct CameraPlugin;
 
 impl Plugin for CameraPlugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<CameraMoveEvent>()
+        app.add_plugins(CameraShakePlugin)
+            .add_plugins(DynamicResolutionPlugin)
+            .add_plugins(LetterboxPlugin)
+            .add_plugins(LevelThumbnailPlugin)
+            .add_plugins(VignettePlugin)
+            .add_event::<CameraMoveEvent>()
             .add_event::<CameraZoomEvent>()
             .add_event::<CameraTransitionEvent>()
             .add_systems(Startup, setup_camera)
             .add_systems(
                 FixedUpdate,
-                move_camera
-                    .after(PhysicsSet::Writeback)
-                    .after(switch_level)
-                    .in_set(LevelSystems::Simulation),
+                (
+                    move_camera
+                        .after(PhysicsSet::Writeback)
+                        .in_set(LevelSystems::Simulation),
+                    zoom_camera_to_level,
+                )
+                    .after(switch_level),
             )
             .add_systems(
                 Update,
@@ -68,14 +92,24 @@ pub struct TransitionMeshMarker;
 
 pub const TRANSITION_CAMERA_LAYER: RenderLayers = RenderLayers::layer(5);
 
+/// Marker [`Component`] for the camera that draws the [`SceneRenderTarget`] over the window.
+#[derive(Component)]
+pub struct UpscaleCamera;
+
+pub const UPSCALE_CAMERA_LAYER: RenderLayers = RenderLayers::layer(6);
+
 /// [`Startup`] [`System`] that spawns the [`Camera2d`] in the world.
 ///
 /// Notes:
 /// - Spawns the camera with [`OrthographicProjection`] with fixed scaling at 320x180
+/// - The [`MainCamera`] and [`BackgroundCamera`] render to the [`SceneRenderTarget`], which the
+///   [`UpscaleCamera`] stretches over the window, or the part of it left by the
+///   [`Letterbox`](letterbox::Letterbox)
 pub fn setup_camera(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
+    scene_target: Res<SceneRenderTarget>,
 ) {
     let projection = OrthographicProjection {
         scaling_mode: ScalingMode::Fixed {
@@ -90,7 +124,7 @@ pub fn setup_camera(
         TransitionCamera,
         Camera {
             hdr: true,
-            order: 2,
+            order: 3,
             clear_color: ClearColorConfig::None,
             ..default()
         },
@@ -99,6 +133,33 @@ pub fn setup_camera(
         TRANSITION_CAMERA_LAYER,
     ));
 
+    commands.spawn((
+        Camera2d,
+        UpscaleCamera,
+        Camera {
+            hdr: true,
+            order: 2,
+            // only shows outside of the scene, as the bars of the letterbox
+            clear_color: ClearColorConfig::Custom(Color::BLACK),
+            ..default()
+        },
+        // the scene was already tonemapped by the main camera
+        Tonemapping::None,
+        projection.clone(),
+        Transform::default(),
+        UPSCALE_CAMERA_LAYER,
+    ));
+
+    commands.spawn((
+        Sprite {
+            image: scene_target.0.clone(),
+            custom_size: Some(Vec2::new(CAMERA_WIDTH, CAMERA_HEIGHT)),
+            ..default()
+        },
+        Transform::default(),
+        UPSCALE_CAMERA_LAYER,
+    ));
+
     commands.spawn((
         Mesh2d(meshes.add(Rectangle::new(320.0, 180.0))),
         MeshMaterial2d(materials.add(Color::BLACK)),
@@ -118,6 +179,7 @@ pub fn setup_camera(
             hdr: true,
             order: 1,
             clear_color: ClearColorConfig::None,
+            target: RenderTarget::Image(scene_target.0.clone()),
             ..default()
         },
         Tonemapping::TonyMcMapface,
@@ -132,6 +194,7 @@ pub fn setup_camera(
         Camera {
             hdr: true, // If Cameras mix HDR and non-HDR, then weird ass stuff happens. Seems like
             // https://github.com/bevyengine/bevy/pull/13419 was only a partial fix
+            target: RenderTarget::Image(scene_target.0.clone()),
             ..default()
         },
         projection,
@@ -144,6 +207,8 @@ pub fn setup_camera(
 pub enum CameraTransition {
     SlideToBlack,
     SlideFromBlack,
+    /// Stops the current transition without running its callback, and uncovers the screen
+    Cancel,
 }
 
 #[derive(Event, Debug)]
@@ -212,6 +277,11 @@ pub fn handle_transition_camera(
                 curve: EasingCurve::new(0.0, 1.0, event.ease_fn),
                 callback: event.callback,
             },
+            CameraTransition::Cancel => {
+                mesh_transform.translation = Vec3::new(0.0, -CAMERA_HEIGHT, 0.0);
+                *animation = None;
+                continue;
+            }
         };
         *animation = Some(anim);
     }
@@ -368,24 +438,231 @@ pub fn camera_position_from_level(level_box: Rect, player_pos: Vec2) -> Vec2 {
     camera_position_from_level_with_scale(level_box, player_pos, 1.)
 }
 
+/// The scale of the camera's projection for a level in `level_box` that wants to be shown at
+/// `zoom`. The camera never zooms out further than the level is big, so it doesn't show what is
+/// outside of the level. Levels smaller than the unzoomed camera are left as they are.
+pub fn level_camera_scale(level_box: Rect, zoom: f32) -> f32 {
+    if level_box.is_empty() {
+        return zoom;
+    }
+    let fit = (level_box.width() / CAMERA_WIDTH).min(level_box.height() / CAMERA_HEIGHT);
+    zoom.min(fit.max(1.0))
+}
+
+/// Where the camera at `camera_pos` has to be for `player_pos` to be inside of the deadzone, a box
+/// of `deadzone_size` centered on the camera. The camera stays put while the player is inside of
+/// the deadzone, and otherwise moves just far enough to put the player on its edge.
+pub fn camera_position_with_deadzone(
+    camera_pos: Vec2,
+    player_pos: Vec2,
+    deadzone_size: Vec2,
+) -> Vec2 {
+    let half_size = (deadzone_size * 0.5).max(Vec2::ZERO);
+    let offset = player_pos - camera_pos;
+    camera_pos + offset - offset.clamp(-half_size, half_size)
+}
+
 /// [`System`] that moves camera to player's position and constrains it to the [`CurrentLevel`]'s `world_box`.
+/// The camera only follows the player once they leave the deadzone set in the [`Config`].
 pub fn move_camera(
     current_level: Res<CurrentLevel>,
     q_player: Query<&Transform, With<PlayerMarker>>,
-    q_camera: Query<&Transform, With<MainCamera>>,
+    q_camera: Query<(&Transform, &OrthographicProjection), With<MainCamera>>,
     mut ev_move_camera: EventWriter<CameraMoveEvent>,
+    config: Res<Config>,
 ) {
     let Ok(player_transform) = q_player.get_single() else {
         return;
     };
-    let Ok(camera_transform) = q_camera.get_single() else {
+    let Ok((camera_transform, projection)) = q_camera.get_single() else {
         return;
     };
 
-    let camera_pos =
-        camera_position_from_level(current_level.level_box, player_transform.translation.xy());
+    let deadzone_pos = camera_position_with_deadzone(
+        camera_transform.translation.xy(),
+        player_transform.translation.xy(),
+        Vec2::from(config.camera_config.deadzone),
+    );
+    // clamped after the deadzone, so the camera still stops at the edges of the level. Uses the
+    // current scale, so the camera stays inside of the level while it zooms
+    let camera_pos = camera_position_from_level_with_scale(
+        current_level.level_box,
+        deadzone_pos,
+        projection.scale,
+    );
     ev_move_camera.send(CameraMoveEvent {
         to: camera_transform.translation.xy().lerp(camera_pos, 0.2),
         variant: CameraControlType::Instant,
     });
 }
+
+/// [`System`] that zooms the camera to the zoom of the [`CurrentLevel`] when it changes, see
+/// [`level_camera_scale`]. The zoom is animated along with the camera's move to the new level,
+/// and is instant for the first level.
+pub fn zoom_camera_to_level(
+    current_level: Res<CurrentLevel>,
+    mut ev_zoom_camera: EventWriter<CameraZoomEvent>,
+    config: Res<Config>,
+    mut zoomed_level: Local<Option<LevelIid>>,
+) {
+    if current_level.level_iid.as_str().is_empty() {
+        // entering a level from the level select snaps to it
+        *zoomed_level = None;
+        return;
+    }
+    if zoomed_level.as_ref() == Some(&current_level.level_iid) {
+        return;
+    }
+    let variant = match *zoomed_level {
+        Some(_) => CameraControlType::Animated {
+            duration: Duration::from_secs_f32(CAMERA_ANIMATION_SECS),
+            ease_fn: EaseFunction::SineInOut,
+            callback: None,
+        },
+        None => CameraControlType::Instant,
+    };
+    ev_zoom_camera.send(CameraZoomEvent {
+        scale: current_level.camera_scale(config.camera_config.zoom),
+        variant,
+    });
+    *zoomed_level = Some(current_level.level_iid.clone());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEADZONE: Vec2 = Vec2::new(24.0, 16.0);
+
+    #[test]
+    fn camera_stays_put_inside_deadzone() {
+        let camera_pos = Vec2::new(100.0, 50.0);
+        for player_pos in [
+            camera_pos,
+            camera_pos + Vec2::new(11.0, -7.0),
+            camera_pos + Vec2::new(-12.0, 8.0),
+        ] {
+            assert_eq!(
+                camera_position_with_deadzone(camera_pos, player_pos, DEADZONE),
+                camera_pos
+            );
+        }
+    }
+
+    #[test]
+    fn camera_follows_player_outside_deadzone() {
+        let camera_pos = Vec2::new(100.0, 50.0);
+        // player ends up on the edge of the deadzone
+        assert_eq!(
+            camera_position_with_deadzone(camera_pos, Vec2::new(120.0, 50.0), DEADZONE),
+            Vec2::new(108.0, 50.0)
+        );
+        assert_eq!(
+            camera_position_with_deadzone(camera_pos, Vec2::new(90.0, 20.0), DEADZONE),
+            Vec2::new(100.0, 28.0)
+        );
+        // no deadzone always follows
+        assert_eq!(
+            camera_position_with_deadzone(camera_pos, Vec2::new(101.0, 50.0), Vec2::ZERO),
+            Vec2::new(101.0, 50.0)
+        );
+    }
+
+    #[test]
+    fn deadzone_respects_level_bounds() {
+        let level_box = Rect::new(0.0, 0.0, 640.0, 360.0);
+        let camera_pos = Vec2::new(CAMERA_WIDTH * 0.5, 100.0);
+        let deadzone_pos =
+            camera_position_with_deadzone(camera_pos, Vec2::new(10.0, 100.0), DEADZONE);
+        assert_eq!(
+            camera_position_from_level(level_box, deadzone_pos),
+            camera_pos
+        );
+    }
+
+    #[test]
+    fn zoomed_camera_stays_inside_level() {
+        let level_box = Rect::new(0.0, 0.0, 640.0, 360.0);
+        for zoom in [0.5, 1.0, 1.5, 2.0, 3.0] {
+            let scale = level_camera_scale(level_box, zoom);
+            // too far out to fit in the level
+            assert_eq!(scale, zoom.min(2.0));
+            let half_view = Vec2::new(CAMERA_WIDTH, CAMERA_HEIGHT) * 0.5 * scale;
+            for player_pos in [
+                Vec2::new(-50.0, -50.0),
+                Vec2::new(320.0, 180.0),
+                Vec2::new(600.0, 20.0),
+                Vec2::new(700.0, 400.0),
+            ] {
+                let camera_pos =
+                    camera_position_from_level_with_scale(level_box, player_pos, scale);
+                let view = Rect::from_center_half_size(camera_pos, half_view);
+                assert!(level_box.contains(view.min) && level_box.contains(view.max));
+            }
+        }
+
+        // zoomed in cameras can travel further
+        let player_pos = Vec2::new(20.0, 20.0);
+        assert!(
+            camera_position_from_level_with_scale(level_box, player_pos, 0.5).x
+                < camera_position_from_level(level_box, player_pos).x
+        );
+        // small levels aren't zoomed in to fit
+        let small_box = Rect::new(0.0, 0.0, 320.0, 120.0);
+        assert_eq!(level_camera_scale(small_box, 1.5), 1.0);
+        assert_eq!(level_camera_scale(small_box, 0.75), 0.75);
+    }
+
+    #[test]
+    fn camera_zooms_smoothly_between_levels() {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .init_resource::<CurrentLevel>()
+            .insert_resource(Config::default())
+            .add_event::<CameraZoomEvent>()
+            .add_systems(Update, (zoom_camera_to_level, handle_zoom_camera).chain());
+        let camera = app
+            .world_mut()
+            .spawn((MainCamera, OrthographicProjection::default_2d()))
+            .id();
+        let scale = |app: &App| {
+            app.world()
+                .get::<OrthographicProjection>(camera)
+                .unwrap()
+                .scale
+        };
+        let enter_level = |app: &mut App, level_iid: &str, camera_zoom: Option<f32>| {
+            *app.world_mut().resource_mut::<CurrentLevel>() = CurrentLevel {
+                level_iid: LevelIid::new(level_iid),
+                level_box: Rect::new(0.0, 0.0, 960.0, 540.0),
+                camera_zoom,
+                ..default()
+            };
+        };
+        let advance = |app: &mut App, secs: f32| {
+            app.world_mut()
+                .resource_mut::<Time>()
+                .advance_by(Duration::from_secs_f32(secs));
+            app.update();
+        };
+
+        // the first level is shown right away
+        enter_level(&mut app, "corridor", Some(0.75));
+        advance(&mut app, 0.0);
+        assert_eq!(scale(&app), 0.75);
+
+        // a large open level zooms out
+        enter_level(&mut app, "cavern", Some(1.5));
+        advance(&mut app, 0.0);
+        advance(&mut app, CAMERA_ANIMATION_SECS / 2.0);
+        assert!(scale(&app) > 0.75 && scale(&app) < 1.5);
+        advance(&mut app, CAMERA_ANIMATION_SECS);
+        assert_eq!(scale(&app), 1.5);
+
+        // levels without a zoom use the config's
+        enter_level(&mut app, "room", None);
+        advance(&mut app, 0.0);
+        advance(&mut app, CAMERA_ANIMATION_SECS);
+        assert_eq!(scale(&app), 1.0);
+    }
+}
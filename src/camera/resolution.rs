@@ -0,0 +1,262 @@
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages},
+        texture::ImageSampler,
+    },
+    window::PrimaryWindow,
+};
+
+use crate::config::{Config, DynamicResolutionConfig};
+
+use super::letterbox::Letterbox;
+
+/// How long to wait after changing the [`RenderScale`] before changing it again, in real
+/// seconds. Gives the frame time a chance to settle at the new resolution.
+const RENDER_SCALE_COOLDOWN_SECS: f32 = 1.0;
+
+/// How much each new frame time moves the smoothed frame time used by [`adjust_render_scale`]
+const FRAME_TIME_SMOOTHING: f32 = 0.1;
+
+/// [`Plugin`] that renders the level at a lower resolution when frames take too long, and
+/// upscales it to the window. See the `dynamic_resolution_config` section of `Lightborne.toml`.
+pub struct DynamicResolutionPlugin;
+
+impl Plugin for DynamicResolutionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SceneRenderTarget>()
+            .init_resource::<RenderScale>()
+            .add_systems(
+                Update,
+                (adjust_render_scale, resize_scene_render_target).chain(),
+            );
+    }
+}
+
+/// [`Resource`] holding the image that the [`MainCamera`](super::MainCamera) and
+/// [`BackgroundCamera`](super::BackgroundCamera) render to. Its size is the physical size of the
+/// window, or of the active region of the [`Letterbox`], times the [`RenderScale`], and it is
+/// drawn over that region by the [`UpscaleCamera`](super::UpscaleCamera).
+#[derive(Resource)]
+pub struct SceneRenderTarget(pub Handle<Image>);
+
+impl FromWorld for SceneRenderTarget {
+    fn from_world(world: &mut World) -> Self {
+        let mut images = world.resource_mut::<Assets<Image>>();
+        SceneRenderTarget(images.add(scene_render_target_image(UVec2::ONE)))
+    }
+}
+
+pub fn scene_render_target_image(size: UVec2) -> Image {
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 0],
+        TextureFormat::Bgra8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    image.texture_descriptor.usage =
+        TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST | TextureUsages::RENDER_ATTACHMENT;
+    image.sampler = ImageSampler::linear();
+    image
+}
+
+/// [`Resource`] holding the fraction of the window's resolution that the level is rendered at.
+#[derive(Resource, Debug)]
+pub struct RenderScale {
+    pub scale: f32,
+    smoothed_frame_secs: f32,
+    cooldown: Timer,
+}
+
+impl Default for RenderScale {
+    fn default() -> Self {
+        RenderScale {
+            scale: 1.0,
+            smoothed_frame_secs: 0.0,
+            cooldown: Timer::from_seconds(RENDER_SCALE_COOLDOWN_SECS, TimerMode::Once),
+        }
+    }
+}
+
+/// Finds the render scale to use next, given how long frames are taking. The scale is lowered
+/// when frames are slower than the target, raised when there is plenty of headroom, and always
+/// kept within the configured bounds.
+pub fn next_render_scale(scale: f32, frame_secs: f32, config: &DynamicResolutionConfig) -> f32 {
+    let target_secs = 1.0 / config.target_fps;
+    let scale = if frame_secs > target_secs * 1.1 {
+        scale - config.step
+    } else if frame_secs < target_secs * 0.8 {
+        scale + config.step
+    } else {
+        scale
+    };
+    // not clamp, so a config with min_scale above max_scale doesn't panic
+    scale.max(config.min_scale).min(config.max_scale)
+}
+
+/// The size of the [`SceneRenderTarget`] for a window of `window_size` physical pixels.
+pub fn render_target_size(window_size: UVec2, scale: f32) -> UVec2 {
+    (window_size.as_vec2() * scale)
+        .round()
+        .as_uvec2()
+        .max(UVec2::ONE)
+}
+
+/// [`System`] that updates the [`RenderScale`] from the smoothed frame time, logging every
+/// change. When dynamic resolution is off, the level is rendered at the maximum scale.
+pub fn adjust_render_scale(
+    mut render_scale: ResMut<RenderScale>,
+    time: Res<Time<Real>>,
+    config: Res<Config>,
+) {
+    let config = &config.dynamic_resolution_config;
+    if !config.enabled {
+        if render_scale.scale != config.max_scale {
+            render_scale.scale = config.max_scale;
+        }
+        return;
+    }
+
+    let frame_secs = time.delta_secs();
+    render_scale.smoothed_frame_secs = if render_scale.smoothed_frame_secs == 0.0 {
+        frame_secs
+    } else {
+        render_scale
+            .smoothed_frame_secs
+            .lerp(frame_secs, FRAME_TIME_SMOOTHING)
+    };
+
+    if !render_scale.cooldown.tick(time.delta()).finished() {
+        return;
+    }
+    let scale = next_render_scale(render_scale.scale, render_scale.smoothed_frame_secs, config);
+    if scale != render_scale.scale {
+        info!(
+            "Render scale changed from {:.2} to {:.2} ({:.1}ms frames)",
+            render_scale.scale,
+            scale,
+            render_scale.smoothed_frame_secs * 1000.0
+        );
+        render_scale.scale = scale;
+        render_scale.cooldown.reset();
+    }
+}
+
+/// [`System`] that resizes the [`SceneRenderTarget`] when the window, the [`Letterbox`] or the
+/// [`RenderScale`] changes.
+pub fn resize_scene_render_target(
+    render_scale: Res<RenderScale>,
+    letterbox: Res<Letterbox>,
+    scene_target: Res<SceneRenderTarget>,
+    mut images: ResMut<Assets<Image>>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+) {
+    let Ok(window) = q_window.get_single() else {
+        return;
+    };
+    let size = render_target_size(
+        letterbox.active_size(window.physical_size()),
+        render_scale.scale,
+    );
+
+    // only borrow the image mutably when it actually changes, since that makes the cameras
+    // rendering to it update their targets
+    if images
+        .get(&scene_target.0)
+        .is_none_or(|image| image.size() == size)
+    {
+        return;
+    }
+    let Some(image) = images.get_mut(&scene_target.0) else {
+        return;
+    };
+    image.resize(Extent3d {
+        width: size.x,
+        height: size.y,
+        depth_or_array_layers: 1,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn render_scale_stays_within_bounds() {
+        let config = DynamicResolutionConfig::default();
+        let target_secs = 1.0 / config.target_fps;
+
+        let mut scale = config.max_scale;
+        for _ in 0..20 {
+            scale = next_render_scale(scale, target_secs * 3.0, &config);
+            assert!(scale >= config.min_scale);
+        }
+        assert_eq!(scale, config.min_scale);
+
+        // frames at the target keep the scale where it is
+        assert_eq!(next_render_scale(scale, target_secs, &config), scale);
+
+        for _ in 0..20 {
+            scale = next_render_scale(scale, target_secs * 0.5, &config);
+            assert!(scale <= config.max_scale);
+        }
+        assert_eq!(scale, config.max_scale);
+    }
+
+    #[test]
+    fn dynamic_resolution_is_off_by_default() {
+        let mut app = App::new();
+        app.init_resource::<Config>()
+            .init_resource::<Time<Real>>()
+            .init_resource::<RenderScale>()
+            .add_systems(Update, adjust_render_scale);
+        // frames far slower than the target, long past the cooldown
+        app.world_mut()
+            .resource_mut::<Time<Real>>()
+            .update_with_duration(Duration::from_secs(1));
+        for _ in 0..5 {
+            app.update();
+        }
+        assert_eq!(
+            app.world().resource::<RenderScale>().scale,
+            DynamicResolutionConfig::default().max_scale
+        );
+    }
+
+    #[test]
+    fn scene_render_target_resizes_with_scale() {
+        let config = DynamicResolutionConfig::default();
+        let mut app = App::new();
+        app.init_resource::<Assets<Image>>()
+            .init_resource::<SceneRenderTarget>()
+            .init_resource::<RenderScale>()
+            .init_resource::<Letterbox>()
+            .add_systems(Update, resize_scene_render_target);
+        let window = Window::default();
+        let window_size = window.physical_size();
+        app.world_mut().spawn((window, PrimaryWindow));
+
+        for scale in [config.max_scale, config.min_scale] {
+            app.world_mut().resource_mut::<RenderScale>().scale = scale;
+            app.update();
+
+            let handle = &app.world().resource::<SceneRenderTarget>().0;
+            let size = app
+                .world()
+                .resource::<Assets<Image>>()
+                .get(handle)
+                .unwrap()
+                .size();
+            assert_eq!(size, render_target_size(window_size, scale));
+            assert!(size.x <= window_size.x && size.y <= window_size.y);
+        }
+    }
+}
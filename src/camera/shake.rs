@@ -0,0 +1,147 @@
+use bevy::prelude::*;
+
+use crate::{config::Config, level::CurrentLevel, player::kill::KillPlayerEvent};
+
+use super::{camera_position_from_level_with_scale, handle_move_camera, MainCamera};
+
+/// The trauma added to the [`CameraShake`] when the player dies.
+const DEATH_TRAUMA: f32 = 0.6;
+
+/// [`Plugin`] that shakes the [`MainCamera`] when [`CameraShakeEvent`]s are sent, and when the
+/// player dies.
+pub struct CameraShakePlugin;
+
+impl Plugin for CameraShakePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraShake>()
+            .add_event::<CameraShakeEvent>()
+            .add_systems(PreUpdate, remove_camera_shake_offset)
+            .add_systems(Update, apply_camera_shake.after(handle_move_camera));
+    }
+}
+
+/// [`Resource`] that stores how much the camera is shaking. Trauma ranges from 0 to 1 and decays
+/// over time, and the camera's offset scales with the square of the trauma so that small amounts
+/// of trauma are subtle.
+#[derive(Resource, Default, Debug)]
+pub struct CameraShake {
+    trauma: f32,
+    /// The offset added to the camera this frame, which is removed at the start of the next frame
+    offset: Vec2,
+}
+
+impl CameraShake {
+    /// Adds trauma to the shake, capping it at 1.
+    pub fn add_trauma(&mut self, trauma: f32) {
+        self.trauma = (self.trauma + trauma).min(1.0);
+    }
+
+    /// Decays the trauma, such that a full shake lasts `duration_secs`.
+    pub fn decay(&mut self, delta_secs: f32, duration_secs: f32) {
+        if duration_secs <= 0.0 {
+            self.trauma = 0.0;
+            return;
+        }
+        self.trauma = (self.trauma - delta_secs / duration_secs).max(0.0);
+    }
+
+    pub fn trauma(&self) -> f32 {
+        self.trauma
+    }
+
+    /// How far the camera should be offset, from 0 to 1.
+    pub fn intensity(&self) -> f32 {
+        self.trauma * self.trauma
+    }
+}
+
+/// Send this event to shake the camera. Trauma from multiple events adds up to a maximum of 1.
+#[derive(Event, Debug)]
+pub struct CameraShakeEvent {
+    pub trauma: f32,
+}
+
+/// [`System`] that removes the shake offset added last frame, so that the camera following code
+/// never sees the shaken position.
+pub fn remove_camera_shake_offset(
+    mut shake: ResMut<CameraShake>,
+    mut q_camera: Query<&mut Transform, With<MainCamera>>,
+) {
+    let Ok(mut camera_transform) = q_camera.get_single_mut() else {
+        return;
+    };
+    camera_transform.translation -= shake.offset.extend(0.0);
+    shake.offset = Vec2::ZERO;
+}
+
+/// [`System`] that offsets the camera by a random amount based on the [`CameraShake`]. The shaken
+/// camera is kept inside of the [`CurrentLevel`] so that the shake never shows what is outside
+/// of the level.
+pub fn apply_camera_shake(
+    mut shake: ResMut<CameraShake>,
+    mut ev_camera_shake: EventReader<CameraShakeEvent>,
+    mut ev_kill_player: EventReader<KillPlayerEvent>,
+    mut q_camera: Query<(&mut Transform, &OrthographicProjection), With<MainCamera>>,
+    current_level: Res<CurrentLevel>,
+    config: Res<Config>,
+    time: Res<Time>,
+) {
+    for ev in ev_camera_shake.read() {
+        shake.add_trauma(ev.trauma);
+    }
+    if !ev_kill_player.is_empty() {
+        ev_kill_player.clear();
+        shake.add_trauma(DEATH_TRAUMA);
+    }
+
+    if shake.trauma() == 0.0 {
+        return;
+    }
+    let Ok((mut camera_transform, projection)) = q_camera.get_single_mut() else {
+        return;
+    };
+
+    let max_offset = config.camera_config.shake_intensity * shake.intensity();
+    shake.decay(time.delta_secs(), config.camera_config.shake_duration);
+
+    let clamp_to_level = |pos: Vec2| {
+        camera_position_from_level_with_scale(current_level.level_box, pos, projection.scale)
+    };
+
+    // don't shake if the camera is outside of the level, like when animating between levels
+    let camera_pos = camera_transform.translation.xy();
+    if clamp_to_level(camera_pos).distance_squared(camera_pos) > 0.01 {
+        return;
+    }
+
+    let offset =
+        Vec2::new(rand::random_range(-1.0..1.0), rand::random_range(-1.0..1.0)) * max_offset;
+    shake.offset = clamp_to_level(camera_pos + offset) - camera_pos;
+    camera_transform.translation += shake.offset.extend(0.0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn camera_shake_trauma_is_capped() {
+        let mut shake = CameraShake::default();
+        shake.add_trauma(0.6);
+        shake.add_trauma(0.6);
+        assert_eq!(shake.trauma(), 1.0);
+    }
+
+    #[test]
+    fn camera_shake_decays_to_zero() {
+        let mut shake = CameraShake::default();
+        shake.add_trauma(1.0);
+        shake.decay(0.25, 1.0);
+        assert_eq!(shake.trauma(), 0.75);
+        for _ in 0..10 {
+            shake.decay(0.25, 1.0);
+        }
+        assert_eq!(shake.trauma(), 0.0);
+        assert_eq!(shake.intensity(), 0.0);
+    }
+}
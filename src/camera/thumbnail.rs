@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+
+use bevy::{
+    core_pipeline::tonemapping::Tonemapping,
+    prelude::*,
+    render::camera::{RenderTarget, ScalingMode},
+};
+use bevy_ecs_ldtk::LevelIid;
+
+use crate::{level::CurrentLevel, lighting::AmbientLight2d};
+
+use super::{resolution::scene_render_target_image, MainCamera};
+
+/// Thumbnail pixels per unit of the level, so an 8 unit tile is 2 pixels across.
+const THUMBNAIL_SCALE: f32 = 0.25;
+
+/// The largest a thumbnail can be in either direction. Bigger levels are scaled down to fit.
+const MAX_THUMBNAIL_SIZE: u32 = 256;
+
+/// How many frames a [`ThumbnailCamera`] renders for before it is despawned. Ldtk spawns the
+/// level a few frames after it becomes the [`CurrentLevel`], and the last frame rendered is the
+/// one that stays in the thumbnail.
+const THUMBNAIL_FRAMES: u32 = 8;
+
+/// [`Plugin`] that renders a small thumbnail of each level the first time it is entered, for the
+/// level select and minimaps.
+pub struct LevelThumbnailPlugin;
+
+impl Plugin for LevelThumbnailPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LevelThumbnails>().add_systems(
+            Update,
+            (
+                capture_level_thumbnail.run_if(resource_changed::<CurrentLevel>),
+                despawn_thumbnail_cameras,
+            ),
+        );
+    }
+}
+
+/// [`Resource`] holding the thumbnail of every level that has been entered. A thumbnail is added
+/// as soon as its level is entered, and is filled in over the next [`THUMBNAIL_FRAMES`] frames.
+#[derive(Resource, Default, Debug)]
+pub struct LevelThumbnails(pub HashMap<LevelIid, Handle<Image>>);
+
+impl LevelThumbnails {
+    pub fn get(&self, level_iid: &LevelIid) -> Option<Handle<Image>> {
+        self.0.get(level_iid).cloned()
+    }
+}
+
+/// [`Component`] for the cameras rendering a level into its thumbnail.
+#[derive(Component, Debug)]
+pub struct ThumbnailCamera {
+    frames_left: u32,
+}
+
+/// The size of the thumbnail of a level of `level_size` units, with the same aspect ratio as the
+/// level.
+pub fn thumbnail_size(level_size: Vec2) -> UVec2 {
+    let size = level_size * THUMBNAIL_SCALE;
+    let fit = (MAX_THUMBNAIL_SIZE as f32 / size.max_element()).min(1.0);
+    (size * fit).round().as_uvec2().max(UVec2::ONE)
+}
+
+/// [`System`] that spawns a [`ThumbnailCamera`] looking at the whole [`CurrentLevel`] when it is
+/// entered for the first time. The camera is lit with the [`MainCamera`]'s [`AmbientLight2d`], so
+/// lights and the shadows of occluders show up in the thumbnail the same way they do in game.
+pub fn capture_level_thumbnail(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mut thumbnails: ResMut<LevelThumbnails>,
+    current_level: Res<CurrentLevel>,
+    q_main_camera: Query<&AmbientLight2d, With<MainCamera>>,
+) {
+    let level_iid = &current_level.level_iid;
+    if level_iid.as_str().is_empty() || thumbnails.0.contains_key(level_iid) {
+        return;
+    }
+    let level_box = current_level.level_box;
+    let image = images.add(scene_render_target_image(thumbnail_size(level_box.size())));
+    thumbnails.0.insert(level_iid.clone(), image.clone());
+
+    let mut camera = commands.spawn((
+        Camera2d,
+        ThumbnailCamera {
+            frames_left: THUMBNAIL_FRAMES,
+        },
+        Camera {
+            // lighting renders to HDR textures, like the main camera
+            hdr: true,
+            // before every other camera, so nothing else is affected by it
+            order: -1,
+            clear_color: ClearColorConfig::Custom(Color::BLACK),
+            target: RenderTarget::Image(image),
+            ..default()
+        },
+        OrthographicProjection {
+            scaling_mode: ScalingMode::Fixed {
+                width: level_box.width(),
+                height: level_box.height(),
+            },
+            ..OrthographicProjection::default_2d()
+        },
+        Transform::from_translation(level_box.center().extend(0.0)),
+        Tonemapping::TonyMcMapface,
+    ));
+    if let Ok(ambient) = q_main_camera.get_single() {
+        camera.insert(*ambient);
+    }
+}
+
+/// [`System`] that despawns [`ThumbnailCamera`]s once they are done rendering.
+pub fn despawn_thumbnail_cameras(
+    mut commands: Commands,
+    mut q_cameras: Query<(Entity, &mut ThumbnailCamera)>,
+) {
+    for (entity, mut camera) in q_cameras.iter_mut() {
+        if camera.frames_left == 0 {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        camera.frames_left -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thumbnail_matches_level_aspect() {
+        assert_eq!(thumbnail_size(Vec2::new(320.0, 180.0)), UVec2::new(80, 45));
+        assert_eq!(thumbnail_size(Vec2::new(640.0, 320.0)), UVec2::new(160, 80));
+    }
+
+    #[test]
+    fn large_levels_are_scaled_to_fit() {
+        assert_eq!(
+            thumbnail_size(Vec2::new(4096.0, 1024.0)),
+            UVec2::new(MAX_THUMBNAIL_SIZE, 64)
+        );
+        assert_eq!(
+            thumbnail_size(Vec2::new(320.0, 8192.0)),
+            UVec2::new(10, MAX_THUMBNAIL_SIZE)
+        );
+    }
+
+    #[test]
+    fn entering_a_level_captures_its_thumbnail() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .init_asset::<Image>()
+            .init_resource::<CurrentLevel>()
+            .add_plugins(LevelThumbnailPlugin);
+        let ambient = AmbientLight2d {
+            color: Vec4::new(1.0, 1.0, 1.0, 0.4),
+        };
+        app.world_mut().spawn((MainCamera, ambient));
+        app.update();
+        // no level has been entered yet
+        assert!(app.world().resource::<LevelThumbnails>().0.is_empty());
+
+        let level_iid = LevelIid::new("level");
+        *app.world_mut().resource_mut::<CurrentLevel>() = CurrentLevel {
+            level_iid: level_iid.clone(),
+            level_box: Rect::new(0.0, 0.0, 640.0, 360.0),
+            ..default()
+        };
+        app.update();
+
+        let thumbnail = app
+            .world()
+            .resource::<LevelThumbnails>()
+            .get(&level_iid)
+            .unwrap();
+        let image = app
+            .world()
+            .resource::<Assets<Image>>()
+            .get(&thumbnail)
+            .unwrap();
+        assert_eq!(image.size(), UVec2::new(160, 90));
+        // the thumbnail is lit, so occluders cast their shadows in it
+        let mut q_lit = app
+            .world_mut()
+            .query_filtered::<&AmbientLight2d, With<ThumbnailCamera>>();
+        assert_eq!(q_lit.single(app.world()).color, ambient.color);
+
+        for _ in 0..=THUMBNAIL_FRAMES {
+            app.update();
+        }
+        let mut q_cameras = app.world_mut().query::<&ThumbnailCamera>();
+        assert_eq!(q_cameras.iter(app.world()).count(), 0);
+    }
+}
@@ -0,0 +1,159 @@
+use bevy::{
+    prelude::*,
+    render::render_resource::{AsBindGroup, ShaderRef, ShaderType},
+    sprite::{AlphaMode2d, Material2d, Material2dPlugin},
+};
+
+use super::{CAMERA_HEIGHT, CAMERA_WIDTH, UPSCALE_CAMERA_LAYER};
+
+/// The path to the shader used by the [`VignetteMaterial`]
+const VIGNETTE_SHADER_PATH: &str = "shaders/vignette.wgsl";
+
+/// How far from the center of the screen the vignette starts, where 1 is a corner. Must match
+/// `VIGNETTE_START` in the shader.
+const VIGNETTE_START: f32 = 0.4;
+
+/// [`Plugin`] that darkens the edges of the screen based on the [`Vignette`].
+pub struct VignettePlugin;
+
+impl Plugin for VignettePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(Material2dPlugin::<VignetteMaterial>::default())
+            .init_resource::<Vignette>()
+            .add_systems(Startup, spawn_vignette)
+            .add_systems(PostUpdate, sync_vignette);
+    }
+}
+
+/// [`Resource`] that controls how much the edges of the screen are darkened towards `color`. The
+/// vignette is drawn by the [`UpscaleCamera`](super::UpscaleCamera) on top of the scene, which
+/// the main camera already tonemapped, so it blends in display space. A `strength` of 0 hides it
+/// entirely. See the `camera_config.vignette_strength` setting of `Lightborne.toml`.
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct Vignette {
+    pub strength: f32,
+    pub color: Vec4,
+}
+
+impl Default for Vignette {
+    fn default() -> Self {
+        Vignette {
+            strength: 0.0,
+            color: Vec4::new(0.0, 0.0, 0.0, 1.0),
+        }
+    }
+}
+
+/// How much of the vignette's color covers the point at `uv` on the screen, from 0 to 1. Mirrors
+/// the vignette shader.
+pub fn vignette_amount(uv: Vec2, strength: f32) -> f32 {
+    // 0 at the center of the screen and 1 in the corners
+    let distance = ((uv - 0.5) * 2.0).length() / std::f32::consts::SQRT_2;
+    let t = ((distance - VIGNETTE_START) / (1.0 - VIGNETTE_START)).clamp(0.0, 1.0);
+    let falloff = t * t * (3.0 - 2.0 * t);
+    (falloff * strength).clamp(0.0, 1.0)
+}
+
+#[derive(ShaderType, Debug, Clone)]
+pub struct VignetteUniform {
+    pub color: Vec4,
+    pub strength: f32,
+}
+
+/// Custom [`Material2d`] for the quad covering the screen that draws the [`Vignette`].
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+pub struct VignetteMaterial {
+    #[uniform(0)]
+    pub settings: VignetteUniform,
+}
+
+impl Material2d for VignetteMaterial {
+    fn fragment_shader() -> ShaderRef {
+        VIGNETTE_SHADER_PATH.into()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode2d {
+        AlphaMode2d::Blend
+    }
+}
+
+/// Marker [`Component`] for the quad that draws the [`Vignette`].
+#[derive(Component)]
+pub struct VignetteMarker;
+
+/// [`Startup`] [`System`] that spawns the quad that draws the [`Vignette`], in front of the
+/// upscaled scene.
+pub fn spawn_vignette(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<VignetteMaterial>>,
+    vignette: Res<Vignette>,
+) {
+    commands.spawn((
+        Mesh2d(meshes.add(Rectangle::new(CAMERA_WIDTH, CAMERA_HEIGHT))),
+        MeshMaterial2d(materials.add(VignetteMaterial {
+            settings: VignetteUniform {
+                color: vignette.color,
+                strength: vignette.strength,
+            },
+        })),
+        Transform::from_xyz(0.0, 0.0, 1.0),
+        Visibility::Hidden,
+        VignetteMarker,
+        UPSCALE_CAMERA_LAYER,
+    ));
+}
+
+/// [`System`] that updates the [`VignetteMaterial`] when the [`Vignette`] changes, and hides the
+/// vignette while its strength is 0 so it costs nothing.
+pub fn sync_vignette(
+    mut q_vignette: Query<
+        (&MeshMaterial2d<VignetteMaterial>, &mut Visibility),
+        With<VignetteMarker>,
+    >,
+    mut materials: ResMut<Assets<VignetteMaterial>>,
+    vignette: Res<Vignette>,
+) {
+    for (material, mut visibility) in q_vignette.iter_mut() {
+        let shown = vignette.strength > 0.0;
+        visibility.set_if_neq(if shown {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        });
+        if !vignette.is_changed() {
+            continue;
+        }
+        let Some(material) = materials.get_mut(&material.0) else {
+            continue;
+        };
+        material.settings = VignetteUniform {
+            color: vignette.color,
+            strength: vignette.strength,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn center_is_never_darkened() {
+        assert_eq!(vignette_amount(Vec2::splat(0.5), 1.0), 0.0);
+        assert_eq!(vignette_amount(Vec2::ZERO, 0.0), 0.0);
+    }
+
+    #[test]
+    fn corner_darkening_scales_with_strength() {
+        let weak = vignette_amount(Vec2::ZERO, 0.3);
+        let strong = vignette_amount(Vec2::ZERO, 0.6);
+        assert!((weak - 0.3).abs() < 1e-5);
+        assert!((strong - 0.6).abs() < 1e-5);
+        assert_eq!(vignette_amount(Vec2::ONE, 2.0), 1.0);
+
+        // edges are darkened less than corners
+        let edge = vignette_amount(Vec2::new(0.0, 0.5), 0.6);
+        assert!(edge > 0.0 && edge < strong);
+    }
+}
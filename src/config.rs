@@ -1,15 +1,55 @@
+use std::{collections::HashMap, path::Path};
+
 use bevy::prelude::*;
 use serde::Deserialize;
 
+use crate::{
+    camera::{letterbox::Letterbox, vignette::Vignette},
+    hud::HudPosition,
+    input::menu::MenuRepeatConfig,
+    level::streaming::StreamingConfig,
+    light::{fog::VolumetricFog, BeamBounces},
+    lighting::{
+        ComputeShadows, GlowSprites, LightBufferScale, LightingDither, LineLight2dBlendMode,
+        LitSprites, MaxShadowLength, SoftShadows, TimeOfDay,
+    },
+    player::{
+        kill::{KillCause, RespawnTarget, SpawnProtectionMode},
+        ledge::LedgeGrabConfig,
+        movement::{Gravity, VariableJump},
+    },
+    save::{slot_name_or_default, SaveSlot, DEFAULT_SLOT},
+};
+
 pub struct ConfigPlugin;
 
 impl Plugin for ConfigPlugin {
     fn build(&self, app: &mut App) {
-        let config: Config = match std::fs::read_to_string("Lightborne.toml") {
-            Ok(contents) => toml::from_str(&contents).expect("Failed to parse Lightborne.toml"),
-            Err(_) => Config::default(),
-        };
-        app.insert_resource(config);
+        let config = Config::load();
+        app.insert_resource(LitSprites(config.lighting_config.lit_sprites))
+            .insert_resource(LightingDither(config.lighting_config.dither))
+            .insert_resource(config.lighting_config.blend_mode)
+            .insert_resource(config.lighting_config.soft_shadows)
+            .insert_resource(config.lighting_config.compute_shadows)
+            .insert_resource(LightBufferScale(config.lighting_config.light_buffer_scale))
+            .insert_resource(config.lighting_config.beam_bounces)
+            .insert_resource(config.lighting_config.time_of_day)
+            .insert_resource(GlowSprites(config.lighting_config.glow_sprites))
+            .insert_resource(MaxShadowLength(config.lighting_config.max_shadow_length))
+            .insert_resource(VolumetricFog {
+                density: config.lighting_config.fog_density,
+            })
+            .insert_resource(Vignette {
+                strength: config.camera_config.vignette_strength,
+                color: Vec4::from(config.camera_config.vignette_color),
+            })
+            .insert_resource(config.gravity_config)
+            .insert_resource(config.jump_config)
+            .insert_resource(config.ledge_grab_config)
+            .insert_resource(config.streaming_config)
+            .insert_resource(config.menu_repeat_config)
+            .insert_resource(config.window_config.letterbox())
+            .insert_resource(config);
     }
 }
 
@@ -17,6 +57,41 @@ impl Plugin for ConfigPlugin {
 pub struct Config {
     pub level_config: LevelConfig,
     pub debug_config: DebugConfig,
+    #[serde(default)]
+    pub demo_config: DemoConfig,
+    #[serde(default)]
+    pub save_config: SaveConfig,
+    #[serde(default)]
+    pub camera_config: CameraConfig,
+    #[serde(default)]
+    pub lighting_config: LightingConfig,
+    #[serde(default)]
+    pub window_config: WindowConfig,
+    #[serde(default)]
+    pub death_config: DeathConfig,
+    #[serde(default)]
+    pub light_sail_config: LightSailConfig,
+    #[serde(default)]
+    pub cross_point_config: CrossPointConfig,
+    #[serde(default)]
+    pub prism_config: PrismConfig,
+    #[serde(default)]
+    pub hud_config: HudConfig,
+    #[serde(default)]
+    pub dynamic_resolution_config: DynamicResolutionConfig,
+    #[serde(default)]
+    pub gravity_config: Gravity,
+    #[serde(default)]
+    pub jump_config: VariableJump,
+    #[serde(default)]
+    pub ledge_grab_config: LedgeGrabConfig,
+    #[serde(default)]
+    pub streaming_config: StreamingConfig,
+    #[serde(default)]
+    pub menu_repeat_config: MenuRepeatConfig,
+    /// Named light colors, see [`LightPalette`](crate::level::palette::LightPalette)
+    #[serde(default = "default_light_palette")]
+    pub light_palette: HashMap<String, PaletteColor>,
 }
 
 impl Default for Config {
@@ -24,8 +99,69 @@ impl Default for Config {
         Config {
             level_config: LevelConfig {
                 level_path: "levels/lightborne.ldtk".into(),
+                level_index: default_level_index(),
+                run_seed: 0,
+                win_pose_secs: default_win_pose_secs(),
             },
             debug_config: DebugConfig::default(),
+            demo_config: DemoConfig::default(),
+            save_config: SaveConfig::default(),
+            camera_config: CameraConfig::default(),
+            lighting_config: LightingConfig::default(),
+            window_config: WindowConfig::default(),
+            death_config: DeathConfig::default(),
+            light_sail_config: LightSailConfig::default(),
+            cross_point_config: CrossPointConfig::default(),
+            prism_config: PrismConfig::default(),
+            hud_config: HudConfig::default(),
+            dynamic_resolution_config: DynamicResolutionConfig::default(),
+            gravity_config: Gravity::default(),
+            jump_config: VariableJump::default(),
+            ledge_grab_config: LedgeGrabConfig::default(),
+            streaming_config: StreamingConfig::default(),
+            menu_repeat_config: MenuRepeatConfig::default(),
+            light_palette: default_light_palette(),
+        }
+    }
+}
+
+impl Config {
+    /// Reads `Lightborne.toml` with the settings of the save slot picked in its `save_config`
+    /// on top, or uses the default config if it doesn't exist.
+    pub fn load() -> Config {
+        let Ok(contents) = std::fs::read_to_string("Lightborne.toml") else {
+            return Config::default();
+        };
+        let mut settings: toml::Table =
+            toml::from_str(&contents).expect("Failed to parse Lightborne.toml");
+        let save_config: SaveConfig = settings
+            .get("save_config")
+            .cloned()
+            .map(|save_config| save_config.try_into().expect("Failed to parse save_config"))
+            .unwrap_or_default();
+        // an invalid slot name could read settings from outside of the save directory
+        let slot = slot_name_or_default(&save_config.slot);
+        let overrides = SaveSlot::read_settings(Path::new(&save_config.dir), slot);
+        merge_settings(&mut settings, overrides);
+        let mut config: Config = toml::Value::Table(settings)
+            .try_into()
+            .expect("Failed to parse Lightborne.toml with the settings of the save slot");
+        config.death_config.validate();
+        config
+    }
+}
+
+/// Replaces the values in `settings` with the ones in `overrides`, merging tables so overrides
+/// only need to list the values they change.
+pub fn merge_settings(settings: &mut toml::Table, overrides: toml::Table) {
+    for (key, value) in overrides {
+        match (settings.get_mut(&key), value) {
+            (Some(toml::Value::Table(table)), toml::Value::Table(overrides)) => {
+                merge_settings(table, overrides);
+            }
+            (_, value) => {
+                settings.insert(key, value);
+            }
         }
     }
 }
@@ -33,9 +169,509 @@ impl Default for Config {
 #[derive(Deserialize, Default)]
 pub struct DebugConfig {
     pub ui: bool,
+    /// Label light beams with their color and intensity, and highlight the sensors they hit. Needs
+    /// the `dev` feature
+    #[serde(default)]
+    pub beams: bool,
+    /// Middle click lights to edit them in a window, needs `ui` and the `dev` feature
+    #[serde(default)]
+    pub lights: bool,
+    /// F6 turns on the level editor, where emitters and mirrors can be dragged around. Needs the
+    /// `dev` feature
+    #[serde(default)]
+    pub editor: bool,
 }
 
 #[derive(Deserialize)]
 pub struct LevelConfig {
     pub level_path: String,
+    /// Index of the level loaded on startup. Falls back to the first level if out of range.
+    #[serde(default = "default_level_index")]
+    pub level_index: usize,
+    /// Seed for the randomness of every level, see [`LevelRng`](crate::level::rng::LevelRng).
+    /// Runs with the same seed play out the same way.
+    #[serde(default)]
+    pub run_seed: u64,
+    /// How long the player poses after completing a level before the camera moves on, see
+    /// [`WinPose`](crate::player::win::WinPose). 0 moves on right away.
+    #[serde(default = "default_win_pose_secs")]
+    pub win_pose_secs: f32,
+}
+
+fn default_level_index() -> usize {
+    8
+}
+
+fn default_win_pose_secs() -> f32 {
+    0.75
+}
+
+/// Paths used by the [`DemoPlugin`](crate::demo::DemoPlugin). If both are set, playback takes
+/// priority.
+#[derive(Deserialize, Default)]
+pub struct DemoConfig {
+    /// Record inputs and write them to this path when the game closes
+    pub record: Option<String>,
+    /// Play back the inputs stored at this path instead of reading the player's inputs
+    pub playback: Option<String>,
+}
+
+/// Which save slot is played, see [`SaveSlotPlugin`](crate::save::SaveSlotPlugin).
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct SaveConfig {
+    /// The name of the slot, made if it doesn't exist yet
+    pub slot: String,
+    /// The directory every slot is saved in
+    pub dir: String,
+}
+
+impl Default for SaveConfig {
+    fn default() -> Self {
+        SaveConfig {
+            slot: DEFAULT_SLOT.into(),
+            dir: "saves".into(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct CameraConfig {
+    /// The maximum distance the camera moves when shaking, in pixels
+    pub shake_intensity: f32,
+    /// How long it takes for the strongest camera shake to stop, in seconds
+    pub shake_duration: f32,
+    /// Width and height of the box in the middle of the screen the player can move around in
+    /// without moving the camera, in pixels
+    pub deadzone: [f32; 2],
+    /// How much the edges of the screen are darkened, see [`Vignette`]
+    pub vignette_strength: f32,
+    /// Linear RGBA color the edges of the screen are darkened towards
+    pub vignette_color: [f32; 4],
+    /// How much of the world the camera shows, where 2 shows twice as much in each direction.
+    /// Levels can override it with their `CameraZoom` field in Ldtk, see
+    /// [`level_camera_scale`](crate::camera::level_camera_scale).
+    pub zoom: f32,
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        CameraConfig {
+            shake_intensity: 4.0,
+            shake_duration: 0.5,
+            deadzone: [24.0, 16.0],
+            vignette_strength: 0.0,
+            vignette_color: [0.0, 0.0, 0.0, 1.0],
+            zoom: 1.0,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct DeathConfig {
+    /// How fast time passes right after the player dies, relative to normal speed
+    pub slow_motion_speed: f32,
+    /// How long the slow motion lasts before the screen fades out, in real seconds. Set to 0 to
+    /// turn it off.
+    pub slow_motion_secs: f32,
+    /// How many times the player can die before the level restarts from scratch. Unset means
+    /// infinite lives.
+    pub lives: Option<u32>,
+    /// Whether entering a new level gives the player all their lives back
+    pub refill_lives_on_level_switch: bool,
+    /// Where the player respawns after each [`KillCause`]
+    pub respawn_targets: HashMap<KillCause, RespawnTarget>,
+    /// Where the player respawns after a [`KillCause`] that isn't in `respawn_targets`
+    pub default_respawn_target: RespawnTarget,
+    /// How long the player takes to glide back to the checkpoint after dying, in seconds,
+    /// instead of the screen fading out while they are moved. Set to 0 to turn it off.
+    pub respawn_glide_secs: f32,
+    /// How long the screen stays black after the death fade before the player respawns, in
+    /// seconds. Set to 0 to respawn right away. See
+    /// [`RespawnDelay`](crate::player::kill::RespawnDelay).
+    pub respawn_delay_secs: f32,
+    /// Whether pressing any key during the `respawn_delay_secs` respawns the player right away
+    pub respawn_delay_skippable: bool,
+    /// How close to the checkpoint hazards can't kill the player, 0 to turn it off. See
+    /// [`SpawnProtection`](crate::player::kill::SpawnProtection).
+    pub spawn_protection_radius: f32,
+    pub spawn_protection: SpawnProtectionMode,
+    /// How long the retry key has to be held to restart the level, in seconds. Set to 0 to turn
+    /// it off. See [`RetryHold`](crate::level::restart::RetryHold).
+    pub retry_hold_secs: f32,
+}
+
+impl DeathConfig {
+    /// Turns off the slow motion if its speed is negative or not a number, which [`Time<Virtual>`]
+    /// can't run at.
+    pub fn validate(&mut self) {
+        if !(self.slow_motion_speed.is_finite() && self.slow_motion_speed >= 0.0) {
+            warn!(
+                "Invalid death_config.slow_motion_speed {}, turning slow motion off",
+                self.slow_motion_speed
+            );
+            self.slow_motion_speed = 1.0;
+        }
+    }
+
+    pub fn respawn_target(&self, cause: KillCause) -> RespawnTarget {
+        self.respawn_targets
+            .get(&cause)
+            .copied()
+            .unwrap_or(self.default_respawn_target)
+    }
+}
+
+impl Default for DeathConfig {
+    fn default() -> Self {
+        DeathConfig {
+            slow_motion_speed: 0.3,
+            slow_motion_secs: 0.25,
+            lives: None,
+            refill_lives_on_level_switch: true,
+            respawn_targets: HashMap::from([
+                (KillCause::Hazard, RespawnTarget::Checkpoint),
+                (KillCause::OutOfBounds, RespawnTarget::LevelStart),
+            ]),
+            default_respawn_target: RespawnTarget::Checkpoint,
+            respawn_glide_secs: 0.0,
+            respawn_delay_secs: 0.0,
+            respawn_delay_skippable: false,
+            spawn_protection_radius: 0.0,
+            spawn_protection: SpawnProtectionMode::AfterRespawn,
+            retry_hold_secs: 1.0,
+        }
+    }
+}
+
+/// Tuning for [`LightSail`](crate::level::light_sail::LightSail)s. Speeds are in units per
+/// [`FixedUpdate`].
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct LightSailConfig {
+    /// How much speed a beam with an intensity of 1 adds every [`FixedUpdate`]
+    pub acceleration: f32,
+    /// The fraction of speed lost every [`FixedUpdate`] while no beam hits the sail
+    pub damping: f32,
+    pub max_speed: f32,
+}
+
+impl Default for LightSailConfig {
+    fn default() -> Self {
+        LightSailConfig {
+            acceleration: 0.05,
+            damping: 0.05,
+            max_speed: 1.5,
+        }
+    }
+}
+
+/// Settings for [`CrossPoint`](crate::level::cross_point::CrossPoint)s.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct CrossPointConfig {
+    /// Whether a single white beam can stand in for all of a cross point's required colors
+    pub white_counts_as_any_color: bool,
+}
+
+/// Settings for [`Prism`](crate::level::prism::Prism)s. The fractions are the share of the white
+/// beam's intensity each color gets, and are scaled down if they add up to more than 1.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct PrismConfig {
+    pub purple: f32,
+    pub green: f32,
+    pub blue: f32,
+    /// The angle in degrees between the green beam and the purple and blue beams on either side
+    pub spread: f32,
+}
+
+impl Default for PrismConfig {
+    fn default() -> Self {
+        PrismConfig {
+            purple: 1.0 / 3.0,
+            green: 1.0 / 3.0,
+            blue: 1.0 / 3.0,
+            spread: 15.0,
+        }
+    }
+}
+
+/// Settings for the HUD showing the time and deaths in the current level, see
+/// [`HudPlugin`](crate::hud::HudPlugin).
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct HudConfig {
+    pub enabled: bool,
+    pub position: HudPosition,
+}
+
+impl Default for HudConfig {
+    fn default() -> Self {
+        HudConfig {
+            enabled: true,
+            position: HudPosition::TopLeft,
+        }
+    }
+}
+
+/// Settings for rendering the level at a lower resolution when frames take too long, see
+/// [`RenderScale`](crate::camera::resolution::RenderScale).
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct DynamicResolutionConfig {
+    pub enabled: bool,
+    /// The framerate to try to keep
+    pub target_fps: f32,
+    /// The lowest fraction of the window's resolution the level can be rendered at
+    pub min_scale: f32,
+    /// The highest fraction of the window's resolution the level can be rendered at
+    pub max_scale: f32,
+    /// How much the scale changes at a time
+    pub step: f32,
+}
+
+impl Default for DynamicResolutionConfig {
+    fn default() -> Self {
+        DynamicResolutionConfig {
+            // changing resolution mid-level is noticeable, so players opt in
+            enabled: false,
+            target_fps: 60.0,
+            min_scale: 0.5,
+            max_scale: 1.0,
+            step: 0.1,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct LightingConfig {
+    /// Whether sprites with a [`NormalMap2d`](crate::lighting::NormalMap2d) are lit based on the
+    /// direction of the light
+    pub lit_sprites: bool,
+    /// Whether to dither the lit image to hide banding in dark gradients, see
+    /// [`LightingDither`]
+    pub dither: bool,
+    pub blend_mode: LineLight2dBlendMode,
+    pub soft_shadows: SoftShadows,
+    pub compute_shadows: ComputeShadows,
+    /// Lights are drawn at `1 / light_buffer_scale` of the screen resolution, see
+    /// [`LightBufferScale`]
+    pub light_buffer_scale: u32,
+    /// Starting density of the [`VolumetricFog`] light beams travel through, 0 for clear air
+    pub fog_density: f32,
+    /// [`BeamImpactTick`](crate::light::events::BeamImpactTick)s sent per second while a beam
+    /// ends on a surface
+    pub beam_impact_rate: f32,
+    pub beam_bounces: BeamBounces,
+    pub time_of_day: TimeOfDay,
+    /// Draw lights as plain glowing sprites without shadows, for GPUs too slow for the full
+    /// lighting, see [`GlowSprites`]
+    pub glow_sprites: bool,
+    /// How far shadows reach past their occluder before they have faded out, see
+    /// [`MaxShadowLength`]
+    pub max_shadow_length: f32,
+}
+
+impl Default for LightingConfig {
+    fn default() -> Self {
+        LightingConfig {
+            lit_sprites: true,
+            dither: true,
+            blend_mode: LineLight2dBlendMode::Add,
+            soft_shadows: SoftShadows::default(),
+            compute_shadows: ComputeShadows::default(),
+            light_buffer_scale: 1,
+            fog_density: 0.0,
+            beam_impact_rate: 20.0,
+            beam_bounces: BeamBounces::default(),
+            time_of_day: TimeOfDay::default(),
+            glow_sprites: false,
+            max_shadow_length: f32::INFINITY,
+        }
+    }
+}
+
+/// Settings used to create the window, see [`primary_window`](crate::window::primary_window).
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    /// The width and height of the window, in logical pixels
+    pub resolution: [f32; 2],
+    pub mode: WindowModeSetting,
+    pub vsync: bool,
+    /// Open the window at the size it had when the game was last closed
+    pub remember_size: bool,
+    /// The width and height of the aspect ratio the game is locked to, see
+    /// [`Letterbox`](crate::camera::letterbox::Letterbox). Stretches to the window if not set.
+    pub aspect_ratio: Option<[f32; 2]>,
+}
+
+impl WindowConfig {
+    pub const DEFAULT_RESOLUTION: Vec2 = Vec2::new(1280.0, 720.0);
+    /// Resolutions smaller than this are too small to play in
+    pub const MIN_RESOLUTION: Vec2 = Vec2::new(320.0, 180.0);
+
+    /// The size the window should open at. Uses `saved_size` if given, then the configured
+    /// resolution, and falls back to [`Self::DEFAULT_RESOLUTION`] if neither is valid.
+    pub fn resolution(&self, saved_size: Option<Vec2>) -> Vec2 {
+        let is_valid = |size: &Vec2| size.is_finite() && size.cmpge(Self::MIN_RESOLUTION).all();
+        saved_size
+            .filter(is_valid)
+            .or(Some(Vec2::from(self.resolution)).filter(is_valid))
+            .unwrap_or_else(|| {
+                warn!(
+                    "Invalid window resolution {:?}, using {}",
+                    self.resolution,
+                    Self::DEFAULT_RESOLUTION
+                );
+                Self::DEFAULT_RESOLUTION
+            })
+    }
+
+    /// The [`Letterbox`](crate::camera::letterbox::Letterbox) for the configured aspect ratio.
+    pub fn letterbox(&self) -> Letterbox {
+        Letterbox {
+            aspect_ratio: self
+                .aspect_ratio
+                .map(|[width, height]| width / height)
+                .filter(|aspect| aspect.is_finite() && *aspect > 0.0),
+        }
+    }
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        WindowConfig {
+            resolution: Self::DEFAULT_RESOLUTION.into(),
+            mode: WindowModeSetting::default(),
+            vsync: false,
+            remember_size: false,
+            aspect_ratio: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum WindowModeSetting {
+    #[default]
+    Windowed,
+    Borderless,
+    Fullscreen,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub struct PaletteColor {
+    /// Linear RGB color of the light
+    pub color: [f32; 3],
+    #[serde(default = "default_palette_intensity")]
+    pub intensity: f32,
+}
+
+fn default_palette_intensity() -> f32 {
+    1.0
+}
+
+fn default_light_palette() -> HashMap<String, PaletteColor> {
+    HashMap::from([
+        (
+            "warm_torch".into(),
+            PaletteColor {
+                color: [1.0, 0.6, 0.3],
+                intensity: 1.0,
+            },
+        ),
+        (
+            "cold_moon".into(),
+            PaletteColor {
+                color: [0.5, 0.6, 1.0],
+                intensity: 0.6,
+            },
+        ),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_resolution_falls_back_to_default() {
+        let mut window_config = WindowConfig {
+            resolution: [1600.0, 900.0],
+            ..default()
+        };
+        assert_eq!(window_config.resolution(None), Vec2::new(1600.0, 900.0));
+        assert_eq!(
+            window_config.resolution(Some(Vec2::new(800.0, 600.0))),
+            Vec2::new(800.0, 600.0)
+        );
+        // a bad saved size falls back to the configured resolution
+        assert_eq!(
+            window_config.resolution(Some(Vec2::new(0.0, 600.0))),
+            Vec2::new(1600.0, 900.0)
+        );
+
+        window_config.resolution = [-1.0, 900.0];
+        assert_eq!(
+            window_config.resolution(None),
+            WindowConfig::DEFAULT_RESOLUTION
+        );
+        window_config.resolution = [f32::NAN, 900.0];
+        assert_eq!(
+            window_config.resolution(None),
+            WindowConfig::DEFAULT_RESOLUTION
+        );
+    }
+
+    #[test]
+    fn invalid_slow_motion_speed_turns_slow_motion_off() {
+        let mut death_config = DeathConfig {
+            slow_motion_speed: 0.0,
+            ..default()
+        };
+        death_config.validate();
+        assert_eq!(death_config.slow_motion_speed, 0.0);
+
+        for speed in [-0.5, f32::NAN, f32::INFINITY] {
+            death_config.slow_motion_speed = speed;
+            death_config.validate();
+            assert_eq!(death_config.slow_motion_speed, 1.0);
+        }
+    }
+
+    #[test]
+    fn slot_settings_override_config() {
+        let mut settings: toml::Table = toml::from_str(
+            r#"
+            [save_config]
+            slot = "bob"
+            [hud_config]
+            enabled = true
+            position = "top_right"
+            "#,
+        )
+        .unwrap();
+        let overrides = toml::from_str("[hud_config]\nenabled = false\n").unwrap();
+        merge_settings(&mut settings, overrides);
+
+        let hud_config: HudConfig = settings["hud_config"].clone().try_into().unwrap();
+        assert!(!hud_config.enabled);
+        // values the slot doesn't change are kept
+        assert_eq!(hud_config.position, HudPosition::TopRight);
+        let save_config: SaveConfig = settings["save_config"].clone().try_into().unwrap();
+        assert_eq!(save_config.slot, "bob");
+        assert_eq!(save_config.dir, "saves");
+    }
+
+    #[test]
+    fn window_mode_parses_from_lowercase() {
+        let window_config: WindowConfig = toml::from_str("mode = \"borderless\"").unwrap();
+        assert_eq!(window_config.mode, WindowModeSetting::Borderless);
+        assert_eq!(window_config.resolution, [1280.0, 720.0]);
+    }
 }
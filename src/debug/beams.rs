@@ -0,0 +1,227 @@
+use bevy::{prelude::*, utils::HashMap};
+use bevy_ecs_ldtk::EntityIid;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    config::Config,
+    level::sensor::LightSensor,
+    light::{
+        fog::VolumetricFog,
+        segments::{play_light_beam, BeamFreeze, BeamMedia, LightSegment, PrevLightBeamPlayback},
+        BeamBounces, LightBeamSource,
+    },
+    lighting::LineLight2d,
+};
+
+/// The z coordinate of the labels spawned by [`draw_beam_overlay`], in front of everything else.
+const BEAM_LABEL_Z: f32 = 200.0;
+
+/// How far the labels are moved away from the center line of their segment.
+const BEAM_LABEL_OFFSET: f32 = 6.0;
+
+/// Key that freezes and unfreezes every light beam, see [`step_frozen_beams`].
+const FREEZE_BEAMS_KEY: KeyCode = KeyCode::F7;
+
+/// Key that advances frozen light beams by one segment, see [`step_frozen_beams`].
+const STEP_BEAMS_KEY: KeyCode = KeyCode::F8;
+
+/// [`Component`] for the text labels spawned by [`draw_beam_overlay`], each following the light
+/// segment it labels.
+#[derive(Component)]
+pub struct BeamDebugLabel {
+    segment: Entity,
+}
+
+/// [`System`] that labels every visible light segment with its color and intensity, marks the
+/// points where beams hit something, and highlights the [`LightSensor`]s beams are hitting. Active
+/// sensors get a second, white outline. Does nothing unless `beams` is set in the
+/// [`DebugConfig`](crate::config::DebugConfig).
+#[allow(clippy::type_complexity)]
+pub fn draw_beam_overlay(
+    mut commands: Commands,
+    mut gizmos: Gizmos,
+    config: Res<Config>,
+    mut q_labels: Query<
+        (
+            Entity,
+            &BeamDebugLabel,
+            &mut Text2d,
+            &mut TextColor,
+            &mut Transform,
+        ),
+        Without<LightSegment>,
+    >,
+    q_segments: Query<(Entity, &Transform, &Visibility, &LightSegment, &LineLight2d)>,
+    q_light_sources: Query<(&LightBeamSource, &PrevLightBeamPlayback)>,
+    q_light_sensors: Query<(&GlobalTransform, &LightSensor)>,
+) {
+    // the labels of segments that are no longer drawn are despawned at the end
+    let mut stale_labels: HashMap<Entity, Entity> = q_labels
+        .iter()
+        .map(|(label, debug_label, ..)| (debug_label.segment, label))
+        .collect();
+    if !config.debug_config.beams {
+        for label in stale_labels.into_values() {
+            commands.entity(label).despawn();
+        }
+        return;
+    }
+
+    for (segment_entity, transform, visibility, segment, line_light) in q_segments.iter() {
+        if *visibility == Visibility::Hidden {
+            continue;
+        }
+        let center = transform.translation.xy();
+        let dir = (transform.rotation * Vec3::X).xy();
+        let color = segment.color.indicator_color();
+
+        gizmos.line_2d(
+            center - dir * line_light.half_length,
+            center + dir * line_light.half_length,
+            color,
+        );
+
+        let intensity = line_light.color.truncate().max_element() * line_light.color.w;
+        let text = format!(
+            "{} {:.1}",
+            format!("{:?}", segment.color).to_lowercase(),
+            intensity
+        );
+        let label_transform = Transform::from_translation(
+            (center + dir.perp() * BEAM_LABEL_OFFSET).extend(BEAM_LABEL_Z),
+        );
+
+        // labels are updated in place, so their text is only laid out again when it changes
+        let existing = stale_labels
+            .remove(&segment_entity)
+            .and_then(|label| q_labels.get_mut(label).ok());
+        let Some((_, _, mut label_text, mut label_color, mut current_transform)) = existing else {
+            commands.spawn((
+                BeamDebugLabel {
+                    segment: segment_entity,
+                },
+                Text2d::new(text),
+                TextFont {
+                    font_size: 6.0,
+                    ..default()
+                },
+                TextColor(color),
+                label_transform,
+            ));
+            continue;
+        };
+        if label_text.0 != text {
+            label_text.0 = text;
+        }
+        if label_color.0 != color {
+            label_color.0 = color;
+        }
+        current_transform.set_if_neq(label_transform);
+    }
+    for label in stale_labels.into_values() {
+        commands.entity(label).despawn();
+    }
+
+    for (source, prev_playback) in q_light_sources.iter() {
+        for intersection in prev_playback.intersections.iter().flatten() {
+            gizmos.circle_2d(
+                Isometry2d::from_translation(intersection.point),
+                2.0,
+                source.color.indicator_color(),
+            );
+        }
+    }
+
+    for (transform, sensor) in q_light_sensors.iter() {
+        let center = transform.translation().xy();
+        let Some((color, _)) = sensor.hit_by.iter().find(|(_, is_hit)| **is_hit) else {
+            continue;
+        };
+        gizmos.rect_2d(
+            Isometry2d::from_translation(center),
+            Vec2::splat(12.0),
+            color.indicator_color(),
+        );
+        if sensor.is_active {
+            gizmos.rect_2d(
+                Isometry2d::from_translation(center),
+                Vec2::splat(16.0),
+                Color::WHITE,
+            );
+        }
+    }
+}
+
+/// [`System`] that freezes every light beam at its first segment when F7 is pressed, and lets
+/// F8 advance them one bounce at a time, logging the color, origin and direction of each beam's
+/// new segment and what it hit. Pressing F7 again lets the beams run normally. Does nothing
+/// unless `beams` is set in the [`DebugConfig`](crate::config::DebugConfig).
+#[allow(clippy::too_many_arguments)]
+pub fn step_frozen_beams(
+    keys: Res<ButtonInput<KeyCode>>,
+    config: Res<Config>,
+    mut beam_freeze: ResMut<BeamFreeze>,
+    mut q_rapier: Query<&mut RapierContext>,
+    q_light_sources: Query<(Entity, &LightBeamSource)>,
+    q_iids: Query<&EntityIid>,
+    fog: Res<VolumetricFog>,
+    beam_bounces: Res<BeamBounces>,
+    media: BeamMedia,
+) {
+    if !config.debug_config.beams {
+        if beam_freeze.0.is_some() {
+            beam_freeze.0 = None;
+        }
+        return;
+    }
+    if keys.just_pressed(FREEZE_BEAMS_KEY) {
+        beam_freeze.0 = match beam_freeze.0 {
+            Some(_) => None,
+            None => Some(0),
+        };
+        info!(
+            "beams {}",
+            if beam_freeze.0.is_some() {
+                "frozen"
+            } else {
+                "unfrozen"
+            }
+        );
+    }
+    let Some(segments) = beam_freeze.0 else {
+        return;
+    };
+    if !keys.just_pressed(STEP_BEAMS_KEY) {
+        return;
+    }
+    let Ok(mut rapier_context) = q_rapier.get_single_mut() else {
+        return;
+    };
+
+    beam_freeze.0 = Some(segments + 1);
+    for (entity, source) in q_light_sources.iter() {
+        let max_bounces = beam_bounces.get(source.color);
+        let playback = play_light_beam(&mut rapier_context, source, max_bounces, &fog, &media);
+        let points: Vec<Vec2> = playback.iter_points(source).collect();
+        let (Some(origin), Some(end)) = (points.get(segments), points.get(segments + 1)) else {
+            info!("{:?} beam {}: no more segments", source.color, entity);
+            continue;
+        };
+        let hit = match playback.intersections.get(segments) {
+            Some(intersection) => match q_iids.get(intersection.entity) {
+                Ok(iid) => format!("{} ({})", intersection.entity, iid.as_str()),
+                Err(_) => format!("{}", intersection.entity),
+            },
+            None => "nothing".to_string(),
+        };
+        info!(
+            "{:?} beam {} segment {}: origin {}, direction {}, hit {}",
+            source.color,
+            entity,
+            segments,
+            origin,
+            (*end - *origin).normalize_or_zero(),
+            hit
+        );
+    }
+}
@@ -0,0 +1,328 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::{prelude::*, EntityIid};
+
+use crate::{
+    config::Config,
+    input::CursorWorldCoords,
+    level::{
+        aimable_emitter::AimableEmitter, carry_mirror::Mirror, grid::GridConfig, CurrentLevel,
+    },
+    player::{InputLocked, PlayerMarker},
+};
+
+/// Key that turns the level editor on and off, see [`toggle_level_editor`].
+const TOGGLE_EDITOR_KEY: KeyCode = KeyCode::F6;
+
+/// Key that logs the positions of everything that can be dragged, see
+/// [`export_editor_positions`].
+const EXPORT_POSITIONS_KEY: KeyCode = KeyCode::F9;
+
+/// How close the cursor needs to be to the center of something to drag it.
+const EDITOR_PICK_RADIUS: f32 = 12.0;
+
+/// The things that can be dragged around in the level editor.
+type Editable = Or<(With<AimableEmitter>, With<Mirror>)>;
+
+/// [`Resource`] for the level editor, where [`AimableEmitter`]s and [`Mirror`]s can be dragged
+/// around with the left mouse button to try out beam paths without going back to Ldtk. Beams are
+/// simulated as usual, so they follow whatever is being dragged.
+#[derive(Resource, Default, Debug)]
+pub struct LevelEditor {
+    pub enabled: bool,
+    dragging: Option<EditorDrag>,
+    /// The entities that were dragged and where they were before, the last drag at the end
+    history: Vec<(Entity, Vec2)>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct EditorDrag {
+    entity: Entity,
+    /// Where the entity was when the drag started, relative to its level
+    from: Vec2,
+    /// The position of the entity relative to the cursor, so it doesn't jump to the cursor
+    grab_offset: Vec2,
+}
+
+impl LevelEditor {
+    pub fn dragging(&self) -> Option<Entity> {
+        self.dragging.map(|drag| drag.entity)
+    }
+
+    /// Undoes the last drag, returning the entity that was dragged and where it goes back to.
+    pub fn undo(&mut self) -> Option<(Entity, Vec2)> {
+        self.history.pop()
+    }
+}
+
+/// Where something that was at `from` when the drag started is dragged to when the cursor wants
+/// it at `target`. Unless `grid` is [`None`], it only moves in whole grid cells, so things stay
+/// lined up with the tiles they were placed on.
+pub fn drag_position(from: Vec2, target: Vec2, grid: Option<&GridConfig>) -> Vec2 {
+    match grid {
+        Some(grid) => from + grid.snap_to_grid(target - from),
+        None => target,
+    }
+}
+
+/// The pixel coordinates of an entity at `translation` in a level `level_height` pixels tall, as
+/// Ldtk stores them in the entity's `px` field. Undoes the conversion and pivot offset applied by
+/// bevy_ecs_ldtk when the entity was spawned.
+pub fn ldtk_px(translation: Vec2, level_height: f32, instance: &EntityInstance) -> IVec2 {
+    let size = Vec2::new(instance.width as f32, instance.height as f32);
+    let pivot_offset = size * Vec2::new(0.5 - instance.pivot.x, instance.pivot.y - 0.5);
+    let pivot_point = translation - pivot_offset;
+    Vec2::new(pivot_point.x, level_height - pivot_point.y)
+        .round()
+        .as_ivec2()
+}
+
+/// [`System`] that turns the [`LevelEditor`] on and off when F6 is pressed, locking the player's
+/// inputs while it is on so clicks don't shoot light. Does nothing unless `editor` is set in the
+/// [`DebugConfig`](crate::config::DebugConfig).
+pub fn toggle_level_editor(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    config: Res<Config>,
+    q_player: Query<Entity, With<PlayerMarker>>,
+    mut editor: ResMut<LevelEditor>,
+) {
+    let toggled = config.debug_config.editor && keys.just_pressed(TOGGLE_EDITOR_KEY);
+    // also turn the editor off if it is turned off in the config while on
+    let turned_off = editor.enabled && !config.debug_config.editor;
+    if !toggled && !turned_off {
+        return;
+    }
+    editor.enabled = !editor.enabled;
+    editor.dragging = None;
+    info!("level editor {}", if editor.enabled { "on" } else { "off" });
+    for player in q_player.iter() {
+        if editor.enabled {
+            commands.entity(player).insert(InputLocked);
+        } else {
+            commands.entity(player).remove::<InputLocked>();
+        }
+    }
+}
+
+/// [`System`] that drags [`AimableEmitter`]s and [`Mirror`]s with the left mouse button while the
+/// [`LevelEditor`] is on. Drags snap to the [`GridConfig`] unless shift is held, and ctrl + Z
+/// undoes the last drag.
+pub fn drag_editable_entities(
+    buttons: Res<ButtonInput<MouseButton>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    q_cursor: Query<&CursorWorldCoords>,
+    mut q_editable: Query<(Entity, &GlobalTransform, &mut Transform), Editable>,
+    grid: Res<GridConfig>,
+    mut editor: ResMut<LevelEditor>,
+) {
+    if !editor.enabled {
+        return;
+    }
+
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if ctrl && keys.just_pressed(KeyCode::KeyZ) {
+        editor.dragging = None;
+        if let Some((entity, pos)) = editor.undo() {
+            if let Ok((_, _, mut transform)) = q_editable.get_mut(entity) {
+                transform.translation = pos.extend(transform.translation.z);
+            }
+        }
+        return;
+    }
+
+    let Ok(cursor) = q_cursor.get_single() else {
+        return;
+    };
+    if buttons.just_pressed(MouseButton::Left) {
+        let picked = q_editable
+            .iter()
+            .map(|(entity, global_transform, transform)| {
+                let distance = global_transform.translation().xy().distance(cursor.pos);
+                (entity, distance, transform.translation.xy())
+            })
+            .filter(|(_, distance, _)| *distance <= EDITOR_PICK_RADIUS)
+            .min_by(|(_, a, _), (_, b, _)| a.total_cmp(b));
+        if let Some((entity, _, from)) = picked {
+            editor.dragging = Some(EditorDrag {
+                entity,
+                from,
+                grab_offset: from - cursor.pos,
+            });
+            editor.history.push((entity, from));
+        }
+    }
+
+    let Some(drag) = editor.dragging else {
+        return;
+    };
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let target = cursor.pos + drag.grab_offset;
+    let pos = drag_position(drag.from, target, (!shift).then_some(&*grid));
+    if let Ok((_, _, mut transform)) = q_editable.get_mut(drag.entity) {
+        if transform.translation.xy() != pos {
+            transform.translation = pos.extend(transform.translation.z);
+        }
+    }
+
+    if !buttons.pressed(MouseButton::Left) {
+        // clicking without moving isn't worth undoing
+        if editor.history.last() == Some(&(drag.entity, pos)) {
+            editor.history.pop();
+        }
+        editor.dragging = None;
+    }
+}
+
+/// [`System`] that circles everything that can be dragged while the [`LevelEditor`] is on,
+/// highlighting what is being dragged.
+pub fn draw_editor_gizmos(
+    mut gizmos: Gizmos,
+    q_editable: Query<(Entity, &GlobalTransform), Editable>,
+    editor: Res<LevelEditor>,
+) {
+    if !editor.enabled {
+        return;
+    }
+    for (entity, transform) in q_editable.iter() {
+        let color = if editor.dragging() == Some(entity) {
+            Color::WHITE
+        } else {
+            Color::srgb(0.9, 0.6, 0.1)
+        };
+        gizmos.circle_2d(
+            Isometry2d::from_translation(transform.translation().xy()),
+            EDITOR_PICK_RADIUS,
+            color,
+        );
+    }
+}
+
+/// [`System`] that logs the Ldtk coordinates of everything that can be dragged in the
+/// [`CurrentLevel`] when F9 is pressed while the [`LevelEditor`] is on, so the adjusted positions
+/// can be copied back into the level.
+pub fn export_editor_positions(
+    keys: Res<ButtonInput<KeyCode>>,
+    q_editable: Query<(&EntityIid, &EntityInstance, &GlobalTransform, &Transform), Editable>,
+    current_level: Res<CurrentLevel>,
+    editor: Res<LevelEditor>,
+) {
+    if !editor.enabled || !keys.just_pressed(EXPORT_POSITIONS_KEY) {
+        return;
+    }
+    let level_height = current_level.level_box.height();
+    for (iid, instance, global_transform, transform) in q_editable.iter() {
+        if !current_level
+            .level_box
+            .contains(global_transform.translation().xy())
+        {
+            continue;
+        }
+        let px = ldtk_px(transform.translation.xy(), level_height, instance);
+        info!(
+            "{} {}: px [{}, {}]",
+            instance.identifier,
+            iid.as_str(),
+            px.x,
+            px.y
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ldtk_coordinates_round_trip() {
+        let instance = EntityInstance {
+            width: 16,
+            height: 8,
+            pivot: Vec2::new(0.5, 1.0),
+            ..default()
+        };
+        // bevy_ecs_ldtk puts the center of the entity at its translation
+        let translation = Vec2::new(40.0, 96.0 - 20.0 + 4.0);
+        assert_eq!(ldtk_px(translation, 96.0, &instance), IVec2::new(40, 20));
+
+        let centered = EntityInstance {
+            pivot: Vec2::splat(0.5),
+            ..instance
+        };
+        assert_eq!(
+            ldtk_px(Vec2::new(8.0, 88.0), 96.0, &centered),
+            IVec2::new(8, 8)
+        );
+    }
+
+    fn editor_app() -> (App, Entity) {
+        let mut app = App::new();
+        app.insert_resource(GridConfig { size: 8.0 })
+            .init_resource::<LevelEditor>()
+            .init_resource::<ButtonInput<MouseButton>>()
+            .init_resource::<ButtonInput<KeyCode>>()
+            .add_systems(Update, drag_editable_entities);
+        app.world_mut().resource_mut::<LevelEditor>().enabled = true;
+        app.world_mut().spawn(CursorWorldCoords::default());
+        let mirror = app
+            .world_mut()
+            .spawn((
+                Mirror,
+                Transform::from_xyz(4.0, 4.0, 1.0),
+                GlobalTransform::from_xyz(4.0, 4.0, 1.0),
+            ))
+            .id();
+        (app, mirror)
+    }
+
+    fn move_cursor(app: &mut App, pos: Vec2) {
+        let mut q_cursor = app.world_mut().query::<&mut CursorWorldCoords>();
+        q_cursor.single_mut(app.world_mut()).pos = pos;
+    }
+
+    fn position(app: &App, entity: Entity) -> Vec3 {
+        app.world().get::<Transform>(entity).unwrap().translation
+    }
+
+    #[test]
+    fn dragging_snaps_to_grid_and_undoes() {
+        let (mut app, mirror) = editor_app();
+
+        move_cursor(&mut app, Vec2::new(6.0, 2.0));
+        app.world_mut()
+            .resource_mut::<ButtonInput<MouseButton>>()
+            .press(MouseButton::Left);
+        app.update();
+        assert_eq!(
+            app.world().resource::<LevelEditor>().dragging(),
+            Some(mirror)
+        );
+        app.world_mut()
+            .resource_mut::<ButtonInput<MouseButton>>()
+            .clear();
+
+        // stays on the same offset from the grid it was placed at
+        move_cursor(&mut app, Vec2::new(21.0, -13.0));
+        app.update();
+        assert_eq!(position(&app, mirror), Vec3::new(20.0, -12.0, 1.0));
+
+        app.world_mut()
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .press(KeyCode::ShiftLeft);
+        app.update();
+        assert_eq!(position(&app, mirror), Vec3::new(19.0, -11.0, 1.0));
+
+        app.world_mut()
+            .resource_mut::<ButtonInput<MouseButton>>()
+            .release(MouseButton::Left);
+        app.update();
+        assert_eq!(app.world().resource::<LevelEditor>().dragging(), None);
+
+        let mut keys = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+        keys.release(KeyCode::ShiftLeft);
+        keys.press(KeyCode::ControlLeft);
+        keys.press(KeyCode::KeyZ);
+        app.update();
+        assert_eq!(position(&app, mirror), Vec3::new(4.0, 4.0, 1.0));
+        assert_eq!(app.world_mut().resource_mut::<LevelEditor>().undo(), None);
+    }
+}
@@ -0,0 +1,226 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_inspector_egui::{bevy_egui::EguiContext, egui};
+
+use crate::{
+    config::Config,
+    input::CursorWorldCoords,
+    lighting::{LightToggle, LineLight2d},
+};
+
+/// [`Resource`] holding the lights shown in the light inspector, see [`light_inspector_ui`].
+#[derive(Resource, Default, Debug)]
+pub struct InspectedLights(pub Vec<Entity>);
+
+/// Whether `point` is inside the bounds of `light`, a capsule around its center line.
+pub fn light_contains_point(transform: &GlobalTransform, light: &LineLight2d, point: Vec2) -> bool {
+    let center = transform.translation().xy();
+    let dir = (transform.rotation() * Vec3::X).xy();
+    let t = (point - center)
+        .dot(dir)
+        .clamp(-light.half_length, light.half_length);
+    point.distance(center + dir * t) <= light.radius
+}
+
+/// The smallest light whose bounds contain `point`, so small lights in front of big ones can still
+/// be picked.
+pub fn pick_light<'a>(
+    lights: impl IntoIterator<Item = (Entity, &'a GlobalTransform, &'a LineLight2d)>,
+    point: Vec2,
+) -> Option<Entity> {
+    lights
+        .into_iter()
+        .filter(|(_, transform, light)| light_contains_point(transform, light, point))
+        .min_by(|(_, _, a), (_, _, b)| {
+            (a.radius + a.half_length).total_cmp(&(b.radius + b.half_length))
+        })
+        .map(|(entity, ..)| entity)
+}
+
+/// [`System`] that selects the light under the cursor on middle click, or adds it to the
+/// selection if shift is held. Shift clicking a selected light deselects it. Does nothing unless
+/// `lights` is set in the [`DebugConfig`](crate::config::DebugConfig).
+pub fn select_inspected_lights(
+    config: Res<Config>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    q_cursor: Query<&CursorWorldCoords>,
+    q_lights: Query<(Entity, &GlobalTransform, &LineLight2d)>,
+    mut inspected: ResMut<InspectedLights>,
+) {
+    if !config.debug_config.lights || !buttons.just_pressed(MouseButton::Middle) {
+        return;
+    }
+    let Ok(cursor) = q_cursor.get_single() else {
+        return;
+    };
+    let picked = pick_light(q_lights.iter(), cursor.pos);
+
+    if !keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        inspected.0 = picked.into_iter().collect();
+        return;
+    }
+    let Some(picked) = picked else {
+        return;
+    };
+    match inspected.0.iter().position(|entity| *entity == picked) {
+        Some(index) => {
+            inspected.0.remove(index);
+        }
+        None => inspected.0.push(picked),
+    }
+}
+
+/// [`System`] that outlines the bounds of the inspected lights.
+pub fn draw_inspected_lights(
+    mut gizmos: Gizmos,
+    config: Res<Config>,
+    inspected: Res<InspectedLights>,
+    q_lights: Query<(&GlobalTransform, &LineLight2d)>,
+) {
+    if !config.debug_config.lights {
+        return;
+    }
+    for (transform, light) in q_lights.iter_many(&inspected.0) {
+        let center = transform.translation().xy();
+        let dir = (transform.rotation() * Vec3::X).xy();
+        let color = Color::srgb(light.color.x, light.color.y, light.color.z);
+        gizmos.line_2d(
+            center - dir * light.half_length,
+            center + dir * light.half_length,
+            color,
+        );
+        gizmos.circle_2d(
+            Isometry2d::from_translation(center - dir * light.half_length),
+            light.radius,
+            color,
+        );
+        gizmos.circle_2d(
+            Isometry2d::from_translation(center + dir * light.half_length),
+            light.radius,
+            color,
+        );
+    }
+}
+
+/// Exclusive [`System`] that shows a window with sliders for every field of the inspected
+/// [`LineLight2d`]s, writing edits straight back to the lights. The intensity of a light with a
+/// [`LightToggle`] is the toggle's intensity, since the toggle overwrites the light's every frame.
+pub fn light_inspector_ui(world: &mut World) {
+    let config = world.resource::<Config>();
+    if !config.debug_config.ui || !config.debug_config.lights {
+        return;
+    }
+
+    let Ok(egui_context) = world
+        .query_filtered::<&mut EguiContext, With<PrimaryWindow>>()
+        .get_single(world)
+    else {
+        return;
+    };
+    let mut egui_context = egui_context.clone();
+    let inspected = world.resource::<InspectedLights>().0.clone();
+
+    egui::Window::new("Lights").show(egui_context.get_mut(), |ui| {
+        if inspected.is_empty() {
+            ui.label("Middle click a light to inspect it, shift to select more than one");
+        }
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for entity in inspected {
+                let Some(mut light) = world.get::<LineLight2d>(entity).cloned() else {
+                    continue;
+                };
+                let mut toggle_intensity = world.get::<LightToggle>(entity).map(|t| t.intensity);
+
+                ui.heading(format!("{entity}"));
+                let mut changed = false;
+                // light colors go above 1, so they are edited as numbers instead of with a picker
+                ui.horizontal(|ui| {
+                    ui.label("color");
+                    for channel in [&mut light.color.x, &mut light.color.y, &mut light.color.z] {
+                        changed |= ui
+                            .add(egui::DragValue::new(channel).speed(0.01).range(0.0..=10.0))
+                            .changed();
+                    }
+                });
+                let intensity = toggle_intensity.as_mut().unwrap_or(&mut light.color.w);
+                changed |= ui
+                    .add(egui::Slider::new(intensity, 0.0..=5.0).text("intensity"))
+                    .changed();
+                changed |= ui
+                    .add(egui::Slider::new(&mut light.radius, 0.0..=200.0).text("radius"))
+                    .changed();
+                changed |= ui
+                    .add(
+                        egui::Slider::new(&mut light.inner_radius, 0.0..=200.0)
+                            .text("inner_radius"),
+                    )
+                    .changed();
+                changed |= ui
+                    .add(egui::Slider::new(&mut light.half_length, 0.0..=200.0).text("half_length"))
+                    .changed();
+                changed |= ui
+                    .add(
+                        egui::Slider::new(&mut light.volumetric_intensity, 0.0..=2.0)
+                            .text("volumetric_intensity"),
+                    )
+                    .changed();
+                ui.separator();
+
+                // only write back edits, so the light isn't marked as changed every frame
+                if !changed {
+                    continue;
+                }
+                if let Some(intensity) = toggle_intensity {
+                    if let Some(mut toggle) = world.get_mut::<LightToggle>(entity) {
+                        toggle.intensity = intensity;
+                    }
+                }
+                if let Some(mut old_light) = world.get_mut::<LineLight2d>(entity) {
+                    *old_light = light;
+                }
+            }
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_smallest_light_under_cursor() {
+        let mut world = World::new();
+        let big = world.spawn_empty().id();
+        let small = world.spawn_empty().id();
+        let beam = world.spawn_empty().id();
+
+        let origin = GlobalTransform::default();
+        let big_light = LineLight2d::point(Vec4::ONE, 40.0, 0.0);
+        let small_light = LineLight2d::point(Vec4::ONE, 8.0, 0.0);
+        // a beam pointing up, 60 pixels to the right
+        let beam_transform = GlobalTransform::from(
+            Transform::from_xyz(60.0, 0.0, 0.0)
+                .with_rotation(Quat::from_rotation_z(std::f32::consts::FRAC_PI_2)),
+        );
+        let beam_light = LineLight2d {
+            color: Vec4::ONE,
+            half_length: 30.0,
+            radius: 4.0,
+            inner_radius: 0.0,
+            volumetric_intensity: 0.0,
+            temperature_k: None,
+            shadow_tint: Vec4::ZERO,
+            falloff_texture: None,
+        };
+        let lights = [
+            (big, &origin, &big_light),
+            (small, &origin, &small_light),
+            (beam, &beam_transform, &beam_light),
+        ];
+
+        assert_eq!(pick_light(lights, Vec2::new(2.0, 2.0)), Some(small));
+        assert_eq!(pick_light(lights, Vec2::new(20.0, 0.0)), Some(big));
+        assert_eq!(pick_light(lights, Vec2::new(62.0, 28.0)), Some(beam));
+        assert_eq!(pick_light(lights, Vec2::new(62.0, 40.0)), None);
+    }
+}
@@ -2,6 +2,7 @@ use bevy::{
     diagnostic::FrameTimeDiagnosticsPlugin,
     ecs::schedule::{LogLevel, ScheduleBuildSettings},
     prelude::*,
+    render::diagnostic::RenderDiagnosticsPlugin,
     window::PrimaryWindow,
 };
 use bevy_ecs_ldtk::LevelIid;
@@ -12,6 +13,23 @@ use bevy_inspector_egui::{
 use bevy_rapier2d::render::RapierDebugRenderPlugin;
 
 use crate::config::Config;
+#[cfg(feature = "dev")]
+use beams::{draw_beam_overlay, step_frozen_beams};
+#[cfg(feature = "dev")]
+use editor::{
+    drag_editable_entities, draw_editor_gizmos, export_editor_positions, toggle_level_editor,
+    LevelEditor,
+};
+#[cfg(feature = "dev")]
+use lights::{draw_inspected_lights, light_inspector_ui, select_inspected_lights, InspectedLights};
+
+// tools for building levels are left out of release builds, see the `dev` feature in `Cargo.toml`
+#[cfg(feature = "dev")]
+mod beams;
+#[cfg(feature = "dev")]
+mod editor;
+#[cfg(feature = "dev")]
+mod lights;
 
 pub struct DebugPlugin {
     pub physics: bool,
@@ -22,17 +40,38 @@ pub struct DebugPlugin {
 
 impl Plugin for DebugPlugin {
     fn build(&self, app: &mut App) {
+        #[cfg(feature = "dev")]
+        app.add_systems(Update, (draw_beam_overlay, step_frozen_beams));
+        #[cfg(feature = "dev")]
+        app.init_resource::<InspectedLights>()
+            .add_systems(Update, (select_inspected_lights, draw_inspected_lights));
+        #[cfg(feature = "dev")]
+        app.init_resource::<LevelEditor>().add_systems(
+            Update,
+            (
+                toggle_level_editor,
+                drag_editable_entities,
+                draw_editor_gizmos,
+                export_editor_positions,
+            )
+                .chain(),
+        );
+
         if self.ui {
             app.add_plugins(EguiPlugin)
                 .add_plugins(bevy_inspector_egui::DefaultInspectorConfigPlugin)
                 .add_systems(Last, debug_ui);
+            #[cfg(feature = "dev")]
+            app.add_systems(Last, light_inspector_ui);
         }
 
         if self.physics {
             app.add_plugins(RapierDebugRenderPlugin::default());
         }
         if self.frame_time {
-            app.add_plugins(FrameTimeDiagnosticsPlugin);
+            app.add_plugins(FrameTimeDiagnosticsPlugin)
+                // GPU times of render passes, including the lighting pass
+                .add_plugins(RenderDiagnosticsPlugin);
         }
         if self.ambiguity {
             app.edit_schedule(Update, |schedule| {
@@ -0,0 +1,549 @@
+use std::time::Duration;
+
+use bevy::{
+    input::{
+        mouse::{MouseScrollUnit, MouseWheel},
+        InputSystem,
+    },
+    prelude::*,
+    time::{TimeSystem, TimeUpdateStrategy},
+    ui::UiSystem,
+    window::PrimaryWindow,
+};
+
+use crate::config::Config;
+
+/// [`Plugin`] that records the player's inputs to a file, or plays them back from one. Which mode
+/// is active (if any) is decided by the `demo_config` section of `Lightborne.toml`.
+///
+/// Along with the inputs, the real time delta of every frame is recorded and fed back through
+/// [`TimeUpdateStrategy::ManualDuration`] during playback, so that the same number of
+/// [`FixedUpdate`] ticks run on the same frames as in the original run. The
+/// `level_config.run_seed` is recorded too and replaces the one in the config during playback,
+/// so the [`LevelRng`](crate::level::rng::LevelRng) rolls the same numbers as in the original run.
+pub struct DemoPlugin;
+
+impl Plugin for DemoPlugin {
+    fn build(&self, app: &mut App) {
+        let demo_config = &app.world().resource::<Config>().demo_config;
+        let record = demo_config.record.clone();
+        let playback = demo_config.playback.clone();
+
+        if let Some(path) = playback {
+            let contents = std::fs::read_to_string(&path)
+                .unwrap_or_else(|_| panic!("Failed to read demo file {}", path));
+            let demo = Demo::parse(&contents).expect("Failed to parse demo file");
+            let Some(first_frame) = demo.frames.first() else {
+                warn!("Demo file {} has no frames, skipping playback", path);
+                return;
+            };
+            demo.apply_run_seed(&mut app.world_mut().resource_mut::<Config>());
+
+            app.insert_resource(TimeUpdateStrategy::ManualDuration(first_frame.delta))
+                .insert_resource(DemoPlayback { demo, index: 0 })
+                .add_systems(First, apply_demo_time_speed.before(TimeSystem))
+                .add_systems(
+                    PreUpdate,
+                    play_demo_frame.after(InputSystem).before(UiSystem::Focus),
+                );
+        } else if let Some(path) = record {
+            let run_seed = app.world().resource::<Config>().level_config.run_seed;
+            app.insert_resource(DemoRecording {
+                path,
+                demo: Demo {
+                    run_seed: Some(run_seed),
+                    frames: vec![],
+                },
+            })
+            .add_systems(PreUpdate, record_demo_frame.after(InputSystem))
+            .add_systems(Last, save_demo_recording.run_if(on_event::<AppExit>));
+        }
+    }
+}
+
+/// The keys that are saved in a [`DemoFrame`]. The index of a key in this array is its bit in
+/// [`DemoFrame::keys`], so only append to this list to keep old demo files valid.
+const DEMO_KEYS: [KeyCode; 17] = [
+    KeyCode::KeyA,
+    KeyCode::KeyD,
+    KeyCode::KeyS,
+    KeyCode::KeyW,
+    KeyCode::Space,
+    KeyCode::KeyR,
+    KeyCode::KeyL,
+    KeyCode::Escape,
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::ShiftLeft,
+    KeyCode::KeyQ,
+    KeyCode::KeyE,
+    KeyCode::KeyF,
+    KeyCode::KeyT,
+];
+
+/// The mouse buttons that are saved in a [`DemoFrame`], see [`DEMO_KEYS`].
+const DEMO_MOUSE_BUTTONS: [MouseButton; 2] = [MouseButton::Left, MouseButton::Right];
+
+/// The first line of every demo file, used to reject files from incompatible versions.
+const DEMO_HEADER: &str = "lightborne-demo 2";
+
+/// The first line of demo files recorded before the run seed was saved, which are played back
+/// with the run seed in the config.
+const DEMO_HEADER_V1: &str = "lightborne-demo 1";
+
+/// The input state of a single frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DemoFrame {
+    /// The real time that passed since the last frame
+    pub delta: Duration,
+    /// Bitmask of the pressed [`DEMO_KEYS`]
+    pub keys: u32,
+    /// Bitmask of the pressed [`DEMO_MOUSE_BUTTONS`]
+    pub mouse: u8,
+    /// Logical position of the cursor in the primary window
+    pub cursor: Option<Vec2>,
+    /// Number of scroll wheel steps, positive is up
+    pub scroll: i32,
+    /// The relative speed of [`Time<Virtual>`] during the frame, e.g. during the death slow motion
+    pub speed: f32,
+}
+
+impl Default for DemoFrame {
+    fn default() -> Self {
+        DemoFrame {
+            delta: Duration::ZERO,
+            keys: 0,
+            mouse: 0,
+            cursor: None,
+            scroll: 0,
+            speed: 1.0,
+        }
+    }
+}
+
+/// A list of [`DemoFrame`]s and the run seed they were recorded with, along with its text
+/// representation used to save it to disk.
+#[derive(Debug, Default, PartialEq)]
+pub struct Demo {
+    /// The `level_config.run_seed` of the recorded run, [`None`] for old demos that didn't save it
+    pub run_seed: Option<u64>,
+    pub frames: Vec<DemoFrame>,
+}
+
+impl Demo {
+    /// Parses a demo from the format written by [`Demo::serialize`].
+    pub fn parse(contents: &str) -> Result<Demo, String> {
+        let mut lines = contents.lines();
+        let run_seed = match lines.next() {
+            Some(DEMO_HEADER) => Some(
+                lines
+                    .next()
+                    .and_then(|line| line.strip_prefix("run_seed "))
+                    .and_then(|seed| seed.parse().ok())
+                    .ok_or("Demo file should have its run seed on the second line")?,
+            ),
+            Some(DEMO_HEADER_V1) => None,
+            _ => return Err(format!("Demo file should start with \"{}\"", DEMO_HEADER)),
+        };
+
+        let mut frames = vec![];
+        for (i, line) in lines.enumerate() {
+            let mut parts: Vec<&str> = line.split_whitespace().collect();
+            // demos recorded before the speed was saved have 6 fields
+            let speed = if parts.len() == 7 { parts.pop() } else { None };
+            let [delta, keys, mouse, cursor_x, cursor_y, scroll] = parts[..] else {
+                return Err(format!("Frame {} should have 6 or 7 fields", i));
+            };
+            let err = |field: &str| format!("Frame {} has an invalid {}", i, field);
+
+            let cursor = match (cursor_x, cursor_y) {
+                ("-", "-") => None,
+                (x, y) => Some(Vec2::new(
+                    x.parse().map_err(|_| err("cursor"))?,
+                    y.parse().map_err(|_| err("cursor"))?,
+                )),
+            };
+
+            frames.push(DemoFrame {
+                delta: Duration::from_nanos(delta.parse().map_err(|_| err("delta"))?),
+                keys: keys.parse().map_err(|_| err("key mask"))?,
+                mouse: mouse.parse().map_err(|_| err("mouse mask"))?,
+                cursor,
+                scroll: scroll.parse().map_err(|_| err("scroll"))?,
+                // `Time<Virtual>` can't run at negative speeds
+                speed: match speed {
+                    Some(speed) => speed
+                        .parse()
+                        .ok()
+                        .filter(|speed: &f32| speed.is_finite() && *speed >= 0.0)
+                        .ok_or_else(|| err("speed"))?,
+                    None => 1.0,
+                },
+            });
+        }
+
+        Ok(Demo { run_seed, frames })
+    }
+
+    /// Replaces the run seed in `config` with the one the demo was recorded with, if it was saved.
+    pub fn apply_run_seed(&self, config: &mut Config) {
+        if let Some(run_seed) = self.run_seed {
+            config.level_config.run_seed = run_seed;
+        }
+    }
+
+    /// Writes the demo as text, with the run seed after the header and then one frame per line.
+    pub fn serialize(&self) -> String {
+        let mut out = String::from(DEMO_HEADER);
+        out.push('\n');
+        out.push_str(&format!("run_seed {}\n", self.run_seed.unwrap_or_default()));
+        for frame in self.frames.iter() {
+            let (cursor_x, cursor_y) = match frame.cursor {
+                Some(pos) => (pos.x.to_string(), pos.y.to_string()),
+                None => ("-".into(), "-".into()),
+            };
+            out.push_str(&format!(
+                "{} {} {} {} {} {} {}\n",
+                frame.delta.as_nanos(),
+                frame.keys,
+                frame.mouse,
+                cursor_x,
+                cursor_y,
+                frame.scroll,
+                frame.speed
+            ));
+        }
+        out
+    }
+}
+
+/// [`Resource`] that holds the frames recorded so far, inserted if `demo_config.record` is set.
+#[derive(Resource)]
+pub struct DemoRecording {
+    path: String,
+    demo: Demo,
+}
+
+/// [`Resource`] that holds the demo being played back, inserted if `demo_config.playback` is set.
+#[derive(Resource)]
+pub struct DemoPlayback {
+    demo: Demo,
+    index: usize,
+}
+
+/// [`System`] that runs right after Bevy processes input events, saving the input state of this
+/// frame into the [`DemoRecording`].
+pub fn record_demo_frame(
+    mut recording: ResMut<DemoRecording>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    mut ev_scroll: EventReader<MouseWheel>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    time: Res<Time<Real>>,
+    virtual_time: Res<Time<Virtual>>,
+) {
+    let mut frame = DemoFrame {
+        delta: time.delta(),
+        cursor: q_window.get_single().ok().and_then(Window::cursor_position),
+        speed: virtual_time.relative_speed(),
+        ..default()
+    };
+    for (i, key) in DEMO_KEYS.iter().enumerate() {
+        if keys.pressed(*key) {
+            frame.keys |= 1 << i;
+        }
+    }
+    for (i, button) in DEMO_MOUSE_BUTTONS.iter().enumerate() {
+        if mouse.pressed(*button) {
+            frame.mouse |= 1 << i;
+        }
+    }
+    for scroll in ev_scroll.read() {
+        frame.scroll += scroll.y.signum() as i32;
+    }
+
+    recording.demo.frames.push(frame);
+}
+
+/// [`System`] that writes the [`DemoRecording`] to disk when the game is closed.
+pub fn save_demo_recording(recording: Res<DemoRecording>) {
+    match std::fs::write(&recording.path, recording.demo.serialize()) {
+        Ok(()) => info!(
+            "Saved demo with {} frames to {}",
+            recording.demo.frames.len(),
+            recording.path
+        ),
+        Err(err) => error!("Failed to save demo to {}: {}", recording.path, err),
+    }
+}
+
+/// [`System`] that runs right before [`Time`] is updated, setting the speed of [`Time<Virtual>`]
+/// to the speed recorded for the frame that is about to be played back. The game changes the speed
+/// itself (e.g. when the player dies), so this only matters if that would happen on a different
+/// frame than in the recording, which would desync the rest of the demo.
+pub fn apply_demo_time_speed(playback: Res<DemoPlayback>, mut time: ResMut<Time<Virtual>>) {
+    let Some(frame) = playback.demo.frames.get(playback.index) else {
+        return;
+    };
+    if time.relative_speed() != frame.speed {
+        time.set_relative_speed(frame.speed);
+    }
+}
+
+/// Presses or releases `input` so that its pressed state matches `pressed`. Only changes the
+/// input when needed so that `just_pressed` and `just_released` behave like they would for real
+/// input.
+fn set_pressed<T: Copy + Eq + std::hash::Hash + Send + Sync + 'static>(
+    input: &mut ButtonInput<T>,
+    button: T,
+    pressed: bool,
+) {
+    if pressed && !input.pressed(button) {
+        input.press(button);
+    } else if !pressed && input.pressed(button) {
+        input.release(button);
+    }
+}
+
+/// Forgets every button of `input` that isn't in `recorded`, as if it was never pressed.
+fn reset_unrecorded<T: Copy + Eq + std::hash::Hash + Send + Sync + 'static>(
+    input: &mut ButtonInput<T>,
+    recorded: &[T],
+) {
+    let unrecorded: Vec<T> = input
+        .get_pressed()
+        .chain(input.get_just_released())
+        .filter(|button| !recorded.contains(button))
+        .copied()
+        .collect();
+    for button in unrecorded {
+        input.reset(button);
+    }
+}
+
+/// [`System`] that runs right after Bevy processes input events, overwriting the input state with
+/// the next frame of the [`DemoPlayback`]. Runs before UI focus is computed so that the recorded
+/// cursor position is also used to interact with menus. Live input is ignored until the demo
+/// finishes, including the keys and mouse buttons that aren't recorded, and scrolling.
+pub fn play_demo_frame(
+    mut playback: ResMut<DemoPlayback>,
+    mut keys: ResMut<ButtonInput<KeyCode>>,
+    mut mouse: ResMut<ButtonInput<MouseButton>>,
+    mut ev_scroll: ResMut<Events<MouseWheel>>,
+    mut q_window: Query<(Entity, &mut Window), With<PrimaryWindow>>,
+    mut time_update_strategy: ResMut<TimeUpdateStrategy>,
+) {
+    let Some(frame) = playback.demo.frames.get(playback.index).copied() else {
+        return;
+    };
+    playback.index += 1;
+
+    reset_unrecorded(&mut keys, &DEMO_KEYS);
+    reset_unrecorded(&mut mouse, &DEMO_MOUSE_BUTTONS);
+    ev_scroll.clear();
+    for (i, key) in DEMO_KEYS.iter().enumerate() {
+        set_pressed(&mut keys, *key, frame.keys & (1 << i) != 0);
+    }
+    for (i, button) in DEMO_MOUSE_BUTTONS.iter().enumerate() {
+        set_pressed(&mut mouse, *button, frame.mouse & (1 << i) != 0);
+    }
+
+    if let Ok((window_entity, mut window)) = q_window.get_single_mut() {
+        window.set_cursor_position(frame.cursor);
+        for _ in 0..frame.scroll.abs() {
+            ev_scroll.send(MouseWheel {
+                unit: MouseScrollUnit::Line,
+                x: 0.0,
+                y: frame.scroll.signum() as f32,
+                window: window_entity,
+            });
+        }
+    }
+
+    // The time strategy is read in `First`, so this sets the delta of the next frame
+    match playback.demo.frames.get(playback.index) {
+        Some(next_frame) => {
+            *time_update_strategy = TimeUpdateStrategy::ManualDuration(next_frame.delta);
+        }
+        None => {
+            *time_update_strategy = TimeUpdateStrategy::Automatic;
+            info!("Demo playback finished");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::level::{
+        rng::{reseed_level_rng, LevelRng},
+        CurrentLevel,
+    };
+
+    use super::*;
+
+    #[test]
+    fn demo_round_trip() {
+        let demo = Demo {
+            run_seed: Some(42),
+            frames: vec![
+                DemoFrame::default(),
+                DemoFrame {
+                    delta: Duration::from_nanos(16_666_667),
+                    keys: 0b1_0001_0011,
+                    mouse: 0b01,
+                    cursor: Some(Vec2::new(123.456, -0.1)),
+                    scroll: -2,
+                    speed: 0.3,
+                },
+                DemoFrame {
+                    delta: Duration::from_nanos(15_625_000),
+                    keys: 0,
+                    mouse: 0b10,
+                    cursor: None,
+                    scroll: 1,
+                    speed: 1.0,
+                },
+            ],
+        };
+
+        assert_eq!(Demo::parse(&demo.serialize()), Ok(demo));
+    }
+
+    #[test]
+    fn demo_rejects_bad_files() {
+        assert!(Demo::parse("not a demo\n").is_err());
+        assert!(Demo::parse(&format!("{}\n1 2 3\n", DEMO_HEADER)).is_err());
+        assert!(Demo::parse(&format!("{}\nrun_seed 1\n1 2 3\n", DEMO_HEADER)).is_err());
+        assert!(Demo::parse(&format!("{}\n16666667 0 0 - - 0 -1\n", DEMO_HEADER)).is_err());
+        assert!(Demo::parse(&format!("{}\n16666667 0 0 - - 0 NaN\n", DEMO_HEADER)).is_err());
+    }
+
+    #[test]
+    fn demo_without_speed_plays_at_normal_speed() {
+        let demo = Demo::parse(&format!("{}\n16666667 0 0 - - 0\n", DEMO_HEADER_V1)).unwrap();
+        assert_eq!(demo.frames[0].speed, 1.0);
+        // and with the run seed of the config
+        assert_eq!(demo.run_seed, None);
+        let mut config = Config::default();
+        config.level_config.run_seed = 3;
+        demo.apply_run_seed(&mut config);
+        assert_eq!(config.level_config.run_seed, 3);
+    }
+
+    /// The numbers rolled by [`roll_while_jumping`].
+    #[derive(Resource, Default)]
+    struct Rolls(Vec<f32>);
+
+    /// Rolls the [`LevelRng`] every frame the jump key is held, like a hazard that reacts to the
+    /// player.
+    fn roll_while_jumping(
+        keys: Res<ButtonInput<KeyCode>>,
+        mut level_rng: ResMut<LevelRng>,
+        mut rolls: ResMut<Rolls>,
+    ) {
+        if keys.pressed(KeyCode::Space) {
+            let roll = level_rng.random_range(0.0..1.0);
+            rolls.0.push(roll);
+        }
+    }
+
+    fn demo_app(config: Config) -> App {
+        let mut app = App::new();
+        app.insert_resource(config)
+            .init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<ButtonInput<MouseButton>>()
+            .add_event::<MouseWheel>()
+            .init_resource::<Time<Real>>()
+            .init_resource::<Time<Virtual>>()
+            .init_resource::<TimeUpdateStrategy>()
+            .init_resource::<CurrentLevel>()
+            .init_resource::<LevelRng>()
+            .init_resource::<Rolls>()
+            .add_systems(Startup, reseed_level_rng)
+            .add_systems(Update, roll_while_jumping);
+        app
+    }
+
+    #[test]
+    fn replayed_runs_match_the_recording() {
+        let mut config = Config::default();
+        config.level_config.run_seed = 7;
+        let mut app = demo_app(config);
+        app.insert_resource(DemoRecording {
+            path: String::new(),
+            demo: Demo {
+                run_seed: Some(7),
+                frames: vec![],
+            },
+        })
+        .add_systems(PreUpdate, record_demo_frame);
+        for jump in [false, true, true, false, true, false, true] {
+            let mut keys = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+            set_pressed(&mut keys, KeyCode::Space, jump);
+            app.update();
+        }
+        let recorded = std::mem::take(&mut app.world_mut().resource_mut::<Rolls>().0);
+        assert_eq!(recorded.len(), 4);
+        let contents = app.world().resource::<DemoRecording>().demo.serialize();
+
+        // played back with a different seed in the config
+        let demo = Demo::parse(&contents).unwrap();
+        let mut config = Config::default();
+        demo.apply_run_seed(&mut config);
+        let frames = demo.frames.len();
+        let mut app = demo_app(config);
+        app.insert_resource(DemoPlayback { demo, index: 0 })
+            .add_systems(PreUpdate, play_demo_frame);
+        for _ in 0..frames {
+            app.update();
+        }
+        assert_eq!(app.world().resource::<Rolls>().0, recorded);
+    }
+
+    #[test]
+    fn live_input_is_ignored_during_playback() {
+        let demo = Demo {
+            run_seed: None,
+            frames: vec![DemoFrame::default(); 2],
+        };
+        let mut app = demo_app(Config::default());
+        app.insert_resource(DemoPlayback { demo, index: 0 })
+            .add_systems(PreUpdate, play_demo_frame);
+
+        let press_live = |app: &mut App| {
+            let mut keys = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+            // recorded, and not recorded
+            keys.press(KeyCode::Space);
+            keys.press(KeyCode::KeyZ);
+            let mut mouse = app.world_mut().resource_mut::<ButtonInput<MouseButton>>();
+            mouse.press(MouseButton::Middle);
+            app.world_mut().send_event(MouseWheel {
+                unit: MouseScrollUnit::Line,
+                x: 0.0,
+                y: 1.0,
+                window: Entity::PLACEHOLDER,
+            });
+        };
+
+        press_live(&mut app);
+        app.update();
+        let keys = app.world().resource::<ButtonInput<KeyCode>>();
+        assert!(!keys.pressed(KeyCode::Space));
+        assert!(!keys.pressed(KeyCode::KeyZ));
+        assert!(!keys.just_pressed(KeyCode::KeyZ));
+        let mouse = app.world().resource::<ButtonInput<MouseButton>>();
+        assert!(!mouse.pressed(MouseButton::Middle));
+        assert!(app.world().resource::<Events<MouseWheel>>().is_empty());
+        assert!(app.world().resource::<Rolls>().0.is_empty());
+
+        // once the demo finishes, the player has control again
+        app.update();
+        press_live(&mut app);
+        app.update();
+        let keys = app.world().resource::<ButtonInput<KeyCode>>();
+        assert!(keys.pressed(KeyCode::KeyZ));
+        assert!(keys.pressed(KeyCode::Space));
+    }
+}
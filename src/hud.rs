@@ -0,0 +1,168 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::{config::Config, level::rating::LevelRun, shared::GameState};
+
+/// Space between the HUD and the edge of the screen, in pixels.
+const HUD_MARGIN: f32 = 12.0;
+
+/// [`Plugin`] for a minimal HUD showing how long the player has been in the current level and how
+/// many times they died there, read from the [`LevelRun`]. The [`LevelRun`] only advances while
+/// the level is being played, so the counters freeze while paused and start over on entering a
+/// new level.
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_hud).add_systems(
+            Update,
+            (
+                update_hud_text.run_if(resource_changed::<LevelRun>),
+                update_hud_visibility.run_if(state_changed::<GameState>),
+            ),
+        );
+    }
+}
+
+/// Which corner of the screen the HUD is drawn in.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HudPosition {
+    #[default]
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl HudPosition {
+    /// A [`Node`] placing the HUD in this corner.
+    pub fn node(&self) -> Node {
+        let margin = Val::Px(HUD_MARGIN);
+        let mut node = Node {
+            position_type: PositionType::Absolute,
+            ..default()
+        };
+        match self {
+            HudPosition::TopLeft | HudPosition::TopRight => node.top = margin,
+            HudPosition::BottomLeft | HudPosition::BottomRight => node.bottom = margin,
+        }
+        match self {
+            HudPosition::TopLeft | HudPosition::BottomLeft => node.left = margin,
+            HudPosition::TopRight | HudPosition::BottomRight => node.right = margin,
+        }
+        node
+    }
+}
+
+#[derive(Component)]
+pub struct HudMarker;
+
+/// Formats the counters shown on the HUD, with the time as minutes, seconds and hundredths.
+pub fn hud_text(time: Duration, deaths: u32) -> String {
+    let hundredths = time.as_millis() / 10;
+    format!(
+        "{}:{:02}.{:02}  deaths: {}",
+        hundredths / 6000,
+        hundredths / 100 % 60,
+        hundredths % 100,
+        deaths
+    )
+}
+
+fn spawn_hud(mut commands: Commands, config: Res<Config>, asset_server: Res<AssetServer>) {
+    if !config.hud_config.enabled {
+        return;
+    }
+    commands.spawn((
+        HudMarker,
+        config.hud_config.position.node(),
+        Text::new(hud_text(Duration::ZERO, 0)),
+        TextFont {
+            font: asset_server.load("fonts/Munro.ttf"),
+            font_size: 24.0,
+            ..default()
+        },
+        // the game starts in the level select
+        Visibility::Hidden,
+    ));
+}
+
+/// [`System`] that keeps the HUD in sync with the [`LevelRun`].
+pub fn update_hud_text(mut q_hud: Query<&mut Text, With<HudMarker>>, level_run: Res<LevelRun>) {
+    for mut text in q_hud.iter_mut() {
+        text.0 = hud_text(level_run.time.elapsed(), level_run.deaths);
+    }
+}
+
+/// [`System`] that hides the HUD in menus like the level select.
+pub fn update_hud_visibility(
+    mut q_hud: Query<&mut Visibility, With<HudMarker>>,
+    state: Res<State<GameState>>,
+) {
+    for mut visibility in q_hud.iter_mut() {
+        *visibility = match state.get() {
+            GameState::Ui => Visibility::Hidden,
+            _ => Visibility::Inherited,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::state::app::StatesPlugin;
+
+    use crate::{level::rating::count_level_run_deaths, shared::ResetLevel};
+
+    use super::*;
+
+    #[test]
+    fn hud_text_formats_time() {
+        assert_eq!(hud_text(Duration::ZERO, 0), "0:00.00  deaths: 0");
+        assert_eq!(
+            hud_text(Duration::from_millis(83_456), 12),
+            "1:23.45  deaths: 12"
+        );
+    }
+
+    #[test]
+    fn corners_anchor_to_their_edges() {
+        let node = HudPosition::BottomRight.node();
+        assert_eq!(node.bottom, Val::Px(HUD_MARGIN));
+        assert_eq!(node.right, Val::Px(HUD_MARGIN));
+        assert_eq!(node.top, Val::Auto);
+        assert_eq!(node.left, Val::Auto);
+    }
+
+    #[test]
+    fn hud_follows_level_run() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default(), StatesPlugin))
+            .insert_state(GameState::Playing)
+            .insert_resource(Config::default())
+            .init_resource::<LevelRun>()
+            .add_event::<ResetLevel>()
+            .add_systems(Update, count_level_run_deaths.before(update_hud_text))
+            .add_plugins(HudPlugin);
+        app.update();
+
+        let text = |app: &mut App| {
+            let mut q_hud = app.world_mut().query_filtered::<&Text, With<HudMarker>>();
+            q_hud.single(app.world()).0.clone()
+        };
+        app.world_mut()
+            .resource_mut::<LevelRun>()
+            .time
+            .tick(Duration::from_secs(3));
+        app.world_mut().send_event(ResetLevel::Respawn);
+        app.update();
+        assert_eq!(text(&mut app), "0:03.00  deaths: 1");
+
+        // entering a new level starts over
+        app.world_mut().send_event(ResetLevel::Switching);
+        app.update();
+        assert_eq!(text(&mut app), "0:00.00  deaths: 0");
+    }
+}
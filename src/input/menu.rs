@@ -0,0 +1,201 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::shared::GameState;
+
+/// [`Plugin`] that turns the direction keys into [`MenuNavigateEvent`]s while a menu is open.
+/// Holding a direction sends it again after [`MenuRepeatConfig::delay_secs`], and then
+/// [`MenuRepeatConfig::rate`] times a second, so long lists can be scrolled through at a steady
+/// pace. Gameplay reads the keys directly and never sees these events.
+pub struct MenuInputPlugin;
+
+impl Plugin for MenuInputPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<MenuNavigateEvent>()
+            .init_resource::<MenuRepeatConfig>()
+            .init_resource::<MenuRepeat>()
+            .add_systems(Update, send_menu_navigation.run_if(in_state(GameState::Ui)))
+            .add_systems(OnExit(GameState::Ui), reset_menu_repeat);
+    }
+}
+
+/// How held directions repeat in menus, see the `menu_repeat_config` section of
+/// `Lightborne.toml`.
+#[derive(Resource, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct MenuRepeatConfig {
+    /// How long a direction has to be held before it starts repeating, in seconds
+    pub delay_secs: f32,
+    /// How many times a second a held direction repeats after the delay, 0 to never repeat
+    pub rate: f32,
+}
+
+impl Default for MenuRepeatConfig {
+    fn default() -> Self {
+        MenuRepeatConfig {
+            delay_secs: 0.4,
+            rate: 10.0,
+        }
+    }
+}
+
+impl MenuRepeatConfig {
+    /// How many times a direction has repeated after being held for `held_secs`, not counting
+    /// the first press.
+    pub fn repeats(&self, held_secs: f32) -> u32 {
+        if self.rate <= 0.0 || held_secs < self.delay_secs {
+            return 0;
+        }
+        1 + ((held_secs - self.delay_secs) * self.rate) as u32
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MenuDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl MenuDirection {
+    const ALL: [MenuDirection; 4] = [
+        MenuDirection::Up,
+        MenuDirection::Down,
+        MenuDirection::Left,
+        MenuDirection::Right,
+    ];
+
+    fn keys(self) -> [KeyCode; 2] {
+        match self {
+            MenuDirection::Up => [KeyCode::ArrowUp, KeyCode::KeyW],
+            MenuDirection::Down => [KeyCode::ArrowDown, KeyCode::KeyS],
+            MenuDirection::Left => [KeyCode::ArrowLeft, KeyCode::KeyA],
+            MenuDirection::Right => [KeyCode::ArrowRight, KeyCode::KeyD],
+        }
+    }
+}
+
+/// [`Event`] sent when a direction is pressed in a menu, and again while it is held.
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MenuNavigateEvent(pub MenuDirection);
+
+/// [`Resource`] tracking the direction being held in a menu, and for how long.
+#[derive(Resource, Default, Debug)]
+pub struct MenuRepeat(Option<(MenuDirection, f32)>);
+
+/// [`System`] that sends a [`MenuNavigateEvent`] when a direction is pressed, and repeats it
+/// while the direction is held. Only directions pressed while the menu is open repeat, so keys
+/// still held from gameplay don't scroll the menu.
+pub fn send_menu_navigation(
+    keys: Res<ButtonInput<KeyCode>>,
+    config: Res<MenuRepeatConfig>,
+    mut repeat: ResMut<MenuRepeat>,
+    mut ev_navigate: EventWriter<MenuNavigateEvent>,
+    time: Res<Time>,
+) {
+    // the latest direction pressed takes over from the one held before
+    if let Some(direction) = MenuDirection::ALL
+        .into_iter()
+        .find(|direction| keys.any_just_pressed(direction.keys()))
+    {
+        repeat.0 = Some((direction, 0.0));
+        ev_navigate.send(MenuNavigateEvent(direction));
+        return;
+    }
+
+    let Some((direction, held_secs)) = &mut repeat.0 else {
+        return;
+    };
+    if !keys.any_pressed(direction.keys()) {
+        repeat.0 = None;
+        return;
+    }
+    let before = config.repeats(*held_secs);
+    *held_secs += time.delta_secs();
+    for _ in before..config.repeats(*held_secs) {
+        ev_navigate.send(MenuNavigateEvent(*direction));
+    }
+}
+
+/// [`System`] that forgets the held direction when the menus are closed.
+pub fn reset_menu_repeat(mut repeat: ResMut<MenuRepeat>) {
+    repeat.0 = None;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::state::app::StatesPlugin;
+
+    use super::*;
+
+    fn app(state: GameState) -> App {
+        let mut app = App::new();
+        app.add_plugins(StatesPlugin)
+            .insert_state(state)
+            .init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<Time>()
+            .insert_resource(MenuRepeatConfig {
+                delay_secs: 0.5,
+                rate: 4.0,
+            })
+            .add_plugins(MenuInputPlugin);
+        app
+    }
+
+    /// Holds `key` for `secs` more seconds and returns how many times the menu moved.
+    fn hold(app: &mut App, key: KeyCode, secs: f32) -> usize {
+        app.world_mut()
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .press(key);
+        app.world_mut()
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_secs_f32(secs));
+        app.update();
+        app.world_mut()
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .clear();
+        app.world_mut()
+            .resource_mut::<Events<MenuNavigateEvent>>()
+            .drain()
+            .filter(|ev| *ev == MenuNavigateEvent(MenuDirection::Down))
+            .count()
+    }
+
+    #[test]
+    fn held_directions_repeat_after_delay_at_rate() {
+        let mut app = app(GameState::Ui);
+        assert_eq!(hold(&mut app, KeyCode::ArrowDown, 0.0), 1);
+        assert_eq!(hold(&mut app, KeyCode::ArrowDown, 0.4), 0);
+        // the delay ends at 0.5s
+        assert_eq!(hold(&mut app, KeyCode::ArrowDown, 0.15), 1);
+        assert_eq!(hold(&mut app, KeyCode::ArrowDown, 0.3), 1);
+        // then repeats every 0.25s, more than once if a frame is long
+        assert_eq!(hold(&mut app, KeyCode::ArrowDown, 0.55), 2);
+
+        app.world_mut()
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .release(KeyCode::ArrowDown);
+        app.update();
+        assert!(app.world().resource::<MenuRepeat>().0.is_none());
+        // pressing again starts over from the delay
+        assert_eq!(hold(&mut app, KeyCode::ArrowDown, 0.0), 1);
+        assert_eq!(hold(&mut app, KeyCode::ArrowDown, 0.4), 0);
+    }
+
+    #[test]
+    fn gameplay_keys_do_not_repeat() {
+        let mut app = app(GameState::Playing);
+        assert_eq!(hold(&mut app, KeyCode::KeyS, 0.0), 0);
+        assert_eq!(hold(&mut app, KeyCode::KeyS, 1.0), 0);
+
+        // a key held since gameplay doesn't scroll the menu once it opens
+        app.world_mut()
+            .resource_mut::<NextState<GameState>>()
+            .set(GameState::Ui);
+        assert_eq!(hold(&mut app, KeyCode::KeyS, 1.0), 0);
+        assert_eq!(hold(&mut app, KeyCode::KeyS, 1.0), 0);
+    }
+}
@@ -1,7 +1,9 @@
 use bevy::prelude::*;
 use bevy::window::PrimaryWindow;
 
-use crate::camera::MainCamera;
+use crate::camera::{letterbox::Letterbox, MainCamera};
+
+pub mod menu;
 
 /// [`Component`] that holds the position of the cursor, in world coordinates. You should query
 /// for this [`Component`] if you need the cursor position to do something. Note that if your
@@ -22,6 +24,7 @@ pub fn update_cursor_world_coords(
     mut q_coords: Query<&mut CursorWorldCoords>,
     q_window: Query<&Window, With<PrimaryWindow>>,
     q_camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    letterbox: Res<Letterbox>,
 ) {
     let Ok((camera, camera_transform)) = q_camera.get_single() else {
         return;
@@ -35,6 +38,17 @@ pub fn update_cursor_world_coords(
     let Some(cursor_pos) = window.cursor_position() else {
         return;
     };
+    // the main camera renders to a scaled down image instead of the window, see
+    // [`RenderScale`](crate::camera::resolution::RenderScale)
+    let Some(viewport_size) = camera.logical_viewport_size() else {
+        return;
+    };
+    // the scene only covers the active region of the letterbox
+    let active_rect = letterbox.active_rect(window.size());
+    if !active_rect.contains(cursor_pos) {
+        return;
+    }
+    let cursor_pos = (cursor_pos - active_rect.min) * viewport_size / active_rect.size();
     let Ok(cursor_ray) = camera.viewport_to_world(camera_transform, cursor_pos) else {
         return;
     };
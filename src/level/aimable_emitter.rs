@@ -0,0 +1,487 @@
+use std::{collections::HashMap, f32::consts::TAU};
+
+use bevy::prelude::*;
+use bevy_ecs_ldtk::{prelude::*, EntityIid};
+
+use crate::{
+    light::{
+        segments::{simulate_light_sources, PrevLightBeamPlayback},
+        LightBeamDepth, LightBeamSource, LightColor,
+    },
+    lighting::{LightToggle, TimeOfDayWindow},
+    player::{not_input_locked, PlayerMarker},
+};
+
+use super::{restart::RestartLevelEvent, CurrentLevel, LevelSystems};
+
+/// How close the player needs to be to an [`AimableEmitter`] to turn it.
+const AIM_REACH: f32 = 16.0;
+
+/// How fast [`AimableEmitter`]s without angle snapping turn, in radians per second.
+const AIM_SPEED: f32 = 1.5;
+
+/// How far the [`LightToggle`] of an [`AimableEmitter`] needs to be warmed up before it shines a
+/// beam.
+const EMITTER_WARMUP_THRESHOLD: f32 = 0.5;
+
+/// [`Plugin`] for light emitters that the player can turn to aim their beams.
+pub struct AimableEmitterPlugin;
+
+impl Plugin for AimableEmitterPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity::<AimableEmitterBundle>("AimableEmitter")
+            .init_resource::<AimedEmitterAngles>()
+            .add_systems(
+                PreUpdate,
+                init_aimable_emitters.in_set(LevelSystems::Processing),
+            )
+            .add_systems(
+                Update,
+                (
+                    forget_aimed_angles_on_restart.run_if(on_event::<RestartLevelEvent>),
+                    aim_emitters
+                        .run_if(not_input_locked)
+                        .in_set(LevelSystems::Simulation),
+                ),
+            )
+            .add_systems(
+                FixedUpdate,
+                emit_aimable_beams
+                    .before(simulate_light_sources)
+                    .in_set(LevelSystems::Simulation),
+            );
+    }
+}
+
+/// [`Component`] for emitters that shine a beam of `color` at `angle` (in radians,
+/// counterclockwise from the right). The player turns the emitter by standing next to it and
+/// holding Q or E.
+///
+/// Emitters with a `snap` angle turn a full step per key press, and always point at a multiple of
+/// the step, so a step of 45 degrees keeps the beam lined up with the level's walls and slopes.
+/// Emitters without one turn smoothly for as long as the key is held.
+///
+/// Emitters with a [`LightToggle`], like the ones on a
+/// [`LightSchedule`](crate::lighting::LightSchedule), only shine while their light is on and has
+/// warmed up past [`EMITTER_WARMUP_THRESHOLD`]. Emitters with the optional `active_from` and
+/// `active_until` fields in Ldtk get a [`TimeOfDayWindow`], so they only shine during part of the
+/// day.
+#[derive(Component, Debug)]
+pub struct AimableEmitter {
+    pub color: LightColor,
+    pub angle: f32,
+    pub snap: Option<f32>,
+    /// The [`penetration`](crate::light::LightBeamSource::penetration) of the emitter's beam,
+    /// read from the optional `penetration` field in Ldtk
+    pub penetration: f32,
+    /// The [`depth`](crate::light::LightBeamSource::depth) of the emitter's beam, read from the
+    /// optional `depth` field in Ldtk
+    pub depth: LightBeamDepth,
+    /// The beam currently shining out of the emitter
+    beam: Option<Entity>,
+}
+
+impl AimableEmitter {
+    /// The direction of the emitter's beam.
+    pub fn dir(&self) -> Vec2 {
+        Vec2::from_angle(self.angle)
+    }
+}
+
+impl From<&EntityInstance> for AimableEmitter {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        let color: LightColor = entity_instance
+            .get_enum_field("color")
+            .expect("color needs to be an enum field on all aimable emitters")
+            .into();
+        let angle = *entity_instance
+            .get_float_field("angle")
+            .expect("angle needs to be a float field on all aimable emitters");
+        let snap = *entity_instance
+            .get_float_field("snap_angle")
+            .expect("snap_angle needs to be a float field on all aimable emitters");
+        let penetration = entity_instance
+            .get_float_field("penetration")
+            .copied()
+            .unwrap_or(0.0);
+
+        let snap = (snap > 0.0).then(|| snap.to_radians());
+        let angle = angle.to_radians();
+        AimableEmitter {
+            color,
+            angle: snap.map_or(angle, |snap| snap_angle(angle, snap)),
+            snap,
+            penetration,
+            depth: LightBeamDepth::from(entity_instance),
+            beam: None,
+        }
+    }
+}
+
+/// [`Bundle`] that includes all the [`Component`]s needed for an [`AimableEmitter`] to function
+/// properly.
+#[derive(Bundle, LdtkEntity)]
+pub struct AimableEmitterBundle {
+    #[from_entity_instance]
+    emitter: AimableEmitter,
+    #[with(aimable_emitter_sprite)]
+    sprite: Sprite,
+}
+
+pub fn aimable_emitter_sprite(entity_instance: &EntityInstance) -> Sprite {
+    let emitter = AimableEmitter::from(entity_instance);
+    Sprite::from_color(
+        emitter.color.indicator_color(),
+        Vec2::new(entity_instance.width as f32, entity_instance.height as f32),
+    )
+}
+
+/// [`Resource`] that remembers the angle of every [`AimableEmitter`] the player has turned,
+/// grouped by level. The angles outlive the emitters themselves, so an emitter keeps its angle
+/// when the player dies or leaves the level and comes back. Restarting a level forgets its
+/// angles.
+#[derive(Resource, Default, Debug)]
+pub struct AimedEmitterAngles(pub HashMap<LevelIid, HashMap<EntityIid, f32>>);
+
+impl AimedEmitterAngles {
+    pub fn get(&self, emitter: &EntityIid) -> Option<f32> {
+        self.0
+            .values()
+            .find_map(|angles| angles.get(emitter).copied())
+    }
+}
+
+/// Rounds `angle` to the nearest multiple of `snap`.
+pub fn snap_angle(angle: f32, snap: f32) -> f32 {
+    ((angle / snap).round() * snap).rem_euclid(TAU)
+}
+
+/// The angle of an [`AimableEmitter`] after turning it in the direction of `turn` (1 for
+/// counterclockwise, -1 for clockwise). `pressed` is whether the turn key was just pressed, and
+/// `delta_secs` is how long it has been held for this frame.
+pub fn aim_angle(angle: f32, turn: f32, snap: Option<f32>, pressed: bool, delta_secs: f32) -> f32 {
+    match snap {
+        Some(snap) if pressed => snap_angle(angle + turn * snap, snap),
+        Some(_) => angle,
+        None => (angle + turn * AIM_SPEED * delta_secs).rem_euclid(TAU),
+    }
+}
+
+/// The [`TimeOfDayWindow`] of an [`AimableEmitter`], if it has one.
+pub fn emitter_time_window(entity_instance: &EntityInstance) -> Option<TimeOfDayWindow> {
+    let from = entity_instance.get_float_field("active_from").ok()?;
+    let until = entity_instance.get_float_field("active_until").ok()?;
+    Some(TimeOfDayWindow {
+        from: *from,
+        until: *until,
+    })
+}
+
+/// [`System`] that restores the angle of [`AimableEmitter`]s that were turned before their level
+/// was last spawned, and gives emitters that only shine during part of the day their
+/// [`TimeOfDayWindow`].
+pub fn init_aimable_emitters(
+    mut commands: Commands,
+    mut q_emitters: Query<
+        (
+            Entity,
+            &mut AimableEmitter,
+            &EntityIid,
+            Option<&EntityInstance>,
+        ),
+        Added<AimableEmitter>,
+    >,
+    angles: Res<AimedEmitterAngles>,
+) {
+    for (entity, mut emitter, iid, instance) in q_emitters.iter_mut() {
+        if let Some(angle) = angles.get(iid) {
+            emitter.angle = angle;
+        }
+        if let Some(window) = instance.and_then(emitter_time_window) {
+            commands.entity(entity).insert(window);
+        }
+    }
+}
+
+/// [`System`] that forgets the angles of the current level's [`AimableEmitter`]s when it is
+/// restarted. Ldtk respawns the emitters with the angles they were placed at.
+pub fn forget_aimed_angles_on_restart(
+    mut angles: ResMut<AimedEmitterAngles>,
+    current_level: Res<CurrentLevel>,
+) {
+    angles.0.remove(&current_level.level_iid);
+}
+
+/// [`System`] that turns the [`AimableEmitter`] closest to the player while Q or E is held.
+pub fn aim_emitters(
+    q_player: Query<&GlobalTransform, With<PlayerMarker>>,
+    mut q_emitters: Query<(&mut AimableEmitter, &GlobalTransform, &EntityIid)>,
+    keys: Res<ButtonInput<KeyCode>>,
+    time: Res<Time>,
+    current_level: Res<CurrentLevel>,
+    mut angles: ResMut<AimedEmitterAngles>,
+) {
+    let turn = match (keys.pressed(KeyCode::KeyQ), keys.pressed(KeyCode::KeyE)) {
+        (true, false) => 1.0,
+        (false, true) => -1.0,
+        _ => return,
+    };
+    let pressed = keys.any_just_pressed([KeyCode::KeyQ, KeyCode::KeyE]);
+
+    let Ok(player_transform) = q_player.get_single() else {
+        return;
+    };
+    let player_pos = player_transform.translation().xy();
+
+    let Some((mut emitter, _, iid)) = q_emitters
+        .iter_mut()
+        .map(|(emitter, transform, iid)| {
+            (
+                emitter,
+                transform.translation().xy().distance(player_pos),
+                iid,
+            )
+        })
+        .filter(|(_, dist, _)| *dist <= AIM_REACH)
+        .min_by(|(_, a, _), (_, b, _)| a.total_cmp(b))
+    else {
+        return;
+    };
+
+    let angle = aim_angle(
+        emitter.angle,
+        turn,
+        emitter.snap,
+        pressed,
+        time.delta_secs(),
+    );
+    if angle == emitter.angle {
+        return;
+    }
+    emitter.angle = angle;
+    angles
+        .0
+        .entry(current_level.level_iid.clone())
+        .or_default()
+        .insert(iid.clone(), angle);
+}
+
+/// [`System`] that keeps a [`LightBeamSource`] shining out of every [`AimableEmitter`] in the
+/// direction it is aimed, and despawns the beams of emitters whose light is off. Beams are
+/// despawned whenever the level resets, so they are spawned again here.
+pub fn emit_aimable_beams(
+    mut commands: Commands,
+    mut q_emitters: Query<(
+        &mut AimableEmitter,
+        &GlobalTransform,
+        &mut Transform,
+        &Sprite,
+        Option<&LightToggle>,
+    )>,
+    mut q_sources: Query<&mut LightBeamSource>,
+) {
+    for (mut emitter, global_transform, mut transform, sprite, toggle) in q_emitters.iter_mut() {
+        transform.rotation = Quat::from_rotation_z(emitter.angle);
+
+        let shining = toggle.is_none_or(|toggle| toggle.warmup() >= EMITTER_WARMUP_THRESHOLD);
+        if !shining {
+            if let Some(beam) = emitter.beam.take().filter(|beam| q_sources.contains(*beam)) {
+                commands.entity(beam).despawn_recursive();
+            }
+            continue;
+        }
+
+        let dir = emitter.dir();
+        let half_size = sprite.custom_size.unwrap_or_default() / 2.0;
+        // start the beam just outside of the emitter, so it doesn't hit the emitter itself
+        let start_pos = global_transform.translation().xy() + dir * (half_size.max_element() + 1.0);
+
+        if let Some(mut source) = emitter.beam.and_then(|beam| q_sources.get_mut(beam).ok()) {
+            source.start_pos = start_pos;
+            source.start_dir = dir;
+            continue;
+        }
+
+        emitter.beam = Some(
+            commands
+                .spawn((
+                    LightBeamSource {
+                        start_pos,
+                        start_dir: dir,
+                        time_traveled: 0.0,
+                        color: emitter.color,
+                        width: 0.0,
+                        intensity: 1.0,
+                        penetration: emitter.penetration,
+                        depth: emitter.depth,
+                    },
+                    PrevLightBeamPlayback::default(),
+                ))
+                .id(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        f32::consts::{FRAC_PI_2, FRAC_PI_4},
+        time::Duration,
+    };
+
+    use crate::{
+        lighting::{LightSchedule, LightTogglePlugin},
+        shared::ResetLevel,
+    };
+
+    use super::*;
+
+    #[test]
+    fn snapped_emitters_turn_a_step_per_press() {
+        let angle = aim_angle(0.0, 1.0, Some(FRAC_PI_4), true, 0.5);
+        assert!((angle - FRAC_PI_4).abs() < 1e-5);
+        // holding the key doesn't keep turning
+        assert_eq!(aim_angle(angle, 1.0, Some(FRAC_PI_4), false, 0.5), angle);
+        // an emitter placed slightly off of a step snaps onto one
+        let angle = aim_angle(0.1, -1.0, Some(FRAC_PI_2), true, 0.0);
+        assert!((angle - 3.0 * FRAC_PI_2).abs() < 1e-5);
+    }
+
+    #[test]
+    fn free_emitters_turn_while_held() {
+        let angle = aim_angle(0.0, 1.0, None, false, 0.5);
+        assert!((angle - AIM_SPEED * 0.5).abs() < 1e-5);
+    }
+
+    fn emitter_app() -> (App, Entity) {
+        let mut app = App::new();
+        app.init_resource::<AimedEmitterAngles>()
+            .insert_resource(CurrentLevel {
+                level_iid: LevelIid::new("level"),
+                ..default()
+            })
+            .init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<Time>()
+            .add_event::<ResetLevel>()
+            .add_systems(
+                Update,
+                (init_aimable_emitters, aim_emitters, emit_aimable_beams).chain(),
+            );
+        app.world_mut()
+            .spawn((PlayerMarker, GlobalTransform::default()));
+        let emitter = app.world_mut().spawn(emitter_components()).id();
+        (app, emitter)
+    }
+
+    fn emitter_components() -> impl Bundle {
+        (
+            AimableEmitter {
+                color: LightColor::Green,
+                angle: 0.0,
+                snap: Some(FRAC_PI_2),
+                penetration: 0.0,
+                depth: LightBeamDepth::Background,
+                beam: None,
+            },
+            EntityIid::new("emitter"),
+            Sprite::from_color(Color::WHITE, Vec2::splat(8.0)),
+            GlobalTransform::default(),
+            Transform::default(),
+        )
+    }
+
+    fn beam_dir(app: &mut App) -> Vec2 {
+        let mut q_sources = app.world_mut().query::<&LightBeamSource>();
+        let sources = q_sources.iter(app.world()).collect::<Vec<_>>();
+        assert_eq!(sources.len(), 1);
+        sources[0].start_dir
+    }
+
+    #[test]
+    fn aiming_turns_the_beam() {
+        let (mut app, _) = emitter_app();
+        app.update();
+        assert!(beam_dir(&mut app).abs_diff_eq(Vec2::X, 1e-5));
+
+        app.world_mut()
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .press(KeyCode::KeyQ);
+        app.update();
+        assert!(beam_dir(&mut app).abs_diff_eq(Vec2::Y, 1e-5));
+    }
+
+    fn num_beams(app: &mut App) -> usize {
+        let mut q_sources = app.world_mut().query::<&LightBeamSource>();
+        q_sources.iter(app.world()).count()
+    }
+
+    fn advance(app: &mut App, secs: f32) {
+        app.world_mut()
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_secs_f32(secs));
+        app.update();
+    }
+
+    #[test]
+    fn beam_follows_light_schedule() {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .add_plugins(LightTogglePlugin)
+            .add_systems(Update, emit_aimable_beams);
+        app.world_mut().spawn((
+            emitter_components(),
+            LightSchedule::new(1.0, 1.0, 0.0),
+            LightToggle {
+                warmup_secs: 0.2,
+                ..default()
+            },
+        ));
+
+        // the light turns on, but the beam waits for it to warm up
+        advance(&mut app, 0.0);
+        advance(&mut app, 0.05);
+        assert_eq!(num_beams(&mut app), 0);
+        advance(&mut app, 0.15);
+        assert_eq!(num_beams(&mut app), 0);
+        advance(&mut app, 0.5);
+        assert_eq!(num_beams(&mut app), 1);
+
+        // the light turns off after a second, and the beam with it
+        advance(&mut app, 0.5);
+        advance(&mut app, 0.0);
+        assert_eq!(num_beams(&mut app), 0);
+        advance(&mut app, 0.9);
+        assert_eq!(num_beams(&mut app), 0);
+
+        // and back on a second later
+        advance(&mut app, 0.3);
+        advance(&mut app, 0.3);
+        assert_eq!(num_beams(&mut app), 1);
+    }
+
+    #[test]
+    fn aimed_angle_is_restored_on_respawn() {
+        let (mut app, emitter) = emitter_app();
+        app.world_mut()
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .press(KeyCode::KeyQ);
+        app.update();
+
+        // dying despawns every beam, and Ldtk may respawn the emitter itself
+        let mut q_sources = app
+            .world_mut()
+            .query_filtered::<Entity, With<LightBeamSource>>();
+        let sources = q_sources.iter(app.world()).collect::<Vec<_>>();
+        for source in sources {
+            app.world_mut().despawn(source);
+        }
+        app.world_mut().despawn(emitter);
+        app.world_mut().spawn(emitter_components());
+        app.world_mut()
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .release_all();
+        app.update();
+
+        assert!(beam_dir(&mut app).abs_diff_eq(Vec2::Y, 1e-5));
+    }
+}
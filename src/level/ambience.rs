@@ -0,0 +1,112 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::{ldtk::Level, prelude::*};
+
+use crate::shared::ResetLevel;
+
+use super::{CurrentLevel, LevelSystems};
+
+/// [`Plugin`] that sends a [`LevelAudioEvent`] whenever a level is loaded.
+pub struct LevelAmbiencePlugin;
+
+impl Plugin for LevelAmbiencePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<LevelAudioEvent>()
+            .add_systems(Update, send_level_audio_events.in_set(LevelSystems::Reset));
+    }
+}
+
+/// [`Event`] sent when a level is entered or restarted, with the id of the music or ambience it
+/// wants from its optional `Ambience` field in Ldtk. This crate doesn't play anything for it, so
+/// that an audio layer can crossfade between tracks however it likes.
+#[derive(Event, Clone, Debug, PartialEq, Eq)]
+pub struct LevelAudioEvent {
+    pub level_iid: LevelIid,
+    pub ambience_id: Option<String>,
+    /// Whether the ambience is the same as in the last [`LevelAudioEvent`], like when the level
+    /// is restarted, so the track that is playing can keep going instead of starting over
+    pub same_as_previous: bool,
+}
+
+/// The ambience id of a level, read from its optional `Ambience` field. Empty ids are treated as
+/// no ambience.
+pub fn level_ambience(level: &Level) -> Option<String> {
+    level
+        .get_string_field("Ambience")
+        .ok()?
+        .clone()
+        .filter(|ambience_id| !ambience_id.is_empty())
+}
+
+/// [`System`] that sends a [`LevelAudioEvent`] for the [`CurrentLevel`] whenever it is loaded.
+pub fn send_level_audio_events(
+    mut ev_reset_level: EventReader<ResetLevel>,
+    current_level: Res<CurrentLevel>,
+    mut ev_level_audio: EventWriter<LevelAudioEvent>,
+    mut previous: Local<Option<Option<String>>>,
+) {
+    if !ev_reset_level.read().any(|ev| *ev == ResetLevel::Switching) {
+        return;
+    }
+    let ambience_id = current_level.ambience_id.clone();
+    let same_as_previous = previous.as_ref() == Some(&ambience_id);
+    *previous = Some(ambience_id.clone());
+    ev_level_audio.send(LevelAudioEvent {
+        level_iid: current_level.level_iid.clone(),
+        ambience_id,
+        same_as_previous,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_level(app: &mut App, level_iid: &str, ambience_id: Option<&str>) -> LevelAudioEvent {
+        *app.world_mut().resource_mut::<CurrentLevel>() = CurrentLevel {
+            level_iid: LevelIid::new(level_iid),
+            ambience_id: ambience_id.map(String::from),
+            ..default()
+        };
+        app.world_mut().send_event(ResetLevel::Switching);
+        app.update();
+        let sent = app
+            .world_mut()
+            .resource_mut::<Events<LevelAudioEvent>>()
+            .drain()
+            .collect::<Vec<_>>();
+        assert_eq!(sent.len(), 1);
+        sent.into_iter().next().unwrap()
+    }
+
+    #[test]
+    fn level_audio_event_fires_on_transition() {
+        let mut app = App::new();
+        app.init_resource::<CurrentLevel>()
+            .add_event::<ResetLevel>()
+            .add_event::<LevelAudioEvent>()
+            .add_systems(Update, send_level_audio_events);
+
+        assert_eq!(
+            load_level(&mut app, "cave", Some("drips")),
+            LevelAudioEvent {
+                level_iid: LevelIid::new("cave"),
+                ambience_id: Some("drips".into()),
+                same_as_previous: false,
+            }
+        );
+        // restarting the level keeps the track going
+        assert!(load_level(&mut app, "cave", Some("drips")).same_as_previous);
+
+        let event = load_level(&mut app, "forest", Some("birds"));
+        assert_eq!(event.level_iid, LevelIid::new("forest"));
+        assert_eq!(event.ambience_id.as_deref(), Some("birds"));
+        assert!(!event.same_as_previous);
+        assert!(load_level(&mut app, "glade", Some("birds")).same_as_previous);
+        assert!(!load_level(&mut app, "void", None).same_as_previous);
+
+        // respawning doesn't reload the level
+        app.world_mut().send_event(ResetLevel::Respawn);
+        app.update();
+        assert!(app.world().resource::<Events<LevelAudioEvent>>().is_empty());
+    }
+}
@@ -0,0 +1,246 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    light::{
+        segments::{simulate_light_sources, LightSegment, PrevLightBeamPlayback},
+        LightBeamDepth, LightBeamSource, LightColor,
+    },
+    lighting::{light_line_of_sight, LineLight2d},
+};
+
+use super::LevelSystems;
+
+/// [`Plugin`] for gaps in walls that focus point lights shining through them into light beams.
+pub struct AperturePlugin;
+
+impl Plugin for AperturePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity::<ApertureBundle>("Aperture")
+            .add_systems(
+                FixedUpdate,
+                update_aperture_beams
+                    .before(simulate_light_sources)
+                    .in_set(LevelSystems::Simulation),
+            );
+    }
+}
+
+/// [`Component`] for narrow gaps, placed in Ldtk between occluding walls. When a point light is
+/// close enough to reach the aperture, and nothing that stops beams is in the way, a
+/// [`LightBeamSource`] shines out of the far side, pointing away from the light and colored with
+/// the [`LightColor`] closest to the light's color. Moving the light re-aims the beam. Like every
+/// other [`LightBeamSource`], the beam is drawn with its own segments from the
+/// [`LightSegmentCache`](crate::light::segments::LightSegmentCache).
+#[derive(Component, Debug)]
+pub struct Aperture {
+    pub half_size: Vec2,
+    /// The [`depth`](crate::light::LightBeamSource::depth) of the aperture's beams, read from the
+    /// optional `depth` field in Ldtk
+    pub depth: LightBeamDepth,
+    /// The beam currently shining out of the aperture
+    beam: Option<Entity>,
+}
+
+impl From<&EntityInstance> for Aperture {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        Aperture {
+            half_size: Vec2::new(
+                entity_instance.width as f32 / 2.0,
+                entity_instance.height as f32 / 2.0,
+            ),
+            depth: LightBeamDepth::from(entity_instance),
+            beam: None,
+        }
+    }
+}
+
+/// [`Bundle`] that includes all the [`Component`]s needed for an [`Aperture`] to function
+/// properly.
+#[derive(Bundle, LdtkEntity)]
+pub struct ApertureBundle {
+    #[from_entity_instance]
+    aperture: Aperture,
+}
+
+/// The beam focused by an [`Aperture`] at `aperture_pos`, given the position of every point light
+/// in the level. The closest light whose radius reaches the aperture is used. Returns the
+/// direction and color of the beam.
+pub fn aperture_beam<'a>(
+    aperture_pos: Vec2,
+    lights: impl IntoIterator<Item = (Vec2, &'a LineLight2d)>,
+) -> Option<(Vec2, LightColor)> {
+    let (light_pos, light) = lights
+        .into_iter()
+        .filter(|(pos, light)| {
+            light.half_length == 0.0
+                && light.color.w > 0.0
+                && pos.distance(aperture_pos) <= light.radius
+        })
+        .min_by(|(a, _), (b, _)| {
+            a.distance_squared(aperture_pos)
+                .total_cmp(&b.distance_squared(aperture_pos))
+        })?;
+
+    let dir = (aperture_pos - light_pos).try_normalize()?;
+    Some((dir, LightColor::closest_to(light.color.truncate())))
+}
+
+/// [`System`] that spawns, aims and despawns the beams of [`Aperture`]s based on the point lights
+/// around them. Lights behind walls, or behind the
+/// [`SpectralOccluder`](crate::light::spectral::SpectralOccluder)s of their color, don't reach
+/// the aperture.
+#[allow(clippy::type_complexity)]
+pub fn update_aperture_beams(
+    mut commands: Commands,
+    q_rapier: Query<&RapierContext>,
+    mut q_apertures: Query<(&mut Aperture, &GlobalTransform)>,
+    q_lights: Query<
+        (&GlobalTransform, &LineLight2d),
+        (Without<LightSegment>, Without<LightBeamSource>),
+    >,
+    mut q_sources: Query<&mut LightBeamSource>,
+) {
+    let Ok(rapier_context) = q_rapier.get_single() else {
+        return;
+    };
+
+    for (mut aperture, transform) in q_apertures.iter_mut() {
+        let aperture_pos = transform.translation().xy();
+        let beam = aperture_beam(
+            aperture_pos,
+            q_lights
+                .iter()
+                .map(|(transform, light)| (transform.translation().xy(), light))
+                .filter(|(light_pos, light)| {
+                    let color = LightColor::closest_to(light.color.truncate());
+                    light_line_of_sight(rapier_context, color, *light_pos, aperture_pos)
+                }),
+        );
+
+        // beams are despawned when the level resets, or when they change color
+        let existing = aperture.beam.filter(|entity| q_sources.contains(*entity));
+        let Some((dir, color)) = beam else {
+            if let Some(entity) = existing {
+                commands.entity(entity).despawn_recursive();
+            }
+            aperture.beam = None;
+            continue;
+        };
+
+        // start the beam just past the aperture, so it doesn't hit the walls around it
+        let start_pos = aperture_pos + dir * (aperture.half_size.max_element() + 1.0);
+        if let Some(entity) = existing {
+            let mut source = q_sources.get_mut(entity).unwrap();
+            if source.color == color {
+                source.start_pos = start_pos;
+                source.start_dir = dir;
+                continue;
+            }
+            commands.entity(entity).despawn_recursive();
+        }
+
+        aperture.beam = Some(
+            commands
+                .spawn((
+                    LightBeamSource {
+                        start_pos,
+                        start_dir: dir,
+                        time_traveled: 0.0,
+                        color,
+                        width: 0.0,
+                        intensity: 1.0,
+                        penetration: 0.0,
+                        depth: aperture.depth,
+                    },
+                    PrevLightBeamPlayback::default(),
+                ))
+                .id(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_rapier2d::rapier::{geometry::ColliderBuilder, na::vector};
+
+    use super::*;
+
+    #[test]
+    fn light_behind_aperture_aims_beam() {
+        let light = LineLight2d::point(LightColor::Green.lighting_color().extend(1.0), 40.0, 0.0);
+
+        let (dir, color) = aperture_beam(Vec2::ZERO, [(vec2(-20.0, 0.0), &light)]).unwrap();
+        assert!(dir.abs_diff_eq(Vec2::X, 1e-5));
+        assert_eq!(color, LightColor::Green);
+
+        // moving the light changes the angle of the beam
+        let (dir, _) = aperture_beam(Vec2::ZERO, [(vec2(-20.0, -20.0), &light)]).unwrap();
+        assert!(dir.abs_diff_eq(Vec2::ONE.normalize(), 1e-5));
+
+        // out of the light's reach
+        assert!(aperture_beam(Vec2::ZERO, [(vec2(-50.0, 0.0), &light)]).is_none());
+    }
+
+    #[test]
+    fn closest_light_is_focused() {
+        let purple = LineLight2d::point(LightColor::Purple.lighting_color().extend(1.0), 40.0, 0.0);
+        let blue = LineLight2d::point(LightColor::Blue.lighting_color().extend(1.0), 40.0, 0.0);
+
+        let (dir, color) = aperture_beam(
+            Vec2::ZERO,
+            [(vec2(0.0, 30.0), &purple), (vec2(0.0, -10.0), &blue)],
+        )
+        .unwrap();
+        assert!(dir.abs_diff_eq(Vec2::Y, 1e-5));
+        assert_eq!(color, LightColor::Blue);
+    }
+
+    #[test]
+    fn walls_hide_lights_from_apertures() {
+        let mut app = App::new();
+        app.add_systems(Update, update_aperture_beams);
+
+        // a 10x40 wall centered at (50, 0), between the light and the first aperture
+        let wall = app.world_mut().spawn_empty().id();
+        let mut rapier_context = RapierContext::default();
+        rapier_context.colliders.insert(
+            ColliderBuilder::cuboid(5.0, 20.0)
+                .translation(vector![50.0, 0.0])
+                .user_data(wall.to_bits() as u128)
+                .build(),
+        );
+        rapier_context
+            .query_pipeline
+            .update(&rapier_context.colliders);
+        app.world_mut().spawn(rapier_context);
+
+        app.world_mut().spawn((
+            LineLight2d::point(LightColor::Green.lighting_color().extend(1.0), 100.0, 0.0),
+            GlobalTransform::default(),
+        ));
+        let mut spawn_aperture = |pos: Vec2| {
+            app.world_mut()
+                .spawn((
+                    Aperture {
+                        half_size: Vec2::splat(4.0),
+                        depth: LightBeamDepth::default(),
+                        beam: None,
+                    },
+                    GlobalTransform::from_translation(pos.extend(0.0)),
+                ))
+                .id()
+        };
+        let behind_wall = spawn_aperture(Vec2::new(80.0, 0.0));
+        let in_sight = spawn_aperture(Vec2::new(0.0, 80.0));
+        app.update();
+
+        let beam =
+            |app: &App, aperture: Entity| app.world().get::<Aperture>(aperture).unwrap().beam;
+        assert_eq!(beam(&app, behind_wall), None);
+        let source = beam(&app, in_sight).expect("aperture in sight of the light focuses it");
+        let source = app.world().get::<LightBeamSource>(source).unwrap();
+        assert!(source.start_dir.abs_diff_eq(Vec2::Y, 1e-5));
+    }
+}
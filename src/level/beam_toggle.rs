@@ -0,0 +1,272 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::{
+    level::crystal::{CrystalColor, CrystalIdent, CrystalToggleEvent},
+    light::{
+        events::BeamReflectedEvent,
+        segments::{simulate_light_sources, PrevLightBeamPlayback},
+    },
+};
+
+use super::{entity::FixedEntityBundle, sensor::SwitchChangedEvent, LevelSystems};
+
+/// [`Plugin`] for targets that toggle crystals and doors every time a beam flashes on them.
+pub struct BeamTogglePlugin;
+
+impl Plugin for BeamTogglePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SwitchChangedEvent>()
+            .register_ldtk_entity::<BeamToggleTargetBundle>("BeamToggleTarget")
+            .add_systems(
+                Update,
+                reset_beam_toggle_targets.in_set(LevelSystems::Reset),
+            )
+            .add_systems(
+                FixedUpdate,
+                update_beam_toggle_targets
+                    .after(simulate_light_sources)
+                    .in_set(LevelSystems::Simulation),
+            );
+    }
+}
+
+/// [`Component`] for targets that toggle their crystals each time a beam newly hits them, unlike
+/// a [`LightSensor`](super::sensor::LightSensor) which needs the beam to stay on it. A beam that
+/// stays on the target only toggles it once, so toggling it again needs the beam to leave the
+/// target and come back.
+///
+/// The target is also a switch that flips between on and off with every toggle, so
+/// [`PoweredOccluder`](super::powered_occluder::PoweredOccluder) doors linked to it open and
+/// close with each flash.
+#[derive(Component, Debug)]
+pub struct BeamToggleTarget {
+    /// The color of the crystals to toggle
+    pub toggle_ident: CrystalIdent,
+    /// Whether the switch is currently on
+    pub is_active: bool,
+    /// Whether a beam was on the target last frame
+    lit: bool,
+}
+
+impl BeamToggleTarget {
+    pub fn new(toggle_ident: CrystalIdent) -> Self {
+        BeamToggleTarget {
+            toggle_ident,
+            is_active: false,
+            lit: false,
+        }
+    }
+
+    /// Updates whether a beam is on the target, flipping the switch and returning true if the beam
+    /// just arrived.
+    pub fn update(&mut self, lit: bool) -> bool {
+        let fresh_hit = lit && !self.lit;
+        self.lit = lit;
+        self.is_active ^= fresh_hit;
+        fresh_hit
+    }
+}
+
+impl From<&EntityInstance> for BeamToggleTarget {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        let toggle_color: CrystalColor = entity_instance
+            .get_enum_field("toggle_color")
+            .expect("toggle_color needs to be an enum field on all beam toggle targets")
+            .into();
+
+        let id = entity_instance
+            .get_int_field("id")
+            .expect("id needs to be an int field on all beam toggle targets");
+
+        BeamToggleTarget::new(CrystalIdent {
+            color: toggle_color,
+            id: *id,
+        })
+    }
+}
+
+/// [`Bundle`] that includes all the [`Component`]s needed for a [`BeamToggleTarget`] to function
+/// properly.
+#[derive(Bundle, LdtkEntity)]
+pub struct BeamToggleTargetBundle {
+    #[from_entity_instance]
+    physics: FixedEntityBundle,
+    #[from_entity_instance]
+    beam_toggle_target: BeamToggleTarget,
+    #[with(beam_toggle_target_sprite)]
+    sprite: Sprite,
+}
+
+pub fn beam_toggle_target_sprite(entity_instance: &EntityInstance) -> Sprite {
+    let beam_toggle_target = BeamToggleTarget::from(entity_instance);
+    Sprite::from_color(
+        beam_toggle_target.toggle_ident.color.button_color(),
+        Vec2::splat(8.0),
+    )
+}
+
+/// [`System`] that turns off and forgets the beams on every [`BeamToggleTarget`] when the level is
+/// reset.
+pub fn reset_beam_toggle_targets(mut q_targets: Query<&mut BeamToggleTarget>) {
+    for mut target in q_targets.iter_mut() {
+        target.is_active = false;
+        target.lit = false;
+    }
+}
+
+/// [`System`] that toggles the crystals and flips the switch of each [`BeamToggleTarget`] a beam
+/// newly reached. A target is lit while any beam's last playback ends on it, or when a beam
+/// reached it this frame.
+pub fn update_beam_toggle_targets(
+    mut q_targets: Query<(Entity, &mut BeamToggleTarget)>,
+    q_playbacks: Query<&PrevLightBeamPlayback>,
+    mut ev_beam_reflected: EventReader<BeamReflectedEvent>,
+    mut ev_crystal_toggle: EventWriter<CrystalToggleEvent>,
+    mut ev_switch_changed: EventWriter<SwitchChangedEvent>,
+) {
+    let reached: Vec<Entity> = ev_beam_reflected.read().map(|event| event.entity).collect();
+
+    for (entity, mut target) in q_targets.iter_mut() {
+        let lit = reached.contains(&entity)
+            || q_playbacks.iter().any(|playback| {
+                playback
+                    .intersections
+                    .iter()
+                    .flatten()
+                    .any(|intersection| intersection.entity == entity)
+            });
+        if target.update(lit) {
+            ev_crystal_toggle.send(CrystalToggleEvent {
+                color: target.toggle_ident,
+            });
+            ev_switch_changed.send(SwitchChangedEvent {
+                switch: entity,
+                is_active: target.is_active,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_ecs_ldtk::EntityIid;
+
+    use crate::{
+        level::powered_occluder::{update_powered_occluders, PoweredOccluder},
+        light::{segments::LightBeamIntersection, LightColor},
+    };
+
+    use super::*;
+
+    #[derive(Resource, Default)]
+    struct Toggles(usize);
+
+    fn count_toggles(mut toggles: ResMut<Toggles>, mut ev: EventReader<CrystalToggleEvent>) {
+        toggles.0 += ev.read().count();
+    }
+
+    fn app_with_target() -> (App, Entity) {
+        let mut app = App::new();
+        app.init_resource::<Toggles>()
+            .add_event::<BeamReflectedEvent>()
+            .add_event::<CrystalToggleEvent>()
+            .add_event::<SwitchChangedEvent>()
+            .add_systems(
+                Update,
+                (
+                    update_beam_toggle_targets,
+                    (count_toggles, update_powered_occluders),
+                )
+                    .chain(),
+            );
+        let target = app
+            .world_mut()
+            .spawn((
+                BeamToggleTarget::new(CrystalIdent {
+                    color: CrystalColor::Red,
+                    id: 0,
+                }),
+                EntityIid::new("target"),
+            ))
+            .id();
+        (app, target)
+    }
+
+    fn spawn_door(app: &mut App) -> Entity {
+        app.world_mut()
+            .spawn((
+                PoweredOccluder {
+                    switch: "target".into(),
+                    powered: false,
+                    solid: false,
+                    half_size: Vec2::new(8.0, 16.0),
+                },
+                Sprite::default(),
+            ))
+            .id()
+    }
+
+    fn door_powered(app: &App, door: Entity) -> bool {
+        app.world().get::<PoweredOccluder>(door).unwrap().powered
+    }
+
+    fn flash(app: &mut App, target: Entity) {
+        app.world_mut().send_event(BeamReflectedEvent {
+            source: Entity::PLACEHOLDER,
+            color: LightColor::White,
+            entity: target,
+            point: Vec2::ZERO,
+        });
+    }
+
+    #[test]
+    fn only_fresh_hits_toggle() {
+        let mut target = BeamToggleTarget::new(CrystalIdent {
+            color: CrystalColor::Red,
+            id: 0,
+        });
+        assert!(target.update(true));
+        assert!(target.is_active);
+        assert!(!target.update(true));
+        assert!(!target.update(false));
+        assert!(target.is_active);
+        assert!(target.update(true));
+        assert!(!target.is_active);
+    }
+
+    #[test]
+    fn brief_hits_toggle_each_time() {
+        let (mut app, target) = app_with_target();
+        let door = spawn_door(&mut app);
+        for i in 0..3 {
+            flash(&mut app, target);
+            app.update();
+            assert_eq!(door_powered(&app, door), i % 2 == 0);
+            // the beam is gone again
+            app.update();
+            assert_eq!(door_powered(&app, door), i % 2 == 0);
+        }
+        assert_eq!(app.world().resource::<Toggles>().0, 3);
+    }
+
+    #[test]
+    fn held_beam_toggles_once() {
+        let (mut app, target) = app_with_target();
+        let mut playback = PrevLightBeamPlayback::default();
+        playback.intersections[0] = Some(LightBeamIntersection {
+            entity: target,
+            point: Vec2::ZERO,
+            time: 0.0,
+            refracted: false,
+        });
+        let door = spawn_door(&mut app);
+        flash(&mut app, target);
+        app.world_mut().spawn(playback);
+        for _ in 0..5 {
+            app.update();
+            assert!(door_powered(&app, door));
+        }
+        assert_eq!(app.world().resource::<Toggles>().0, 1);
+    }
+}
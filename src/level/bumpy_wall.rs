@@ -0,0 +1,44 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::lighting::NormalMap2d;
+
+use super::LevelSystems;
+
+/// [`Plugin`] for bumpy walls, decorative tiles with a [`NormalMap2d`] that catch the light of
+/// nearby beams on the side of each brick facing them.
+pub struct BumpyWallPlugin;
+
+impl Plugin for BumpyWallPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity::<BumpyWallBundle>("BumpyWall")
+            .add_systems(
+                PreUpdate,
+                add_bumpy_wall_normal_maps.in_set(LevelSystems::Processing),
+            );
+    }
+}
+
+#[derive(Default, Component)]
+pub struct BumpyWall;
+
+#[derive(Bundle, LdtkEntity)]
+pub struct BumpyWallBundle {
+    #[sprite("lighting/bumpy_wall.png")]
+    sprite: Sprite,
+    #[default]
+    bumpy_wall: BumpyWall,
+}
+
+/// [`System`] that adds the [`NormalMap2d`] to newly spawned [`BumpyWall`]s.
+pub fn add_bumpy_wall_normal_maps(
+    mut commands: Commands,
+    q_bumpy_walls: Query<Entity, Added<BumpyWall>>,
+    asset_server: Res<AssetServer>,
+) {
+    for entity in q_bumpy_walls.iter() {
+        commands.entity(entity).insert(NormalMap2d(
+            asset_server.load("lighting/bumpy_wall_normal.png"),
+        ));
+    }
+}
@@ -0,0 +1,438 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy_ecs_ldtk::{prelude::*, EntityIid};
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    light::{segments::PrevLightBeamPlayback, LightBeamSource},
+    player::{not_input_locked, PlayerMarker},
+    shared::GroupLabel,
+};
+
+use super::{grid::GridConfig, restart::RestartLevelEvent, CurrentLevel, LevelSystems};
+
+/// How close the player needs to be to a [`CarryMirror`] to pick it up.
+const CARRY_REACH: f32 = 16.0;
+
+/// Where a carried [`CarryMirror`] is held, relative to the player.
+const CARRY_OFFSET: Vec2 = Vec2::new(0.0, 14.0);
+
+/// How far in front of the player a [`CarryMirror`] is placed.
+const PLACE_DISTANCE: f32 = 14.0;
+
+const MIRROR_HALF_THICKNESS: f32 = 1.0;
+
+/// [`Plugin`] for mirrors the player can carry around and place to redirect light beams.
+pub struct CarryMirrorPlugin;
+
+impl Plugin for CarryMirrorPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity::<CarryMirrorBundle>("CarryMirror")
+            .init_resource::<PlacedMirrors>()
+            .add_systems(
+                PreUpdate,
+                init_carry_mirrors.in_set(LevelSystems::Processing),
+            )
+            .add_systems(
+                Update,
+                (
+                    forget_placed_mirrors_on_restart.run_if(on_event::<RestartLevelEvent>),
+                    reset_carry_mirrors.in_set(LevelSystems::Reset),
+                    carry_mirrors
+                        .run_if(not_input_locked)
+                        .in_set(LevelSystems::Simulation),
+                ),
+            );
+    }
+}
+
+/// Marker [`Component`] for placed mirrors. Mirrors are terrain, so light beams bounce off of them
+/// like they do off of walls.
+#[derive(Component, Default, Debug)]
+pub struct Mirror;
+
+/// [`Component`] for mirrors that the player picks up and places with F. Carried mirrors float
+/// above the player and don't block anything, and placed ones snap to the [`GridConfig`] in front
+/// of the player and act as a [`Mirror`]. Mirrors can't be placed inside of terrain or the
+/// player, so a mirror placed just inside of a wall is placed a cell closer to the player.
+///
+/// Picking up a mirror cuts off the beams that were bouncing off of it, so they travel on from
+/// where they hit the mirror instead of instantly reaching wherever they now point.
+#[derive(Component, Debug)]
+pub struct CarryMirror {
+    pub half_length: f32,
+    /// The angle of the mirror, in radians counterclockwise from the right
+    pub angle: f32,
+    pub carried: bool,
+    /// Where the mirror was last placed, relative to its level
+    placed_at: Vec2,
+}
+
+impl CarryMirror {
+    pub fn collider(&self) -> Collider {
+        Collider::cuboid(self.half_length, MIRROR_HALF_THICKNESS)
+    }
+
+    /// The components of a placed mirror.
+    fn placed_bundle(&self) -> impl Bundle {
+        (
+            Mirror,
+            self.collider(),
+            CollisionGroups::new(GroupLabel::TERRAIN, GroupLabel::ALL),
+        )
+    }
+}
+
+impl From<&EntityInstance> for CarryMirror {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        let angle = *entity_instance
+            .get_float_field("angle")
+            .expect("angle needs to be a float field on all carry mirrors");
+
+        CarryMirror {
+            half_length: entity_instance.width as f32 / 2.0,
+            angle: angle.to_radians(),
+            carried: false,
+            placed_at: Vec2::ZERO,
+        }
+    }
+}
+
+/// [`Bundle`] that includes all the [`Component`]s needed for a [`CarryMirror`] to function
+/// properly.
+#[derive(Bundle, LdtkEntity)]
+pub struct CarryMirrorBundle {
+    #[from_entity_instance]
+    mirror: CarryMirror,
+    #[with(carry_mirror_sprite)]
+    sprite: Sprite,
+}
+
+pub fn carry_mirror_sprite(entity_instance: &EntityInstance) -> Sprite {
+    Sprite::from_color(
+        Color::srgb(0.8, 0.9, 1.0),
+        Vec2::new(entity_instance.width as f32, MIRROR_HALF_THICKNESS * 2.0),
+    )
+}
+
+/// [`Resource`] that remembers where the player placed each [`CarryMirror`], relative to its
+/// level and grouped by level, so mirrors stay where they were placed when the player dies or
+/// leaves the level and comes back. Restarting a level forgets its placements.
+#[derive(Resource, Default, Debug)]
+pub struct PlacedMirrors(pub HashMap<LevelIid, HashMap<EntityIid, Vec2>>);
+
+impl PlacedMirrors {
+    pub fn get(&self, mirror: &EntityIid) -> Option<Vec2> {
+        self.0
+            .values()
+            .find_map(|placements| placements.get(mirror).copied())
+    }
+}
+
+/// Where a mirror carried by a player at `player_pos` can be placed, in order of preference.
+pub fn mirror_place_positions(player_pos: Vec2, facing_left: bool, grid: &GridConfig) -> [Vec2; 2] {
+    let facing = if facing_left { -1.0 } else { 1.0 };
+    grid.placement_candidates(player_pos + Vec2::X * facing * PLACE_DISTANCE, player_pos)
+}
+
+/// [`System`] that rotates new [`CarryMirror`]s, moves them to where they were last placed, and
+/// places them.
+pub fn init_carry_mirrors(
+    mut commands: Commands,
+    mut q_mirrors: Query<
+        (Entity, &mut CarryMirror, &mut Transform, &EntityIid),
+        Added<CarryMirror>,
+    >,
+    placed: Res<PlacedMirrors>,
+) {
+    for (entity, mut mirror, mut transform, iid) in q_mirrors.iter_mut() {
+        if let Some(pos) = placed.get(iid) {
+            transform.translation = pos.extend(transform.translation.z);
+        }
+        transform.rotation = Quat::from_rotation_z(mirror.angle);
+        mirror.placed_at = transform.translation.xy();
+        commands
+            .entity(entity)
+            .insert((RigidBody::Fixed, mirror.placed_bundle()));
+    }
+}
+
+/// [`System`] that forgets where the [`CarryMirror`]s of the current level were placed when it is
+/// restarted. Ldtk respawns the mirrors where they were placed in the editor.
+pub fn forget_placed_mirrors_on_restart(
+    mut placed: ResMut<PlacedMirrors>,
+    current_level: Res<CurrentLevel>,
+) {
+    placed.0.remove(&current_level.level_iid);
+}
+
+/// [`System`] that puts carried [`CarryMirror`]s back where they were last placed when the level
+/// is reset.
+pub fn reset_carry_mirrors(
+    mut commands: Commands,
+    mut q_mirrors: Query<(Entity, &mut CarryMirror, &mut Transform)>,
+) {
+    for (entity, mut mirror, mut transform) in q_mirrors.iter_mut() {
+        if !mirror.carried {
+            continue;
+        }
+        mirror.carried = false;
+        transform.translation = mirror.placed_at.extend(transform.translation.z);
+        commands.entity(entity).insert(mirror.placed_bundle());
+    }
+}
+
+/// [`System`] that picks up the [`CarryMirror`] closest to the player or places the carried one
+/// when F is pressed, and keeps carried mirrors above the player.
+#[allow(clippy::too_many_arguments)]
+pub fn carry_mirrors(
+    mut commands: Commands,
+    q_player: Query<(&GlobalTransform, &Sprite), With<PlayerMarker>>,
+    mut q_mirrors: Query<(
+        Entity,
+        &mut CarryMirror,
+        &mut Transform,
+        &GlobalTransform,
+        &EntityIid,
+    )>,
+    mut q_sources: Query<(&mut LightBeamSource, &PrevLightBeamPlayback)>,
+    q_rapier: Query<&RapierContext>,
+    keys: Res<ButtonInput<KeyCode>>,
+    current_level: Res<CurrentLevel>,
+    mut placed: ResMut<PlacedMirrors>,
+    grid: Res<GridConfig>,
+) {
+    let Ok((player_transform, player_sprite)) = q_player.get_single() else {
+        return;
+    };
+    let player_pos = player_transform.translation().xy();
+    let interact = keys.just_pressed(KeyCode::KeyF);
+
+    // mirrors are children of their level, so they are moved by the distance to their target
+    let move_to = |transform: &mut Transform, global_transform: &GlobalTransform, target: Vec2| {
+        transform.translation += (target - global_transform.translation().xy()).extend(0.0);
+    };
+
+    if let Some((entity, mut mirror, mut transform, global_transform, iid)) =
+        q_mirrors.iter_mut().find(|(_, mirror, ..)| mirror.carried)
+    {
+        if !interact {
+            move_to(&mut transform, global_transform, player_pos + CARRY_OFFSET);
+            return;
+        }
+
+        let Ok(rapier_context) = q_rapier.get_single() else {
+            return;
+        };
+        let is_free = |target: &Vec2| {
+            rapier_context
+                .intersection_with_shape(
+                    *target,
+                    mirror.angle,
+                    &mirror.collider(),
+                    QueryFilter::new().groups(CollisionGroups::new(
+                        Group::ALL,
+                        GroupLabel::TERRAIN | GroupLabel::PLAYER_COLLIDER,
+                    )),
+                )
+                .is_none()
+        };
+        let Some(target) = mirror_place_positions(player_pos, player_sprite.flip_x, &grid)
+            .into_iter()
+            .find(is_free)
+        else {
+            return;
+        };
+
+        move_to(&mut transform, global_transform, target);
+        mirror.carried = false;
+        mirror.placed_at = transform.translation.xy();
+        commands.entity(entity).insert(mirror.placed_bundle());
+        placed
+            .0
+            .entry(current_level.level_iid.clone())
+            .or_default()
+            .insert(iid.clone(), mirror.placed_at);
+        return;
+    }
+
+    if !interact {
+        return;
+    }
+    let Some((entity, mut mirror, _)) = q_mirrors
+        .iter_mut()
+        .map(|(entity, mirror, _, transform, _)| {
+            (
+                entity,
+                mirror,
+                transform.translation().xy().distance(player_pos),
+            )
+        })
+        .filter(|(_, _, dist)| *dist <= CARRY_REACH)
+        .min_by(|(_, _, a), (_, _, b)| a.total_cmp(b))
+    else {
+        return;
+    };
+
+    mirror.carried = true;
+    commands.entity(entity).remove::<(Mirror, Collider)>();
+
+    // beams bouncing off of the mirror continue from where they hit it
+    for (mut source, playback) in q_sources.iter_mut() {
+        if let Some(hit) = playback
+            .intersections
+            .iter()
+            .flatten()
+            .find(|intersection| intersection.entity == entity)
+        {
+            source.time_traveled = source.time_traveled.min(hit.time);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_4;
+
+    use crate::light::{segments::LightBeamIntersection, LightBeamDepth, LightColor};
+
+    use super::*;
+
+    #[test]
+    fn mirrors_are_placed_on_the_grid_in_front_of_the_player() {
+        let grid = GridConfig::default();
+        assert_eq!(
+            mirror_place_positions(Vec2::new(100.0, 41.0), false, &grid)[0],
+            Vec2::new(112.0, 40.0)
+        );
+        assert_eq!(
+            mirror_place_positions(Vec2::new(100.0, 41.0), true, &grid),
+            [Vec2::new(88.0, 40.0), Vec2::new(96.0, 40.0)]
+        );
+
+        // levels with a larger grid place mirrors on it
+        let grid = GridConfig { size: 16.0 };
+        assert_eq!(
+            mirror_place_positions(Vec2::new(100.0, 41.0), false, &grid)[0],
+            Vec2::new(112.0, 48.0)
+        );
+    }
+
+    #[test]
+    fn placed_mirror_reflects_beam() {
+        let mut app = App::new();
+        let mirror = CarryMirror {
+            half_length: 8.0,
+            angle: FRAC_PI_4,
+            carried: false,
+            placed_at: Vec2::ZERO,
+        };
+        let entity = app.world_mut().spawn(mirror.placed_bundle()).id();
+
+        // beams of every color bounce off of terrain
+        let groups = app.world().get::<CollisionGroups>(entity).unwrap();
+        assert!(groups.memberships.contains(GroupLabel::TERRAIN));
+        assert!(app.world().get::<Collider>(entity).is_some());
+
+        // a beam going right bounces off of a mirror at 45 degrees and goes up
+        let normal = Vec2::from_angle(FRAC_PI_4 * 3.0);
+        assert!(Vec2::X.reflect(normal).abs_diff_eq(Vec2::Y, 1e-5));
+    }
+
+    fn mirror_app() -> (App, Entity, Entity) {
+        let mut app = App::new();
+        app.init_resource::<PlacedMirrors>()
+            .init_resource::<GridConfig>()
+            .insert_resource(CurrentLevel {
+                level_iid: LevelIid::new("level"),
+                ..default()
+            })
+            .init_resource::<ButtonInput<KeyCode>>()
+            .add_systems(Update, carry_mirrors);
+        app.world_mut().spawn((
+            PlayerMarker,
+            Sprite::default(),
+            GlobalTransform::from_xyz(4.0, 0.0, 0.0),
+        ));
+        let mirror = app
+            .world_mut()
+            .spawn((
+                CarryMirror {
+                    half_length: 8.0,
+                    angle: FRAC_PI_4,
+                    carried: false,
+                    placed_at: Vec2::ZERO,
+                },
+                Mirror,
+                Transform::default(),
+                GlobalTransform::default(),
+                EntityIid::new("mirror"),
+            ))
+            .id();
+        let source = app
+            .world_mut()
+            .spawn((
+                LightBeamSource {
+                    start_pos: Vec2::new(-40.0, 0.0),
+                    start_dir: Vec2::X,
+                    time_traveled: 100.0,
+                    color: LightColor::Green,
+                    width: 0.0,
+                    intensity: 1.0,
+                    penetration: 0.0,
+                    depth: LightBeamDepth::Background,
+                },
+                PrevLightBeamPlayback {
+                    intersections: vec![
+                        Some(LightBeamIntersection {
+                            entity: mirror,
+                            point: Vec2::ZERO,
+                            time: 40.0,
+                            refracted: false,
+                        }),
+                        None,
+                    ],
+                    ..default()
+                },
+            ))
+            .id();
+        (app, mirror, source)
+    }
+
+    #[test]
+    fn picking_up_a_mirror_carries_it_and_breaks_its_beams() {
+        let (mut app, mirror, source) = mirror_app();
+        app.world_mut()
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .press(KeyCode::KeyF);
+        app.update();
+
+        assert!(app.world().get::<CarryMirror>(mirror).unwrap().carried);
+        assert!(app.world().get::<Mirror>(mirror).is_none());
+        assert_eq!(
+            app.world()
+                .get::<LightBeamSource>(source)
+                .unwrap()
+                .time_traveled,
+            40.0
+        );
+
+        // the carried mirror follows the player
+        app.world_mut()
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .release_all();
+        app.world_mut()
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .clear();
+        app.update();
+        assert_eq!(
+            app.world()
+                .get::<Transform>(mirror)
+                .unwrap()
+                .translation
+                .xy(),
+            Vec2::new(4.0, 0.0) + CARRY_OFFSET
+        );
+    }
+}
@@ -0,0 +1,69 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::lighting::{CookieAnimation, Occluder2d, Occluder2dAlphaMask};
+
+use super::LevelSystems;
+
+/// [`Plugin`] for caustics, rippling patches of light cast onto the floor through water.
+pub struct CausticsPlugin;
+
+impl Plugin for CausticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity::<CausticsBundle>("Caustics")
+            .add_systems(
+                PreUpdate,
+                add_caustics_masks.in_set(LevelSystems::Processing),
+            );
+    }
+}
+
+/// [`Component`] for an invisible occluder with a scrolling caustics mask, which only lets light
+/// through along the mask's thin rippling lines. Placed between a light and the floor, the floor
+/// is lit by the moving web of lines.
+#[derive(Default, Component)]
+pub struct Caustics;
+
+#[derive(Bundle, LdtkEntity)]
+pub struct CausticsBundle {
+    #[default]
+    caustics: Caustics,
+    #[with(caustics_occluder)]
+    occluder: Occluder2d,
+    #[with(caustics_animation)]
+    animation: CookieAnimation,
+}
+
+pub fn caustics_occluder(entity_instance: &EntityInstance) -> Occluder2d {
+    Occluder2d::new(
+        entity_instance.width as f32 / 2.0,
+        entity_instance.height as f32 / 2.0,
+    )
+}
+
+pub fn caustics_animation(entity_instance: &EntityInstance) -> CookieAnimation {
+    let scroll_x = *entity_instance
+        .get_float_field("scroll_x")
+        .expect("scroll_x needs to be a float field on all caustics");
+    let scroll_y = *entity_instance
+        .get_float_field("scroll_y")
+        .expect("scroll_y needs to be a float field on all caustics");
+
+    CookieAnimation {
+        rotation_speed: 0.0,
+        scroll: Vec2::new(scroll_x, scroll_y),
+    }
+}
+
+/// [`System`] that adds the caustics [`Occluder2dAlphaMask`] to newly spawned [`Caustics`].
+pub fn add_caustics_masks(
+    mut commands: Commands,
+    q_caustics: Query<Entity, Added<Caustics>>,
+    asset_server: Res<AssetServer>,
+) {
+    for entity in q_caustics.iter() {
+        commands.entity(entity).insert(Occluder2dAlphaMask(
+            asset_server.load("lighting/caustics.png"),
+        ));
+    }
+}
@@ -0,0 +1,188 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::{prelude::*, EntityIid};
+
+use crate::lighting::{LightIgnition, LightToggle, LineLight2d};
+
+use super::{
+    sensor::{update_light_sensors, LightSensor},
+    LevelSystems,
+};
+
+/// The radius of a [`ChargeLight`] without a `radius` field.
+const DEFAULT_CHARGE_LIGHT_RADIUS: f32 = 80.0;
+
+/// [`Plugin`] for lights that brighten as a [`LightSensor`] charges up.
+pub struct ChargeLightPlugin;
+
+impl Plugin for ChargeLightPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity::<ChargeLightBundle>("ChargeLight")
+            .add_systems(
+                FixedUpdate,
+                update_charge_lights
+                    .after(update_light_sensors)
+                    .in_set(LevelSystems::Simulation),
+            );
+    }
+}
+
+/// [`Component`] for lights, placed in Ldtk, whose intensity follows the meter of the
+/// [`LightSensor`] they are linked to, so a room lights up as the player keeps a beam on the
+/// sensor and goes dark again as the sensor drains. Lights with `flash` set flash with a
+/// [`LightIgnition`] once the sensor is fully charged.
+#[derive(Component, Debug)]
+#[require(LightToggle)]
+pub struct ChargeLight {
+    /// The [`EntityIid`] of the sensor that charges the light
+    pub sensor: String,
+    /// The intensity of the light once the sensor is fully charged
+    pub intensity: f32,
+    /// Whether the sensor was fully charged the last time the light was updated
+    full: bool,
+}
+
+impl ChargeLight {
+    pub fn new(sensor: String, intensity: f32) -> Self {
+        ChargeLight {
+            sensor,
+            intensity,
+            full: false,
+        }
+    }
+
+    /// Updates the light's [`LightToggle`] with the `charge` of its sensor, from 0 to 1.
+    pub fn update(&mut self, charge: f32, toggle: &mut LightToggle) {
+        let charge = charge.clamp(0.0, 1.0);
+        toggle.intensity = self.intensity * charge;
+        toggle.set_on(true);
+
+        let full = charge >= 1.0;
+        if full && !self.full {
+            toggle.ignite();
+        }
+        self.full = full;
+    }
+}
+
+impl From<&EntityInstance> for ChargeLight {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        let sensor = entity_instance
+            .get_entity_ref_field("sensor")
+            .expect("sensor needs to be an entity ref field on all charge lights")
+            .entity_iid
+            .clone();
+        let intensity = *entity_instance
+            .get_float_field("intensity")
+            .expect("intensity needs to be a float field on all charge lights");
+
+        ChargeLight::new(sensor, intensity)
+    }
+}
+
+/// [`Bundle`] that includes all the [`Component`]s needed for a [`ChargeLight`] to function
+/// properly.
+#[derive(Bundle, LdtkEntity)]
+pub struct ChargeLightBundle {
+    #[from_entity_instance]
+    charge_light: ChargeLight,
+    #[with(charge_light_light)]
+    lighting: LineLight2d,
+    #[with(charge_light_toggle)]
+    toggle: LightToggle,
+}
+
+pub fn charge_light_light(entity_instance: &EntityInstance) -> LineLight2d {
+    let radius = entity_instance
+        .get_float_field("radius")
+        .copied()
+        .unwrap_or(DEFAULT_CHARGE_LIGHT_RADIUS);
+    LineLight2d::point(Vec4::new(1.0, 0.9, 0.75, 0.0), radius, 0.008)
+}
+
+pub fn charge_light_toggle(entity_instance: &EntityInstance) -> LightToggle {
+    let flash = entity_instance
+        .get_bool_field("flash")
+        .copied()
+        .unwrap_or(false);
+    LightToggle {
+        intensity: 0.0,
+        ignition: flash.then(LightIgnition::default),
+        ..default()
+    }
+}
+
+/// [`System`] that sets the intensity of each [`ChargeLight`] from the meter of its
+/// [`LightSensor`].
+pub fn update_charge_lights(
+    mut q_charge_lights: Query<(&mut ChargeLight, &mut LightToggle)>,
+    q_sensors: Query<(&EntityIid, &LightSensor)>,
+) {
+    for (mut charge_light, mut toggle) in q_charge_lights.iter_mut() {
+        let Some((_, sensor)) = q_sensors
+            .iter()
+            .find(|(iid, _)| iid.as_str() == charge_light.sensor)
+        else {
+            continue;
+        };
+        charge_light.update(sensor.meter, &mut toggle);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::level::crystal::CrystalIdent;
+
+    use super::*;
+
+    #[test]
+    fn intensity_tracks_charge() {
+        let mut app = App::new();
+        app.add_systems(Update, update_charge_lights);
+
+        let sensor = app
+            .world_mut()
+            .spawn((
+                EntityIid::new("sensor"),
+                LightSensor::new(CrystalIdent::default(), 1000),
+            ))
+            .id();
+        let light = app
+            .world_mut()
+            .spawn((
+                ChargeLight::new("sensor".into(), 2.0),
+                LightToggle {
+                    ignition: Some(LightIgnition::default()),
+                    ..default()
+                },
+            ))
+            .id();
+
+        // the intensity update_light_toggles gives the light, after `secs` more seconds
+        let charge_to = |app: &mut App, meter: f32, secs: f32| {
+            app.world_mut()
+                .get_mut::<LightSensor>(sensor)
+                .unwrap()
+                .meter = meter;
+            app.update();
+            let mut toggle = app.world_mut().get_mut::<LightToggle>(light).unwrap();
+            toggle.tick(secs);
+            toggle.intensity * toggle.brightness()
+        };
+
+        assert_eq!(charge_to(&mut app, 0.0, 0.1), 0.0);
+        // brightens as the sensor charges
+        assert!((charge_to(&mut app, 0.25, 0.1) - 0.5).abs() < 1e-5);
+        assert!((charge_to(&mut app, 0.5, 0.1) - 1.0).abs() < 1e-5);
+
+        // flashes once it's full
+        let peak = 2.0 * LightIgnition::default().peak;
+        assert!((charge_to(&mut app, 1.0, 0.0) - peak).abs() < 1e-5);
+        assert!((charge_to(&mut app, 1.0, 0.1) - 2.0).abs() < 1e-5);
+        // but only once
+        assert!((charge_to(&mut app, 1.0, 0.0) - 2.0).abs() < 1e-5);
+
+        // then dims as it drains
+        assert!((charge_to(&mut app, 0.75, 0.1) - 1.5).abs() < 1e-5);
+        assert_eq!(charge_to(&mut app, 0.0, 0.1), 0.0);
+    }
+}
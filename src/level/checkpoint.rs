@@ -0,0 +1,155 @@
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::player::PlayerMarker;
+
+use super::{entity_kind::spawn_entity_kinds, LevelSystems};
+
+/// [`Plugin`] for checkpoints, which move where the player respawns in their level once the
+/// player touches them.
+pub struct CheckpointPlugin;
+
+impl Plugin for CheckpointPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PreUpdate,
+            init_checkpoints
+                .after(spawn_entity_kinds)
+                .in_set(LevelSystems::Processing),
+        )
+        .add_systems(
+            FixedUpdate,
+            activate_checkpoints.in_set(LevelSystems::Simulation),
+        );
+    }
+}
+
+/// [`Component`] for checkpoints, placed with the `Checkpoint` `EntityKind` in Ldtk. The player
+/// respawns at the active checkpoint of their level, or at the level's
+/// [`StartFlag`](super::start_flag::StartFlag) while none of its checkpoints are active.
+#[derive(Component, Default, Debug)]
+pub struct Checkpoint {
+    /// The `level_iid` of the checkpoint's level, initialized like the one of a
+    /// [`StartFlag`](super::start_flag::StartFlag).
+    pub level_iid: LevelIid,
+    pub half_size: Vec2,
+    /// Whether this is the last checkpoint of its level the player touched
+    pub active: bool,
+}
+
+impl Checkpoint {
+    /// Whether the player at `player_pos` touches the checkpoint at `pos`.
+    pub fn touches(&self, pos: Vec2, player_pos: Vec2) -> bool {
+        let offset = (player_pos - pos).abs();
+        offset.x <= self.half_size.x && offset.y <= self.half_size.y
+    }
+}
+
+pub fn spawn_checkpoint(commands: &mut EntityCommands, entity_instance: &EntityInstance) {
+    commands.insert(Checkpoint {
+        half_size: Vec2::new(
+            entity_instance.width as f32 / 2.0,
+            entity_instance.height as f32 / 2.0,
+        ),
+        ..default()
+    });
+}
+
+/// [`System`] that gives new [`Checkpoint`]s the `level_iid` of the level they are in.
+pub fn init_checkpoints(
+    mut q_checkpoints: Query<(&mut Checkpoint, &Parent), Added<Checkpoint>>,
+    q_parent: Query<&Parent, Without<Checkpoint>>,
+    q_level: Query<&LevelIid>,
+) {
+    for (mut checkpoint, parent) in q_checkpoints.iter_mut() {
+        let Ok(level_entity) = q_parent.get(parent.get()) else {
+            continue;
+        };
+        let Ok(level_iid) = q_level.get(level_entity.get()) else {
+            continue;
+        };
+        checkpoint.level_iid = level_iid.clone();
+    }
+}
+
+/// [`System`] that activates the [`Checkpoint`] the player touches, and deactivates the other
+/// checkpoints of its level.
+pub fn activate_checkpoints(
+    q_player: Query<&GlobalTransform, With<PlayerMarker>>,
+    mut q_checkpoints: Query<(Entity, &mut Checkpoint, &GlobalTransform)>,
+) {
+    let Ok(player_transform) = q_player.get_single() else {
+        return;
+    };
+    let player_pos = player_transform.translation().xy();
+    let Some((touched, level_iid)) = q_checkpoints
+        .iter()
+        .find(|(_, checkpoint, transform)| {
+            !checkpoint.active && checkpoint.touches(transform.translation().xy(), player_pos)
+        })
+        .map(|(entity, checkpoint, _)| (entity, checkpoint.level_iid.clone()))
+    else {
+        return;
+    };
+    for (entity, mut checkpoint, _) in q_checkpoints.iter_mut() {
+        if checkpoint.level_iid == level_iid {
+            checkpoint.active = entity == touched;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn touching_a_checkpoint_activates_only_it() {
+        let mut app = App::new();
+        app.add_systems(Update, activate_checkpoints);
+
+        let mut spawn_at = |x: f32, level_iid: &str| {
+            app.world_mut()
+                .spawn((
+                    Checkpoint {
+                        level_iid: LevelIid::new(level_iid),
+                        half_size: Vec2::splat(8.0),
+                        active: false,
+                    },
+                    GlobalTransform::from_xyz(x, 0.0, 0.0),
+                ))
+                .id()
+        };
+        let first = spawn_at(0.0, "level");
+        let second = spawn_at(100.0, "level");
+        let elsewhere = spawn_at(1000.0, "other");
+        let player = app
+            .world_mut()
+            .spawn((PlayerMarker, GlobalTransform::default()))
+            .id();
+
+        let active = |app: &App, checkpoint: Entity| {
+            app.world().get::<Checkpoint>(checkpoint).unwrap().active
+        };
+        let move_player = |app: &mut App, x: f32| {
+            *app.world_mut().get_mut::<GlobalTransform>(player).unwrap() =
+                GlobalTransform::from_xyz(x, 4.0, 0.0);
+            app.update();
+        };
+
+        move_player(&mut app, 4.0);
+        assert!(active(&app, first));
+        assert!(!active(&app, second));
+
+        move_player(&mut app, 50.0);
+        assert!(active(&app, first));
+
+        move_player(&mut app, 96.0);
+        assert!(!active(&app, first));
+        assert!(active(&app, second));
+
+        // checkpoints of other levels are left alone
+        move_player(&mut app, 1000.0);
+        assert!(active(&app, second));
+        assert!(active(&app, elsewhere));
+    }
+}
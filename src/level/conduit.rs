@@ -0,0 +1,368 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::light::{
+    segments::{simulate_light_sources, PrevLightBeamPlayback},
+    LightBeamSource, LightColor,
+};
+
+use super::{entity::FixedEntityBundle, occluder::LDTK_GRID_SIZE, LevelSystems};
+
+/// The width of the pipe drawn along the path of a [`Conduit`].
+const CONDUIT_PIPE_WIDTH: f32 = 2.0;
+
+/// [`Plugin`] for fiber-optic conduits that carry beams along a path.
+pub struct ConduitPlugin;
+
+impl Plugin for ConduitPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity::<ConduitBundle>("Conduit")
+            .add_systems(
+                PreUpdate,
+                add_conduit_pipes.in_set(LevelSystems::Processing),
+            )
+            .add_systems(
+                FixedUpdate,
+                update_conduit_beams
+                    .before(simulate_light_sources)
+                    .in_set(LevelSystems::Simulation),
+            );
+    }
+}
+
+/// [`Component`] for conduits, placed in Ldtk at their mouth. While a beam hits the mouth, the
+/// same beam leaves the far end of the conduit's path, aimed along the last piece of the path, so
+/// beams can be routed around geometry without mirrors. The beam keeps its color and width, and
+/// loses the `loss` fraction of its intensity on the way.
+#[derive(Component, Debug)]
+pub struct Conduit {
+    /// The points the conduit runs through after its mouth, relative to the entity
+    pub path: Vec<Vec2>,
+    pub loss: f32,
+    /// The beam currently leaving the conduit
+    beam: Option<Entity>,
+}
+
+impl From<&EntityInstance> for Conduit {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        let points = entity_instance
+            .get_points_field("path")
+            .expect("path needs to be a point array field on all conduits");
+        let loss = entity_instance
+            .get_float_field("loss")
+            .copied()
+            .unwrap_or(0.0);
+
+        // points are grid cells counted from the top left of the level, like the points of a
+        // PolygonWall, but the path runs through the middle of each cell
+        let size = Vec2::new(entity_instance.width as f32, entity_instance.height as f32);
+        let center =
+            entity_instance.px.as_vec2() + (Vec2::splat(0.5) - entity_instance.pivot) * size;
+        let path = points
+            .iter()
+            .flatten()
+            .map(|point| {
+                let offset = (point.as_vec2() + Vec2::splat(0.5)) * LDTK_GRID_SIZE - center;
+                Vec2::new(offset.x, -offset.y)
+            })
+            .collect();
+
+        Conduit {
+            path,
+            loss: loss.clamp(0.0, 1.0),
+            beam: None,
+        }
+    }
+}
+
+impl Conduit {
+    /// Where the beam leaves a conduit with its mouth at `mouth_pos`, and the direction it leaves
+    /// in, or [`None`] if the path doesn't go anywhere.
+    pub fn exit(&self, mouth_pos: Vec2) -> Option<(Vec2, Vec2)> {
+        let end = *self.path.last()?;
+        let before_end = self.path.iter().rev().nth(1).copied().unwrap_or(Vec2::ZERO);
+        let dir = (end - before_end).try_normalize()?;
+        // start the beam just past the end, so it doesn't hit the conduit's mouth if the path
+        // loops back to it
+        Some((mouth_pos + end + dir, dir))
+    }
+}
+
+/// [`Bundle`] that includes all the [`Component`]s needed for a [`Conduit`] to function properly.
+#[derive(Bundle, LdtkEntity)]
+pub struct ConduitBundle {
+    #[from_entity_instance]
+    physics: FixedEntityBundle,
+    #[from_entity_instance]
+    conduit: Conduit,
+    #[with(conduit_sprite)]
+    sprite: Sprite,
+}
+
+pub fn conduit_sprite(entity_instance: &EntityInstance) -> Sprite {
+    Sprite::from_color(
+        Color::srgb(0.6, 0.6, 0.7),
+        Vec2::new(entity_instance.width as f32, entity_instance.height as f32),
+    )
+}
+
+/// [`System`] that draws the path of new [`Conduit`]s as pipes, children of the conduit.
+pub fn add_conduit_pipes(
+    mut commands: Commands,
+    q_conduits: Query<(Entity, &Conduit), Added<Conduit>>,
+) {
+    for (entity, conduit) in q_conduits.iter() {
+        let mut start = Vec2::ZERO;
+        for end in conduit.path.iter().copied() {
+            let offset = end - start;
+            commands
+                .spawn((
+                    Sprite::from_color(
+                        Color::srgb(0.6, 0.6, 0.7),
+                        Vec2::new(offset.length() + CONDUIT_PIPE_WIDTH, CONDUIT_PIPE_WIDTH),
+                    ),
+                    // behind the conduit's mouth
+                    Transform::from_translation(((start + end) / 2.0).extend(-0.1))
+                        .with_rotation(Quat::from_rotation_z(offset.to_angle())),
+                ))
+                .set_parent(entity);
+            start = end;
+        }
+    }
+}
+
+/// [`System`] that spawns, aims and despawns the beam leaving each [`Conduit`] based on the beams
+/// that reach its mouth. Beams that haven't traveled as far as the mouth yet don't count.
+pub fn update_conduit_beams(
+    mut commands: Commands,
+    mut q_conduits: Query<(Entity, &mut Conduit, &GlobalTransform)>,
+    mut q_sources: Query<(Entity, &mut LightBeamSource, &PrevLightBeamPlayback)>,
+) {
+    for (entity, mut conduit, transform) in q_conduits.iter_mut() {
+        // beams are despawned when the level resets
+        let existing = conduit.beam.filter(|beam| q_sources.contains(*beam));
+
+        // the beam that last reached the mouth, intersections are only recorded once the beam
+        // has traveled to them. The conduit's own beam can't keep it powered.
+        let hit = q_sources
+            .iter()
+            .filter(|(other, _, prev_playback)| {
+                Some(*other) != existing
+                    && prev_playback
+                        .intersections
+                        .iter()
+                        .flatten()
+                        .any(|intersection| intersection.entity == entity)
+            })
+            .map(|(_, source, _)| LightBeamSource {
+                start_pos: Vec2::ZERO,
+                start_dir: Vec2::ZERO,
+                time_traveled: 0.0,
+                intensity: source.intensity * (1.0 - conduit.loss),
+                ..*source
+            })
+            .last();
+
+        let exit = conduit.exit(transform.translation().xy());
+        let (Some(beam), Some((start_pos, start_dir))) = (hit, exit) else {
+            if let Some(beam) = existing {
+                commands.entity(beam).despawn_recursive();
+            }
+            conduit.beam = None;
+            continue;
+        };
+
+        if let Some(existing) = existing {
+            let (_, mut source, _) = q_sources.get_mut(existing).unwrap();
+            // a beam that changes color travels from the end of the conduit again
+            let time_traveled = if source.color == beam.color {
+                source.time_traveled
+            } else {
+                0.0
+            };
+            *source = LightBeamSource {
+                start_pos,
+                start_dir,
+                time_traveled,
+                ..beam
+            };
+            continue;
+        }
+
+        conduit.beam = Some(
+            commands
+                .spawn((
+                    LightBeamSource {
+                        start_pos,
+                        start_dir,
+                        ..beam
+                    },
+                    PrevLightBeamPlayback::default(),
+                ))
+                .id(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_rapier2d::{
+        plugin::RapierContext,
+        rapier::{geometry::ColliderBuilder, na::vector},
+    };
+
+    use crate::{
+        level::{crystal::CrystalIdent, sensor::LightSensor},
+        light::{
+            segments::{
+                assign_light_segments, beam_simulation_app, tick_light_sources,
+                LightBeamIntersection, LightSegment,
+            },
+            BeamBounces,
+        },
+    };
+
+    use super::*;
+
+    fn beam_into_mouth(mouth: Entity, reached: bool) -> (LightBeamSource, PrevLightBeamPlayback) {
+        let mut prev_playback = PrevLightBeamPlayback::default();
+        if reached {
+            prev_playback.intersections[0] = Some(LightBeamIntersection {
+                entity: mouth,
+                point: Vec2::new(-4.0, 0.0),
+                time: 96.0,
+                refracted: false,
+            });
+        }
+        (
+            LightBeamSource {
+                start_pos: Vec2::new(-100.0, 0.0),
+                start_dir: Vec2::X,
+                time_traveled: 96.0,
+                color: LightColor::Green,
+                width: 2.0,
+                intensity: 0.8,
+                penetration: 0.0,
+                depth: default(),
+            },
+            prev_playback,
+        )
+    }
+
+    #[test]
+    fn beams_are_routed_through_conduits() {
+        let mut app = App::new();
+        app.add_systems(Update, update_conduit_beams);
+        // up from the mouth and then right, ending at (40, 30)
+        let conduit = app
+            .world_mut()
+            .spawn((
+                Conduit {
+                    path: vec![Vec2::new(0.0, 30.0), Vec2::new(40.0, 30.0)],
+                    loss: 0.25,
+                    beam: None,
+                },
+                GlobalTransform::default(),
+            ))
+            .id();
+        let source = app.world_mut().spawn(beam_into_mouth(conduit, false)).id();
+
+        // the beam hasn't reached the mouth yet
+        app.update();
+        assert!(app.world().get::<Conduit>(conduit).unwrap().beam.is_none());
+
+        app.world_mut()
+            .entity_mut(source)
+            .insert(beam_into_mouth(conduit, true));
+        app.update();
+        let beam = app.world().get::<Conduit>(conduit).unwrap().beam.unwrap();
+        let output = app.world().get::<LightBeamSource>(beam).unwrap();
+        assert_eq!(output.start_dir, Vec2::X);
+        assert_eq!(output.start_pos, Vec2::new(41.0, 30.0));
+        assert_eq!(output.color, LightColor::Green);
+        assert_eq!(output.width, 2.0);
+        assert!((output.intensity - 0.6).abs() < 1e-5);
+
+        // the beam stops leaving the conduit once nothing reaches the mouth
+        app.world_mut().despawn(source);
+        app.update();
+        assert!(app.world().get_entity(beam).is_err());
+        assert!(app.world().get::<Conduit>(conduit).unwrap().beam.is_none());
+    }
+
+    #[test]
+    fn routed_beams_reach_sensors_past_the_conduit() {
+        let mut app = beam_simulation_app();
+        app.insert_resource(BeamBounces {
+            green: 0,
+            ..default()
+        })
+        .add_systems(
+            Update,
+            (tick_light_sources, update_conduit_beams)
+                .chain()
+                .before(assign_light_segments),
+        );
+        let conduit = app
+            .world_mut()
+            .spawn((
+                Conduit {
+                    path: vec![Vec2::new(0.0, 30.0), Vec2::new(40.0, 30.0)],
+                    loss: 0.25,
+                    beam: None,
+                },
+                GlobalTransform::default(),
+            ))
+            .id();
+        let sensor = app
+            .world_mut()
+            .spawn(LightSensor::new(CrystalIdent::default(), 100))
+            .id();
+        // the conduit's mouth, and a sensor in the way of the beam leaving it
+        let mut rapier_context = RapierContext::default();
+        for (entity, pos) in [(conduit, Vec2::ZERO), (sensor, Vec2::new(80.0, 30.0))] {
+            rapier_context.colliders.insert(
+                ColliderBuilder::cuboid(4.0, 4.0)
+                    .translation(vector![pos.x, pos.y])
+                    .user_data(entity.to_bits() as u128)
+                    .build(),
+            );
+        }
+        rapier_context
+            .query_pipeline
+            .update(&rapier_context.colliders);
+        app.world_mut().spawn(rapier_context);
+        let source = app
+            .world_mut()
+            .spawn(LightBeamSource {
+                time_traveled: 200.0,
+                ..beam_into_mouth(conduit, false).0
+            })
+            .id();
+        let sensor_hit = |app: &App| app.world().get::<LightSensor>(sensor).unwrap().hit_by;
+
+        // the beam leaving the conduit travels to the sensor like any other beam
+        for _ in 0..10 {
+            app.update();
+        }
+        assert!(sensor_hit(&app)[LightColor::Green]);
+        let mut drawn: Vec<Vec2> = app
+            .world_mut()
+            .query::<(&LightSegment, &Visibility, &Transform)>()
+            .iter(app.world())
+            .filter(|(_, visibility, _)| **visibility == Visibility::Visible)
+            .map(|(_, _, transform)| transform.translation.xy())
+            .collect();
+        drawn.sort_by(|a, b| a.x.total_cmp(&b.x));
+        // both the beam into the mouth and the one leaving the conduit are drawn, up to where the
+        // edges of the wide beams touch
+        assert_eq!(drawn.len(), 2);
+        assert!(drawn[0].distance(Vec2::new(-52.5, 0.0)) < 1e-3);
+        assert!(drawn[1].distance(Vec2::new(58.0, 30.0)) < 1e-3);
+
+        app.world_mut().despawn(source);
+        app.update();
+        app.update();
+        assert!(!sensor_hit(&app)[LightColor::Green]);
+    }
+}
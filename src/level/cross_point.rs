@@ -0,0 +1,305 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use enum_map::EnumMap;
+
+use crate::{
+    config::Config,
+    level::crystal::{CrystalColor, CrystalIdent, CrystalToggleEvent},
+    light::{
+        segments::{simulate_light_sources, LightSegment},
+        LightColor,
+    },
+    lighting::LineLight2d,
+};
+
+use super::{sensor::SwitchChangedEvent, LevelSystems};
+
+/// [`Plugin`] for targets that activate when beams of several colors cross inside of them.
+pub struct CrossPointPlugin;
+
+impl Plugin for CrossPointPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SwitchChangedEvent>()
+            .register_ldtk_entity::<CrossPointBundle>("CrossPoint")
+            .add_systems(Update, reset_cross_points.in_set(LevelSystems::Reset))
+            .add_systems(
+                FixedUpdate,
+                update_cross_points
+                    .after(simulate_light_sources)
+                    .in_set(LevelSystems::Simulation),
+            );
+    }
+}
+
+/// [`Component`] for small regions that activate while beams of every one of the
+/// `required_colors` pass through them at the same time, and toggle crystals when they activate or
+/// deactivate. Unlike a [`LightSensor`](super::sensor::LightSensor), a cross point has no
+/// collider, so beams go straight through it.
+#[derive(Component, Debug)]
+pub struct CrossPoint {
+    pub required_colors: Vec<LightColor>,
+    pub is_active: bool,
+    /// The color of the crystals to toggle
+    pub toggle_ident: CrystalIdent,
+    pub half_size: Vec2,
+}
+
+impl CrossPoint {
+    /// Whether the beams in `hit_by` satisfy the cross point. White beams reflect every other
+    /// color, so when `white_counts_as_any_color` is set a single white beam is enough.
+    pub fn is_satisfied(
+        &self,
+        hit_by: &EnumMap<LightColor, bool>,
+        white_counts_as_any_color: bool,
+    ) -> bool {
+        if self.required_colors.is_empty() {
+            return false;
+        }
+        if white_counts_as_any_color && hit_by[LightColor::White] {
+            return true;
+        }
+        self.required_colors.iter().all(|color| hit_by[*color])
+    }
+
+    /// Updates the cross point with the beams currently passing through it, returning the new
+    /// active state if it changed.
+    pub fn update(
+        &mut self,
+        hit_by: &EnumMap<LightColor, bool>,
+        white_counts_as_any_color: bool,
+    ) -> Option<bool> {
+        let is_active = self.is_satisfied(hit_by, white_counts_as_any_color);
+        if is_active == self.is_active {
+            return None;
+        }
+        self.is_active = is_active;
+        Some(is_active)
+    }
+}
+
+impl From<&EntityInstance> for CrossPoint {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        let required_colors = entity_instance
+            .iter_enums_field("required_colors")
+            .expect("required_colors needs to be an enum array field on all cross points")
+            .map(|color_str| color_str.into())
+            .collect();
+
+        let toggle_color: CrystalColor = entity_instance
+            .get_enum_field("toggle_color")
+            .expect("toggle_color needs to be an enum field on all cross points")
+            .into();
+
+        let id = entity_instance
+            .get_int_field("id")
+            .expect("id needs to be an int field on all cross points");
+
+        CrossPoint {
+            required_colors,
+            is_active: false,
+            toggle_ident: CrystalIdent {
+                color: toggle_color,
+                id: *id,
+            },
+            half_size: Vec2::new(
+                entity_instance.width as f32 / 2.0,
+                entity_instance.height as f32 / 2.0,
+            ),
+        }
+    }
+}
+
+/// [`Bundle`] that includes all the [`Component`]s needed for a [`CrossPoint`] to function
+/// properly.
+#[derive(Bundle, LdtkEntity)]
+pub struct CrossPointBundle {
+    #[from_entity_instance]
+    cross_point: CrossPoint,
+    #[with(cross_point_sprite)]
+    sprite: Sprite,
+}
+
+pub fn cross_point_sprite(entity_instance: &EntityInstance) -> Sprite {
+    let cross_point = CrossPoint::from(entity_instance);
+    Sprite::from_color(
+        cross_point
+            .toggle_ident
+            .color
+            .button_color()
+            .with_alpha(0.3),
+        cross_point.half_size * 2.0,
+    )
+}
+
+/// Whether the segment from `start` to `end` passes through `rect`, using the slab method.
+pub fn segment_intersects_rect(start: Vec2, end: Vec2, rect: Rect) -> bool {
+    let dir = end - start;
+    let (mut t_min, mut t_max) = (0.0_f32, 1.0_f32);
+    for axis in 0..2 {
+        if dir[axis].abs() < f32::EPSILON {
+            if start[axis] < rect.min[axis] || start[axis] > rect.max[axis] {
+                return false;
+            }
+            continue;
+        }
+        let t1 = (rect.min[axis] - start[axis]) / dir[axis];
+        let t2 = (rect.max[axis] - start[axis]) / dir[axis];
+        t_min = t_min.max(t1.min(t2));
+        t_max = t_max.min(t1.max(t2));
+        if t_min > t_max {
+            return false;
+        }
+    }
+    true
+}
+
+/// Finds the colors of the beam segments, given as `(color, start, end)`, that pass through
+/// `rect`.
+pub fn beams_through_rect(
+    rect: Rect,
+    segments: impl IntoIterator<Item = (LightColor, Vec2, Vec2)>,
+) -> EnumMap<LightColor, bool> {
+    let mut hit_by = EnumMap::default();
+    for (color, start, end) in segments {
+        if segment_intersects_rect(start, end, rect) {
+            hit_by[color] = true;
+        }
+    }
+    hit_by
+}
+
+/// [`System`] that deactivates [`CrossPoint`]s when the level is reset.
+pub fn reset_cross_points(mut q_cross_points: Query<&mut CrossPoint>) {
+    for mut cross_point in q_cross_points.iter_mut() {
+        cross_point.is_active = false;
+    }
+}
+
+/// [`System`] that checks which visible [`LightSegment`]s pass through each [`CrossPoint`], and
+/// toggles the cross point's crystals when it activates or deactivates.
+pub fn update_cross_points(
+    mut q_cross_points: Query<(Entity, &mut CrossPoint, &GlobalTransform, &mut Sprite)>,
+    q_segments: Query<(&Transform, &Visibility, &LightSegment, &LineLight2d)>,
+    mut ev_crystal_toggle: EventWriter<CrystalToggleEvent>,
+    mut ev_switch_changed: EventWriter<SwitchChangedEvent>,
+    config: Res<Config>,
+) {
+    let segments: Vec<(LightColor, Vec2, Vec2)> = q_segments
+        .iter()
+        .filter(|(_, visibility, _, _)| **visibility != Visibility::Hidden)
+        .map(|(transform, _, segment, line_light)| {
+            let center = transform.translation.xy();
+            let dir = (transform.rotation * Vec3::X).xy();
+            (
+                segment.color,
+                center - dir * line_light.half_length,
+                center + dir * line_light.half_length,
+            )
+        })
+        .collect();
+
+    for (entity, mut cross_point, transform, mut sprite) in q_cross_points.iter_mut() {
+        let rect = Rect::from_center_half_size(transform.translation().xy(), cross_point.half_size);
+        let hit_by = beams_through_rect(rect, segments.iter().copied());
+
+        let Some(is_active) =
+            cross_point.update(&hit_by, config.cross_point_config.white_counts_as_any_color)
+        else {
+            continue;
+        };
+
+        ev_crystal_toggle.send(CrystalToggleEvent {
+            color: cross_point.toggle_ident,
+        });
+        ev_switch_changed.send(SwitchChangedEvent {
+            switch: entity,
+            is_active,
+        });
+
+        let alpha = if is_active { 0.8 } else { 0.3 };
+        sprite.color = cross_point
+            .toggle_ident
+            .color
+            .button_color()
+            .with_alpha(alpha);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cross_point(required_colors: Vec<LightColor>) -> CrossPoint {
+        CrossPoint {
+            required_colors,
+            is_active: false,
+            toggle_ident: CrystalIdent {
+                color: CrystalColor::Red,
+                id: 0,
+            },
+            half_size: Vec2::splat(2.0),
+        }
+    }
+
+    #[test]
+    fn segment_rect_intersection() {
+        let rect = Rect::from_center_half_size(Vec2::ZERO, Vec2::splat(2.0));
+        assert!(segment_intersects_rect(
+            vec2(-10.0, -10.0),
+            vec2(10.0, 10.0),
+            rect
+        ));
+        assert!(segment_intersects_rect(
+            vec2(0.0, -10.0),
+            vec2(0.0, 10.0),
+            rect
+        ));
+        // passes beside the rect
+        assert!(!segment_intersects_rect(
+            vec2(-10.0, 3.0),
+            vec2(10.0, 3.0),
+            rect
+        ));
+        // stops short of the rect
+        assert!(!segment_intersects_rect(
+            vec2(-10.0, 0.0),
+            vec2(-3.0, 0.0),
+            rect
+        ));
+    }
+
+    #[test]
+    fn crossing_beams_activate_until_one_moves() {
+        let mut cross_point = cross_point(vec![LightColor::Green, LightColor::Purple]);
+        let rect = Rect::from_center_half_size(Vec2::ZERO, cross_point.half_size);
+
+        let green = (LightColor::Green, vec2(-20.0, 0.0), vec2(20.0, 0.0));
+        let purple = (LightColor::Purple, vec2(0.0, -20.0), vec2(0.0, 20.0));
+        assert_eq!(
+            cross_point.update(&beams_through_rect(rect, [green]), false),
+            None
+        );
+        assert_eq!(
+            cross_point.update(&beams_through_rect(rect, [green, purple]), false),
+            Some(true)
+        );
+
+        // purple beam moves away from the cross point
+        let purple = (LightColor::Purple, vec2(10.0, -20.0), vec2(10.0, 20.0));
+        assert_eq!(
+            cross_point.update(&beams_through_rect(rect, [green, purple]), false),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn white_beam_counts_as_any_color_if_configured() {
+        let mut cross_point = cross_point(vec![LightColor::Green, LightColor::Purple]);
+        let rect = Rect::from_center_half_size(Vec2::ZERO, cross_point.half_size);
+
+        let white = (LightColor::White, vec2(-20.0, 0.0), vec2(20.0, 0.0));
+        let hit_by = beams_through_rect(rect, [white]);
+        assert_eq!(cross_point.update(&hit_by, false), None);
+        assert_eq!(cross_point.update(&hit_by, true), Some(true));
+    }
+}
@@ -2,7 +2,10 @@ use bevy::prelude::*;
 use bevy_ecs_ldtk::prelude::*;
 use bevy_rapier2d::prelude::*;
 
-use crate::shared::GroupLabel;
+use crate::{light::spectral::SpectralOccluder, shared::GroupLabel};
+
+use super::sensor::is_pass_through_sensor;
+
 /// Component for things that hurt
 #[derive(Default, Component)]
 pub struct HurtMarker;
@@ -32,14 +35,24 @@ pub struct FixedEntityBundle {
 impl From<&EntityInstance> for FixedEntityBundle {
     fn from(entity_instance: &EntityInstance) -> Self {
         match entity_instance.identifier.as_ref() {
-            "Sensor" => FixedEntityBundle {
+            "Sensor" if is_pass_through_sensor(entity_instance) => FixedEntityBundle {
                 collider: Collider::cuboid(4., 4.),
                 rigid_body: RigidBody::Fixed,
                 collision_groups: CollisionGroups::new(
-                    GroupLabel::LIGHT_SENSOR,
+                    GroupLabel::PASS_THROUGH_SENSOR,
                     GroupLabel::LIGHT_RAY | GroupLabel::WHITE_RAY | GroupLabel::BLUE_RAY,
                 ),
             },
+            "Sensor" | "BeamLamp" | "Torch" | "SequenceSwitch" | "BeamToggleTarget" => {
+                FixedEntityBundle {
+                    collider: Collider::cuboid(4., 4.),
+                    rigid_body: RigidBody::Fixed,
+                    collision_groups: CollisionGroups::new(
+                        GroupLabel::LIGHT_SENSOR,
+                        GroupLabel::LIGHT_RAY | GroupLabel::WHITE_RAY | GroupLabel::BLUE_RAY,
+                    ),
+                }
+            }
             "CrystalShard" => FixedEntityBundle {
                 collider: Collider::cuboid(6., 6.),
                 rigid_body: RigidBody::Fixed,
@@ -48,6 +61,47 @@ impl From<&EntityInstance> for FixedEntityBundle {
                     GroupLabel::PLAYER_SENSOR,
                 ),
             },
+            "Prism" | "WaterVolume" | "Conduit" => FixedEntityBundle {
+                collider: Collider::cuboid(
+                    entity_instance.width as f32 / 2.0,
+                    entity_instance.height as f32 / 2.0,
+                ),
+                rigid_body: RigidBody::Fixed,
+                collision_groups: CollisionGroups::new(
+                    GroupLabel::LIGHT_SENSOR,
+                    GroupLabel::LIGHT_RAY | GroupLabel::WHITE_RAY | GroupLabel::BLUE_RAY,
+                ),
+            },
+            "WeakPanel" => FixedEntityBundle {
+                collider: Collider::cuboid(
+                    entity_instance.width as f32 / 2.0,
+                    entity_instance.height as f32 / 2.0,
+                ),
+                rigid_body: RigidBody::Fixed,
+                collision_groups: CollisionGroups::new(GroupLabel::TERRAIN, GroupLabel::ALL),
+            },
+            "SpectralOccluder" => FixedEntityBundle {
+                collider: Collider::cuboid(
+                    entity_instance.width as f32 / 2.0,
+                    entity_instance.height as f32 / 2.0,
+                ),
+                rigid_body: RigidBody::Fixed,
+                // only collides with beams of the color it blocks
+                collision_groups: CollisionGroups::new(
+                    SpectralOccluder::from(entity_instance)
+                        .blocks
+                        .spectral_collision_group(),
+                    GroupLabel::LIGHT_RAY | GroupLabel::WHITE_RAY | GroupLabel::BLUE_RAY,
+                ),
+            },
+            "PushBlock" | "LightSail" => FixedEntityBundle {
+                collider: Collider::cuboid(
+                    entity_instance.width as f32 / 2.0,
+                    entity_instance.height as f32 / 2.0,
+                ),
+                rigid_body: RigidBody::KinematicPositionBased,
+                collision_groups: CollisionGroups::new(GroupLabel::TERRAIN, GroupLabel::ALL),
+            },
             _ => unreachable!(),
         }
     }
@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::shared::GroupLabel;
+
+use super::{
+    checkpoint::spawn_checkpoint,
+    entity::{HurtMarker, Spike},
+    lever::spawn_lever,
+    powered_occluder::spawn_door,
+    pressure_plate::{pressure_plate_sprite, PressurePlate},
+    solidity::spawn_solid_panel,
+    start_flag::init_start_marker,
+    LevelSystems,
+};
+
+/// [`Plugin`] that spawns Ldtk entities based on their `EntityKind` enum field, see
+/// [`EntityKindRegistry`].
+pub struct EntityKindPlugin;
+
+impl Plugin for EntityKindPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EntityKindRegistry>().add_systems(
+            PreUpdate,
+            spawn_entity_kinds
+                .before(init_start_marker)
+                .in_set(LevelSystems::Processing),
+        );
+    }
+}
+
+/// Adds the components of one `EntityKind` to a freshly spawned Ldtk entity.
+pub type EntityKindSpawner = fn(&mut EntityCommands, &EntityInstance);
+
+/// [`Resource`] mapping the values of the `EntityKind` enum field in Ldtk to the components they
+/// spawn. Any Ldtk entity with an `EntityKind` field is spawned through this registry, so new
+/// kinds only need an entry here instead of their own Ldtk entity and bundle.
+#[derive(Resource)]
+pub struct EntityKindRegistry(HashMap<String, EntityKindSpawner>);
+
+impl Default for EntityKindRegistry {
+    fn default() -> Self {
+        let mut registry = EntityKindRegistry(HashMap::new());
+        registry.register("Spike", spawn_hazard);
+        registry.register("Checkpoint", spawn_checkpoint);
+        registry.register("Door", spawn_door);
+        registry.register("Lever", spawn_lever);
+        registry.register("PressurePlate", spawn_pressure_plate);
+        registry.register("SolidPanel", spawn_solid_panel);
+        registry
+    }
+}
+
+impl EntityKindRegistry {
+    /// Registers the components spawned for `kind`, replacing any previous entry.
+    pub fn register(&mut self, kind: impl Into<String>, spawner: EntityKindSpawner) {
+        self.0.insert(kind.into(), spawner);
+    }
+
+    pub fn get(&self, kind: &str) -> Option<EntityKindSpawner> {
+        self.0.get(kind).copied()
+    }
+}
+
+/// A hazard covering the whole entity that kills the player, like spikes.
+pub fn spawn_hazard(commands: &mut EntityCommands, entity_instance: &EntityInstance) {
+    commands.insert((
+        HurtMarker,
+        Spike,
+        Collider::cuboid(
+            entity_instance.width as f32 / 2.0,
+            entity_instance.height as f32 / 2.0,
+        ),
+        RigidBody::Fixed,
+        CollisionGroups::new(
+            GroupLabel::TERRAIN,
+            GroupLabel::ALL & !GroupLabel::PLAYER_COLLIDER,
+        ),
+    ));
+}
+
+pub fn spawn_pressure_plate(commands: &mut EntityCommands, entity_instance: &EntityInstance) {
+    commands.insert((
+        PressurePlate::from(entity_instance),
+        pressure_plate_sprite(entity_instance),
+    ));
+}
+
+/// [`System`] that spawns the components of newly spawned Ldtk entities with an `EntityKind`
+/// field, warning about kinds missing from the [`EntityKindRegistry`].
+pub fn spawn_entity_kinds(
+    mut commands: Commands,
+    q_entities: Query<(Entity, &EntityInstance), Added<EntityInstance>>,
+    registry: Res<EntityKindRegistry>,
+) {
+    for (entity, entity_instance) in q_entities.iter() {
+        let Ok(kind) = entity_instance.get_enum_field("EntityKind") else {
+            continue;
+        };
+        let Some(spawner) = registry.get(kind) else {
+            warn!(
+                "Unknown EntityKind {} on Ldtk entity {}, skipping it",
+                kind, entity_instance.iid
+            );
+            continue;
+        };
+        spawner(&mut commands.entity(entity), entity_instance);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_ecs_ldtk::ldtk::{FieldInstance, FieldValue, ReferenceToAnEntityInstance};
+
+    use crate::level::{checkpoint::Checkpoint, lever::Lever, powered_occluder::PoweredOccluder};
+
+    use super::*;
+
+    fn field(identifier: &str, value: FieldValue) -> FieldInstance {
+        FieldInstance {
+            identifier: identifier.into(),
+            tile: None,
+            field_instance_type: String::new(),
+            value,
+            def_uid: 0,
+            real_editor_values: vec![],
+        }
+    }
+
+    fn instance_of_kind(kind: &str) -> EntityInstance {
+        EntityInstance {
+            identifier: "Entity".into(),
+            width: 8,
+            height: 8,
+            field_instances: vec![field("EntityKind", FieldValue::Enum(Some(kind.into())))],
+            ..default()
+        }
+    }
+
+    #[test]
+    fn entity_kinds_spawn_their_components() {
+        let mut app = App::new();
+        app.init_resource::<EntityKindRegistry>()
+            .add_systems(Update, spawn_entity_kinds);
+
+        let spike = app.world_mut().spawn(instance_of_kind("Spike")).id();
+        let checkpoint = app.world_mut().spawn(instance_of_kind("Checkpoint")).id();
+        let mut door_instance = instance_of_kind("Door");
+        door_instance.field_instances.push(field(
+            "switch",
+            FieldValue::EntityRef(Some(ReferenceToAnEntityInstance {
+                entity_iid: "lever".into(),
+                ..default()
+            })),
+        ));
+        let door = app.world_mut().spawn(door_instance).id();
+        let lever = app.world_mut().spawn(instance_of_kind("Lever")).id();
+        let unknown = app.world_mut().spawn(instance_of_kind("Trampoline")).id();
+        let plain = app.world_mut().spawn(EntityInstance::default()).id();
+        app.update();
+
+        assert!(app.world().get::<HurtMarker>(spike).is_some());
+        assert!(app.world().get::<Collider>(spike).is_some());
+        assert!(app.world().get::<Checkpoint>(checkpoint).is_some());
+        assert!(app.world().get::<HurtMarker>(checkpoint).is_none());
+        assert_eq!(
+            app.world().get::<PoweredOccluder>(door).unwrap().switch,
+            "lever"
+        );
+        assert!(app.world().get::<Lever>(lever).is_some());
+        // unknown kinds and entities without a kind are left alone
+        assert_eq!(app.world().entity(unknown).archetype().len(), 1);
+        assert_eq!(app.world().entity(plain).archetype().len(), 1);
+    }
+
+    #[test]
+    fn new_kinds_are_one_registry_entry() {
+        let mut registry = EntityKindRegistry::default();
+        assert!(registry.get("Trampoline").is_none());
+        registry.register("Trampoline", spawn_hazard);
+        assert!(registry.get("Trampoline").is_some());
+    }
+}
@@ -0,0 +1,262 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::{
+    light::segments::{simulate_light_sources, LightSegment},
+    lighting::LineLight2d,
+    player::kill::{KillCause, KillPlayerEvent, SpawnProtection},
+};
+
+use super::{cross_point::segment_intersects_rect, restart::RestartLevelEvent, LevelSystems};
+
+/// [`Plugin`] for sensors that beams have to be kept away from.
+pub struct ForbiddenSensorPlugin;
+
+impl Plugin for ForbiddenSensorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<KillPlayerEvent>()
+            .add_event::<RestartLevelEvent>()
+            .register_ldtk_entity::<ForbiddenSensorBundle>("ForbiddenSensor")
+            .add_systems(Update, reset_forbidden_sensors.in_set(LevelSystems::Reset))
+            .add_systems(
+                FixedUpdate,
+                update_forbidden_sensors
+                    .after(simulate_light_sources)
+                    .in_set(LevelSystems::Simulation),
+            );
+    }
+}
+
+/// What a [`ForbiddenSensor`] does when a beam trips it.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum AlarmReaction {
+    /// Kill the player, unless they are protected by [`SpawnProtection`]
+    #[default]
+    Kill,
+    /// Restart the level, putting the puzzle back the way it started
+    ResetPuzzle,
+}
+
+/// [`Component`] for the inverse of a [`CrossPoint`](super::cross_point::CrossPoint): a region
+/// that trips an alarm when a beam of any color passes through it, for "don't cross the laser"
+/// puzzles. The alarm goes off once every time a beam starts passing through it, or every tick a
+/// beam passes through it if `continuous` is set.
+#[derive(Component, Debug)]
+pub struct ForbiddenSensor {
+    pub reaction: AlarmReaction,
+    pub continuous: bool,
+    pub half_size: Vec2,
+    /// Whether a beam was passing through the sensor last tick
+    pub is_hit: bool,
+}
+
+impl ForbiddenSensor {
+    /// Updates the sensor with whether a beam is passing through it, returning whether the alarm
+    /// goes off.
+    pub fn update(&mut self, is_hit: bool) -> bool {
+        let was_hit = std::mem::replace(&mut self.is_hit, is_hit);
+        is_hit && (self.continuous || !was_hit)
+    }
+}
+
+impl From<&EntityInstance> for ForbiddenSensor {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        let reaction = match entity_instance.get_enum_field("reaction") {
+            Ok(reaction) => match reaction.as_str() {
+                "Kill" => AlarmReaction::Kill,
+                "ResetPuzzle" => AlarmReaction::ResetPuzzle,
+                _ => panic!("String {} does not represent an alarm reaction", reaction),
+            },
+            Err(_) => AlarmReaction::default(),
+        };
+
+        ForbiddenSensor {
+            reaction,
+            continuous: entity_instance
+                .get_bool_field("continuous")
+                .copied()
+                .unwrap_or(false),
+            half_size: Vec2::new(
+                entity_instance.width as f32 / 2.0,
+                entity_instance.height as f32 / 2.0,
+            ),
+            is_hit: false,
+        }
+    }
+}
+
+/// [`Bundle`] that includes all the [`Component`]s needed for a [`ForbiddenSensor`] to function
+/// properly.
+#[derive(Bundle, LdtkEntity)]
+pub struct ForbiddenSensorBundle {
+    #[from_entity_instance]
+    forbidden_sensor: ForbiddenSensor,
+    #[with(forbidden_sensor_sprite)]
+    sprite: Sprite,
+}
+
+pub fn forbidden_sensor_sprite(entity_instance: &EntityInstance) -> Sprite {
+    Sprite::from_color(
+        Color::srgba(1.0, 0.1, 0.1, 0.3),
+        Vec2::new(entity_instance.width as f32, entity_instance.height as f32),
+    )
+}
+
+/// [`System`] that forgets the beams that were passing through [`ForbiddenSensor`]s when the
+/// level is reset.
+pub fn reset_forbidden_sensors(mut q_sensors: Query<&mut ForbiddenSensor>) {
+    for mut sensor in q_sensors.iter_mut() {
+        sensor.is_hit = false;
+    }
+}
+
+/// [`System`] that checks whether any visible [`LightSegment`] passes through each
+/// [`ForbiddenSensor`], and reacts when its alarm goes off.
+pub fn update_forbidden_sensors(
+    mut q_sensors: Query<(&mut ForbiddenSensor, &GlobalTransform, &mut Sprite)>,
+    q_segments: Query<(&Transform, &Visibility, &LineLight2d), With<LightSegment>>,
+    spawn_protection: Res<SpawnProtection>,
+    mut ev_kill_player: EventWriter<KillPlayerEvent>,
+    mut ev_restart_level: EventWriter<RestartLevelEvent>,
+) {
+    let segments: Vec<(Vec2, Vec2)> = q_segments
+        .iter()
+        .filter(|(_, visibility, _)| **visibility != Visibility::Hidden)
+        .map(|(transform, _, line_light)| {
+            let center = transform.translation.xy();
+            let dir = (transform.rotation * Vec3::X).xy();
+            (
+                center - dir * line_light.half_length,
+                center + dir * line_light.half_length,
+            )
+        })
+        .collect();
+
+    let mut kill = false;
+    let mut restart = false;
+    for (mut sensor, transform, mut sprite) in q_sensors.iter_mut() {
+        let rect = Rect::from_center_half_size(transform.translation().xy(), sensor.half_size);
+        let is_hit = segments
+            .iter()
+            .any(|(start, end)| segment_intersects_rect(*start, *end, rect));
+        sprite.color = Color::srgba(1.0, 0.1, 0.1, if is_hit { 0.8 } else { 0.3 });
+
+        if sensor.update(is_hit) {
+            match sensor.reaction {
+                AlarmReaction::Kill => kill = true,
+                AlarmReaction::ResetPuzzle => restart = true,
+            }
+        }
+    }
+
+    if kill && !spawn_protection.is_active() {
+        ev_kill_player.send(KillPlayerEvent {
+            cause: KillCause::Alarm,
+        });
+    } else if restart {
+        ev_restart_level.send_default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::light::LightColor;
+
+    fn alarm_app(reaction: AlarmReaction, continuous: bool) -> App {
+        let mut app = App::new();
+        app.init_resource::<SpawnProtection>()
+            .add_event::<KillPlayerEvent>()
+            .add_event::<RestartLevelEvent>()
+            .add_systems(Update, update_forbidden_sensors);
+        app.world_mut().spawn((
+            ForbiddenSensor {
+                reaction,
+                continuous,
+                half_size: Vec2::splat(4.0),
+                is_hit: false,
+            },
+            GlobalTransform::default(),
+            Sprite::default(),
+        ));
+        app
+    }
+
+    fn spawn_beam(app: &mut App, y: f32) -> Entity {
+        app.world_mut()
+            .spawn((
+                LightSegment {
+                    color: LightColor::Green,
+                },
+                Transform::from_xyz(0.0, y, 0.0),
+                Visibility::Visible,
+                LineLight2d {
+                    half_length: 50.0,
+                    ..default()
+                },
+            ))
+            .id()
+    }
+
+    /// The number of kill and restart events sent since the last call.
+    fn alarms(app: &mut App) -> (usize, usize) {
+        let kills = app
+            .world_mut()
+            .resource_mut::<Events<KillPlayerEvent>>()
+            .drain()
+            .count();
+        let restarts = app
+            .world_mut()
+            .resource_mut::<Events<RestartLevelEvent>>()
+            .drain()
+            .count();
+        (kills, restarts)
+    }
+
+    #[test]
+    fn crossing_beam_trips_alarm() {
+        let mut app = alarm_app(AlarmReaction::Kill, false);
+        spawn_beam(&mut app, 20.0);
+        app.update();
+        assert_eq!(alarms(&mut app), (0, 0));
+
+        let beam = spawn_beam(&mut app, 0.0);
+        app.update();
+        assert_eq!(alarms(&mut app), (1, 0));
+        // only a fresh hit trips it again
+        app.update();
+        assert_eq!(alarms(&mut app), (0, 0));
+        app.world_mut().entity_mut(beam).insert(Visibility::Hidden);
+        app.update();
+        app.world_mut().entity_mut(beam).insert(Visibility::Visible);
+        app.update();
+        assert_eq!(alarms(&mut app), (1, 0));
+    }
+
+    #[test]
+    fn continuous_alarm_resets_puzzle_every_tick() {
+        let mut app = alarm_app(AlarmReaction::ResetPuzzle, true);
+        spawn_beam(&mut app, 2.0);
+        app.update();
+        app.update();
+        assert_eq!(alarms(&mut app), (0, 2));
+    }
+
+    #[test]
+    fn protected_player_survives_alarm() {
+        let mut app = alarm_app(AlarmReaction::Kill, true);
+        let mut spawn_protection = SpawnProtection::default();
+        spawn_protection.respawn();
+        spawn_protection.update(true, default());
+        app.insert_resource(spawn_protection);
+        spawn_beam(&mut app, 0.0);
+        app.update();
+        assert_eq!(alarms(&mut app), (0, 0));
+
+        app.world_mut()
+            .resource_mut::<SpawnProtection>()
+            .update(false, default());
+        app.update();
+        assert_eq!(alarms(&mut app), (1, 0));
+    }
+}
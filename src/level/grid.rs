@@ -0,0 +1,106 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+/// The grid size used until the Ldtk project is loaded, the size of a tile in Lightborne.
+const DEFAULT_GRID_SIZE: f32 = 8.0;
+
+/// [`Plugin`] that reads the [`GridConfig`] from the Ldtk project.
+pub struct GridConfigPlugin;
+
+impl Plugin for GridConfigPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GridConfig>()
+            .add_systems(Update, load_grid_config);
+    }
+}
+
+/// [`Resource`] that holds the size of the tile grid of the Ldtk project, so objects placed during
+/// the game line up with the tiles placed in the editor.
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct GridConfig {
+    pub size: f32,
+}
+
+impl Default for GridConfig {
+    fn default() -> Self {
+        GridConfig {
+            size: DEFAULT_GRID_SIZE,
+        }
+    }
+}
+
+impl GridConfig {
+    /// Snaps `pos` to the nearest corner of the grid.
+    pub fn snap_to_grid(&self, pos: Vec2) -> Vec2 {
+        (pos / self.size).round() * self.size
+    }
+
+    /// The grid corners to try placing something at `pos` in order, for something placed from
+    /// `from`. When the nearest corner is blocked, for example because `pos` is just inside of a
+    /// wall, the corner one cell back towards `from` is the next closest valid one.
+    pub fn placement_candidates(&self, pos: Vec2, from: Vec2) -> [Vec2; 2] {
+        let snapped = self.snap_to_grid(pos);
+        let back = Vec2::new((from.x - snapped.x).signum(), 0.0);
+        [snapped, snapped + back * self.size]
+    }
+}
+
+/// [`System`] that sets the [`GridConfig`] from the default grid size of the Ldtk project
+/// whenever it is loaded or changed.
+pub fn load_grid_config(
+    mut ev_asset: EventReader<AssetEvent<LdtkProject>>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+    mut grid_config: ResMut<GridConfig>,
+) {
+    for event in ev_asset.read() {
+        let (AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id }) = event
+        else {
+            continue;
+        };
+        let Some(ldtk_project) = ldtk_project_assets.get(*id) else {
+            continue;
+        };
+        let size = ldtk_project.json_data().default_grid_size;
+        if size > 0 {
+            grid_config.set_if_neq(GridConfig { size: size as f32 });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positions_snap_to_project_grid() {
+        let grid = GridConfig { size: 16.0 };
+        assert_eq!(
+            grid.snap_to_grid(Vec2::new(13.0, -3.0)),
+            Vec2::new(16.0, 0.0)
+        );
+        assert_eq!(
+            grid.snap_to_grid(Vec2::new(-25.0, 40.0)),
+            Vec2::new(-32.0, 48.0)
+        );
+
+        let grid = GridConfig::default();
+        assert_eq!(
+            grid.snap_to_grid(Vec2::new(13.0, -3.0)),
+            Vec2::new(16.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn blocked_placement_falls_back_towards_placer() {
+        let grid = GridConfig { size: 16.0 };
+        let player = Vec2::new(0.0, 8.0);
+        assert_eq!(
+            grid.placement_candidates(Vec2::new(25.0, 8.0), player),
+            [Vec2::new(32.0, 16.0), Vec2::new(16.0, 16.0)]
+        );
+        assert_eq!(
+            grid.placement_candidates(Vec2::new(-25.0, 8.0), player),
+            [Vec2::new(-32.0, 16.0), Vec2::new(-16.0, 16.0)]
+        );
+    }
+}
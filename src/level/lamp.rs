@@ -0,0 +1,205 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    light::{
+        fog::VolumetricFog,
+        segments::{simulate_light_sources, PrevLightBeamPlayback},
+        LightBeamSource,
+    },
+    lighting::{LightIgnition, LightToggle, LineLight2d},
+};
+
+use super::{entity::FixedEntityBundle, LevelSystems};
+
+/// The distance a light beam can travel before it is too dim to light up a [`BeamLamp`].
+const BEAM_LAMP_FALLOFF_DISTANCE: f32 = 400.0;
+
+/// The radius of the light emitted by a [`BeamLamp`].
+const BEAM_LAMP_RADIUS: f32 = 50.0;
+
+pub struct BeamLampPlugin;
+
+impl Plugin for BeamLampPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity::<BeamLampBundle>("BeamLamp")
+            .add_systems(
+                FixedUpdate,
+                update_beam_lamps
+                    .after(simulate_light_sources)
+                    .in_set(LevelSystems::Simulation),
+            );
+    }
+}
+
+/// [`Component`] for lamps that are lit by light beams. The lamp's [`LineLight2d`] stays dark
+/// until a beam hits it, and then glows with the color of the beam. Beams that travel further
+/// before hitting the lamp light it up less. Lamps flash briefly when they light up, see
+/// [`LightIgnition`].
+#[derive(Component, Default, Debug)]
+pub struct BeamLamp {
+    /// How brightly the lamp is lit, from 0 to 1
+    pub intensity: f32,
+}
+
+/// [`Bundle`] that includes all the [`Component`]s needed for a [`BeamLamp`] to function
+/// properly.
+#[derive(Bundle, LdtkEntity)]
+pub struct BeamLampBundle {
+    #[from_entity_instance]
+    physics: FixedEntityBundle,
+    #[default]
+    sensor: Sensor,
+    #[default]
+    lamp: BeamLamp,
+    #[with(beam_lamp_light)]
+    lighting: LineLight2d,
+    #[with(beam_lamp_toggle)]
+    toggle: LightToggle,
+}
+
+pub fn beam_lamp_light(_: &EntityInstance) -> LineLight2d {
+    LineLight2d::point(Vec4::new(1.0, 1.0, 1.0, 0.0), BEAM_LAMP_RADIUS, 0.008)
+}
+
+pub fn beam_lamp_toggle(_: &EntityInstance) -> LightToggle {
+    LightToggle {
+        ignition: Some(LightIgnition::default()),
+        ..default()
+    }
+}
+
+/// The intensity of a beam that has traveled `distance` units, from 0 to 1.
+pub fn beam_lamp_intensity(distance: f32) -> f32 {
+    (1.0 - distance / BEAM_LAMP_FALLOFF_DISTANCE).clamp(0.0, 1.0)
+}
+
+/// [`System`] that lights up [`BeamLamp`]s hit by a light beam. If multiple beams hit the same
+/// lamp, the brightest one is used. Lamps that are no longer hit go dark.
+pub fn update_beam_lamps(
+    q_light_sources: Query<(&LightBeamSource, &PrevLightBeamPlayback)>,
+    mut q_lamps: Query<(Entity, &mut BeamLamp, &mut LineLight2d, &mut LightToggle)>,
+    fog: Res<VolumetricFog>,
+) {
+    for (lamp_entity, mut lamp, mut light, mut toggle) in q_lamps.iter_mut() {
+        let mut intensity = 0.0;
+        let mut color = light.color.truncate();
+
+        for (source, playback) in q_light_sources.iter() {
+            for intersection in playback.intersections.iter().flatten() {
+                if intersection.entity != lamp_entity {
+                    continue;
+                }
+                let hit_intensity = beam_lamp_intensity(intersection.time)
+                    * playback.received_intensity(source, intersection.time, &fog);
+                if hit_intensity > intensity {
+                    intensity = hit_intensity;
+                    color = source.color.lighting_color();
+                }
+            }
+        }
+
+        lamp.intensity = intensity;
+        // the intensity is set by the toggle, which adds the ignition flash
+        light.color = color.extend(light.color.w);
+        toggle.intensity = intensity;
+        toggle.set_on(intensity > 0.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_rapier2d::rapier::{geometry::ColliderBuilder, na::vector};
+
+    use crate::light::{segments::beam_simulation_app, LightColor};
+
+    use super::*;
+
+    #[test]
+    fn beam_lamp_dims_with_distance() {
+        assert_eq!(beam_lamp_intensity(0.0), 1.0);
+        assert!(beam_lamp_intensity(50.0) > beam_lamp_intensity(200.0));
+        assert!(beam_lamp_intensity(200.0) > 0.0);
+        assert_eq!(beam_lamp_intensity(BEAM_LAMP_FALLOFF_DISTANCE), 0.0);
+        assert_eq!(beam_lamp_intensity(BEAM_LAMP_FALLOFF_DISTANCE * 2.0), 0.0);
+    }
+
+    #[test]
+    fn beam_lamps_brighten_as_the_beam_gets_closer() {
+        let mut app = beam_simulation_app();
+        app.add_systems(Update, update_beam_lamps.after(simulate_light_sources));
+        let lamp = app
+            .world_mut()
+            .spawn((
+                BeamLamp::default(),
+                beam_lamp_light(&default()),
+                beam_lamp_toggle(&default()),
+            ))
+            .id();
+        let wall = app.world_mut().spawn_empty().id();
+        let mut rapier_context = RapierContext::default();
+        rapier_context.colliders.insert(
+            ColliderBuilder::cuboid(4.0, 4.0)
+                .user_data(lamp.to_bits() as u128)
+                .build(),
+        );
+        rapier_context
+            .query_pipeline
+            .update(&rapier_context.colliders);
+        let context = app.world_mut().spawn(rapier_context).id();
+        let source = app
+            .world_mut()
+            .spawn(LightBeamSource {
+                start_pos: Vec2::new(-300.0, 0.0),
+                start_dir: Vec2::X,
+                time_traveled: 1000.0,
+                color: LightColor::Green,
+                width: 0.0,
+                intensity: 1.0,
+                penetration: 0.0,
+                depth: default(),
+            })
+            .id();
+        let lamp_intensity = |app: &App| app.world().get::<BeamLamp>(lamp).unwrap().intensity;
+        let lamp_on = |app: &App| app.world().get::<LightToggle>(lamp).unwrap().is_on();
+
+        // a dim distant beam gives a dim lamp
+        app.update();
+        let far = lamp_intensity(&app);
+        assert!(far > 0.0 && far < 0.5, "far {far}");
+        assert!(lamp_on(&app));
+
+        app.world_mut()
+            .get_mut::<LightBeamSource>(source)
+            .unwrap()
+            .start_pos = Vec2::new(-100.0, 0.0);
+        app.update();
+        let near = lamp_intensity(&app);
+        assert!(near > far, "near {near} far {far}");
+
+        // a dimmer beam lights the lamp as much less as it lights torches and pushes sails
+        app.world_mut()
+            .get_mut::<LightBeamSource>(source)
+            .unwrap()
+            .intensity = 0.5;
+        app.update();
+        assert!((lamp_intensity(&app) - near * 0.5).abs() < 1e-5);
+
+        // the lamp goes dark once a wall cuts the beam off
+        let mut rapier_context = app.world_mut().get_mut::<RapierContext>(context).unwrap();
+        rapier_context.colliders.insert(
+            ColliderBuilder::cuboid(4.0, 20.0)
+                .translation(vector![-50.0, 0.0])
+                .user_data(wall.to_bits() as u128)
+                .build(),
+        );
+        let rapier_context = rapier_context.into_inner();
+        rapier_context
+            .query_pipeline
+            .update(&rapier_context.colliders);
+        app.update();
+        assert_eq!(lamp_intensity(&app), 0.0);
+        assert!(!lamp_on(&app));
+    }
+}
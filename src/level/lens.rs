@@ -0,0 +1,219 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::{lighting::LensFocus, player::PlayerMarker};
+
+use super::LevelSystems;
+
+/// How close the player needs to be to a [`Lens`] to pick it up.
+const LENS_REACH: f32 = 12.0;
+
+/// Where a held [`Lens`] floats, relative to the player facing right.
+const LENS_HOLD_OFFSET: Vec2 = Vec2::new(10.0, 8.0);
+
+/// How quickly a held [`Lens`] catches up with the player. Each second, all but
+/// `exp(-LENS_FOLLOW_RATE)` of the distance left is covered.
+const LENS_FOLLOW_RATE: f32 = 10.0;
+
+/// The radius of what a [`Lens`] without a `radius` field covers.
+const DEFAULT_LENS_RADIUS: f32 = 32.0;
+
+/// How much a [`Lens`] without a `gain` field brightens light seen through it.
+const DEFAULT_LENS_GAIN: f32 = 2.0;
+
+/// [`Plugin`] for lenses the player can pick up to bring out details in the light.
+pub struct LensPlugin;
+
+impl Plugin for LensPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity::<LensBundle>("Lens").add_systems(
+            Update,
+            (
+                reset_lenses.in_set(LevelSystems::Reset),
+                hold_lenses.in_set(LevelSystems::Simulation),
+            ),
+        );
+    }
+}
+
+/// [`Component`] for lenses, which the player picks up by walking into them. A held lens floats
+/// next to the player, trailing behind a little when they move, and concentrates the light seen
+/// through it, see [`LensFocus`]. Faint lights that were barely visible show up brightly through
+/// the lens, so details lit by them can be hidden in plain sight. Lenses are dropped back where
+/// they started when the level is reset.
+#[derive(Component, Debug)]
+pub struct Lens {
+    /// The radius of the circle around the lens that its light is concentrated in
+    pub radius: f32,
+    /// How much the light seen through the lens is multiplied by
+    pub gain: f32,
+    pub held: bool,
+    /// Where the lens was before it was picked up, relative to its level
+    home: Vec3,
+}
+
+impl Lens {
+    pub fn new(radius: f32, gain: f32) -> Self {
+        Lens {
+            radius,
+            gain,
+            held: false,
+            home: Vec3::ZERO,
+        }
+    }
+}
+
+impl From<&EntityInstance> for Lens {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        let radius = entity_instance
+            .get_float_field("radius")
+            .copied()
+            .unwrap_or(DEFAULT_LENS_RADIUS);
+        let gain = entity_instance
+            .get_float_field("gain")
+            .copied()
+            .unwrap_or(DEFAULT_LENS_GAIN);
+        Lens::new(radius, gain)
+    }
+}
+
+/// [`Bundle`] that includes all the [`Component`]s needed for a [`Lens`] to function properly.
+#[derive(Bundle, LdtkEntity)]
+pub struct LensBundle {
+    #[from_entity_instance]
+    lens: Lens,
+    #[with(lens_sprite)]
+    sprite: Sprite,
+}
+
+pub fn lens_sprite(entity_instance: &EntityInstance) -> Sprite {
+    Sprite::from_color(
+        Color::srgba(0.75, 0.9, 1.0, 0.6),
+        Vec2::new(entity_instance.width as f32, entity_instance.height as f32),
+    )
+}
+
+/// [`System`] that picks up [`Lens`]es the player walks into, moves the held lens after the
+/// player and points the [`LensFocus`] at it.
+pub fn hold_lenses(
+    q_player: Query<(&GlobalTransform, &Sprite), With<PlayerMarker>>,
+    mut q_lenses: Query<(&mut Lens, &mut Transform, &GlobalTransform)>,
+    mut focus: ResMut<LensFocus>,
+    time: Res<Time>,
+) {
+    let Ok((player_transform, player_sprite)) = q_player.get_single() else {
+        return;
+    };
+    let player_pos = player_transform.translation().xy();
+
+    if !q_lenses.iter().any(|(lens, ..)| lens.held) {
+        if let Some((mut lens, transform, _)) = q_lenses
+            .iter_mut()
+            .find(|(_, _, global)| global.translation().xy().distance(player_pos) <= LENS_REACH)
+        {
+            lens.held = true;
+            lens.home = transform.translation;
+        }
+    }
+
+    let Some((lens, mut transform, global_transform)) =
+        q_lenses.iter_mut().find(|(lens, ..)| lens.held)
+    else {
+        focus.set_if_neq(LensFocus::default());
+        return;
+    };
+
+    let facing = if player_sprite.flip_x { -1.0 } else { 1.0 };
+    let target = player_pos + LENS_HOLD_OFFSET * Vec2::new(facing, 1.0);
+    let pos = global_transform.translation().xy();
+    let follow = 1.0 - (-LENS_FOLLOW_RATE * time.delta_secs()).exp();
+    let new_pos = pos.lerp(target, follow);
+    // lenses are children of their level, so they are moved by the distance to their target
+    transform.translation += (new_pos - pos).extend(0.0);
+
+    focus.set_if_neq(LensFocus {
+        center: new_pos,
+        radius: lens.radius,
+        gain: lens.gain,
+    });
+}
+
+/// [`System`] that drops held [`Lens`]es back where they were picked up when the level is reset.
+pub fn reset_lenses(mut q_lenses: Query<(&mut Lens, &mut Transform)>) {
+    for (mut lens, mut transform) in q_lenses.iter_mut() {
+        if lens.held {
+            lens.held = false;
+            transform.translation = lens.home;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn held_lens_follows_player_smoothly() {
+        let mut app = App::new();
+        app.add_plugins(TransformPlugin)
+            .init_resource::<Time>()
+            .init_resource::<LensFocus>()
+            .add_systems(Update, hold_lenses);
+        let player = app
+            .world_mut()
+            .spawn((
+                PlayerMarker,
+                Sprite::default(),
+                Transform::from_xyz(-100.0, 0.0, 0.0),
+            ))
+            .id();
+        let lens = app
+            .world_mut()
+            .spawn((
+                Lens::new(20.0, 3.0),
+                Transform::from_xyz(40.0, 0.0, 0.0),
+                GlobalTransform::from_xyz(40.0, 0.0, 0.0),
+            ))
+            .id();
+        let step = |app: &mut App, secs: f32| {
+            app.world_mut()
+                .resource_mut::<Time>()
+                .advance_by(Duration::from_secs_f32(secs));
+            app.update();
+        };
+        let lens_pos = |app: &App| app.world().get::<Transform>(lens).unwrap().translation.xy();
+
+        step(&mut app, 0.1);
+        step(&mut app, 0.1);
+        assert!(!app.world().get::<Lens>(lens).unwrap().held);
+        assert_eq!(*app.world().resource::<LensFocus>(), LensFocus::default());
+
+        app.world_mut()
+            .get_mut::<Transform>(player)
+            .unwrap()
+            .translation = Vec3::new(35.0, 0.0, 0.0);
+        step(&mut app, 0.0);
+        step(&mut app, 0.0);
+        assert!(app.world().get::<Lens>(lens).unwrap().held);
+        let focus = *app.world().resource::<LensFocus>();
+        assert_eq!(
+            (focus.center, focus.radius, focus.gain),
+            (lens_pos(&app), 20.0, 3.0)
+        );
+
+        // the lens trails behind the player instead of snapping to them
+        step(&mut app, 0.02);
+        let target = Vec2::new(35.0, 0.0) + LENS_HOLD_OFFSET;
+        let first = lens_pos(&app);
+        assert!(first.distance(target) < Vec2::new(40.0, 0.0).distance(target));
+        assert!(first.distance(target) > 1.0);
+        for _ in 0..60 {
+            step(&mut app, 0.02);
+        }
+        assert!(lens_pos(&app).distance(target) < 0.01);
+        let center = app.world().resource::<LensFocus>().center;
+        assert!(center.distance(lens_pos(&app)) < 1e-4);
+    }
+}
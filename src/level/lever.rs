@@ -0,0 +1,159 @@
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::player::{not_input_locked, PlayerMarker};
+
+use super::{
+    carry_mirror::{carry_mirrors, CarryMirror},
+    sensor::SwitchChangedEvent,
+    LevelSystems,
+};
+
+/// How close the player needs to be to a [`Lever`] to pull it.
+const LEVER_REACH: f32 = 12.0;
+
+/// [`Plugin`] for levers the player pulls to flip a switch.
+pub struct LeverPlugin;
+
+impl Plugin for LeverPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SwitchChangedEvent>().add_systems(
+            Update,
+            (
+                reset_levers.in_set(LevelSystems::Reset),
+                pull_levers
+                    .run_if(not_input_locked)
+                    .before(carry_mirrors)
+                    .in_set(LevelSystems::Simulation),
+            ),
+        );
+    }
+}
+
+/// [`Component`] for levers, placed with the `Lever` `EntityKind` in Ldtk, that flip between on
+/// and off each time the player pulls them with F. Like any other switch, a lever opens and closes
+/// the [`PoweredOccluder`](super::powered_occluder::PoweredOccluder) doors linked to it.
+#[derive(Component, Default, Debug)]
+pub struct Lever {
+    pub is_active: bool,
+}
+
+pub fn spawn_lever(commands: &mut EntityCommands, entity_instance: &EntityInstance) {
+    commands.insert((
+        Lever::default(),
+        Sprite::from_color(
+            lever_color(false),
+            Vec2::new(entity_instance.width as f32, entity_instance.height as f32),
+        ),
+    ));
+}
+
+fn lever_color(is_active: bool) -> Color {
+    if is_active {
+        Color::srgb(0.9, 0.8, 0.3)
+    } else {
+        Color::srgb(0.4, 0.35, 0.3)
+    }
+}
+
+/// [`System`] that pulls the closest [`Lever`] in reach when F is pressed. Pressing F while
+/// carrying a [`CarryMirror`] places the mirror instead.
+pub fn pull_levers(
+    mut q_levers: Query<(Entity, &mut Lever, &GlobalTransform, &mut Sprite)>,
+    q_player: Query<&GlobalTransform, With<PlayerMarker>>,
+    q_mirrors: Query<&CarryMirror>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut ev_switch_changed: EventWriter<SwitchChangedEvent>,
+) {
+    if !keys.just_pressed(KeyCode::KeyF) || q_mirrors.iter().any(|mirror| mirror.carried) {
+        return;
+    }
+    let Ok(player_transform) = q_player.get_single() else {
+        return;
+    };
+    let player_pos = player_transform.translation().xy();
+    let Some((entity, _)) = q_levers
+        .iter()
+        .map(|(entity, _, transform, _)| {
+            (entity, transform.translation().xy().distance(player_pos))
+        })
+        .filter(|(_, distance)| *distance <= LEVER_REACH)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+    else {
+        return;
+    };
+    let Ok((_, mut lever, _, mut sprite)) = q_levers.get_mut(entity) else {
+        return;
+    };
+    lever.is_active = !lever.is_active;
+    sprite.color = lever_color(lever.is_active);
+    ev_switch_changed.send(SwitchChangedEvent {
+        switch: entity,
+        is_active: lever.is_active,
+    });
+}
+
+/// [`System`] that turns every [`Lever`] off when the level is reset.
+pub fn reset_levers(mut q_levers: Query<(&mut Lever, &mut Sprite)>) {
+    for (mut lever, mut sprite) in q_levers.iter_mut() {
+        lever.is_active = false;
+        sprite.color = lever_color(false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pulling_flips_the_closest_lever() {
+        let mut app = App::new();
+        app.init_resource::<ButtonInput<KeyCode>>()
+            .add_event::<SwitchChangedEvent>()
+            .add_systems(Update, pull_levers);
+
+        let mut spawn_at = |x: f32| {
+            app.world_mut()
+                .spawn((
+                    Lever::default(),
+                    Sprite::default(),
+                    GlobalTransform::from_xyz(x, 0.0, 0.0),
+                ))
+                .id()
+        };
+        let near = spawn_at(4.0);
+        let far = spawn_at(10.0);
+        let out_of_reach = spawn_at(-20.0);
+        app.world_mut()
+            .spawn((PlayerMarker, GlobalTransform::default()));
+
+        let pull = |app: &mut App| {
+            let mut keys = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+            keys.release(KeyCode::KeyF);
+            keys.clear();
+            keys.press(KeyCode::KeyF);
+            app.update();
+            app.world_mut()
+                .resource_mut::<ButtonInput<KeyCode>>()
+                .clear();
+            app.world_mut()
+                .resource_mut::<Events<SwitchChangedEvent>>()
+                .drain()
+                .map(|ev| (ev.switch, ev.is_active))
+                .collect::<Vec<_>>()
+        };
+        let active = |app: &App, lever: Entity| app.world().get::<Lever>(lever).unwrap().is_active;
+
+        assert_eq!(pull(&mut app), vec![(near, true)]);
+        assert_eq!(pull(&mut app), vec![(near, false)]);
+        assert!(!active(&app, far));
+        assert!(!active(&app, out_of_reach));
+
+        // holding the key doesn't pull the lever again
+        app.update();
+        assert!(app
+            .world()
+            .resource::<Events<SwitchChangedEvent>>()
+            .is_empty());
+    }
+}
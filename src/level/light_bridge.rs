@@ -0,0 +1,289 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    light::segments::{simulate_light_sources, LightSegment},
+    lighting::{LineLight2d, Occluder2d},
+    shared::GroupLabel,
+};
+
+use super::{cross_point::segment_intersects_rect, LevelSystems};
+
+/// [`Plugin`] for bridges that extend while a light beam powers them.
+pub struct LightBridgePlugin;
+
+impl Plugin for LightBridgePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity::<LightBridgeBundle>("LightBridge")
+            .add_systems(
+                PreUpdate,
+                init_light_bridges.in_set(LevelSystems::Processing),
+            )
+            .add_systems(Update, reset_light_bridges.in_set(LevelSystems::Reset))
+            .add_systems(
+                FixedUpdate,
+                update_light_bridges
+                    .after(simulate_light_sources)
+                    .in_set(LevelSystems::Simulation),
+            );
+    }
+}
+
+/// [`Component`] for bridges that extend one segment at a time while a light beam passes through
+/// their first segment, and retract one segment at a time once the beam is gone. Extended
+/// segments are terrain with an [`Occluder2d`], so the player can walk on them and they block
+/// light. Retracting a segment removes its collider, so a player standing on it falls.
+#[derive(Component, Debug)]
+pub struct LightBridge {
+    pub num_segments: usize,
+    /// Seconds it takes to extend or retract one segment
+    pub segment_time: f32,
+    /// How many segments are currently extended, counted from the anchored end
+    pub extended: usize,
+    /// Time spent on the segment that is currently extending or retracting
+    timer: f32,
+    powered: bool,
+    /// Whether the bridge is anchored at its right end and extends to the left
+    extend_left: bool,
+    half_size: Vec2,
+    segments: Vec<Entity>,
+}
+
+impl LightBridge {
+    /// Advances the bridge by `delta` seconds, returning whether the number of extended segments
+    /// changed. Turning the power on or off starts the next segment from the beginning.
+    pub fn step(&mut self, powered: bool, delta: f32) -> bool {
+        if powered != self.powered {
+            self.powered = powered;
+            self.timer = 0.0;
+        }
+        let target = if powered { self.num_segments } else { 0 };
+        if self.extended == target {
+            self.timer = 0.0;
+            return false;
+        }
+
+        self.timer += delta;
+        let mut changed = false;
+        while self.timer >= self.segment_time && self.extended != target {
+            self.timer -= self.segment_time;
+            if powered {
+                self.extended += 1;
+            } else {
+                self.extended -= 1;
+            }
+            changed = true;
+        }
+        changed
+    }
+
+    pub fn segment_half_size(&self) -> Vec2 {
+        Vec2::new(
+            self.half_size.x / self.num_segments.max(1) as f32,
+            self.half_size.y,
+        )
+    }
+
+    /// The position of segment `index` relative to the center of the bridge, where segment 0 is
+    /// at the anchored end.
+    pub fn segment_offset(&self, index: usize) -> Vec2 {
+        let segment_width = self.segment_half_size().x * 2.0;
+        let from_anchor = -self.half_size.x + segment_width * (index as f32 + 0.5);
+        let sign = if self.extend_left { -1.0 } else { 1.0 };
+        Vec2::new(from_anchor * sign, 0.0)
+    }
+}
+
+impl From<&EntityInstance> for LightBridge {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        let num_segments = *entity_instance
+            .get_int_field("segments")
+            .expect("segments needs to be an int field on all light bridges");
+        let segment_time = *entity_instance
+            .get_float_field("segment_time")
+            .expect("segment_time needs to be a float field on all light bridges");
+        let extend_left = *entity_instance
+            .get_bool_field("extend_left")
+            .expect("extend_left needs to be a bool field on all light bridges");
+
+        LightBridge {
+            num_segments: num_segments.max(1) as usize,
+            segment_time,
+            extended: 0,
+            timer: 0.0,
+            powered: false,
+            extend_left,
+            half_size: Vec2::new(
+                entity_instance.width as f32 / 2.0,
+                entity_instance.height as f32 / 2.0,
+            ),
+            segments: vec![],
+        }
+    }
+}
+
+/// [`Bundle`] that includes all the [`Component`]s needed for a [`LightBridge`] to function
+/// properly.
+#[derive(Bundle, LdtkEntity)]
+pub struct LightBridgeBundle {
+    #[from_entity_instance]
+    light_bridge: LightBridge,
+}
+
+/// [`System`] that spawns the retracted segments of new [`LightBridge`]s.
+pub fn init_light_bridges(
+    mut commands: Commands,
+    mut q_light_bridges: Query<(Entity, &mut LightBridge), Added<LightBridge>>,
+) {
+    for (entity, mut light_bridge) in q_light_bridges.iter_mut() {
+        let half_size = light_bridge.segment_half_size();
+        let segments = (0..light_bridge.num_segments)
+            .map(|index| {
+                commands
+                    .spawn((
+                        Transform::from_translation(light_bridge.segment_offset(index).extend(0.0)),
+                        Sprite::from_color(Color::srgb(0.9, 0.95, 1.2), half_size * 2.0),
+                        Visibility::Hidden,
+                        RigidBody::Fixed,
+                        CollisionGroups::new(GroupLabel::TERRAIN, GroupLabel::ALL),
+                    ))
+                    .set_parent(entity)
+                    .id()
+            })
+            .collect();
+        light_bridge.segments = segments;
+    }
+}
+
+/// Shows the extended segments of a [`LightBridge`] and gives them colliders, and hides and
+/// removes the colliders of the retracted ones.
+fn sync_light_bridge_segments(commands: &mut Commands, light_bridge: &LightBridge) {
+    let half_size = light_bridge.segment_half_size();
+    for (index, segment) in light_bridge.segments.iter().enumerate() {
+        if index < light_bridge.extended {
+            commands.entity(*segment).insert((
+                Visibility::Inherited,
+                Collider::cuboid(half_size.x, half_size.y),
+                Occluder2d::new(half_size.x, half_size.y),
+            ));
+        } else {
+            commands
+                .entity(*segment)
+                .insert(Visibility::Hidden)
+                .remove::<(Collider, Occluder2d)>();
+        }
+    }
+}
+
+/// [`System`] that fully retracts [`LightBridge`]s when the level is reset.
+pub fn reset_light_bridges(mut commands: Commands, mut q_light_bridges: Query<&mut LightBridge>) {
+    for mut light_bridge in q_light_bridges.iter_mut() {
+        light_bridge.extended = 0;
+        light_bridge.timer = 0.0;
+        light_bridge.powered = false;
+        sync_light_bridge_segments(&mut commands, &light_bridge);
+    }
+}
+
+/// [`System`] that powers each [`LightBridge`] with a visible [`LightSegment`] passing through its
+/// first segment, and extends or retracts its segments.
+pub fn update_light_bridges(
+    mut commands: Commands,
+    mut q_light_bridges: Query<(&mut LightBridge, &GlobalTransform)>,
+    q_segments: Query<(&Transform, &Visibility, &LineLight2d), With<LightSegment>>,
+    time: Res<Time>,
+) {
+    let beams: Vec<(Vec2, Vec2)> = q_segments
+        .iter()
+        .filter(|(_, visibility, _)| **visibility != Visibility::Hidden)
+        .map(|(transform, _, line_light)| {
+            let center = transform.translation.xy();
+            let dir = (transform.rotation * Vec3::X).xy();
+            (
+                center - dir * line_light.half_length,
+                center + dir * line_light.half_length,
+            )
+        })
+        .collect();
+
+    for (mut light_bridge, transform) in q_light_bridges.iter_mut() {
+        let anchor = Rect::from_center_half_size(
+            transform.translation().xy() + light_bridge.segment_offset(0),
+            light_bridge.segment_half_size(),
+        );
+        let powered = beams
+            .iter()
+            .any(|(start, end)| segment_intersects_rect(*start, *end, anchor));
+
+        if light_bridge.step(powered, time.delta_secs()) {
+            sync_light_bridge_segments(&mut commands, &light_bridge);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bridge(num_segments: usize, segment_time: f32) -> LightBridge {
+        LightBridge {
+            num_segments,
+            segment_time,
+            extended: 0,
+            timer: 0.0,
+            powered: false,
+            extend_left: false,
+            half_size: Vec2::new(num_segments as f32 * 4.0, 2.0),
+            segments: vec![],
+        }
+    }
+
+    #[test]
+    fn bridge_extends_one_segment_per_segment_time() {
+        let mut light_bridge = bridge(3, 0.25);
+
+        assert!(!light_bridge.step(true, 0.2));
+        assert_eq!(light_bridge.extended, 0);
+        assert!(light_bridge.step(true, 0.1));
+        assert_eq!(light_bridge.extended, 1);
+        assert!(light_bridge.step(true, 0.25));
+        assert_eq!(light_bridge.extended, 2);
+        // a long frame extends several segments, but never past the end of the bridge
+        assert!(light_bridge.step(true, 1.0));
+        assert_eq!(light_bridge.extended, 3);
+        assert!(!light_bridge.step(true, 1.0));
+        assert_eq!(light_bridge.extended, 3);
+    }
+
+    #[test]
+    fn bridge_retracts_when_beam_toggles_off() {
+        let mut light_bridge = bridge(3, 0.25);
+        light_bridge.step(true, 0.5);
+        assert_eq!(light_bridge.extended, 2);
+
+        // time spent extending doesn't carry over to retracting
+        light_bridge.step(true, 0.2);
+        assert!(!light_bridge.step(false, 0.2));
+        assert_eq!(light_bridge.extended, 2);
+        assert!(light_bridge.step(false, 0.05));
+        assert_eq!(light_bridge.extended, 1);
+
+        // beam comes back before the bridge is gone
+        assert!(light_bridge.step(true, 0.25));
+        assert_eq!(light_bridge.extended, 2);
+        light_bridge.step(false, 1.0);
+        assert_eq!(light_bridge.extended, 0);
+    }
+
+    #[test]
+    fn segments_start_at_anchored_end() {
+        let mut light_bridge = bridge(3, 0.25);
+        assert_eq!(light_bridge.segment_half_size(), Vec2::new(4.0, 2.0));
+        assert_eq!(light_bridge.segment_offset(0), Vec2::new(-8.0, 0.0));
+        assert_eq!(light_bridge.segment_offset(2), Vec2::new(8.0, 0.0));
+
+        light_bridge.extend_left = true;
+        assert_eq!(light_bridge.segment_offset(0), Vec2::new(8.0, 0.0));
+    }
+}
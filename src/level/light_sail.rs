@@ -0,0 +1,197 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    config::{Config, LightSailConfig},
+    light::{
+        fog::VolumetricFog,
+        segments::{simulate_light_sources, PrevLightBeamPlayback},
+        LightBeamSource,
+    },
+};
+
+use super::{entity::FixedEntityBundle, push_block::push_block_controller, LevelSystems};
+
+/// [`Plugin`] for sails that are pushed along by the light beams hitting them.
+pub struct LightSailPlugin;
+
+impl Plugin for LightSailPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity::<LightSailBundle>("LightSail")
+            .add_systems(PreUpdate, init_light_sails.in_set(LevelSystems::Processing))
+            .add_systems(Update, reset_light_sails.in_set(LevelSystems::Reset))
+            .add_systems(
+                FixedUpdate,
+                push_light_sails
+                    .after(simulate_light_sources)
+                    .before(PhysicsSet::SyncBackend)
+                    .in_set(LevelSystems::Simulation),
+            );
+    }
+}
+
+/// [`Component`] for sails that accelerate in the direction of the light beams hitting them,
+/// along a single axis. Sails are part of the terrain, so they stop at walls, block light beams,
+/// and can hold down [`PressurePlate`](super::pressure_plate::PressurePlate)s.
+#[derive(Component, Debug)]
+pub struct LightSail {
+    /// The unit vector the sail moves along, either horizontal or vertical
+    pub axis: Vec2,
+    /// The velocity of the sail along its axis, in units per [`FixedUpdate`]
+    pub speed: f32,
+    /// Where the sail was placed in Ldtk, used to put it back when the level is reset
+    start: Vec3,
+}
+
+impl LightSail {
+    /// Advances the speed of the sail by one [`FixedUpdate`], returning how far it should move.
+    /// `push` is the sum of the intensities of the beams hitting the sail, each scaled by how much
+    /// the beam points along the sail's axis. Sails without a push drift to a stop.
+    pub fn step(&mut self, push: f32, config: &LightSailConfig) -> f32 {
+        if push == 0.0 {
+            self.speed *= 1.0 - config.damping;
+        } else {
+            self.speed += push * config.acceleration;
+        }
+        self.speed = self.speed.clamp(-config.max_speed, config.max_speed);
+        self.speed
+    }
+}
+
+impl From<&EntityInstance> for LightSail {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        let axis = entity_instance
+            .get_enum_field("axis")
+            .expect("axis needs to be an enum field on all light sails");
+
+        LightSail {
+            axis: match axis.as_str() {
+                "Horizontal" => Vec2::X,
+                "Vertical" => Vec2::Y,
+                _ => panic!("String {} does not represent a light sail axis", axis),
+            },
+            speed: 0.0,
+            start: Vec3::ZERO,
+        }
+    }
+}
+
+/// [`Bundle`] that includes all the [`Component`]s needed for a [`LightSail`] to function
+/// properly.
+#[derive(Bundle, LdtkEntity)]
+pub struct LightSailBundle {
+    #[from_entity_instance]
+    physics: FixedEntityBundle,
+    #[from_entity_instance]
+    light_sail: LightSail,
+    #[with(push_block_controller)]
+    controller: KinematicCharacterController,
+    #[default]
+    controller_output: KinematicCharacterControllerOutput,
+    #[with(light_sail_sprite)]
+    sprite: Sprite,
+}
+
+pub fn light_sail_sprite(entity_instance: &EntityInstance) -> Sprite {
+    Sprite::from_color(
+        Color::srgb(0.85, 0.8, 0.6),
+        Vec2::new(entity_instance.width as f32, entity_instance.height as f32),
+    )
+}
+
+/// [`System`] that stores the starting position of new [`LightSail`]s.
+pub fn init_light_sails(mut q_light_sails: Query<(&mut LightSail, &Transform), Added<LightSail>>) {
+    for (mut light_sail, transform) in q_light_sails.iter_mut() {
+        light_sail.start = transform.translation;
+    }
+}
+
+/// [`System`] that moves [`LightSail`]s back to where they started when the level is reset.
+pub fn reset_light_sails(mut q_light_sails: Query<(&mut LightSail, &mut Transform)>) {
+    for (mut light_sail, mut transform) in q_light_sails.iter_mut() {
+        transform.translation = light_sail.start;
+        light_sail.speed = 0.0;
+    }
+}
+
+/// [`System`] that pushes each [`LightSail`] in the direction of the beams currently hitting it.
+/// Sails that were blocked during the last physics step lose all their speed.
+pub fn push_light_sails(
+    mut q_light_sails: Query<(
+        Entity,
+        &mut LightSail,
+        &mut KinematicCharacterController,
+        &KinematicCharacterControllerOutput,
+    )>,
+    q_light_sources: Query<(&LightBeamSource, &PrevLightBeamPlayback)>,
+    config: Res<Config>,
+    fog: Res<VolumetricFog>,
+) {
+    for (entity, mut light_sail, mut controller, output) in q_light_sails.iter_mut() {
+        if (output.desired_translation - output.effective_translation).length() > 0.01 {
+            light_sail.speed = 0.0;
+        }
+
+        let mut push = 0.0;
+        for (source, prev_playback) in q_light_sources.iter() {
+            let mut prev_point = source.start_pos;
+            for intersection in prev_playback.intersections.iter().flatten() {
+                if intersection.entity == entity {
+                    let dir = (intersection.point - prev_point).normalize_or_zero();
+                    push += dir.dot(light_sail.axis)
+                        * source.color.beam_intensity()
+                        * prev_playback.received_intensity(source, intersection.time, &fog);
+                }
+                prev_point = intersection.point;
+            }
+        }
+
+        let distance = light_sail.step(push, &config.light_sail_config);
+        controller.translation = Some(light_sail.axis * distance);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::light::LightColor;
+
+    fn sail() -> LightSail {
+        LightSail {
+            axis: Vec2::X,
+            speed: 0.0,
+            start: Vec3::ZERO,
+        }
+    }
+
+    #[test]
+    fn sustained_beam_moves_sail_predictably() {
+        let config = LightSailConfig::default();
+        let push = LightColor::Green.beam_intensity();
+        let mut light_sail = sail();
+
+        let steps = 10;
+        let distance: f32 = (0..steps).map(|_| light_sail.step(push, &config)).sum();
+
+        // speed grows by the same amount every step, so the distance is a triangular number
+        let expected = push * config.acceleration * (steps * (steps + 1) / 2) as f32;
+        assert!(expected < config.max_speed * steps as f32);
+        assert!((distance - expected).abs() < 1e-4);
+    }
+
+    #[test]
+    fn sail_drifts_to_a_stop_without_beam() {
+        let config = LightSailConfig::default();
+        let mut light_sail = sail();
+        light_sail.speed = config.max_speed;
+
+        let mut prev_speed = light_sail.speed;
+        for _ in 0..200 {
+            let speed = light_sail.step(0.0, &config);
+            assert!(speed <= prev_speed);
+            prev_speed = speed;
+        }
+        assert!(prev_speed < 0.01);
+    }
+}
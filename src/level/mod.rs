@@ -1,21 +1,60 @@
 use std::time::Duration;
 
+use aimable_emitter::AimableEmitterPlugin;
+use ambience::{level_ambience, LevelAmbiencePlugin};
+use aperture::AperturePlugin;
+use beam_toggle::BeamTogglePlugin;
 use bevy::{ecs::system::SystemId, prelude::*};
 use bevy_ecs_ldtk::{ldtk::Level, prelude::*, systems::process_ldtk_levels, LevelIid};
+use bumpy_wall::BumpyWallPlugin;
+use carry_mirror::CarryMirrorPlugin;
+use caustics::CausticsPlugin;
+use charge_light::ChargeLightPlugin;
+use checkpoint::CheckpointPlugin;
+use conduit::ConduitPlugin;
+use cross_point::CrossPointPlugin;
 use egg::EggPlugin;
+use entity_kind::EntityKindPlugin;
 use enum_map::{enum_map, EnumMap};
+use forbidden_sensor::ForbiddenSensorPlugin;
+use grid::GridConfigPlugin;
+use lamp::BeamLampPlugin;
+use lens::LensPlugin;
+use lever::LeverPlugin;
+use light_bridge::LightBridgePlugin;
+use light_sail::LightSailPlugin;
 use merge_tile::spawn_merged_tiles;
+use occluder::TerrainOccluderPlugin;
+use palette::LightPalettePlugin;
+use powered_occluder::PoweredOccluderPlugin;
+use pressure_plate::PressurePlatePlugin;
+use prism::PrismPlugin;
+use push_block::PushBlockPlugin;
+use rating::LevelRatingPlugin;
+use restart::LevelRestartPlugin;
+use rng::LevelRngPlugin;
+use room::RoomPlugin;
+use searchlight::SearchlightPlugin;
 use semisolid::SemiSolidPlugin;
 use sensor::LightSensorPlugin;
+use sequence_switch::SequenceSwitchPlugin;
 use shard::CrystalShardPlugin;
+use solidity::SolidityPlugin;
+use streaming::LevelStreamingPlugin;
+use surge::PowerSurgePlugin;
+use torch::TorchPlugin;
+use trigger_zone::TriggerZonePlugin;
 
 use crate::{
     camera::{
-        camera_position_from_level, CameraControlType, CameraMoveEvent, CAMERA_ANIMATION_SECS,
+        camera_position_from_level_with_scale, level_camera_scale, CameraControlType,
+        CameraMoveEvent, CAMERA_ANIMATION_SECS,
     },
+    config::Config,
     level_select::handle_level_selection,
     light::LightColor,
-    player::{LdtkPlayerBundle, PlayerMarker},
+    pause::not_paused,
+    player::{kill::RespawnGlide, win::WinPose, LdtkPlayerBundle, PlayerMarker},
     shared::{AnimationState, GameState, ResetLevel},
     sound::{BgmTrack, ChangeBgmEvent},
 };
@@ -24,17 +63,57 @@ use entity::SpikeBundle;
 use setup::LevelSetupPlugin;
 use start_flag::{init_start_marker, StartFlagBundle};
 use walls::{Wall, WallBundle};
+use water::WaterPlugin;
+use weak_panel::WeakPanelPlugin;
 
+pub mod aimable_emitter;
+pub mod ambience;
+pub mod aperture;
+pub mod beam_toggle;
+mod bumpy_wall;
+pub mod carry_mirror;
+mod caustics;
+pub mod charge_light;
+pub mod checkpoint;
+pub mod conduit;
+pub mod cross_point;
 pub mod crystal;
 mod egg;
 pub mod entity;
+pub mod entity_kind;
+pub mod forbidden_sensor;
+pub mod grid;
+pub mod lamp;
+pub mod lens;
+pub mod lever;
+pub mod light_bridge;
+pub mod light_sail;
 mod merge_tile;
+pub mod occluder;
+pub mod palette;
+pub mod powered_occluder;
+pub mod pressure_plate;
+pub mod prism;
+pub mod push_block;
+pub mod rating;
+pub mod restart;
+pub mod rng;
+pub mod room;
+pub mod searchlight;
 mod semisolid;
 pub mod sensor;
-mod setup;
+pub mod sequence_switch;
+pub mod setup;
 pub mod shard;
+pub mod solidity;
 pub mod start_flag;
+pub mod streaming;
+pub mod surge;
+pub mod torch;
+pub mod trigger_zone;
 mod walls;
+pub mod water;
+pub mod weak_panel;
 
 /// [`Plugin`] that handles everything related to the level.
 pub struct LevelManagementPlugin;
@@ -43,11 +122,49 @@ impl Plugin for LevelManagementPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(LdtkPlugin)
             .add_plugins(LevelSetupPlugin)
+            .add_plugins(LevelRngPlugin)
+            .add_plugins(GridConfigPlugin)
             .add_plugins(CrystalPlugin)
             .add_plugins(CrystalShardPlugin)
             .add_plugins(LightSensorPlugin)
             .add_plugins(SemiSolidPlugin)
             .add_plugins(EggPlugin)
+            .add_plugins(BeamLampPlugin)
+            .add_plugins(LightPalettePlugin)
+            .add_plugins(PushBlockPlugin)
+            .add_plugins(PressurePlatePlugin)
+            .add_plugins(SearchlightPlugin)
+            .add_plugins(LevelRestartPlugin)
+            .add_plugins(BumpyWallPlugin)
+            .add_plugins(TerrainOccluderPlugin)
+            .add_plugins(LightSailPlugin)
+            .add_plugins(CrossPointPlugin)
+            .add_plugins(TriggerZonePlugin)
+            .add_plugins(LevelRatingPlugin)
+            .add_plugins(AperturePlugin)
+            .add_plugins(AimableEmitterPlugin)
+            .add_plugins(EntityKindPlugin)
+            .add_plugins(CheckpointPlugin)
+            .add_plugins(LeverPlugin)
+            .add_plugins(LightBridgePlugin)
+            .add_plugins(CarryMirrorPlugin)
+            .add_plugins(SequenceSwitchPlugin)
+            .add_plugins(CausticsPlugin)
+            .add_plugins(PrismPlugin)
+            .add_plugins(ConduitPlugin)
+            .add_plugins(TorchPlugin)
+            .add_plugins(LensPlugin)
+            .add_plugins(BeamTogglePlugin)
+            .add_plugins(WeakPanelPlugin)
+            .add_plugins(PoweredOccluderPlugin)
+            .add_plugins(WaterPlugin)
+            .add_plugins(ChargeLightPlugin)
+            .add_plugins(SolidityPlugin)
+            .add_plugins(LevelAmbiencePlugin)
+            .add_plugins(ForbiddenSensorPlugin)
+            .add_plugins(RoomPlugin)
+            .add_plugins(PowerSurgePlugin)
+            .add_plugins(LevelStreamingPlugin)
             .init_resource::<CurrentLevel>()
             .register_ldtk_entity::<LdtkPlayerBundle>("Lyra")
             .register_ldtk_entity::<StartFlagBundle>("Start")
@@ -85,12 +202,14 @@ impl Plugin for LevelManagementPlugin {
             .configure_sets(
                 Update,
                 LevelSystems::Simulation
-                    .run_if(in_state(GameState::Playing).or(in_state(AnimationState::Shard))),
+                    .run_if(in_state(GameState::Playing).or(in_state(AnimationState::Shard)))
+                    .run_if(not_paused),
             )
             .configure_sets(
                 FixedUpdate,
                 LevelSystems::Simulation
-                    .run_if(in_state(GameState::Playing).or(in_state(AnimationState::Shard))),
+                    .run_if(in_state(GameState::Playing).or(in_state(AnimationState::Shard)))
+                    .run_if(not_paused),
             );
     }
 }
@@ -101,6 +220,17 @@ pub struct CurrentLevel {
     pub level_iid: LevelIid,
     pub level_box: Rect,
     pub allowed_colors: EnumMap<LightColor, bool>,
+    /// The music or ambience the level wants, see [`LevelAudioEvent`](ambience::LevelAudioEvent)
+    pub ambience_id: Option<String>,
+    /// The zoom the level wants instead of the one in the [`Config`], from its `CameraZoom` field
+    pub camera_zoom: Option<f32>,
+}
+
+impl CurrentLevel {
+    /// The scale of the camera's projection in this level, see [`level_camera_scale`].
+    pub fn camera_scale(&self, default_zoom: f32) -> f32 {
+        level_camera_scale(self.level_box, self.camera_zoom.unwrap_or(default_zoom))
+    }
 }
 
 /// [`SystemSet`] used to distinguish different types of systems
@@ -136,19 +266,24 @@ pub fn level_box_from_level(level: &Level) -> Rect {
 /// [`System`] that will run on [`Update`] to check if the Player has moved to another level. If
 /// the player has, then a MoveCameraEvent is sent. After the animation is finished, the Camera
 /// handling code will send a LevelSwitch event that will notify other systems to cleanup the
-/// levels.
+/// levels. Leaving a level while playing completes it, and the camera only moves once the
+/// player's [`WinPose`] ends.
 #[allow(clippy::too_many_arguments)]
 pub fn switch_level(
-    q_player: Query<&Transform, With<PlayerMarker>>,
+    // gliding back to a checkpoint shouldn't pass through other levels
+    q_player: Query<&Transform, (With<PlayerMarker>, Without<RespawnGlide>)>,
     mut level_selection: ResMut<LevelSelection>,
     ldtk_projects: Query<&LdtkProjectHandle>,
     ldtk_project_assets: Res<Assets<LdtkProject>>,
+    game_state: Res<State<GameState>>,
     mut next_game_state: ResMut<NextState<GameState>>,
     mut next_anim_state: ResMut<NextState<AnimationState>>,
     mut current_level: ResMut<CurrentLevel>,
     on_level_switch_finish_cb: Local<OnFinishLevelSwitchCallback>,
     mut ev_move_camera: EventWriter<CameraMoveEvent>,
     mut ev_level_switch: EventWriter<ResetLevel>,
+    mut win_pose: ResMut<WinPose>,
+    config: Res<Config>,
 ) {
     let Ok(player_transform) = q_player.get_single() else {
         return;
@@ -164,22 +299,33 @@ pub fn switch_level(
 
         if level_box.contains(player_transform.translation.xy()) {
             if current_level.level_iid.as_str() != level.iid {
+                let camera_zoom = level.get_float_field("CameraZoom").ok().copied();
+                let camera_scale =
+                    level_camera_scale(level_box, camera_zoom.unwrap_or(config.camera_config.zoom));
                 // relies on camera to reset the state back to switching??
                 if !current_level.level_iid.to_string().is_empty() {
                     next_game_state.set(GameState::Animating);
-                    next_anim_state.set(AnimationState::Switch);
 
-                    ev_move_camera.send(CameraMoveEvent {
-                        to: camera_position_from_level(
+                    let move_camera = CameraMoveEvent {
+                        to: camera_position_from_level_with_scale(
                             level_box,
                             player_transform.translation.xy(),
+                            camera_scale,
                         ),
                         variant: CameraControlType::Animated {
                             duration: Duration::from_secs_f32(CAMERA_ANIMATION_SECS),
                             callback: Some(on_level_switch_finish_cb.0),
                             ease_fn: EaseFunction::SineInOut,
                         },
-                    });
+                    };
+                    let win_pose_secs = config.level_config.win_pose_secs;
+                    if *game_state.get() == GameState::Playing && win_pose_secs > 0.0 {
+                        next_anim_state.set(AnimationState::Win);
+                        win_pose.start(win_pose_secs, move_camera);
+                    } else {
+                        next_anim_state.set(AnimationState::Switch);
+                        ev_move_camera.send(move_camera);
+                    }
                 } else {
                     ev_level_switch.send(ResetLevel::Switching);
                 }
@@ -198,6 +344,8 @@ pub fn switch_level(
                     level_iid: LevelIid::new(level.iid.clone()),
                     level_box,
                     allowed_colors: allowed_colors_map,
+                    ambience_id: level_ambience(level),
+                    camera_zoom,
                 };
                 *level_selection = LevelSelection::iid(current_level.level_iid.clone());
             }
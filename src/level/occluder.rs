@@ -0,0 +1,373 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    lighting::{LightDepth, Occluder2d, Occluder2dGroups, Occluder2dPolygon},
+    particle::dust::DustSurface,
+    shared::GroupLabel,
+};
+
+use super::{
+    merge_tile::{spawn_merged_tiles, MergedTile},
+    walls::Wall,
+    LevelSystems,
+};
+
+/// The Terrain IntGrid values of tiles that cast shadows, and the [`Occluder2dGroups`] their
+/// shadows belong to. Crystals aren't listed, since they add and remove their own occluders when
+/// they toggle.
+const TERRAIN_OCCLUDERS: [(i32, Occluder2dGroups); 1] = [(1, Occluder2dGroups::ALL)];
+
+/// The size of a cell of the Ldtk grid that the points of a [`PolygonWall`] snap to.
+pub const LDTK_GRID_SIZE: f32 = 8.0;
+
+/// [`Plugin`] that gives terrain the player collides with an [`Occluder2d`] matching its collider,
+/// so that shadows always line up with the level's collision. Walls painted on the Background
+/// layer in Ldtk get an occluder in the [`LightDepth::Background`] instead, see
+/// [`BackgroundWall`].
+pub struct TerrainOccluderPlugin;
+
+impl Plugin for TerrainOccluderPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity::<PolygonWallBundle>("PolygonWall")
+            .register_ldtk_int_cell_for_layer::<BackgroundWallBundle>("Background", 1)
+            .add_systems(
+                PreUpdate,
+                (
+                    add_terrain_occluders.after(spawn_merged_tiles::<Wall>),
+                    add_polygon_walls,
+                    spawn_merged_tiles::<BackgroundWall>,
+                )
+                    .in_set(LevelSystems::Processing),
+            );
+    }
+}
+
+/// [`Component`] that holds the Terrain IntGrid value of a merged terrain collider.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct TerrainTile(pub i32);
+
+/// The [`Occluder2dGroups`] of the shadow cast by Terrain tiles with the given IntGrid value, or
+/// [`None`] if they don't cast one.
+pub fn terrain_occluder_groups(value: i32) -> Option<Occluder2dGroups> {
+    TERRAIN_OCCLUDERS
+        .iter()
+        .find(|(occluder_value, _)| *occluder_value == value)
+        .map(|(_, groups)| *groups)
+}
+
+/// [`System`] that adds [`Occluder2d`]s to newly spawned [`TerrainTile`]s. Merged tiles are
+/// respawned along with their level, so this also covers levels that are reloaded or restarted.
+pub fn add_terrain_occluders(
+    mut commands: Commands,
+    q_terrain: Query<(Entity, &TerrainTile, &Collider), (Added<TerrainTile>, Without<Occluder2d>)>,
+) {
+    for (entity, terrain, collider) in q_terrain.iter() {
+        let Some(groups) = terrain_occluder_groups(terrain.0) else {
+            continue;
+        };
+        let Some(cuboid) = collider.as_cuboid() else {
+            warn!("Terrain tile {} should have a cuboid collider", terrain.0);
+            continue;
+        };
+        let half_extents = cuboid.half_extents();
+        commands
+            .entity(entity)
+            .insert((Occluder2d::new(half_extents.x, half_extents.y), groups));
+    }
+}
+
+/// [`Component`] for the walls painted on the Background IntGrid layer in Ldtk, which are part of
+/// the distant background. They don't collide with anything, and only shadow
+/// [`LightDepth::Background`] lights.
+#[derive(Default, Component)]
+pub struct BackgroundWall;
+
+/// Background wall [`Bundle`] spawned by Ldtk.
+#[derive(Default, Bundle, LdtkIntCell)]
+pub struct BackgroundWallBundle {
+    background_wall: BackgroundWall,
+}
+
+impl MergedTile for BackgroundWall {
+    type CompareData = ();
+
+    fn bundle(
+        commands: &mut EntityCommands,
+        center: Vec2,
+        half_extent: Vec2,
+        _compare_data: &Self::CompareData,
+    ) {
+        commands.insert((
+            Occluder2d::new(half_extent.x, half_extent.y),
+            LightDepth::Background,
+            Transform::from_xyz(center.x, center.y, 0.),
+        ));
+    }
+
+    fn compare_data(&self) -> Self::CompareData {}
+}
+
+/// [`Component`] for slanted or uneven walls drawn in Ldtk as a polygon, which collide and cast
+/// shadows along the polygon's edges instead of a rectangle. Walls with the optional `background`
+/// field checked are part of the background, so they only cast shadows in the
+/// [`LightDepth::Background`] and don't collide with anything.
+#[derive(Component, Clone, Debug)]
+pub struct PolygonWall {
+    /// The corners of the wall relative to the entity
+    pub points: Vec<Vec2>,
+    pub depth: LightDepth,
+}
+
+impl From<&EntityInstance> for PolygonWall {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        let points = entity_instance
+            .get_points_field("points")
+            .expect("points needs to be a point array field on all polygon walls");
+
+        // points are grid cells counted from the top left of the level, and each snaps to the top
+        // left corner of its cell
+        let size = Vec2::new(entity_instance.width as f32, entity_instance.height as f32);
+        let center =
+            entity_instance.px.as_vec2() + (Vec2::splat(0.5) - entity_instance.pivot) * size;
+        let points = points
+            .iter()
+            .flatten()
+            .map(|point| {
+                let offset = point.as_vec2() * LDTK_GRID_SIZE - center;
+                Vec2::new(offset.x, -offset.y)
+            })
+            .collect();
+
+        let depth = match entity_instance.get_bool_field("background") {
+            Ok(true) => LightDepth::Background,
+            _ => LightDepth::Foreground,
+        };
+
+        PolygonWall { points, depth }
+    }
+}
+
+/// [`Bundle`] spawned by Ldtk for a [`PolygonWall`]. Its collider and occluder are added by
+/// [`add_polygon_walls`].
+#[derive(Bundle, LdtkEntity)]
+pub struct PolygonWallBundle {
+    #[from_entity_instance]
+    polygon_wall: PolygonWall,
+}
+
+/// [`System`] that gives newly spawned [`PolygonWall`]s a collider along their edges and an
+/// [`Occluder2dPolygon`]. Background walls only get the occluder. Walls whose points don't make a
+/// polygon are left out of the level.
+pub fn add_polygon_walls(
+    mut commands: Commands,
+    q_polygon_walls: Query<(Entity, &PolygonWall), Added<PolygonWall>>,
+) {
+    for (entity, polygon_wall) in q_polygon_walls.iter() {
+        let Some(polygon) = Occluder2dPolygon::new(&polygon_wall.points) else {
+            warn!(
+                "Polygon wall with points {:?} has no area or crosses itself",
+                polygon_wall.points
+            );
+            continue;
+        };
+
+        let half_size = polygon.half_size();
+        if polygon_wall.depth == LightDepth::Background {
+            commands.entity(entity).insert((
+                Occluder2d::new(half_size.x, half_size.y),
+                polygon,
+                LightDepth::Background,
+            ));
+            continue;
+        }
+
+        let points = polygon.points().to_vec();
+        let edges = (0..points.len() as u32)
+            .map(|i| [i, (i + 1) % points.len() as u32])
+            .collect();
+        commands.entity(entity).insert((
+            Collider::polyline(points, Some(edges)),
+            CollisionGroups::new(
+                GroupLabel::TERRAIN,
+                GroupLabel::PLAYER_COLLIDER
+                    | GroupLabel::LIGHT_RAY
+                    | GroupLabel::WHITE_RAY
+                    | GroupLabel::STRAND
+                    | GroupLabel::BLUE_RAY,
+            ),
+            RigidBody::Fixed,
+            Occluder2d::new(half_size.x, half_size.y),
+            polygon,
+            DustSurface::Wall,
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_ecs_ldtk::ldtk::{FieldInstance, FieldValue};
+
+    use super::*;
+
+    #[test]
+    fn wall_blocks_player_and_casts_shadow() {
+        let mut app = App::new();
+        app.add_systems(Update, add_terrain_occluders);
+
+        let wall = app.world_mut().spawn_empty().id();
+        let mut commands = app.world_mut().commands();
+        Wall::bundle(
+            &mut commands.entity(wall),
+            Vec2::new(16.0, 8.0),
+            Vec2::new(8.0, 4.0),
+            &1,
+        );
+        app.world_mut().flush();
+        app.update();
+
+        let groups = app.world().get::<CollisionGroups>(wall).unwrap();
+        assert!(groups.filters.contains(GroupLabel::PLAYER_COLLIDER));
+
+        let occluder = app.world().get::<Occluder2d>(wall).unwrap();
+        assert_eq!(occluder.half_size, Vec2::new(8.0, 4.0));
+        assert!(app.world().get::<Occluder2dGroups>(wall) == Some(&Occluder2dGroups::ALL));
+    }
+
+    #[test]
+    fn only_mapped_values_cast_shadows() {
+        assert!(terrain_occluder_groups(1).is_some());
+        // spikes and semi solid platforms
+        assert!(terrain_occluder_groups(2).is_none());
+        assert!(terrain_occluder_groups(15).is_none());
+    }
+
+    #[test]
+    fn beams_hit_polygon_wall_edges() {
+        let mut app = App::new();
+        app.add_systems(Update, add_polygon_walls);
+        // a ramp going up to the right
+        let ramp = app
+            .world_mut()
+            .spawn(PolygonWall {
+                points: vec![
+                    Vec2::new(-8.0, -8.0),
+                    Vec2::new(8.0, -8.0),
+                    Vec2::new(8.0, 8.0),
+                ],
+                depth: LightDepth::Foreground,
+            })
+            .id();
+        let sliver = app
+            .world_mut()
+            .spawn(PolygonWall {
+                points: vec![Vec2::ZERO, Vec2::X, Vec2::new(2.0, 0.0)],
+                depth: LightDepth::Foreground,
+            })
+            .id();
+        app.update();
+
+        assert!(app.world().get::<Occluder2dPolygon>(ramp).is_some());
+        let occluder = app.world().get::<Occluder2d>(ramp).unwrap();
+        assert_eq!(occluder.half_size, Vec2::splat(8.0));
+        assert!(app.world().get::<Collider>(sliver).is_none());
+
+        // a beam shining right hits the slope instead of the bounding box of the ramp
+        let collider = app.world().get::<Collider>(ramp).unwrap();
+        let toi = collider.cast_ray(
+            Vec2::ZERO,
+            0.0,
+            Vec2::new(-20.0, 4.0),
+            Vec2::X,
+            100.0,
+            false,
+        );
+        assert!(toi.is_some_and(|toi| (toi - 24.0).abs() < 1e-4));
+        // and misses above it
+        let toi = collider.cast_ray(
+            Vec2::ZERO,
+            0.0,
+            Vec2::new(-20.0, 10.0),
+            Vec2::X,
+            100.0,
+            false,
+        );
+        assert!(toi.is_none());
+    }
+
+    #[test]
+    fn background_walls_only_shadow_background_lights() {
+        let mut app = App::new();
+        app.add_systems(Update, add_polygon_walls);
+        let wall = app.world_mut().spawn_empty().id();
+        let mut commands = app.world_mut().commands();
+        BackgroundWall::bundle(
+            &mut commands.entity(wall),
+            Vec2::new(16.0, 8.0),
+            Vec2::new(8.0, 4.0),
+            &(),
+        );
+        app.world_mut().flush();
+        let polygon_wall = app
+            .world_mut()
+            .spawn(PolygonWall {
+                points: vec![
+                    Vec2::new(-8.0, -8.0),
+                    Vec2::new(8.0, -8.0),
+                    Vec2::new(8.0, 8.0),
+                ],
+                depth: LightDepth::Background,
+            })
+            .id();
+        app.update();
+
+        for entity in [wall, polygon_wall] {
+            assert!(app.world().get::<Occluder2d>(entity).is_some());
+            assert_eq!(
+                app.world().get::<LightDepth>(entity),
+                Some(&LightDepth::Background)
+            );
+            // the player walks in front of them
+            assert!(app.world().get::<Collider>(entity).is_none());
+        }
+        assert_eq!(
+            app.world().get::<Transform>(wall).unwrap().translation,
+            Vec3::new(16.0, 8.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn polygon_wall_points_are_relative_to_entity() {
+        // a 16x16 entity with its pivot in the top left, at the top left of the level
+        let entity_instance = EntityInstance {
+            px: IVec2::ZERO,
+            pivot: Vec2::ZERO,
+            width: 16,
+            height: 16,
+            field_instances: vec![FieldInstance {
+                identifier: "points".into(),
+                tile: None,
+                field_instance_type: "Array<Point>".into(),
+                value: FieldValue::Points(vec![
+                    Some(IVec2::new(0, 0)),
+                    Some(IVec2::new(2, 2)),
+                    Some(IVec2::new(0, 2)),
+                ]),
+                def_uid: 0,
+                real_editor_values: vec![],
+            }],
+            ..default()
+        };
+        let polygon_wall = PolygonWall::from(&entity_instance);
+        assert_eq!(
+            polygon_wall.points,
+            vec![
+                Vec2::new(-8.0, 8.0),
+                Vec2::new(8.0, -8.0),
+                Vec2::new(-8.0, -8.0)
+            ]
+        );
+        assert_eq!(polygon_wall.depth, LightDepth::Foreground);
+    }
+}
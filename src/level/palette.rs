@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::{
+    config::Config,
+    lighting::{LightDepth, LineLight2d},
+};
+
+/// The color used by [`PaletteLight`]s whose name is not in the [`LightPalette`]. Bright magenta
+/// so that typos are easy to spot.
+const FALLBACK_PALETTE_COLOR: Vec4 = Vec4::new(1.0, 0.0, 1.0, 1.0);
+
+/// [`Plugin`] that colors [`PaletteLight`]s using the named colors in the [`LightPalette`].
+pub struct LightPalettePlugin;
+
+impl Plugin for LightPalettePlugin {
+    fn build(&self, app: &mut App) {
+        let palette = LightPalette::from_config(app.world().resource::<Config>());
+        app.insert_resource(palette)
+            .register_ldtk_entity::<PaletteLightBundle>("PaletteLight")
+            .add_systems(Update, apply_light_palette);
+    }
+}
+
+/// [`Resource`] mapping names like `"warm_torch"` to a light color, with the intensity stored in
+/// `w`. Loaded from the `light_palette` section of `Lightborne.toml`, and changing it recolors
+/// every [`PaletteLight`] that uses the changed name.
+#[derive(Resource, Default, Debug)]
+pub struct LightPalette {
+    colors: HashMap<String, Vec4>,
+}
+
+impl LightPalette {
+    pub fn from_config(config: &Config) -> Self {
+        LightPalette {
+            colors: config
+                .light_palette
+                .iter()
+                .map(|(name, color)| {
+                    (
+                        name.clone(),
+                        Vec3::from(color.color).extend(color.intensity),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Vec4> {
+        self.colors.get(name).copied()
+    }
+
+    pub fn set(&mut self, name: impl Into<String>, color: Vec4) {
+        self.colors.insert(name.into(), color);
+    }
+
+    /// Looks up the color for `name`, warning and returning a fallback color if it is missing.
+    pub fn resolve(&self, name: &str) -> Vec4 {
+        self.get(name).unwrap_or_else(|| {
+            warn!("Light palette has no color named \"{}\"", name);
+            FALLBACK_PALETTE_COLOR
+        })
+    }
+}
+
+/// [`Component`] for lights whose [`LineLight2d`] color is taken from the [`LightPalette`].
+#[derive(Component, Default, Debug)]
+pub struct PaletteLight {
+    pub name: String,
+}
+
+impl From<&EntityInstance> for PaletteLight {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        let name = entity_instance
+            .get_string_field("palette")
+            .expect("palette needs to be a string field on all palette lights");
+        PaletteLight { name: name.clone() }
+    }
+}
+
+/// [`Bundle`] for point lights placed in Ldtk, which are colored by the [`LightPalette`].
+#[derive(Bundle, LdtkEntity)]
+pub struct PaletteLightBundle {
+    #[from_entity_instance]
+    palette_light: PaletteLight,
+    #[with(palette_point_light)]
+    lighting: LineLight2d,
+    #[with(palette_light_depth)]
+    depth: LightDepth,
+}
+
+pub fn palette_point_light(entity_instance: &EntityInstance) -> LineLight2d {
+    let radius = *entity_instance
+        .get_float_field("radius")
+        .expect("radius needs to be a float field on all palette lights");
+
+    // colored by `apply_light_palette` once spawned
+    LineLight2d::point(Vec4::ZERO, radius, 0.008)
+}
+
+/// Palette lights with the optional `background` field checked light up the background, and are
+/// only shadowed by background occluders.
+pub fn palette_light_depth(entity_instance: &EntityInstance) -> LightDepth {
+    match entity_instance.get_bool_field("background") {
+        Ok(true) => LightDepth::Background,
+        _ => LightDepth::Foreground,
+    }
+}
+
+/// [`System`] that sets the color of new [`PaletteLight`]s, and of every [`PaletteLight`] when
+/// the [`LightPalette`] changes.
+pub fn apply_light_palette(
+    palette: Res<LightPalette>,
+    mut q_lights: Query<(Ref<PaletteLight>, &mut LineLight2d)>,
+) {
+    for (palette_light, mut light) in q_lights.iter_mut() {
+        if palette.is_changed() || palette_light.is_changed() {
+            light.color = palette.resolve(&palette_light.name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn light_palette_resolves_names() {
+        let mut palette = LightPalette::default();
+        palette.set("warm_torch", Vec4::new(1.0, 0.6, 0.3, 0.8));
+
+        assert_eq!(
+            palette.get("warm_torch"),
+            Some(Vec4::new(1.0, 0.6, 0.3, 0.8))
+        );
+        assert_eq!(palette.resolve("warm_torch"), Vec4::new(1.0, 0.6, 0.3, 0.8));
+        assert_eq!(palette.get("cold_moon"), None);
+        assert_eq!(palette.resolve("cold_moon"), FALLBACK_PALETTE_COLOR);
+    }
+
+    #[test]
+    fn light_palette_loads_from_config() {
+        let palette = LightPalette::from_config(&Config::default());
+        assert!(palette.get("warm_torch").is_some());
+        assert!(palette.get("cold_moon").is_some());
+    }
+}
@@ -0,0 +1,264 @@
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use bevy_ecs_ldtk::{prelude::*, EntityIid};
+use bevy_rapier2d::prelude::*;
+
+use crate::{lighting::Occluder2d, player::PlayerMarker, shared::GroupLabel};
+
+use super::{sensor::SwitchChangedEvent, LevelSystems};
+
+/// [`Plugin`] for "hard light" walls that only exist while a switch powers them.
+pub struct PoweredOccluderPlugin;
+
+impl Plugin for PoweredOccluderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SwitchChangedEvent>()
+            .register_ldtk_entity::<PoweredOccluderBundle>("PoweredOccluder")
+            .add_systems(Update, reset_powered_occluders.in_set(LevelSystems::Reset))
+            .add_systems(
+                FixedUpdate,
+                update_powered_occluders
+                    .after(PhysicsSet::Writeback)
+                    .in_set(LevelSystems::Simulation),
+            );
+    }
+}
+
+/// [`Component`] for walls, placed in Ldtk, that are solid while the switch they are linked to is
+/// active and transparent otherwise. A solid wall is terrain with an [`Occluder2d`], so it blocks
+/// both the player and light, and a transparent wall has neither. A wall that gets powered while
+/// the player is inside of it waits for the player to leave before materializing, so the player
+/// can never get stuck inside of it.
+#[derive(Component, Debug)]
+pub struct PoweredOccluder {
+    /// The [`EntityIid`] of the switch that powers the wall
+    pub switch: String,
+    pub powered: bool,
+    /// Whether the wall is currently materialized
+    pub solid: bool,
+    pub half_size: Vec2,
+}
+
+impl PoweredOccluder {
+    /// Updates the wall with whether the player is currently inside of it, returning the new solid
+    /// state if it changed. Losing power dematerializes the wall right away.
+    pub fn update(&mut self, player_inside: bool) -> Option<bool> {
+        let solid = self.powered && (self.solid || !player_inside);
+        if solid == self.solid {
+            return None;
+        }
+        self.solid = solid;
+        Some(solid)
+    }
+}
+
+impl From<&EntityInstance> for PoweredOccluder {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        let switch = entity_instance
+            .get_entity_ref_field("switch")
+            .expect("switch needs to be an entity ref field on all powered occluders")
+            .entity_iid
+            .clone();
+
+        PoweredOccluder {
+            switch,
+            powered: false,
+            solid: false,
+            half_size: Vec2::new(
+                entity_instance.width as f32 / 2.0,
+                entity_instance.height as f32 / 2.0,
+            ),
+        }
+    }
+}
+
+/// [`Bundle`] that includes all the [`Component`]s needed for a [`PoweredOccluder`] to function
+/// properly. The collider is only added once the wall materializes.
+#[derive(Bundle, LdtkEntity)]
+pub struct PoweredOccluderBundle {
+    #[from_entity_instance]
+    powered_occluder: PoweredOccluder,
+    #[with(powered_occluder_physics)]
+    physics: (RigidBody, CollisionGroups),
+    #[with(powered_occluder_sprite)]
+    sprite: Sprite,
+}
+
+/// A [`PoweredOccluder`] door, for the `Door` `EntityKind`.
+pub fn spawn_door(commands: &mut EntityCommands, entity_instance: &EntityInstance) {
+    commands.insert((
+        PoweredOccluder::from(entity_instance),
+        powered_occluder_physics(entity_instance),
+        powered_occluder_sprite(entity_instance),
+    ));
+}
+
+pub fn powered_occluder_physics(_: &EntityInstance) -> (RigidBody, CollisionGroups) {
+    (
+        RigidBody::Fixed,
+        CollisionGroups::new(GroupLabel::TERRAIN, GroupLabel::ALL),
+    )
+}
+
+pub fn powered_occluder_sprite(entity_instance: &EntityInstance) -> Sprite {
+    Sprite::from_color(
+        powered_occluder_color(false),
+        Vec2::new(entity_instance.width as f32, entity_instance.height as f32),
+    )
+}
+
+fn powered_occluder_color(solid: bool) -> Color {
+    Color::srgba(0.7, 0.9, 1.0, if solid { 0.9 } else { 0.15 })
+}
+
+/// Gives a [`PoweredOccluder`] its collider and [`Occluder2d`] while it is solid, and removes
+/// both while it is transparent.
+fn sync_powered_occluder(
+    commands: &mut Commands,
+    entity: Entity,
+    powered_occluder: &PoweredOccluder,
+    sprite: &mut Sprite,
+) {
+    let half_size = powered_occluder.half_size;
+    sprite.color = powered_occluder_color(powered_occluder.solid);
+    if powered_occluder.solid {
+        commands.entity(entity).insert((
+            Collider::cuboid(half_size.x, half_size.y),
+            Occluder2d::new(half_size.x, half_size.y),
+        ));
+    } else {
+        commands.entity(entity).remove::<(Collider, Occluder2d)>();
+    }
+}
+
+/// [`System`] that turns every [`PoweredOccluder`] off when the level is reset.
+pub fn reset_powered_occluders(
+    mut commands: Commands,
+    mut q_powered_occluders: Query<(Entity, &mut PoweredOccluder, &mut Sprite)>,
+) {
+    for (entity, mut powered_occluder, mut sprite) in q_powered_occluders.iter_mut() {
+        powered_occluder.powered = false;
+        powered_occluder.solid = false;
+        sync_powered_occluder(&mut commands, entity, &powered_occluder, &mut sprite);
+    }
+}
+
+/// [`System`] that powers [`PoweredOccluder`]s from [`SwitchChangedEvent`]s, and materializes or
+/// dematerializes them. The player is looked for with a shape query, since a transparent wall has
+/// no collider to report collisions with.
+pub fn update_powered_occluders(
+    mut commands: Commands,
+    mut q_powered_occluders: Query<(Entity, &mut PoweredOccluder, &GlobalTransform, &mut Sprite)>,
+    q_switches: Query<&EntityIid>,
+    q_player: Query<(), With<PlayerMarker>>,
+    q_rapier: Query<&RapierContext>,
+    mut ev_switch_changed: EventReader<SwitchChangedEvent>,
+) {
+    for event in ev_switch_changed.read() {
+        let Ok(switch) = q_switches.get(event.switch) else {
+            continue;
+        };
+        for (_, mut powered_occluder, _, _) in q_powered_occluders.iter_mut() {
+            if powered_occluder.switch == switch.as_str() {
+                powered_occluder.powered = event.is_active;
+            }
+        }
+    }
+
+    let Ok(rapier_context) = q_rapier.get_single() else {
+        return;
+    };
+    let filter = QueryFilter::new().groups(CollisionGroups::new(
+        GroupLabel::TERRAIN,
+        GroupLabel::PLAYER_COLLIDER,
+    ));
+
+    for (entity, mut powered_occluder, transform, mut sprite) in q_powered_occluders.iter_mut() {
+        let mut player_inside = false;
+        if powered_occluder.powered && !powered_occluder.solid {
+            let half_size = powered_occluder.half_size;
+            let shape = Collider::cuboid(half_size.x, half_size.y);
+            rapier_context.intersections_with_shape(
+                transform.translation().xy(),
+                0.0,
+                &shape,
+                filter,
+                |other| {
+                    player_inside |= q_player.contains(other);
+                    !player_inside
+                },
+            );
+        }
+
+        if powered_occluder.update(player_inside).is_some() {
+            sync_powered_occluder(&mut commands, entity, &powered_occluder, &mut sprite);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn powered_occluder() -> PoweredOccluder {
+        PoweredOccluder {
+            switch: "switch".into(),
+            powered: false,
+            solid: false,
+            half_size: Vec2::new(8.0, 16.0),
+        }
+    }
+
+    #[test]
+    fn materializing_waits_for_player_to_leave() {
+        let mut powered_occluder = powered_occluder();
+        assert_eq!(powered_occluder.update(false), None);
+
+        powered_occluder.powered = true;
+        assert_eq!(powered_occluder.update(true), None);
+        assert!(!powered_occluder.solid);
+        assert_eq!(powered_occluder.update(false), Some(true));
+
+        // a solid wall stays solid, the player can't be inside of it anyway
+        assert_eq!(powered_occluder.update(true), None);
+
+        powered_occluder.powered = false;
+        assert_eq!(powered_occluder.update(false), Some(false));
+    }
+
+    #[test]
+    fn powering_toggles_collision_and_occlusion_together() {
+        let mut world = World::new();
+        let mut powered_occluder = powered_occluder();
+        let mut sprite = Sprite::default();
+        let entity = world.spawn_empty().id();
+
+        let has = |world: &World| {
+            (
+                world.get::<Collider>(entity).is_some(),
+                world.get::<Occluder2d>(entity).is_some(),
+            )
+        };
+
+        powered_occluder.powered = true;
+        powered_occluder.update(false);
+        sync_powered_occluder(
+            &mut world.commands(),
+            entity,
+            &powered_occluder,
+            &mut sprite,
+        );
+        world.flush();
+        assert_eq!(has(&world), (true, true));
+
+        powered_occluder.powered = false;
+        powered_occluder.update(false);
+        sync_powered_occluder(
+            &mut world.commands(),
+            entity,
+            &powered_occluder,
+            &mut sprite,
+        );
+        world.flush();
+        assert_eq!(has(&world), (false, false));
+    }
+}
@@ -0,0 +1,280 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    level::crystal::{CrystalColor, CrystalIdent, CrystalToggleEvent},
+    player::PlayerMarker,
+    shared::GroupLabel,
+};
+
+use super::{
+    light_sail::LightSail, push_block::PushBlock, sensor::SwitchChangedEvent, LevelSystems,
+};
+
+/// How far above the top of a [`PressurePlate`] an entity can be while still counting as resting
+/// on it. Needs to be larger than the offset of the player's character controller.
+const PRESSURE_PLATE_REACH: f32 = 3.0;
+
+/// [`Plugin`] for plates that toggle crystals while enough things are standing on them.
+pub struct PressurePlatePlugin;
+
+impl Plugin for PressurePlatePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SwitchChangedEvent>()
+            .register_ldtk_entity::<PressurePlateBundle>("PressurePlate")
+            .add_systems(Update, reset_pressure_plates.in_set(LevelSystems::Reset))
+            .add_systems(
+                FixedUpdate,
+                update_pressure_plates
+                    .after(PhysicsSet::Writeback)
+                    .in_set(LevelSystems::Simulation),
+            );
+    }
+}
+
+/// [`Component`] for plates that activate when at least `threshold` entities (the player,
+/// [`PushBlock`]s or [`LightSail`]s) rest on them, and deactivate as soon as there are fewer.
+#[derive(Component, Debug)]
+pub struct PressurePlate {
+    pub threshold: u32,
+    pub is_active: bool,
+    /// The color of the crystals to toggle
+    pub toggle_ident: CrystalIdent,
+    /// The size of the plate, used to find what is resting on top of it
+    pub half_size: Vec2,
+}
+
+impl PressurePlate {
+    /// Updates the plate with the number of entities currently resting on it, returning the new
+    /// active state if it changed.
+    pub fn update(&mut self, weight: u32) -> Option<bool> {
+        let is_active = weight >= self.threshold;
+        if is_active == self.is_active {
+            return None;
+        }
+        self.is_active = is_active;
+        Some(is_active)
+    }
+}
+
+impl From<&EntityInstance> for PressurePlate {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        let toggle_color: CrystalColor = entity_instance
+            .get_enum_field("toggle_color")
+            .expect("toggle_color needs to be an enum field on all pressure plates")
+            .into();
+
+        let id = entity_instance
+            .get_int_field("id")
+            .expect("id needs to be an int field on all pressure plates");
+
+        let threshold = entity_instance
+            .get_int_field("threshold")
+            .expect("threshold needs to be an int field on all pressure plates");
+
+        PressurePlate {
+            threshold: (*threshold).max(1) as u32,
+            is_active: false,
+            toggle_ident: CrystalIdent {
+                color: toggle_color,
+                id: *id,
+            },
+            half_size: Vec2::new(
+                entity_instance.width as f32 / 2.0,
+                entity_instance.height as f32 / 2.0,
+            ),
+        }
+    }
+}
+
+/// [`Bundle`] that includes all the [`Component`]s needed for a [`PressurePlate`] to function
+/// properly.
+#[derive(Bundle, LdtkEntity)]
+pub struct PressurePlateBundle {
+    #[from_entity_instance]
+    pressure_plate: PressurePlate,
+    #[with(pressure_plate_sprite)]
+    sprite: Sprite,
+}
+
+pub fn pressure_plate_sprite(entity_instance: &EntityInstance) -> Sprite {
+    let plate = PressurePlate::from(entity_instance);
+    Sprite::from_color(
+        plate.toggle_ident.color.button_color().darker(0.3),
+        plate.half_size * 2.0,
+    )
+}
+
+/// [`System`] that deactivates [`PressurePlate`]s when the level is reset. Anything still resting
+/// on the plate will activate it again on the next physics step.
+pub fn reset_pressure_plates(mut q_plates: Query<(&mut PressurePlate, &mut Sprite)>) {
+    for (mut plate, mut sprite) in q_plates.iter_mut() {
+        plate.is_active = false;
+        sprite.color = plate.toggle_ident.color.button_color().darker(0.3);
+    }
+}
+
+/// [`System`] that counts the player, [`PushBlock`]s and [`LightSail`]s resting on each
+/// [`PressurePlate`], and toggles the plate's crystals when it activates or deactivates. Overlaps
+/// are queried from scratch every step instead of relying on collision events, so nothing is
+/// missed when an entity is teleported on or off of the plate.
+pub fn update_pressure_plates(
+    mut q_plates: Query<(Entity, &mut PressurePlate, &GlobalTransform, &mut Sprite)>,
+    q_weights: Query<(), Or<(With<PlayerMarker>, With<PushBlock>, With<LightSail>)>>,
+    q_rapier: Query<&RapierContext>,
+    mut ev_crystal_toggle: EventWriter<CrystalToggleEvent>,
+    mut ev_switch_changed: EventWriter<SwitchChangedEvent>,
+) {
+    let Ok(rapier_context) = q_rapier.get_single() else {
+        return;
+    };
+
+    let filter = QueryFilter::new().groups(CollisionGroups::new(
+        GroupLabel::TERRAIN,
+        GroupLabel::PLAYER_COLLIDER | GroupLabel::TERRAIN,
+    ));
+
+    for (entity, mut plate, transform, mut sprite) in q_plates.iter_mut() {
+        // a thin box covering the top of the plate
+        let shape = Collider::cuboid(plate.half_size.x, PRESSURE_PLATE_REACH / 2.0);
+        let shape_pos = transform.translation().xy()
+            + Vec2::new(0.0, plate.half_size.y + PRESSURE_PLATE_REACH / 2.0 - 1.0);
+
+        let mut weight = 0;
+        rapier_context.intersections_with_shape(shape_pos, 0.0, &shape, filter, |other| {
+            if q_weights.contains(other) {
+                weight += 1;
+            }
+            true
+        });
+
+        let Some(is_active) = plate.update(weight) else {
+            continue;
+        };
+
+        ev_crystal_toggle.send(CrystalToggleEvent {
+            color: plate.toggle_ident,
+        });
+        ev_switch_changed.send(SwitchChangedEvent {
+            switch: entity,
+            is_active,
+        });
+
+        let color = plate.toggle_ident.color.button_color();
+        sprite.color = if is_active { color } else { color.darker(0.3) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_rapier2d::rapier::{geometry::ColliderBuilder, na::vector};
+
+    use super::*;
+
+    fn plate(threshold: u32) -> PressurePlate {
+        PressurePlate {
+            threshold,
+            is_active: false,
+            toggle_ident: CrystalIdent {
+                color: CrystalColor::Red,
+                id: 0,
+            },
+            half_size: Vec2::new(8.0, 2.0),
+        }
+    }
+
+    #[test]
+    fn pressure_plate_needs_block_and_player() {
+        let mut plate = plate(2);
+        // block pushed onto the plate
+        assert_eq!(plate.update(1), None);
+        // player jumps onto it too
+        assert_eq!(plate.update(2), Some(true));
+        assert_eq!(plate.update(2), None);
+        // player jumps off
+        assert_eq!(plate.update(1), Some(false));
+        assert_eq!(plate.update(1), None);
+    }
+
+    #[test]
+    fn pressure_plate_stays_held_by_block() {
+        let mut plate = plate(1);
+        assert_eq!(plate.update(2), Some(true));
+        // player leaves, block keeps the plate held down
+        assert_eq!(plate.update(1), None);
+        assert!(plate.is_active);
+    }
+
+    /// A [`RapierContext`] with an 8x8 collider for each entity at its position.
+    fn weights_context(weights: &[(Entity, Vec2)]) -> RapierContext {
+        let mut rapier_context = RapierContext::default();
+        for (entity, pos) in weights {
+            rapier_context.colliders.insert(
+                ColliderBuilder::cuboid(4.0, 4.0)
+                    .translation(vector![pos.x, pos.y])
+                    .user_data(entity.to_bits() as u128)
+                    .build(),
+            );
+        }
+        rapier_context
+            .query_pipeline
+            .update(&rapier_context.colliders);
+        rapier_context
+    }
+
+    #[test]
+    fn block_and_player_hold_down_the_plate() {
+        let mut app = App::new();
+        app.add_event::<CrystalToggleEvent>()
+            .add_event::<SwitchChangedEvent>()
+            .add_systems(Update, update_pressure_plates);
+
+        let plate = app
+            .world_mut()
+            .spawn((plate(2), Sprite::default(), GlobalTransform::default()))
+            .id();
+        let block = app.world_mut().spawn(PushBlock::default()).id();
+        let player = app.world_mut().spawn(PlayerMarker).id();
+        // something that isn't heavy enough to count
+        let other = app.world_mut().spawn_empty().id();
+        let context = app.world_mut().spawn(RapierContext::default()).id();
+
+        // resting on top of the plate centered at (0, 0)
+        let on_plate = |x: f32| Vec2::new(x, 6.0);
+        let step = |app: &mut App, weights: &[(Entity, Vec2)]| {
+            *app.world_mut().get_mut::<RapierContext>(context).unwrap() = weights_context(weights);
+            app.update();
+            app.world_mut()
+                .resource_mut::<Events<SwitchChangedEvent>>()
+                .drain()
+                .map(|ev| (ev.switch, ev.is_active))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(step(&mut app, &[(block, on_plate(-4.0))]), vec![]);
+        assert_eq!(
+            step(&mut app, &[(block, on_plate(-4.0)), (other, on_plate(4.0))]),
+            vec![]
+        );
+        assert_eq!(
+            step(
+                &mut app,
+                &[(block, on_plate(-4.0)), (player, on_plate(4.0))]
+            ),
+            vec![(plate, true)]
+        );
+        assert_eq!(
+            app.world().resource::<Events<CrystalToggleEvent>>().len(),
+            1
+        );
+        // the player jumps off
+        assert_eq!(
+            step(
+                &mut app,
+                &[(block, on_plate(-4.0)), (player, Vec2::new(4.0, 40.0))]
+            ),
+            vec![(plate, false)]
+        );
+    }
+}
@@ -0,0 +1,308 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use enum_map::EnumMap;
+
+use crate::{
+    config::{Config, PrismConfig},
+    light::{
+        fog::VolumetricFog,
+        segments::{simulate_light_sources, PrevLightBeamPlayback},
+        LightBeamDepth, LightBeamSource, LightColor,
+    },
+};
+
+use super::{entity::FixedEntityBundle, LevelSystems};
+
+/// [`Plugin`] for prisms that split white beams into purple, green and blue beams.
+pub struct PrismPlugin;
+
+impl Plugin for PrismPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity::<PrismBundle>("Prism")
+            .add_systems(
+                FixedUpdate,
+                update_prism_beams
+                    .before(simulate_light_sources)
+                    .in_set(LevelSystems::Simulation),
+            );
+    }
+}
+
+/// [`Component`] for prisms, placed in Ldtk. While a white beam hits a prism, a purple, green and
+/// blue [`LightBeamSource`] fan out of its far side, each carrying the share of the light reaching
+/// the prism given by the [`PrismConfig`]. The shares never add up to more than the white beam,
+/// so splitting a beam can't make more light than it started with.
+#[derive(Component, Debug)]
+pub struct Prism {
+    pub half_size: Vec2,
+    /// The [`depth`](crate::light::LightBeamSource::depth) of the prism's beams, read from the
+    /// optional `depth` field in Ldtk
+    pub depth: LightBeamDepth,
+    /// The beams currently leaving the prism
+    beams: EnumMap<LightColor, Option<Entity>>,
+}
+
+impl From<&EntityInstance> for Prism {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        Prism {
+            half_size: Vec2::new(
+                entity_instance.width as f32 / 2.0,
+                entity_instance.height as f32 / 2.0,
+            ),
+            depth: LightBeamDepth::from(entity_instance),
+            beams: EnumMap::default(),
+        }
+    }
+}
+
+/// [`Bundle`] that includes all the [`Component`]s needed for a [`Prism`] to function properly.
+#[derive(Bundle, LdtkEntity)]
+pub struct PrismBundle {
+    #[from_entity_instance]
+    physics: FixedEntityBundle,
+    #[from_entity_instance]
+    prism: Prism,
+    #[with(prism_sprite)]
+    sprite: Sprite,
+}
+
+pub fn prism_sprite(entity_instance: &EntityInstance) -> Sprite {
+    Sprite::from_color(
+        LightColor::White.indicator_color().with_alpha(0.4),
+        Vec2::new(entity_instance.width as f32, entity_instance.height as f32),
+    )
+}
+
+/// Splits a white beam with `intensity` into the colors leaving a [`Prism`], with the angle of
+/// each color's beam from the white beam in radians. Like [`LightBeamSource::intensity`], the
+/// intensities are fractions of the [`beam_intensity`](LightColor::beam_intensity) of their own
+/// color, so the outputs are scaled by how much brighter or dimmer their color is than white. The
+/// light the outputs carry adds up to at most the light of the white beam.
+pub fn split_beam(config: &PrismConfig, intensity: f32) -> [(LightColor, f32, f32); 3] {
+    let fractions = [config.purple, config.green, config.blue].map(|fraction| fraction.max(0.0));
+    let total = fractions.iter().sum::<f32>();
+    let scale = if total > 1.0 {
+        intensity / total
+    } else {
+        intensity
+    };
+    let spread = config.spread.to_radians();
+    let output = |color: LightColor, fraction: f32| {
+        fraction * scale * LightColor::White.beam_intensity() / color.beam_intensity()
+    };
+
+    [
+        (
+            LightColor::Purple,
+            output(LightColor::Purple, fractions[0]),
+            -spread,
+        ),
+        (
+            LightColor::Green,
+            output(LightColor::Green, fractions[1]),
+            0.0,
+        ),
+        (
+            LightColor::Blue,
+            output(LightColor::Blue, fractions[2]),
+            spread,
+        ),
+    ]
+}
+
+/// [`System`] that spawns, aims and despawns the beams leaving each [`Prism`] based on the white
+/// beams hitting it. The beams split the light that reaches the prism, after the fog and the
+/// [`WeakPanel`](super::weak_panel::WeakPanel)s on the way.
+pub fn update_prism_beams(
+    mut commands: Commands,
+    mut q_prisms: Query<(Entity, &mut Prism, &GlobalTransform)>,
+    mut q_sources: Query<(&mut LightBeamSource, &PrevLightBeamPlayback)>,
+    config: Res<Config>,
+    fog: Res<VolumetricFog>,
+) {
+    for (entity, mut prism, transform) in q_prisms.iter_mut() {
+        // the direction and intensity of a white beam hitting the prism
+        let mut hit = None;
+        for (source, prev_playback) in q_sources.iter() {
+            if source.color != LightColor::White {
+                continue;
+            }
+            let mut prev_point = source.start_pos;
+            for intersection in prev_playback.intersections.iter().flatten() {
+                if intersection.entity == entity {
+                    hit = Some((
+                        intersection.point - prev_point,
+                        prev_playback.received_intensity(source, intersection.time, &fog),
+                    ));
+                    break;
+                }
+                prev_point = intersection.point;
+            }
+        }
+
+        let hit = hit.and_then(|(dir, intensity)| Some((dir.try_normalize()?, intensity)));
+        let Some((dir, intensity)) = hit else {
+            for beam in prism.beams.values_mut() {
+                if let Some(entity) = beam.take().filter(|entity| q_sources.contains(*entity)) {
+                    commands.entity(entity).despawn_recursive();
+                }
+            }
+            continue;
+        };
+
+        let prism_pos = transform.translation().xy();
+        for (color, beam_intensity, angle) in split_beam(&config.prism_config, intensity) {
+            // beams are despawned when the level resets
+            let existing = prism.beams[color].filter(|entity| q_sources.contains(*entity));
+            if beam_intensity <= 0.0 {
+                if let Some(entity) = existing {
+                    commands.entity(entity).despawn_recursive();
+                }
+                prism.beams[color] = None;
+                continue;
+            }
+
+            let start_dir = Vec2::from_angle(angle).rotate(dir);
+            // start the beam just past the prism, so it doesn't hit the prism itself
+            let start_pos = prism_pos + start_dir * (prism.half_size.length() + 1.0);
+            if let Some(entity) = existing {
+                let (mut source, _) = q_sources.get_mut(entity).unwrap();
+                source.start_pos = start_pos;
+                source.start_dir = start_dir;
+                source.intensity = beam_intensity;
+                continue;
+            }
+
+            prism.beams[color] = Some(
+                commands
+                    .spawn((
+                        LightBeamSource {
+                            start_pos,
+                            start_dir,
+                            time_traveled: 0.0,
+                            color,
+                            width: 0.0,
+                            intensity: beam_intensity,
+                            penetration: 0.0,
+                            depth: prism.depth,
+                        },
+                        PrevLightBeamPlayback::default(),
+                    ))
+                    .id(),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::light::segments::LightBeamIntersection;
+
+    use super::*;
+
+    /// The light carried by the beams of `split`, as a fraction of a full white beam.
+    fn total_intensity(split: [(LightColor, f32, f32); 3]) -> f32 {
+        split
+            .iter()
+            .map(|(color, intensity, _)| intensity * color.beam_intensity())
+            .sum::<f32>()
+            / LightColor::White.beam_intensity()
+    }
+
+    #[test]
+    fn splitting_divides_intensity_evenly_by_default() {
+        let split = split_beam(&PrismConfig::default(), 1.0);
+        for (color, intensity, _) in split {
+            let light = intensity * color.beam_intensity() / LightColor::White.beam_intensity();
+            assert!((light - 1.0 / 3.0).abs() < 1e-5);
+        }
+        assert!(total_intensity(split) <= 1.0 + 1e-5);
+
+        // dimmer beams split into dimmer beams
+        assert!(total_intensity(split_beam(&PrismConfig::default(), 0.5)) <= 0.5 + 1e-5);
+    }
+
+    #[test]
+    fn splitting_never_creates_intensity() {
+        let config = PrismConfig {
+            purple: 0.8,
+            green: 0.8,
+            blue: -0.2,
+            spread: 15.0,
+        };
+        let split = split_beam(&config, 1.0);
+        assert!(total_intensity(split) <= 1.0 + 1e-5);
+        assert_eq!(split[2].1, 0.0);
+        // the shares keep their proportions when they are scaled down
+        let purple = split[0].1 * LightColor::Purple.beam_intensity();
+        let green = split[1].1 * LightColor::Green.beam_intensity();
+        assert!((purple - green).abs() < 1e-5);
+
+        // fractions that add up to less than 1 lose the rest of the beam
+        let config = PrismConfig {
+            purple: 0.1,
+            green: 0.2,
+            blue: 0.3,
+            spread: 15.0,
+        };
+        assert!((total_intensity(split_beam(&config, 1.0)) - 0.6).abs() < 1e-5);
+    }
+
+    #[test]
+    fn prisms_split_the_light_that_reaches_them() {
+        let mut app = App::new();
+        app.insert_resource(Config::default())
+            .insert_resource(VolumetricFog { density: 0.01 })
+            .add_systems(Update, update_prism_beams);
+        let prism = app
+            .world_mut()
+            .spawn((
+                Prism {
+                    half_size: Vec2::splat(4.0),
+                    depth: LightBeamDepth::default(),
+                    beams: EnumMap::default(),
+                },
+                GlobalTransform::default(),
+            ))
+            .id();
+        let mut prev_playback = PrevLightBeamPlayback::default();
+        prev_playback.intersections[0] = Some(LightBeamIntersection {
+            entity: prism,
+            point: Vec2::new(-4.0, 0.0),
+            time: 96.0,
+            refracted: false,
+        });
+        let white = LightBeamSource {
+            start_pos: Vec2::new(-100.0, 0.0),
+            start_dir: Vec2::X,
+            time_traveled: 96.0,
+            color: LightColor::White,
+            width: 0.0,
+            intensity: 0.9,
+            penetration: 0.0,
+            depth: default(),
+        };
+        let received = {
+            let fog = app.world().resource::<VolumetricFog>();
+            prev_playback.received_intensity(&white, 96.0, fog)
+        };
+        assert!(received < white.intensity);
+        app.world_mut().spawn((white, prev_playback));
+        app.update();
+
+        let mut q_sources = app.world_mut().query::<&LightBeamSource>();
+        let outputs: Vec<(LightColor, f32)> = q_sources
+            .iter(app.world())
+            .filter(|source| source.color != LightColor::White)
+            .map(|source| (source.color, source.intensity))
+            .collect();
+        assert_eq!(outputs.len(), 3);
+        // the outputs carry no more light than the fogged beam that reached the prism
+        let light: f32 = outputs
+            .iter()
+            .map(|(color, intensity)| intensity * color.beam_intensity())
+            .sum();
+        assert!(light <= received * LightColor::White.beam_intensity() + 1e-5);
+        assert!(light > 0.0);
+    }
+}
@@ -0,0 +1,303 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    lighting::Occluder2d,
+    player::{
+        movement::{Gravity, PlayerMovement},
+        PlayerMarker,
+    },
+    shared::GroupLabel,
+};
+
+use super::{entity::FixedEntityBundle, LevelSystems};
+
+/// Fraction of the player's horizontal velocity that is given to a [`PushBlock`] being pushed, so
+/// that blocks feel heavy.
+const PUSH_BLOCK_SPEED_FACTOR: f32 = 0.5;
+
+/// [`Plugin`] for blocks that the player can push around to block light beams.
+pub struct PushBlockPlugin;
+
+impl Plugin for PushBlockPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity::<PushBlockBundle>("PushBlock")
+            .add_systems(PreUpdate, init_push_blocks.in_set(LevelSystems::Processing))
+            .add_systems(Update, reset_push_blocks.in_set(LevelSystems::Reset))
+            .add_systems(
+                FixedUpdate,
+                push_blocks
+                    .before(PhysicsSet::SyncBackend)
+                    .in_set(LevelSystems::Simulation),
+            );
+    }
+}
+
+/// [`Component`] for blocks that move horizontally when the player walks into them, and fall
+/// when there is nothing below them. Blocks are part of the terrain, so they stop at walls, stop
+/// light beams, and cast shadows.
+#[derive(Component, Default, Debug)]
+pub struct PushBlock {
+    pub velocity: Vec2,
+    /// Where the block was placed in Ldtk, used to put it back when the level is reset
+    start: Vec3,
+}
+
+/// [`Bundle`] that includes all the [`Component`]s needed for a [`PushBlock`] to function
+/// properly.
+#[derive(Bundle, LdtkEntity)]
+pub struct PushBlockBundle {
+    #[from_entity_instance]
+    physics: FixedEntityBundle,
+    #[default]
+    push_block: PushBlock,
+    #[with(push_block_controller)]
+    controller: KinematicCharacterController,
+    #[default]
+    controller_output: KinematicCharacterControllerOutput,
+    #[with(push_block_occluder)]
+    occluder: Occluder2d,
+    #[with(push_block_sprite)]
+    sprite: Sprite,
+}
+
+pub fn push_block_controller(_: &EntityInstance) -> KinematicCharacterController {
+    KinematicCharacterController {
+        filter_groups: Some(CollisionGroups::new(
+            GroupLabel::TERRAIN,
+            GroupLabel::TERRAIN,
+        )),
+        offset: CharacterLength::Absolute(0.5),
+        // blocks are never pushed up slopes or stairs
+        autostep: None,
+        snap_to_ground: None,
+        ..default()
+    }
+}
+
+pub fn push_block_occluder(entity_instance: &EntityInstance) -> Occluder2d {
+    Occluder2d::new(
+        entity_instance.width as f32 / 2.0,
+        entity_instance.height as f32 / 2.0,
+    )
+}
+
+pub fn push_block_sprite(entity_instance: &EntityInstance) -> Sprite {
+    Sprite::from_color(
+        Color::srgb(0.35, 0.3, 0.4),
+        Vec2::new(entity_instance.width as f32, entity_instance.height as f32),
+    )
+}
+
+/// The horizontal velocity of a [`PushBlock`] that the player collided with. Only collisions with
+/// the side of the block push it, so standing on a block doesn't move it.
+pub fn push_block_velocity(collision_normal: Vec2, player_velocity_x: f32) -> f32 {
+    if collision_normal.x.abs() < 0.5 {
+        return 0.0;
+    }
+    player_velocity_x * PUSH_BLOCK_SPEED_FACTOR
+}
+
+/// [`System`] that stores the starting position of new [`PushBlock`]s.
+pub fn init_push_blocks(mut q_push_blocks: Query<(&mut PushBlock, &Transform), Added<PushBlock>>) {
+    for (mut push_block, transform) in q_push_blocks.iter_mut() {
+        push_block.start = transform.translation;
+    }
+}
+
+/// [`System`] that moves [`PushBlock`]s back to where they started when the level is reset.
+pub fn reset_push_blocks(mut q_push_blocks: Query<(&mut PushBlock, &mut Transform)>) {
+    for (mut push_block, mut transform) in q_push_blocks.iter_mut() {
+        transform.translation = push_block.start;
+        push_block.velocity = Vec2::ZERO;
+    }
+}
+
+/// [`System`] that pushes the [`PushBlock`]s the player walked into during the last physics step,
+/// and applies gravity to all of them. The [`KinematicCharacterController`] on each block stops
+/// it at walls and on the ground.
+pub fn push_blocks(
+    q_player: Query<(&PlayerMovement, &KinematicCharacterControllerOutput), With<PlayerMarker>>,
+    mut q_push_blocks: Query<(
+        Entity,
+        &mut PushBlock,
+        &mut KinematicCharacterController,
+        &KinematicCharacterControllerOutput,
+    )>,
+    gravity: Res<Gravity>,
+) {
+    let player = q_player.get_single().ok();
+
+    for (entity, mut push_block, mut controller, output) in q_push_blocks.iter_mut() {
+        push_block.velocity.x = 0.0;
+        if let Some((movement, player_output)) = player {
+            for collision in player_output.collisions.iter() {
+                if collision.entity == entity {
+                    push_block.velocity.x = push_block_velocity(
+                        collision
+                            .hit
+                            .details
+                            .map_or(Vec2::ZERO, |details| details.normal1),
+                        movement.velocity.x,
+                    );
+                }
+            }
+        }
+
+        if output.grounded {
+            push_block.velocity.y = 0.0;
+        }
+        push_block.velocity.y = gravity.fall(push_block.velocity.y);
+
+        controller.up = gravity.up();
+        controller.translation = Some(push_block.velocity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_ecs_ldtk::EntityIid;
+    use bevy_rapier2d::rapier::{geometry::ColliderBuilder, na::vector};
+
+    use crate::level::{
+        crystal::{CrystalColor, CrystalIdent, CrystalToggleEvent},
+        powered_occluder::{update_powered_occluders, PoweredOccluder},
+        pressure_plate::{update_pressure_plates, PressurePlate},
+        sensor::SwitchChangedEvent,
+    };
+
+    use super::*;
+
+    /// Stands in for rapier's character controller, moving [`PushBlock`]s along the ground by
+    /// their controller's translation and putting their colliders where they end up.
+    fn slide_push_blocks(
+        mut q_push_blocks: Query<
+            (Entity, &KinematicCharacterController, &mut Transform),
+            With<PushBlock>,
+        >,
+        mut q_rapier: Query<&mut RapierContext>,
+    ) {
+        let mut rapier_context = q_rapier.single_mut();
+        *rapier_context = RapierContext::default();
+        for (entity, controller, mut transform) in q_push_blocks.iter_mut() {
+            transform.translation.x += controller.translation.unwrap_or_default().x;
+            rapier_context.colliders.insert(
+                ColliderBuilder::cuboid(4.0, 4.0)
+                    .translation(vector![transform.translation.x, transform.translation.y])
+                    .user_data(entity.to_bits() as u128)
+                    .build(),
+            );
+        }
+        let rapier_context = rapier_context.into_inner();
+        rapier_context
+            .query_pipeline
+            .update(&rapier_context.colliders);
+    }
+
+    #[test]
+    fn push_blocks_are_only_pushed_from_the_side() {
+        assert_eq!(push_block_velocity(Vec2::new(-1.0, 0.0), 2.0), 1.0);
+        assert_eq!(push_block_velocity(Vec2::new(1.0, 0.0), -2.0), -1.0);
+        assert_eq!(push_block_velocity(Vec2::new(0.0, 1.0), 2.0), 0.0);
+    }
+
+    #[test]
+    fn pushing_a_block_onto_a_plate_powers_its_door() {
+        let mut app = App::new();
+        app.init_resource::<Gravity>()
+            .add_event::<CrystalToggleEvent>()
+            .add_event::<SwitchChangedEvent>()
+            .add_systems(
+                Update,
+                (
+                    push_blocks,
+                    slide_push_blocks,
+                    update_pressure_plates,
+                    update_powered_occluders,
+                )
+                    .chain(),
+            );
+        app.world_mut().spawn(RapierContext::default());
+
+        // an 8x8 block resting on the ground to the left of a plate centered at (0, 0)
+        let block = app
+            .world_mut()
+            .spawn((
+                PushBlock::default(),
+                KinematicCharacterController::default(),
+                KinematicCharacterControllerOutput {
+                    grounded: true,
+                    ..default()
+                },
+                Transform::from_xyz(-20.0, 6.0, 0.0),
+            ))
+            .id();
+        app.world_mut().spawn((
+            PlayerMarker,
+            PlayerMovement {
+                velocity: Vec2::new(2.0, 0.0),
+                ..default()
+            },
+            // the player walking into the left side of the block
+            KinematicCharacterControllerOutput {
+                collisions: vec![CharacterCollision {
+                    entity: block,
+                    character_translation: Vec2::new(-28.0, 6.0),
+                    character_rotation: 0.0,
+                    translation_applied: Vec2::ZERO,
+                    translation_remaining: Vec2::new(2.0, 0.0),
+                    hit: ShapeCastHit {
+                        time_of_impact: 0.0,
+                        details: Some(ShapeCastHitDetails {
+                            witness1: Vec2::ZERO,
+                            witness2: Vec2::ZERO,
+                            normal1: Vec2::X,
+                            normal2: Vec2::NEG_X,
+                        }),
+                        status: ShapeCastStatus::Converged,
+                    },
+                }],
+                ..default()
+            },
+        ));
+        app.world_mut().spawn((
+            PressurePlate {
+                threshold: 1,
+                is_active: false,
+                toggle_ident: CrystalIdent {
+                    color: CrystalColor::Red,
+                    id: 0,
+                },
+                half_size: Vec2::new(8.0, 2.0),
+            },
+            EntityIid::new("plate"),
+            Sprite::default(),
+            GlobalTransform::default(),
+        ));
+        let door = app
+            .world_mut()
+            .spawn((
+                PoweredOccluder {
+                    switch: "plate".into(),
+                    powered: false,
+                    solid: false,
+                    half_size: Vec2::new(4.0, 16.0),
+                },
+                Sprite::default(),
+                GlobalTransform::from_xyz(40.0, 16.0, 0.0),
+            ))
+            .id();
+        let door_solid = |app: &App| app.world().get::<PoweredOccluder>(door).unwrap().solid;
+
+        app.update();
+        assert!(app.world().get::<Transform>(block).unwrap().translation.x > -20.0);
+        assert!(!door_solid(&app));
+
+        for _ in 0..20 {
+            app.update();
+        }
+        assert!(door_solid(&app));
+        assert!(app.world().get::<Collider>(door).is_some());
+    }
+}
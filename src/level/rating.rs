@@ -0,0 +1,335 @@
+use std::{collections::HashMap, path::Path, time::Duration};
+
+use bevy::{prelude::*, time::Stopwatch};
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::{
+    save::SaveSlots,
+    shared::{GameState, ResetLevel},
+};
+
+use super::{
+    get_ldtk_level_data, restart::RestartLevelEvent, switch_level, CurrentLevel, LevelSystems,
+};
+
+/// [`Plugin`] that rates each completed level with up to 3 stars, based on how fast it was
+/// finished and how many times the player died compared to the level's par values. The best
+/// ratings and times are saved to the active save slot, see [`SaveSlots`].
+pub struct LevelRatingPlugin;
+
+impl Plugin for LevelRatingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<LevelCompletedEvent>()
+            .add_event::<LevelRatedEvent>()
+            .init_resource::<LevelRun>()
+            .init_resource::<BestRatings>()
+            .init_resource::<BestTimes>()
+            .add_systems(
+                FixedUpdate,
+                (
+                    tick_level_run.in_set(LevelSystems::Simulation),
+                    complete_level_on_switch.after(switch_level),
+                ),
+            )
+            .add_systems(Update, count_level_run_deaths.in_set(LevelSystems::Reset))
+            .add_systems(
+                Update,
+                rate_completed_levels.run_if(on_event::<LevelCompletedEvent>),
+            );
+    }
+}
+
+/// [`Event`] sent when the player leaves a level for the next one while playing.
+#[derive(Event, Debug)]
+pub struct LevelCompletedEvent {
+    pub level_iid: LevelIid,
+    pub time: Duration,
+    pub deaths: u32,
+}
+
+/// [`Event`] sent with the rating of every [`LevelCompletedEvent`], from 1 to 3 stars.
+#[derive(Event, Debug)]
+pub struct LevelRatedEvent {
+    pub level_iid: LevelIid,
+    pub stars: u8,
+}
+
+/// [`Resource`] tracking the player's current attempt at the [`CurrentLevel`]. Time only passes
+/// while the level is being played, and deaths are kept until the player leaves the level.
+#[derive(Resource, Default, Debug)]
+pub struct LevelRun {
+    pub time: Stopwatch,
+    pub deaths: u32,
+}
+
+/// The par values of a level, read from its `ParTime` (in seconds) and `ParDeaths` fields in Ldtk.
+#[derive(Clone, Copy, Debug)]
+pub struct LevelPar {
+    pub time_secs: f32,
+    pub deaths: u32,
+}
+
+/// Rates a completed level. Finishing is worth one star, and finishing within the par time and
+/// with at most the par deaths are worth one more each. Levels without par values only get the
+/// star for finishing.
+pub fn level_rating(par: Option<LevelPar>, time: Duration, deaths: u32) -> u8 {
+    let Some(par) = par else {
+        return 1;
+    };
+    1 + u8::from(time.as_secs_f32() <= par.time_secs) + u8::from(deaths <= par.deaths)
+}
+
+/// [`Resource`] holding the best number of stars earned in each level, by level iid. Saved to
+/// the active save slot whenever a rating improves.
+#[derive(Resource, Default, Debug)]
+pub struct BestRatings(pub HashMap<String, u8>);
+
+impl BestRatings {
+    pub fn load(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .map(|contents| parse_best_ratings(&contents))
+            .unwrap_or_default()
+    }
+
+    /// Stores a new rating, returning true if it beat the best rating of the level.
+    pub fn record(&mut self, level_iid: &str, stars: u8) -> bool {
+        let best = self.0.entry(level_iid.to_string()).or_default();
+        if stars <= *best {
+            return false;
+        }
+        *best = stars;
+        true
+    }
+
+    pub fn save(&self, path: &Path) {
+        let contents: String = self
+            .0
+            .iter()
+            .map(|(level_iid, stars)| format!("{} {}\n", level_iid, stars))
+            .collect();
+        if let Err(err) = std::fs::write(path, contents) {
+            warn!("Failed to save level ratings: {}", err);
+        }
+    }
+}
+
+/// [`Resource`] holding the fastest time each level was completed in, by level iid. Saved to the
+/// active save slot whenever a time improves.
+#[derive(Resource, Default, Debug)]
+pub struct BestTimes(pub HashMap<String, Duration>);
+
+impl BestTimes {
+    pub fn load(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .map(|contents| parse_best_times(&contents))
+            .unwrap_or_default()
+    }
+
+    /// Stores a new time, returning true if it beat the best time of the level.
+    pub fn record(&mut self, level_iid: &str, time: Duration) -> bool {
+        if self.0.get(level_iid).is_some_and(|best| time >= *best) {
+            return false;
+        }
+        self.0.insert(level_iid.to_string(), time);
+        true
+    }
+
+    pub fn save(&self, path: &Path) {
+        let contents: String = self
+            .0
+            .iter()
+            .map(|(level_iid, time)| format!("{} {}\n", level_iid, time.as_secs_f64()))
+            .collect();
+        if let Err(err) = std::fs::write(path, contents) {
+            warn!("Failed to save best times: {}", err);
+        }
+    }
+}
+
+/// Parses ratings saved by [`BestRatings`], written as a level iid and a number of stars per line.
+/// Lines that can't be parsed are skipped.
+fn parse_best_ratings(contents: &str) -> BestRatings {
+    BestRatings(
+        contents
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.split_whitespace();
+                let level_iid = parts.next()?;
+                let stars = parts.next()?.parse().ok()?;
+                Some((level_iid.to_string(), stars))
+            })
+            .collect(),
+    )
+}
+
+/// Parses times saved by [`BestTimes`], written as a level iid and a number of seconds per line.
+/// Lines that can't be parsed are skipped.
+fn parse_best_times(contents: &str) -> BestTimes {
+    BestTimes(
+        contents
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.split_whitespace();
+                let level_iid = parts.next()?;
+                let secs = parts.next()?.parse().ok()?;
+                let time = Duration::try_from_secs_f64(secs).ok()?;
+                Some((level_iid.to_string(), time))
+            })
+            .collect(),
+    )
+}
+
+/// [`System`] that advances the time of the [`LevelRun`] while the level is being played.
+pub fn tick_level_run(mut level_run: ResMut<LevelRun>, time: Res<Time>) {
+    level_run.time.tick(time.delta());
+}
+
+/// [`System`] that counts deaths in the [`LevelRun`], and starts a new run when the level is
+/// entered or restarted. Restarting the level because the player died counts as a death instead.
+pub fn count_level_run_deaths(
+    mut ev_reset_level: EventReader<ResetLevel>,
+    mut ev_restart_level: EventReader<RestartLevelEvent>,
+    mut level_run: ResMut<LevelRun>,
+) {
+    let resets: Vec<ResetLevel> = ev_reset_level.read().copied().collect();
+    let after_death = ev_restart_level.read().any(|ev| ev.after_death);
+    if resets.contains(&ResetLevel::Switching) && !after_death {
+        *level_run = LevelRun::default();
+    } else if resets.contains(&ResetLevel::Respawn) {
+        level_run.deaths += 1;
+    }
+}
+
+/// [`System`] that sends a [`LevelCompletedEvent`] when the player moves on from a level while
+/// playing. Moving between levels from the level select doesn't count.
+pub fn complete_level_on_switch(
+    current_level: Res<CurrentLevel>,
+    mut prev_level_iid: Local<Option<LevelIid>>,
+    mut level_run: ResMut<LevelRun>,
+    game_state: Res<State<GameState>>,
+    mut ev_level_completed: EventWriter<LevelCompletedEvent>,
+) {
+    if prev_level_iid.as_ref() == Some(&current_level.level_iid) {
+        return;
+    }
+    let prev = prev_level_iid.replace(current_level.level_iid.clone());
+    let Some(prev) = prev else {
+        return;
+    };
+    if *game_state.get() != GameState::Playing || prev.as_str().is_empty() {
+        return;
+    }
+
+    ev_level_completed.send(LevelCompletedEvent {
+        level_iid: prev,
+        time: level_run.time.elapsed(),
+        deaths: level_run.deaths,
+    });
+    *level_run = LevelRun::default();
+}
+
+/// [`System`] that rates each [`LevelCompletedEvent`] against the level's par values, and saves
+/// the rating and time if they are the best so far.
+pub fn rate_completed_levels(
+    mut ev_level_completed: EventReader<LevelCompletedEvent>,
+    mut ev_level_rated: EventWriter<LevelRatedEvent>,
+    mut best_ratings: ResMut<BestRatings>,
+    mut best_times: ResMut<BestTimes>,
+    save_slots: Res<SaveSlots>,
+    ldtk_projects: Query<&LdtkProjectHandle>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+) {
+    let Ok(ldtk_handle) = ldtk_projects.get_single() else {
+        return;
+    };
+    let Ok(ldtk_levels) = get_ldtk_level_data(ldtk_project_assets.into_inner(), ldtk_handle) else {
+        return;
+    };
+
+    let mut improved = false;
+    let mut faster = false;
+    for completed in ev_level_completed.read() {
+        let Some(level) = ldtk_levels
+            .iter()
+            .find(|level| level.iid == completed.level_iid.as_str())
+        else {
+            continue;
+        };
+
+        let par = match (
+            level.get_float_field("ParTime"),
+            level.get_int_field("ParDeaths"),
+        ) {
+            (Ok(time_secs), Ok(deaths)) => Some(LevelPar {
+                time_secs: *time_secs,
+                deaths: (*deaths).max(0) as u32,
+            }),
+            _ => {
+                warn!(
+                    "Level {} has no ParTime or ParDeaths field, rating it as completed",
+                    completed.level_iid
+                );
+                None
+            }
+        };
+
+        let stars = level_rating(par, completed.time, completed.deaths);
+        improved |= best_ratings.record(completed.level_iid.as_str(), stars);
+        faster |= best_times.record(completed.level_iid.as_str(), completed.time);
+        ev_level_rated.send(LevelRatedEvent {
+            level_iid: completed.level_iid.clone(),
+            stars,
+        });
+    }
+
+    if improved {
+        save_slots.save_ratings(&best_ratings);
+    }
+    if faster {
+        save_slots.save_best_times(&best_times);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rating_thresholds_at_par() {
+        let par = Some(LevelPar {
+            time_secs: 30.0,
+            deaths: 2,
+        });
+        let secs = Duration::from_secs_f32;
+
+        assert_eq!(level_rating(par, secs(30.0), 2), 3);
+        assert_eq!(level_rating(par, secs(30.01), 2), 2);
+        assert_eq!(level_rating(par, secs(30.0), 3), 2);
+        assert_eq!(level_rating(par, secs(30.01), 3), 1);
+        assert_eq!(level_rating(None, secs(1.0), 0), 1);
+    }
+
+    #[test]
+    fn best_ratings_only_improve() {
+        let mut ratings = parse_best_ratings("level-a 2\nnot a rating\nlevel-b 1\n");
+        assert_eq!(ratings.0.len(), 2);
+
+        assert!(!ratings.record("level-a", 1));
+        assert!(!ratings.record("level-a", 2));
+        assert!(ratings.record("level-a", 3));
+        assert!(ratings.record("level-c", 1));
+        assert_eq!(ratings.0["level-a"], 3);
+    }
+
+    #[test]
+    fn best_times_only_improve() {
+        let mut times = parse_best_times("level-a 12.5\nlevel-b -1\nlevel-c\n");
+        assert_eq!(times.0.len(), 1);
+        let secs = Duration::from_secs_f32;
+
+        assert!(!times.record("level-a", secs(13.0)));
+        assert!(times.record("level-a", secs(11.0)));
+        assert!(times.record("level-b", secs(20.0)));
+        assert_eq!(times.0["level-a"], secs(11.0));
+    }
+}
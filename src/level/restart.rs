@@ -0,0 +1,357 @@
+use std::{f32::consts::TAU, time::Duration};
+
+use bevy::{
+    input::common_conditions::{input_just_pressed, input_pressed},
+    prelude::*,
+    time::Stopwatch,
+};
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::{
+    camera::{CameraTransition, CameraTransitionEvent},
+    config::Config,
+    shared::{AnimationState, GameState, ResetLevel},
+};
+
+use super::{CurrentLevel, LevelSystems};
+
+/// The key that restarts the level when held, see [`RetryHold`].
+const RETRY_KEY: KeyCode = KeyCode::KeyT;
+
+/// The number of dots in the ring of the [`RetryIndicator`].
+const RETRY_INDICATOR_DOTS: usize = 12;
+
+/// The radius of the ring of the [`RetryIndicator`], in pixels.
+const RETRY_INDICATOR_RADIUS: f32 = 20.0;
+
+const RETRY_INDICATOR_DOT_SIZE: f32 = 6.0;
+
+/// [`Plugin`] that restarts the current level from scratch when Shift + R is pressed, or when
+/// the retry key is held.
+pub struct LevelRestartPlugin;
+
+impl Plugin for LevelRestartPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<RestartLevelEvent>()
+            .init_resource::<RetryHold>()
+            .add_systems(Startup, spawn_retry_indicator)
+            .add_systems(
+                Update,
+                (
+                    send_restart_level.run_if(
+                        input_just_pressed(KeyCode::KeyR).and(input_pressed(KeyCode::ShiftLeft)),
+                    ),
+                    hold_to_retry,
+                    update_retry_indicator,
+                    restart_level
+                        .run_if(on_event::<RestartLevelEvent>)
+                        // restarting is allowed while paused, or while the death fade is playing,
+                        // but not while switching levels
+                        .run_if(
+                            in_state(GameState::Playing)
+                                .or(in_state(GameState::Paused))
+                                .or(in_state(AnimationState::Respawn)),
+                        ),
+                )
+                    .chain()
+                    .before(LevelSystems::Reset),
+            );
+    }
+}
+
+/// Send this event to respawn every entity in the current level and put the player back at the
+/// level's start flag, as if the level was just entered.
+#[derive(Event, Debug, Default)]
+pub struct RestartLevelEvent {
+    /// Whether the level restarts because the player died, in which case the player keeps their
+    /// lives and the death counts towards the current [`LevelRun`](super::rating::LevelRun)
+    pub after_death: bool,
+}
+
+pub fn send_restart_level(mut ev_restart_level: EventWriter<RestartLevelEvent>) {
+    ev_restart_level.send_default();
+}
+
+/// [`System`] that restarts the [`CurrentLevel`]. Ldtk respawns the level's entities, and the
+/// same [`ResetLevel`] events sent when entering a level and when respawning reset everything
+/// else. A death fade that is playing is cancelled, so the level is visible right away.
+pub fn restart_level(
+    mut commands: Commands,
+    mut ev_restart_level: EventReader<RestartLevelEvent>,
+    q_levels: Query<(Entity, &LevelIid)>,
+    current_level: Res<CurrentLevel>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+    mut ev_transition_camera: EventWriter<CameraTransitionEvent>,
+    mut ev_reset_level: EventWriter<ResetLevel>,
+) {
+    ev_restart_level.clear();
+
+    let Some((level_entity, _)) = q_levels
+        .iter()
+        .find(|(_, level_iid)| **level_iid == current_level.level_iid)
+    else {
+        return;
+    };
+    commands.entity(level_entity).insert(Respawn);
+
+    ev_transition_camera.send(CameraTransitionEvent {
+        duration: Duration::ZERO,
+        ease_fn: EaseFunction::Linear,
+        callback: None,
+        effect: CameraTransition::Cancel,
+    });
+    next_game_state.set(GameState::Playing);
+
+    // Switching resets the player's inventory like entering the level does, and Respawn moves the
+    // player back to the start flag
+    ev_reset_level.send(ResetLevel::Switching);
+    ev_reset_level.send(ResetLevel::Respawn);
+}
+
+/// [`Resource`] tracking how long the retry key has been held. Once it has been held for
+/// `retry_hold_secs` in the `death_config` section of `Lightborne.toml`, the level restarts like
+/// with Shift + R, and the key has to be let go before it can restart the level again.
+#[derive(Resource, Default, Debug)]
+pub struct RetryHold {
+    held: Stopwatch,
+    /// Whether the level was restarted since the key was pressed
+    restarted: bool,
+}
+
+impl RetryHold {
+    /// Updates the hold with whether the key is being held, returning whether the level should
+    /// restart.
+    pub fn update(&mut self, holding: bool, delta: Duration, hold_secs: f32) -> bool {
+        if !holding || hold_secs <= 0.0 {
+            self.held.reset();
+            self.restarted = false;
+            return false;
+        }
+        if self.restarted {
+            return false;
+        }
+        self.held.tick(delta);
+        if self.held.elapsed_secs() < hold_secs {
+            return false;
+        }
+        self.held.reset();
+        self.restarted = true;
+        true
+    }
+
+    /// How far the hold is from restarting the level, from 0 to 1.
+    pub fn progress(&self, hold_secs: f32) -> f32 {
+        if hold_secs <= 0.0 {
+            return 0.0;
+        }
+        (self.held.elapsed_secs() / hold_secs).min(1.0)
+    }
+}
+
+/// [`System`] that restarts the level once the retry key has been held long enough, see
+/// [`RetryHold`]. The key only counts while playing, so transitions, death fades and the pause
+/// menu cancel the hold.
+pub fn hold_to_retry(
+    keys: Res<ButtonInput<KeyCode>>,
+    state: Res<State<GameState>>,
+    mut retry_hold: ResMut<RetryHold>,
+    mut ev_restart_level: EventWriter<RestartLevelEvent>,
+    config: Res<Config>,
+    time: Res<Time>,
+) {
+    let holding = keys.pressed(RETRY_KEY) && *state.get() == GameState::Playing;
+    if retry_hold.update(holding, time.delta(), config.death_config.retry_hold_secs) {
+        ev_restart_level.send_default();
+    }
+}
+
+/// Marker [`Component`] for the ring of dots in the middle of the screen that fills up while the
+/// retry key is held.
+#[derive(Component)]
+pub struct RetryIndicator;
+
+/// [`Component`] for each dot of the [`RetryIndicator`], clockwise from the top.
+#[derive(Component)]
+pub struct RetryIndicatorDot(usize);
+
+/// Where the center of dot `index` of the [`RetryIndicator`] is, relative to the top left of the
+/// ring.
+fn retry_indicator_dot_position(index: usize) -> Vec2 {
+    let angle = index as f32 / RETRY_INDICATOR_DOTS as f32 * TAU;
+    Vec2::splat(RETRY_INDICATOR_RADIUS)
+        + Vec2::new(angle.sin(), -angle.cos()) * RETRY_INDICATOR_RADIUS
+}
+
+fn spawn_retry_indicator(mut commands: Commands) {
+    commands
+        .spawn((
+            RetryIndicator,
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            Visibility::Hidden,
+        ))
+        .with_children(|indicator| {
+            indicator
+                .spawn(Node {
+                    width: Val::Px(RETRY_INDICATOR_RADIUS * 2.0),
+                    height: Val::Px(RETRY_INDICATOR_RADIUS * 2.0),
+                    ..default()
+                })
+                .with_children(|ring| {
+                    for index in 0..RETRY_INDICATOR_DOTS {
+                        let corner = retry_indicator_dot_position(index)
+                            - Vec2::splat(RETRY_INDICATOR_DOT_SIZE / 2.0);
+                        ring.spawn((
+                            RetryIndicatorDot(index),
+                            Node {
+                                position_type: PositionType::Absolute,
+                                left: Val::Px(corner.x),
+                                top: Val::Px(corner.y),
+                                width: Val::Px(RETRY_INDICATOR_DOT_SIZE),
+                                height: Val::Px(RETRY_INDICATOR_DOT_SIZE),
+                                ..default()
+                            },
+                            BorderRadius::MAX,
+                            BackgroundColor(Color::srgba(1.0, 1.0, 1.0, 0.25)),
+                        ));
+                    }
+                });
+        });
+}
+
+/// [`System`] that shows the [`RetryIndicator`] while the retry key is held, lighting up its dots
+/// as the hold gets closer to restarting the level.
+pub fn update_retry_indicator(
+    mut q_indicator: Query<&mut Visibility, With<RetryIndicator>>,
+    mut q_dots: Query<(&RetryIndicatorDot, &mut BackgroundColor)>,
+    retry_hold: Res<RetryHold>,
+    config: Res<Config>,
+) {
+    let progress = retry_hold.progress(config.death_config.retry_hold_secs);
+    for mut visibility in q_indicator.iter_mut() {
+        visibility.set_if_neq(if progress > 0.0 {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        });
+    }
+    let lit_dots = (progress * RETRY_INDICATOR_DOTS as f32).ceil() as usize;
+    for (dot, mut color) in q_dots.iter_mut() {
+        let alpha = if dot.0 < lit_dots { 1.0 } else { 0.25 };
+        color.set_if_neq(BackgroundColor(Color::srgba(1.0, 1.0, 1.0, alpha)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::state::app::StatesPlugin;
+
+    use super::*;
+
+    #[derive(Resource, Default)]
+    struct ResetEvents(Vec<ResetLevel>);
+
+    fn collect_reset_events(
+        mut reset_events: ResMut<ResetEvents>,
+        mut ev_reset_level: EventReader<ResetLevel>,
+    ) {
+        reset_events.0.extend(ev_reset_level.read().copied());
+    }
+
+    #[test]
+    fn restart_respawns_current_level_like_a_fresh_entry() {
+        let mut app = App::new();
+        app.init_resource::<ResetEvents>()
+            .init_resource::<NextState<GameState>>()
+            .add_event::<RestartLevelEvent>()
+            .add_event::<CameraTransitionEvent>()
+            .add_event::<ResetLevel>()
+            .insert_resource(CurrentLevel {
+                level_iid: LevelIid::new("current"),
+                ..default()
+            })
+            .add_systems(Update, (restart_level, collect_reset_events).chain());
+
+        let current = app.world_mut().spawn(LevelIid::new("current")).id();
+        let other = app.world_mut().spawn(LevelIid::new("other")).id();
+
+        app.world_mut().send_event(RestartLevelEvent::default());
+        app.update();
+
+        assert!(app.world().get::<Respawn>(current).is_some());
+        assert!(app.world().get::<Respawn>(other).is_none());
+        assert_eq!(
+            app.world().resource::<ResetEvents>().0,
+            vec![ResetLevel::Switching, ResetLevel::Respawn]
+        );
+        assert!(matches!(
+            app.world().resource::<NextState<GameState>>(),
+            NextState::Pending(GameState::Playing)
+        ));
+    }
+
+    #[test]
+    fn hold_duration_gates_retry() {
+        let mut app = App::new();
+        app.add_plugins(StatesPlugin)
+            .insert_state(GameState::Playing)
+            .init_resource::<Time>()
+            .init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<RetryHold>()
+            .insert_resource(Config::default())
+            .add_event::<RestartLevelEvent>()
+            .add_systems(Update, hold_to_retry);
+        app.world_mut()
+            .resource_mut::<Config>()
+            .death_config
+            .retry_hold_secs = 1.0;
+
+        let advance = |app: &mut App, secs: f32| {
+            app.world_mut()
+                .resource_mut::<Time>()
+                .advance_by(Duration::from_secs_f32(secs));
+            app.update();
+            app.world_mut()
+                .resource_mut::<Events<RestartLevelEvent>>()
+                .drain()
+                .count()
+        };
+        let set_held = |app: &mut App, held: bool| {
+            let mut keys = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+            if held {
+                keys.press(RETRY_KEY);
+            } else {
+                keys.release(RETRY_KEY);
+            }
+        };
+
+        set_held(&mut app, true);
+        assert_eq!(advance(&mut app, 0.6), 0);
+        // letting go early cancels the hold
+        set_held(&mut app, false);
+        assert_eq!(advance(&mut app, 0.1), 0);
+        set_held(&mut app, true);
+        assert_eq!(advance(&mut app, 0.6), 0);
+        assert!((app.world().resource::<RetryHold>().progress(1.0) - 0.6).abs() < 1e-4);
+        assert_eq!(advance(&mut app, 0.5), 1);
+        // keeping the key held doesn't restart again
+        assert_eq!(advance(&mut app, 2.0), 0);
+
+        // transitions cancel the hold
+        set_held(&mut app, false);
+        advance(&mut app, 0.1);
+        set_held(&mut app, true);
+        assert_eq!(advance(&mut app, 0.6), 0);
+        app.world_mut()
+            .resource_mut::<NextState<GameState>>()
+            .set(GameState::Animating);
+        assert_eq!(advance(&mut app, 0.6), 0);
+        assert_eq!(app.world().resource::<RetryHold>().progress(1.0), 0.0);
+    }
+}
@@ -0,0 +1,138 @@
+use std::ops::Range;
+
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::config::Config;
+
+use super::{CurrentLevel, LevelSystems};
+
+/// [`Plugin`] for the [`LevelRng`].
+pub struct LevelRngPlugin;
+
+impl Plugin for LevelRngPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LevelRng>()
+            .add_systems(Update, reseed_level_rng.in_set(LevelSystems::Reset));
+    }
+}
+
+/// [`Resource`] for randomness that affects gameplay, like the phases of hazards. It is seeded
+/// from the level's iid and the `level_config.run_seed` in `Lightborne.toml`, and reseeded every
+/// time the level is reset, so a level plays out the same way every time it is entered with the
+/// same run seed. This keeps recorded demos and speedruns fair. Cosmetic randomness, like
+/// particles and camera shake, should keep using [`rand::rng`].
+///
+/// Systems that reset in [`LevelSystems::Reset`] and read from the [`LevelRng`] need to run
+/// after [`reseed_level_rng`]. Entities that need their own randomness should use
+/// [`LevelRng::fork`], which doesn't depend on the order entities are visited in.
+#[derive(Resource, Debug)]
+pub struct LevelRng {
+    seed: u64,
+    rng: StdRng,
+}
+
+impl Default for LevelRng {
+    fn default() -> Self {
+        LevelRng::new(0)
+    }
+}
+
+impl LevelRng {
+    pub fn new(seed: u64) -> Self {
+        LevelRng {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// The [`LevelRng`] for the level with `level_iid`.
+    pub fn for_level(level_iid: &str, run_seed: u64) -> Self {
+        LevelRng::new(hash_str(level_iid) ^ run_seed.rotate_left(32))
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn random_range(&mut self, range: Range<f32>) -> f32 {
+        if range.is_empty() {
+            return range.start;
+        }
+        self.rng.random_range(range)
+    }
+
+    /// A separate rng for `key`, like the iid of an entity, that only depends on the level's seed
+    /// and the key.
+    pub fn fork(&self, key: &str) -> StdRng {
+        StdRng::seed_from_u64(self.seed ^ hash_str(key))
+    }
+}
+
+/// FNV-1a hash of `s`. Unlike the hashers in [`std`], it is the same on every platform and Rust
+/// version, so seeds don't change between builds.
+fn hash_str(s: &str) -> u64 {
+    s.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// [`System`] that reseeds the [`LevelRng`] for the [`CurrentLevel`] when the level is reset.
+pub fn reseed_level_rng(
+    mut level_rng: ResMut<LevelRng>,
+    current_level: Res<CurrentLevel>,
+    config: Res<Config>,
+) {
+    *level_rng = LevelRng::for_level(
+        current_level.level_iid.as_str(),
+        config.level_config.run_seed,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_ecs_ldtk::LevelIid;
+
+    use super::*;
+
+    fn load_level(app: &mut App, level_iid: &str) -> Vec<f32> {
+        app.world_mut().resource_mut::<CurrentLevel>().level_iid = LevelIid::new(level_iid);
+        app.update();
+        let mut level_rng = app.world_mut().resource_mut::<LevelRng>();
+        (0..8).map(|_| level_rng.random_range(0.0..1.0)).collect()
+    }
+
+    #[test]
+    fn same_level_rolls_the_same_numbers() {
+        let mut app = App::new();
+        let mut config = Config::default();
+        config.level_config.run_seed = 7;
+        app.insert_resource(config)
+            .init_resource::<CurrentLevel>()
+            .init_resource::<LevelRng>()
+            .add_systems(Update, reseed_level_rng);
+
+        let first = load_level(&mut app, "level-a");
+        let other = load_level(&mut app, "level-b");
+        // coming back to a level mid-run starts it over
+        let second = load_level(&mut app, "level-a");
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+
+        app.world_mut()
+            .resource_mut::<Config>()
+            .level_config
+            .run_seed = 8;
+        assert_ne!(load_level(&mut app, "level-a"), first);
+    }
+
+    #[test]
+    fn forks_only_depend_on_seed_and_key() {
+        let mut level_rng = LevelRng::for_level("level-a", 0);
+        let a: f32 = level_rng.fork("entity").random();
+        level_rng.random_range(0.0..1.0);
+        assert_eq!(level_rng.fork("entity").random::<f32>(), a);
+        assert_ne!(level_rng.fork("other").random::<f32>(), a);
+        assert_eq!(level_rng.random_range(1.0..1.0), 1.0);
+    }
+}
@@ -0,0 +1,280 @@
+use bevy::{prelude::*, utils::HashSet};
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::lighting::{LightRoomMask, LineLight2d, MAX_LIGHT_ROOM_RECTS};
+
+use super::LevelSystems;
+
+/// [`Plugin`] for [`Room`]s, which keep the lights inside of them from lighting the rooms around
+/// them through their walls.
+pub struct RoomPlugin;
+
+impl Plugin for RoomPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity::<RoomBundle>("Room")
+            .register_ldtk_entity::<RoomDoorwayBundle>("RoomDoorway")
+            .add_systems(
+                PreUpdate,
+                assign_light_rooms.in_set(LevelSystems::Processing),
+            )
+            .add_systems(Update, update_light_room_masks);
+    }
+}
+
+/// [`Component`] for a rectangular room, placed in Ldtk with a `room_id`. Lights with a
+/// [`LightRoom`] only light the rooms with their id, and the rooms those open into through a
+/// [`RoomDoorway`]. Rooms that aren't rectangles can be made of several rooms with the same id.
+#[derive(Component, Clone, Debug)]
+pub struct Room {
+    pub id: String,
+    pub half_size: Vec2,
+}
+
+impl From<&EntityInstance> for Room {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        let id = entity_instance
+            .get_string_field("room_id")
+            .expect("room_id needs to be a string field on all rooms");
+
+        Room {
+            id: id.clone(),
+            half_size: Vec2::new(
+                entity_instance.width as f32 / 2.0,
+                entity_instance.height as f32 / 2.0,
+            ),
+        }
+    }
+}
+
+/// [`Component`] for an opening between [`Room`]s, placed in Ldtk across the wall between them so
+/// it overlaps both. Light spills through it from one room into the other, where it is only
+/// stopped by the usual shadows.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct RoomDoorway {
+    pub half_size: Vec2,
+}
+
+impl From<&EntityInstance> for RoomDoorway {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        RoomDoorway {
+            half_size: Vec2::new(
+                entity_instance.width as f32 / 2.0,
+                entity_instance.height as f32 / 2.0,
+            ),
+        }
+    }
+}
+
+/// [`Bundle`] that includes all the [`Component`]s needed for a [`Room`] to function properly.
+#[derive(Bundle, LdtkEntity)]
+pub struct RoomBundle {
+    #[from_entity_instance]
+    room: Room,
+}
+
+/// [`Bundle`] that includes all the [`Component`]s needed for a [`RoomDoorway`] to function
+/// properly.
+#[derive(Bundle, LdtkEntity)]
+pub struct RoomDoorwayBundle {
+    #[from_entity_instance]
+    doorway: RoomDoorway,
+}
+
+/// [`Component`] for lights that only light the [`Room`]s with this id, see [`room_mask`]. Set on
+/// lights placed in Ldtk with a `room` field.
+#[derive(Component, Clone, Debug, PartialEq, Eq)]
+#[require(LightRoomMask)]
+pub struct LightRoom(pub String);
+
+/// Whether `a` and `b` overlap. Rectangles that only touch don't.
+fn overlaps(a: Rect, b: Rect) -> bool {
+    !a.intersect(b).is_empty()
+}
+
+/// The parts of the world a light in the room `room_id` lights, given the id and bounds of every
+/// room and the bounds of every doorway. That is the room itself, the doorways out of it, and the
+/// rooms on the other side of those doorways, in that order.
+pub fn room_mask(room_id: &str, rooms: &[(&str, Rect)], doorways: &[Rect]) -> Vec<Rect> {
+    let own: Vec<Rect> = rooms
+        .iter()
+        .filter(|(id, _)| *id == room_id)
+        .map(|(_, rect)| *rect)
+        .collect();
+    let open: Vec<Rect> = doorways
+        .iter()
+        .copied()
+        .filter(|doorway| own.iter().any(|rect| overlaps(*rect, *doorway)))
+        .collect();
+    let neighbors: Vec<&str> = rooms
+        .iter()
+        .filter(|(id, rect)| *id != room_id && open.iter().any(|doorway| overlaps(*rect, *doorway)))
+        .map(|(id, _)| *id)
+        .collect();
+    let beyond = rooms
+        .iter()
+        .filter(|(id, _)| neighbors.contains(id))
+        .map(|(_, rect)| *rect);
+
+    own.iter().copied().chain(open).chain(beyond).collect()
+}
+
+/// [`System`] that adds a [`LightRoom`] to lights placed in Ldtk with a `room` field.
+pub fn assign_light_rooms(
+    mut commands: Commands,
+    q_lights: Query<(Entity, &EntityInstance), (Added<EntityInstance>, With<LineLight2d>)>,
+) {
+    for (entity, entity_instance) in q_lights.iter() {
+        if let Ok(room_id) = entity_instance.get_string_field("room") {
+            commands.entity(entity).insert(LightRoom(room_id.clone()));
+        }
+    }
+}
+
+/// [`System`] that sets the [`LightRoomMask`] of every light with a [`LightRoom`] from the
+/// [`Room`]s and [`RoomDoorway`]s around it. Warns once about each room id that no room has, since
+/// the empty mask of a light in a misspelled room lights everything, and about masks with more
+/// rectangles than the shader can hold.
+pub fn update_light_room_masks(
+    mut q_lights: Query<(&LightRoom, &mut LightRoomMask)>,
+    q_rooms: Query<(&Room, &GlobalTransform)>,
+    q_doorways: Query<(&RoomDoorway, &GlobalTransform)>,
+    mut missing_rooms: Local<HashSet<String>>,
+) {
+    let rooms: Vec<(&str, Rect)> = q_rooms
+        .iter()
+        .map(|(room, transform)| {
+            let rect = Rect::from_center_half_size(transform.translation().xy(), room.half_size);
+            (room.id.as_str(), rect)
+        })
+        .collect();
+    let doorways: Vec<Rect> = q_doorways
+        .iter()
+        .map(|(doorway, transform)| {
+            Rect::from_center_half_size(transform.translation().xy(), doorway.half_size)
+        })
+        .collect();
+
+    for (light_room, mut mask) in q_lights.iter_mut() {
+        // the rooms of a level may not have spawned yet
+        let room_exists = rooms.iter().any(|(id, _)| *id == light_room.0);
+        if !rooms.is_empty() && !room_exists && missing_rooms.insert(light_room.0.clone()) {
+            warn!(
+                "No room has the id {:?} of a light's room, so the light lights everything",
+                light_room.0
+            );
+        }
+
+        let rects = room_mask(&light_room.0, &rooms, &doorways);
+        if mask.rects != rects {
+            if rects.len() > MAX_LIGHT_ROOM_RECTS {
+                warn!(
+                    "The light mask of room {:?} has {} rectangles, only the first {} are used",
+                    light_room.0,
+                    rects.len(),
+                    MAX_LIGHT_ROOM_RECTS
+                );
+            }
+            mask.rects = rects;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn room_app() -> (App, Entity) {
+        let mut app = App::new();
+        app.add_systems(Update, update_light_room_masks);
+        let mut spawn_room = |id: &str, center: Vec2, half_size: Vec2| {
+            app.world_mut().spawn((
+                Room {
+                    id: id.into(),
+                    half_size,
+                },
+                GlobalTransform::from_translation(center.extend(0.0)),
+            ));
+        };
+        // two rooms with a thin wall between them, and a corridor past the corner of the first
+        spawn_room("a", Vec2::ZERO, Vec2::new(50.0, 30.0));
+        spawn_room("b", Vec2::new(110.0, 0.0), Vec2::new(50.0, 30.0));
+        spawn_room("corridor", Vec2::new(105.0, -50.0), Vec2::new(50.0, 10.0));
+        let lamp = app
+            .world_mut()
+            .spawn((LineLight2d::default(), LightRoom("a".into())))
+            .id();
+        (app, lamp)
+    }
+
+    fn lit(app: &App, lamp: Entity, point: Vec2) -> bool {
+        app.world()
+            .get::<LightRoomMask>(lamp)
+            .unwrap()
+            .contains(point)
+    }
+
+    #[test]
+    fn lights_stay_in_their_room() {
+        let (mut app, lamp) = room_app();
+        app.update();
+        assert!(lit(&app, lamp, Vec2::new(-40.0, 20.0)));
+        // through the wall, and around the corner
+        assert!(!lit(&app, lamp, Vec2::new(55.0, 0.0)));
+        assert!(!lit(&app, lamp, Vec2::new(70.0, 0.0)));
+        assert!(!lit(&app, lamp, Vec2::new(58.0, -45.0)));
+
+        // a doorway in the wall lets light spill into the next room
+        app.world_mut().spawn((
+            RoomDoorway {
+                half_size: Vec2::new(10.0, 8.0),
+            },
+            GlobalTransform::from_xyz(55.0, 0.0, 0.0),
+        ));
+        app.update();
+        assert!(lit(&app, lamp, Vec2::new(55.0, 0.0)));
+        assert!(lit(&app, lamp, Vec2::new(150.0, 25.0)));
+        assert!(!lit(&app, lamp, Vec2::new(58.0, -45.0)));
+    }
+
+    #[test]
+    fn room_mask_follows_doorways_one_room_deep() {
+        let rooms = [
+            ("a", Rect::new(0.0, 0.0, 10.0, 10.0)),
+            ("a", Rect::new(10.0, 0.0, 20.0, 5.0)),
+            ("b", Rect::new(22.0, 0.0, 30.0, 10.0)),
+            ("c", Rect::new(32.0, 0.0, 40.0, 10.0)),
+        ];
+        let doorways = [
+            Rect::new(18.0, 1.0, 24.0, 4.0),
+            Rect::new(28.0, 1.0, 34.0, 4.0),
+        ];
+
+        assert_eq!(room_mask("a", &rooms, &[]), vec![rooms[0].1, rooms[1].1]);
+        assert_eq!(
+            room_mask("a", &rooms, &doorways),
+            vec![rooms[0].1, rooms[1].1, doorways[0], rooms[2].1]
+        );
+        assert_eq!(
+            room_mask("b", &rooms, &doorways),
+            vec![
+                rooms[2].1,
+                doorways[0],
+                doorways[1],
+                rooms[0].1,
+                rooms[1].1,
+                rooms[3].1
+            ]
+        );
+        assert!(room_mask("missing", &rooms, &doorways).is_empty());
+
+        // every rectangle of the mask is lit, including the ones past the first few
+        let mask = LightRoomMask {
+            rects: room_mask("b", &rooms, &doorways),
+        };
+        assert_eq!(mask.shader_rects().1, 6);
+        assert!(mask.contains(Vec2::new(15.0, 2.0)));
+        assert!(mask.contains(Vec2::new(36.0, 5.0)));
+        assert!(!mask.contains(Vec2::new(15.0, 8.0)));
+        assert!(!mask.contains(Vec2::new(31.0, 8.0)));
+    }
+}
@@ -0,0 +1,238 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use bevy_ecs_ldtk::{prelude::*, EntityIid};
+use rand::Rng;
+
+use crate::lighting::LineLight2d;
+
+use super::{
+    rng::{reseed_level_rng, LevelRng},
+    LevelSystems,
+};
+
+/// [`Plugin`] for lights that sweep back and forth, like searchlights.
+pub struct SearchlightPlugin;
+
+impl Plugin for SearchlightPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity::<SearchlightBundle>("Searchlight")
+            .add_systems(
+                PreUpdate,
+                add_searchlight_beams.in_set(LevelSystems::Processing),
+            )
+            .add_systems(
+                Update,
+                reset_light_sweeps
+                    .after(reseed_level_rng)
+                    .in_set(LevelSystems::Reset),
+            )
+            .add_systems(FixedUpdate, sweep_lights.in_set(LevelSystems::Simulation));
+    }
+}
+
+/// [`Component`] that rotates an entity back and forth over an `arc` (in radians) centered on its
+/// starting rotation. The sweep follows a sine wave, so it slows down smoothly at each end, and it
+/// is driven by the fixed timestep so it is the same every time a level is played.
+#[derive(Component, Debug)]
+pub struct LightSweep {
+    /// The fastest the sweep turns, in radians per second
+    pub speed: f32,
+    pub arc: f32,
+    /// Whether the sweep starts at a random point of its loop, drawn from the [`LevelRng`] so
+    /// it's the same every time the level is played with the same run seed
+    pub random_phase: bool,
+    elapsed: f32,
+    base_rotation: Option<Quat>,
+}
+
+impl LightSweep {
+    pub fn new(speed: f32, arc: f32) -> Self {
+        LightSweep {
+            speed,
+            arc,
+            random_phase: false,
+            elapsed: 0.0,
+            base_rotation: None,
+        }
+    }
+
+    /// How long it takes the sweep to loop, see [`sweep_angle`].
+    pub fn period(&self) -> f32 {
+        if self.arc <= 0.0 || self.speed <= 0.0 {
+            return 0.0;
+        }
+        TAU * (self.arc / 2.0) / self.speed
+    }
+
+    /// The point of its loop the sweep starts at, for the sweep with the given `iid`.
+    pub fn start_elapsed(&self, iid: Option<&EntityIid>, level_rng: &LevelRng) -> f32 {
+        let period = self.period();
+        match iid {
+            Some(iid) if self.random_phase && period > 0.0 => {
+                level_rng.fork(iid.as_str()).random_range(0.0..period)
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+/// The angle of a [`LightSweep`] relative to its starting rotation after `elapsed` seconds. The
+/// sweep loops every `2 * PI * (arc / 2) / speed` seconds, which is when a sine wave with an
+/// amplitude of `arc / 2` reaches a peak speed of `speed`.
+pub fn sweep_angle(elapsed: f32, speed: f32, arc: f32) -> f32 {
+    let amplitude = arc / 2.0;
+    if amplitude <= 0.0 || speed <= 0.0 {
+        return 0.0;
+    }
+    let period = TAU * amplitude / speed;
+    amplitude * (TAU * (elapsed % period) / period).sin()
+}
+
+/// [`Component`] for searchlights placed in Ldtk. The searchlight sweeps a long
+/// [`LineLight2d`] around its position.
+#[derive(Component, Debug)]
+pub struct Searchlight {
+    /// How far the light reaches from the searchlight
+    pub reach: f32,
+    pub color: Vec4,
+}
+
+impl From<&EntityInstance> for Searchlight {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        let reach = *entity_instance
+            .get_float_field("reach")
+            .expect("reach needs to be a float field on all searchlights");
+        Searchlight {
+            reach,
+            color: Vec4::new(1.0, 0.95, 0.8, 0.8),
+        }
+    }
+}
+
+impl From<&EntityInstance> for LightSweep {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        let speed = *entity_instance
+            .get_float_field("sweep_speed")
+            .expect("sweep_speed needs to be a float field on all searchlights");
+        let arc = *entity_instance
+            .get_float_field("sweep_arc")
+            .expect("sweep_arc needs to be a float field on all searchlights");
+        let random_phase = entity_instance
+            .get_bool_field("random_phase")
+            .copied()
+            .unwrap_or(false);
+        LightSweep {
+            random_phase,
+            ..LightSweep::new(speed.to_radians(), arc.to_radians())
+        }
+    }
+}
+
+/// [`Bundle`] that includes all the [`Component`]s needed for a [`Searchlight`] to function
+/// properly.
+#[derive(Bundle, LdtkEntity)]
+pub struct SearchlightBundle {
+    #[from_entity_instance]
+    searchlight: Searchlight,
+    #[from_entity_instance]
+    sweep: LightSweep,
+}
+
+/// [`System`] that gives new [`Searchlight`]s their light. The light is a child so that it
+/// extends out from the searchlight instead of being centered on it.
+pub fn add_searchlight_beams(
+    mut commands: Commands,
+    q_searchlights: Query<(Entity, &Searchlight), Added<Searchlight>>,
+) {
+    for (entity, searchlight) in q_searchlights.iter() {
+        let half_length = searchlight.reach / 2.0;
+        commands.entity(entity).with_child((
+            LineLight2d {
+                color: searchlight.color,
+                half_length,
+                radius: 20.0,
+                inner_radius: 0.0,
+                volumetric_intensity: 0.01,
+                temperature_k: None,
+                shadow_tint: Vec4::ZERO,
+                falloff_texture: None,
+            },
+            Transform::from_xyz(half_length, 0.0, 0.0),
+        ));
+    }
+}
+
+/// [`System`] that restarts every [`LightSweep`] when the level is reset, so that sweeps are in
+/// the same place every time the player respawns.
+pub fn reset_light_sweeps(
+    mut q_sweeps: Query<(&mut LightSweep, &mut Transform, Option<&EntityIid>)>,
+    level_rng: Res<LevelRng>,
+) {
+    for (mut sweep, mut transform, iid) in q_sweeps.iter_mut() {
+        sweep.elapsed = sweep.start_elapsed(iid, &level_rng);
+        if let Some(base_rotation) = sweep.base_rotation {
+            transform.rotation = base_rotation;
+        }
+    }
+}
+
+/// [`System`] that rotates every [`LightSweep`].
+pub fn sweep_lights(
+    mut q_sweeps: Query<(&mut LightSweep, &mut Transform, Option<&EntityIid>)>,
+    level_rng: Res<LevelRng>,
+    time: Res<Time>,
+) {
+    for (mut sweep, mut transform, iid) in q_sweeps.iter_mut() {
+        if sweep.base_rotation.is_none() {
+            sweep.elapsed = sweep.start_elapsed(iid, &level_rng);
+        }
+        let base_rotation = *sweep.base_rotation.get_or_insert(transform.rotation);
+        sweep.elapsed += time.delta_secs();
+        let angle = sweep_angle(sweep.elapsed, sweep.speed, sweep.arc);
+        transform.rotation = base_rotation * Quat::from_rotation_z(angle);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use super::*;
+
+    #[test]
+    fn sweep_stays_within_arc_and_loops() {
+        let speed = 1.0;
+        let arc = FRAC_PI_2;
+        let period = TAU * (arc / 2.0) / speed;
+
+        let dt = 1.0 / 64.0;
+        let mut prev = sweep_angle(0.0, speed, arc);
+        let mut t = dt;
+        while t < period * 2.0 {
+            let angle = sweep_angle(t, speed, arc);
+            assert!(angle.abs() <= arc / 2.0 + 1e-5);
+            // never turns faster than the sweep speed
+            assert!((angle - prev).abs() <= speed * dt + 1e-5);
+            prev = angle;
+            t += dt;
+        }
+
+        assert!((sweep_angle(period, speed, arc) - sweep_angle(0.0, speed, arc)).abs() < 1e-5);
+        assert!((sweep_angle(period / 4.0, speed, arc) - arc / 2.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn random_phase_is_the_same_every_load() {
+        let iid = EntityIid::new("searchlight");
+        let mut sweep = LightSweep::new(1.0, FRAC_PI_2);
+        let level_rng = LevelRng::for_level("level", 3);
+        assert_eq!(sweep.start_elapsed(Some(&iid), &level_rng), 0.0);
+
+        sweep.random_phase = true;
+        let start = sweep.start_elapsed(Some(&iid), &level_rng);
+        assert!((0.0..sweep.period()).contains(&start));
+        let reloaded = LevelRng::for_level("level", 3);
+        assert_eq!(sweep.start_elapsed(Some(&iid), &reloaded), start);
+    }
+}
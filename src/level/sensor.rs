@@ -16,6 +16,7 @@ pub struct LightSensorPlugin;
 impl Plugin for LightSensorPlugin {
     fn build(&self, app: &mut App) {
         app.register_ldtk_entity::<LightSensorBundle>("Sensor")
+            .add_event::<SwitchChangedEvent>()
             .add_systems(
                 PreUpdate,
                 add_sensor_sprites.in_set(LevelSystems::Processing),
@@ -30,6 +31,14 @@ impl Plugin for LightSensorPlugin {
     }
 }
 
+/// [`Event`] sent when a switch, like a [`LightSensor`] or a
+/// [`PressurePlate`](super::pressure_plate::PressurePlate), turns on or off.
+#[derive(Event, Debug)]
+pub struct SwitchChangedEvent {
+    pub switch: Entity,
+    pub is_active: bool,
+}
+
 /// [`Component`] added to entities receptive to light. The
 /// [`activation_timer`](LightSensor::activation_timer) should be initialized in the
 /// `From<&EntityInstance>` implemenation for the [`LightSensorBundle`], if not default.
@@ -55,7 +64,7 @@ pub struct LightSensor {
 }
 
 impl LightSensor {
-    fn new(toggle_ident: CrystalIdent, millis: i32) -> Self {
+    pub fn new(toggle_ident: CrystalIdent, millis: i32) -> Self {
         let rate = 1.0 / (millis as f32) * (1000.0 / 64.0);
         LightSensor {
             meter: 0.0,
@@ -110,6 +119,17 @@ impl From<&EntityInstance> for LightSensor {
     }
 }
 
+/// Whether the sensor is one that light beams pass through instead of stopping at, so one beam
+/// can hold several of them down at once. Set with the optional `pass_through` bool field.
+/// Anything that stops the beam still keeps it from reaching the sensors behind it, see
+/// [`play_light_beam`](crate::light::segments::play_light_beam).
+pub fn is_pass_through_sensor(entity_instance: &EntityInstance) -> bool {
+    entity_instance
+        .get_bool_field("pass_through")
+        .copied()
+        .unwrap_or(false)
+}
+
 pub fn add_sensor_sprites(
     mut commands: Commands,
     q_sensors: Query<(Entity, &LightSensor), Added<LightSensor>>,
@@ -189,6 +209,7 @@ pub fn update_light_sensors(
     mut commands: Commands,
     mut q_sensors: Query<(Entity, &mut LightSensor, &mut Sprite)>,
     mut ev_crystal_toggle: EventWriter<CrystalToggleEvent>,
+    mut ev_switch_changed: EventWriter<SwitchChangedEvent>,
     asset_server: Res<AssetServer>,
     time: Res<Time>,
 ) {
@@ -210,10 +231,14 @@ pub fn update_light_sensors(
         let juice = if was_hit { sensor.rate } else { -sensor.rate };
         sensor.meter += juice;
 
-        let mut send_toggle = || {
+        let mut send_toggle = |is_active: bool| {
             ev_crystal_toggle.send(CrystalToggleEvent {
                 color: sensor.toggle_ident,
             });
+            ev_switch_changed.send(SwitchChangedEvent {
+                switch: entity,
+                is_active,
+            });
             commands.entity(entity).with_child((
                 AudioPlayer::new(asset_server.load("sfx/button.wav")),
                 PlaybackSettings::DESPAWN,
@@ -222,13 +247,13 @@ pub fn update_light_sensors(
 
         if sensor.meter > 1.0 {
             if !sensor.is_active {
-                send_toggle();
+                send_toggle(true);
                 sensor.is_active = true;
             }
             sensor.meter = 1.0;
         } else if sensor.meter < 0.0 {
             if sensor.is_active {
-                send_toggle();
+                send_toggle(false);
                 sensor.is_active = false;
             }
             sensor.meter = 0.0;
@@ -0,0 +1,264 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::{
+    level::crystal::{CrystalColor, CrystalIdent, CrystalToggleEvent},
+    light::{events::BeamReflectedEvent, segments::simulate_light_sources},
+};
+
+use super::{entity::FixedEntityBundle, sensor::SwitchChangedEvent, LevelSystems, LightColor};
+
+/// [`Plugin`] for switches that need to be hit by beams of the right colors in the right order.
+pub struct SequenceSwitchPlugin;
+
+impl Plugin for SequenceSwitchPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SwitchChangedEvent>()
+            .register_ldtk_entity::<SequenceSwitchBundle>("SequenceSwitch")
+            .add_systems(Update, reset_sequence_switches.in_set(LevelSystems::Reset))
+            .add_systems(
+                FixedUpdate,
+                update_sequence_switches
+                    .after(simulate_light_sources)
+                    .in_set(LevelSystems::Simulation),
+            );
+    }
+}
+
+/// [`Component`] for switches that activate once they are hit by beams with the colors of
+/// `sequence`, in order, with at most `window` seconds between two hits. A wrong color or running
+/// out of time starts the sequence over, and once active the switch stays on until the level is
+/// reset.
+///
+/// The sequence only advances when the color hitting the switch changes, so a beam that keeps
+/// hitting the switch, or a second beam of the same color, can't count twice. This means a
+/// sequence can't have the same color twice in a row.
+#[derive(Component, Debug)]
+pub struct SequenceSwitch {
+    pub sequence: Vec<LightColor>,
+    /// Seconds allowed between two hits of the sequence
+    pub window: f32,
+    /// How many colors of the sequence have been hit
+    pub progress: usize,
+    pub is_active: bool,
+    /// The color of the crystals to toggle
+    pub toggle_ident: CrystalIdent,
+    /// The last color that hit the switch
+    last_color: Option<LightColor>,
+    /// Seconds since the sequence last advanced
+    timer: f32,
+}
+
+impl SequenceSwitch {
+    pub fn new(sequence: Vec<LightColor>, window: f32, toggle_ident: CrystalIdent) -> Self {
+        SequenceSwitch {
+            sequence,
+            window,
+            progress: 0,
+            is_active: false,
+            toggle_ident,
+            last_color: None,
+            timer: 0.0,
+        }
+    }
+
+    /// Registers a beam of `color` hitting the switch, returning whether it activated the switch.
+    pub fn hit(&mut self, color: LightColor) -> bool {
+        if self.is_active || self.last_color == Some(color) {
+            return false;
+        }
+        self.last_color = Some(color);
+        self.timer = 0.0;
+
+        if self.sequence.get(self.progress) == Some(&color) {
+            self.progress += 1;
+        } else {
+            // the wrong color can still start the sequence over
+            self.progress = usize::from(self.sequence.first() == Some(&color));
+        }
+        self.is_active = self.progress == self.sequence.len();
+        self.is_active
+    }
+
+    /// Advances the time since the sequence last advanced, starting the sequence over if it is
+    /// longer than `window`.
+    pub fn tick(&mut self, delta: f32) {
+        if self.is_active || self.progress == 0 {
+            return;
+        }
+        self.timer += delta;
+        if self.timer > self.window {
+            self.reset();
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.progress = 0;
+        self.is_active = false;
+        self.last_color = None;
+        self.timer = 0.0;
+    }
+}
+
+impl From<&EntityInstance> for SequenceSwitch {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        let toggle_color: CrystalColor = entity_instance
+            .get_enum_field("toggle_color")
+            .expect("toggle_color needs to be an enum field on all sequence switches")
+            .into();
+
+        let id = entity_instance
+            .get_int_field("id")
+            .expect("id needs to be an int field on all sequence switches");
+
+        let sequence = entity_instance
+            .get_enums_field("sequence")
+            .expect("sequence needs to be an enum array field on all sequence switches")
+            .iter()
+            .flatten()
+            .map(LightColor::from)
+            .collect();
+
+        let window = *entity_instance
+            .get_float_field("window")
+            .expect("window needs to be a float field on all sequence switches");
+
+        SequenceSwitch::new(
+            sequence,
+            window,
+            CrystalIdent {
+                color: toggle_color,
+                id: *id,
+            },
+        )
+    }
+}
+
+/// [`Bundle`] that includes all the [`Component`]s needed for a [`SequenceSwitch`] to function
+/// properly.
+#[derive(Bundle, LdtkEntity)]
+pub struct SequenceSwitchBundle {
+    #[from_entity_instance]
+    physics: FixedEntityBundle,
+    #[from_entity_instance]
+    sequence_switch: SequenceSwitch,
+    #[with(sequence_switch_sprite)]
+    sprite: Sprite,
+}
+
+pub fn sequence_switch_sprite(entity_instance: &EntityInstance) -> Sprite {
+    let sequence_switch = SequenceSwitch::from(entity_instance);
+    Sprite::from_color(
+        sequence_switch
+            .toggle_ident
+            .color
+            .button_color()
+            .darker(0.3),
+        Vec2::splat(8.0),
+    )
+}
+
+/// [`System`] that starts every [`SequenceSwitch`] over when the level is reset.
+pub fn reset_sequence_switches(mut q_switches: Query<(&mut SequenceSwitch, &mut Sprite)>) {
+    for (mut sequence_switch, mut sprite) in q_switches.iter_mut() {
+        sequence_switch.reset();
+        sprite.color = sequence_switch
+            .toggle_ident
+            .color
+            .button_color()
+            .darker(0.3);
+    }
+}
+
+/// [`System`] that advances each [`SequenceSwitch`] with the beams that reached it, and toggles
+/// its crystals when it activates.
+pub fn update_sequence_switches(
+    mut q_switches: Query<(Entity, &mut SequenceSwitch, &mut Sprite)>,
+    mut ev_beam_reflected: EventReader<BeamReflectedEvent>,
+    mut ev_crystal_toggle: EventWriter<CrystalToggleEvent>,
+    mut ev_switch_changed: EventWriter<SwitchChangedEvent>,
+    time: Res<Time>,
+) {
+    for (_, mut sequence_switch, _) in q_switches.iter_mut() {
+        sequence_switch.tick(time.delta_secs());
+    }
+
+    for event in ev_beam_reflected.read() {
+        let Ok((entity, mut sequence_switch, mut sprite)) = q_switches.get_mut(event.entity) else {
+            continue;
+        };
+        if !sequence_switch.hit(event.color) {
+            continue;
+        }
+
+        ev_crystal_toggle.send(CrystalToggleEvent {
+            color: sequence_switch.toggle_ident,
+        });
+        ev_switch_changed.send(SwitchChangedEvent {
+            switch: entity,
+            is_active: true,
+        });
+        sprite.color = sequence_switch.toggle_ident.color.button_color();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sequence_switch() -> SequenceSwitch {
+        SequenceSwitch::new(
+            vec![LightColor::Purple, LightColor::Green, LightColor::Blue],
+            1.0,
+            CrystalIdent {
+                color: CrystalColor::Red,
+                id: 0,
+            },
+        )
+    }
+
+    #[test]
+    fn correct_sequence_activates() {
+        let mut sequence_switch = sequence_switch();
+        assert!(!sequence_switch.hit(LightColor::Purple));
+        sequence_switch.tick(0.5);
+        assert!(!sequence_switch.hit(LightColor::Green));
+        // holding the green beam on the switch doesn't advance it again
+        assert!(!sequence_switch.hit(LightColor::Green));
+        assert_eq!(sequence_switch.progress, 2);
+        sequence_switch.tick(0.5);
+        assert!(sequence_switch.hit(LightColor::Blue));
+        assert!(sequence_switch.is_active);
+
+        // active switches ignore everything
+        assert!(!sequence_switch.hit(LightColor::White));
+        sequence_switch.tick(10.0);
+        assert!(sequence_switch.is_active);
+    }
+
+    #[test]
+    fn wrong_color_resets_progress() {
+        let mut sequence_switch = sequence_switch();
+        sequence_switch.hit(LightColor::Purple);
+        sequence_switch.hit(LightColor::Green);
+        assert!(!sequence_switch.hit(LightColor::White));
+        assert_eq!(sequence_switch.progress, 0);
+
+        // starting the sequence over with its first color counts
+        sequence_switch.hit(LightColor::Purple);
+        assert_eq!(sequence_switch.progress, 1);
+        assert!(!sequence_switch.hit(LightColor::Blue));
+        assert_eq!(sequence_switch.progress, 0);
+    }
+
+    #[test]
+    fn timeout_resets_progress() {
+        let mut sequence_switch = sequence_switch();
+        sequence_switch.hit(LightColor::Purple);
+        sequence_switch.tick(1.5);
+        assert_eq!(sequence_switch.progress, 0);
+        // the same color can start the sequence again after a timeout
+        sequence_switch.hit(LightColor::Purple);
+        assert_eq!(sequence_switch.progress, 1);
+    }
+}
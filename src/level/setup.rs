@@ -3,11 +3,14 @@ use crate::shared::GameState;
 use bevy::{prelude::*, render::view::RenderLayers};
 use bevy_ecs_ldtk::prelude::*;
 
+use super::get_ldtk_level_data;
+
 pub struct LevelSetupPlugin;
 
 impl Plugin for LevelSetupPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(LevelSelection::index(8))
+        let level_index = app.world().resource::<Config>().level_config.level_index;
+        app.insert_resource(LevelSelection::index(level_index))
             .insert_resource(LdtkSettings {
                 level_spawn_behavior: LevelSpawnBehavior::UseWorldTranslation {
                     load_level_neighbors: true,
@@ -15,7 +18,8 @@ impl Plugin for LevelSetupPlugin {
                 level_background: LevelBackground::Nonexistent,
                 ..default()
             })
-            .add_systems(Startup, setup_level);
+            .add_systems(Startup, setup_level)
+            .add_systems(PreUpdate, validate_level_selection);
     }
 }
 
@@ -35,3 +39,53 @@ pub fn setup_level(
         RenderLayers::layer(1),
     ));
 }
+
+/// Returns `index` if it refers to one of the `num_levels` levels, and logs an error and falls
+/// back to the first level otherwise.
+pub fn validate_level_index(index: usize, num_levels: usize) -> usize {
+    if index < num_levels {
+        return index;
+    }
+    error!(
+        "Level index {} is out of range ({} levels), falling back to level 0",
+        index, num_levels
+    );
+    0
+}
+
+/// [`System`] that checks that the [`LevelSelection`] refers to an existing level once the
+/// [`LdtkProject`] is loaded, so that a misconfigured `level_index` doesn't spawn the player into
+/// nothing.
+pub fn validate_level_selection(
+    mut level_selection: ResMut<LevelSelection>,
+    ldtk_projects: Query<&LdtkProjectHandle>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+) {
+    let LevelSelection::Indices(indices) = level_selection.as_ref() else {
+        return;
+    };
+    let Ok(ldtk_handle) = ldtk_projects.get_single() else {
+        return;
+    };
+    let Ok(ldtk_levels) = get_ldtk_level_data(ldtk_project_assets.into_inner(), ldtk_handle) else {
+        return;
+    };
+
+    let index = validate_level_index(indices.level, ldtk_levels.len());
+    if index != indices.level {
+        *level_selection = LevelSelection::index(index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn out_of_range_level_index_falls_back_to_first_level() {
+        assert_eq!(validate_level_index(3, 10), 3);
+        assert_eq!(validate_level_index(9, 10), 9);
+        assert_eq!(validate_level_index(10, 10), 0);
+        assert_eq!(validate_level_index(usize::MAX, 10), 0);
+    }
+}
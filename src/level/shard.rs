@@ -8,9 +8,10 @@ use enum_map::EnumMap;
 use crate::{
     animation::AnimationConfig,
     camera::{
-        camera_position_from_level, camera_position_from_level_with_scale, CameraControlType,
-        CameraMoveEvent, CameraZoomEvent, MainCamera,
+        camera_position_from_level_with_scale, CameraControlType, CameraMoveEvent, CameraZoomEvent,
+        MainCamera,
     },
+    config::Config,
     light::LightColor,
     lighting::LineLight2d,
     player::{
@@ -380,6 +381,7 @@ pub fn on_shard_zoom_in_finished(
         ));
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn on_shard_text_read_finish(
     mut commands: Commands,
     mut ev_move_camera: EventWriter<CameraMoveEvent>,
@@ -389,6 +391,7 @@ pub fn on_shard_text_read_finish(
     q_shard_text: Query<Entity, With<ShardUiMarker>>,
     shard_anim_cbs: Res<ShardAnimationCallbacks>,
     q_bgm: Query<Entity, (With<BgmMarker>, Without<ShardUiMarker>)>,
+    config: Res<Config>,
 ) {
     let (player_transform, mut player_light_inventory) = q_player
         .get_single_mut()
@@ -405,8 +408,12 @@ pub fn on_shard_text_read_finish(
     player_light_inventory.current_color = Some(shard_color);
     current_level.allowed_colors[shard_color] = true;
 
-    let camera_pos =
-        camera_position_from_level(current_level.level_box, player_transform.translation().xy());
+    let camera_scale = current_level.camera_scale(config.camera_config.zoom);
+    let camera_pos = camera_position_from_level_with_scale(
+        current_level.level_box,
+        player_transform.translation().xy(),
+        camera_scale,
+    );
 
     ev_move_camera.send(CameraMoveEvent {
         to: camera_pos,
@@ -417,7 +424,7 @@ pub fn on_shard_text_read_finish(
         },
     });
     ev_zoom_camera.send(CameraZoomEvent {
-        scale: 1.,
+        scale: camera_scale,
         variant: CameraControlType::Animated {
             duration: Duration::from_millis(500),
             ease_fn: EaseFunction::SineInOut,
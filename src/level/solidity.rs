@@ -0,0 +1,209 @@
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    lighting::{Occluder2d, Occluder2dGroups},
+    shared::GroupLabel,
+};
+
+use super::{entity_kind::spawn_entity_kinds, LevelSystems};
+
+/// [`Plugin`] for [`Solidity`].
+pub struct SolidityPlugin;
+
+impl Plugin for SolidityPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PreUpdate,
+            apply_solidity
+                .after(spawn_entity_kinds)
+                .in_set(LevelSystems::Processing),
+        );
+    }
+}
+
+/// [`Component`] for terrain that only some things can pass through, like a glass floor the player
+/// stands on that light shines through, or a force field the player walks through that casts a
+/// shadow. Sets the [`CollisionGroups`] the player's controller and light beams collide with, and
+/// the [`Occluder2dGroups`] the lighting casts shadows with, see [`apply_solidity`]. Terrain
+/// without it blocks everything.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Solidity {
+    pub blocks_player: bool,
+    /// Whether the entity casts shadows from lights
+    pub blocks_light: bool,
+    /// Whether light beams stop at or bounce off of the entity
+    pub blocks_beam: bool,
+}
+
+impl Default for Solidity {
+    fn default() -> Self {
+        Solidity {
+            blocks_player: true,
+            blocks_light: true,
+            blocks_beam: true,
+        }
+    }
+}
+
+impl Solidity {
+    pub fn collision_groups(&self) -> CollisionGroups {
+        let memberships = match (self.blocks_player, self.blocks_beam) {
+            (true, true) => GroupLabel::TERRAIN,
+            (true, false) => GroupLabel::PLAYER_TERRAIN,
+            (false, true) => GroupLabel::BEAM_TERRAIN,
+            (false, false) => Group::NONE,
+        };
+        CollisionGroups::new(memberships, GroupLabel::ALL)
+    }
+
+    pub fn occluder_groups(&self) -> Occluder2dGroups {
+        if self.blocks_light {
+            Occluder2dGroups::ALL
+        } else {
+            Occluder2dGroups::NONE
+        }
+    }
+}
+
+impl From<&EntityInstance> for Solidity {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        let field = |name| {
+            entity_instance
+                .get_bool_field(name)
+                .copied()
+                .unwrap_or(true)
+        };
+        Solidity {
+            blocks_player: field("blocks_player"),
+            blocks_light: field("blocks_light"),
+            blocks_beam: field("blocks_beam"),
+        }
+    }
+}
+
+/// A panel of terrain covering the whole entity, blocking what its [`Solidity`] says.
+pub fn spawn_solid_panel(commands: &mut EntityCommands, entity_instance: &EntityInstance) {
+    let half_size = Vec2::new(
+        entity_instance.width as f32 / 2.0,
+        entity_instance.height as f32 / 2.0,
+    );
+    let solidity = Solidity::from(entity_instance);
+    let color = if solidity.blocks_player {
+        Color::srgba(0.7, 0.85, 0.9, 0.5)
+    } else {
+        Color::srgba(0.5, 0.3, 0.9, 0.35)
+    };
+    commands.insert((
+        solidity,
+        Collider::cuboid(half_size.x, half_size.y),
+        RigidBody::Fixed,
+        Occluder2d::new(half_size.x, half_size.y),
+        Sprite::from_color(color, half_size * 2.0),
+    ));
+}
+
+/// [`System`] that sets the [`CollisionGroups`] and [`Occluder2dGroups`] of entities from their
+/// [`Solidity`] whenever it changes.
+pub fn apply_solidity(
+    mut commands: Commands,
+    q_solidity: Query<(Entity, &Solidity), Changed<Solidity>>,
+) {
+    for (entity, solidity) in q_solidity.iter() {
+        commands
+            .entity(entity)
+            .insert((solidity.collision_groups(), solidity.occluder_groups()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        light::{segments::beam_collision_groups, LightColor},
+        lighting::{occluder_2d_occludes, LightDepth},
+        player::spawn::player_collision_groups,
+    };
+
+    fn collide(a: CollisionGroups, b: CollisionGroups) -> bool {
+        a.memberships.intersects(b.filters) && b.memberships.intersects(a.filters)
+    }
+
+    /// Whether the player, shadows, and beams of each color are blocked by `solidity`.
+    fn blocks(solidity: Solidity) -> (bool, bool, [bool; 3]) {
+        let groups = solidity.collision_groups();
+        (
+            collide(player_collision_groups(), groups),
+            occluder_2d_occludes(
+                Occluder2dGroups::ALL,
+                LightDepth::Foreground,
+                solidity.occluder_groups(),
+                LightDepth::Foreground,
+            ),
+            [LightColor::Green, LightColor::White, LightColor::Blue]
+                .map(|color| collide(beam_collision_groups(color), groups)),
+        )
+    }
+
+    #[test]
+    fn solidity_blocks_each_thing_separately() {
+        let solidity = |blocks_player, blocks_light, blocks_beam| Solidity {
+            blocks_player,
+            blocks_light,
+            blocks_beam,
+        };
+        for blocks_player in [false, true] {
+            for blocks_light in [false, true] {
+                for blocks_beam in [false, true] {
+                    assert_eq!(
+                        blocks(solidity(blocks_player, blocks_light, blocks_beam)),
+                        (blocks_player, blocks_light, [blocks_beam; 3])
+                    );
+                }
+            }
+        }
+
+        // a glass floor
+        assert_eq!(
+            blocks(solidity(true, false, false)),
+            (true, false, [false; 3])
+        );
+        assert_eq!(blocks(Solidity::default()), (true, true, [true; 3]));
+    }
+
+    #[test]
+    fn solidity_sets_groups() {
+        let mut app = App::new();
+        app.add_systems(Update, apply_solidity);
+        let glass = Solidity {
+            blocks_player: true,
+            blocks_light: false,
+            blocks_beam: false,
+        };
+        let panel = app.world_mut().spawn(glass).id();
+        app.update();
+        assert!(*app.world().get::<Occluder2dGroups>(panel).unwrap() == Occluder2dGroups::NONE);
+        assert_eq!(
+            app.world()
+                .get::<CollisionGroups>(panel)
+                .unwrap()
+                .memberships,
+            GroupLabel::PLAYER_TERRAIN
+        );
+
+        // beams bounce off of it too
+        app.world_mut()
+            .get_mut::<Solidity>(panel)
+            .unwrap()
+            .blocks_beam = true;
+        app.update();
+        assert_eq!(
+            app.world()
+                .get::<CollisionGroups>(panel)
+                .unwrap()
+                .memberships,
+            GroupLabel::TERRAIN
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, utils::HashSet};
+use bevy_ecs_ldtk::{
+    ldtk::Level,
+    prelude::*,
+    systems::{apply_level_selection, apply_level_set},
+    LevelIid,
+};
+use serde::Deserialize;
+
+use crate::light::{segments::PrevLightBeamPlayback, LightBeamSource};
+
+use super::{get_ldtk_level_data, level_box_from_level, CurrentLevel};
+
+/// [`Plugin`] that keeps only the levels near the [`CurrentLevel`] loaded, see
+/// [`StreamingConfig`].
+pub struct LevelStreamingPlugin;
+
+impl Plugin for LevelStreamingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<StreamingConfig>().add_systems(
+            PreUpdate,
+            stream_levels
+                .after(apply_level_selection)
+                .before(apply_level_set),
+        );
+    }
+}
+
+/// [`Resource`] for how many levels away from the [`CurrentLevel`] stay loaded, following the
+/// neighbours Ldtk stores for each level. Levels further away are despawned, and spawned again
+/// once the player gets close, so big worlds don't keep every level in memory. Levels reached by
+/// a light beam that starts in one of those nearby levels stay loaded no matter how far away they
+/// are, so puzzles aren't taken apart halfway through. See the `streaming_config` section of
+/// `Lightborne.toml`.
+#[derive(Resource, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct StreamingConfig {
+    pub radius: usize,
+}
+
+impl Default for StreamingConfig {
+    fn default() -> Self {
+        StreamingConfig { radius: 1 }
+    }
+}
+
+/// The levels that should be loaded while `center` is the current level: the ones at most
+/// `radius` neighbours away from it, and the ones reached by any of the `beams` that start in one
+/// of those. Each beam is the list of points it passes through, starting with where it starts.
+pub fn streamed_levels(
+    levels: &[Level],
+    center: &str,
+    radius: usize,
+    beams: &[Vec<Vec2>],
+) -> HashSet<LevelIid> {
+    let mut streamed: HashSet<LevelIid> = HashSet::new();
+    let mut queue = VecDeque::from([(center, 0)]);
+    while let Some((iid, distance)) = queue.pop_front() {
+        let Some(level) = levels.iter().find(|level| level.iid == iid) else {
+            continue;
+        };
+        if !streamed.insert(LevelIid::new(iid)) || distance == radius {
+            continue;
+        }
+        for neighbour in level.neighbours.iter() {
+            queue.push_back((neighbour.level_iid.as_str(), distance + 1));
+        }
+    }
+
+    let nearby_boxes: Vec<Rect> = levels
+        .iter()
+        .filter(|level| streamed.contains(&LevelIid::new(level.iid.clone())))
+        .map(level_box_from_level)
+        .collect();
+    // beams of levels further away are unloaded with them, so they don't pin anything
+    let pinned: Vec<Vec2> = beams
+        .iter()
+        .filter(|beam| {
+            beam.first().is_some_and(|start| {
+                nearby_boxes
+                    .iter()
+                    .any(|level_box| level_box.contains(*start))
+            })
+        })
+        .flatten()
+        .copied()
+        .collect();
+
+    for level in levels.iter() {
+        let level_box = level_box_from_level(level);
+        if pinned.iter().any(|point| level_box.contains(*point)) {
+            streamed.insert(LevelIid::new(level.iid.clone()));
+        }
+    }
+    streamed
+}
+
+/// [`System`] that sets the [`LevelSet`] of the Ldtk world to the [`streamed_levels`] around the
+/// [`CurrentLevel`]. It runs right after bevy_ecs_ldtk turns the [`LevelSelection`] into a
+/// [`LevelSet`] and before the levels are spawned, so levels that stay loaded aren't despawned
+/// for a frame when the level changes.
+pub fn stream_levels(
+    mut q_level_set: Query<(&mut LevelSet, &LdtkProjectHandle)>,
+    q_beams: Query<(&LightBeamSource, &PrevLightBeamPlayback)>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+    current_level: Res<CurrentLevel>,
+    streaming_config: Res<StreamingConfig>,
+) {
+    // the current level isn't known until the player spawns, until then the level selection
+    // decides what is loaded
+    if current_level.level_iid.as_str().is_empty() {
+        return;
+    }
+    let Ok((mut level_set, ldtk_handle)) = q_level_set.get_single_mut() else {
+        return;
+    };
+    let Ok(levels) = get_ldtk_level_data(ldtk_project_assets.into_inner(), ldtk_handle) else {
+        return;
+    };
+
+    let beams: Vec<Vec<Vec2>> = q_beams
+        .iter()
+        .map(|(source, playback)| {
+            std::iter::once(source.start_pos)
+                .chain(
+                    playback
+                        .intersections
+                        .iter()
+                        .flatten()
+                        .map(|intersection| intersection.point),
+                )
+                .collect()
+        })
+        .collect();
+    let streamed = streamed_levels(
+        levels,
+        current_level.level_iid.as_str(),
+        streaming_config.radius,
+        &beams,
+    );
+    // only touch the level set when it changes, changing it respawns levels
+    if level_set.iids != streamed {
+        level_set.iids = streamed;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_ecs_ldtk::ldtk::NeighbourLevel;
+
+    use super::*;
+
+    /// `count` levels 100 pixels wide in a row, each a neighbour of the ones next to it.
+    fn level_row(count: usize) -> Vec<Level> {
+        (0..count)
+            .map(|i| Level {
+                iid: i.to_string(),
+                world_x: i as i32 * 100,
+                px_wid: 100,
+                px_hei: 100,
+                neighbours: [i.checked_sub(1), Some(i + 1).filter(|n| *n < count)]
+                    .into_iter()
+                    .flatten()
+                    .map(|n| NeighbourLevel {
+                        level_iid: n.to_string(),
+                        dir: if n < i { "w" } else { "e" }.into(),
+                        ..default()
+                    })
+                    .collect(),
+                ..default()
+            })
+            .collect()
+    }
+
+    fn iids(levels: &[usize]) -> HashSet<LevelIid> {
+        levels
+            .iter()
+            .map(|level| LevelIid::new(level.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn levels_within_radius_are_loaded() {
+        let levels = level_row(7);
+        assert_eq!(streamed_levels(&levels, "3", 1, &[]), iids(&[2, 3, 4]));
+        assert_eq!(
+            streamed_levels(&levels, "3", 2, &[]),
+            iids(&[1, 2, 3, 4, 5])
+        );
+        // walking to the right loads ahead and unloads behind
+        assert_eq!(streamed_levels(&levels, "4", 1, &[]), iids(&[3, 4, 5]));
+        assert_eq!(streamed_levels(&levels, "6", 1, &[]), iids(&[5, 6]));
+        // the current level is always loaded
+        assert_eq!(streamed_levels(&levels, "0", 0, &[]), iids(&[0]));
+
+        // a beam reaching into a far away level keeps it loaded
+        let beam = vec![
+            Vec2::new(50.0, -50.0),
+            Vec2::new(150.0, -50.0),
+            Vec2::new(550.0, -50.0),
+        ];
+        assert_eq!(streamed_levels(&levels, "0", 0, &[beam]), iids(&[0, 1, 5]));
+    }
+
+    #[test]
+    fn far_away_emitters_are_unloaded() {
+        let levels = level_row(7);
+        // an emitter in level 5 shining into level 6 doesn't keep either loaded from level 1
+        let beam = vec![Vec2::new(550.0, -50.0), Vec2::new(650.0, -50.0)];
+        assert_eq!(
+            streamed_levels(&levels, "1", 1, &[beam.clone()]),
+            iids(&[0, 1, 2])
+        );
+        assert_eq!(
+            streamed_levels(&levels, "4", 1, &[beam]),
+            iids(&[3, 4, 5, 6])
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::lighting::{GlobalFlicker, LightToggle, LineLight2d, SyncedFlicker};
+
+use super::{
+    trigger_zone::{TriggerZone, ZoneEnteredEvent},
+    LevelSystems,
+};
+
+/// [`Plugin`] that lets levels set off power surges, which flicker every light placed in Ldtk
+/// with `synced_flicker` set together, see [`GlobalFlicker`].
+pub struct PowerSurgePlugin;
+
+impl Plugin for PowerSurgePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ZoneEnteredEvent>()
+            .add_systems(
+                PreUpdate,
+                (assign_synced_flickers, add_surge_zones).in_set(LevelSystems::Processing),
+            )
+            .add_systems(Update, surge_on_zone_entered);
+    }
+}
+
+/// [`Component`] for [`TriggerZone`]s placed in Ldtk with a `surge_secs` field, which set off a
+/// surge of the [`GlobalFlicker`] lasting that long when the player walks in.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct SurgeZone {
+    pub secs: f32,
+}
+
+/// [`System`] that adds a [`SyncedFlicker`] to lights placed in Ldtk with `synced_flicker` set.
+/// Lights that don't turn on and off yet keep the intensity they were placed with.
+pub fn assign_synced_flickers(
+    mut commands: Commands,
+    q_lights: Query<
+        (Entity, &EntityInstance, &LineLight2d, Has<LightToggle>),
+        Added<EntityInstance>,
+    >,
+) {
+    for (entity, entity_instance, light, has_toggle) in q_lights.iter() {
+        if !matches!(entity_instance.get_bool_field("synced_flicker"), Ok(true)) {
+            continue;
+        }
+        let mut entity = commands.entity(entity);
+        if !has_toggle {
+            entity.insert(LightToggle::lit(light.color.w));
+        }
+        entity.insert(SyncedFlicker);
+    }
+}
+
+/// [`System`] that adds a [`SurgeZone`] to [`TriggerZone`]s placed in Ldtk with a `surge_secs`
+/// field.
+pub fn add_surge_zones(
+    mut commands: Commands,
+    q_zones: Query<(Entity, &EntityInstance), (Added<EntityInstance>, With<TriggerZone>)>,
+) {
+    for (entity, entity_instance) in q_zones.iter() {
+        if let Ok(secs) = entity_instance.get_float_field("surge_secs") {
+            commands.entity(entity).insert(SurgeZone { secs: *secs });
+        }
+    }
+}
+
+/// [`System`] that starts a surge of the [`GlobalFlicker`] when the player enters a [`SurgeZone`].
+pub fn surge_on_zone_entered(
+    q_zones: Query<(&TriggerZone, &SurgeZone)>,
+    mut ev_zone_entered: EventReader<ZoneEnteredEvent>,
+    mut global_flicker: ResMut<GlobalFlicker>,
+) {
+    for event in ev_zone_entered.read() {
+        for (zone, surge) in q_zones.iter() {
+            if zone.id == event.id {
+                global_flicker.surge(surge.secs);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instance_with(identifier: &str, value: FieldValue) -> EntityInstance {
+        EntityInstance {
+            field_instances: vec![FieldInstance {
+                identifier: identifier.into(),
+                tile: None,
+                field_instance_type: String::new(),
+                value,
+                def_uid: 0,
+                real_editor_values: vec![],
+            }],
+            ..default()
+        }
+    }
+
+    #[test]
+    fn entering_a_surge_zone_flickers_synced_lights() {
+        let mut app = App::new();
+        app.init_resource::<GlobalFlicker>()
+            .add_event::<ZoneEnteredEvent>()
+            .add_systems(
+                Update,
+                (
+                    (assign_synced_flickers, add_surge_zones),
+                    surge_on_zone_entered,
+                )
+                    .chain(),
+            );
+        let synced = app
+            .world_mut()
+            .spawn((
+                LineLight2d::point(Vec4::new(1.0, 1.0, 1.0, 0.6), 40.0, 0.0),
+                instance_with("synced_flicker", FieldValue::Bool(true)),
+            ))
+            .id();
+        let independent = app
+            .world_mut()
+            .spawn((
+                LineLight2d::default(),
+                instance_with("synced_flicker", FieldValue::Bool(false)),
+            ))
+            .id();
+        app.world_mut().spawn((
+            TriggerZone::new("surge".into(), false, Vec2::splat(8.0)),
+            instance_with("surge_secs", FieldValue::Float(Some(0.5))),
+        ));
+        app.world_mut().spawn((
+            TriggerZone::new("tutorial".into(), false, Vec2::splat(8.0)),
+            instance_with("once", FieldValue::Bool(true)),
+        ));
+        app.update();
+
+        // synced lights stay lit at the intensity they were placed with
+        let toggle = app.world().get::<LightToggle>(synced).unwrap();
+        assert!(toggle.is_on());
+        assert_eq!(toggle.intensity, 0.6);
+        assert!(app.world().get::<SyncedFlicker>(independent).is_none());
+
+        app.world_mut().send_event(ZoneEnteredEvent {
+            id: "tutorial".into(),
+        });
+        app.update();
+        assert!(!app.world().resource::<GlobalFlicker>().is_surging());
+
+        app.world_mut()
+            .send_event(ZoneEnteredEvent { id: "surge".into() });
+        app.update();
+        assert!(app.world().resource::<GlobalFlicker>().is_surging());
+    }
+}
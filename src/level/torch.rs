@@ -0,0 +1,400 @@
+use std::{collections::HashSet, path::Path};
+
+use bevy::prelude::*;
+use bevy_ecs_ldtk::{prelude::*, EntityIid};
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    light::{
+        fog::VolumetricFog,
+        segments::{simulate_light_sources, PrevLightBeamPlayback},
+        LightBeamSource,
+    },
+    lighting::{LightIgnition, LightToggle, LineLight2d},
+    save::SaveSlots,
+};
+
+use super::{
+    entity::FixedEntityBundle, lamp::beam_lamp_intensity, water::WaterVolume, LevelSystems,
+};
+
+/// How long a beam has to keep touching a [`Torch`] to light it, in seconds.
+const TORCH_IGNITE_SECS: f32 = 0.25;
+
+/// The intensity a beam needs to light a [`Torch`] without a `min_intensity` field.
+const DEFAULT_TORCH_MIN_INTENSITY: f32 = 0.5;
+
+/// The radius of the light given off by a lit [`Torch`].
+const TORCH_RADIUS: f32 = 60.0;
+
+/// [`Plugin`] for torches that are lit by light beams and stay lit.
+pub struct TorchPlugin;
+
+impl Plugin for TorchPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity::<TorchBundle>("Torch")
+            .init_resource::<LitTorches>()
+            .add_systems(
+                PreUpdate,
+                light_spawned_torches.in_set(LevelSystems::Processing),
+            )
+            .add_systems(
+                FixedUpdate,
+                update_torches
+                    .after(simulate_light_sources)
+                    .in_set(LevelSystems::Simulation),
+            )
+            .add_systems(
+                Update,
+                save_lit_torches.run_if(resource_changed::<LitTorches>),
+            );
+    }
+}
+
+/// [`Component`] for torches, which stay dark until a beam bright enough touches them for
+/// [`TORCH_IGNITE_SECS`], and then keep burning on their own once the beam is gone. A beam that
+/// was bent by a [`WaterVolume`] on its way puts the torch out instead, as does water around the
+/// torch. Lit torches are remembered in [`LitTorches`], so they stay lit through respawns, levels
+/// being unloaded and restarting the game.
+#[derive(Component, Debug)]
+pub struct Torch {
+    /// The intensity a beam needs to light the torch, from 0 to 1
+    pub min_intensity: f32,
+    /// How long a bright enough beam has been touching the torch, in seconds
+    heat: f32,
+    lit: bool,
+}
+
+impl Torch {
+    pub fn new(min_intensity: f32) -> Self {
+        Torch {
+            min_intensity,
+            heat: 0.0,
+            lit: false,
+        }
+    }
+
+    pub fn is_lit(&self) -> bool {
+        self.lit
+    }
+}
+
+impl From<&EntityInstance> for Torch {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        let min_intensity = entity_instance
+            .get_float_field("min_intensity")
+            .copied()
+            .unwrap_or(DEFAULT_TORCH_MIN_INTENSITY);
+        Torch::new(min_intensity)
+    }
+}
+
+/// [`Bundle`] that includes all the [`Component`]s needed for a [`Torch`] to function properly.
+#[derive(Bundle, LdtkEntity)]
+pub struct TorchBundle {
+    #[from_entity_instance]
+    physics: FixedEntityBundle,
+    #[default]
+    sensor: Sensor,
+    #[from_entity_instance]
+    torch: Torch,
+    #[with(torch_light)]
+    lighting: LineLight2d,
+    #[with(torch_toggle)]
+    toggle: LightToggle,
+}
+
+pub fn torch_light(_: &EntityInstance) -> LineLight2d {
+    LineLight2d::point(Vec4::new(1.0, 0.6, 0.3, 0.0), TORCH_RADIUS, 0.008)
+}
+
+pub fn torch_toggle(_: &EntityInstance) -> LightToggle {
+    LightToggle {
+        warmup_secs: 0.2,
+        ignition: Some(LightIgnition::default()),
+        ..default()
+    }
+}
+
+/// [`Resource`] holding the [`EntityIid`]s of every lit [`Torch`]. Saved to the active save slot
+/// whenever a torch is lit or put out.
+#[derive(Resource, Default, Debug)]
+pub struct LitTorches(pub HashSet<String>);
+
+impl LitTorches {
+    /// Reads torches saved by [`LitTorches::save`], one iid per line.
+    pub fn load(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .map(|contents| {
+                LitTorches(
+                    contents
+                        .lines()
+                        .map(str::trim)
+                        .filter(|line| !line.is_empty())
+                        .map(str::to_string)
+                        .collect(),
+                )
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) {
+        let contents: String = self.0.iter().map(|iid| format!("{}\n", iid)).collect();
+        if let Err(err) = std::fs::write(path, contents) {
+            warn!("Failed to save lit torches: {}", err);
+        }
+    }
+}
+
+/// [`System`] that lights the [`Torch`]es that were already lit when their level was spawned.
+pub fn light_spawned_torches(
+    mut q_torches: Query<(&EntityIid, &mut Torch, &mut LightToggle), Added<Torch>>,
+    lit_torches: Res<LitTorches>,
+) {
+    for (iid, mut torch, mut toggle) in q_torches.iter_mut() {
+        if lit_torches.0.contains(iid.as_str()) {
+            torch.lit = true;
+            toggle.set_on(true);
+        }
+    }
+}
+
+/// [`System`] that lights [`Torch`]es touched by a beam for long enough, and puts them out when
+/// they are touched by a beam that went through water or are under water.
+pub fn update_torches(
+    q_light_sources: Query<(&LightBeamSource, &PrevLightBeamPlayback)>,
+    mut q_torches: Query<(
+        Entity,
+        &EntityIid,
+        &mut Torch,
+        &mut LightToggle,
+        &GlobalTransform,
+    )>,
+    q_water: Query<(&WaterVolume, &GlobalTransform)>,
+    mut lit_torches: ResMut<LitTorches>,
+    fog: Res<VolumetricFog>,
+    time: Res<Time>,
+) {
+    for (torch_entity, iid, mut torch, mut toggle, transform) in q_torches.iter_mut() {
+        let pos = transform.translation().truncate();
+        let mut doused = q_water.iter().any(|(water, water_transform)| {
+            Rect::from_center_half_size(water_transform.translation().truncate(), water.half_size)
+                .contains(pos)
+        });
+        let mut intensity: f32 = 0.0;
+
+        for (source, playback) in q_light_sources.iter() {
+            let mut wet = false;
+            for intersection in playback.intersections.iter().flatten() {
+                if intersection.entity == torch_entity {
+                    if wet {
+                        doused = true;
+                    } else {
+                        let hit_intensity = beam_lamp_intensity(intersection.time)
+                            * playback.received_intensity(source, intersection.time, &fog);
+                        intensity = intensity.max(hit_intensity);
+                    }
+                    break;
+                }
+                wet |= intersection.refracted;
+            }
+        }
+
+        if doused {
+            torch.heat = 0.0;
+            if torch.lit {
+                torch.lit = false;
+                toggle.set_on(false);
+                lit_torches.0.remove(iid.as_str());
+            }
+            continue;
+        }
+
+        if intensity < torch.min_intensity || intensity <= 0.0 {
+            torch.heat = 0.0;
+            continue;
+        }
+        torch.heat += time.delta_secs();
+        if torch.heat >= TORCH_IGNITE_SECS && !torch.lit {
+            torch.lit = true;
+            toggle.set_on(true);
+            lit_torches.0.insert(iid.to_string());
+        }
+    }
+}
+
+/// [`System`] that writes the [`LitTorches`] to the active save slot.
+pub fn save_lit_torches(lit_torches: Res<LitTorches>, save_slots: Res<SaveSlots>) {
+    lit_torches.save(&save_slots.lit_torches_path());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::light::{segments::LightBeamIntersection, LightColor};
+
+    use super::*;
+
+    fn beam(torch: Entity, through_water: bool) -> (LightBeamSource, PrevLightBeamPlayback) {
+        let mut prev_playback = PrevLightBeamPlayback::default();
+        let hit = LightBeamIntersection {
+            entity: torch,
+            point: Vec2::new(-4.0, 0.0),
+            time: 20.0,
+            refracted: false,
+        };
+        if through_water {
+            prev_playback.intersections[0] = Some(LightBeamIntersection {
+                entity: Entity::from_raw(100),
+                point: Vec2::new(-10.0, 0.0),
+                time: 14.0,
+                refracted: true,
+            });
+            prev_playback.intersections[1] = Some(hit);
+        } else {
+            prev_playback.intersections[0] = Some(hit);
+        }
+        (
+            LightBeamSource {
+                start_pos: Vec2::new(-24.0, 0.0),
+                start_dir: Vec2::X,
+                time_traveled: 20.0,
+                color: LightColor::White,
+                width: 0.0,
+                intensity: 1.0,
+                penetration: 0.0,
+                depth: default(),
+            },
+            prev_playback,
+        )
+    }
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .init_resource::<VolumetricFog>()
+            .init_resource::<LitTorches>()
+            .add_systems(Update, (light_spawned_torches, update_torches).chain());
+        app
+    }
+
+    fn spawn_torch(app: &mut App) -> Entity {
+        app.world_mut()
+            .spawn((
+                EntityIid::new("torch"),
+                Torch::new(0.5),
+                LightToggle::default(),
+                GlobalTransform::default(),
+            ))
+            .id()
+    }
+
+    fn step(app: &mut App, secs: f32) {
+        app.world_mut()
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_secs_f32(secs));
+        app.update();
+    }
+
+    fn is_lit(app: &App, torch: Entity) -> bool {
+        let lit = app.world().get::<Torch>(torch).unwrap().is_lit();
+        assert_eq!(lit, app.world().get::<LightToggle>(torch).unwrap().is_on());
+        lit
+    }
+
+    #[test]
+    fn beams_light_torches_for_good() {
+        let mut app = app();
+        let torch = spawn_torch(&mut app);
+        step(&mut app, 0.1);
+        assert!(!is_lit(&app, torch));
+
+        // a brief touch isn't enough
+        let source = app.world_mut().spawn(beam(torch, false)).id();
+        step(&mut app, 0.1);
+        step(&mut app, 0.1);
+        assert!(!is_lit(&app, torch));
+        app.world_mut().entity_mut(source).despawn();
+        step(&mut app, 0.1);
+        let source = app.world_mut().spawn(beam(torch, false)).id();
+        step(&mut app, 0.1);
+        assert!(!is_lit(&app, torch));
+
+        step(&mut app, 0.1);
+        step(&mut app, 0.1);
+        assert!(is_lit(&app, torch));
+        assert!(app.world().resource::<LitTorches>().0.contains("torch"));
+
+        // the torch keeps burning without the beam
+        app.world_mut().entity_mut(source).despawn();
+        step(&mut app, 1.0);
+        assert!(is_lit(&app, torch));
+
+        // and is lit again when its level is spawned again
+        app.world_mut().entity_mut(torch).despawn();
+        let torch = spawn_torch(&mut app);
+        step(&mut app, 0.1);
+        assert!(is_lit(&app, torch));
+    }
+
+    #[test]
+    fn dim_beams_do_not_light_torches() {
+        let mut app = app();
+        let torch = spawn_torch(&mut app);
+        let (mut source, playback) = beam(torch, false);
+        source.intensity = 0.3;
+        app.world_mut().spawn((source, playback));
+        step(&mut app, 1.0);
+        step(&mut app, 1.0);
+        assert!(!is_lit(&app, torch));
+    }
+
+    #[test]
+    fn water_puts_torches_out() {
+        let mut app = app();
+        let torch = spawn_torch(&mut app);
+        app.world_mut()
+            .resource_mut::<LitTorches>()
+            .0
+            .insert("torch".into());
+        step(&mut app, 0.1);
+        assert!(is_lit(&app, torch));
+
+        // a beam that went through water douses the torch and can't light it
+        let source = app.world_mut().spawn(beam(torch, true)).id();
+        step(&mut app, 1.0);
+        step(&mut app, 1.0);
+        assert!(!is_lit(&app, torch));
+        assert!(app.world().resource::<LitTorches>().0.is_empty());
+
+        app.world_mut().entity_mut(source).despawn();
+        app.world_mut().spawn(beam(torch, false));
+        step(&mut app, 0.2);
+        step(&mut app, 0.2);
+        assert!(is_lit(&app, torch));
+
+        // and so does being under water
+        app.world_mut().spawn((
+            WaterVolume {
+                refraction: 1.33,
+                half_size: Vec2::splat(16.0),
+            },
+            GlobalTransform::default(),
+        ));
+        step(&mut app, 0.1);
+        assert!(!is_lit(&app, torch));
+    }
+
+    #[test]
+    fn lit_torches_are_saved() {
+        let path = std::env::temp_dir().join(format!("lightborne-torches-{}", std::process::id()));
+        let mut lit_torches = LitTorches::default();
+        lit_torches.0.insert("a".into());
+        lit_torches.0.insert("b".into());
+        lit_torches.save(&path);
+        assert_eq!(LitTorches::load(&path).0, lit_torches.0);
+        std::fs::remove_file(&path).unwrap();
+        assert!(LitTorches::load(&path).0.is_empty());
+    }
+}
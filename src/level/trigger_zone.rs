@@ -0,0 +1,217 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::player::PlayerMarker;
+
+use super::LevelSystems;
+
+/// [`Plugin`] for invisible zones that send events when the player walks in or out of them, used
+/// as hooks for tutorials, camera changes or cutscenes.
+pub struct TriggerZonePlugin;
+
+impl Plugin for TriggerZonePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ZoneEnteredEvent>()
+            .add_event::<ZoneExitedEvent>()
+            .register_ldtk_entity::<TriggerZoneBundle>("TriggerZone")
+            .add_systems(
+                FixedUpdate,
+                update_trigger_zones
+                    .after(PhysicsSet::Writeback)
+                    .in_set(LevelSystems::Simulation),
+            );
+    }
+}
+
+/// [`Event`] sent when the player enters a [`TriggerZone`].
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct ZoneEnteredEvent {
+    pub id: String,
+}
+
+/// [`Event`] sent when the player leaves a [`TriggerZone`].
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct ZoneExitedEvent {
+    pub id: String,
+}
+
+/// [`Component`] for zones that send a [`ZoneEnteredEvent`] and [`ZoneExitedEvent`] with their
+/// `id` when the player walks in and out of them. Zones that are `once` only send each event the
+/// first time, even if the player comes back later.
+#[derive(Component, Debug)]
+pub struct TriggerZone {
+    pub id: String,
+    pub once: bool,
+    pub half_size: Vec2,
+    /// Whether the player was inside the zone during the last update
+    player_inside: bool,
+    /// Whether the zone has sent a [`ZoneEnteredEvent`], so `once` zones don't send another
+    has_entered: bool,
+    /// Whether the zone has sent a [`ZoneExitedEvent`], so `once` zones don't send another
+    has_exited: bool,
+}
+
+impl TriggerZone {
+    pub fn new(id: String, once: bool, half_size: Vec2) -> Self {
+        TriggerZone {
+            id,
+            once,
+            half_size,
+            player_inside: false,
+            has_entered: false,
+            has_exited: false,
+        }
+    }
+
+    /// Updates the zone with whether the player is currently inside it, returning `Some(true)` if
+    /// a [`ZoneEnteredEvent`] should be sent and `Some(false)` if a [`ZoneExitedEvent`] should be.
+    pub fn update(&mut self, player_inside: bool) -> Option<bool> {
+        if player_inside == self.player_inside {
+            return None;
+        }
+        self.player_inside = player_inside;
+
+        if player_inside {
+            if self.once && self.has_entered {
+                return None;
+            }
+            self.has_entered = true;
+            Some(true)
+        } else {
+            if self.once && self.has_exited {
+                return None;
+            }
+            self.has_exited = true;
+            Some(false)
+        }
+    }
+}
+
+impl From<&EntityInstance> for TriggerZone {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        let id = entity_instance
+            .get_string_field("id")
+            .expect("id needs to be a string field on all trigger zones");
+
+        let once = entity_instance
+            .get_bool_field("once")
+            .expect("once needs to be a bool field on all trigger zones");
+
+        TriggerZone::new(
+            id.clone(),
+            *once,
+            Vec2::new(
+                entity_instance.width as f32 / 2.0,
+                entity_instance.height as f32 / 2.0,
+            ),
+        )
+    }
+}
+
+/// [`Bundle`] that includes all the [`Component`]s needed for a [`TriggerZone`] to function
+/// properly.
+#[derive(Bundle, LdtkEntity)]
+pub struct TriggerZoneBundle {
+    #[from_entity_instance]
+    trigger_zone: TriggerZone,
+}
+
+/// [`System`] that checks whether the player is inside each [`TriggerZone`] and sends
+/// [`ZoneEnteredEvent`]s and [`ZoneExitedEvent`]s.
+pub fn update_trigger_zones(
+    mut q_trigger_zones: Query<(&mut TriggerZone, &GlobalTransform)>,
+    q_player: Query<&Transform, With<PlayerMarker>>,
+    mut ev_zone_entered: EventWriter<ZoneEnteredEvent>,
+    mut ev_zone_exited: EventWriter<ZoneExitedEvent>,
+) {
+    let Ok(player_transform) = q_player.get_single() else {
+        return;
+    };
+    let player_pos = player_transform.translation.xy();
+
+    for (mut zone, transform) in q_trigger_zones.iter_mut() {
+        let rect = Rect::from_center_half_size(transform.translation().xy(), zone.half_size);
+        match zone.update(rect.contains(player_pos)) {
+            Some(true) => {
+                ev_zone_entered.send(ZoneEnteredEvent {
+                    id: zone.id.clone(),
+                });
+            }
+            Some(false) => {
+                ev_zone_exited.send(ZoneExitedEvent {
+                    id: zone.id.clone(),
+                });
+            }
+            None => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zone_app(once: bool) -> App {
+        let mut app = App::new();
+        app.add_event::<ZoneEnteredEvent>()
+            .add_event::<ZoneExitedEvent>()
+            .add_systems(Update, update_trigger_zones);
+        app.world_mut().spawn((
+            TriggerZone::new("tutorial".into(), once, Vec2::splat(8.0)),
+            GlobalTransform::default(),
+        ));
+        app.world_mut()
+            .spawn((PlayerMarker, Transform::from_xyz(-20.0, 0.0, 0.0)));
+        app
+    }
+
+    fn move_player(app: &mut App, x: f32) {
+        let mut q_player = app
+            .world_mut()
+            .query_filtered::<&mut Transform, With<PlayerMarker>>();
+        q_player.single_mut(app.world_mut()).translation.x = x;
+        app.update();
+    }
+
+    fn event_counts(app: &App) -> (usize, usize) {
+        (
+            app.world()
+                .resource::<Events<ZoneEnteredEvent>>()
+                .iter_current_update_events()
+                .count(),
+            app.world()
+                .resource::<Events<ZoneExitedEvent>>()
+                .iter_current_update_events()
+                .count(),
+        )
+    }
+
+    #[test]
+    fn once_zone_fires_once_on_reentry() {
+        let mut app = zone_app(true);
+        let mut entered = 0;
+        let mut exited = 0;
+        for x in [0.0, -20.0, 0.0, -20.0, 0.0] {
+            move_player(&mut app, x);
+            let (new_entered, new_exited) = event_counts(&app);
+            entered += new_entered;
+            exited += new_exited;
+        }
+        assert_eq!((entered, exited), (1, 1));
+    }
+
+    #[test]
+    fn repeatable_zone_fires_on_every_entry() {
+        let mut app = zone_app(false);
+        let mut entered = 0;
+        let mut exited = 0;
+        for x in [0.0, 1.0, -20.0, 0.0, -20.0] {
+            move_player(&mut app, x);
+            let (new_entered, new_exited) = event_counts(&app);
+            entered += new_entered;
+            exited += new_exited;
+        }
+        assert_eq!((entered, exited), (2, 2));
+    }
+}
@@ -2,32 +2,42 @@ use bevy::prelude::*;
 use bevy_ecs_ldtk::prelude::*;
 use bevy_rapier2d::prelude::*;
 
-use crate::{lighting::Occluder2d, particle::dust::DustSurface, shared::GroupLabel};
+use crate::{particle::dust::DustSurface, shared::GroupLabel};
 
-use super::merge_tile::MergedTile;
+use super::{merge_tile::MergedTile, occluder::TerrainTile};
 
-/// Marker [`Component`] representing a wall.
+/// [`Component`] representing a wall, holding the Terrain IntGrid value it was spawned from.
 #[derive(Default, Component)]
-pub struct Wall;
+pub struct Wall {
+    value: i32,
+}
+
+impl From<IntGridCell> for Wall {
+    fn from(cell: IntGridCell) -> Self {
+        Wall { value: cell.value }
+    }
+}
 
 /// Wall [`Bundle`] spawned int by Ldtk.
 #[derive(Default, Bundle, LdtkIntCell)]
 pub struct WallBundle {
+    #[from_int_grid_cell]
     wall: Wall,
 }
 
 impl MergedTile for Wall {
-    type CompareData = ();
+    type CompareData = i32;
 
     fn bundle(
         commands: &mut EntityCommands,
         center: Vec2,
         half_extent: Vec2,
-        _compare_data: &Self::CompareData,
+        compare_data: &Self::CompareData,
     ) {
         commands.insert((
             Collider::cuboid(half_extent.x, half_extent.y),
-            Occluder2d::new(half_extent.x, half_extent.y),
+            // the occluder is added by add_terrain_occluders
+            TerrainTile(*compare_data),
             CollisionGroups::new(
                 GroupLabel::TERRAIN,
                 GroupLabel::PLAYER_COLLIDER
@@ -43,6 +53,7 @@ impl MergedTile for Wall {
     }
 
     fn compare_data(&self) -> Self::CompareData {
-        // all walls are mergable
+        // walls are mergable if they cast the same shadows
+        self.value
     }
 }
@@ -0,0 +1,199 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+use super::entity::FixedEntityBundle;
+
+/// [`Plugin`] for bodies of water that bend the light beams passing through them.
+pub struct WaterPlugin;
+
+impl Plugin for WaterPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity::<WaterVolumeBundle>("WaterVolume");
+    }
+}
+
+/// [`Component`] for rectangular bodies of water, placed in Ldtk. Beams entering the water are
+/// bent towards the surface's normal based on its `refraction` index, travel through it in a
+/// straight line, and are bent back when they leave. A beam that meets the inside of the surface
+/// at too steep of an angle to leave reflects back into the water instead, like it would at the
+/// surface of a real pool. Bending doesn't use up any of a beam's bounces.
+///
+/// Beams go straight through anything else inside of the water, so water volumes shouldn't
+/// overlap with other things beams interact with.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct WaterVolume {
+    /// The refractive index of the water relative to the air around it. Real water is about 1.33,
+    /// and values below 1 bend beams away from the normal instead.
+    pub refraction: f32,
+    pub half_size: Vec2,
+}
+
+/// The path a beam takes inside of a [`WaterVolume`], see [`WaterVolume::trace`].
+#[derive(Debug, PartialEq)]
+pub struct WaterPath {
+    /// The points where the beam reflected off of the inside of the surface, followed by the
+    /// point it left the water at
+    pub points: Vec<Vec2>,
+    /// The direction the beam leaves the water in, or [`None`] if it was still reflecting
+    /// inside of the water after the maximum number of points
+    pub exit_dir: Option<Vec2>,
+}
+
+impl WaterVolume {
+    /// Traces a beam that entered the water centered at `center` at `entry`, and was refracted
+    /// into `dir`, until it leaves the water or reaches `max_points` points.
+    pub fn trace(&self, center: Vec2, entry: Vec2, dir: Vec2, max_points: usize) -> WaterPath {
+        let rect = Rect::from_center_half_size(center, self.half_size);
+        let mut path = WaterPath {
+            points: vec![],
+            exit_dir: None,
+        };
+        let mut pos = entry;
+        let mut dir = dir;
+
+        while path.points.len() < max_points {
+            let (distance, normal) = distance_to_exit(rect, pos, dir);
+            pos += dir * distance;
+            path.points.push(pos);
+            match refract(dir, -normal, self.refraction) {
+                Some(exit_dir) => {
+                    path.exit_dir = Some(exit_dir);
+                    break;
+                }
+                // total internal reflection
+                None => dir = dir.reflect(normal),
+            }
+        }
+        path
+    }
+}
+
+/// The distance from `pos` inside of `rect` to its edge along `dir`, and the outward normal of
+/// the edge.
+fn distance_to_exit(rect: Rect, pos: Vec2, dir: Vec2) -> (f32, Vec2) {
+    let axis_distance = |pos: f32, dir: f32, min: f32, max: f32| {
+        if dir > 0.0 {
+            (max - pos) / dir
+        } else if dir < 0.0 {
+            (min - pos) / dir
+        } else {
+            f32::INFINITY
+        }
+    };
+    let x = axis_distance(pos.x, dir.x, rect.min.x, rect.max.x).max(0.0);
+    let y = axis_distance(pos.y, dir.y, rect.min.y, rect.max.y).max(0.0);
+    if x < y {
+        (x, Vec2::new(dir.x.signum(), 0.0))
+    } else {
+        (y, Vec2::new(0.0, dir.y.signum()))
+    }
+}
+
+/// Refracts `dir` through a surface with a `normal` facing against it, where `eta` is the
+/// refractive index on the side `dir` comes from divided by the one on the other side. Returns
+/// [`None`] when the beam is totally internally reflected instead.
+pub fn refract(dir: Vec2, normal: Vec2, eta: f32) -> Option<Vec2> {
+    let cos_incident = -normal.dot(dir);
+    let k = 1.0 - eta * eta * (1.0 - cos_incident * cos_incident);
+    if k < 0.0 {
+        return None;
+    }
+    Some((dir * eta + normal * (eta * cos_incident - k.sqrt())).normalize())
+}
+
+impl From<&EntityInstance> for WaterVolume {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        let refraction = *entity_instance
+            .get_float_field("refraction")
+            .expect("refraction needs to be a float field on all water volumes");
+
+        WaterVolume {
+            refraction,
+            half_size: Vec2::new(
+                entity_instance.width as f32 / 2.0,
+                entity_instance.height as f32 / 2.0,
+            ),
+        }
+    }
+}
+
+/// [`Bundle`] that includes all the [`Component`]s needed for a [`WaterVolume`] to function
+/// properly.
+#[derive(Bundle, LdtkEntity)]
+pub struct WaterVolumeBundle {
+    #[from_entity_instance]
+    physics: FixedEntityBundle,
+    #[from_entity_instance]
+    water_volume: WaterVolume,
+    #[with(water_volume_sprite)]
+    sprite: Sprite,
+}
+
+pub fn water_volume_sprite(entity_instance: &EntityInstance) -> Sprite {
+    Sprite::from_color(
+        Color::srgba(0.2, 0.4, 0.9, 0.35),
+        Vec2::new(entity_instance.width as f32, entity_instance.height as f32),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn angle_from_vertical(dir: Vec2) -> f32 {
+        dir.x.abs().atan2(dir.y.abs()).to_degrees()
+    }
+
+    #[test]
+    fn beam_bends_through_water() {
+        let water = WaterVolume {
+            refraction: 1.33,
+            half_size: Vec2::new(50.0, 10.0),
+        };
+        let incoming = Vec2::new(1.0, -1.0).normalize();
+        // entering through the top of the water
+        let inside = refract(incoming, Vec2::Y, 1.0 / water.refraction).unwrap();
+        let expected = (45f32.to_radians().sin() / 1.33).asin().to_degrees();
+        assert!((angle_from_vertical(inside) - expected).abs() < 1e-3);
+
+        let path = water.trace(Vec2::ZERO, Vec2::new(-20.0, 10.0), inside, 4);
+        assert_eq!(path.points.len(), 1);
+        // leaves through the bottom, parallel to the beam that came in
+        assert!((path.points[0].y + 10.0).abs() < 1e-4);
+        let expected_x = -20.0 + 20.0 * expected.to_radians().tan();
+        assert!((path.points[0].x - expected_x).abs() < 1e-3);
+        assert!(path.exit_dir.unwrap().distance(incoming) < 1e-4);
+    }
+
+    #[test]
+    fn steep_beams_reflect_inside_water() {
+        let water = WaterVolume {
+            refraction: 1.5,
+            half_size: Vec2::new(10.0, 50.0),
+        };
+        let incoming = Vec2::from_angle(-10f32.to_radians());
+        let inside = refract(incoming, Vec2::Y, 1.0 / water.refraction).unwrap();
+
+        let path = water.trace(Vec2::ZERO, Vec2::new(0.0, 50.0), inside, 8);
+        // bounces between the sides of the water on its way down
+        assert!(path.points.len() > 2);
+        assert!((path.points[0].x - 10.0).abs() < 1e-4);
+        assert!((path.points[1].x + 10.0).abs() < 1e-4);
+        let last = path.points.last().unwrap();
+        assert!((last.y + 50.0).abs() < 1e-4);
+        assert!(path.exit_dir.unwrap().y < 0.0);
+
+        // runs out of points before it gets out
+        let path = water.trace(Vec2::ZERO, Vec2::new(0.0, 50.0), inside, 2);
+        assert_eq!(path.points.len(), 2);
+        assert_eq!(path.exit_dir, None);
+    }
+
+    #[test]
+    fn leaving_dense_water_at_a_steep_angle_reflects() {
+        assert!(refract(Vec2::NEG_Y, Vec2::Y, 1.5).is_some());
+        let steep = Vec2::from_angle(-30f32.to_radians());
+        assert_eq!(refract(steep, Vec2::Y, 1.5), None);
+        assert!(refract(steep, Vec2::Y, 1.0 / 1.5).is_some());
+    }
+}
@@ -0,0 +1,104 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+use super::entity::FixedEntityBundle;
+
+/// [`Plugin`] for thin panels that strong beams can punch through.
+pub struct WeakPanelPlugin;
+
+impl Plugin for WeakPanelPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity::<WeakPanelBundle>("WeakPanel");
+    }
+}
+
+/// [`Component`] for thin panels, placed in Ldtk, that beams with enough
+/// [`penetration`](crate::light::LightBeamSource::penetration) pass straight through. Each panel
+/// a beam passes through takes its own `attenuation` off of the beam's penetration, and the same
+/// fraction off of the beam's intensity. A beam without enough penetration left bounces off of
+/// the panel like it would off of a wall.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct WeakPanel {
+    pub attenuation: f32,
+}
+
+impl WeakPanel {
+    /// The penetration a beam has left after passing through the panel, or [`None`] if the beam
+    /// can't pass through it. Panels that would let none of the beam's intensity through can't be
+    /// passed through either.
+    pub fn penetrate(&self, penetration: f32) -> Option<f32> {
+        let remaining = penetration - self.attenuation.max(0.0);
+        (penetration > 0.0 && remaining > 0.0 && self.transmittance() > 0.0).then_some(remaining)
+    }
+
+    /// The fraction of a beam's intensity that makes it through the panel.
+    pub fn transmittance(&self) -> f32 {
+        (1.0 - self.attenuation).clamp(0.0, 1.0)
+    }
+}
+
+impl From<&EntityInstance> for WeakPanel {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        let attenuation = *entity_instance
+            .get_float_field("attenuation")
+            .expect("attenuation needs to be a float field on all weak panels");
+
+        WeakPanel { attenuation }
+    }
+}
+
+/// [`Bundle`] that includes all the [`Component`]s needed for a [`WeakPanel`] to function
+/// properly.
+#[derive(Bundle, LdtkEntity)]
+pub struct WeakPanelBundle {
+    #[from_entity_instance]
+    physics: FixedEntityBundle,
+    #[from_entity_instance]
+    weak_panel: WeakPanel,
+    #[with(weak_panel_sprite)]
+    sprite: Sprite,
+}
+
+pub fn weak_panel_sprite(entity_instance: &EntityInstance) -> Sprite {
+    Sprite::from_color(
+        Color::srgba(0.8, 0.8, 0.9, 0.5),
+        Vec2::new(entity_instance.width as f32, entity_instance.height as f32),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Steps a beam with `penetration` through `hits`, where [`None`] is a wall, returning how
+    /// many of them it passed through and the penetration it had left.
+    fn step_beam(mut penetration: f32, hits: &[Option<WeakPanel>]) -> (usize, f32) {
+        for (i, hit) in hits.iter().enumerate() {
+            match hit.and_then(|panel| panel.penetrate(penetration)) {
+                Some(remaining) => penetration = remaining,
+                None => return (i, penetration),
+            }
+        }
+        (hits.len(), penetration)
+    }
+
+    #[test]
+    fn beam_punches_through_panels_until_a_wall() {
+        let thin = WeakPanel { attenuation: 0.2 };
+        let thick = WeakPanel { attenuation: 0.5 };
+        let (passed, remaining) = step_beam(1.0, &[Some(thin), Some(thick), None, Some(thin)]);
+        assert_eq!(passed, 2);
+        // each panel takes off its own attenuation
+        assert!((remaining - 0.3).abs() < 1e-5);
+    }
+
+    #[test]
+    fn beam_stops_when_out_of_penetration() {
+        let panel = WeakPanel { attenuation: 0.4 };
+        assert_eq!(step_beam(1.0, &[Some(panel); 3]).0, 2);
+        // beams without penetration bounce off of every panel
+        assert_eq!(step_beam(0.0, &[Some(WeakPanel { attenuation: 0.0 })]).0, 0);
+        // and so does every beam off of panels that let nothing through
+        assert_eq!(step_beam(5.0, &[Some(WeakPanel { attenuation: 1.0 })]).0, 0);
+    }
+}
@@ -13,6 +13,8 @@ use bevy_ecs_ldtk::{prelude::LdtkProject, LdtkProjectHandle};
 use crate::camera::{
     camera_position_from_level, handle_move_camera, CameraControlType, CameraMoveEvent,
 };
+use crate::input::menu::{MenuDirection, MenuNavigateEvent};
+use crate::level::setup::validate_level_index;
 use crate::level::start_flag::StartFlag;
 use crate::level::{get_ldtk_level_data, level_box_from_level, CurrentLevel};
 use crate::player::PlayerMarker;
@@ -27,6 +29,8 @@ const ENTITY_LAYER_IDENT: &str = "Entities";
 const SENSOR_ENTITY_IDENT: &str = "Sensor";
 const SENSOR_COLOR_IDENT: &str = "toggle_color";
 
+const SELECTED_BORDER_COLOR: Color = Color::srgb(1.0, 0.85, 0.3);
+
 // [R, G, B, A] colors for level preview
 const LEVEL_PREVIEW_COLORS: [[u8; 4]; 16] = [
     [0, 0, 0, 255],       // intgrid 0
@@ -69,13 +73,30 @@ pub struct LevelPreviewStore(HashMap<String, (Vec2, Handle<Image>)>);
 #[derive(Component)]
 pub struct LevelSelectButtonIndex(usize);
 
+/// [`Component`] for the position of a level select button, in the order they are shown.
+#[derive(Component)]
+pub struct LevelSelectButtonSlot(usize);
+
+/// [`Resource`] for the level picked with the keyboard in the level select, as the slot and index
+/// of its button. `confirmed` is set once it has been chosen to be played.
+#[derive(Resource, Default)]
+pub struct LevelSelectCursor {
+    selected: Option<(usize, usize)>,
+    confirmed: bool,
+}
+
 impl Plugin for LevelSelectPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(LevelPreviewStore(HashMap::new()))
+            .init_resource::<LevelSelectCursor>()
             .add_systems(
                 PostUpdate,
                 switch_to_level_select.run_if(input_just_pressed(KeyCode::KeyL)),
             )
+            .add_systems(
+                Update,
+                navigate_level_select.run_if(in_state(UiState::LevelSelect)),
+            )
             .add_systems(
                 FixedUpdate,
                 (
@@ -166,7 +187,7 @@ fn spawn_level_select(
                     ..default()
                 })
                 .with_children(|parent| {
-                    for (level_id, index) in sorted_levels.iter() {
+                    for (slot, (level_id, index)) in sorted_levels.iter().enumerate() {
                         parent
                             .spawn((
                                 Button,
@@ -182,6 +203,7 @@ fn spawn_level_select(
                                 },
                                 BorderColor(Color::WHITE),
                                 LevelSelectButtonIndex(*index),
+                                LevelSelectButtonSlot(slot),
                             ))
                             .with_child((
                                 Text::new(level_id.to_string()),
@@ -206,21 +228,71 @@ fn spawn_level_select(
 fn despawn_level_select(
     mut commands: Commands,
     mut level_select_ui_query: Query<Entity, With<LevelSelectUiMarker>>,
+    mut cursor: ResMut<LevelSelectCursor>,
 ) {
     let Ok(entity) = level_select_ui_query.get_single_mut() else {
         return;
     };
 
     commands.entity(entity).despawn_recursive();
+    *cursor = LevelSelectCursor::default();
+}
+
+/// [`System`] that moves the [`LevelSelectCursor`] between the level select buttons with the
+/// direction keys, and chooses the selected level with Enter or Space.
+fn navigate_level_select(
+    mut ev_navigate: EventReader<MenuNavigateEvent>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut cursor: ResMut<LevelSelectCursor>,
+    mut q_buttons: Query<(
+        &LevelSelectButtonSlot,
+        &LevelSelectButtonIndex,
+        &mut BorderColor,
+    )>,
+) {
+    if q_buttons.is_empty() {
+        ev_navigate.clear();
+        return;
+    }
+    let last_slot = q_buttons
+        .iter()
+        .map(|(slot, _, _)| slot.0)
+        .max()
+        .unwrap_or(0);
+
+    let mut slot = cursor.selected.map(|(slot, _)| slot);
+    for MenuNavigateEvent(direction) in ev_navigate.read() {
+        slot = Some(match (slot, direction) {
+            (None, _) => 0,
+            (Some(slot), MenuDirection::Up | MenuDirection::Left) => slot.saturating_sub(1),
+            (Some(slot), MenuDirection::Down | MenuDirection::Right) => (slot + 1).min(last_slot),
+        });
+    }
+
+    if slot != cursor.selected.map(|(slot, _)| slot) {
+        for (button_slot, index, mut border) in q_buttons.iter_mut() {
+            if Some(button_slot.0) == slot {
+                cursor.selected = Some((button_slot.0, index.0));
+                border.0 = SELECTED_BORDER_COLOR;
+            } else {
+                border.0 = Color::WHITE;
+            }
+        }
+    }
+
+    if cursor.selected.is_some() && keys.any_just_pressed([KeyCode::Enter, KeyCode::Space]) {
+        cursor.confirmed = true;
+    }
 }
 
 #[allow(clippy::type_complexity)]
 #[allow(clippy::too_many_arguments)]
 pub fn handle_level_selection(
-    mut interaction_query: Query<
+    interaction_query: Query<
         (&Interaction, &LevelSelectButtonIndex),
         (Changed<Interaction>, With<Button>),
     >,
+    cursor: Res<LevelSelectCursor>,
     mut next_game_state: ResMut<NextState<GameState>>,
     ldtk_assets: Res<Assets<LdtkProject>>,
     query_ldtk: Query<&LdtkProjectHandle>,
@@ -238,12 +310,25 @@ pub fn handle_level_selection(
     let Ok(ldtk_levels) = get_ldtk_level_data(ldtk_assets.into_inner(), ldtk_handle) else {
         return;
     };
-    'loop_interactions: for (interaction, index) in interaction_query.iter_mut() {
-        if index.0 >= ldtk_levels.len() {
-            panic!("Selected level index is out of bounds!")
-        }
-        let level = &ldtk_levels[index.0];
-        match *interaction {
+    // the level picked with the keyboard acts like a button the mouse is over or pressing
+    let keyboard_interaction = cursor
+        .selected
+        .filter(|_| cursor.is_changed())
+        .map(|(_, index)| {
+            if cursor.confirmed {
+                (Interaction::Pressed, index)
+            } else {
+                (Interaction::Hovered, index)
+            }
+        });
+    let interactions = interaction_query
+        .iter()
+        .map(|(interaction, index)| (*interaction, index.0))
+        .chain(keyboard_interaction);
+    'loop_interactions: for (interaction, index) in interactions {
+        let index = validate_level_index(index, ldtk_levels.len());
+        let level = &ldtk_levels[index];
+        match interaction {
             Interaction::Pressed => {
                 let Some(layers) = level.layer_instances.as_ref() else {
                     panic!("Layers not found! (This is probably because you are using the \"Separate level files\" option.)")
@@ -266,7 +351,7 @@ pub fn handle_level_selection(
 
                                 // Send a camera transition event to tp the camera immediately
                                 let camera_pos = camera_position_from_level(
-                                    level_box_from_level(&ldtk_levels[index.0]),
+                                    level_box_from_level(&ldtk_levels[index]),
                                     player_transform.translation.xy(),
                                 );
                                 ev_move_camera.send(CameraMoveEvent {
@@ -0,0 +1,291 @@
+use bevy::prelude::*;
+
+use crate::config::Config;
+
+use super::{segments::PrevLightBeamPlayback, BeamBounces, LightBeamSource, LightColor};
+
+/// [`Event`] sent when a [`LightBeamSource`] is spawned, e.g. when the player shoots a beam.
+#[derive(Event, Debug)]
+pub struct BeamStartedEvent {
+    pub source: Entity,
+    pub color: LightColor,
+    pub position: Vec2,
+}
+
+/// [`Event`] sent when a [`LightBeamSource`] is despawned, e.g. when the level is reset.
+#[derive(Event, Debug)]
+pub struct BeamStoppedEvent {
+    pub source: Entity,
+}
+
+/// [`Event`] sent when a light beam reaches a new surface, sent from
+/// [`simulate_light_sources`](super::segments::simulate_light_sources). A beam sends this once
+/// per surface it reaches, not every frame it stays on it.
+#[derive(Event, Debug)]
+pub struct BeamReflectedEvent {
+    pub source: Entity,
+    pub color: LightColor,
+    /// The entity that was hit
+    pub entity: Entity,
+    pub point: Vec2,
+}
+
+/// [`Event`] sent at a fixed rate while a light beam ends on a surface, for effects like sparks
+/// at the point of impact. The rate is set by `lighting_config.beam_impact_rate` in
+/// `Lightborne.toml`.
+#[derive(Event, Debug)]
+pub struct BeamImpactTick {
+    pub source: Entity,
+    pub color: LightColor,
+    pub point: Vec2,
+}
+
+/// [`Component`] on every [`LightBeamSource`] that keeps the time since its last
+/// [`BeamImpactTick`].
+#[derive(Component, Default, Debug)]
+pub struct BeamImpactTimer {
+    elapsed: f32,
+}
+
+impl BeamImpactTimer {
+    /// Advances the timer by `delta` seconds, returning how many ticks are due at `rate` ticks per
+    /// second.
+    pub fn tick(&mut self, delta: f32, rate: f32) -> u32 {
+        if rate <= 0.0 {
+            self.elapsed = 0.0;
+            return 0;
+        }
+        self.elapsed += delta;
+        let ticks = (self.elapsed * rate).floor();
+        self.elapsed -= ticks / rate;
+        ticks as u32
+    }
+
+    pub fn reset(&mut self) {
+        self.elapsed = 0.0;
+    }
+}
+
+/// The point where a beam that can bounce `max_bounces` times ends, if it ends on a surface.
+/// Beams end on a surface once they have used up all of their bounces, so the last surface they
+/// reached is where they stop.
+pub fn beam_impact_point(playback: &PrevLightBeamPlayback, max_bounces: usize) -> Option<Vec2> {
+    let intersections = playback.intersections.iter().flatten();
+    let bounces = intersections
+        .clone()
+        .filter(|intersection| !intersection.refracted)
+        .count();
+    if bounces <= max_bounces {
+        return None;
+    }
+    intersections.last().map(|intersection| intersection.point)
+}
+
+/// [`System`] that sends [`BeamImpactTick`]s while [`LightBeamSource`]s end on a surface. The
+/// timer starts over as soon as a beam leaves the surface, so ticks stop on the same step.
+pub fn send_beam_impact_ticks(
+    mut q_sources: Query<(
+        Entity,
+        &LightBeamSource,
+        &PrevLightBeamPlayback,
+        &mut BeamImpactTimer,
+    )>,
+    mut ev_beam_impact_tick: EventWriter<BeamImpactTick>,
+    beam_bounces: Res<BeamBounces>,
+    config: Res<Config>,
+    time: Res<Time>,
+) {
+    for (entity, source, playback, mut timer) in q_sources.iter_mut() {
+        let Some(point) = beam_impact_point(playback, beam_bounces.get(source.color)) else {
+            timer.reset();
+            continue;
+        };
+        let ticks = timer.tick(time.delta_secs(), config.lighting_config.beam_impact_rate);
+        for _ in 0..ticks {
+            ev_beam_impact_tick.send(BeamImpactTick {
+                source: entity,
+                color: source.color,
+                point,
+            });
+        }
+    }
+}
+
+/// [`System`] that sends [`BeamStartedEvent`]s and [`BeamStoppedEvent`]s when
+/// [`LightBeamSource`]s are added and removed.
+pub fn send_beam_lifecycle_events(
+    q_new_sources: Query<(Entity, &LightBeamSource), Added<LightBeamSource>>,
+    mut removed_sources: RemovedComponents<LightBeamSource>,
+    mut ev_beam_started: EventWriter<BeamStartedEvent>,
+    mut ev_beam_stopped: EventWriter<BeamStoppedEvent>,
+) {
+    for (entity, source) in q_new_sources.iter() {
+        ev_beam_started.send(BeamStartedEvent {
+            source: entity,
+            color: source.color,
+            position: source.start_pos,
+        });
+    }
+    for entity in removed_sources.read() {
+        ev_beam_stopped.send(BeamStoppedEvent { source: entity });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::light::{segments::LightBeamIntersection, LightBeamDepth};
+
+    fn source() -> LightBeamSource {
+        LightBeamSource {
+            start_pos: Vec2::ZERO,
+            start_dir: Vec2::X,
+            time_traveled: 0.0,
+            color: LightColor::Green,
+            width: 0.0,
+            intensity: 1.0,
+            penetration: 0.0,
+            depth: LightBeamDepth::Background,
+        }
+    }
+
+    #[derive(Resource, Default)]
+    struct EventCounts {
+        started: usize,
+        stopped: usize,
+    }
+
+    fn count_events(
+        mut counts: ResMut<EventCounts>,
+        mut ev_beam_started: EventReader<BeamStartedEvent>,
+        mut ev_beam_stopped: EventReader<BeamStoppedEvent>,
+    ) {
+        counts.started += ev_beam_started.read().count();
+        counts.stopped += ev_beam_stopped.read().count();
+    }
+
+    #[test]
+    fn beam_lifecycle_events_fire_once_per_transition() {
+        let mut app = App::new();
+        app.init_resource::<EventCounts>()
+            .add_event::<BeamStartedEvent>()
+            .add_event::<BeamStoppedEvent>()
+            .add_systems(Update, (send_beam_lifecycle_events, count_events).chain());
+
+        let source = app.world_mut().spawn(source()).id();
+        for _ in 0..3 {
+            app.update();
+        }
+        let counts = app.world().resource::<EventCounts>();
+        assert_eq!((counts.started, counts.stopped), (1, 0));
+
+        app.world_mut().despawn(source);
+        for _ in 0..3 {
+            app.update();
+        }
+        let counts = app.world().resource::<EventCounts>();
+        assert_eq!((counts.started, counts.stopped), (1, 1));
+    }
+
+    #[derive(Resource, Default)]
+    struct ImpactTicks(Vec<Vec2>);
+
+    fn count_impact_ticks(
+        mut ticks: ResMut<ImpactTicks>,
+        mut ev_beam_impact_tick: EventReader<BeamImpactTick>,
+    ) {
+        ticks
+            .0
+            .extend(ev_beam_impact_tick.read().map(|tick| tick.point));
+    }
+
+    #[test]
+    fn beams_stop_after_configured_bounces() {
+        let mut playback = PrevLightBeamPlayback::default();
+        for i in 0..9 {
+            playback.intersections[i] = Some(LightBeamIntersection {
+                entity: Entity::PLACEHOLDER,
+                point: Vec2::new(i as f32, 0.0),
+                time: i as f32,
+                refracted: false,
+            });
+        }
+        // 8 bounces and a surface to stop at
+        assert_eq!(beam_impact_point(&playback, 8), Some(Vec2::new(8.0, 0.0)));
+        // the beam is still in the air after its ninth surface
+        assert_eq!(beam_impact_point(&playback, 9), None);
+    }
+
+    #[test]
+    fn impact_ticks_follow_rate() {
+        let mut timer = BeamImpactTimer::default();
+        let ticks: Vec<u32> = (0..6).map(|_| timer.tick(0.25, 2.0)).collect();
+        assert_eq!(ticks, vec![0, 1, 0, 1, 0, 1]);
+        // long frames send every tick that is due
+        assert_eq!(timer.tick(1.75, 2.0), 3);
+        assert_eq!(timer.tick(1.0, 0.0), 0);
+    }
+
+    #[test]
+    fn impact_ticks_stop_when_beam_clears() {
+        let mut app = App::new();
+        let mut config = Config::default();
+        config.lighting_config.beam_impact_rate = 2.0;
+        app.insert_resource(config)
+            .init_resource::<Time>()
+            .init_resource::<BeamBounces>()
+            .init_resource::<ImpactTicks>()
+            .add_event::<BeamImpactTick>()
+            .add_systems(Update, (send_beam_impact_ticks, count_impact_ticks).chain());
+
+        let point = Vec2::new(4.0, 2.0);
+        let mut playback = PrevLightBeamPlayback::default();
+        let intersection = |point| {
+            Some(LightBeamIntersection {
+                entity: Entity::PLACEHOLDER,
+                point,
+                time: 1.0,
+                refracted: false,
+            })
+        };
+        // the beam bounces once and stops at its second surface
+        playback.intersections[0] = intersection(Vec2::ZERO);
+        playback.intersections[1] = intersection(point);
+        let source = app
+            .world_mut()
+            .spawn((source(), playback, BeamImpactTimer::default()))
+            .id();
+
+        let mut step = |app: &mut App| {
+            app.world_mut()
+                .resource_mut::<Time>()
+                .advance_by(Duration::from_millis(250));
+            app.update();
+        };
+        for _ in 0..10 {
+            step(&mut app);
+        }
+        let ticks = &app.world().resource::<ImpactTicks>().0;
+        assert_eq!(ticks.len(), 5);
+        assert!(ticks.iter().all(|tick| *tick == point));
+
+        // the beam leaves the surface
+        app.world_mut()
+            .get_mut::<PrevLightBeamPlayback>(source)
+            .unwrap()
+            .intersections
+            .iter_mut()
+            .for_each(|intersection| *intersection = None);
+        for _ in 0..10 {
+            step(&mut app);
+        }
+        assert_eq!(app.world().resource::<ImpactTicks>().0.len(), 5);
+
+        // and is removed entirely
+        app.world_mut().despawn(source);
+        step(&mut app);
+        assert_eq!(app.world().resource::<ImpactTicks>().0.len(), 5);
+    }
+}
@@ -0,0 +1,64 @@
+use bevy::prelude::*;
+
+/// Light beams fainter than this are considered to have faded out completely in
+/// [`VolumetricFog`], so they stop traveling.
+const MIN_BEAM_INTENSITY: f32 = 0.05;
+
+/// [`Resource`] describing the fog that light beams travel through. Beams lose intensity
+/// exponentially with the distance they travel, which dims their far ends, weakens what they hit,
+/// and cuts them off once they are too faint to see. A `density` of 0 means clear air, where
+/// beams never lose intensity.
+#[derive(Resource, Clone, Copy, Debug, Default)]
+pub struct VolumetricFog {
+    /// The fraction of a beam's intensity lost per unit traveled
+    pub density: f32,
+}
+
+impl VolumetricFog {
+    /// The fraction of a beam's intensity left after traveling `distance` units.
+    pub fn attenuation(&self, distance: f32) -> f32 {
+        if self.density <= 0.0 {
+            return 1.0;
+        }
+        (-self.density * distance).exp()
+    }
+
+    /// How far a beam that starts with `intensity` can travel before it fades out.
+    pub fn reach(&self, intensity: f32) -> f32 {
+        if self.density <= 0.0 {
+            return f32::INFINITY;
+        }
+        ((intensity / MIN_BEAM_INTENSITY).ln() / self.density).max(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thicker_fog_dims_hits() {
+        let distance = 100.0;
+        let intensities: Vec<f32> = [0.0, 0.002, 0.01, 0.05]
+            .into_iter()
+            .map(|density| VolumetricFog { density }.attenuation(distance))
+            .collect();
+
+        assert_eq!(intensities[0], 1.0);
+        for pair in intensities.windows(2) {
+            assert!(pair[1] < pair[0]);
+        }
+    }
+
+    #[test]
+    fn thicker_fog_shortens_reach() {
+        assert_eq!(VolumetricFog::default().reach(1.0), f32::INFINITY);
+
+        let thin = VolumetricFog { density: 0.005 };
+        let thick = VolumetricFog { density: 0.02 };
+        assert!(thick.reach(1.0) < thin.reach(1.0));
+        // at the edge of its reach, the beam is as faint as it can be
+        let reach = thin.reach(0.8);
+        assert!((0.8 * thin.attenuation(reach) - MIN_BEAM_INTENSITY).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,211 @@
+use bevy::prelude::*;
+
+use crate::lighting::LineLight2d;
+
+use super::{
+    events::beam_impact_point, segments::PrevLightBeamPlayback, BeamBounces, LightBeamSource,
+};
+
+/// How long a beam has to rest on the same spot for its [`BeamImpactGlow`] to get as hot as it
+/// gets, in seconds.
+const IMPACT_GLOW_HEAT_SECS: f32 = 1.5;
+
+/// How far the impact point of a beam can drift before its [`BeamImpactGlow`] cools back down.
+const IMPACT_GLOW_MOVE_TOLERANCE: f32 = 1.0;
+
+/// The intensity of a cold [`BeamImpactGlow`], doubled once it is as hot as it gets.
+const IMPACT_GLOW_INTENSITY: f32 = 0.6;
+
+/// How far towards white a fully heated [`BeamImpactGlow`] shifts the beam's color.
+const IMPACT_GLOW_MAX_WHITENESS: f32 = 0.8;
+
+const IMPACT_GLOW_RADIUS: f32 = 12.0;
+
+/// [`Component`] on every [`LightBeamSource`] for the small light where the beam ends on a
+/// surface. The longer the beam rests on the same spot, the brighter and whiter the light gets,
+/// so the player can tell a beam is charging something up. It cools down as soon as the beam
+/// moves.
+#[derive(Component, Default, Debug)]
+pub struct BeamImpactGlow {
+    /// How long the beam has been resting on `point`, in seconds
+    heat: f32,
+    point: Option<Vec2>,
+    /// The light at the impact point, a child of the source
+    light: Option<Entity>,
+}
+
+impl BeamImpactGlow {
+    /// How hot the glow is, from 0 when the beam just landed to 1 after
+    /// [`IMPACT_GLOW_HEAT_SECS`].
+    pub fn heat(&self) -> f32 {
+        (self.heat / IMPACT_GLOW_HEAT_SECS).min(1.0)
+    }
+
+    /// Moves the glow to `point` and heats it by `delta` seconds, starting over if the point
+    /// moved. `None` means the beam no longer ends on a surface.
+    pub fn update(&mut self, point: Option<Vec2>, delta: f32) {
+        let rested = match (self.point, point) {
+            (Some(prev), Some(point)) => prev.distance(point) <= IMPACT_GLOW_MOVE_TOLERANCE,
+            _ => false,
+        };
+        self.heat = if rested { self.heat + delta } else { 0.0 };
+        self.point = point;
+    }
+}
+
+/// The color and intensity of a [`BeamImpactGlow`] of a beam with `color`, `heat` from 0 to 1.
+pub fn impact_glow_color(color: Vec3, heat: f32) -> Vec4 {
+    let heat = heat.clamp(0.0, 1.0);
+    color
+        .lerp(Vec3::ONE, heat * IMPACT_GLOW_MAX_WHITENESS)
+        .extend(IMPACT_GLOW_INTENSITY * (1.0 + heat))
+}
+
+/// [`System`] that moves the [`BeamImpactGlow`] of each [`LightBeamSource`] to where the beam
+/// ends and heats it up, spawning its light when the beam lands and despawning it when the beam
+/// leaves the surface.
+pub fn update_beam_impact_glows(
+    mut commands: Commands,
+    mut q_sources: Query<(
+        Entity,
+        &LightBeamSource,
+        &PrevLightBeamPlayback,
+        &GlobalTransform,
+        &mut BeamImpactGlow,
+    )>,
+    mut q_lights: Query<(&mut LineLight2d, &mut Transform)>,
+    beam_bounces: Res<BeamBounces>,
+    time: Res<Time>,
+) {
+    for (entity, source, playback, source_transform, mut glow) in q_sources.iter_mut() {
+        let point = beam_impact_point(playback, beam_bounces.get(source.color));
+        glow.update(point, time.delta_secs());
+
+        let Some(point) = point else {
+            if let Some(light) = glow.light.take() {
+                commands.entity(light).despawn_recursive();
+            }
+            continue;
+        };
+
+        let color = impact_glow_color(source.color.lighting_color(), glow.heat());
+        let translation = source_transform
+            .affine()
+            .inverse()
+            .transform_point3(point.extend(0.0));
+        if let Some((mut light, mut transform)) =
+            glow.light.and_then(|light| q_lights.get_mut(light).ok())
+        {
+            light.color = color;
+            transform.translation = translation;
+            continue;
+        }
+        let light = commands
+            .spawn((
+                LineLight2d::point(color, IMPACT_GLOW_RADIUS, 0.0),
+                Transform::from_translation(translation),
+            ))
+            .set_parent(entity)
+            .id();
+        glow.light = Some(light);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::light::{segments::LightBeamIntersection, LightColor};
+
+    use super::*;
+
+    fn ending_at(point: Option<Vec2>) -> PrevLightBeamPlayback {
+        let mut playback = PrevLightBeamPlayback::default();
+        if let Some(point) = point {
+            // the beam stops at the first surface it reaches
+            playback.intersections[0] = Some(LightBeamIntersection {
+                entity: Entity::PLACEHOLDER,
+                point,
+                time: 1.0,
+                refracted: false,
+            });
+        }
+        playback
+    }
+
+    #[test]
+    fn resting_beams_glow_hotter_and_moving_resets() {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .insert_resource(BeamBounces {
+                green: 0,
+                ..default()
+            })
+            .add_systems(Update, update_beam_impact_glows);
+        let source = app
+            .world_mut()
+            .spawn((
+                LightBeamSource {
+                    start_pos: Vec2::ZERO,
+                    start_dir: Vec2::X,
+                    time_traveled: 0.0,
+                    color: LightColor::Green,
+                    width: 0.0,
+                    intensity: 1.0,
+                    penetration: 0.0,
+                    depth: default(),
+                },
+                ending_at(Some(Vec2::new(20.0, 0.0))),
+            ))
+            .id();
+        let step = |app: &mut App, secs: f32| {
+            app.world_mut()
+                .resource_mut::<Time>()
+                .advance_by(Duration::from_secs_f32(secs));
+            app.update();
+            let glow = app.world().get::<BeamImpactGlow>(source).unwrap();
+            glow.light
+                .map(|light| app.world().get::<LineLight2d>(light).unwrap().color)
+        };
+        let cold = impact_glow_color(LightColor::Green.lighting_color(), 0.0);
+
+        assert_eq!(step(&mut app, 0.1), Some(cold));
+        let warm = step(&mut app, 0.5).unwrap();
+        let hot = step(&mut app, 0.5).unwrap();
+        // brighter and whiter the longer the beam rests
+        assert!(warm.w > cold.w && hot.w > warm.w);
+        assert!(warm.x > cold.x && hot.x > warm.x);
+        assert!(hot.y - hot.x < warm.y - warm.x);
+        let hottest = step(&mut app, 5.0).unwrap();
+        assert_eq!(
+            hottest,
+            impact_glow_color(LightColor::Green.lighting_color(), 1.0)
+        );
+
+        // moving the beam cools it down on the same step
+        app.world_mut()
+            .entity_mut(source)
+            .insert(ending_at(Some(Vec2::new(20.0, 10.0))));
+        assert_eq!(step(&mut app, 0.1), Some(cold));
+        let light = app
+            .world()
+            .get::<BeamImpactGlow>(source)
+            .unwrap()
+            .light
+            .unwrap();
+        assert_eq!(
+            app.world().get::<Transform>(light).unwrap().translation,
+            Vec3::new(20.0, 10.0, 0.0)
+        );
+
+        // and so does leaving the surface
+        step(&mut app, 1.0);
+        app.world_mut().entity_mut(source).insert(ending_at(None));
+        assert_eq!(step(&mut app, 0.1), None);
+        assert!(!app.world().entities().contains(light));
+        app.world_mut()
+            .entity_mut(source)
+            .insert(ending_at(Some(Vec2::new(20.0, 10.0))));
+        assert_eq!(step(&mut app, 0.1), Some(cold));
+    }
+}
@@ -3,18 +3,30 @@ use bevy::{
     sprite::{AlphaMode2d, Material2dPlugin},
 };
 use bevy_ecs_ldtk::prelude::*;
+use serde::Deserialize;
 
-use enum_map::Enum;
+use enum_map::{enum_map, Enum, EnumMap};
+use events::{
+    send_beam_impact_ticks, send_beam_lifecycle_events, BeamImpactTick, BeamImpactTimer,
+    BeamReflectedEvent, BeamStartedEvent, BeamStoppedEvent,
+};
+use fog::VolumetricFog;
+use impact_glow::{update_beam_impact_glows, BeamImpactGlow};
 use render::{LightMaterial, LightRenderData};
 use segments::{
-    cleanup_light_sources, insert_line_lights, simulate_light_sources, tick_light_sources,
-    LightSegmentCache, PrevLightBeamPlayback,
+    assign_light_segments, cleanup_light_sources, insert_line_lights, simulate_light_sources,
+    tick_light_sources, BeamFreeze, LightSegmentCache, PrevLightBeamPlayback,
 };
+use spectral::{update_spectral_occluder_groups, SpectralOccluderBundle};
 
 use crate::level::LevelSystems;
 
+pub mod events;
+pub mod fog;
+pub mod impact_glow;
 mod render;
 pub mod segments;
+pub mod spectral;
 
 /// The speed of the light beam in units per [`FixedUpdate`].
 const LIGHT_SPEED: f32 = 8.0;
@@ -22,6 +34,29 @@ const LIGHT_SPEED: f32 = 8.0;
 /// The width of the rectangle used to represent [`LightSegment`](segments::LightSegmentBundle)s.
 const LIGHT_SEGMENT_THICKNESS: f32 = 3.0;
 
+/// How far apart bevy_ecs_ldtk puts the layers of a level on the z axis, starting from the
+/// level's background at 0.
+const LDTK_LAYER_Z_STEP: f32 = 1.0;
+
+/// More layers than any level has, counting its background and the extra layers bevy_ecs_ldtk
+/// spawns for IntGrid layers with auto tiles.
+const MAX_LDTK_LAYERS: u32 = 32;
+
+/// The z coordinate of [`LightBeamDepth::Foreground`] light segments, in front of every Ldtk layer.
+const FOREGROUND_LIGHT_SEGMENT_Z: f32 = (MAX_LDTK_LAYERS + 1) as f32 * LDTK_LAYER_Z_STEP;
+
+/// How many times a light beam can be bent by [`WaterVolume`](crate::level::water::WaterVolume)s
+/// on top of its bounces, counting each time it enters, leaves or reflects inside of the water.
+const MAX_REFRACTIONS: usize = 6;
+
+/// The most [`BeamBounces`] can be set to for any [`LightColor`], which decides how many segments
+/// are spawned for each beam.
+pub const MAX_BEAM_BOUNCES: usize = 16;
+
+/// The most surfaces a beam can reach: one for each bounce, the one it stops at, and
+/// [`MAX_REFRACTIONS`] more for water bending it along the way.
+pub const MAX_BEAM_INTERSECTIONS: usize = MAX_BEAM_BOUNCES + 1 + MAX_REFRACTIONS;
+
 /// [`Plugin`] that manages everything light related.
 pub struct LightManagementPlugin;
 
@@ -30,13 +65,30 @@ impl Plugin for LightManagementPlugin {
         app.add_plugins(Material2dPlugin::<LightMaterial>::default())
             .init_resource::<LightRenderData>()
             .init_resource::<LightSegmentCache>()
+            .init_resource::<VolumetricFog>()
+            .init_resource::<BeamBounces>()
+            .init_resource::<BeamFreeze>()
+            .add_event::<BeamStartedEvent>()
+            .add_event::<BeamStoppedEvent>()
+            .add_event::<BeamReflectedEvent>()
+            .add_event::<BeamImpactTick>()
             .register_ldtk_entity::<LightSegmentZBundle>("LightSegmentZMarker")
             .register_ldtk_entity::<LightSourceZBundle>("LightSourceZMarker")
+            .register_ldtk_entity::<SpectralOccluderBundle>("SpectralOccluder")
             .add_systems(
                 FixedUpdate,
-                (simulate_light_sources, tick_light_sources).in_set(LevelSystems::Simulation),
+                (
+                    assign_light_segments.before(simulate_light_sources),
+                    simulate_light_sources,
+                    tick_light_sources,
+                    send_beam_impact_ticks.after(simulate_light_sources),
+                    update_beam_impact_glows.after(simulate_light_sources),
+                )
+                    .in_set(LevelSystems::Simulation),
             )
             .add_systems(Startup, insert_line_lights)
+            .add_systems(Update, update_spectral_occluder_groups)
+            .add_systems(PostUpdate, send_beam_lifecycle_events)
             // why does this need to be on update???
             .add_systems(Update, cleanup_light_sources.in_set(LevelSystems::Reset));
     }
@@ -98,15 +150,42 @@ impl From<&String> for LightColor {
     }
 }
 
-impl LightColor {
-    /// The number of bounces off of terrain each [`LightColor`] can make.
-    pub fn num_bounces(&self) -> usize {
-        match self {
-            LightColor::Purple => 2,
-            _ => 1,
+/// [`Resource`] holding the number of bounces off of terrain beams of each [`LightColor`] can
+/// make before they stop on the next surface they reach. Each is capped at [`MAX_BEAM_BOUNCES`].
+/// See the `lighting_config.beam_bounces` section of `Lightborne.toml`.
+#[derive(Resource, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct BeamBounces {
+    pub green: usize,
+    pub purple: usize,
+    pub white: usize,
+    pub blue: usize,
+}
+
+impl Default for BeamBounces {
+    fn default() -> Self {
+        BeamBounces {
+            green: 1,
+            purple: 2,
+            white: 1,
+            blue: 1,
         }
     }
+}
+
+impl BeamBounces {
+    pub fn get(&self, color: LightColor) -> usize {
+        let bounces = match color {
+            LightColor::Green => self.green,
+            LightColor::Purple => self.purple,
+            LightColor::White => self.white,
+            LightColor::Blue => self.blue,
+        };
+        bounces.min(MAX_BEAM_BOUNCES)
+    }
+}
 
+impl LightColor {
     pub fn lighting_color(&self) -> Vec3 {
         match self {
             LightColor::Purple => Vec3::new(0.7, 0.2, 0.8),
@@ -116,6 +195,29 @@ impl LightColor {
         }
     }
 
+    /// The [`LightColor`] whose [`lighting_color`](LightColor::lighting_color) is closest to
+    /// `color`, used to turn the color of an ordinary light into a beam.
+    pub fn closest_to(color: Vec3) -> LightColor {
+        let colors: EnumMap<LightColor, Vec3> = enum_map! {
+            light_color => light_color.lighting_color(),
+        };
+        colors
+            .iter()
+            .min_by(|(_, a), (_, b)| {
+                a.distance_squared(color)
+                    .total_cmp(&b.distance_squared(color))
+            })
+            .map(|(light_color, _)| light_color)
+            .unwrap_or_default()
+    }
+
+    /// How strongly beams of this color push things like
+    /// [`LightSail`](crate::level::light_sail::LightSail)s, the brightness of the light they give
+    /// off.
+    pub fn beam_intensity(&self) -> f32 {
+        self.lighting_color().max_element()
+    }
+
     pub fn light_beam_color(&self) -> Color {
         match self {
             LightColor::Purple => Color::srgb(1.5, 0.5, 3.0),
@@ -143,10 +245,118 @@ impl LightColor {
 /// [`shoot_light`](crate::player::light::shoot_light), and simulated in
 /// [`simulate_light_sources`]
 #[derive(Component)]
-#[require(Transform, Visibility, Sprite, PrevLightBeamPlayback)]
+#[require(
+    Transform,
+    Visibility,
+    Sprite,
+    PrevLightBeamPlayback,
+    BeamImpactTimer,
+    BeamImpactGlow
+)]
 pub struct LightBeamSource {
     pub start_pos: Vec2,
     pub start_dir: Vec2,
     pub time_traveled: f32,
     pub color: LightColor,
+    /// The width of the beam. Beams with a width of 0 are simulated with a ray cast, while wider
+    /// beams sweep a circle of this diameter along their path, so they can hit things that are
+    /// slightly off of their center line.
+    pub width: f32,
+    /// The fraction of the full [`beam_intensity`](LightColor::beam_intensity) of its color the
+    /// beam carries. Beams split off of other beams, like the ones leaving a
+    /// [`Prism`](crate::level::prism::Prism), carry less than 1.
+    pub intensity: f32,
+    /// How many [`WeakPanel`](crate::level::weak_panel::WeakPanel)s the beam can pass through,
+    /// with each panel taking its attenuation off. Beams with no penetration bounce off of panels
+    /// like off of walls.
+    pub penetration: f32,
+    /// Whether the beam is drawn behind or in front of the level's foreground tiles
+    pub depth: LightBeamDepth,
+}
+
+/// Controls where the [`LightSegment`](segments::LightSegmentBundle)s of a [`LightBeamSource`] are
+/// drawn relative to the level.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LightBeamDepth {
+    /// Drawn at the depth of the [`LightSegmentZMarker`] placed in Ldtk, so tiles on layers above
+    /// the marker cover the beam
+    #[default]
+    Background,
+    /// Drawn in front of every tile in the level
+    Foreground,
+}
+
+/// The depth of the beams an Ldtk entity shines, read from its optional `depth` enum field, which
+/// is either `Background` or `Foreground`. Entities without one shine
+/// [`LightBeamDepth::Background`] beams.
+impl From<&EntityInstance> for LightBeamDepth {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        match entity_instance.get_enum_field("depth").map(String::as_str) {
+            Ok("Foreground") => LightBeamDepth::Foreground,
+            Ok("Background") | Err(_) => LightBeamDepth::Background,
+            Ok(depth) => panic!("String {} does not represent a light beam depth", depth),
+        }
+    }
+}
+
+impl LightBeamDepth {
+    /// The z coordinate of a light segment with this depth, given the z coordinate of the
+    /// [`LightSegmentZMarker`].
+    pub fn segment_z(&self, background_z: f32) -> f32 {
+        match self {
+            LightBeamDepth::Background => background_z,
+            LightBeamDepth::Foreground => FOREGROUND_LIGHT_SEGMENT_Z,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_ecs_ldtk::ldtk::{FieldInstance, FieldValue};
+
+    use super::*;
+
+    #[test]
+    fn beam_depth_is_read_from_ldtk() {
+        let with_depth = |depth: &str| EntityInstance {
+            field_instances: vec![FieldInstance {
+                identifier: "depth".into(),
+                tile: None,
+                field_instance_type: "LocalEnum.LightBeamDepth".into(),
+                value: FieldValue::Enum(Some(depth.into())),
+                def_uid: 0,
+                real_editor_values: vec![],
+            }],
+            ..default()
+        };
+        assert_eq!(
+            LightBeamDepth::from(&with_depth("Foreground")),
+            LightBeamDepth::Foreground
+        );
+        assert_eq!(
+            LightBeamDepth::from(&with_depth("Background")),
+            LightBeamDepth::Background
+        );
+        // entities without the field keep their beams behind the tiles
+        assert_eq!(
+            LightBeamDepth::from(&EntityInstance::default()),
+            LightBeamDepth::Background
+        );
+    }
+
+    #[test]
+    fn beam_bounces_are_capped() {
+        let mut bounces = BeamBounces::default();
+        assert_eq!(bounces.get(LightColor::Purple), 2);
+        assert_eq!(bounces.get(LightColor::Green), 1);
+
+        bounces.green = 8;
+        assert_eq!(bounces.get(LightColor::Green), 8);
+        bounces.green = 0;
+        assert_eq!(bounces.get(LightColor::Green), 0);
+        // every surface a beam can reach has a segment
+        bounces.white = 1000;
+        assert_eq!(bounces.get(LightColor::White), MAX_BEAM_BOUNCES);
+        assert!(bounces.get(LightColor::White) < MAX_BEAM_INTERSECTIONS);
+    }
 }
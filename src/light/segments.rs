@@ -1,17 +1,28 @@
-use bevy::prelude::*;
+use bevy::{ecs::system::SystemParam, prelude::*, utils::HashMap};
 use bevy_rapier2d::prelude::*;
 use enum_map::EnumMap;
 
 use super::{
+    events::BeamReflectedEvent,
+    fog::VolumetricFog,
     render::{LightMaterial, LightRenderData},
-    LightBeamSource, LightColor, LightSegmentZMarker, LIGHT_SPEED,
+    BeamBounces, LightBeamSource, LightColor, LightSegmentZMarker, LIGHT_SEGMENT_THICKNESS,
+    LIGHT_SPEED, MAX_BEAM_INTERSECTIONS,
+};
+use crate::{
+    level::{
+        sensor::LightSensor,
+        water::{refract, WaterVolume},
+        weak_panel::WeakPanel,
+    },
+    lighting::{LineLight2d, Occluder2dGroups},
+    shared::GroupLabel,
 };
-use crate::{level::sensor::LightSensor, lighting::LineLight2d, shared::GroupLabel};
 
 /// Marker [`Component`] used to query for light segments.
 #[derive(Default, Component, Clone, Debug)]
 pub struct LightSegment {
-    color: LightColor,
+    pub color: LightColor,
 }
 
 /// [`Bundle`] used in the initialization of the [`LightSegmentCache`] to spawn segment entities.
@@ -25,59 +36,103 @@ pub struct LightSegmentBundle {
 }
 
 /// [`Resource`] used to store [`Entity`] handles to the light segments so they aren't added and
-/// despawned every frame. See [`simulate_light_sources`] for details.
+/// despawned every frame. Every [`LightBeamSource`] draws its own segments, which it is given by
+/// [`assign_light_segments`]. The segments of sources that are gone are hidden and kept for the
+/// next source of the same color.
 #[derive(Resource)]
 pub struct LightSegmentCache {
-    segments: EnumMap<LightColor, Vec<Entity>>,
+    /// Sets of [`MAX_BEAM_INTERSECTIONS`] hidden segments that no source is drawing
+    free: EnumMap<LightColor, Vec<Vec<Entity>>>,
+    /// The color and segments of each source
+    owned: HashMap<Entity, (LightColor, Vec<Entity>)>,
+}
+
+impl LightSegmentCache {
+    /// The segments drawing the beam of `source`, if it has been given any yet.
+    pub fn segments(&self, source: Entity) -> Option<&[Entity]> {
+        self.owned
+            .get(&source)
+            .map(|(_, segments)| segments.as_slice())
+    }
 }
 
 impl FromWorld for LightSegmentCache {
     fn from_world(world: &mut World) -> Self {
         let mut cache = LightSegmentCache {
-            segments: EnumMap::default(),
+            free: EnumMap::default(),
+            owned: HashMap::default(),
         };
         let render_data = world.resource::<LightRenderData>();
 
         let mut segment_bundles: EnumMap<LightColor, LightSegmentBundle> = EnumMap::default();
 
-        for (color, _) in cache.segments.iter_mut() {
-            segment_bundles[color] = LightSegmentBundle {
-                segment: LightSegment { color },
-                mesh: render_data.mesh.clone(),
-                material: render_data.material_map[color].clone(),
-                visibility: Visibility::Hidden,
-                transform: Transform::default(),
-            }
+        for (color, _) in cache.free.iter_mut() {
+            segment_bundles[color] = light_segment_bundle(color, render_data);
         }
 
-        for (color, segments) in cache.segments.iter_mut() {
-            while segments.len() < color.num_bounces() + 1 {
+        // one set of segments for each color up front, more are spawned when there are more beams
+        for (color, free) in cache.free.iter_mut() {
+            let mut segments = vec![];
+            while segments.len() < MAX_BEAM_INTERSECTIONS {
                 let mut cmds = world.spawn(());
                 cmds.insert(segment_bundles[color].clone());
 
                 // White beams need colliders
                 if color == LightColor::White {
-                    cmds.insert((
-                        Collider::cuboid(0.5, 0.5),
-                        Sensor,
-                        CollisionGroups::new(
-                            GroupLabel::WHITE_RAY,
-                            GroupLabel::TERRAIN
-                                | GroupLabel::LIGHT_SENSOR
-                                | GroupLabel::LIGHT_RAY
-                                | GroupLabel::BLUE_RAY,
-                        ),
-                    ));
+                    cmds.insert(white_segment_collider());
                 }
 
                 segments.push(cmds.id());
             }
+            free.push(segments);
         }
 
         cache
     }
 }
 
+fn light_segment_bundle(color: LightColor, render_data: &LightRenderData) -> LightSegmentBundle {
+    LightSegmentBundle {
+        segment: LightSegment { color },
+        mesh: render_data.mesh.clone(),
+        material: render_data.material_map[color].clone(),
+        visibility: Visibility::Hidden,
+        transform: Transform::default(),
+    }
+}
+
+/// The collider of white segments, which other beams reflect off of.
+fn white_segment_collider() -> (Collider, Sensor, CollisionGroups) {
+    (
+        Collider::cuboid(0.5, 0.5),
+        Sensor,
+        CollisionGroups::new(
+            GroupLabel::WHITE_RAY,
+            GroupLabel::TERRAIN
+                | GroupLabel::LIGHT_SENSOR
+                | GroupLabel::LIGHT_RAY
+                | GroupLabel::BLUE_RAY,
+        ),
+    )
+}
+
+/// The light given off by a segment of `color`.
+fn light_segment_line_light(color: LightColor) -> (LineLight2d, Occluder2dGroups) {
+    (
+        LineLight2d {
+            color: color.lighting_color().extend(1.0),
+            half_length: 10.0,
+            radius: 20.0,
+            inner_radius: 0.0,
+            volumetric_intensity: 0.008,
+            temperature_k: None,
+            shadow_tint: Vec4::ZERO,
+            falloff_texture: None,
+        },
+        color.light_groups(),
+    )
+}
+
 /// System to insert line lights into segments. This insertion cannot be done in FromWorld
 /// because doing so inserts the archetype in the world before the required components are
 /// registered for it. This is currently not allowed by Bevy, and therefore the light segments
@@ -87,12 +142,69 @@ pub fn insert_line_lights(
     q_new_segments: Query<(Entity, &LightSegment), Added<LightSegment>>,
 ) {
     for (entity, segment) in q_new_segments.iter() {
-        commands.entity(entity).insert(LineLight2d {
-            color: segment.color.lighting_color().extend(1.0),
-            half_length: 10.0,
-            radius: 20.0,
-            volumetric_intensity: 0.008,
+        commands
+            .entity(entity)
+            .insert(light_segment_line_light(segment.color));
+    }
+}
+
+/// Hides `segments`, moving them out of the way of other beams.
+fn hide_segments(
+    segments: &[Entity],
+    q_segments: &mut Query<(&mut Transform, &mut Visibility), With<LightSegment>>,
+) {
+    for &entity in segments {
+        let Ok((mut transform, mut visibility)) = q_segments.get_mut(entity) else {
+            continue;
+        };
+        // required for white beam
+        *transform = Transform::default();
+        *visibility = Visibility::Hidden;
+    }
+}
+
+/// [`System`] that gives every new [`LightBeamSource`] its own segments from the
+/// [`LightSegmentCache`], spawning more when none of its color are left over, and hides the
+/// segments of sources that are gone so later beams can reuse them.
+pub fn assign_light_segments(
+    mut commands: Commands,
+    q_light_sources: Query<(Entity, &LightBeamSource)>,
+    mut q_segments: Query<(&mut Transform, &mut Visibility), With<LightSegment>>,
+    mut segment_cache: ResMut<LightSegmentCache>,
+    render_data: Res<LightRenderData>,
+) {
+    let LightSegmentCache { free, owned } = &mut *segment_cache;
+    owned.retain(|source, (color, segments)| {
+        let kept = q_light_sources
+            .get(*source)
+            .is_ok_and(|(_, source)| source.color == *color);
+        if !kept {
+            hide_segments(segments, &mut q_segments);
+            free[*color].push(std::mem::take(segments));
+        }
+        kept
+    });
+
+    for (entity, source) in q_light_sources.iter() {
+        if owned.contains_key(&entity) {
+            continue;
+        }
+        let color = source.color;
+        let segments = free[color].pop().unwrap_or_else(|| {
+            (0..MAX_BEAM_INTERSECTIONS)
+                .map(|_| {
+                    let mut segment = commands.spawn((
+                        light_segment_bundle(color, &render_data),
+                        light_segment_line_light(color),
+                    ));
+                    if color == LightColor::White {
+                        segment.insert(white_segment_collider());
+                    }
+                    segment.id()
+                })
+                .collect()
         });
+        owned.insert(entity, (color, segments));
     }
 }
 
@@ -125,6 +237,9 @@ pub struct LightBeamIntersection {
     pub entity: Entity,
     pub point: Vec2,
     pub time: f32,
+    /// Whether the beam was bent by a [`WaterVolume`] here instead of bouncing, which doesn't use
+    /// up one of its bounces
+    pub refracted: bool,
 }
 
 /// Stores information about the trajectory of a LightBeam
@@ -133,9 +248,52 @@ pub struct LightBeamPlayback {
     pub intersections: Vec<LightBeamIntersection>,
     pub end_point: Option<Vec2>,
     pub elapsed_time: f32,
+    /// The pass-through [`LightSensor`]s the beam went through on its way, in the order it reached
+    /// them. They don't bend or stop the beam, so they aren't part of its `intersections`, see
+    /// [`is_pass_through_sensor`](crate::level::sensor::is_pass_through_sensor).
+    pub passed_sensors: Vec<LightBeamIntersection>,
+    /// The [`WeakPanel`]s the beam punched through on its way, in the order it reached them
+    pub penetrations: Vec<BeamPenetration>,
+}
+
+/// The fraction of a beam's intensity left `time` along it, after the `penetrations` before that.
+fn penetrated_intensity(penetrations: &[BeamPenetration], time: f32) -> f32 {
+    penetrations
+        .iter()
+        .take_while(|penetration| penetration.time <= time)
+        .last()
+        .map_or(1.0, |penetration| penetration.intensity)
+}
+
+/// A [`WeakPanel`] a beam passed through, see [`LightBeamPlayback::penetrations`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BeamPenetration {
+    pub entity: Entity,
+    pub time: f32,
+    /// The fraction of the beam's intensity left past the panel
+    pub intensity: f32,
 }
 
 impl LightBeamPlayback {
+    /// The fraction of the beam's intensity left `time` along the beam, after the [`WeakPanel`]s
+    /// it passed through before that. Doesn't include the fog, see
+    /// [`VolumetricFog::attenuation`].
+    pub fn intensity_at(&self, time: f32) -> f32 {
+        penetrated_intensity(&self.penetrations, time)
+    }
+
+    /// Cuts the beam off after its first `segments` segments, as if it stopped at the surface the
+    /// last of them hit. Beams with fewer segments are left alone.
+    pub fn truncate_segments(&mut self, segments: usize) {
+        if segments <= self.intersections.len() {
+            self.intersections.truncate(segments);
+            let end_time = self.intersections.last().map_or(0.0, |x| x.time);
+            self.passed_sensors.retain(|pass| pass.time <= end_time);
+            self.penetrations.retain(|pass| pass.time <= end_time);
+            self.end_point = None;
+        }
+    }
+
     pub fn iter_points<'a>(
         &'a self,
         source: &'a LightBeamSource,
@@ -150,93 +308,394 @@ impl LightBeamPlayback {
     }
 }
 
-#[derive(Default, Debug, Component)]
+/// [`Resource`] that freezes every light beam at a number of segments when set, so complicated
+/// mirror setups can be stepped through one bounce at a time. Beams keep traveling while frozen,
+/// they just aren't drawn past the frozen segment.
+#[derive(Resource, Default, Debug)]
+pub struct BeamFreeze(pub Option<usize>);
+
+#[derive(Debug, Component)]
 pub struct PrevLightBeamPlayback {
     pub intersections: Vec<Option<LightBeamIntersection>>,
+    /// The pass-through [`LightSensor`]s the beam went through, see
+    /// [`LightBeamPlayback::passed_sensors`]
+    pub passed_sensors: Vec<Entity>,
+    /// The [`WeakPanel`]s the beam punched through, see [`LightBeamPlayback::penetrations`]
+    pub penetrations: Vec<BeamPenetration>,
 }
 
-impl PrevLightBeamPlayback {
-    pub fn from_color(color: LightColor) -> Self {
+impl Default for PrevLightBeamPlayback {
+    fn default() -> Self {
         PrevLightBeamPlayback {
-            intersections: vec![None; color.num_bounces() + 1],
+            intersections: vec![None; MAX_BEAM_INTERSECTIONS],
+            passed_sensors: vec![],
+            penetrations: vec![],
         }
     }
 }
 
+impl PrevLightBeamPlayback {
+    /// The fraction of the full [`beam_intensity`](LightColor::beam_intensity) of its color that
+    /// the beam of `source` carries `time` along it, after the [`WeakPanel`]s it passed through and
+    /// the fog. Everything lit or pushed by beams goes through this, so a beam is equally strong
+    /// for all of them.
+    pub fn received_intensity(
+        &self,
+        source: &LightBeamSource,
+        time: f32,
+        fog: &VolumetricFog,
+    ) -> f32 {
+        source.intensity * penetrated_intensity(&self.penetrations, time) * fog.attenuation(time)
+    }
+}
+
+/// [`SystemParam`] for the things light beams can travel through instead of bouncing off of, see
+/// [`play_light_beam`].
+#[derive(SystemParam)]
+pub struct BeamMedia<'w, 's> {
+    weak_panels: Query<'w, 's, &'static WeakPanel>,
+    water_volumes: Query<'w, 's, (&'static WaterVolume, &'static GlobalTransform)>,
+}
+
+/// Plays out the path of a beam from `source`, which stops on the surface it reaches after
+/// `max_bounces` bounces. Beams pass straight through the [`WeakPanel`]s their penetration lets
+/// them through, losing some of their intensity to each, and are bent by the [`WaterVolume`]s
+/// they pass through, without either counting as a bounce. The pass-through [`LightSensor`]s
+/// along the way are recorded in [`passed_sensors`](LightBeamPlayback::passed_sensors).
 pub fn play_light_beam(
     rapier_context: &mut RapierContext,
     source: &LightBeamSource,
+    max_bounces: usize,
+    fog: &VolumetricFog,
+    media: &BeamMedia,
 ) -> LightBeamPlayback {
     let mut ray_pos = source.start_pos;
     let mut ray_dir = source.start_dir;
-    let collision_groups = match source.color {
-        LightColor::White => CollisionGroups::new(
-            GroupLabel::WHITE_RAY,
-            GroupLabel::TERRAIN | GroupLabel::LIGHT_SENSOR,
-        ),
-        LightColor::Blue => CollisionGroups::new(
-            GroupLabel::BLUE_RAY,
-            GroupLabel::TERRAIN | GroupLabel::LIGHT_SENSOR | GroupLabel::WHITE_RAY,
-        ),
-        _ => CollisionGroups::new(
-            GroupLabel::LIGHT_RAY,
-            GroupLabel::TERRAIN | GroupLabel::LIGHT_SENSOR | GroupLabel::WHITE_RAY,
-        ),
-    };
-
-    let mut ray_qry = QueryFilter::new().groups(collision_groups);
-    let mut remaining_time = source.time_traveled;
+    let mut ray_qry = QueryFilter::new().groups(beam_collision_groups(source.color));
+    let source_intensity = source.color.beam_intensity() * source.intensity;
+    // beams that fade out in the fog stop traveling
+    let mut remaining_time = source.time_traveled.min(fog.reach(source_intensity));
 
     let mut playback = LightBeamPlayback {
         intersections: vec![],
         end_point: None,
         elapsed_time: 0.0,
+        passed_sensors: vec![],
+        penetrations: vec![],
     };
+    let mut penetration = source.penetration;
+    let mut intensity = 1.0;
+    let mut bounces = 0;
 
-    for _ in 0..source.color.num_bounces() + 1 {
-        let Some((entity, intersection)) =
-            rapier_context.cast_ray_and_get_normal(ray_pos, ray_dir, remaining_time, true, ray_qry)
-        else {
+    while bounces <= max_bounces && playback.intersections.len() < MAX_BEAM_INTERSECTIONS {
+        let mut hit = cast_light_beam(
+            rapier_context,
+            ray_pos,
+            ray_dir,
+            remaining_time,
+            source.width,
+            ray_qry,
+        );
+        while let Some(panel_hit) = &hit {
+            let Some((panel, remaining)) = media
+                .weak_panels
+                .get(panel_hit.entity)
+                .ok()
+                .and_then(|panel| Some((panel, panel.penetrate(penetration)?)))
+            else {
+                break;
+            };
+            penetration = remaining;
+            intensity *= panel.transmittance();
+            cast_pass_through_sensors(
+                rapier_context,
+                source.color,
+                ray_pos,
+                ray_dir,
+                panel_hit.time_of_impact,
+                &mut playback,
+            );
+            playback.elapsed_time += panel_hit.time_of_impact;
+            playback.penetrations.push(BeamPenetration {
+                entity: panel_hit.entity,
+                time: playback.elapsed_time,
+                intensity,
+            });
+            // the dimmer beam fades out sooner in the fog
+            remaining_time = (remaining_time - panel_hit.time_of_impact)
+                .min(fog.reach(source_intensity * intensity) - playback.elapsed_time)
+                .max(0.0);
+            ray_pos = panel_hit.point;
+            ray_qry = ray_qry.exclude_collider(panel_hit.entity);
+            hit = cast_light_beam(
+                rapier_context,
+                ray_pos,
+                ray_dir,
+                remaining_time,
+                source.width,
+                ray_qry,
+            );
+        }
+        let Some(hit) = hit else {
+            cast_pass_through_sensors(
+                rapier_context,
+                source.color,
+                ray_pos,
+                ray_dir,
+                remaining_time,
+                &mut playback,
+            );
             let final_point = ray_pos + ray_dir * remaining_time;
             playback.elapsed_time += remaining_time;
             playback.end_point = Some(final_point);
             break;
         };
 
-        if intersection.time_of_impact < 0.01 {
+        if hit.time_of_impact < 0.01 {
             break;
         }
 
-        playback.elapsed_time += intersection.time_of_impact;
-        remaining_time -= intersection.time_of_impact;
+        cast_pass_through_sensors(
+            rapier_context,
+            source.color,
+            ray_pos,
+            ray_dir,
+            hit.time_of_impact,
+            &mut playback,
+        );
+        playback.elapsed_time += hit.time_of_impact;
+        remaining_time -= hit.time_of_impact;
 
+        // beams that can't get into the water reflect off of it like off of a wall
+        let water = media
+            .water_volumes
+            .get(hit.entity)
+            .ok()
+            .and_then(|(water, transform)| {
+                let inside_dir = refract(ray_dir, hit.normal, 1.0 / water.refraction)?;
+                Some((water, transform.translation().xy(), inside_dir))
+            });
         playback.intersections.push(LightBeamIntersection {
-            entity,
-            point: intersection.point,
+            entity: hit.entity,
+            point: hit.point,
             time: playback.elapsed_time,
+            refracted: water.is_some(),
         });
+        ray_pos = hit.point;
+        ray_qry = ray_qry.exclude_collider(hit.entity);
 
-        ray_pos = intersection.point;
-        ray_dir = ray_dir.reflect(intersection.normal);
-        ray_qry = ray_qry.exclude_collider(entity);
+        let Some((water, center, inside_dir)) = water else {
+            ray_dir = ray_dir.reflect(hit.normal);
+            bounces += 1;
+            continue;
+        };
+
+        let max_points = MAX_BEAM_INTERSECTIONS - playback.intersections.len();
+        let path = water.trace(center, ray_pos, inside_dir, max_points);
+        for point in path.points {
+            let distance = ray_pos.distance(point);
+            let dir = (point - ray_pos).normalize_or(inside_dir);
+            if distance > remaining_time {
+                cast_pass_through_sensors(
+                    rapier_context,
+                    source.color,
+                    ray_pos,
+                    dir,
+                    remaining_time,
+                    &mut playback,
+                );
+                playback.elapsed_time += remaining_time;
+                playback.end_point = Some(ray_pos + dir * remaining_time);
+                return playback;
+            }
+            cast_pass_through_sensors(
+                rapier_context,
+                source.color,
+                ray_pos,
+                dir,
+                distance,
+                &mut playback,
+            );
+            playback.elapsed_time += distance;
+            remaining_time -= distance;
+            playback.intersections.push(LightBeamIntersection {
+                entity: hit.entity,
+                point,
+                time: playback.elapsed_time,
+                refracted: true,
+            });
+            ray_pos = point;
+        }
+        // beams still reflecting inside of the water when they run out of intersections stop
+        let Some(exit_dir) = path.exit_dir else {
+            break;
+        };
+        ray_dir = exit_dir;
     }
 
     playback
 }
 
+/// Records the pass-through [`LightSensor`]s crossed by a straight section of a beam of `color`
+/// that starts at `ray_pos` and goes `max_time` along `ray_dir`, after the beam has traveled the
+/// `elapsed_time` of the `playback`. Only the center line of the beam counts.
+fn cast_pass_through_sensors(
+    rapier_context: &RapierContext,
+    color: LightColor,
+    ray_pos: Vec2,
+    ray_dir: Vec2,
+    max_time: f32,
+    playback: &mut LightBeamPlayback,
+) {
+    let groups = CollisionGroups::new(
+        beam_collision_groups(color).memberships,
+        GroupLabel::PASS_THROUGH_SENSOR,
+    );
+    let mut passed = vec![];
+    rapier_context.intersections_with_ray(
+        ray_pos,
+        ray_dir,
+        max_time,
+        true,
+        QueryFilter::new().groups(groups),
+        |entity, intersection| {
+            passed.push(LightBeamIntersection {
+                entity,
+                point: intersection.point,
+                time: playback.elapsed_time + intersection.time_of_impact,
+                refracted: false,
+            });
+            true
+        },
+    );
+    passed.sort_by(|a, b| a.time.total_cmp(&b.time));
+    for pass in passed {
+        // sections start on the surface the last one ended on, which can be inside of a sensor
+        if !playback
+            .passed_sensors
+            .iter()
+            .any(|x| x.entity == pass.entity)
+        {
+            playback.passed_sensors.push(pass);
+        }
+    }
+}
+
+/// The [`CollisionGroups`] of beams of `color`, which decide what stops them. Beams are only
+/// stopped by the [`SpectralOccluder`](super::spectral::SpectralOccluder)s of their own color.
+pub fn beam_collision_groups(color: LightColor) -> CollisionGroups {
+    let groups = match color {
+        LightColor::White => CollisionGroups::new(
+            GroupLabel::WHITE_RAY,
+            GroupLabel::TERRAIN | GroupLabel::BEAM_TERRAIN | GroupLabel::LIGHT_SENSOR,
+        ),
+        LightColor::Blue => CollisionGroups::new(
+            GroupLabel::BLUE_RAY,
+            GroupLabel::TERRAIN
+                | GroupLabel::BEAM_TERRAIN
+                | GroupLabel::LIGHT_SENSOR
+                | GroupLabel::WHITE_RAY,
+        ),
+        _ => CollisionGroups::new(
+            GroupLabel::LIGHT_RAY,
+            GroupLabel::TERRAIN
+                | GroupLabel::BEAM_TERRAIN
+                | GroupLabel::LIGHT_SENSOR
+                | GroupLabel::WHITE_RAY,
+        ),
+    };
+    CollisionGroups::new(
+        groups.memberships,
+        groups.filters | color.spectral_collision_group(),
+    )
+}
+
+/// The result of [`cast_light_beam`].
+#[derive(Debug, PartialEq)]
+pub struct LightBeamHit {
+    pub entity: Entity,
+    /// The point on the center line of the beam where the hit happened
+    pub point: Vec2,
+    /// How far along the beam the hit happened
+    pub time_of_impact: f32,
+    pub normal: Vec2,
+}
+
+/// Casts a single straight section of a light beam. Thin beams (`width` of 0) use a ray cast,
+/// and wider beams cast a ball with a diameter of `width`, so their edges can clip colliders that
+/// the center line would miss.
+pub fn cast_light_beam(
+    rapier_context: &RapierContext,
+    ray_pos: Vec2,
+    ray_dir: Vec2,
+    max_time: f32,
+    width: f32,
+    ray_qry: QueryFilter,
+) -> Option<LightBeamHit> {
+    if width <= 0.0 {
+        let (entity, intersection) =
+            rapier_context.cast_ray_and_get_normal(ray_pos, ray_dir, max_time, true, ray_qry)?;
+        return Some(LightBeamHit {
+            entity,
+            point: intersection.point,
+            time_of_impact: intersection.time_of_impact,
+            normal: intersection.normal,
+        });
+    }
+
+    let (entity, hit) = rapier_context.cast_shape(
+        ray_pos,
+        0.0,
+        ray_dir,
+        &Collider::ball(width / 2.0),
+        ShapeCastOptions {
+            max_time_of_impact: max_time,
+            target_distance: 0.0,
+            // beams start on the surface they just bounced off of, so ignore shapes that the ball
+            // is already overlapping and moving away from
+            stop_at_penetration: false,
+            compute_impact_geometry_on_penetration: false,
+        },
+        ray_qry,
+    )?;
+    // a beam that starts inside of a shape and moves further in is stopped right away, facing
+    // back the way it came
+    let normal = hit.details.map_or(-ray_dir, |details| details.normal1);
+
+    Some(LightBeamHit {
+        entity,
+        point: ray_pos + ray_dir * hit.time_of_impact,
+        time_of_impact: hit.time_of_impact,
+        normal,
+    })
+}
+
+/// The order [`simulate_light_sources`] plays beams in, by [`Entity`], which is the order their
+/// [`BeamReflectedEvent`]s are sent and bounce sounds are played in. Query order changes whenever
+/// entities move between archetypes, so sorting keeps it the same every frame for the same scene.
+pub fn beam_evaluation_order(sources: impl IntoIterator<Item = Entity>) -> Vec<Entity> {
+    let mut order: Vec<Entity> = sources.into_iter().collect();
+    order.sort_unstable();
+    order
+}
+
 /// [`System`] that runs on [`Update`], calculating the [`Transform`] of light segments from the
 /// corresponding [`LightBeamSource`]. Note that this calculation happens every frame, so instead of
 /// rapidly spawning/despawning the entities, we spawn them and cache them in the
 /// [`LightSegmentCache`], then modify their [`Visibility`] and [`Transform`]s.
 ///
+/// Beams are played one at a time in [`beam_evaluation_order`], each following its reflections in
+/// the order it reaches them and drawing its own segments. Once every beam is played, each
+/// [`LightSensor`] is hit by the colors of all of the beams that end on or pass through it.
+///
 /// If needed, optimization work can be done by recalculating only segments that are currently
 /// changing (segments already "stabilized" usually won't move).
 #[allow(clippy::too_many_arguments)]
 pub fn simulate_light_sources(
     mut commands: Commands,
-    mut q_light_sources: Query<(&mut LightBeamSource, &mut PrevLightBeamPlayback)>,
+    mut q_light_sources: Query<(Entity, &mut LightBeamSource, &mut PrevLightBeamPlayback)>,
     mut q_rapier: Query<&mut RapierContext>,
-    mut q_light_sensor: Query<&mut LightSensor>,
+    mut q_light_sensor: Query<(Entity, &mut LightSensor)>,
     mut q_segments: Query<
         (
             &mut Transform,
@@ -248,7 +707,12 @@ pub fn simulate_light_sources(
     >,
     q_light_segment_z: Query<&Transform, With<LightSegmentZMarker>>,
     segment_cache: Res<LightSegmentCache>,
+    mut ev_beam_reflected: EventWriter<BeamReflectedEvent>,
     light_bounce_sfx: Local<LightBounceSfx>,
+    fog: Res<VolumetricFog>,
+    beam_freeze: Res<BeamFreeze>,
+    beam_bounces: Res<BeamBounces>,
+    media: BeamMedia,
 ) {
     let Ok(rapier_context) = q_rapier.get_single_mut() else {
         return;
@@ -259,12 +723,18 @@ pub fn simulate_light_sources(
     // Reborrow!!!
     let rapier_context = rapier_context.into_inner();
 
-    for (mut source, mut prev_playback) in q_light_sources.iter_mut() {
-        let playback = play_light_beam(rapier_context, &source);
+    for source_entity in beam_evaluation_order(q_light_sources.iter().map(|(entity, ..)| entity)) {
+        let Ok((_, mut source, mut prev_playback)) = q_light_sources.get_mut(source_entity) else {
+            continue;
+        };
+        let max_bounces = beam_bounces.get(source.color);
+        let mut playback = play_light_beam(rapier_context, &source, max_bounces, &fog, &media);
+        if let Some(segments) = beam_freeze.0 {
+            playback.truncate_segments(segments);
+        }
 
         let mut pts: Vec<Vec2> = playback.iter_points(&source).collect();
 
-        let max_intersections = source.color.num_bounces() + 1;
         let intersections = playback.intersections.len();
         for i in 0..intersections {
             let prev_x = prev_playback.intersections[i];
@@ -286,20 +756,20 @@ pub fn simulate_light_sources(
                 // handle remove before add because it could be the case that both are true
                 if remove_intersection {
                     pts[i + 1] = prev_x.unwrap().point;
-                    if let Ok(mut sensor) = q_light_sensor.get_mut(prev_x.unwrap().entity) {
-                        sensor.hit_by[source.color] = false;
-                    }
                     prev_playback.intersections[i] = None;
                     source.time_traveled = prev_x.unwrap().time;
                 }
 
                 if add_intersection {
                     pts[i + 1] = new_x.point;
-                    if let Ok(mut sensor) = q_light_sensor.get_mut(new_x.entity) {
-                        sensor.hit_by[source.color] = true;
-                    }
                     prev_playback.intersections[i] = Some(new_x);
                     source.time_traveled = new_x.time;
+                    ev_beam_reflected.send(BeamReflectedEvent {
+                        source: source_entity,
+                        color: source.color,
+                        entity: new_x.entity,
+                        point: new_x.point,
+                    });
                 }
 
                 if play_sound {
@@ -308,10 +778,12 @@ pub fn simulate_light_sources(
                         _ => false,
                     };
 
+                    // beams bent by water can reach more surfaces than there are sounds
+                    let sfx = i.min(light_bounce_sfx.bounce.len() - 1);
                     let audio = if reflect {
-                        light_bounce_sfx.reflect[i].clone()
+                        light_bounce_sfx.reflect[sfx].clone()
                     } else {
-                        light_bounce_sfx.bounce[i].clone()
+                        light_bounce_sfx.bounce[sfx].clone()
                     };
 
                     commands
@@ -320,15 +792,7 @@ pub fn simulate_light_sources(
                 }
 
                 // discard and update all future intersections
-                for intersection in prev_playback.intersections[i + 1..max_intersections].iter_mut()
-                {
-                    if let Some(intersection) = intersection {
-                        if let Ok(mut sensor) = q_light_sensor.get_mut(intersection.entity) {
-                            sensor.hit_by[source.color] = false;
-                        }
-                    }
-                    *intersection = None;
-                }
+                prev_playback.intersections[i + 1..].fill(None);
                 break;
             } else {
                 // keep on updating the previous intersection buffer because this could be a moving
@@ -336,8 +800,24 @@ pub fn simulate_light_sources(
                 prev_playback.intersections[i] = Some(new_x);
             }
         }
+        // beams that were turned away no longer reach the surfaces past their last intersection
+        prev_playback.intersections[intersections..].fill(None);
 
-        for (i, segment) in segment_cache.segments[source.color].iter().enumerate() {
+        // pass-through sensors stay hit for as long as the beam goes through them
+        prev_playback.passed_sensors.clear();
+        prev_playback
+            .passed_sensors
+            .extend(playback.passed_sensors.iter().map(|pass| pass.entity));
+        prev_playback
+            .penetrations
+            .clone_from(&playback.penetrations);
+
+        let Some(segments) = segment_cache.segments(source_entity) else {
+            continue;
+        };
+        // distance along the beam to the start of the current segment
+        let mut distance = 0.0;
+        for (i, segment) in segments.iter().enumerate() {
             let Ok((mut c_transform, mut c_visibility, mut line_light, _)) =
                 q_segments.get_mut(*segment)
             else {
@@ -346,7 +826,9 @@ pub fn simulate_light_sources(
 
             if i + 1 < pts.len() && pts[i].distance(pts[i + 1]) > 0.1 {
                 let midpoint = pts[i].midpoint(pts[i + 1]).extend(1.0);
-                let scale = Vec3::new(pts[i].distance(pts[i + 1]), 1., 1.);
+                // beams thinner than the segment mesh are drawn at the mesh's thickness
+                let thickness = (source.width / LIGHT_SEGMENT_THICKNESS).max(1.0);
+                let scale = Vec3::new(pts[i].distance(pts[i + 1]), thickness, 1.);
                 let rotation = (pts[i + 1] - pts[i]).to_angle();
 
                 let transform = Transform::from_translation(midpoint)
@@ -354,6 +836,14 @@ pub fn simulate_light_sources(
                     .with_rotation(Quat::from_rotation_z(rotation));
 
                 line_light.half_length = scale.x / 2.0;
+                // segments are dimmed by the fog and the panels between the source and their
+                // midpoint
+                let midpoint_distance = distance + line_light.half_length;
+                let attenuation =
+                    fog.attenuation(midpoint_distance) * playback.intensity_at(midpoint_distance);
+                line_light.color =
+                    (source.color.lighting_color() * source.intensity * attenuation).extend(1.0);
+                distance += scale.x;
                 *c_transform = transform;
                 *c_visibility = Visibility::Visible;
             } else {
@@ -364,8 +854,29 @@ pub fn simulate_light_sources(
             }
 
             // match the z index with the dummy entity spawned by ldtk to match the stupid ldtk
-            // plugin's arbitrary z-indexing order
-            c_transform.translation.z = light_segment_z.translation.z;
+            // plugin's arbitrary z-indexing order, unless the beam should be drawn in front
+            c_transform.translation.z = source.depth.segment_z(light_segment_z.translation.z);
+        }
+    }
+
+    // a sensor stays hit as long as any beam of a color hits it, so beams of the same color can't
+    // turn off each other's sensors
+    let mut hits: HashMap<Entity, EnumMap<LightColor, bool>> = HashMap::default();
+    for (_, source, prev_playback) in q_light_sources.iter() {
+        let hit_entities = prev_playback
+            .intersections
+            .iter()
+            .flatten()
+            .map(|intersection| intersection.entity)
+            .chain(prev_playback.passed_sensors.iter().copied());
+        for entity in hit_entities {
+            hits.entry(entity).or_default()[source.color] = true;
+        }
+    }
+    for (entity, mut sensor) in q_light_sensor.iter_mut() {
+        let hit_by = hits.get(&entity).copied().unwrap_or_default();
+        if sensor.hit_by != hit_by {
+            sensor.hit_by = hit_by;
         }
     }
 }
@@ -382,7 +893,7 @@ pub fn tick_light_sources(mut q_light_sources: Query<&mut LightBeamSource>) {
 pub fn cleanup_light_sources(
     mut commands: Commands,
     q_light_sources: Query<Entity, With<LightBeamSource>>,
-    segment_cache: Res<LightSegmentCache>,
+    mut segment_cache: ResMut<LightSegmentCache>,
     mut q_segments: Query<(&mut Transform, &mut Visibility), With<LightSegment>>,
 ) {
     // FIXME: should make these entities children of the level so that they are despawned
@@ -392,15 +903,536 @@ pub fn cleanup_light_sources(
         commands.entity(entity).despawn_recursive();
     }
 
-    segment_cache.segments.iter().for_each(|(_, items)| {
-        for &entity in items.iter() {
-            let (mut transform, mut visibility) = q_segments
-                .get_mut(entity)
-                .expect("Segment should have visibility");
+    let LightSegmentCache { free, owned } = &mut *segment_cache;
+    for (_, (color, segments)) in owned.drain() {
+        free[color].push(segments);
+    }
+    for (_, sets) in free.iter() {
+        for segments in sets {
+            hide_segments(segments, &mut q_segments);
+        }
+    }
+}
+
+/// An [`App`] that plays the [`LightBeamSource`]s spawned in it with [`simulate_light_sources`],
+/// for tests of the things beams interact with. The beams are cast in the [`RapierContext`] that
+/// the test spawns in it.
+#[cfg(test)]
+pub fn beam_simulation_app() -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+        .init_asset::<Mesh>()
+        .init_asset::<LightMaterial>()
+        .init_asset::<AudioSource>()
+        .init_resource::<LightRenderData>()
+        .init_resource::<LightSegmentCache>()
+        .init_resource::<VolumetricFog>()
+        .init_resource::<BeamFreeze>()
+        .init_resource::<BeamBounces>()
+        .add_event::<BeamReflectedEvent>()
+        .add_systems(Startup, insert_line_lights)
+        .add_systems(
+            Update,
+            (assign_light_segments, simulate_light_sources).chain(),
+        );
+    app.world_mut()
+        .spawn((LightSegmentZMarker, Transform::default()));
+    app
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::SystemState;
+    use bevy_rapier2d::rapier::{
+        geometry::{ColliderBuilder, InteractionGroups},
+        na::vector,
+    };
 
-            // required for white beam
-            *transform = Transform::default();
-            *visibility = Visibility::Hidden;
+    use crate::{
+        level::crystal::CrystalIdent,
+        light::{LightBeamDepth, FOREGROUND_LIGHT_SEGMENT_Z},
+    };
+
+    use super::*;
+
+    /// A [`RapierContext`] with a 10x40 wall centered at (50, 0).
+    fn wall_context(wall: Entity) -> RapierContext {
+        let mut rapier_context = RapierContext::default();
+        rapier_context.colliders.insert(
+            ColliderBuilder::cuboid(5.0, 20.0)
+                .translation(vector![50.0, 0.0])
+                .user_data(wall.to_bits() as u128)
+                .build(),
+        );
+        rapier_context
+            .query_pipeline
+            .update(&rapier_context.colliders);
+        rapier_context
+    }
+
+    /// A [`RapierContext`] with a 10x40 wall centered at (50, 0), and pass-through sensors
+    /// centered at `sensors`.
+    fn sensor_context(wall: Entity, sensors: &[(Entity, Vec2)]) -> RapierContext {
+        let mut rapier_context = wall_context(wall);
+        for (sensor, pos) in sensors {
+            rapier_context.colliders.insert(
+                ColliderBuilder::cuboid(4.0, 4.0)
+                    .translation(vector![pos.x, pos.y])
+                    .sensor(true)
+                    .collision_groups(InteractionGroups::new(
+                        GroupLabel::PASS_THROUGH_SENSOR,
+                        GroupLabel::LIGHT_RAY | GroupLabel::WHITE_RAY | GroupLabel::BLUE_RAY,
+                    ))
+                    .user_data(sensor.to_bits() as u128)
+                    .build(),
+            );
         }
-    });
+        rapier_context
+            .query_pipeline
+            .update(&rapier_context.colliders);
+        rapier_context
+    }
+
+    #[test]
+    fn frozen_beams_stop_at_their_last_segment() {
+        let intersection = |x| LightBeamIntersection {
+            entity: Entity::PLACEHOLDER,
+            point: Vec2::new(x, 0.0),
+            time: x,
+            refracted: false,
+        };
+        let playback = || LightBeamPlayback {
+            intersections: vec![intersection(10.0), intersection(20.0)],
+            end_point: Some(Vec2::new(30.0, 0.0)),
+            elapsed_time: 30.0,
+            passed_sensors: vec![],
+            penetrations: vec![],
+        };
+        let source = LightBeamSource {
+            start_pos: Vec2::ZERO,
+            start_dir: Vec2::X,
+            time_traveled: 30.0,
+            color: LightColor::White,
+            width: 0.0,
+            intensity: 1.0,
+            penetration: 0.0,
+            depth: LightBeamDepth::default(),
+        };
+        let points = |playback: &LightBeamPlayback| playback.iter_points(&source).count();
+
+        let mut frozen = playback();
+        frozen.truncate_segments(0);
+        assert_eq!(points(&frozen), 1);
+
+        let mut frozen = playback();
+        frozen.truncate_segments(2);
+        assert_eq!(points(&frozen), 3);
+        assert_eq!(frozen.end_point, None);
+
+        // the last segment ends in the air
+        let mut frozen = playback();
+        frozen.truncate_segments(3);
+        assert_eq!(points(&frozen), 4);
+    }
+
+    #[test]
+    fn crossing_beams_resolve_the_same_every_frame() {
+        let mut rapier_context = wall_context(Entity::from_raw(7));
+        let mut world = World::new();
+        let mut media_state = SystemState::<BeamMedia>::new(&mut world);
+        let media = media_state.get(&world);
+        let fog = VolumetricFog::default();
+        let source = |start_pos: Vec2, start_dir: Vec2, color| LightBeamSource {
+            start_pos,
+            start_dir: start_dir.normalize(),
+            time_traveled: 200.0,
+            color,
+            width: 0.0,
+            intensity: 1.0,
+            penetration: 0.0,
+            depth: LightBeamDepth::default(),
+        };
+        // two beams crossing in front of the wall
+        let sources = [
+            (
+                Entity::from_raw(3),
+                source(Vec2::new(0.0, 10.0), Vec2::X, LightColor::Green),
+            ),
+            (
+                Entity::from_raw(1),
+                source(
+                    Vec2::new(20.0, -30.0),
+                    Vec2::new(1.0, 1.0),
+                    LightColor::Green,
+                ),
+            ),
+        ];
+
+        let mut frame = |spawn_order: [usize; 2]| {
+            let entities = spawn_order.map(|i| sources[i].0);
+            beam_evaluation_order(entities)
+                .into_iter()
+                .map(|entity| {
+                    let (_, source) = sources.iter().find(|(e, _)| *e == entity).unwrap();
+                    let playback = play_light_beam(&mut rapier_context, source, 2, &fog, &media);
+                    (entity, playback.iter_points(source).collect::<Vec<_>>())
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let first = frame([0, 1]);
+        assert_eq!(
+            first.iter().map(|(entity, _)| *entity).collect::<Vec<_>>(),
+            vec![Entity::from_raw(1), Entity::from_raw(3)]
+        );
+        // both beams reach the wall
+        assert!(first.iter().all(|(_, pts)| pts.len() >= 2));
+        for _ in 0..4 {
+            assert_eq!(frame([0, 1]), first);
+            assert_eq!(frame([1, 0]), first);
+        }
+    }
+
+    /// The midpoints of the visible segments of `color`.
+    fn visible_segments(app: &mut App, color: LightColor) -> Vec<Vec2> {
+        app.world_mut()
+            .query::<(&LightSegment, &Visibility, &Transform)>()
+            .iter(app.world())
+            .filter(|(segment, visibility, _)| {
+                segment.color == color && **visibility == Visibility::Visible
+            })
+            .map(|(_, _, transform)| transform.translation.xy())
+            .collect()
+    }
+
+    #[test]
+    fn foreground_beams_are_drawn_in_front_of_the_level() {
+        let mut app = beam_simulation_app();
+        app.world_mut().spawn(RapierContext::default());
+        app.world_mut()
+            .query_filtered::<&mut Transform, With<LightSegmentZMarker>>()
+            .single_mut(app.world_mut())
+            .translation
+            .z = 3.0;
+
+        let source = |y: f32, depth: LightBeamDepth| LightBeamSource {
+            start_pos: Vec2::new(0.0, y),
+            start_dir: Vec2::X,
+            time_traveled: 100.0,
+            color: LightColor::Green,
+            width: 0.0,
+            intensity: 1.0,
+            penetration: 0.0,
+            depth,
+        };
+        let background = app
+            .world_mut()
+            .spawn(source(0.0, LightBeamDepth::Background))
+            .id();
+        let foreground = app
+            .world_mut()
+            .spawn(source(100.0, LightBeamDepth::Foreground))
+            .id();
+        app.update();
+
+        let segment_z = |app: &App, source: Entity| {
+            let segment = app
+                .world()
+                .resource::<LightSegmentCache>()
+                .segments(source)
+                .unwrap()[0];
+            app.world().get::<Transform>(segment).unwrap().translation.z
+        };
+        assert_eq!(segment_z(&app, background), 3.0);
+        assert_eq!(segment_z(&app, foreground), FOREGROUND_LIGHT_SEGMENT_Z);
+        assert!(segment_z(&app, foreground) > segment_z(&app, background));
+    }
+
+    #[test]
+    fn beams_of_the_same_color_are_all_drawn() {
+        let mut app = beam_simulation_app();
+        app.insert_resource(BeamBounces {
+            green: 0,
+            ..default()
+        });
+        let sensors = [0.0, 100.0].map(|y| {
+            let sensor = app
+                .world_mut()
+                .spawn(LightSensor::new(CrystalIdent::default(), 100))
+                .id();
+            (sensor, y)
+        });
+        let mut rapier_context = RapierContext::default();
+        for (sensor, y) in sensors {
+            rapier_context.colliders.insert(
+                ColliderBuilder::cuboid(5.0, 20.0)
+                    .translation(vector![50.0, y])
+                    .user_data(sensor.to_bits() as u128)
+                    .build(),
+            );
+        }
+        rapier_context
+            .query_pipeline
+            .update(&rapier_context.colliders);
+        app.world_mut().spawn(rapier_context);
+
+        let source = |y: f32| LightBeamSource {
+            start_pos: Vec2::new(0.0, y),
+            start_dir: Vec2::X,
+            time_traveled: 200.0,
+            color: LightColor::Green,
+            width: 0.0,
+            intensity: 1.0,
+            penetration: 0.0,
+            depth: LightBeamDepth::default(),
+        };
+        let first = app.world_mut().spawn(source(0.0)).id();
+        let second = app.world_mut().spawn(source(100.0)).id();
+        let hit_by_green = |app: &App, sensor: Entity| {
+            app.world().get::<LightSensor>(sensor).unwrap().hit_by[LightColor::Green]
+        };
+
+        app.update();
+        let mut drawn = visible_segments(&mut app, LightColor::Green);
+        drawn.sort_by(|a, b| a.y.total_cmp(&b.y));
+        assert_eq!(drawn.len(), 2);
+        assert!(drawn[0].distance(Vec2::new(22.5, 0.0)) < 1e-3);
+        assert!(drawn[1].distance(Vec2::new(22.5, 100.0)) < 1e-3);
+        assert!(hit_by_green(&app, sensors[0].0));
+        assert!(hit_by_green(&app, sensors[1].0));
+
+        // turning one beam away only turns off its own sensor
+        app.world_mut()
+            .get_mut::<LightBeamSource>(second)
+            .unwrap()
+            .start_dir = Vec2::NEG_X;
+        app.update();
+        assert_eq!(visible_segments(&mut app, LightColor::Green).len(), 2);
+        assert!(hit_by_green(&app, sensors[0].0));
+        assert!(!hit_by_green(&app, sensors[1].0));
+
+        // and the segments of beams that are gone are hidden
+        app.world_mut().entity_mut(first).despawn();
+        app.update();
+        let drawn = visible_segments(&mut app, LightColor::Green);
+        assert_eq!(drawn.len(), 1);
+        assert!(drawn[0].distance(Vec2::new(-100.0, 100.0)) < 1e-3);
+        assert!(!hit_by_green(&app, sensors[0].0));
+    }
+
+    #[test]
+    fn crossing_beams_are_both_drawn_in_any_spawn_order() {
+        let sources = [
+            (Vec2::new(0.0, 10.0), Vec2::X),
+            (Vec2::new(20.0, -30.0), Vec2::new(1.0, 1.0).normalize()),
+        ];
+        let frame = |spawn_order: [usize; 2]| {
+            let mut app = beam_simulation_app();
+            app.insert_resource(BeamBounces {
+                green: 0,
+                ..default()
+            });
+            let wall = app.world_mut().spawn_empty().id();
+            app.world_mut().spawn(wall_context(wall));
+            for i in spawn_order {
+                let (start_pos, start_dir) = sources[i];
+                app.world_mut().spawn(LightBeamSource {
+                    start_pos,
+                    start_dir,
+                    time_traveled: 200.0,
+                    color: LightColor::Green,
+                    width: 0.0,
+                    intensity: 1.0,
+                    penetration: 0.0,
+                    depth: LightBeamDepth::default(),
+                });
+            }
+            app.update();
+            app.update();
+            let mut drawn = visible_segments(&mut app, LightColor::Green);
+            drawn.sort_by(|a, b| a.y.total_cmp(&b.y));
+            drawn
+        };
+
+        let drawn = frame([0, 1]);
+        // both beams reach the wall, where they stop
+        assert_eq!(drawn.len(), 2);
+        assert!(drawn[0].distance(Vec2::new(32.5, -17.5)) < 1e-3);
+        assert!(drawn[1].distance(Vec2::new(22.5, 10.0)) < 1e-3);
+        assert_eq!(frame([1, 0]), drawn);
+    }
+
+    #[test]
+    fn wide_beams_clip_edges_thin_beams_miss() {
+        let wall = Entity::from_raw(7);
+        let mut rapier_context = wall_context(wall);
+        let mut world = World::new();
+        let mut media_state = SystemState::<BeamMedia>::new(&mut world);
+        let media = media_state.get(&world);
+        let fog = VolumetricFog::default();
+        // the center line passes 2 above the top edge of the wall
+        let source = |width| LightBeamSource {
+            start_pos: Vec2::new(0.0, 22.0),
+            start_dir: Vec2::X,
+            time_traveled: 100.0,
+            color: LightColor::Green,
+            width,
+            intensity: 1.0,
+            penetration: 0.0,
+            depth: LightBeamDepth::default(),
+        };
+
+        for width in [0.0, 2.0] {
+            let playback = play_light_beam(&mut rapier_context, &source(width), 0, &fog, &media);
+            assert!(playback.intersections.is_empty());
+            assert_eq!(playback.end_point, Some(Vec2::new(100.0, 22.0)));
+        }
+
+        // the lower edge of the beam catches the corner of the wall
+        let playback = play_light_beam(&mut rapier_context, &source(6.0), 0, &fog, &media);
+        assert_eq!(playback.intersections.len(), 1);
+        assert_eq!(playback.intersections[0].entity, wall);
+        let expected_x = 45.0 - (3.0f32.powi(2) - 2.0f32.powi(2)).sqrt();
+        assert!(
+            playback.intersections[0]
+                .point
+                .distance(Vec2::new(expected_x, 22.0))
+                < 1e-3
+        );
+    }
+
+    #[test]
+    fn wide_beams_starting_inside_walls_are_stopped() {
+        let wall = Entity::from_raw(7);
+        let mut rapier_context = wall_context(wall);
+        let mut world = World::new();
+        let mut media_state = SystemState::<BeamMedia>::new(&mut world);
+        let media = media_state.get(&world);
+        let fog = VolumetricFog::default();
+
+        // the edge of the beam already overlaps the left side of the wall
+        let source = LightBeamSource {
+            start_pos: Vec2::new(44.0, 0.0),
+            start_dir: Vec2::X,
+            time_traveled: 100.0,
+            color: LightColor::Green,
+            width: 4.0,
+            intensity: 1.0,
+            penetration: 0.0,
+            depth: LightBeamDepth::default(),
+        };
+        let playback = play_light_beam(&mut rapier_context, &source, 0, &fog, &media);
+        // the beam doesn't get anywhere, instead of passing through the wall
+        let points: Vec<Vec2> = playback.iter_points(&source).collect();
+        assert_eq!(points, vec![source.start_pos]);
+    }
+
+    #[test]
+    fn beams_pass_through_sensors_up_to_walls() {
+        let wall = Entity::from_raw(7);
+        let sensors = [10, 11, 12, 13].map(Entity::from_raw);
+        let mut rapier_context = sensor_context(
+            wall,
+            &[
+                (sensors[0], Vec2::new(30.0, 0.0)),
+                (sensors[1], Vec2::new(10.0, 0.0)),
+                (sensors[2], Vec2::new(20.0, 0.0)),
+                // behind the wall
+                (sensors[3], Vec2::new(70.0, 0.0)),
+            ],
+        );
+        let mut world = World::new();
+        let mut media_state = SystemState::<BeamMedia>::new(&mut world);
+        let media = media_state.get(&world);
+        let fog = VolumetricFog::default();
+        let source = |time_traveled| LightBeamSource {
+            start_pos: Vec2::ZERO,
+            start_dir: Vec2::X,
+            time_traveled,
+            color: LightColor::Green,
+            width: 0.0,
+            intensity: 1.0,
+            penetration: 0.0,
+            depth: LightBeamDepth::default(),
+        };
+        let passed = |playback: &LightBeamPlayback| {
+            playback
+                .passed_sensors
+                .iter()
+                .map(|pass| pass.entity)
+                .collect::<Vec<_>>()
+        };
+
+        let playback = play_light_beam(&mut rapier_context, &source(200.0), 0, &fog, &media);
+        assert_eq!(passed(&playback), vec![sensors[1], sensors[2], sensors[0]]);
+        // the beam still goes on to the wall
+        assert_eq!(playback.intersections.len(), 1);
+        assert_eq!(playback.intersections[0].entity, wall);
+        assert!(
+            playback.passed_sensors[0]
+                .point
+                .distance(Vec2::new(6.0, 0.0))
+                < 1e-4
+        );
+
+        // a beam that hasn't reached the last sensor yet
+        let playback = play_light_beam(&mut rapier_context, &source(22.0), 0, &fog, &media);
+        assert_eq!(passed(&playback), vec![sensors[1], sensors[2]]);
+
+        // nothing is passed after the frozen segment
+        let mut playback = play_light_beam(&mut rapier_context, &source(200.0), 0, &fog, &media);
+        playback.truncate_segments(0);
+        assert!(playback.passed_sensors.is_empty());
+    }
+
+    #[test]
+    fn beams_lose_intensity_through_each_weak_panel() {
+        let wall = Entity::from_raw(7);
+        let mut world = World::new();
+        let thin = world.spawn(WeakPanel { attenuation: 0.2 }).id();
+        let thick = world.spawn(WeakPanel { attenuation: 0.5 }).id();
+        let mut rapier_context = wall_context(wall);
+        for (panel, x) in [(thin, 20.0), (thick, 35.0)] {
+            rapier_context.colliders.insert(
+                ColliderBuilder::cuboid(1.0, 20.0)
+                    .translation(vector![x, 0.0])
+                    .user_data(panel.to_bits() as u128)
+                    .build(),
+            );
+        }
+        rapier_context
+            .query_pipeline
+            .update(&rapier_context.colliders);
+        let mut media_state = SystemState::<BeamMedia>::new(&mut world);
+        let media = media_state.get(&world);
+        let fog = VolumetricFog::default();
+        let source = |penetration| LightBeamSource {
+            start_pos: Vec2::ZERO,
+            start_dir: Vec2::X,
+            time_traveled: 200.0,
+            color: LightColor::Green,
+            width: 0.0,
+            intensity: 1.0,
+            penetration,
+            depth: LightBeamDepth::default(),
+        };
+
+        // through both panels, stopping at the wall
+        let playback = play_light_beam(&mut rapier_context, &source(1.0), 0, &fog, &media);
+        assert_eq!(playback.intersections.len(), 1);
+        assert_eq!(playback.intersections[0].entity, wall);
+        let panels: Vec<Entity> = playback.penetrations.iter().map(|p| p.entity).collect();
+        assert_eq!(panels, vec![thin, thick]);
+        // each panel takes off its own attenuation
+        assert_eq!(playback.intensity_at(10.0), 1.0);
+        assert!((playback.intensity_at(30.0) - 0.8).abs() < 1e-5);
+        assert!((playback.intensity_at(40.0) - 0.4).abs() < 1e-5);
+
+        // not enough penetration left for the thick panel, which the beam stops at instead
+        let playback = play_light_beam(&mut rapier_context, &source(0.5), 0, &fog, &media);
+        assert_eq!(playback.intersections.len(), 1);
+        assert_eq!(playback.intersections[0].entity, thick);
+        assert_eq!(playback.penetrations.len(), 1);
+        assert!((playback.intensity_at(40.0) - 0.8).abs() < 1e-5);
+    }
 }
@@ -0,0 +1,154 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+use enum_map::Enum;
+
+use crate::{
+    level::entity::FixedEntityBundle,
+    lighting::{Occluder2d, Occluder2dGroups},
+    shared::GroupLabel,
+};
+
+use super::LightColor;
+
+/// The lowest of the [`Occluder2dGroups`] bits reserved for [`SpectralOccluder`]s, one bit per
+/// [`LightColor`] at the top of the mask.
+const SPECTRAL_GROUPS_SHIFT: u32 = 32 - LightColor::LENGTH as u32;
+
+impl LightColor {
+    /// The [`Occluder2dGroups`] of a [`SpectralOccluder`] that blocks this color.
+    pub fn spectral_group(&self) -> Occluder2dGroups {
+        Occluder2dGroups(1 << (SPECTRAL_GROUPS_SHIFT + self.into_usize() as u32))
+    }
+
+    /// The [`Occluder2dGroups`] of lights of this color, which are shadowed by ordinary occluders
+    /// and by [`SpectralOccluder`]s of the same color only.
+    pub fn light_groups(&self) -> Occluder2dGroups {
+        let spectral_groups = !0u32 << SPECTRAL_GROUPS_SHIFT;
+        Occluder2dGroups(!spectral_groups | self.spectral_group().0)
+    }
+
+    /// The collision [`Group`] of a [`SpectralOccluder`] that blocks this color, which only beams
+    /// of this color collide with, see
+    /// [`beam_collision_groups`](super::segments::beam_collision_groups).
+    pub fn spectral_collision_group(&self) -> Group {
+        Group::from_bits_truncate(GroupLabel::SPECTRAL_OCCLUDER.bits() << self.into_usize())
+    }
+}
+
+/// [`Component`] for occluders that only cast shadows from lights of one [`LightColor`], like
+/// tinted glass, and let the light of every other color through. Light beams of that color are
+/// stopped like by a wall, and beams of every other color pass through. Lights without a color,
+/// like lamps and the player's light, have every [`Occluder2dGroups`] bit, so they are shadowed
+/// by every spectral occluder.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct SpectralOccluder {
+    pub blocks: LightColor,
+}
+
+impl From<&EntityInstance> for SpectralOccluder {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        let blocks = entity_instance
+            .get_enum_field("blocks")
+            .expect("blocks needs to be an enum field on all spectral occluders")
+            .into();
+
+        SpectralOccluder { blocks }
+    }
+}
+
+/// [`Bundle`] that includes all the [`Component`]s needed for a [`SpectralOccluder`] to function
+/// properly.
+#[derive(Bundle, LdtkEntity)]
+pub struct SpectralOccluderBundle {
+    #[from_entity_instance]
+    spectral_occluder: SpectralOccluder,
+    #[from_entity_instance]
+    physics: FixedEntityBundle,
+    #[with(spectral_occluder_shape)]
+    occluder: Occluder2d,
+    #[with(spectral_occluder_sprite)]
+    sprite: Sprite,
+}
+
+pub fn spectral_occluder_shape(entity_instance: &EntityInstance) -> Occluder2d {
+    Occluder2d::new(
+        entity_instance.width as f32 / 2.0,
+        entity_instance.height as f32 / 2.0,
+    )
+}
+
+pub fn spectral_occluder_sprite(entity_instance: &EntityInstance) -> Sprite {
+    let spectral_occluder = SpectralOccluder::from(entity_instance);
+    Sprite::from_color(
+        spectral_occluder.blocks.indicator_color().with_alpha(0.4),
+        Vec2::new(entity_instance.width as f32, entity_instance.height as f32),
+    )
+}
+
+/// [`System`] that gives each [`SpectralOccluder`] the [`Occluder2dGroups`] of its color.
+pub fn update_spectral_occluder_groups(
+    mut commands: Commands,
+    q_spectral_occluders: Query<(Entity, &SpectralOccluder), Changed<SpectralOccluder>>,
+) {
+    for (entity, spectral_occluder) in q_spectral_occluders.iter() {
+        commands
+            .entity(entity)
+            .insert(spectral_occluder.blocks.spectral_group());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lighting::{occluder_2d_occludes, LightDepth};
+
+    use super::*;
+
+    fn occludes(light_groups: Occluder2dGroups, occluder_groups: Occluder2dGroups) -> bool {
+        occluder_2d_occludes(
+            light_groups,
+            LightDepth::Foreground,
+            occluder_groups,
+            LightDepth::Foreground,
+        )
+    }
+
+    #[test]
+    fn spectral_occluder_only_shadows_its_color() {
+        let blocks_purple = LightColor::Purple.spectral_group();
+        assert!(occludes(LightColor::Purple.light_groups(), blocks_purple));
+        assert!(!occludes(LightColor::Blue.light_groups(), blocks_purple));
+        assert!(!occludes(LightColor::White.light_groups(), blocks_purple));
+    }
+
+    #[test]
+    fn ordinary_occluders_shadow_every_color() {
+        for color in [
+            LightColor::Green,
+            LightColor::Purple,
+            LightColor::White,
+            LightColor::Blue,
+        ] {
+            assert!(occludes(color.light_groups(), Occluder2dGroups::ALL));
+            // lights without a color are shadowed by spectral occluders too
+            assert!(occludes(Occluder2dGroups::ALL, color.spectral_group()));
+        }
+    }
+
+    #[test]
+    fn spectral_occluders_get_groups_of_their_color() {
+        let mut app = App::new();
+        app.add_systems(Update, update_spectral_occluder_groups);
+        let occluder = app
+            .world_mut()
+            .spawn(SpectralOccluder {
+                blocks: LightColor::Green,
+            })
+            .id();
+        app.update();
+        assert!(
+            app.world().get::<Occluder2dGroups>(occluder)
+                == Some(&LightColor::Green.spectral_group())
+        );
+    }
+}
@@ -0,0 +1,206 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::light::{
+    segments::{beam_collision_groups, cast_light_beam},
+    LightColor,
+};
+
+/// The result of [`cast_light_ray`].
+#[derive(Debug, PartialEq)]
+pub struct RayHit {
+    pub entity: Entity,
+    pub point: Vec2,
+    /// The normal of the surface that was hit. A ray that starts inside of something is stopped
+    /// right away, facing back the way it came, like a beam.
+    pub normal: Vec2,
+    /// How far along the ray the hit happened
+    pub time_of_impact: f32,
+}
+
+/// Casts a ray that is stopped by the same things as a beam of `color`, for other systems that
+/// need to know what light would hit without spawning a
+/// [`LightBeamSource`](crate::light::LightBeamSource). Unlike a beam, the ray stops at the first
+/// thing it hits, including mirrors, weak panels and water. `dir` needs to be normalized.
+pub fn cast_light_ray(
+    rapier_context: &RapierContext,
+    color: LightColor,
+    origin: Vec2,
+    dir: Vec2,
+    max_len: f32,
+) -> Option<RayHit> {
+    let ray_qry = QueryFilter::new().groups(beam_collision_groups(color));
+    let hit = cast_light_beam(rapier_context, origin, dir, max_len, 0.0, ray_qry)?;
+    Some(RayHit {
+        entity: hit.entity,
+        point: hit.point,
+        // solid ray casts that start inside of a shape don't have a normal
+        normal: if hit.normal == Vec2::ZERO {
+            -dir
+        } else {
+            hit.normal
+        },
+        time_of_impact: hit.time_of_impact,
+    })
+}
+
+/// Whether a beam of `color` could get from `from` to `to` without being stopped, see
+/// [`cast_light_ray`]. The player doesn't stop beams, so this can be used to check if something
+/// can see them.
+pub fn light_line_of_sight(
+    rapier_context: &RapierContext,
+    color: LightColor,
+    from: Vec2,
+    to: Vec2,
+) -> bool {
+    let Ok(dir) = Dir2::new(to - from) else {
+        return true;
+    };
+    cast_light_ray(rapier_context, color, from, *dir, from.distance(to)).is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_rapier2d::rapier::{
+        geometry::{ColliderBuilder, InteractionGroups},
+        na::vector,
+    };
+
+    use crate::shared::GroupLabel;
+
+    use super::*;
+
+    /// A [`RapierContext`] with a 10x40 collider centered at (50, 0) in `groups`.
+    fn wall_context(wall: Entity, groups: InteractionGroups) -> RapierContext {
+        let mut rapier_context = RapierContext::default();
+        rapier_context.colliders.insert(
+            ColliderBuilder::cuboid(5.0, 20.0)
+                .translation(vector![50.0, 0.0])
+                .collision_groups(groups)
+                .user_data(wall.to_bits() as u128)
+                .build(),
+        );
+        rapier_context
+            .query_pipeline
+            .update(&rapier_context.colliders);
+        rapier_context
+    }
+
+    #[test]
+    fn light_rays_hit_walls() {
+        let wall = Entity::from_raw(7);
+        let rapier_context = wall_context(wall, InteractionGroups::all());
+
+        let hit = cast_light_ray(
+            &rapier_context,
+            LightColor::Green,
+            Vec2::ZERO,
+            Vec2::X,
+            100.0,
+        )
+        .expect("ray should hit the wall");
+        assert_eq!(hit.entity, wall);
+        assert!(hit.point.distance(Vec2::new(45.0, 0.0)) < 1e-4);
+        assert!(hit.normal.distance(Vec2::NEG_X) < 1e-4);
+        assert!((hit.time_of_impact - 45.0).abs() < 1e-4);
+
+        // too short, and pointing away
+        assert_eq!(
+            cast_light_ray(
+                &rapier_context,
+                LightColor::Green,
+                Vec2::ZERO,
+                Vec2::X,
+                40.0
+            ),
+            None
+        );
+        assert_eq!(
+            cast_light_ray(
+                &rapier_context,
+                LightColor::Green,
+                Vec2::ZERO,
+                Vec2::Y,
+                100.0
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn light_rays_starting_inside_walls_are_stopped() {
+        let wall = Entity::from_raw(7);
+        let rapier_context = wall_context(wall, InteractionGroups::all());
+
+        let hit = cast_light_ray(
+            &rapier_context,
+            LightColor::Green,
+            Vec2::new(48.0, 0.0),
+            Vec2::X,
+            100.0,
+        )
+        .expect("ray should be stopped inside of the wall");
+        assert_eq!(hit.entity, wall);
+        assert_eq!(hit.time_of_impact, 0.0);
+        assert_eq!(hit.normal, Vec2::NEG_X);
+        assert!(!light_line_of_sight(
+            &rapier_context,
+            LightColor::Green,
+            Vec2::new(48.0, 0.0),
+            Vec2::new(100.0, 0.0)
+        ));
+    }
+
+    #[test]
+    fn spectral_occluders_only_stop_rays_of_their_color() {
+        let occluder = Entity::from_raw(7);
+        let rapier_context = wall_context(
+            occluder,
+            InteractionGroups::new(
+                LightColor::Purple.spectral_collision_group(),
+                GroupLabel::LIGHT_RAY | GroupLabel::WHITE_RAY | GroupLabel::BLUE_RAY,
+            ),
+        );
+
+        let hit = cast_light_ray(
+            &rapier_context,
+            LightColor::Purple,
+            Vec2::ZERO,
+            Vec2::X,
+            100.0,
+        )
+        .expect("purple ray should be stopped");
+        assert_eq!(hit.entity, occluder);
+        for color in [LightColor::Green, LightColor::White, LightColor::Blue] {
+            assert_eq!(
+                cast_light_ray(&rapier_context, color, Vec2::ZERO, Vec2::X, 100.0),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn walls_block_line_of_sight() {
+        let rapier_context = wall_context(Entity::from_raw(7), InteractionGroups::all());
+        let color = LightColor::White;
+        assert!(!light_line_of_sight(
+            &rapier_context,
+            color,
+            Vec2::ZERO,
+            Vec2::new(100.0, 0.0)
+        ));
+        assert!(light_line_of_sight(
+            &rapier_context,
+            color,
+            Vec2::ZERO,
+            Vec2::new(0.0, 100.0)
+        ));
+        // over the top of the wall
+        assert!(light_line_of_sight(
+            &rapier_context,
+            color,
+            Vec2::new(0.0, 30.0),
+            Vec2::new(100.0, 30.0)
+        ));
+    }
+}
@@ -0,0 +1,1044 @@
+use bevy::{
+    ecs::{
+        entity::EntityHashSet,
+        query::ROQueryItem,
+        system::{lifetimeless::SRes, SystemParamItem},
+    },
+    math::Affine3A,
+    prelude::*,
+    render::{
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        render_graph::{Node, NodeRunError, RenderGraphContext, RenderLabel},
+        render_phase::{PhaseItem, RenderCommand, RenderCommandResult, TrackedRenderPass},
+        render_resource::{
+            binding_types::{
+                storage_buffer_read_only, storage_buffer_read_only_sized, storage_buffer_sized,
+            },
+            *,
+        },
+        renderer::{RenderContext, RenderDevice, RenderQueue},
+        Extract, ExtractSchedule, Render, RenderApp, RenderSet,
+    },
+};
+use serde::Deserialize;
+
+use super::{
+    line_light::{ExtractLineLight2d, LineLight2dBounds},
+    occluder::{
+        shadow_fade, LightDepth, Occluder2dBatchGeneration, Occluder2dGroups, Occluder2dPolygon,
+    },
+    render::queue_deferred_lighting,
+    shadow_mask::SoftShadows,
+    Occluder2d, Occluder2dAlphaMask,
+};
+
+/// The most occluder edges that can shadow a single light with [`ComputeShadows`]. Must match
+/// `MAX_LIGHT_SEGMENTS` in `shadow_segments.wgsl`. Lights with more edges around them are drawn
+/// with stencil shadows instead, see [`ComputeShadowBuffers::stencil_fallback`].
+pub const MAX_LIGHT_SEGMENTS: u32 = 255;
+
+/// The number of `u32`s each light gets in the [`ComputeShadowBuffers::lists`], the number of
+/// edges followed by their indices.
+pub const SHADOW_LIST_STRIDE: u32 = MAX_LIGHT_SEGMENTS + 1;
+
+/// Must match the `@workgroup_size` of `compute_shadows.wgsl`.
+const WORKGROUP_SIZE: u32 = 64;
+
+/// How many times the volumetric glow of a light tests for shadows at each pixel, see
+/// [`volumetric_visibility`]. Must match `VOLUMETRIC_MARCH_STEPS` in `line_light.wgsl`.
+pub const VOLUMETRIC_MARCH_STEPS: u32 = 8;
+
+pub struct ComputeShadowsPlugin;
+
+impl Plugin for ComputeShadowsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ComputeShadows>()
+            .add_plugins(ExtractResourcePlugin::<ComputeShadows>::default());
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .init_resource::<ComputeShadowBuffers>()
+            .add_systems(ExtractSchedule, extract_shadow_segments)
+            .add_systems(
+                Render,
+                prepare_shadow_lights
+                    .in_set(RenderSet::Queue)
+                    .before(queue_deferred_lighting),
+            )
+            .add_systems(
+                Render,
+                prepare_compute_shadow_buffers.in_set(RenderSet::PrepareResources),
+            )
+            .add_systems(
+                Render,
+                prepare_compute_shadow_bind_groups.in_set(RenderSet::PrepareBindGroups),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        let supported = compute_shadows_supported(render_app.world().resource::<RenderDevice>());
+        if !supported {
+            info!("Compute shaders aren't supported, falling back to stencil shadows");
+        }
+        render_app.insert_resource(ComputeShadowsSupported(supported));
+        if supported {
+            render_app.init_resource::<ComputeShadowPipeline>();
+        }
+    }
+}
+
+/// [`Resource`] that switches hard shadows from the stencil path to a compute pass. Instead of
+/// every light drawing the shadow of every occluder around it into the stencil buffer, the edges
+/// of all occluders are uploaded to the GPU once, a compute shader finds the edges close enough
+/// to each light, and the light's fragment shader checks whether the ray from the light crosses
+/// any of them. This takes most of the per light, per occluder work off of the CPU, so it scales
+/// much better in levels with lots of lights and occluders, see `bench_compute_shadows`. Off by
+/// default while the stencil path is the one that is tested on every platform.
+///
+/// Occluders with an [`Occluder2dAlphaMask`] still use the stencil, and
+/// [`SoftShadows`] take precedence over compute shadows. Platforms without compute shaders, like
+/// WebGL2, always use the stencil path. See the `lighting_config.compute_shadows` section of
+/// `Lightborne.toml`.
+#[derive(Resource, ExtractResource, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct ComputeShadows {
+    pub enabled: bool,
+}
+
+impl Default for ComputeShadows {
+    fn default() -> Self {
+        ComputeShadows { enabled: false }
+    }
+}
+
+/// Render world [`Resource`] holding whether the GPU can run [`ComputeShadows`].
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct ComputeShadowsSupported(pub bool);
+
+pub fn compute_shadows_supported(render_device: &RenderDevice) -> bool {
+    let limits = render_device.limits();
+    limits.max_compute_workgroups_per_dimension > 0
+        && limits.max_compute_invocations_per_workgroup >= WORKGROUP_SIZE
+        && limits.max_storage_buffers_per_shader_stage >= 3
+}
+
+/// A single edge of an occluder in world space. Edges go counterclockwise around their occluder.
+#[derive(ShaderType, Clone, Copy, Debug, Default, PartialEq)]
+pub struct ShadowSegment {
+    pub a: Vec2,
+    pub b: Vec2,
+    pub groups: u32,
+    pub depth: u32,
+}
+
+/// A light in world space, as seen by the compute pass.
+#[derive(ShaderType, Clone, Copy, Debug, Default, PartialEq)]
+pub struct ShadowLight {
+    pub a: Vec2,
+    pub b: Vec2,
+    pub radius: f32,
+    pub groups: u32,
+    pub depth: u32,
+}
+
+fn light_depth_index(depth: LightDepth) -> u32 {
+    match depth {
+        LightDepth::Foreground => 0,
+        LightDepth::Background => 1,
+    }
+}
+
+/// The corners of an [`Occluder2d`] without an [`Occluder2dPolygon`], counterclockwise.
+pub fn rectangle_points(half_size: Vec2) -> [Vec2; 4] {
+    [
+        Vec2::new(-half_size.x, -half_size.y),
+        Vec2::new(half_size.x, -half_size.y),
+        Vec2::new(half_size.x, half_size.y),
+        Vec2::new(-half_size.x, half_size.y),
+    ]
+}
+
+/// Adds the edges of an occluder with counterclockwise corners `points` to `segments`. Like the
+/// [`Occluder2dBatch`](super::occluder::Occluder2dBatch), the scale of the transform is ignored
+/// apart from its sign.
+pub fn push_occluder_segments(
+    segments: &mut Vec<ShadowSegment>,
+    transform: &GlobalTransform,
+    points: &[Vec2],
+    groups: Occluder2dGroups,
+    depth: LightDepth,
+) {
+    let (scale, rotation, translation) = transform.to_scale_rotation_translation();
+    let world_from_local =
+        Affine3A::from_scale_rotation_translation(scale.signum(), rotation, translation);
+    // mirroring the occluder turns its corners clockwise
+    let mirrored = scale.x.signum() * scale.y.signum() < 0.0;
+
+    let n = points.len();
+    for i in 0..n {
+        let a = world_from_local
+            .transform_point3(points[i].extend(0.0))
+            .xy();
+        let b = world_from_local
+            .transform_point3(points[(i + 1) % n].extend(0.0))
+            .xy();
+        let (a, b) = if mirrored { (b, a) } else { (a, b) };
+        segments.push(ShadowSegment {
+            a,
+            b,
+            groups: groups.0,
+            depth: light_depth_index(depth),
+        });
+    }
+}
+
+/// Whether `segment` is close enough to `light` to shadow it, and is in the same groups and depth.
+/// The same test the `cull` entry point of `compute_shadows.wgsl` uses to list the edges of each
+/// light.
+pub fn segment_reaches_light(segment: &ShadowSegment, light: &ShadowLight) -> bool {
+    // same as occluder_2d_occludes
+    if segment.depth != light.depth || segment.groups & light.groups == 0 {
+        return false;
+    }
+    let light_center = light.a.midpoint(light.b);
+    let reach = light.radius + light.a.distance(light.b) / 2.0;
+    let edge = segment.b - segment.a;
+    let t = if edge.length_squared() == 0.0 {
+        0.0
+    } else {
+        ((light_center - segment.a).dot(edge) / edge.length_squared()).clamp(0.0, 1.0)
+    };
+    (segment.a + edge * t).distance(light_center) <= reach
+}
+
+/// Whether more than [`MAX_LIGHT_SEGMENTS`] of `segments` can shadow `light`, so that the compute
+/// pass would leave some of them out of its list.
+pub fn light_overflows_segments(light: &ShadowLight, segments: &[ShadowSegment]) -> bool {
+    segments
+        .iter()
+        .filter(|segment| segment_reaches_light(segment, light))
+        .nth(MAX_LIGHT_SEGMENTS as usize)
+        .is_some()
+}
+
+/// The [`ShadowLight`] of a light with `bounds`.
+pub fn shadow_light(
+    bounds: &LineLight2dBounds,
+    groups: Occluder2dGroups,
+    depth: LightDepth,
+) -> ShadowLight {
+    let center = bounds.transform.translation.xy();
+    let axis = (bounds.transform.rotation * Vec3::X).xy();
+    ShadowLight {
+        a: center - axis * bounds.half_length,
+        b: center + axis * bounds.half_length,
+        radius: bounds.radius,
+        groups: groups.0,
+        depth: light_depth_index(depth),
+    }
+}
+
+/// How far past `segment` the point `p` is, if `segment` shadows `p` from `light_point`. The same
+/// test as `segment_shadow_distance` in `shadow_segments.wgsl`.
+pub fn segment_shadow_distance(segment: &ShadowSegment, light_point: Vec2, p: Vec2) -> Option<f32> {
+    let edge = segment.b - segment.a;
+    let ray = p - light_point;
+    let outward = Vec2::new(edge.y, -edge.x);
+    if outward.dot(ray) <= 0.0 {
+        return None;
+    }
+    let denom = ray.perp_dot(edge);
+    if denom.abs() < 1e-6 {
+        return None;
+    }
+    let to_a = segment.a - light_point;
+    let t = to_a.perp_dot(edge) / denom;
+    let u = to_a.perp_dot(ray) / denom;
+    ((0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u)).then(|| (1.0 - t) * ray.length())
+}
+
+/// Whether `segment` shadows `p` from `light_point`, no matter how far the shadow reaches.
+pub fn segment_shadows(segment: &ShadowSegment, light_point: Vec2, p: Vec2) -> bool {
+    segment_shadow_distance(segment, light_point, p).is_some()
+}
+
+/// How dark `p` is in the shadows of `segments` from `light_point`, from 0 lit to 1 in full
+/// shadow, with shadows fading out at `max_shadow_length`. Mirrors `compute_shadow_from` in
+/// `line_light.wgsl`.
+pub fn shadow_amount(
+    segments: &[ShadowSegment],
+    light_point: Vec2,
+    p: Vec2,
+    max_shadow_length: f32,
+) -> f32 {
+    segments
+        .iter()
+        .filter_map(|segment| segment_shadow_distance(segment, light_point, p))
+        .map(|distance| shadow_fade(distance, max_shadow_length))
+        .fold(0.0, f32::max)
+}
+
+/// How much of the volumetric glow of a light from `light_a` to `light_b` reaches `p`, a pixel
+/// `footprint` world units wide, from 0 to 1, with shadows fading out at `max_shadow_length`.
+/// Mirrors `compute_volumetric_visibility` in `line_light.wgsl`.
+///
+/// Each of the [`VOLUMETRIC_MARCH_STEPS`] tests the shadows between a different point along
+/// the light and a different point across the pixel, so the glow behind a slatted wall is split
+/// into a sharp shaft for each gap, and gaps thinner than a pixel dim their shaft instead of
+/// flickering between fully lit and dark.
+pub fn volumetric_visibility(
+    segments: &[ShadowSegment],
+    light_a: Vec2,
+    light_b: Vec2,
+    p: Vec2,
+    footprint: f32,
+    max_shadow_length: f32,
+) -> f32 {
+    let steps = VOLUMETRIC_MARCH_STEPS;
+    let visible: f32 = (0..steps)
+        .map(|i| {
+            let along_light = (i as f32 + 0.5) / steps as f32;
+            // shuffled so that the points across the pixel aren't lined up with the light's
+            let across_pixel = ((i * 3 % steps) as f32 + 0.5) / steps as f32 - 0.5;
+            let light_point = light_a.lerp(light_b, along_light);
+            let offset = (p - light_point).normalize_or_zero().perp() * across_pixel * footprint;
+            1.0 - shadow_amount(segments, light_point, p + offset, max_shadow_length)
+        })
+        .sum();
+    visible / steps as f32
+}
+
+/// Render world [`Resource`] holding the buffers of [`ComputeShadows`].
+#[derive(Resource)]
+pub struct ComputeShadowBuffers {
+    /// The edges of every occluder that isn't alpha masked
+    pub segments: StorageBuffer<Vec<ShadowSegment>>,
+    pub lights: StorageBuffer<Vec<ShadowLight>>,
+    /// Written by the compute pass. For every light, the number of edges that can shadow it
+    /// followed by their indices in `segments`, [`SHADOW_LIST_STRIDE`] `u32`s per light.
+    pub lists: Option<Buffer>,
+    pub num_lights: u32,
+    /// Whether lights are drawn with compute shadows this frame
+    pub active: bool,
+    /// The lights with more than [`MAX_LIGHT_SEGMENTS`] edges around them. The compute pass can't
+    /// list all of their edges, so they are drawn with stencil shadows instead.
+    pub stencil_fallback: EntityHashSet,
+    generation: Option<u32>,
+    segments_dirty: bool,
+}
+
+impl Default for ComputeShadowBuffers {
+    fn default() -> Self {
+        ComputeShadowBuffers {
+            segments: StorageBuffer::default(),
+            lights: StorageBuffer::default(),
+            lists: None,
+            num_lights: 0,
+            active: false,
+            stencil_fallback: EntityHashSet::default(),
+            generation: None,
+            segments_dirty: false,
+        }
+    }
+}
+
+/// [`System`] that rebuilds the [`ShadowSegment`]s when the [`Occluder2dBatchGeneration`]
+/// changes, which covers every change to an occluder that could move its shadow.
+#[allow(clippy::type_complexity)]
+pub fn extract_shadow_segments(
+    mut buffers: ResMut<ComputeShadowBuffers>,
+    compute_shadows: Extract<Res<ComputeShadows>>,
+    generation: Extract<Res<Occluder2dBatchGeneration>>,
+    q_occluders: Extract<
+        Query<
+            (
+                &GlobalTransform,
+                &Occluder2d,
+                Option<&Occluder2dPolygon>,
+                &Occluder2dGroups,
+                Option<&LightDepth>,
+                &InheritedVisibility,
+            ),
+            Without<Occluder2dAlphaMask>,
+        >,
+    >,
+) {
+    if !compute_shadows.enabled {
+        // rebuilt from scratch when turned back on
+        buffers.generation = None;
+        return;
+    }
+    if buffers.generation == Some(generation.0) {
+        return;
+    }
+    buffers.generation = Some(generation.0);
+    buffers.segments_dirty = true;
+
+    let segments = buffers.segments.get_mut();
+    segments.clear();
+    for (transform, occluder, polygon, groups, depth, visibility) in q_occluders.iter() {
+        if !visibility.get() {
+            continue;
+        }
+        let depth = depth.copied().unwrap_or_default();
+        match polygon {
+            Some(polygon) => {
+                push_occluder_segments(segments, transform, polygon.points(), *groups, depth)
+            }
+            None => push_occluder_segments(
+                segments,
+                transform,
+                &rectangle_points(occluder.half_size),
+                *groups,
+                depth,
+            ),
+        }
+    }
+    // storage buffers can't be empty, and an edge without groups never casts a shadow
+    if segments.is_empty() {
+        segments.push(ShadowSegment::default());
+    }
+}
+
+/// [`System`] that gathers the [`ShadowLight`] of every light, and tells each light where to find
+/// its edges in the [`ComputeShadowBuffers::lists`]. Lights with too many edges around them for
+/// their list are left to the stencil path.
+pub fn prepare_shadow_lights(
+    mut buffers: ResMut<ComputeShadowBuffers>,
+    compute_shadows: Res<ComputeShadows>,
+    supported: Res<ComputeShadowsSupported>,
+    soft_shadows: Res<SoftShadows>,
+    mut q_lights: Query<(
+        Entity,
+        &mut ExtractLineLight2d,
+        &LineLight2dBounds,
+        Option<&Occluder2dGroups>,
+        Option<&LightDepth>,
+    )>,
+) {
+    buffers.active = compute_shadows.enabled && supported.0 && !soft_shadows.enabled;
+    if !buffers.active {
+        return;
+    }
+
+    let ComputeShadowBuffers {
+        segments,
+        lights,
+        stencil_fallback,
+        ..
+    } = buffers.as_mut();
+    let lights = lights.get_mut();
+    lights.clear();
+    stencil_fallback.clear();
+    for (entity, mut light, bounds, groups, depth) in q_lights.iter_mut() {
+        let shadow = shadow_light(
+            bounds,
+            groups.copied().unwrap_or_default(),
+            depth.copied().unwrap_or_default(),
+        );
+        if light_overflows_segments(&shadow, segments.get()) {
+            warn_once!(
+                "A light has more than {} occluder edges around it, falling back to stencil \
+                 shadows for it",
+                MAX_LIGHT_SEGMENTS
+            );
+            stencil_fallback.insert(entity);
+        }
+        light.shadow_index = lights.len() as u32;
+        lights.push(shadow);
+    }
+    buffers.num_lights = buffers.lights.get().len() as u32;
+    if buffers.lights.get().is_empty() {
+        buffers.lights.get_mut().push(ShadowLight::default());
+    }
+}
+
+pub fn prepare_compute_shadow_buffers(
+    mut buffers: ResMut<ComputeShadowBuffers>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    if !buffers.active {
+        return;
+    }
+    if buffers.segments_dirty {
+        buffers.segments_dirty = false;
+        buffers.segments.write_buffer(&render_device, &render_queue);
+    }
+    buffers.lights.write_buffer(&render_device, &render_queue);
+
+    let size = (buffers.num_lights.max(1) * SHADOW_LIST_STRIDE) as u64 * 4;
+    if buffers
+        .lists
+        .as_ref()
+        .is_none_or(|lists| lists.size() < size)
+    {
+        buffers.lists = Some(render_device.create_buffer(&BufferDescriptor {
+            label: Some("compute_shadow_lists_buffer"),
+            size,
+            usage: BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        }));
+    }
+}
+
+/// The layout of the bind group lights use to read the results of the compute pass.
+pub fn compute_shadow_bind_group_layout(render_device: &RenderDevice) -> BindGroupLayout {
+    render_device.create_bind_group_layout(
+        "compute_shadow_light_bind_group_layout",
+        &BindGroupLayoutEntries::sequential(
+            ShaderStages::FRAGMENT,
+            (
+                storage_buffer_read_only::<Vec<ShadowSegment>>(false),
+                storage_buffer_read_only_sized(false, None),
+            ),
+        ),
+    )
+}
+
+#[derive(Resource)]
+pub struct ComputeShadowPipeline {
+    pub cull_layout: BindGroupLayout,
+    pub light_layout: BindGroupLayout,
+    pub cull_pipeline_id: CachedComputePipelineId,
+}
+
+impl FromWorld for ComputeShadowPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+        let cull_layout = render_device.create_bind_group_layout(
+            "compute_shadow_cull_bind_group_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::COMPUTE,
+                (
+                    storage_buffer_read_only::<Vec<ShadowSegment>>(false),
+                    storage_buffer_read_only::<Vec<ShadowLight>>(false),
+                    storage_buffer_sized(false, None),
+                ),
+            ),
+        );
+        let light_layout = compute_shadow_bind_group_layout(render_device);
+
+        let shader = world.load_asset("shaders/lighting/compute_shadows.wgsl");
+        let cull_pipeline_id = world
+            .resource_mut::<PipelineCache>()
+            .queue_compute_pipeline(ComputePipelineDescriptor {
+                label: Some("compute_shadow_cull_pipeline".into()),
+                layout: vec![cull_layout.clone()],
+                push_constant_ranges: vec![],
+                shader,
+                shader_defs: vec![],
+                entry_point: "cull".into(),
+                zero_initialize_workgroup_memory: false,
+            });
+
+        ComputeShadowPipeline {
+            cull_layout,
+            light_layout,
+            cull_pipeline_id,
+        }
+    }
+}
+
+/// Render world [`Resource`] holding the bind groups of [`ComputeShadows`].
+#[derive(Resource)]
+pub struct ComputeShadowBindGroups {
+    pub cull: BindGroup,
+    pub light: BindGroup,
+}
+
+pub fn prepare_compute_shadow_bind_groups(
+    mut commands: Commands,
+    buffers: Res<ComputeShadowBuffers>,
+    pipeline: Option<Res<ComputeShadowPipeline>>,
+    render_device: Res<RenderDevice>,
+) {
+    let Some(pipeline) = pipeline else {
+        return;
+    };
+    if !buffers.active {
+        return;
+    }
+    let (Some(segments), Some(lights), Some(lists)) = (
+        buffers.segments.binding(),
+        buffers.lights.binding(),
+        buffers.lists.as_ref(),
+    ) else {
+        return;
+    };
+    commands.insert_resource(ComputeShadowBindGroups {
+        cull: render_device.create_bind_group(
+            "compute_shadow_cull_bind_group",
+            &pipeline.cull_layout,
+            &BindGroupEntries::sequential((segments.clone(), lights, lists.as_entire_binding())),
+        ),
+        light: render_device.create_bind_group(
+            "compute_shadow_light_bind_group",
+            &pipeline.light_layout,
+            &BindGroupEntries::sequential((segments, lists.as_entire_binding())),
+        ),
+    });
+}
+
+pub struct SetComputeShadowBindGroup<const I: usize>;
+impl<P: PhaseItem, const I: usize> RenderCommand<P> for SetComputeShadowBindGroup<I> {
+    type Param = Option<SRes<ComputeShadowBindGroups>>;
+    type ViewQuery = ();
+    type ItemQuery = ();
+
+    fn render<'w>(
+        _item: &P,
+        _view: ROQueryItem<'w, Self::ViewQuery>,
+        _entity: Option<ROQueryItem<'w, Self::ItemQuery>>,
+        param: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Some(bind_groups) = param else {
+            return RenderCommandResult::Skip;
+        };
+        pass.set_bind_group(I, &bind_groups.into_inner().light, &[]);
+        RenderCommandResult::Success
+    }
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+pub struct ComputeShadowsLabel;
+
+/// Render graph [`Node`] that runs the compute pass of [`ComputeShadows`], finding the edges
+/// that can shadow each light before the lights are drawn.
+#[derive(Default)]
+pub struct ComputeShadowsNode;
+
+impl Node for ComputeShadowsNode {
+    fn run<'w>(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext<'w>,
+        world: &'w World,
+    ) -> Result<(), NodeRunError> {
+        let buffers = world.resource::<ComputeShadowBuffers>();
+        if !buffers.active || buffers.num_lights == 0 {
+            return Ok(());
+        }
+        let (Some(pipeline), Some(bind_groups)) = (
+            world.get_resource::<ComputeShadowPipeline>(),
+            world.get_resource::<ComputeShadowBindGroups>(),
+        ) else {
+            return Ok(());
+        };
+        let Some(cull_pipeline) = world
+            .resource::<PipelineCache>()
+            .get_compute_pipeline(pipeline.cull_pipeline_id)
+        else {
+            return Ok(());
+        };
+
+        let mut pass =
+            render_context
+                .command_encoder()
+                .begin_compute_pass(&ComputePassDescriptor {
+                    label: Some("compute_shadows_pass"),
+                    timestamp_writes: None,
+                });
+        pass.set_pipeline(cull_pipeline);
+        pass.set_bind_group(0, &bind_groups.cull, &[]);
+        pass.dispatch_workgroups(buffers.num_lights.div_ceil(WORKGROUP_SIZE), 1, 1);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use bevy::{
+        render::sync_world::MainEntity,
+        tasks::{ComputeTaskPool, TaskPool},
+    };
+
+    use crate::lighting::occluder::{
+        filter_light_occluders, MaxShadowLength, Occluder2dBounds, ViewLight2d, ViewOccluder2d,
+    };
+
+    use super::*;
+
+    fn in_shadow(segments: &[ShadowSegment], light_point: Vec2, p: Vec2) -> bool {
+        segments
+            .iter()
+            .any(|segment| segment_shadows(segment, light_point, p))
+    }
+
+    fn box_segments(transform: Transform, half_size: Vec2) -> Vec<ShadowSegment> {
+        let mut segments = vec![];
+        push_occluder_segments(
+            &mut segments,
+            &GlobalTransform::from(transform),
+            &rectangle_points(half_size),
+            Occluder2dGroups::ALL,
+            LightDepth::Foreground,
+        );
+        segments
+    }
+
+    #[test]
+    fn occluder_edges_face_outwards() {
+        for transform in [
+            Transform::from_xyz(10.0, 0.0, 0.0),
+            Transform::from_xyz(10.0, 0.0, 0.0).with_scale(Vec3::new(-1.0, 1.0, 1.0)),
+            Transform::from_rotation(Quat::from_rotation_z(1.0)),
+        ] {
+            let center = transform.translation.xy();
+            for segment in box_segments(transform, Vec2::new(4.0, 2.0)) {
+                let edge = segment.b - segment.a;
+                let outward = Vec2::new(edge.y, -edge.x);
+                assert!(outward.dot(segment.a.midpoint(segment.b) - center) > 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn edges_shadow_points_behind_occluders_only() {
+        let segments = box_segments(Transform::from_xyz(20.0, 0.0, 0.0), Vec2::splat(4.0));
+        let light = Vec2::ZERO;
+
+        assert!(in_shadow(&segments, light, Vec2::new(40.0, 0.0)));
+        assert!(in_shadow(&segments, light, Vec2::new(40.0, 6.0)));
+        // in front of the occluder, beside its shadow, and inside of its body
+        assert!(!in_shadow(&segments, light, Vec2::new(10.0, 0.0)));
+        assert!(!in_shadow(&segments, light, Vec2::new(40.0, 20.0)));
+        assert!(!in_shadow(&segments, light, Vec2::new(20.0, 0.0)));
+    }
+
+    #[test]
+    fn shadows_fade_to_lit_past_max_length() {
+        // the shadow starts at x = 24
+        let segments = box_segments(Transform::from_xyz(20.0, 0.0, 0.0), Vec2::splat(4.0));
+        let shadow = |x, max_shadow_length| {
+            shadow_amount(&segments, Vec2::ZERO, Vec2::new(x, 0.0), max_shadow_length)
+        };
+
+        assert_eq!(shadow(34.0, 40.0), 1.0);
+        // fading between half of the length and the whole length
+        assert!((shadow(54.0, 40.0) - 0.5).abs() < 1e-4);
+        assert_eq!(shadow(64.0, 40.0), 0.0);
+        assert_eq!(shadow(200.0, 40.0), 0.0);
+        // nothing in front of the occluder
+        assert_eq!(shadow(10.0, 40.0), 0.0);
+
+        // no shadows at all, and shadows that never fade
+        assert_eq!(shadow(25.0, 0.0), 0.0);
+        assert_eq!(shadow(10_000.0, f32::INFINITY), 1.0);
+        let length = MaxShadowLength(f32::INFINITY).shader_length();
+        assert!(length.is_finite());
+        assert_eq!(shadow(10_000.0, length), 1.0);
+
+        // the volumetric glow shows up again where the shadow fades out
+        assert_eq!(
+            volumetric_visibility(
+                &segments,
+                Vec2::ZERO,
+                Vec2::ZERO,
+                Vec2::new(34.0, 0.0),
+                0.0,
+                40.0
+            ),
+            0.0
+        );
+        assert_eq!(
+            volumetric_visibility(
+                &segments,
+                Vec2::ZERO,
+                Vec2::ZERO,
+                Vec2::new(64.0, 0.0),
+                0.0,
+                40.0
+            ),
+            1.0
+        );
+    }
+
+    #[test]
+    fn line_lights_span_their_length() {
+        let bounds = LineLight2dBounds {
+            transform: Transform::from_xyz(5.0, 5.0, 0.0)
+                .with_rotation(Quat::from_rotation_z(std::f32::consts::FRAC_PI_2)),
+            radius: 30.0,
+            half_length: 10.0,
+        };
+        let light = shadow_light(&bounds, Occluder2dGroups::ALL, LightDepth::Background);
+        assert!(light.a.abs_diff_eq(Vec2::new(5.0, -5.0), 1e-4));
+        assert!(light.b.abs_diff_eq(Vec2::new(5.0, 15.0), 1e-4));
+        assert_eq!(light.depth, 1);
+    }
+
+    /// The volumetric visibility along a row of pixels `y` units below a point light at the
+    /// origin, from `x = -40` to `40`.
+    fn volumetric_row(segments: &[ShadowSegment], y: f32) -> Vec<f32> {
+        (0..=80)
+            .map(|x| {
+                let p = Vec2::new(x as f32 - 40.0, -y);
+                volumetric_visibility(segments, Vec2::ZERO, Vec2::ZERO, p, 1.0, f32::INFINITY)
+            })
+            .collect()
+    }
+
+    /// The number of separate runs of lit pixels in `row`.
+    fn count_shafts(row: &[f32]) -> usize {
+        let lit: Vec<bool> = row.iter().map(|visibility| *visibility > 0.5).collect();
+        lit.windows(2).filter(|pair| !pair[0] && pair[1]).count() + usize::from(lit[0])
+    }
+
+    #[test]
+    fn slatted_wall_splits_glow_into_shafts() {
+        // 6 units wide slats with 2 unit gaps, 10 units below the light
+        let segments: Vec<ShadowSegment> = (-3..=3)
+            .flat_map(|i| {
+                box_segments(
+                    Transform::from_xyz(i as f32 * 8.0, -10.0, 0.0),
+                    Vec2::new(3.0, 0.5),
+                )
+            })
+            .collect();
+
+        let row = volumetric_row(&segments, 20.0);
+        // a shaft through each of the gaps that isn't too steep to see through the slats
+        assert_eq!(count_shafts(&row), 4);
+        // under the middle of a slat and of a gap
+        assert_eq!(row[40], 0.0);
+        assert_eq!(row[48], 1.0);
+    }
+
+    #[test]
+    fn thin_slits_dim_their_shafts() {
+        let gap = 0.2;
+        let half_width = 20.0 - gap / 2.0;
+        let segments: Vec<ShadowSegment> = [-1.0, 1.0]
+            .into_iter()
+            .flat_map(|side| {
+                box_segments(
+                    Transform::from_xyz(side * (half_width + gap / 2.0), -10.0, 0.0),
+                    Vec2::new(half_width, 1.0),
+                )
+            })
+            .collect();
+
+        // the shaft through the slit is thinner than a pixel, but still shows up dimmed
+        let row = volumetric_row(&segments, 20.0);
+        let brightest = row.iter().copied().fold(0.0, f32::max);
+        assert!(brightest > 0.0 && brightest < 1.0);
+        assert_eq!(row[30], 0.0);
+    }
+
+    /// A grid of 100 lights 64 units apart and a grid of 200 occluders.
+    fn shadow_preparation_scene() -> (
+        Vec<LineLight2dBounds>,
+        Vec<(GlobalTransform, Occluder2dBounds)>,
+    ) {
+        let lights = (0..100)
+            .map(|i| LineLight2dBounds {
+                transform: Transform::from_xyz((i % 10) as f32 * 64.0, (i / 10) as f32 * 64.0, 0.0),
+                radius: 96.0,
+                half_length: 0.0,
+            })
+            .collect();
+        let occluders = (0..200)
+            .map(|i| {
+                let transform =
+                    Transform::from_xyz((i % 20) as f32 * 32.0, (i / 20) as f32 * 64.0, 0.0);
+                (
+                    GlobalTransform::from(transform),
+                    Occluder2dBounds {
+                        transform,
+                        half_size: Vec2::new(8.0, 4.0),
+                    },
+                )
+            })
+            .collect();
+        (lights, occluders)
+    }
+
+    #[test]
+    fn compute_shadows_queue_less_work_at_100_lights() {
+        let (lights, occluders) = shadow_preparation_scene();
+
+        // the stencil path draws the shadow and the cutout of every occluder near every light
+        let stencil_draws: usize = lights
+            .iter()
+            .map(|light| {
+                occluders
+                    .iter()
+                    .filter(|(_, bounds)| bounds.visible_from_line_light(light))
+                    .count()
+                    * 2
+            })
+            .sum();
+
+        // the compute path uploads every edge and light once
+        let mut segments = vec![];
+        for (transform, bounds) in occluders.iter() {
+            push_occluder_segments(
+                &mut segments,
+                transform,
+                &rectangle_points(bounds.half_size),
+                Occluder2dGroups::ALL,
+                LightDepth::Foreground,
+            );
+        }
+        let shadow_lights: Vec<ShadowLight> = lights
+            .iter()
+            .map(|light| shadow_light(light, Occluder2dGroups::ALL, LightDepth::Foreground))
+            .collect();
+
+        assert_eq!(segments.len(), 800);
+        assert!(stencil_draws > segments.len() + shadow_lights.len());
+        // and every light's edges fit in its list, so none of them fall back to the stencil
+        assert!(shadow_lights
+            .iter()
+            .all(|light| !light_overflows_segments(light, &segments)));
+    }
+
+    #[test]
+    fn lights_with_too_many_edges_fall_back_to_stencil() {
+        let light = ShadowLight {
+            a: Vec2::ZERO,
+            b: Vec2::ZERO,
+            radius: 100.0,
+            groups: Occluder2dGroups::ALL.0,
+            depth: light_depth_index(LightDepth::Foreground),
+        };
+        let box_at = |i: u32| {
+            box_segments(
+                Transform::from_xyz((i % 16) as f32 * 8.0 - 64.0, (i / 16) as f32 * 8.0, 0.0),
+                Vec2::splat(2.0),
+            )
+        };
+        let mut segments: Vec<ShadowSegment> =
+            (0..MAX_LIGHT_SEGMENTS / 4).flat_map(box_at).collect();
+        assert!(!light_overflows_segments(&light, &segments));
+
+        // far away, in other groups or at another depth doesn't count
+        let mut far = box_segments(Transform::from_xyz(500.0, 0.0, 0.0), Vec2::splat(2.0));
+        let mut other_groups = box_at(0);
+        let mut background = box_at(1);
+        for segment in other_groups.iter_mut() {
+            segment.groups = 0;
+        }
+        for segment in background.iter_mut() {
+            segment.depth = light_depth_index(LightDepth::Background);
+        }
+        segments.append(&mut far);
+        segments.append(&mut other_groups);
+        segments.append(&mut background);
+        assert!(!light_overflows_segments(&light, &segments));
+
+        segments.extend(box_at(MAX_LIGHT_SEGMENTS / 4));
+        assert!(light_overflows_segments(&light, &segments));
+    }
+
+    /// Compares the CPU work of shadowing 100 lights with 200 occluders each frame. The stencil
+    /// path queues a shadow draw for every occluder near each light, while [`ComputeShadows`]
+    /// only uploads the edges of the occluders and the lights, and checks that no light has too
+    /// many edges for its list. Run with
+    /// `cargo test --release bench_compute_shadows -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_compute_shadows() {
+        const ITERATIONS: u32 = 200;
+        const OCCLUDER_HALF_SIZE: Vec2 = Vec2::new(8.0, 4.0);
+        ComputeTaskPool::get_or_init(TaskPool::default);
+        let entity = (Entity::PLACEHOLDER, MainEntity::from(Entity::PLACEHOLDER));
+
+        let light_bounds: Vec<LineLight2dBounds> = (0..100)
+            .map(|i| LineLight2dBounds {
+                transform: Transform::from_xyz(
+                    (i % 10) as f32 * 96.0 + 24.0,
+                    (i / 10) as f32 * 48.0 + 24.0,
+                    0.0,
+                ),
+                radius: 64.0,
+                half_length: 8.0,
+            })
+            .collect();
+        let view_lights = |compute_shadows: bool| -> Vec<ViewLight2d> {
+            light_bounds
+                .iter()
+                .map(|bounds| ViewLight2d {
+                    entity,
+                    bounds: *bounds,
+                    groups: Occluder2dGroups::ALL,
+                    // background occluders are never batched, so each of them is drawn alone
+                    depth: LightDepth::Background,
+                    compute_shadows,
+                })
+                .collect()
+        };
+        let occluder_transforms: Vec<Transform> = (0..200)
+            .map(|i| Transform::from_xyz((i % 20) as f32 * 48.0, (i / 20) as f32 * 48.0, 0.0))
+            .collect();
+        let occluders: Vec<ViewOccluder2d> = occluder_transforms
+            .iter()
+            .map(|transform| ViewOccluder2d {
+                entity,
+                bounds: Occluder2dBounds {
+                    transform: *transform,
+                    half_size: OCCLUDER_HALF_SIZE,
+                },
+                groups: Occluder2dGroups::ALL,
+                depth: LightDepth::Background,
+                alpha_masked: false,
+            })
+            .collect();
+
+        let stencil_lights = view_lights(false);
+        let start = Instant::now();
+        let mut stencil_draws = 0;
+        for _ in 0..ITERATIONS {
+            stencil_draws = filter_light_occluders(&stencil_lights, &occluders)
+                .iter()
+                .map(Vec::len)
+                .sum::<usize>();
+        }
+        let stencil = start.elapsed() / ITERATIONS;
+
+        let compute_lights = view_lights(true);
+        let start = Instant::now();
+        let mut compute_draws = 0;
+        let mut fallbacks = 0;
+        for _ in 0..ITERATIONS {
+            // the edges are only uploaded again when an occluder changes, this is the worst case
+            let mut segments = vec![];
+            for transform in occluder_transforms.iter() {
+                push_occluder_segments(
+                    &mut segments,
+                    &GlobalTransform::from(*transform),
+                    &rectangle_points(OCCLUDER_HALF_SIZE),
+                    Occluder2dGroups::ALL,
+                    LightDepth::Background,
+                );
+            }
+            fallbacks = light_bounds
+                .iter()
+                .map(|bounds| shadow_light(bounds, Occluder2dGroups::ALL, LightDepth::Background))
+                .filter(|light| light_overflows_segments(light, &segments))
+                .count();
+            compute_draws = filter_light_occluders(&compute_lights, &occluders)
+                .iter()
+                .map(Vec::len)
+                .sum::<usize>();
+        }
+        let compute = start.elapsed() / ITERATIONS;
+
+        assert!(stencil_draws > 0);
+        assert_eq!((compute_draws, fallbacks), (0, 0));
+        println!(
+            "stencil: {:?} per frame queuing {} shadow draws, compute: {:?} per frame queuing {}",
+            stencil, stencil_draws, compute, compute_draws
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, DiagnosticsStore, RegisterDiagnostic},
+    prelude::*,
+    render::view::{VisibilitySystems, VisibleEntities},
+    utils::HashSet,
+};
+
+use super::{AmbientLight2d, LineLight2d, Occluder2d};
+
+/// The name of the GPU time span around the deferred lighting pass, see
+/// [`DeferredLightingNode`](super::render::DeferredLightingNode).
+pub const LIGHT_PASS_SPAN: &str = "deferred_lighting";
+
+/// The GPU time of the deferred lighting pass, recorded while Bevy's `RenderDiagnosticsPlugin` is
+/// added and the GPU supports timestamp queries.
+const LIGHT_PASS_GPU_TIME: DiagnosticPath =
+    DiagnosticPath::const_new("render/deferred_lighting/elapsed_gpu");
+
+pub const LIGHTS: DiagnosticPath = DiagnosticPath::const_new("lighting/lights");
+pub const DRAWN_LIGHTS: DiagnosticPath = DiagnosticPath::const_new("lighting/drawn_lights");
+pub const CULLED_LIGHTS: DiagnosticPath = DiagnosticPath::const_new("lighting/culled_lights");
+pub const OCCLUDERS: DiagnosticPath = DiagnosticPath::const_new("lighting/occluders");
+
+/// [`Plugin`] for the [`LightingDiagnostics`], which also registers them with Bevy's diagnostics,
+/// so they are logged and shown alongside the frame time.
+pub struct LightingDiagnosticsPlugin;
+
+impl Plugin for LightingDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LightingDiagnostics>()
+            .register_diagnostic(Diagnostic::new(LIGHTS))
+            .register_diagnostic(Diagnostic::new(DRAWN_LIGHTS))
+            .register_diagnostic(Diagnostic::new(CULLED_LIGHTS))
+            .register_diagnostic(Diagnostic::new(OCCLUDERS))
+            .add_systems(
+                PostUpdate,
+                update_lighting_diagnostics.after(VisibilitySystems::CheckVisibility),
+            );
+    }
+}
+
+/// [`Resource`] with what the lighting drew this frame. A light is drawn if any camera with an
+/// [`AmbientLight2d`] sees it, and culled otherwise, so `drawn_lights + culled_lights` is always
+/// `lights`. While [`GlowSprites`](super::GlowSprites) are on no camera is lit, so every light
+/// counts as culled.
+#[derive(Resource, Default, Clone, Copy, Debug, PartialEq)]
+pub struct LightingDiagnostics {
+    pub lights: usize,
+    pub drawn_lights: usize,
+    pub culled_lights: usize,
+    /// The [`Occluder2d`]s seen by a lit camera, which are the ones that can cast shadows
+    pub occluders: usize,
+    /// The smoothed GPU time of the lighting pass in milliseconds, if it is being measured
+    pub light_pass_ms: Option<f64>,
+}
+
+/// [`System`] that updates the [`LightingDiagnostics`] from what lit cameras see after visibility
+/// is checked, which is what the lighting pass draws.
+pub fn update_lighting_diagnostics(
+    q_cameras: Query<&VisibleEntities, With<AmbientLight2d>>,
+    q_lights: Query<(), With<LineLight2d>>,
+    q_occluders: Query<(), With<Occluder2d>>,
+    store: Option<Res<DiagnosticsStore>>,
+    mut lighting_diagnostics: ResMut<LightingDiagnostics>,
+    mut diagnostics: Diagnostics,
+) {
+    let mut drawn = HashSet::new();
+    let mut occluders = HashSet::new();
+    for visible in q_cameras.iter() {
+        drawn.extend(
+            visible
+                .iter::<With<LineLight2d>>()
+                .filter(|light| q_lights.contains(**light)),
+        );
+        occluders.extend(
+            visible
+                .iter::<With<Occluder2d>>()
+                .filter(|occluder| q_occluders.contains(**occluder)),
+        );
+    }
+
+    let lights = q_lights.iter().count();
+    *lighting_diagnostics = LightingDiagnostics {
+        lights,
+        drawn_lights: drawn.len(),
+        culled_lights: lights - drawn.len(),
+        occluders: occluders.len(),
+        light_pass_ms: store.and_then(|store| store.get(&LIGHT_PASS_GPU_TIME)?.smoothed()),
+    };
+
+    diagnostics.add_measurement(&LIGHTS, || lighting_diagnostics.lights as f64);
+    diagnostics.add_measurement(&DRAWN_LIGHTS, || lighting_diagnostics.drawn_lights as f64);
+    diagnostics.add_measurement(&CULLED_LIGHTS, || lighting_diagnostics.culled_lights as f64);
+    diagnostics.add_measurement(&OCCLUDERS, || lighting_diagnostics.occluders as f64);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drawn_and_culled_lights_add_up() {
+        let mut app = App::new();
+        app.add_plugins(LightingDiagnosticsPlugin);
+
+        let light = || LineLight2d::point(Vec4::ONE, 20.0, 0.0);
+        let lights = [(); 4].map(|_| app.world_mut().spawn(light()).id());
+        let occluder = app.world_mut().spawn(Occluder2d::new(10.0, 10.0)).id();
+
+        // both lit cameras see the second light
+        let mut visible = VisibleEntities::default();
+        visible.push::<With<LineLight2d>>(lights[0]);
+        visible.push::<With<LineLight2d>>(lights[1]);
+        visible.push::<With<Occluder2d>>(occluder);
+        app.world_mut()
+            .spawn((AmbientLight2d { color: Vec4::ONE }, visible));
+        let mut visible = VisibleEntities::default();
+        visible.push::<With<LineLight2d>>(lights[1]);
+        visible.push::<With<LineLight2d>>(lights[2]);
+        app.world_mut()
+            .spawn((AmbientLight2d { color: Vec4::ONE }, visible));
+        // cameras without lighting don't draw lights
+        let mut visible = VisibleEntities::default();
+        visible.push::<With<LineLight2d>>(lights[3]);
+        app.world_mut().spawn(visible);
+
+        app.update();
+        let stats = *app.world().resource::<LightingDiagnostics>();
+        assert_eq!(stats.lights, 4);
+        assert_eq!(stats.drawn_lights, 3);
+        assert_eq!(stats.culled_lights, 1);
+        assert_eq!(stats.drawn_lights + stats.culled_lights, stats.lights);
+        assert_eq!(stats.occluders, 1);
+        assert_eq!(stats.light_pass_ms, None);
+        let store = app.world().resource::<DiagnosticsStore>();
+        assert_eq!(store.get(&CULLED_LIGHTS).unwrap().value(), Some(1.0));
+
+        // a light despawned after visibility was checked isn't drawn
+        app.world_mut().despawn(lights[2]);
+        app.update();
+        let stats = *app.world().resource::<LightingDiagnostics>();
+        assert_eq!(stats.lights, 3);
+        assert_eq!(stats.drawn_lights, 2);
+        assert_eq!(stats.drawn_lights + stats.culled_lights, stats.lights);
+    }
+}
@@ -0,0 +1,75 @@
+use bevy::{core_pipeline::tonemapping::DebandDither, prelude::*};
+
+use super::AmbientLight2d;
+
+pub struct LightingDitherPlugin;
+
+impl Plugin for LightingDitherPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LightingDither>()
+            .add_systems(PostUpdate, sync_lighting_dither);
+    }
+}
+
+/// [`Resource`] that turns dithering on and off for cameras with an [`AmbientLight2d`]. The
+/// dithering is done by the tonemapping pass, right before the HDR image is written to the 8-bit
+/// screen, so the falloff of large dim lights doesn't show visible bands. The noise is a fraction
+/// of one 8-bit step, so it only shows up in smooth gradients.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct LightingDither(pub bool);
+
+impl Default for LightingDither {
+    fn default() -> Self {
+        LightingDither(true)
+    }
+}
+
+/// [`System`] that sets the [`DebandDither`] of every lit camera from [`LightingDither`].
+pub fn sync_lighting_dither(
+    mut q_cameras: Query<&mut DebandDither, With<AmbientLight2d>>,
+    dither: Res<LightingDither>,
+) {
+    let deband_dither = if dither.0 {
+        DebandDither::Enabled
+    } else {
+        DebandDither::Disabled
+    };
+    for mut camera_dither in q_cameras.iter_mut() {
+        camera_dither.set_if_neq(deband_dither);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dither_follows_resource() {
+        let mut app = App::new();
+        app.init_resource::<LightingDither>()
+            .add_systems(Update, sync_lighting_dither);
+
+        let lit = app
+            .world_mut()
+            .spawn((AmbientLight2d { color: Vec4::ONE }, DebandDither::Disabled))
+            .id();
+        let unlit = app.world_mut().spawn(DebandDither::Disabled).id();
+
+        app.update();
+        assert_eq!(
+            app.world().get::<DebandDither>(lit),
+            Some(&DebandDither::Enabled)
+        );
+        assert_eq!(
+            app.world().get::<DebandDither>(unlit),
+            Some(&DebandDither::Disabled)
+        );
+
+        app.insert_resource(LightingDither(false));
+        app.update();
+        assert_eq!(
+            app.world().get::<DebandDither>(lit),
+            Some(&DebandDither::Disabled)
+        );
+    }
+}
@@ -0,0 +1,369 @@
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::MeshVertexBufferLayoutRef,
+        render_asset::RenderAssetUsages,
+        render_resource::{
+            AsBindGroup, BlendComponent, BlendFactor, BlendOperation, BlendState, Extent3d,
+            RenderPipelineDescriptor, ShaderRef, SpecializedMeshPipelineError, TextureDimension,
+            TextureFormat,
+        },
+    },
+    sprite::{AlphaMode2d, Material2d, Material2dKey, Material2dPlugin},
+};
+
+use super::{AmbientLight2d, LineLight2d};
+
+/// The path to the shader used by the [`GlowSpriteMaterial`]
+const GLOW_SPRITE_SHADER_PATH: &str = "shaders/lighting/glow_sprite.wgsl";
+
+/// The width and height of the [`glow_sprite_image`], in pixels.
+const GLOW_SPRITE_IMAGE_SIZE: u32 = 64;
+
+pub struct GlowSpritePlugin;
+
+impl Plugin for GlowSpritePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(Material2dPlugin::<GlowSpriteMaterial>::default())
+            .init_resource::<GlowSprites>()
+            .add_systems(Startup, load_glow_sprite_assets)
+            .add_systems(
+                PostUpdate,
+                (
+                    sync_glow_sprite_cameras,
+                    spawn_light_glow_sprites,
+                    update_light_glow_sprites,
+                )
+                    .chain()
+                    .before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+/// [`Resource`] that swaps the deferred lighting for additive sprites, for GPUs too slow to draw
+/// it. Every [`LineLight2d`] gets a [`LightGlowSprite`] with a radial gradient in its color and
+/// range instead, which is added onto the scene like light, and lit cameras stop darkening the
+/// level with their [`AmbientLight2d`]. There are no shadows, normal maps or volumetric glow in
+/// this mode. See `glow_sprites` in the `lighting_config` section of `Lightborne.toml`.
+#[derive(Resource, Clone, Copy, Default, Debug)]
+pub struct GlowSprites(pub bool);
+
+/// [`Resource`] holding the gradient and the quad every [`LightGlowSprite`] is drawn with.
+#[derive(Resource)]
+pub struct GlowSpriteAssets {
+    pub image: Handle<Image>,
+    pub mesh: Handle<Mesh>,
+}
+
+/// [`Component`] for the sprite drawn in place of a light while [`GlowSprites`] is on, a child of
+/// the light. It is a unit quad scaled to the light's range, drawn with its own
+/// [`GlowSpriteMaterial`].
+#[derive(Component, Debug)]
+pub struct LightGlowSprite;
+
+/// Custom [`Material2d`] for [`LightGlowSprite`]s, which adds the gradient in `color` onto the
+/// scene instead of blending over it, so overlapping glows brighten each other like lights do.
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+pub struct GlowSpriteMaterial {
+    #[uniform(0)]
+    pub color: LinearRgba,
+    #[texture(1)]
+    #[sampler(2)]
+    pub image: Handle<Image>,
+}
+
+impl Material2d for GlowSpriteMaterial {
+    fn fragment_shader() -> ShaderRef {
+        GLOW_SPRITE_SHADER_PATH.into()
+    }
+
+    // drawn in the transparent phase, after everything it lights up
+    fn alpha_mode(&self) -> AlphaMode2d {
+        AlphaMode2d::Blend
+    }
+
+    fn specialize(
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayoutRef,
+        _key: Material2dKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        if let Some(target) = descriptor
+            .fragment
+            .as_mut()
+            .and_then(|fragment| fragment.targets[0].as_mut())
+        {
+            target.blend = Some(BlendState {
+                color: BlendComponent {
+                    src_factor: BlendFactor::One,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+                alpha: BlendComponent {
+                    src_factor: BlendFactor::Zero,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+            });
+        }
+        Ok(())
+    }
+}
+
+/// [`Component`] for lights with a [`LightGlowSprite`], holding the sprite's entity.
+#[derive(Component, Debug)]
+pub struct LightGlow(pub Entity);
+
+/// [`Component`] holding the [`AmbientLight2d`] of a lit camera while [`GlowSprites`] is on, so
+/// it can be put back when the mode is turned off.
+#[derive(Component, Debug)]
+pub struct StashedAmbientLight2d(pub AmbientLight2d);
+
+/// A white radial gradient that fades out towards its edge the same way a [`LineLight2d`] falls
+/// off, see [`LineLight2d::radial_fall_off`].
+pub fn glow_sprite_image(size: u32) -> Image {
+    let center = size as f32 / 2.0;
+    let data = (0..size * size)
+        .flat_map(|i| {
+            let pos = Vec2::new((i % size) as f32 + 0.5, (i / size) as f32 + 0.5);
+            let distance = pos.distance(Vec2::splat(center)) / center;
+            let alpha = LineLight2d::radial_fall_off(distance, None);
+            [255, 255, 255, (alpha * 255.0).round() as u8]
+        })
+        .collect();
+    Image::new(
+        Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+}
+
+/// The color a [`LightGlowSprite`] adds at the center of `light`, its color scaled by its
+/// intensity. The glow is added onto the HDR scene, so it can be brighter than white.
+pub fn glow_sprite_color(light: &LineLight2d) -> LinearRgba {
+    let color = light.shaded_color();
+    let rgb = color.truncate() * color.w.max(0.0);
+    LinearRgba::rgb(rgb.x, rgb.y, rgb.z)
+}
+
+pub fn load_glow_sprite_assets(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    commands.insert_resource(GlowSpriteAssets {
+        image: images.add(glow_sprite_image(GLOW_SPRITE_IMAGE_SIZE)),
+        mesh: meshes.add(Rectangle::new(1.0, 1.0)),
+    });
+}
+
+/// [`System`] that stashes the [`AmbientLight2d`] of lit cameras while [`GlowSprites`] is on,
+/// which turns the deferred lighting off for them, and puts it back once it is turned off. Cameras
+/// spawned while it is on are stashed too.
+pub fn sync_glow_sprite_cameras(
+    mut commands: Commands,
+    q_lit: Query<(Entity, &AmbientLight2d)>,
+    q_stashed: Query<(Entity, &StashedAmbientLight2d)>,
+    glow_sprites: Res<GlowSprites>,
+) {
+    if glow_sprites.0 {
+        for (camera, ambient) in q_lit.iter() {
+            commands
+                .entity(camera)
+                .remove::<AmbientLight2d>()
+                .insert(StashedAmbientLight2d(*ambient));
+        }
+    } else if glow_sprites.is_changed() {
+        for (camera, stashed) in q_stashed.iter() {
+            commands
+                .entity(camera)
+                .remove::<StashedAmbientLight2d>()
+                .insert(stashed.0);
+        }
+    }
+}
+
+/// [`System`] that gives every [`LineLight2d`] a [`LightGlowSprite`] while [`GlowSprites`] is on,
+/// and removes them once it is turned off.
+pub fn spawn_light_glow_sprites(
+    mut commands: Commands,
+    q_lights: Query<Entity, (With<LineLight2d>, Without<LightGlow>)>,
+    q_glows: Query<(Entity, &LightGlow)>,
+    glow_sprites: Res<GlowSprites>,
+    assets: Option<Res<GlowSpriteAssets>>,
+    mut materials: ResMut<Assets<GlowSpriteMaterial>>,
+) {
+    if !glow_sprites.0 {
+        if glow_sprites.is_changed() {
+            for (light, glow) in q_glows.iter() {
+                commands.entity(glow.0).despawn_recursive();
+                commands.entity(light).remove::<LightGlow>();
+            }
+        }
+        return;
+    }
+    let Some(assets) = assets else {
+        return;
+    };
+    for light in q_lights.iter() {
+        let material = materials.add(GlowSpriteMaterial {
+            color: LinearRgba::BLACK,
+            image: assets.image.clone(),
+        });
+        let glow = commands
+            .spawn((
+                LightGlowSprite,
+                Mesh2d(assets.mesh.clone()),
+                MeshMaterial2d(material),
+            ))
+            .set_parent(light)
+            .id();
+        commands.entity(light).insert(LightGlow(glow));
+    }
+}
+
+/// [`System`] that sizes and colors every [`LightGlowSprite`] to match its light. Lights are drawn
+/// without their scale, like beam segments that are stretched to their length, so the sprite
+/// undoes the scale of the light. Materials are only touched when their color changes, so still
+/// lights don't have their material prepared again every frame.
+pub fn update_light_glow_sprites(
+    q_lights: Query<(&LineLight2d, &GlobalTransform, &LightGlow)>,
+    mut q_glows: Query<
+        (&MeshMaterial2d<GlowSpriteMaterial>, &mut Transform),
+        With<LightGlowSprite>,
+    >,
+    mut materials: ResMut<Assets<GlowSpriteMaterial>>,
+) {
+    for (light, light_transform, glow) in q_lights.iter() {
+        let Ok((material, mut transform)) = q_glows.get_mut(glow.0) else {
+            continue;
+        };
+        let color = glow_sprite_color(light);
+        if materials.get(&material.0).is_some_and(|m| m.color != color) {
+            if let Some(material) = materials.get_mut(&material.0) {
+                material.color = color;
+            }
+        }
+
+        let size = Vec2::new(light.half_length + light.radius, light.radius) * 2.0;
+        let scale = light_transform.compute_transform().scale;
+        let inverse_scale = Vec3::select(scale.cmpne(Vec3::ZERO), scale.recip(), Vec3::ONE);
+        // just in front of the light, so it isn't hidden behind what it is attached to
+        transform.set_if_neq(
+            Transform::from_xyz(0.0, 0.0, 0.5).with_scale(inverse_scale * size.extend(1.0)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glow_app() -> App {
+        let mut app = App::new();
+        app.init_resource::<Assets<Image>>()
+            .init_resource::<Assets<Mesh>>()
+            .init_resource::<Assets<GlowSpriteMaterial>>()
+            .init_resource::<GlowSprites>()
+            .add_systems(Startup, load_glow_sprite_assets)
+            .add_systems(
+                Update,
+                (
+                    sync_glow_sprite_cameras,
+                    spawn_light_glow_sprites,
+                    update_light_glow_sprites,
+                )
+                    .chain(),
+            );
+        app
+    }
+
+    #[test]
+    fn glow_sprites_replace_lights() {
+        let mut app = glow_app();
+        let camera = app
+            .world_mut()
+            .spawn(AmbientLight2d {
+                color: Vec4::new(0.5, 0.5, 0.5, 0.2),
+            })
+            .id();
+        let lamp = app
+            .world_mut()
+            .spawn((
+                LineLight2d::point(Vec4::new(1.0, 0.5, 0.0, 0.8), 40.0, 0.0),
+                GlobalTransform::from_xyz(30.0, 40.0, 0.0),
+            ))
+            .id();
+        // a beam segment, stretched to its length
+        let mut beam_light = LineLight2d::point(Vec4::ONE, 10.0, 0.0);
+        beam_light.half_length = 25.0;
+        let beam = app
+            .world_mut()
+            .spawn((
+                beam_light,
+                GlobalTransform::from_scale(Vec3::new(50.0, 1.0, 1.0)),
+            ))
+            .id();
+        let glow = |app: &App, light: Entity| {
+            let glow = app.world().get::<LightGlow>(light)?.0;
+            assert_eq!(app.world().get::<Parent>(glow).unwrap().get(), light);
+            let material = app
+                .world()
+                .get::<MeshMaterial2d<GlowSpriteMaterial>>(glow)?;
+            let materials = app.world().resource::<Assets<GlowSpriteMaterial>>();
+            let color = materials.get(&material.0).unwrap().color;
+            let transform = app.world().get::<Transform>(glow).unwrap();
+            Some((color, *transform))
+        };
+
+        app.update();
+        assert!(glow(&app, lamp).is_none());
+        assert!(app.world().get::<AmbientLight2d>(camera).is_some());
+
+        app.insert_resource(GlowSprites(true));
+        app.update();
+        app.update();
+        let (color, transform) = glow(&app, lamp).unwrap();
+        // scaled by the intensity, since the glow is added onto the scene
+        assert_eq!(color, LinearRgba::rgb(0.8, 0.4, 0.0));
+        // centered on the light, and as big as its range
+        assert_eq!(transform.translation.xy(), Vec2::ZERO);
+        assert_eq!(transform.scale, Vec3::new(80.0, 80.0, 1.0));
+        let (color, transform) = glow(&app, beam).unwrap();
+        assert_eq!(color, LinearRgba::rgb(1.0, 1.0, 1.0));
+        assert!(transform
+            .scale
+            .abs_diff_eq(Vec3::new(70.0 / 50.0, 20.0, 1.0), 1e-5));
+        assert!(app.world().get::<AmbientLight2d>(camera).is_none());
+
+        app.insert_resource(GlowSprites(false));
+        app.update();
+        assert!(glow(&app, lamp).is_none());
+        assert_eq!(
+            app.world_mut()
+                .query::<&LightGlowSprite>()
+                .iter(app.world())
+                .count(),
+            0
+        );
+        assert_eq!(
+            app.world().get::<AmbientLight2d>(camera).unwrap().color,
+            Vec4::new(0.5, 0.5, 0.5, 0.2)
+        );
+    }
+
+    #[test]
+    fn glow_fades_out_to_its_edge() {
+        let image = glow_sprite_image(16);
+        let alpha = |x, y| image.get_color_at(x, y).unwrap().alpha();
+        assert!(alpha(8, 8) > 0.75);
+        assert!(alpha(12, 8) < alpha(8, 8));
+        assert_eq!(alpha(0, 0), 0.0);
+        assert_eq!(alpha(15, 8), alpha(0, 8));
+    }
+}
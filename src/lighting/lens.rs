@@ -0,0 +1,112 @@
+use bevy::{
+    prelude::*,
+    render::{
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        Render, RenderApp, RenderSet,
+    },
+};
+
+use super::line_light::ExtractLineLight2d;
+
+/// How far out from its center a [`LensFocus`] keeps its full strength, as a fraction of its
+/// radius. Must match `LENS_EDGE_START` in `functions.wgsl`.
+pub const LENS_EDGE_START: f32 = 0.7;
+
+/// [`Plugin`] that brightens lights seen through the [`LensFocus`].
+pub struct LensFocusPlugin;
+
+impl Plugin for LensFocusPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(ExtractResourcePlugin::<LensFocus>::default())
+            .init_resource::<LensFocus>();
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app.add_systems(
+            Render,
+            prepare_line_light_2d_lens_focus.in_set(RenderSet::Queue),
+        );
+    }
+}
+
+/// [`Resource`] for a lens between the lights and the camera, like the one the player can hold.
+/// Light seen through the circle of `radius` world units around `center` is multiplied by `gain`,
+/// fading back to normal towards the edge of the circle. The camera is orthographic, so the
+/// circle in the world is also exactly what the lens covers on the screen. A `radius` of 0 turns
+/// the lens off.
+#[derive(Resource, ExtractResource, Clone, Copy, Debug, PartialEq)]
+pub struct LensFocus {
+    pub center: Vec2,
+    pub radius: f32,
+    pub gain: f32,
+}
+
+impl Default for LensFocus {
+    fn default() -> Self {
+        LensFocus {
+            center: Vec2::ZERO,
+            radius: 0.0,
+            gain: 1.0,
+        }
+    }
+}
+
+impl LensFocus {
+    /// The lens as passed to the shaders, as its center, radius and gain.
+    pub fn shader_lens(&self) -> Vec4 {
+        Vec4::new(
+            self.center.x,
+            self.center.y,
+            self.radius.max(0.0),
+            self.gain,
+        )
+    }
+
+    /// How much the light at `point` is multiplied by. Mirrors `lens_gain` in `functions.wgsl`.
+    pub fn gain_at(&self, point: Vec2) -> f32 {
+        if self.radius <= 0.0 {
+            return 1.0;
+        }
+        let distance = point.distance(self.center) / self.radius;
+        let t = ((distance - LENS_EDGE_START) / (1.0 - LENS_EDGE_START)).clamp(0.0, 1.0);
+        let edge = t * t * (3.0 - 2.0 * t);
+        1.0 + (self.gain - 1.0) * (1.0 - edge)
+    }
+}
+
+/// [`System`] that gives every light the [`LensFocus`], which it is drawn with.
+pub fn prepare_line_light_2d_lens_focus(
+    mut q_lights: Query<&mut ExtractLineLight2d>,
+    lens: Res<LensFocus>,
+) {
+    let lens = lens.shader_lens();
+    q_lights
+        .par_iter_mut()
+        .for_each(|mut light| light.lens = lens);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lens_brightens_lights_seen_through_it() {
+        let lens = LensFocus {
+            center: Vec2::new(100.0, 50.0),
+            radius: 20.0,
+            gain: 2.0,
+        };
+        assert_eq!(lens.gain_at(Vec2::new(100.0, 50.0)), 2.0);
+        assert_eq!(lens.gain_at(Vec2::new(110.0, 50.0)), 2.0);
+        // fades out towards the edge
+        let edge = lens.gain_at(Vec2::new(100.0, 67.0));
+        assert!(edge > 1.0 && edge < 2.0);
+        // and leaves the rest of the screen alone
+        assert_eq!(lens.gain_at(Vec2::new(100.0, 70.0)), 1.0);
+        assert_eq!(lens.gain_at(Vec2::ZERO), 1.0);
+
+        assert_eq!(LensFocus::default().gain_at(Vec2::ZERO), 1.0);
+    }
+}
@@ -0,0 +1,419 @@
+use bevy::{
+    core_pipeline::fullscreen_vertex_shader::fullscreen_shader_vertex_state,
+    prelude::*,
+    render::{
+        camera::ExtractedCamera,
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        render_resource::{
+            binding_types::{sampler, texture_2d},
+            *,
+        },
+        renderer::{RenderContext, RenderDevice},
+        texture::{CachedTexture, TextureCache},
+        view::ViewTarget,
+        Render, RenderApp, RenderSet,
+    },
+};
+
+use super::{render::PostProcessRes, AmbientLight2d};
+
+pub struct LightBufferPlugin;
+
+impl Plugin for LightBufferPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LightBufferScale>()
+            .add_plugins(ExtractResourcePlugin::<LightBufferScale>::default());
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .add_systems(
+                Render,
+                prepare_light_buffer_textures.in_set(RenderSet::PrepareResources),
+            )
+            .add_systems(
+                Render,
+                prepare_light_buffer_bind_groups.in_set(RenderSet::PrepareBindGroups),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app.init_resource::<LightBufferPipeline>();
+    }
+}
+
+/// [`Resource`] holding the divisor of the resolution lights are drawn at. Above 1, lights are
+/// drawn into a light buffer that is `1 / scale` of the screen in each direction, which is then
+/// upsampled and multiplied with the full resolution image. Light falls off smoothly, so at a
+/// scale of 2 the light passes fill a quarter of the pixels while looking almost the same. Normal
+/// mapping and hard shadow edges are resolved at the lower resolution, so they get slightly
+/// softer. See the `lighting_config.light_buffer_scale` setting of `Lightborne.toml`.
+#[derive(Resource, ExtractResource, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LightBufferScale(pub u32);
+
+impl Default for LightBufferScale {
+    fn default() -> Self {
+        LightBufferScale(1)
+    }
+}
+
+impl LightBufferScale {
+    /// Whether lights are drawn into the light buffer instead of straight onto the screen.
+    pub fn is_downsampled(&self) -> bool {
+        self.0 > 1
+    }
+}
+
+/// The size of the light buffer for a view of size `target_size`, rounded up so the buffer covers
+/// the whole view.
+pub fn light_buffer_size(target_size: UVec2, scale: LightBufferScale) -> UVec2 {
+    let scale = scale.0.max(1);
+    ((target_size + UVec2::splat(scale - 1)) / scale).max(UVec2::ONE)
+}
+
+/// Render world [`Component`] holding the light buffer of a view and the stencil buffer used to
+/// draw shadows at its resolution.
+#[derive(Component)]
+pub struct LightBufferTextures {
+    pub light: CachedTexture,
+    pub stencil: CachedTexture,
+}
+
+pub fn prepare_light_buffer_textures(
+    mut commands: Commands,
+    mut texture_cache: ResMut<TextureCache>,
+    render_device: Res<RenderDevice>,
+    scale: Res<LightBufferScale>,
+    views: Query<(Entity, &ExtractedCamera), (With<Camera2d>, With<AmbientLight2d>)>,
+) {
+    if !scale.is_downsampled() {
+        return;
+    }
+    for (view, camera) in &views {
+        let Some(physical_target_size) = camera.physical_target_size else {
+            continue;
+        };
+        let size = light_buffer_size(physical_target_size, *scale);
+        let size = Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        };
+
+        let light = texture_cache.get(
+            &render_device,
+            TextureDescriptor {
+                label: Some("light_buffer_texture"),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: ViewTarget::TEXTURE_FORMAT_HDR,
+                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+        );
+        let stencil = texture_cache.get(
+            &render_device,
+            TextureDescriptor {
+                label: Some("light_buffer_stencil_texture"),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: TextureFormat::Stencil8,
+                usage: TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            },
+        );
+
+        commands
+            .entity(view)
+            .insert(LightBufferTextures { light, stencil });
+    }
+}
+
+/// Render world [`Component`] holding the bind group used to sample a view's light buffer.
+#[derive(Component)]
+pub struct LightBufferBindGroup(pub BindGroup);
+
+pub fn prepare_light_buffer_bind_groups(
+    mut commands: Commands,
+    pipeline: Res<LightBufferPipeline>,
+    render_device: Res<RenderDevice>,
+    views: Query<(Entity, &LightBufferTextures)>,
+) {
+    for (view, textures) in &views {
+        commands
+            .entity(view)
+            .insert(LightBufferBindGroup(render_device.create_bind_group(
+                "light_buffer_bind_group",
+                &pipeline.layout,
+                &BindGroupEntries::sequential((&textures.light.default_view, &pipeline.sampler)),
+            )));
+    }
+}
+
+/// Render world [`Component`] holding the index of the first
+/// [`DeferredLighting2d`](super::render::DeferredLighting2d) item drawn into the light buffer.
+/// Only used with stencil shadows, soft shadows already split the phase per light.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct LightBufferPhaseStart(pub usize);
+
+#[derive(Resource)]
+pub struct LightBufferPipeline {
+    pub layout: BindGroupLayout,
+    pub sampler: Sampler,
+    pub pipeline_id: CachedRenderPipelineId,
+}
+
+impl FromWorld for LightBufferPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+        let post_process_layout = world.resource::<PostProcessRes>().layout.clone();
+
+        let layout = render_device.create_bind_group_layout(
+            "light_buffer_bind_group_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::FRAGMENT,
+                (
+                    texture_2d(TextureSampleType::Float { filterable: true }),
+                    sampler(SamplerBindingType::Filtering),
+                ),
+            ),
+        );
+        // bilinear upsampling, the light buffer is never sampled outside of the view
+        let sampler = render_device.create_sampler(&SamplerDescriptor {
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..default()
+        });
+
+        let shader = world.load_asset("shaders/lighting/light_buffer.wgsl");
+
+        let pipeline_id =
+            world
+                .resource_mut::<PipelineCache>()
+                .queue_render_pipeline(RenderPipelineDescriptor {
+                    label: Some("light_buffer_composite_pipeline".into()),
+                    layout: vec![post_process_layout, layout.clone()],
+                    vertex: fullscreen_shader_vertex_state(),
+                    fragment: Some(FragmentState {
+                        shader,
+                        shader_defs: vec![],
+                        entry_point: "fragment".into(),
+                        // added on top of the ambient light
+                        targets: vec![Some(ColorTargetState {
+                            format: ViewTarget::TEXTURE_FORMAT_HDR,
+                            blend: Some(BlendState {
+                                color: BlendComponent {
+                                    src_factor: BlendFactor::One,
+                                    dst_factor: BlendFactor::One,
+                                    operation: BlendOperation::Add,
+                                },
+                                alpha: BlendComponent {
+                                    src_factor: BlendFactor::Zero,
+                                    dst_factor: BlendFactor::One,
+                                    operation: BlendOperation::Add,
+                                },
+                            }),
+                            write_mask: ColorWrites::ALL,
+                        })],
+                    }),
+                    primitive: PrimitiveState::default(),
+                    depth_stencil: None,
+                    multisample: MultisampleState::default(),
+                    push_constant_ranges: vec![],
+                    zero_initialize_workgroup_memory: false,
+                });
+
+        LightBufferPipeline {
+            layout,
+            sampler,
+            pipeline_id,
+        }
+    }
+}
+
+/// Adds the lights drawn into a view's light buffer onto `destination`. `post_process_group` is
+/// bound at group 0 for the unlit image, like in the light passes.
+pub fn composite_light_buffer(
+    render_context: &mut RenderContext,
+    world: &World,
+    bind_group: &LightBufferBindGroup,
+    post_process_group: &BindGroup,
+    destination: &TextureView,
+) {
+    let pipeline_cache = world.resource::<PipelineCache>();
+    let Some(pipeline) =
+        pipeline_cache.get_render_pipeline(world.resource::<LightBufferPipeline>().pipeline_id)
+    else {
+        return;
+    };
+
+    let mut composite_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+        label: Some("light_buffer_composite_pass"),
+        color_attachments: &[Some(RenderPassColorAttachment {
+            view: destination,
+            resolve_target: None,
+            // the lights are added onto the ambient lit scene already in the destination
+            ops: Operations {
+                load: LoadOp::Load,
+                store: StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        timestamp_writes: None,
+        occlusion_query_set: None,
+    });
+    composite_pass.set_render_pipeline(pipeline);
+    composite_pass.set_bind_group(0, post_process_group, &[]);
+    composite_pass.set_bind_group(1, &bind_group.0, &[]);
+    composite_pass.draw(0..3, 0..1);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use crate::lighting::LineLight2d;
+
+    use super::*;
+
+    #[test]
+    fn light_buffer_is_divided_by_scale() {
+        let target = UVec2::new(1280, 720);
+        assert_eq!(
+            light_buffer_size(target, LightBufferScale(1)),
+            UVec2::new(1280, 720)
+        );
+        assert_eq!(
+            light_buffer_size(target, LightBufferScale(2)),
+            UVec2::new(640, 360)
+        );
+        assert_eq!(
+            light_buffer_size(target, LightBufferScale(4)),
+            UVec2::new(320, 180)
+        );
+    }
+
+    #[test]
+    fn light_buffer_covers_odd_sizes() {
+        assert_eq!(
+            light_buffer_size(UVec2::new(1281, 721), LightBufferScale(2)),
+            UVec2::new(641, 361)
+        );
+        assert_eq!(
+            light_buffer_size(UVec2::new(1, 1), LightBufferScale(0)),
+            UVec2::ONE
+        );
+    }
+
+    #[test]
+    fn only_scales_above_one_are_downsampled() {
+        assert!(!LightBufferScale(0).is_downsampled());
+        assert!(!LightBufferScale::default().is_downsampled());
+        assert!(LightBufferScale(2).is_downsampled());
+    }
+
+    #[test]
+    fn half_resolution_light_passes_fill_a_quarter_of_the_pixels() {
+        let fill = |target: UVec2, scale: u32| {
+            let size = light_buffer_size(target, LightBufferScale(scale));
+            (size.x * size.y) as f32 / (target.x * target.y) as f32
+        };
+        for target in [UVec2::new(1280, 720), UVec2::new(1920, 1080)] {
+            assert_eq!(fill(target, 1), 1.0);
+            assert_eq!(fill(target, 2), 0.25);
+            assert_eq!(fill(target, 4), 0.0625);
+        }
+        // odd sizes are rounded up to cover the whole view, which costs barely anything
+        assert!(fill(UVec2::new(1281, 721), 2) < 0.252);
+    }
+
+    /// Draws 50 lights over a 1280x720 view on the CPU, with the fall off of `line_light.wgsl`,
+    /// into a full resolution light buffer and into one at half resolution that is upsampled like
+    /// the composite pass does. Compares how long drawing the lights takes, and how far the
+    /// upsampled lighting is from the full resolution one. Run with
+    /// `cargo test --release bench_light_buffer_scale -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_light_buffer_scale() {
+        const ITERATIONS: u32 = 20;
+        let target = UVec2::new(1280, 720);
+        let lights: Vec<(Vec2, f32)> = (0..50)
+            .map(|i| {
+                let center = Vec2::new((i % 10) as f32 * 128.0 + 64.0, (i / 10) as f32 * 144.0);
+                (center + 72.0, 96.0)
+            })
+            .collect();
+
+        // the light at the center of each pixel of the light buffer, drawing each light as a quad
+        let draw = |scale: u32| -> (Vec<f32>, UVec2) {
+            let size = light_buffer_size(target, LightBufferScale(scale));
+            let mut buffer = vec![0.0; (size.x * size.y) as usize];
+            for (center, radius) in lights.iter() {
+                let min = ((*center - *radius) / scale as f32).floor().max(Vec2::ZERO);
+                let max = ((*center + *radius) / scale as f32)
+                    .ceil()
+                    .as_uvec2()
+                    .min(size);
+                for y in min.y as u32..max.y {
+                    for x in min.x as u32..max.x {
+                        let p = (Vec2::new(x as f32, y as f32) + 0.5) * scale as f32;
+                        buffer[(y * size.x + x) as usize] +=
+                            LineLight2d::radial_fall_off(p.distance(*center) / radius, None);
+                    }
+                }
+            }
+            (buffer, size)
+        };
+        let time = |scale: u32| {
+            let start = Instant::now();
+            for _ in 0..ITERATIONS {
+                draw(scale);
+            }
+            start.elapsed() / ITERATIONS
+        };
+        let (full_time, half_time) = (time(1), time(2));
+
+        // bilinear filtering, like the sampler of the composite pass
+        let sample = |buffer: &[f32], size: UVec2, uv: Vec2| {
+            let texel = uv * size.as_vec2() - 0.5;
+            let base = texel.floor();
+            let t = texel - base;
+            let at = |x: f32, y: f32| {
+                let x = (x.max(0.0) as u32).min(size.x - 1);
+                let y = (y.max(0.0) as u32).min(size.y - 1);
+                buffer[(y * size.x + x) as usize]
+            };
+            let top = at(base.x, base.y) * (1.0 - t.x) + at(base.x + 1.0, base.y) * t.x;
+            let bottom =
+                at(base.x, base.y + 1.0) * (1.0 - t.x) + at(base.x + 1.0, base.y + 1.0) * t.x;
+            top * (1.0 - t.y) + bottom * t.y
+        };
+        let (full, _) = draw(1);
+        let (half, half_size) = draw(2);
+        let errors: Vec<f32> = (0..target.y)
+            .flat_map(|y| (0..target.x).map(move |x| UVec2::new(x, y)))
+            .map(|pixel| {
+                let uv = (pixel.as_vec2() + 0.5) / target.as_vec2();
+                let upsampled = sample(&half, half_size, uv);
+                (upsampled - full[(pixel.y * target.x + pixel.x) as usize]).abs()
+            })
+            .collect();
+        let mean_error = errors.iter().sum::<f32>() / errors.len() as f32;
+        let max_error = errors.iter().copied().fold(0.0, f32::max);
+
+        assert!(mean_error < 0.01);
+        println!(
+            "full resolution: {:?} per frame, half resolution: {:?} per frame, upsampling error: \
+             {:.4} on average and {:.4} at most",
+            full_time, half_time, mean_error, max_error
+        );
+    }
+}
@@ -0,0 +1,419 @@
+use bevy::prelude::*;
+
+use super::{line_light::calculate_line_light_2d_bounds, LineLight2d};
+
+pub struct LightTogglePlugin;
+
+impl Plugin for LightTogglePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GlobalFlicker>().add_systems(
+            PostUpdate,
+            (
+                update_light_schedules,
+                tick_global_flicker,
+                update_light_toggles,
+            )
+                .chain()
+                .before(calculate_line_light_2d_bounds),
+        );
+    }
+}
+
+/// A brief over-bright flash when a [`LightToggle`] turns on, like a fluorescent tube or an arc
+/// lamp igniting.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LightIgnition {
+    /// Brightness at the start of the flash, relative to the light's steady intensity
+    pub peak: f32,
+    /// How long the flash takes to fade, in seconds
+    pub secs: f32,
+}
+
+impl Default for LightIgnition {
+    fn default() -> Self {
+        LightIgnition {
+            peak: 2.5,
+            secs: 0.08,
+        }
+    }
+}
+
+/// [`Component`] for lights that turn on and off. Turning on ramps the [`LineLight2d`] up to
+/// `intensity` over `warmup_secs`, starting with the [`LightIgnition`] flash if there is one.
+/// The intensity is written into the alpha of the light's color in [`PostUpdate`], so other
+/// systems only need to call [`LightToggle::set_on`].
+#[derive(Component, Clone, Debug)]
+#[require(LineLight2d)]
+pub struct LightToggle {
+    /// The steady intensity of the light while it is on
+    pub intensity: f32,
+    pub warmup_secs: f32,
+    /// Off by default
+    pub ignition: Option<LightIgnition>,
+    on: bool,
+    /// Seconds since the light was turned on
+    elapsed: f32,
+}
+
+impl Default for LightToggle {
+    fn default() -> Self {
+        LightToggle {
+            intensity: 1.0,
+            warmup_secs: 0.0,
+            ignition: None,
+            on: false,
+            elapsed: 0.0,
+        }
+    }
+}
+
+impl LightToggle {
+    /// A toggle for a light that is already on at `intensity`, without a warmup or ignition.
+    pub fn lit(intensity: f32) -> Self {
+        LightToggle {
+            intensity,
+            on: true,
+            ..default()
+        }
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.on
+    }
+
+    /// Turns the light on or off. Turning on a light that is off restarts its warmup and
+    /// ignition.
+    pub fn set_on(&mut self, on: bool) {
+        if on && !self.on {
+            self.elapsed = 0.0;
+        }
+        self.on = on;
+    }
+
+    /// Restarts the warmup and ignition of a light that is on, as if it was just turned on.
+    pub fn ignite(&mut self) {
+        self.elapsed = 0.0;
+    }
+
+    pub fn tick(&mut self, delta: f32) {
+        if self.on {
+            self.elapsed += delta;
+        }
+    }
+
+    /// How far the light has warmed up since it was turned on, from 0 to 1, ignoring the
+    /// ignition flash.
+    pub fn warmup(&self) -> f32 {
+        if !self.on {
+            0.0
+        } else if self.warmup_secs > 0.0 {
+            (self.elapsed / self.warmup_secs).min(1.0)
+        } else {
+            1.0
+        }
+    }
+
+    /// Brightness relative to `intensity`. The ignition flash fades out on top of the warmup
+    /// ramp, so once it is over the light continues warming up where the ramp is.
+    pub fn brightness(&self) -> f32 {
+        if !self.on {
+            return 0.0;
+        }
+        let warmup = self.warmup();
+        let flash = match self.ignition {
+            Some(ignition) if self.elapsed < ignition.secs => {
+                ignition.peak * (1.0 - self.elapsed / ignition.secs)
+            }
+            _ => 0.0,
+        };
+        warmup.max(flash)
+    }
+}
+
+/// [`Component`] for lights that blink on a fixed schedule, on for `on_secs` and then off for
+/// `off_secs`. Lights with the same schedule blink together unless they have different
+/// `offset_secs`.
+#[derive(Component, Clone, Debug)]
+#[require(LightToggle)]
+pub struct LightSchedule {
+    pub on_secs: f32,
+    pub off_secs: f32,
+    pub offset_secs: f32,
+    elapsed: f32,
+}
+
+impl LightSchedule {
+    pub fn new(on_secs: f32, off_secs: f32, offset_secs: f32) -> Self {
+        LightSchedule {
+            on_secs,
+            off_secs,
+            offset_secs,
+            elapsed: 0.0,
+        }
+    }
+
+    pub fn tick(&mut self, delta: f32) {
+        self.elapsed += delta;
+    }
+
+    /// Whether the light should be on at this point of the schedule. Schedules that are never off
+    /// are always on.
+    pub fn is_on(&self) -> bool {
+        let period = self.on_secs + self.off_secs;
+        if self.off_secs <= 0.0 || period <= 0.0 {
+            return true;
+        }
+        (self.elapsed + self.offset_secs).rem_euclid(period) < self.on_secs
+    }
+}
+
+/// [`Resource`] for a flicker shared by every light with a [`SyncedFlicker`], like a power surge
+/// running through the whole level. Calling [`GlobalFlicker::surge`] dims all synced lights
+/// together, flickering at `frequency` and fading back to full brightness over the surge. Lights
+/// without a [`SyncedFlicker`], including ones blinking on their own [`LightSchedule`], are left
+/// alone.
+#[derive(Resource, Clone, Debug)]
+pub struct GlobalFlicker {
+    /// How much synced lights dim at the start of a surge, from 0 to 1
+    pub depth: f32,
+    /// Flickers per second during a surge
+    pub frequency: f32,
+    duration: f32,
+    elapsed: f32,
+}
+
+impl Default for GlobalFlicker {
+    fn default() -> Self {
+        GlobalFlicker {
+            depth: 0.8,
+            frequency: 12.0,
+            duration: 0.0,
+            elapsed: 0.0,
+        }
+    }
+}
+
+impl GlobalFlicker {
+    /// Starts a surge lasting `secs`, restarting any surge already going on.
+    pub fn surge(&mut self, secs: f32) {
+        self.duration = secs.max(0.0);
+        self.elapsed = 0.0;
+    }
+
+    pub fn is_surging(&self) -> bool {
+        self.elapsed < self.duration
+    }
+
+    pub fn tick(&mut self, delta: f32) {
+        if self.is_surging() {
+            self.elapsed += delta;
+        }
+    }
+
+    /// Brightness of synced lights relative to their steady intensity, from `1 - depth` to 1.
+    pub fn modulation(&self) -> f32 {
+        if !self.is_surging() {
+            return 1.0;
+        }
+        let fade = 1.0 - self.elapsed / self.duration;
+        let flicker = 0.5 + 0.5 * (std::f32::consts::TAU * self.frequency * self.elapsed).cos();
+        1.0 - self.depth.clamp(0.0, 1.0) * fade * flicker
+    }
+}
+
+/// [`Component`] for lights that dim with the [`GlobalFlicker`] instead of on their own. The
+/// flicker is applied on top of the light's [`LightToggle`], which starts out on for lights that
+/// don't have one yet.
+#[derive(Component, Clone, Copy, Debug, Default)]
+#[require(LightToggle(synced_flicker_toggle))]
+pub struct SyncedFlicker;
+
+fn synced_flicker_toggle() -> LightToggle {
+    LightToggle::lit(1.0)
+}
+
+/// [`System`] that advances the [`GlobalFlicker`].
+pub fn tick_global_flicker(mut global_flicker: ResMut<GlobalFlicker>, time: Res<Time>) {
+    global_flicker.tick(time.delta_secs());
+}
+
+/// [`System`] that advances each [`LightSchedule`] and turns its [`LightToggle`] on or off.
+pub fn update_light_schedules(
+    mut q_lights: Query<(&mut LightSchedule, &mut LightToggle)>,
+    time: Res<Time>,
+) {
+    for (mut schedule, mut toggle) in q_lights.iter_mut() {
+        schedule.tick(time.delta_secs());
+        toggle.set_on(schedule.is_on());
+    }
+}
+
+/// [`System`] that advances each [`LightToggle`] and sets the intensity of its [`LineLight2d`].
+pub fn update_light_toggles(
+    mut q_lights: Query<(&mut LightToggle, &mut LineLight2d, Has<SyncedFlicker>)>,
+    global_flicker: Res<GlobalFlicker>,
+    time: Res<Time>,
+) {
+    for (mut toggle, mut light, synced) in q_lights.iter_mut() {
+        toggle.tick(time.delta_secs());
+        let mut intensity = toggle.intensity * toggle.brightness();
+        if synced {
+            intensity *= global_flicker.modulation();
+        }
+        if light.color.w != intensity {
+            light.color.w = intensity;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ignition_flashes_then_steadies() {
+        let mut toggle = LightToggle {
+            ignition: Some(LightIgnition::default()),
+            ..default()
+        };
+        assert_eq!(toggle.brightness(), 0.0);
+
+        toggle.set_on(true);
+        assert_eq!(toggle.brightness(), LightIgnition::default().peak);
+        toggle.tick(0.04);
+        assert!(toggle.brightness() > 1.0);
+        toggle.tick(0.1);
+        assert_eq!(toggle.brightness(), 1.0);
+
+        // turning it off and on again flashes again
+        toggle.set_on(false);
+        assert_eq!(toggle.brightness(), 0.0);
+        toggle.set_on(true);
+        assert_eq!(toggle.brightness(), LightIgnition::default().peak);
+    }
+
+    #[test]
+    fn no_flash_by_default() {
+        let mut toggle = LightToggle::default();
+        toggle.set_on(true);
+        assert_eq!(toggle.brightness(), 1.0);
+    }
+
+    #[test]
+    fn warmup_continues_after_flash() {
+        let mut toggle = LightToggle {
+            warmup_secs: 1.0,
+            ignition: Some(LightIgnition::default()),
+            ..default()
+        };
+        toggle.set_on(true);
+        toggle.tick(0.5);
+        assert!((toggle.brightness() - 0.5).abs() < 1e-5);
+        toggle.tick(1.0);
+        assert_eq!(toggle.brightness(), 1.0);
+        // staying on doesn't restart anything
+        toggle.set_on(true);
+        assert_eq!(toggle.brightness(), 1.0);
+    }
+
+    #[test]
+    fn schedule_blinks_with_offset() {
+        let mut schedule = LightSchedule::new(1.0, 0.5, 0.0);
+        assert!(schedule.is_on());
+        schedule.tick(1.2);
+        assert!(!schedule.is_on());
+        schedule.tick(0.4);
+        assert!(schedule.is_on());
+
+        // a light half a period ahead is off while the other is on
+        let mut schedule = LightSchedule::new(1.0, 1.0, 1.0);
+        assert!(!schedule.is_on());
+        schedule.tick(1.0);
+        assert!(schedule.is_on());
+        assert!(LightSchedule::new(1.0, 0.0, 0.3).is_on());
+    }
+
+    #[test]
+    fn toggle_sets_light_intensity() {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .init_resource::<GlobalFlicker>()
+            .add_systems(Update, update_light_toggles);
+
+        let mut toggle = LightToggle {
+            intensity: 0.6,
+            ..default()
+        };
+        toggle.set_on(true);
+        let light = app.world_mut().spawn(toggle).id();
+        app.update();
+        assert_eq!(app.world().get::<LineLight2d>(light).unwrap().color.w, 0.6);
+
+        app.world_mut()
+            .get_mut::<LightToggle>(light)
+            .unwrap()
+            .set_on(false);
+        app.update();
+        assert_eq!(app.world().get::<LineLight2d>(light).unwrap().color.w, 0.0);
+    }
+
+    #[test]
+    fn surge_dims_then_recovers() {
+        let mut global_flicker = GlobalFlicker::default();
+        assert_eq!(global_flicker.modulation(), 1.0);
+
+        global_flicker.surge(0.5);
+        assert!((global_flicker.modulation() - 0.2).abs() < 1e-5);
+        global_flicker.tick(0.25);
+        assert!(global_flicker.modulation() < 1.0);
+        global_flicker.tick(0.25);
+        assert_eq!(global_flicker.modulation(), 1.0);
+    }
+
+    #[test]
+    fn synced_lights_start_on() {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .init_resource::<GlobalFlicker>()
+            .add_systems(Update, update_light_toggles);
+        let light = app.world_mut().spawn(SyncedFlicker).id();
+        app.update();
+        assert!(app.world().get::<LightToggle>(light).unwrap().is_on());
+        assert_eq!(app.world().get::<LineLight2d>(light).unwrap().color.w, 1.0);
+    }
+
+    #[test]
+    fn synced_lights_share_modulation() {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .init_resource::<GlobalFlicker>()
+            .add_systems(Update, update_light_toggles);
+
+        let mut spawn_light = |intensity: f32, synced: bool| {
+            let mut toggle = LightToggle {
+                intensity,
+                ..default()
+            };
+            toggle.set_on(true);
+            let mut light = app.world_mut().spawn(toggle);
+            if synced {
+                light.insert(SyncedFlicker);
+            }
+            light.id()
+        };
+        let synced_a = spawn_light(1.0, true);
+        let synced_b = spawn_light(0.5, true);
+        let independent = spawn_light(0.8, false);
+
+        app.world_mut().resource_mut::<GlobalFlicker>().surge(1.0);
+        app.update();
+
+        let intensity = |app: &App, light| app.world().get::<LineLight2d>(light).unwrap().color.w;
+        let modulation = app.world().resource::<GlobalFlicker>().modulation();
+        assert!(modulation < 1.0);
+        assert!((intensity(&app, synced_a) - modulation).abs() < 1e-5);
+        assert!((intensity(&app, synced_b) - 0.5 * modulation).abs() < 1e-5);
+        // lights that aren't synced keep their own intensity
+        assert_eq!(intensity(&app, independent), 0.8);
+    }
+}
@@ -15,23 +15,37 @@ use bevy::{
         },
         mesh::VertexBufferLayout,
         primitives::Aabb,
+        render_asset::RenderAssets,
         render_phase::{PhaseItem, RenderCommand, RenderCommandResult, TrackedRenderPass},
-        render_resource::{binding_types::uniform_buffer, *},
+        render_resource::{
+            binding_types::{sampler, texture_2d, uniform_buffer},
+            *,
+        },
         renderer::{RenderDevice, RenderQueue},
+        sync_world::RenderEntity,
+        texture::{FallbackImage, GpuImage},
         view::{check_visibility, ViewTarget, VisibilitySystems},
-        Render, RenderApp, RenderSet,
+        Extract, ExtractSchedule, Render, RenderApp, RenderSet,
     },
     sprite::Mesh2dPipeline,
 };
 use bytemuck::{Pod, Zeroable};
+use serde::Deserialize;
 
-use super::render::PostProcessRes;
+use super::{
+    compute_shadows::{compute_shadow_bind_group_layout, compute_shadows_supported},
+    lens::LensFocus,
+    render::PostProcessRes,
+    room_mask::{LightRoomMask, MAX_LIGHT_ROOM_RECTS},
+    shadow_mask::shadow_mask_bind_group_layout,
+};
 
 pub struct LineLight2dPlugin;
 
 impl Plugin for LineLight2dPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(ExtractComponentPlugin::<LineLight2d>::default())
+        app.init_resource::<LineLight2dBlendMode>()
+            .add_plugins(ExtractComponentPlugin::<LineLight2d>::default())
             .add_plugins(UniformComponentPlugin::<ExtractLineLight2d>::default())
             .add_systems(
                 PostUpdate,
@@ -45,28 +59,84 @@ impl Plugin for LineLight2dPlugin {
         let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
             return;
         };
-        render_app.add_systems(
-            Render,
-            prepare_line_light_2d_bind_group.in_set(RenderSet::PrepareBindGroups),
-        );
+        render_app
+            .add_systems(ExtractSchedule, extract_line_light_2d_falloff_textures)
+            .add_systems(
+                Render,
+                prepare_line_light_2d_bind_group.in_set(RenderSet::PrepareBindGroups),
+            );
     }
     fn finish(&self, app: &mut App) {
+        let blend_mode = *app.world().resource::<LineLight2dBlendMode>();
         let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
             return;
         };
         render_app
+            .insert_resource(blend_mode)
             .init_resource::<LineLight2dPipeline>()
             .init_resource::<LineLight2dBuffers>();
     }
 }
 
+/// [`Resource`] for how overlapping [`LineLight2d`]s combine. Adding them up is brighter where
+/// lights overlap, which can blow out to white, while keeping the brightest of them gives a
+/// flatter look. The ambient light is drawn first, so with [`LineLight2dBlendMode::Max`] lights
+/// only show where they are brighter than it. This is baked into the [`LineLight2dPipeline`] when
+/// it is created, so changes only apply on restart. See the `lighting_config.blend_mode` setting
+/// of `Lightborne.toml`.
+#[derive(Resource, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LineLight2dBlendMode {
+    #[default]
+    Add,
+    Max,
+}
+
+impl LineLight2dBlendMode {
+    pub fn blend_component(self) -> BlendComponent {
+        BlendComponent {
+            src_factor: BlendFactor::One,
+            dst_factor: BlendFactor::One,
+            operation: match self {
+                LineLight2dBlendMode::Add => BlendOperation::Add,
+                LineLight2dBlendMode::Max => BlendOperation::Max,
+            },
+        }
+    }
+
+    /// The color of a light drawn over `dst`. Mirrors the [`BlendComponent`] of the pipeline.
+    pub fn blend(self, src: Vec3, dst: Vec3) -> Vec3 {
+        match self {
+            LineLight2dBlendMode::Add => src + dst,
+            LineLight2dBlendMode::Max => src.max(dst),
+        }
+    }
+}
+
 #[derive(Component, Default, Clone, Debug)]
 #[require(Transform, Visibility)]
 pub struct LineLight2d {
+    /// The color of the light, with its intensity in the alpha
     pub color: Vec4,
     pub half_length: f32,
     pub radius: f32,
+    /// How far from the light's center line it stays at full intensity before falling off towards
+    /// `radius`, for flat lighting across a room. 0 falls off from the center line, values close
+    /// to `radius` draw a hard edged disc, and values past `radius` are clamped to it.
+    pub inner_radius: f32,
     pub volumetric_intensity: f32,
+    /// Color temperature in Kelvin. When set, replaces the rgb of `color` with the color of a
+    /// blackbody at this temperature, see [`kelvin_to_rgb`].
+    pub temperature_k: Option<f32>,
+    /// The color parts of the light's range in shadow are lit with instead of black, with its
+    /// intensity in the alpha, so shadows can carry some ambient color. Falls off with distance
+    /// like `color`. The default of black keeps shadows fully dark.
+    pub shadow_tint: Vec4,
+    /// Gradient the light falls off with instead of the usual curve, for example to draw rings.
+    /// The red channel of the texture's first row is sampled from the light's center line on the
+    /// left to the edge of its range on the right, see [`LineLight2d::radial_fall_off`]. The
+    /// light isn't drawn until the texture is loaded.
+    pub falloff_texture: Option<Handle<Image>>,
 }
 
 impl LineLight2d {
@@ -75,33 +145,158 @@ impl LineLight2d {
             color,
             half_length: 0.0,
             radius,
+            inner_radius: 0.0,
             volumetric_intensity,
+            temperature_k: None,
+            shadow_tint: Vec4::ZERO,
+            falloff_texture: None,
         }
     }
+
+    /// The color the light is drawn with, taking its `temperature_k` into account.
+    pub fn shaded_color(&self) -> Vec4 {
+        match self.temperature_k {
+            Some(kelvin) => kelvin_to_rgb(kelvin).extend(self.color.w),
+            None => self.color,
+        }
+    }
+
+    /// Whether parts of the light's range in shadow are lit by its `shadow_tint`.
+    pub fn has_shadow_tint(&self) -> bool {
+        shadow_tint_visible(self.shadow_tint)
+    }
+
+    /// The light added at a point `fall_off` of the way from the edge of the light's range to
+    /// its center, where `shadow` is how much the point is in shadow, from 0 to 1. Mirrors
+    /// `line_light.wgsl`.
+    pub fn light_at(&self, fall_off: f32, shadow: f32) -> Vec3 {
+        let color = self.shaded_color();
+        let lit = color.truncate() * color.w;
+        let tint = self.shadow_tint.truncate() * self.shadow_tint.w;
+        lit.lerp(tint, shadow) * fall_off
+    }
+
+    /// How far `inner_radius` is out towards the edge of the light's range, from 0 to 1.
+    pub fn inner_fraction(&self) -> f32 {
+        if self.radius <= 0.0 {
+            return 0.0;
+        }
+        (self.inner_radius / self.radius).clamp(0.0, 1.0)
+    }
+
+    /// Remaps a point `distance` of the way from the light's center line to the edge of its range
+    /// so the fall off starts at `inner_fraction` instead of the center line, see
+    /// [`inner_fraction`](Self::inner_fraction). Points inside of it are at a distance of 0.
+    /// Mirrors `line_light.wgsl`.
+    pub fn inner_distance(distance: f32, inner_fraction: f32) -> f32 {
+        if distance < inner_fraction {
+            return 0.0;
+        }
+        if inner_fraction >= 1.0 {
+            return 1.0;
+        }
+        ((distance - inner_fraction) / (1.0 - inner_fraction)).clamp(0.0, 1.0)
+    }
+
+    /// How much of the light reaches a point `distance` of the way from the light's center line
+    /// to the edge of its range, where `falloff_texture` is the light's
+    /// [`falloff_texture`](Self::falloff_texture) if it has one. Mirrors `line_light.wgsl`,
+    /// including the linear filtering of the texture.
+    pub fn radial_fall_off(distance: f32, falloff_texture: Option<&Image>) -> f32 {
+        let distance = distance.clamp(0.0, 1.0);
+        let Some(image) = falloff_texture else {
+            return (1.0 - distance).powi(2);
+        };
+        let width = image.width();
+        let texel = |x: f32| {
+            let x = (x.max(0.0) as u32).min(width.saturating_sub(1));
+            image
+                .get_color_at(x, 0)
+                .map_or(0.0, |color| color.to_linear().red)
+        };
+        // texel centers are half a texel in from the edges
+        let u = distance * width as f32 - 0.5;
+        let t = u - u.floor();
+        texel(u.floor()) * (1.0 - t) + texel(u.floor() + 1.0) * t
+    }
+}
+
+fn shadow_tint_visible(shadow_tint: Vec4) -> bool {
+    shadow_tint.w > 0.0 && shadow_tint.truncate() != Vec3::ZERO
+}
+
+/// The temperatures in Kelvin [`kelvin_to_rgb`] is fit to. Temperatures outside of this range are
+/// clamped to it.
+pub const LIGHT_TEMPERATURE_RANGE: (f32, f32) = (1000.0, 40000.0);
+
+/// Approximates the color of a blackbody at `kelvin`, with the brightest channel at 1, using
+/// Tanner Helland's fit of blackbody data. 2700K is the warm orange of a tungsten bulb, and
+/// 6500K is close to white.
+pub fn kelvin_to_rgb(kelvin: f32) -> Vec3 {
+    let (min, max) = LIGHT_TEMPERATURE_RANGE;
+    let t = kelvin.clamp(min, max) / 100.0;
+    let red = if t <= 66.0 {
+        255.0
+    } else {
+        329.69873 * (t - 60.0).powf(-0.13320476)
+    };
+    let green = if t <= 66.0 {
+        99.4708 * t.ln() - 161.11957
+    } else {
+        288.12216 * (t - 60.0).powf(-0.07551485)
+    };
+    let blue = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.51773 * (t - 10.0).ln() - 305.04479
+    };
+    (Vec3::new(red, green, blue) / 255.0).clamp(Vec3::ZERO, Vec3::ONE)
 }
 
+/// The [`Aabb`] of everything a light with a [`GlobalTransform`] scaled by `scale` draws,
+/// including the glow out to its radius, so lights whose center is off-screen still get drawn
+/// while their glow reaches the view. The light is drawn without its scale, see
+/// [`ExtractLineLight2d`], so the bounds undo the scale the culling applies to them.
+pub fn line_light_2d_aabb(light: &LineLight2d, scale: Vec3) -> Aabb {
+    let reach = Vec2::new(light.half_length + light.radius, light.radius);
+    let scale = scale.truncate().abs();
+    let half_extents = Vec2::select(scale.cmpgt(Vec2::ZERO), reach / scale, reach);
+    Aabb {
+        center: Vec3::ZERO.into(),
+        half_extents: half_extents.extend(0.0).into(),
+    }
+}
+
+/// [`System`] that updates the [`Aabb`] lights are culled with, see [`line_light_2d_aabb`].
 pub fn calculate_line_light_2d_bounds(
     mut commands: Commands,
-    q_light_changed: Query<(Entity, &LineLight2d), Changed<LineLight2d>>,
+    q_light_changed: Query<
+        (Entity, &LineLight2d, &GlobalTransform, Option<&Aabb>),
+        Or<(Changed<LineLight2d>, Changed<GlobalTransform>)>,
+    >,
 ) {
-    for (entity, light) in q_light_changed.iter() {
-        let aabb = Aabb {
-            center: Vec3::ZERO.into(),
-            half_extents: Vec2::new(light.half_length + light.radius, light.radius)
-                .extend(0.0)
-                .into(),
-        };
+    for (entity, light, transform, prev_aabb) in q_light_changed.iter() {
+        let aabb = line_light_2d_aabb(light, transform.compute_transform().scale);
+        if prev_aabb.is_some_and(|prev_aabb| *prev_aabb == aabb) {
+            continue;
+        }
         commands.entity(entity).try_insert(aabb);
     }
 }
 
 impl ExtractComponent for LineLight2d {
     type Out = (ExtractLineLight2d, LineLight2dBounds);
-    type QueryData = (&'static GlobalTransform, &'static LineLight2d);
+    type QueryData = (
+        &'static GlobalTransform,
+        &'static LineLight2d,
+        Option<&'static LightRoomMask>,
+    );
     type QueryFilter = ();
 
     fn extract_component(
-        (transform, line_light): QueryItem<'_, Self::QueryData>,
+        (transform, line_light, room_mask): QueryItem<'_, Self::QueryData>,
     ) -> Option<Self::Out> {
         // FIXME: don't do computations in extract
         let (scale, rotation, translation) = transform.to_scale_rotation_translation();
@@ -109,16 +304,27 @@ impl ExtractComponent for LineLight2d {
             Affine3A::from_scale_rotation_translation(scale.signum(), rotation, translation);
         let affine = Affine3::from(&transform_no_scale);
         let (a, b) = affine.inverse_transpose_3x3();
+        let (room_rects, room_rect_count) = room_mask
+            .map(LightRoomMask::shader_rects)
+            .unwrap_or_default();
 
         Some((
             ExtractLineLight2d {
                 world_from_local: affine.to_transpose(),
                 local_from_world_transpose_a: a,
                 local_from_world_transpose_b: b,
-                color: line_light.color,
+                color: line_light.shaded_color(),
                 half_length: line_light.half_length,
                 radius: line_light.radius,
                 volumetric_intensity: line_light.volumetric_intensity,
+                shadow_index: 0,
+                shadow_tint: line_light.shadow_tint,
+                falloff_texture: line_light.falloff_texture.is_some() as u32,
+                inner_fraction: line_light.inner_fraction(),
+                room_rects,
+                room_rect_count,
+                max_shadow_length: f32::MAX,
+                lens: LensFocus::default().shader_lens(),
             },
             LineLight2dBounds {
                 transform: transform.compute_transform(),
@@ -139,6 +345,32 @@ pub struct ExtractLineLight2d {
     pub half_length: f32,
     pub radius: f32,
     volumetric_intensity: f32,
+    /// Where this light's edges are in the
+    /// [`ComputeShadowBuffers`](super::compute_shadows::ComputeShadowBuffers), set in the render
+    /// world
+    pub shadow_index: u32,
+    shadow_tint: Vec4,
+    /// 1 if the light falls off with its [`LineLight2dFalloffTexture`]
+    falloff_texture: u32,
+    /// See [`LineLight2d::inner_fraction`]
+    inner_fraction: f32,
+    /// The min and max corners of the [`LightRoomMask`] of the light
+    room_rects: [Vec4; MAX_LIGHT_ROOM_RECTS],
+    /// How many of `room_rects` are used, 0 if the light lights everything
+    room_rect_count: u32,
+    /// The [`MaxShadowLength`](super::occluder::MaxShadowLength) of the light's shadows, set in
+    /// the render world
+    pub max_shadow_length: f32,
+    /// The center, radius and gain of the [`LensFocus`] the light is seen through, set in the
+    /// render world
+    pub lens: Vec4,
+}
+
+impl ExtractLineLight2d {
+    /// See [`LineLight2d::has_shadow_tint`].
+    pub fn has_shadow_tint(&self) -> bool {
+        shadow_tint_visible(self.shadow_tint)
+    }
 }
 
 #[derive(Component, Clone, Copy)]
@@ -223,40 +455,129 @@ impl FromWorld for LineLight2dBuffers {
 pub fn line_light_bind_group_layout(render_device: &RenderDevice) -> BindGroupLayout {
     render_device.create_bind_group_layout(
         "line_light_bind_group_layout",
-        &BindGroupLayoutEntries::single(
+        &BindGroupLayoutEntries::sequential(
             ShaderStages::VERTEX_FRAGMENT,
-            uniform_buffer::<ExtractLineLight2d>(true),
+            (
+                uniform_buffer::<ExtractLineLight2d>(true),
+                texture_2d(TextureSampleType::Float { filterable: true }),
+                sampler(SamplerBindingType::Filtering),
+            ),
         ),
     )
 }
 
+/// Render world version of [`LineLight2d::falloff_texture`].
+#[derive(Component, Clone, Copy, Debug)]
+pub struct LineLight2dFalloffTexture(AssetId<Image>);
+
+/// Bind group of a light with a [`LineLight2dFalloffTexture`], used instead of the
+/// [`LineLight2dBindGroup`] once the texture is loaded. Kept from frame to frame, and only created
+/// again when the uniform buffer or the texture it binds changes.
+#[derive(Component)]
+pub struct LineLight2dFalloffBindGroup {
+    value: BindGroup,
+    buffer: BufferId,
+    texture_view: TextureViewId,
+}
+
+pub fn extract_line_light_2d_falloff_textures(
+    mut commands: Commands,
+    q_lights: Extract<Query<(RenderEntity, &LineLight2d)>>,
+) {
+    for (render_entity, light) in q_lights.iter() {
+        let mut entity = commands.entity(render_entity);
+        match &light.falloff_texture {
+            Some(texture) => entity.insert(LineLight2dFalloffTexture(texture.id())),
+            None => entity.remove::<(LineLight2dFalloffTexture, LineLight2dFalloffBindGroup)>(),
+        };
+    }
+}
+
+/// Bind group of the lights without a [`LineLight2dFalloffTexture`], which have the fallback
+/// image bound in its place.
 #[derive(Resource)]
 pub struct LineLight2dBindGroup {
     value: BindGroup,
+    buffer: BufferId,
 }
 
+/// [`System`] that creates the [`LineLight2dBindGroup`], and the [`LineLight2dFalloffBindGroup`]
+/// of every light with a loaded falloff texture. Those are created in parallel, since each only
+/// depends on its own light. The uniform buffer they bind is written before this, in the order
+/// of the lights, so it doesn't depend on how the work is split up. Bind groups are only created
+/// again when the buffer is reallocated or a falloff texture changes.
+#[allow(clippy::too_many_arguments)]
 pub fn prepare_line_light_2d_bind_group(
     mut commands: Commands,
+    par_commands: ParallelCommands,
     uniforms: Res<ComponentUniforms<ExtractLineLight2d>>,
     pipeline: Res<LineLight2dPipeline>,
     render_device: Res<RenderDevice>,
+    fallback_image: Res<FallbackImage>,
+    images: Res<RenderAssets<GpuImage>>,
+    bind_group: Option<Res<LineLight2dBindGroup>>,
+    q_falloff_textures: Query<(
+        Entity,
+        &LineLight2dFalloffTexture,
+        Option<&LineLight2dFalloffBindGroup>,
+    )>,
 ) {
-    if let Some(binding) = uniforms.uniforms().binding() {
+    let (Some(binding), Some(buffer)) =
+        (uniforms.uniforms().binding(), uniforms.uniforms().buffer())
+    else {
+        return;
+    };
+    let buffer = buffer.id();
+    let create_bind_group = |label: &'static str, texture_view: &TextureView| {
+        render_device.create_bind_group(
+            label,
+            &pipeline.layout,
+            &BindGroupEntries::sequential((binding.clone(), texture_view, &pipeline.sampler)),
+        )
+    };
+    if bind_group.is_none_or(|bind_group| bind_group.buffer != buffer) {
         commands.insert_resource(LineLight2dBindGroup {
-            value: render_device.create_bind_group(
-                "line_light_2d_bind_group",
-                &pipeline.layout,
-                &BindGroupEntries::single(binding),
-            ),
-        })
+            value: create_bind_group("line_light_2d_bind_group", &fallback_image.d2.texture_view),
+            buffer,
+        });
     }
+    q_falloff_textures
+        .par_iter()
+        .for_each(|(entity, falloff_texture, falloff_bind_group)| {
+            let image = images.get(falloff_texture.0);
+            let is_current = falloff_bind_group
+                .zip(image)
+                .is_some_and(|(bind_group, image)| {
+                    bind_group.buffer == buffer
+                        && bind_group.texture_view == image.texture_view.id()
+                });
+            if is_current || (image.is_none() && falloff_bind_group.is_none()) {
+                return;
+            }
+            let falloff_bind_group = image.map(|image| LineLight2dFalloffBindGroup {
+                value: create_bind_group("line_light_2d_falloff_bind_group", &image.texture_view),
+                buffer,
+                texture_view: image.texture_view.id(),
+            });
+            par_commands.command_scope(|mut commands| {
+                let mut entity = commands.entity(entity);
+                match falloff_bind_group {
+                    Some(falloff_bind_group) => entity.insert(falloff_bind_group),
+                    None => entity.remove::<LineLight2dFalloffBindGroup>(),
+                };
+            });
+        });
 }
 
 pub struct SetLineLight2dBindGroup<const I: usize>;
 impl<P: PhaseItem, const I: usize> RenderCommand<P> for SetLineLight2dBindGroup<I> {
     type Param = SRes<LineLight2dBindGroup>;
     type ViewQuery = ();
-    type ItemQuery = Read<DynamicUniformIndex<ExtractLineLight2d>>;
+    type ItemQuery = (
+        Read<DynamicUniformIndex<ExtractLineLight2d>>,
+        Has<LineLight2dFalloffTexture>,
+        Option<Read<LineLight2dFalloffBindGroup>>,
+    );
 
     fn render<'w>(
         _item: &P,
@@ -265,10 +586,16 @@ impl<P: PhaseItem, const I: usize> RenderCommand<P> for SetLineLight2dBindGroup<
         param: SystemParamItem<'w, '_, Self::Param>,
         pass: &mut TrackedRenderPass<'w>,
     ) -> RenderCommandResult {
-        let Some(index) = entity else {
+        let Some((index, has_falloff_texture, falloff_bind_group)) = entity else {
             return RenderCommandResult::Skip;
         };
-        pass.set_bind_group(I, &param.into_inner().value, &[index.index()]);
+        let bind_group = match falloff_bind_group {
+            Some(falloff_bind_group) => &falloff_bind_group.value,
+            // the falloff texture is still loading
+            None if has_falloff_texture => return RenderCommandResult::Skip,
+            None => &param.into_inner().value,
+        };
+        pass.set_bind_group(I, bind_group, &[index.index()]);
         RenderCommandResult::Success
     }
 }
@@ -305,7 +632,47 @@ impl<P: PhaseItem> RenderCommand<P> for DrawLineLight2d {
 #[derive(Resource)]
 pub struct LineLight2dPipeline {
     pub layout: BindGroupLayout,
+    /// Samples the [`LineLight2d::falloff_texture`] of each light, linearly so the falloff is
+    /// smooth however small the texture is, and clamped so the far end doesn't wrap around.
+    pub sampler: Sampler,
     pub pipeline_id: CachedRenderPipelineId,
+    /// Pipeline used instead of `pipeline_id` when [`SoftShadows`](super::SoftShadows) are on
+    pub soft_shadow_pipeline_id: CachedRenderPipelineId,
+    /// Pipelines drawing into the light buffer, see [`LightBufferScale`](super::LightBufferScale)
+    pub light_buffer_pipeline_id: CachedRenderPipelineId,
+    pub soft_shadow_light_buffer_pipeline_id: CachedRenderPipelineId,
+    /// Pipelines used with [`ComputeShadows`](super::ComputeShadows), drawing to the screen and
+    /// to the light buffer. Only created if the GPU supports compute shaders.
+    pub compute_shadow_pipeline_ids: Option<[CachedRenderPipelineId; 2]>,
+    /// Pipelines that draw the [`LineLight2d::shadow_tint`] where the stencil is set, drawn after
+    /// the light itself for lights with shadows in the stencil. Drawing to the screen and to the
+    /// light buffer.
+    pub shadow_tint_pipeline_ids: [CachedRenderPipelineId; 2],
+}
+
+impl LineLight2dPipeline {
+    pub fn pipeline_id(
+        &self,
+        soft_shadows: bool,
+        light_buffer: bool,
+        compute_shadows: bool,
+    ) -> CachedRenderPipelineId {
+        if let Some(ids) = self.compute_shadow_pipeline_ids {
+            if compute_shadows && !soft_shadows {
+                return ids[light_buffer as usize];
+            }
+        }
+        match (soft_shadows, light_buffer) {
+            (false, false) => self.pipeline_id,
+            (true, false) => self.soft_shadow_pipeline_id,
+            (false, true) => self.light_buffer_pipeline_id,
+            (true, true) => self.soft_shadow_light_buffer_pipeline_id,
+        }
+    }
+
+    pub fn shadow_tint_pipeline_id(&self, light_buffer: bool) -> CachedRenderPipelineId {
+        self.shadow_tint_pipeline_ids[light_buffer as usize]
+    }
 }
 
 impl FromWorld for LineLight2dPipeline {
@@ -315,6 +682,18 @@ impl FromWorld for LineLight2dPipeline {
         let post_process_layout = post_process_res.layout.clone();
 
         let layout = line_light_bind_group_layout(render_device);
+        let sampler = render_device.create_sampler(&SamplerDescriptor {
+            label: Some("line_light_falloff_sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..default()
+        });
+        let shadow_mask_layout = shadow_mask_bind_group_layout(render_device);
+        let compute_shadow_layout = compute_shadow_bind_group_layout(render_device);
+        let compute_shadows = compute_shadows_supported(render_device);
+        let blend_mode = *world.resource::<LineLight2dBlendMode>();
 
         let shader = world.load_asset("shaders/lighting/line_light.wgsl");
 
@@ -345,66 +724,134 @@ impl FromWorld for LineLight2dPipeline {
 
         let mesh2d_pipeline = Mesh2dPipeline::from_world(world);
 
-        let pipeline_id =
-            world
-                .resource_mut::<PipelineCache>()
-                .queue_render_pipeline(RenderPipelineDescriptor {
-                    label: Some("line_light_pipeline".into()),
-                    layout: vec![
-                        post_process_layout,
-                        mesh2d_pipeline.view_layout,
-                        layout.clone(),
-                    ],
-                    vertex: VertexState {
-                        shader: shader.clone(),
-                        shader_defs: vec![],
-                        entry_point: "vertex".into(),
-                        buffers: vec![pos_buffer_layout],
-                    },
-                    fragment: Some(FragmentState {
-                        shader,
-                        shader_defs: vec![],
-                        entry_point: "fragment".into(),
-                        targets: vec![Some(ColorTargetState {
-                            format: ViewTarget::TEXTURE_FORMAT_HDR,
-                            blend: Some(BlendState {
-                                color: BlendComponent {
-                                    src_factor: BlendFactor::One,
-                                    dst_factor: BlendFactor::One,
-                                    operation: BlendOperation::Add,
-                                },
-                                alpha: BlendComponent::OVER,
-                            }),
-                            write_mask: ColorWrites::ALL,
-                        })],
-                    }),
-                    // below needs changing?
-                    primitive: PrimitiveState::default(),
-                    depth_stencil: Some(DepthStencilState {
-                        format: TextureFormat::Stencil8,
-                        depth_write_enabled: false,
-                        depth_compare: CompareFunction::Always,
-                        stencil: StencilState {
-                            front: StencilFaceState {
-                                compare: CompareFunction::Equal,
-                                fail_op: StencilOperation::Keep,
-                                depth_fail_op: StencilOperation::Keep,
-                                pass_op: StencilOperation::Keep,
+        // lights with soft shadows are masked by the blurred shadow mask instead of the stencil,
+        // lights drawn into the light buffer add up their volumetric intensity in the alpha
+        let descriptor = |label: &'static str, soft_shadows: bool, light_buffer: bool| {
+            let mut layouts = vec![
+                post_process_layout.clone(),
+                mesh2d_pipeline.view_layout.clone(),
+                layout.clone(),
+            ];
+            let mut shader_defs: Vec<ShaderDefVal> = vec![];
+            if soft_shadows {
+                layouts.push(shadow_mask_layout.clone());
+                shader_defs.push("SOFT_SHADOWS".into());
+            }
+            if light_buffer {
+                shader_defs.push("LIGHT_BUFFER".into());
+            }
+            let blend = blend_mode.blend_component();
+
+            RenderPipelineDescriptor {
+                label: Some(label.into()),
+                layout: layouts,
+                vertex: VertexState {
+                    shader: shader.clone(),
+                    shader_defs: shader_defs.clone(),
+                    entry_point: "vertex".into(),
+                    buffers: vec![pos_buffer_layout.clone()],
+                },
+                fragment: Some(FragmentState {
+                    shader: shader.clone(),
+                    shader_defs,
+                    entry_point: "fragment".into(),
+                    targets: vec![Some(ColorTargetState {
+                        format: ViewTarget::TEXTURE_FORMAT_HDR,
+                        blend: Some(BlendState {
+                            color: blend,
+                            alpha: if light_buffer {
+                                blend
+                            } else {
+                                BlendComponent::OVER
                             },
-                            back: StencilFaceState::default(),
-                            read_mask: 0xFF,
-                            write_mask: 0xFF,
+                        }),
+                        write_mask: ColorWrites::ALL,
+                    })],
+                }),
+                // below needs changing?
+                primitive: PrimitiveState::default(),
+                depth_stencil: (!soft_shadows).then(|| DepthStencilState {
+                    format: TextureFormat::Stencil8,
+                    depth_write_enabled: false,
+                    depth_compare: CompareFunction::Always,
+                    stencil: StencilState {
+                        front: StencilFaceState {
+                            compare: CompareFunction::Equal,
+                            fail_op: StencilOperation::Keep,
+                            depth_fail_op: StencilOperation::Keep,
+                            pass_op: StencilOperation::Keep,
                         },
-                        bias: DepthBiasState::default(),
-                    }),
-                    multisample: MultisampleState::default(),
-                    push_constant_ranges: vec![],
-                    zero_initialize_workgroup_memory: false,
-                });
+                        back: StencilFaceState::default(),
+                        read_mask: 0xFF,
+                        write_mask: 0xFF,
+                    },
+                    bias: DepthBiasState::default(),
+                }),
+                multisample: MultisampleState::default(),
+                push_constant_ranges: vec![],
+                zero_initialize_workgroup_memory: false,
+            }
+        };
+        let stencil_descriptor = descriptor("line_light_pipeline", false, false);
+        let soft_shadow_descriptor = descriptor("line_light_soft_shadow_pipeline", true, false);
+        let light_buffer_descriptor = descriptor("line_light_light_buffer_pipeline", false, true);
+        let soft_shadow_light_buffer_descriptor =
+            descriptor("line_light_soft_shadow_light_buffer_pipeline", true, true);
+        // lights with compute shadows also check the edges found by the compute pass
+        let compute_shadow_descriptor = |label: &'static str, light_buffer: bool| {
+            let mut descriptor = descriptor(label, false, light_buffer);
+            descriptor.layout.push(compute_shadow_layout.clone());
+            descriptor.vertex.shader_defs.push("COMPUTE_SHADOWS".into());
+            if let Some(fragment) = descriptor.fragment.as_mut() {
+                fragment.shader_defs.push("COMPUTE_SHADOWS".into());
+            }
+            descriptor
+        };
+        // the shadow tint is drawn where the light was culled by the stencil
+        let shadow_tint_descriptor = |label: &'static str, light_buffer: bool| {
+            let mut descriptor = descriptor(label, false, light_buffer);
+            descriptor.vertex.shader_defs.push("SHADOW_TINT".into());
+            if let Some(fragment) = descriptor.fragment.as_mut() {
+                fragment.shader_defs.push("SHADOW_TINT".into());
+            }
+            if let Some(depth_stencil) = descriptor.depth_stencil.as_mut() {
+                depth_stencil.stencil.front.compare = CompareFunction::NotEqual;
+            }
+            descriptor
+        };
+        let shadow_tint_descriptors = [
+            shadow_tint_descriptor("line_light_shadow_tint_pipeline", false),
+            shadow_tint_descriptor("line_light_shadow_tint_light_buffer_pipeline", true),
+        ];
+        let compute_shadow_descriptors = compute_shadows.then(|| {
+            [
+                compute_shadow_descriptor("line_light_compute_shadow_pipeline", false),
+                compute_shadow_descriptor("line_light_compute_shadow_light_buffer_pipeline", true),
+            ]
+        });
+
+        let pipeline_cache = world.resource_mut::<PipelineCache>();
+        let pipeline_id = pipeline_cache.queue_render_pipeline(stencil_descriptor);
+        let soft_shadow_pipeline_id = pipeline_cache.queue_render_pipeline(soft_shadow_descriptor);
+        let light_buffer_pipeline_id =
+            pipeline_cache.queue_render_pipeline(light_buffer_descriptor);
+        let soft_shadow_light_buffer_pipeline_id =
+            pipeline_cache.queue_render_pipeline(soft_shadow_light_buffer_descriptor);
+        let compute_shadow_pipeline_ids = compute_shadow_descriptors.map(|descriptors| {
+            descriptors.map(|descriptor| pipeline_cache.queue_render_pipeline(descriptor))
+        });
+        let shadow_tint_pipeline_ids = shadow_tint_descriptors
+            .map(|descriptor| pipeline_cache.queue_render_pipeline(descriptor));
 
         LineLight2dPipeline {
             layout,
+            sampler,
             pipeline_id,
+            soft_shadow_pipeline_id,
+            light_buffer_pipeline_id,
+            soft_shadow_light_buffer_pipeline_id,
+            compute_shadow_pipeline_ids,
+            shadow_tint_pipeline_ids,
         }
     }
 }
@@ -419,4 +866,173 @@ mod tests {
     fn line_light_2d_alignment() {
         assert_eq!(mem::size_of::<ExtractLineLight2d>() % 16, 0);
     }
+
+    #[test]
+    fn max_blending_keeps_overlaps_from_blowing_out() {
+        let light = Vec3::new(0.8, 0.6, 0.2);
+        let other = Vec3::new(0.5, 0.7, 0.1);
+
+        let added = LineLight2dBlendMode::Add.blend(light, other);
+        assert!(added.x > 1.0);
+        let kept = LineLight2dBlendMode::Max.blend(light, other);
+        assert_eq!(kept, Vec3::new(0.8, 0.7, 0.2));
+        assert_ne!(added, kept);
+
+        // a light drawn over itself doesn't get any brighter
+        assert_eq!(LineLight2dBlendMode::Max.blend(light, light), light);
+        assert_eq!(
+            LineLight2dBlendMode::Max.blend_component().operation,
+            BlendOperation::Max
+        );
+        assert_eq!(
+            LineLight2dBlendMode::default().blend_component().operation,
+            BlendOperation::Add
+        );
+    }
+
+    #[test]
+    fn ring_gradient_lights_a_ring() {
+        use bevy::render::render_asset::RenderAssetUsages;
+
+        // dark in the middle, bright halfway out and dark again at the edge
+        let ring = [0u8, 0, 0, 255, 255, 0, 0, 0];
+        let image = Image::new(
+            Extent3d {
+                width: ring.len() as u32,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            ring.iter().flat_map(|r| [*r, 0, 0, 255]).collect(),
+            TextureFormat::Rgba8Unorm,
+            RenderAssetUsages::default(),
+        );
+        let fall_off = |distance| LineLight2d::radial_fall_off(distance, Some(&image));
+
+        assert_eq!(fall_off(0.0), 0.0);
+        assert_eq!(fall_off(0.5), 1.0);
+        assert_eq!(fall_off(1.0), 0.0);
+        // filtered between the texels of the ring and the ones around it
+        assert!((fall_off(0.375) - 0.5).abs() < 1e-5);
+        assert!(fall_off(0.25) < fall_off(0.375));
+
+        // without a texture, the light is brightest at its center
+        assert_eq!(LineLight2d::radial_fall_off(0.0, None), 1.0);
+        assert_eq!(LineLight2d::radial_fall_off(0.5, None), 0.25);
+        assert_eq!(LineLight2d::radial_fall_off(1.0, None), 0.0);
+    }
+
+    #[test]
+    fn glow_reaching_the_view_is_drawn() {
+        use bevy::render::primitives::Frustum;
+
+        // a 320x180 view around the origin
+        let clip_from_world = Mat4::orthographic_rh(-160.0, 160.0, -90.0, 90.0, -1000.0, 1000.0);
+        let frustum = Frustum::from_clip_from_world(&clip_from_world);
+        let lamp = LineLight2d::point(Vec4::ONE, 40.0, 0.0);
+        let drawn = |light: &LineLight2d, transform: Transform| {
+            let aabb = line_light_2d_aabb(light, transform.scale);
+            frustum.intersects_obb(&aabb, &transform.compute_affine(), true, false)
+        };
+
+        // off-screen, but its glow reaches in past the edge
+        assert!(drawn(&lamp, Transform::from_xyz(190.0, 0.0, 0.0)));
+        assert!(drawn(&lamp, Transform::from_xyz(0.0, -125.0, 0.0)));
+        // the glow doesn't shrink with the scale of what the lamp is attached to
+        assert!(drawn(
+            &lamp,
+            Transform::from_xyz(190.0, 0.0, 0.0).with_scale(Vec3::splat(0.5))
+        ));
+        // too far for its glow to reach
+        assert!(!drawn(&lamp, Transform::from_xyz(210.0, 0.0, 0.0)));
+        assert!(!drawn(
+            &lamp,
+            Transform::from_xyz(210.0, 0.0, 0.0).with_scale(Vec3::splat(2.0))
+        ));
+
+        // the ends of a line light glow past them too
+        let mut beam = LineLight2d::point(Vec4::ONE, 10.0, 0.0);
+        beam.half_length = 30.0;
+        assert!(drawn(&beam, Transform::from_xyz(-195.0, 0.0, 0.0)));
+        assert!(!drawn(&beam, Transform::from_xyz(-205.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn inner_radius_keeps_full_intensity() {
+        let mut light = LineLight2d::point(Vec4::ONE, 40.0, 0.0);
+        light.inner_radius = 20.0;
+        let fall_off = |light: &LineLight2d, distance| {
+            let distance = LineLight2d::inner_distance(distance, light.inner_fraction());
+            LineLight2d::radial_fall_off(distance, None)
+        };
+
+        // flat out to the inner radius, then the usual curve out to the edge
+        for distance in [0.0, 0.25, 0.49] {
+            assert_eq!(fall_off(&light, distance), 1.0);
+        }
+        assert_eq!(fall_off(&light, 0.75), 0.25);
+        assert_eq!(fall_off(&light, 1.0), 0.0);
+
+        // no inner radius is the usual curve
+        light.inner_radius = 0.0;
+        assert_eq!(fall_off(&light, 0.5), 0.25);
+
+        // past the range, a hard edged disc
+        light.inner_radius = 60.0;
+        assert_eq!(light.inner_fraction(), 1.0);
+        assert_eq!(fall_off(&light, 0.99), 1.0);
+        assert_eq!(fall_off(&light, 1.0), 0.0);
+    }
+
+    #[test]
+    fn temperatures_have_expected_hues() {
+        // tungsten is orange, with much less blue than red
+        let warm = kelvin_to_rgb(2700.0);
+        assert_eq!(warm.x, 1.0);
+        assert!(warm.y > 0.6 && warm.y < 0.7);
+        assert!(warm.z > 0.3 && warm.z < 0.4);
+
+        // daylight is close to white
+        let neutral = kelvin_to_rgb(6500.0);
+        assert!(neutral.min_element() > 0.95);
+
+        // hot stars are blue
+        let cool = kelvin_to_rgb(15000.0);
+        assert!(cool.z > cool.x);
+    }
+
+    #[test]
+    fn temperature_overrides_color() {
+        let mut light = LineLight2d::point(Vec4::new(0.1, 0.2, 0.3, 0.5), 10.0, 0.0);
+        assert_eq!(light.shaded_color(), light.color);
+
+        light.temperature_k = Some(2700.0);
+        assert_eq!(light.shaded_color(), kelvin_to_rgb(2700.0).extend(0.5));
+        // out of range temperatures are clamped
+        light.temperature_k = Some(0.0);
+        assert_eq!(light.shaded_color(), kelvin_to_rgb(1000.0).extend(0.5));
+    }
+
+    #[test]
+    fn shadows_take_shadow_tint() {
+        let mut light = LineLight2d::point(Vec4::new(1.0, 0.5, 0.0, 2.0), 10.0, 0.0);
+        // shadows are black by default
+        assert!(!light.has_shadow_tint());
+        assert_eq!(light.light_at(1.0, 1.0), Vec3::ZERO);
+        assert_eq!(light.light_at(0.5, 0.0), Vec3::new(1.0, 0.5, 0.0));
+
+        light.shadow_tint = Vec4::new(0.0, 0.0, 1.0, 0.5);
+        assert!(light.has_shadow_tint());
+        assert_eq!(light.light_at(1.0, 1.0), Vec3::new(0.0, 0.0, 0.5));
+        // falls off with the rest of the light
+        assert_eq!(light.light_at(0.5, 1.0), Vec3::new(0.0, 0.0, 0.25));
+        // lit parts are unaffected
+        assert_eq!(light.light_at(0.5, 0.0), Vec3::new(1.0, 0.5, 0.0));
+        // soft shadow edges blend between the two
+        assert_eq!(light.light_at(1.0, 0.5), Vec3::new(1.0, 0.5, 0.25));
+
+        // a tint with no intensity doesn't need to be drawn
+        light.shadow_tint = Vec4::new(0.0, 0.0, 1.0, 0.0);
+        assert!(!light.has_shadow_tint());
+    }
 }
@@ -11,23 +11,62 @@ use bevy::{
 };
 
 pub use ambient_light::AmbientLight2d;
-pub use line_light::LineLight2d;
-pub use occluder::{Occluder2d, Occluder2dGroups};
+pub use beam::{cast_light_ray, light_line_of_sight, RayHit};
+pub use compute_shadows::ComputeShadows;
+pub use diagnostics::LightingDiagnostics;
+pub use dither::LightingDither;
+pub use glow_sprite::GlowSprites;
+pub use lens::LensFocus;
+pub use light_buffer::LightBufferScale;
+pub use light_toggle::{
+    GlobalFlicker, LightIgnition, LightSchedule, LightToggle, LightTogglePlugin, SyncedFlicker,
+};
+pub use line_light::{LineLight2d, LineLight2dBlendMode};
+pub use normal_map::{LitSprites, NormalMap2d};
+pub use occluder::{
+    occluder_2d_occludes, CookieAnimation, LightDepth, MaxShadowLength, Occluder2d,
+    Occluder2dAlphaMask, Occluder2dGroups, Occluder2dPolygon,
+};
+pub use room_mask::{LightRoomMask, MAX_LIGHT_ROOM_RECTS};
+pub use shadow_mask::SoftShadows;
+pub use time_of_day::{TimeOfDay, TimeOfDayWindow};
 
 use ambient_light::AmbientLight2dPlugin;
+use compute_shadows::{ComputeShadowsLabel, ComputeShadowsNode, ComputeShadowsPlugin};
+use diagnostics::LightingDiagnosticsPlugin;
+use dither::LightingDitherPlugin;
+use glow_sprite::GlowSpritePlugin;
+use lens::LensFocusPlugin;
+use light_buffer::LightBufferPlugin;
 use line_light::LineLight2dPlugin;
+use normal_map::{NormalMap2dLabel, NormalMap2dNode, NormalMap2dPlugin};
 use occluder::Occluder2dPipelinePlugin;
 use render::{
     extract_deferred_lighting_2d_camera_phases, queue_deferred_lighting, DeferredLighting2d,
     DeferredLightingLabel, DeferredLightingNode, PostProcessRes, PrepareDeferredLighting,
-    PrepareLineLight2d, RenderAmbientLight2d, RenderLineLight2d, RenderOccluder,
-    ResetOccluderStencil,
+    PrepareLineLight2d, RenderAlphaMaskOccluder, RenderAmbientLight2d,
+    RenderComputeShadowLineLight2d, RenderLineLight2d, RenderOccluder, RenderOccluder2dBatch,
+    RenderSoftShadowLineLight2d, ResetOccluderStencil,
 };
+use shadow_mask::ShadowMaskPlugin;
+use time_of_day::TimeOfDayPlugin;
 
 mod ambient_light;
+mod beam;
+mod compute_shadows;
+mod diagnostics;
+mod dither;
+mod glow_sprite;
+mod lens;
+mod light_buffer;
+mod light_toggle;
 mod line_light;
+mod normal_map;
 mod occluder;
 mod render;
+mod room_mask;
+mod shadow_mask;
+mod time_of_day;
 
 pub struct DeferredLightingPlugin;
 
@@ -35,7 +74,17 @@ impl Plugin for DeferredLightingPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(Occluder2dPipelinePlugin)
             .add_plugins(AmbientLight2dPlugin)
-            .add_plugins(LineLight2dPlugin);
+            .add_plugins(LineLight2dPlugin)
+            .add_plugins(NormalMap2dPlugin)
+            .add_plugins(LightingDitherPlugin)
+            .add_plugins(ShadowMaskPlugin)
+            .add_plugins(LightBufferPlugin)
+            .add_plugins(LightTogglePlugin)
+            .add_plugins(TimeOfDayPlugin)
+            .add_plugins(ComputeShadowsPlugin)
+            .add_plugins(GlowSpritePlugin)
+            .add_plugins(LensFocusPlugin)
+            .add_plugins(LightingDiagnosticsPlugin);
 
         let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
             return;
@@ -48,9 +97,15 @@ impl Plugin for DeferredLightingPlugin {
             .add_render_command::<DeferredLighting2d, RenderAmbientLight2d>()
             .add_render_command::<DeferredLighting2d, PrepareLineLight2d>()
             .add_render_command::<DeferredLighting2d, RenderOccluder>()
+            .add_render_command::<DeferredLighting2d, RenderAlphaMaskOccluder>()
+            .add_render_command::<DeferredLighting2d, RenderOccluder2dBatch>()
             .add_render_command::<DeferredLighting2d, RenderLineLight2d>()
+            .add_render_command::<DeferredLighting2d, RenderSoftShadowLineLight2d>()
+            .add_render_command::<DeferredLighting2d, RenderComputeShadowLineLight2d>()
             .add_render_command::<DeferredLighting2d, ResetOccluderStencil>()
             .add_systems(ExtractSchedule, extract_deferred_lighting_2d_camera_phases)
+            .add_render_graph_node::<ViewNodeRunner<NormalMap2dNode>>(Core2d, NormalMap2dLabel)
+            .add_render_graph_node::<ComputeShadowsNode>(Core2d, ComputeShadowsLabel)
             .add_render_graph_node::<ViewNodeRunner<DeferredLightingNode>>(
                 Core2d,
                 DeferredLightingLabel,
@@ -59,6 +114,8 @@ impl Plugin for DeferredLightingPlugin {
                 Core2d,
                 (
                     Node2d::MainTransparentPass,
+                    NormalMap2dLabel,
+                    ComputeShadowsLabel,
                     DeferredLightingLabel,
                     Node2d::EndMainPass,
                 ),
@@ -0,0 +1,440 @@
+use bevy::{
+    ecs::{query::QueryItem, system::lifetimeless::Read},
+    math::{Affine3, Affine3A},
+    prelude::*,
+    render::{
+        camera::ExtractedCamera,
+        extract_component::{ComponentUniforms, DynamicUniformIndex, UniformComponentPlugin},
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        render_asset::RenderAssets,
+        render_graph::{NodeRunError, RenderGraphContext, RenderLabel, ViewNode},
+        render_resource::{
+            binding_types::{sampler, texture_2d, uniform_buffer},
+            *,
+        },
+        renderer::{RenderContext, RenderDevice},
+        sync_world::TemporaryRenderEntity,
+        texture::{CachedTexture, GpuImage, TextureCache},
+        view::ViewUniformOffset,
+        Extract, ExtractSchedule, Render, RenderApp, RenderSet,
+    },
+    sprite::{Mesh2dPipeline, Mesh2dViewBindGroup},
+    utils::HashMap,
+};
+
+use super::AmbientLight2d;
+
+/// The format of the [`NormalMap2dTexture`]. Normals are stored as `normal * 0.5 + 0.5`, and an
+/// alpha of 0 means no normal mapped sprite covers the pixel.
+const NORMAL_MAP_2D_TEXTURE_FORMAT: TextureFormat = TextureFormat::Rgba8Unorm;
+
+pub struct NormalMap2dPlugin;
+
+impl Plugin for NormalMap2dPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LitSprites>()
+            .add_plugins(ExtractResourcePlugin::<LitSprites>::default())
+            .add_plugins(UniformComponentPlugin::<ExtractNormalMap2d>::default());
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .init_resource::<NormalMap2dImageBindGroups>()
+            .add_systems(ExtractSchedule, extract_normal_mapped_sprites)
+            .add_systems(
+                Render,
+                prepare_normal_map_2d_textures.in_set(RenderSet::PrepareResources),
+            )
+            .add_systems(
+                Render,
+                prepare_normal_map_2d_bind_groups.in_set(RenderSet::PrepareBindGroups),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app.init_resource::<NormalMap2dPipeline>();
+    }
+}
+
+/// [`Resource`] that turns normal mapped lighting on and off. When off, every sprite is lit as if
+/// it faces the camera.
+#[derive(Resource, ExtractResource, Clone, Copy, Debug)]
+pub struct LitSprites(pub bool);
+
+impl Default for LitSprites {
+    fn default() -> Self {
+        LitSprites(true)
+    }
+}
+
+/// Add to a [`Sprite`] so that [`LineLight2d`](super::LineLight2d)s light it based on the
+/// direction they shine from. The image is a tangent space normal map the same size as the sprite,
+/// with red pointing right, green pointing up, and alpha used to cut out the sprite's shape.
+/// Texture atlases are not supported, the whole image is used.
+#[derive(Component, Clone, Debug)]
+#[require(Sprite)]
+pub struct NormalMap2d(pub Handle<Image>);
+
+/// Render world version of a [`Sprite`] with a [`NormalMap2d`].
+#[derive(Component, ShaderType, Clone, Copy, Debug)]
+pub struct ExtractNormalMap2d {
+    world_from_local: [Vec4; 3],
+    /// The columns of the 2x2 matrix that rotates and flips the xy of the normals
+    world_from_local_normal: Vec4,
+    size: Vec2,
+    anchor: Vec2,
+    /// -1 on the axes the image is flipped along, 1 otherwise
+    flip: Vec2,
+    _wasm_padding: Vec2,
+}
+
+/// Render world [`Component`] holding the [`NormalMap2d`] image of an [`ExtractNormalMap2d`].
+#[derive(Component)]
+pub struct NormalMap2dImage(Handle<Image>);
+
+pub fn extract_normal_mapped_sprites(
+    mut commands: Commands,
+    images: Extract<Res<Assets<Image>>>,
+    q_sprites: Extract<Query<(&GlobalTransform, &ViewVisibility, &Sprite, &NormalMap2d)>>,
+) {
+    for (transform, visibility, sprite, normal_map) in q_sprites.iter() {
+        if !visibility.get() {
+            continue;
+        }
+        let Some(size) = sprite.custom_size.or_else(|| {
+            sprite
+                .rect
+                .map(|rect| rect.size())
+                .or_else(|| images.get(&sprite.image).map(|image| image.size_f32()))
+        }) else {
+            continue;
+        };
+
+        let (scale, rotation, translation) = transform.to_scale_rotation_translation();
+        let affine = Affine3::from(&Affine3A::from_scale_rotation_translation(
+            scale,
+            rotation,
+            translation,
+        ));
+        let flip = Vec2::new(
+            if sprite.flip_x { -1.0 } else { 1.0 },
+            if sprite.flip_y { -1.0 } else { 1.0 },
+        );
+        // normals point the other way on flipped images and on mirrored transforms
+        let normal_flip = flip * scale.xy().signum();
+        let rotation = Vec2::from_angle(rotation.to_euler(EulerRot::ZYX).0);
+
+        commands.spawn((
+            ExtractNormalMap2d {
+                world_from_local: affine.to_transpose(),
+                world_from_local_normal: Vec4::new(
+                    rotation.x * normal_flip.x,
+                    rotation.y * normal_flip.x,
+                    -rotation.y * normal_flip.y,
+                    rotation.x * normal_flip.y,
+                ),
+                size,
+                anchor: sprite.anchor.as_vec(),
+                flip,
+                _wasm_padding: Vec2::ZERO,
+            },
+            NormalMap2dImage(normal_map.0.clone()),
+            TemporaryRenderEntity,
+        ));
+    }
+}
+
+/// Screen sized texture the [`NormalMap2dNode`] draws the normals of every [`NormalMap2d`] into,
+/// read by the line light shader.
+#[derive(Component)]
+pub struct NormalMap2dTexture(pub CachedTexture);
+
+pub fn prepare_normal_map_2d_textures(
+    mut commands: Commands,
+    mut texture_cache: ResMut<TextureCache>,
+    render_device: Res<RenderDevice>,
+    views: Query<(Entity, &ExtractedCamera), (With<Camera2d>, With<AmbientLight2d>)>,
+) {
+    let mut textures = HashMap::default();
+    for (view, camera) in &views {
+        let Some(physical_target_size) = camera.physical_target_size else {
+            continue;
+        };
+
+        let cached_texture = textures
+            .entry(camera.target.clone())
+            .or_insert_with(|| {
+                let descriptor = TextureDescriptor {
+                    label: Some("normal_map_2d_texture"),
+                    size: Extent3d {
+                        depth_or_array_layers: 1,
+                        width: physical_target_size.x,
+                        height: physical_target_size.y,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format: NORMAL_MAP_2D_TEXTURE_FORMAT,
+                    usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                };
+
+                texture_cache.get(&render_device, descriptor)
+            })
+            .clone();
+
+        commands
+            .entity(view)
+            .insert(NormalMap2dTexture(cached_texture));
+    }
+}
+
+#[derive(Resource)]
+pub struct NormalMap2dUniformBindGroup {
+    value: BindGroup,
+}
+
+/// Render world [`Component`] holding the texture bind group of a [`NormalMap2dImage`].
+#[derive(Component)]
+pub struct NormalMap2dImageBindGroup(BindGroup);
+
+/// [`Resource`] holding the texture bind group of every loaded [`NormalMap2d`] image, shared by
+/// the sprites that use it. The sprites are extracted again every frame, so the bind groups are
+/// kept here instead, and only created again when the texture they bind changes.
+#[derive(Resource, Default)]
+pub struct NormalMap2dImageBindGroups(HashMap<AssetId<Image>, (TextureViewId, BindGroup)>);
+
+/// [`System`] that creates the uniform bind group of the normal mapped sprites, and gives each of
+/// them the [`NormalMap2dImageBindGroup`] of its image once it loads.
+pub fn prepare_normal_map_2d_bind_groups(
+    mut commands: Commands,
+    uniforms: Res<ComponentUniforms<ExtractNormalMap2d>>,
+    q_normal_maps: Query<(Entity, &NormalMap2dImage)>,
+    images: Res<RenderAssets<GpuImage>>,
+    mut image_bind_groups: ResMut<NormalMap2dImageBindGroups>,
+    pipeline: Res<NormalMap2dPipeline>,
+    render_device: Res<RenderDevice>,
+) {
+    if let Some(binding) = uniforms.uniforms().binding() {
+        commands.insert_resource(NormalMap2dUniformBindGroup {
+            value: render_device.create_bind_group(
+                "normal_map_2d_uniform_bind_group",
+                &pipeline.uniform_layout,
+                &BindGroupEntries::single(binding),
+            ),
+        });
+    }
+
+    // images that were unloaded don't need their bind groups anymore
+    image_bind_groups
+        .0
+        .retain(|id, _| images.get(*id).is_some());
+
+    for (entity, normal_map) in q_normal_maps.iter() {
+        // skipped until the image loads, the sprite is lit as if it faces the camera until then
+        let Some(image) = images.get(&normal_map.0) else {
+            continue;
+        };
+        let texture_view = image.texture_view.id();
+        let (_, bind_group) = image_bind_groups
+            .0
+            .entry(normal_map.0.id())
+            .and_modify(|(cached_view, bind_group)| {
+                if *cached_view != texture_view {
+                    *cached_view = texture_view;
+                    *bind_group = create_image_bind_group(&render_device, &pipeline, image);
+                }
+            })
+            .or_insert_with(|| {
+                (
+                    texture_view,
+                    create_image_bind_group(&render_device, &pipeline, image),
+                )
+            });
+        commands
+            .entity(entity)
+            .insert(NormalMap2dImageBindGroup(bind_group.clone()));
+    }
+}
+
+fn create_image_bind_group(
+    render_device: &RenderDevice,
+    pipeline: &NormalMap2dPipeline,
+    image: &GpuImage,
+) -> BindGroup {
+    render_device.create_bind_group(
+        "normal_map_2d_image_bind_group",
+        &pipeline.image_layout,
+        &BindGroupEntries::sequential((&image.texture_view, &image.sampler)),
+    )
+}
+
+#[derive(Resource)]
+pub struct NormalMap2dPipeline {
+    pub uniform_layout: BindGroupLayout,
+    pub image_layout: BindGroupLayout,
+    pub pipeline_id: CachedRenderPipelineId,
+}
+
+impl FromWorld for NormalMap2dPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+
+        let uniform_layout = render_device.create_bind_group_layout(
+            "normal_map_2d_uniform_layout",
+            &BindGroupLayoutEntries::single(
+                ShaderStages::VERTEX_FRAGMENT,
+                uniform_buffer::<ExtractNormalMap2d>(true),
+            ),
+        );
+        let image_layout = render_device.create_bind_group_layout(
+            "normal_map_2d_image_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::FRAGMENT,
+                (
+                    texture_2d(TextureSampleType::Float { filterable: true }),
+                    sampler(SamplerBindingType::Filtering),
+                ),
+            ),
+        );
+
+        let shader = world.load_asset("shaders/lighting/normal_map.wgsl");
+        let mesh2d_pipeline = Mesh2dPipeline::from_world(world);
+
+        let pipeline_id =
+            world
+                .resource_mut::<PipelineCache>()
+                .queue_render_pipeline(RenderPipelineDescriptor {
+                    label: Some("normal_map_2d_pipeline".into()),
+                    layout: vec![
+                        mesh2d_pipeline.view_layout,
+                        uniform_layout.clone(),
+                        image_layout.clone(),
+                    ],
+                    vertex: VertexState {
+                        shader: shader.clone(),
+                        shader_defs: vec![],
+                        entry_point: "vertex".into(),
+                        buffers: vec![],
+                    },
+                    fragment: Some(FragmentState {
+                        shader,
+                        shader_defs: vec![],
+                        entry_point: "fragment".into(),
+                        targets: vec![Some(ColorTargetState {
+                            format: NORMAL_MAP_2D_TEXTURE_FORMAT,
+                            blend: None,
+                            write_mask: ColorWrites::ALL,
+                        })],
+                    }),
+                    primitive: PrimitiveState::default(),
+                    depth_stencil: None,
+                    multisample: MultisampleState::default(),
+                    push_constant_ranges: vec![],
+                    zero_initialize_workgroup_memory: false,
+                });
+
+        NormalMap2dPipeline {
+            uniform_layout,
+            image_layout,
+            pipeline_id,
+        }
+    }
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+pub struct NormalMap2dLabel;
+
+/// Render graph node that draws every [`NormalMap2d`] into the view's [`NormalMap2dTexture`],
+/// before the deferred lighting pass. Sprites are drawn in no particular order, so overlapping
+/// normal mapped sprites may not use the normals of the one in front.
+pub struct NormalMap2dNode {
+    q_normal_maps: QueryState<(
+        Read<DynamicUniformIndex<ExtractNormalMap2d>>,
+        Read<NormalMap2dImageBindGroup>,
+    )>,
+}
+
+impl FromWorld for NormalMap2dNode {
+    fn from_world(world: &mut World) -> Self {
+        NormalMap2dNode {
+            q_normal_maps: world.query(),
+        }
+    }
+}
+
+impl ViewNode for NormalMap2dNode {
+    type ViewQuery = (
+        &'static NormalMap2dTexture,
+        &'static ViewUniformOffset,
+        &'static Mesh2dViewBindGroup,
+    );
+
+    fn update(&mut self, world: &mut World) {
+        self.q_normal_maps.update_archetypes(world);
+    }
+
+    fn run<'w>(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext<'w>,
+        (normal_map_texture, view_uniform_offset, view_bind_group): QueryItem<'w, Self::ViewQuery>,
+        world: &'w World,
+    ) -> Result<(), NodeRunError> {
+        // always cleared, so lights see flat sprites when lit sprites are turned off
+        let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+            label: Some("normal_map_2d_pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: &normal_map_texture.0.default_view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(LinearRgba::NONE.into()),
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        if !world.get_resource::<LitSprites>().is_some_and(|lit| lit.0) {
+            return Ok(());
+        }
+        let pipeline = world.resource::<NormalMap2dPipeline>();
+        let Some(render_pipeline) = world
+            .resource::<PipelineCache>()
+            .get_render_pipeline(pipeline.pipeline_id)
+        else {
+            return Ok(());
+        };
+        let Some(uniform_bind_group) = world.get_resource::<NormalMap2dUniformBindGroup>() else {
+            return Ok(());
+        };
+
+        render_pass.set_render_pipeline(render_pipeline);
+        render_pass.set_bind_group(0, &view_bind_group.value, &[view_uniform_offset.offset]);
+        for (index, image_bind_group) in self.q_normal_maps.iter_manual(world) {
+            render_pass.set_bind_group(1, &uniform_bind_group.value, &[index.index()]);
+            render_pass.set_bind_group(2, &image_bind_group.0, &[]);
+            render_pass.draw(0..6, 0..1);
+        }
+
+        Ok(())
+    }
+}
+
+// WebGL2 requires thes structs be 16-byte aligned
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem;
+
+    #[test]
+    fn normal_map_2d_alignment() {
+        assert_eq!(mem::size_of::<ExtractNormalMap2d>() % 16, 0);
+    }
+}
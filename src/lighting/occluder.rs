@@ -15,22 +15,30 @@ use bevy::{
             ComponentUniforms, DynamicUniformIndex, ExtractComponent, ExtractComponentPlugin,
             UniformComponentPlugin,
         },
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
         primitives::Aabb,
+        render_asset::RenderAssets,
         render_phase::{PhaseItem, RenderCommand, RenderCommandResult, TrackedRenderPass},
-        render_resource::{binding_types::uniform_buffer, *},
+        render_resource::{
+            binding_types::{sampler, texture_2d, uniform_buffer},
+            *,
+        },
         renderer::{RenderDevice, RenderQueue},
-        texture::TextureCache,
+        sync_world::MainEntity,
+        texture::{GpuImage, TextureCache},
         view::{check_visibility, ViewDepthTexture, ViewTarget, VisibilitySystems},
-        Render, RenderApp, RenderSet,
+        Extract, ExtractSchedule, Render, RenderApp, RenderSet,
     },
     sprite::Mesh2dPipeline,
+    tasks::{ComputeTaskPool, ParallelSlice},
     utils::HashMap,
 };
 use bytemuck::{Pod, Zeroable};
 
 use super::{
-    line_light::{line_light_bind_group_layout, LineLight2dBounds},
+    line_light::{line_light_bind_group_layout, ExtractLineLight2d, LineLight2dBounds},
     render::PostProcessRes,
+    shadow_mask::SHADOW_MASK_FORMAT,
     AmbientLight2d,
 };
 
@@ -41,11 +49,19 @@ impl Plugin for Occluder2dPipelinePlugin {
         app.add_plugins(UniformComponentPlugin::<ExtractOccluder2d>::default())
             .add_plugins(ExtractComponentPlugin::<Occluder2d>::default())
             .add_plugins(ExtractComponentPlugin::<Occluder2dGroups>::default())
+            .add_plugins(ExtractComponentPlugin::<LightDepth>::default())
+            .add_plugins(ExtractComponentPlugin::<Occluder2dAlphaMask>::default())
+            .add_plugins(ExtractResourcePlugin::<MaxShadowLength>::default())
+            .init_resource::<Occluder2dBatchGeneration>()
+            .init_resource::<MaxShadowLength>()
             .add_systems(
                 PostUpdate,
                 (
                     calculate_occluder_2d_bounds.in_set(VisibilitySystems::CalculateBounds),
                     check_visibility::<With<Occluder2d>>.in_set(VisibilitySystems::CheckVisibility),
+                    detect_occluder_2d_batch_changes
+                        .after(TransformSystem::TransformPropagate)
+                        .after(VisibilitySystems::VisibilityPropagate),
                 ),
             );
 
@@ -54,13 +70,24 @@ impl Plugin for Occluder2dPipelinePlugin {
         };
 
         render_app
+            .init_resource::<Occluder2dBatch>()
+            .add_systems(ExtractSchedule, extract_occluder_2d_batch)
             .add_systems(
                 Render,
-                prepare_occluder_count_textures.in_set(RenderSet::PrepareResources),
+                prepare_line_light_2d_max_shadow_length.in_set(RenderSet::Queue),
             )
             .add_systems(
                 Render,
-                prepare_occluder_2d_bind_group.in_set(RenderSet::PrepareBindGroups),
+                (prepare_occluder_count_textures, prepare_occluder_2d_batch)
+                    .in_set(RenderSet::PrepareResources),
+            )
+            .add_systems(
+                Render,
+                (
+                    prepare_occluder_2d_bind_group,
+                    prepare_occluder_2d_alpha_mask_bind_groups,
+                )
+                    .in_set(RenderSet::PrepareBindGroups),
             );
     }
 
@@ -103,6 +130,76 @@ impl Default for Occluder2dGroups {
     }
 }
 
+/// Add to line lights and occluders that are part of the background instead of the level itself.
+/// Occlusion only happens within a depth, so background lights are only shadowed by background
+/// occluders, and a foreground wall never shadows a distant background lamp. Entities without
+/// this component are in the [`LightDepth::Foreground`].
+#[derive(Component, ExtractComponent, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum LightDepth {
+    #[default]
+    Foreground,
+    Background,
+}
+
+/// How far into its [`MaxShadowLength`] a shadow starts fading, as a fraction of the length.
+pub const SHADOW_FADE_START: f32 = 0.5;
+
+/// [`Resource`] for how far shadows reach past the occluder casting them, in world units. Past
+/// [`SHADOW_FADE_START`] of the length they fade back to lit, like light scattering back into the
+/// far parts of a shadow, so a small object's shadow doesn't darken the whole level. Hard shadows
+/// drawn with the stencil can only be on or off, so they fade with a dither pattern instead. 0
+/// turns shadows off, and infinity lets them reach as far as they always have. See
+/// `max_shadow_length` in the `lighting_config` section of `Lightborne.toml`.
+#[derive(Resource, ExtractResource, Clone, Copy, Debug, PartialEq)]
+pub struct MaxShadowLength(pub f32);
+
+impl Default for MaxShadowLength {
+    fn default() -> Self {
+        MaxShadowLength(f32::INFINITY)
+    }
+}
+
+impl MaxShadowLength {
+    /// The length passed to the shaders, which can't rely on infinity working.
+    pub fn shader_length(&self) -> f32 {
+        self.0.clamp(0.0, f32::MAX)
+    }
+}
+
+/// How dark a point `distance` past the occluder casting a shadow is, from 1 in full shadow to
+/// 0 where the shadow has faded out, see [`MaxShadowLength`]. Mirrors `shadow_fade` in
+/// `functions.wgsl`.
+pub fn shadow_fade(distance: f32, max_length: f32) -> f32 {
+    let max_length = max_length.min(f32::MAX);
+    if max_length <= 0.0 {
+        return 0.0;
+    }
+    let start = max_length * SHADOW_FADE_START;
+    1.0 - ((distance - start) / (max_length - start)).clamp(0.0, 1.0)
+}
+
+/// [`System`] that gives every light the [`MaxShadowLength`], which its shadows are drawn with.
+pub fn prepare_line_light_2d_max_shadow_length(
+    mut q_lights: Query<&mut ExtractLineLight2d>,
+    max_shadow_length: Res<MaxShadowLength>,
+) {
+    let length = max_shadow_length.shader_length();
+    q_lights
+        .par_iter_mut()
+        .for_each(|mut light| light.max_shadow_length = length);
+}
+
+/// Whether an occluder casts a shadow from a line light, based on their [`Occluder2dGroups`] and
+/// [`LightDepth`]s.
+pub fn occluder_2d_occludes(
+    light_groups: Occluder2dGroups,
+    light_depth: LightDepth,
+    occluder_groups: Occluder2dGroups,
+    occluder_depth: LightDepth,
+) -> bool {
+    light_depth == occluder_depth && light_groups.0 & occluder_groups.0 != 0
+}
+
 #[derive(Component)]
 #[require(Transform, Visibility, Occluder2dGroups)]
 pub struct Occluder2d {
@@ -117,6 +214,206 @@ impl Occluder2d {
     }
 }
 
+/// Add to an [`Occluder2d`] to only cast shadows where the alpha of the image is at least 0.5, so
+/// that light shines through the holes of sprites like grates and foliage. The image is stretched
+/// to cover the occluder's rectangle.
+#[derive(Component, ExtractComponent, Clone, Debug)]
+pub struct Occluder2dAlphaMask(pub Handle<Image>);
+
+/// The number of points an [`Occluder2dAlphaMask`] is sampled at along a light ray. Matches
+/// `ALPHA_MASK_SAMPLES` in `occluder.wgsl`.
+#[cfg(test)]
+const ALPHA_MASK_SAMPLES: usize = 16;
+
+#[cfg(test)]
+impl Occluder2dAlphaMask {
+    /// Whether the light from `light_point` is blocked on its way to `point` by an occluder with
+    /// `half_size` and `mask` as its alpha mask, with both points relative to the occluder's
+    /// center. Mirrors `alpha_mask_along_ray` in `occluder.wgsl` for masks without a
+    /// [`CookieAnimation`], including the nearest filtering of the game's images, so that tests
+    /// can check the shader's shadows on the CPU.
+    fn blocks(mask: &Image, half_size: Vec2, light_point: Vec2, point: Vec2) -> bool {
+        let dir = point - light_point;
+        let dir = Vec2::select(
+            dir.abs().cmplt(Vec2::splat(0.0001)),
+            Vec2::splat(0.0001),
+            dir,
+        );
+
+        // clip the ray to the occluder's rectangle
+        let t0 = (-half_size - light_point) / dir;
+        let t1 = (half_size - light_point) / dir;
+        let t_enter = t0.min(t1).max_element().max(0.0);
+        let t_exit = t0.max(t1).min_element().min(1.0);
+        if t_enter > t_exit {
+            return false;
+        }
+
+        let size = mask.size();
+        let alpha = (0..ALPHA_MASK_SAMPLES)
+            .map(|i| {
+                let t = t_enter + (t_exit - t_enter) * (i as f32 + 0.5) / ALPHA_MASK_SAMPLES as f32;
+                // uv (0, 0) is the top left of the image
+                let mut uv = (light_point + dir * t) / (2.0 * half_size) + 0.5;
+                uv.y = 1.0 - uv.y;
+                let texel = (uv * size.as_vec2())
+                    .floor()
+                    .clamp(Vec2::ZERO, (size - 1).as_vec2())
+                    .as_uvec2();
+                mask.get_color_at(texel.x, texel.y)
+                    .map_or(0.0, |color| color.alpha())
+            })
+            .fold(0.0, f32::max);
+        alpha >= 0.5
+    }
+}
+
+/// Add to an [`Occluder2d`] with an [`Occluder2dAlphaMask`] to turn and slide the mask over time,
+/// like an animated light cookie. A fan blade mask that rotates sweeps its shadows around, and a
+/// scrolling mask casts moving caustics. Scrolled masks wrap around, while the corners of rotated
+/// masks let light through. Masks without this component skip the animation entirely.
+#[derive(Component, Clone, Copy, Default, Debug)]
+pub struct CookieAnimation {
+    /// Counterclockwise turns of the mask around its center, in radians per second
+    pub rotation_speed: f32,
+    /// How far the mask slides every second, in multiples of its size
+    pub scroll: Vec2,
+}
+
+/// Add to an [`Occluder2d`] to cast the shadow of a polygon instead of a rectangle, for slanted
+/// and uneven walls. The occluder's `half_size` should still cover the whole polygon, since it is
+/// used to cull the occluder. Polygons are only drawn as part of the [`Occluder2dBatch`], so
+/// polygon occluders that aren't batched fall back to their rectangle.
+#[derive(Component, Clone, Debug)]
+pub struct Occluder2dPolygon {
+    /// The corners of the polygon relative to the occluder, counterclockwise
+    points: Vec<Vec2>,
+    vertices: Vec<Occluder2dVertex>,
+    indices: Vec<u32>,
+}
+
+impl Occluder2dPolygon {
+    /// Builds the shadow geometry of a polygon with corners `points`, in either winding order.
+    /// Repeated points and points in the middle of a straight edge are skipped. Returns [`None`]
+    /// if there is no area left, or the polygon crosses itself and can't be triangulated.
+    pub fn new(points: &[Vec2]) -> Option<Self> {
+        let points = simplify_polygon(points);
+        if points.len() < 3 {
+            return None;
+        }
+        let fill = triangulate_polygon(&points)?;
+        let n = points.len() as u32;
+
+        // the corners themselves are never pushed away from the light, and make up the polygon's
+        // body along with the base of every edge
+        let mut vertices: Vec<_> = points
+            .iter()
+            .map(|point| Occluder2dVertex::new(point.extend(0.0), Vec3::ZERO))
+            .collect();
+        let mut indices = fill;
+        // every edge gets a quad that is stretched away from the light when the edge faces away
+        // from it, like the sides of the rectangle in VERTICES
+        for i in 0..n {
+            let (a, b) = (points[i as usize], points[((i + 1) % n) as usize]);
+            let normal = -(b - a).perp().normalize().extend(0.0);
+            vertices.push(Occluder2dVertex::new(a.extend(0.0), normal));
+            vertices.push(Occluder2dVertex::new(b.extend(0.0), normal));
+
+            let (a_pushed, b_pushed) = (n + 2 * i, n + 2 * i + 1);
+            indices.extend([i, a_pushed, b_pushed, i, b_pushed, (i + 1) % n]);
+        }
+
+        Some(Occluder2dPolygon {
+            points,
+            vertices,
+            indices,
+        })
+    }
+
+    pub fn points(&self) -> &[Vec2] {
+        &self.points
+    }
+
+    /// The half size of the smallest [`Occluder2d`] centered on the occluder that covers the
+    /// polygon.
+    pub fn half_size(&self) -> Vec2 {
+        self.points
+            .iter()
+            .fold(Vec2::ZERO, |half_size, point| half_size.max(point.abs()))
+    }
+}
+
+/// Removes repeated points and points on a straight line between their neighbors from a polygon,
+/// and makes it counterclockwise.
+fn simplify_polygon(points: &[Vec2]) -> Vec<Vec2> {
+    let mut points = points.to_vec();
+    loop {
+        let n = points.len();
+        let redundant = (0..n).find(|&i| {
+            let (prev, point, next) = (points[(i + n - 1) % n], points[i], points[(i + 1) % n]);
+            prev.distance_squared(point) < 1e-6
+                || (point - prev).perp_dot(next - point).abs() < 1e-6
+        });
+        match redundant {
+            Some(i) if n > 2 => {
+                points.remove(i);
+            }
+            _ => break,
+        }
+    }
+
+    let double_area: f32 = (0..points.len())
+        .map(|i| points[i].perp_dot(points[(i + 1) % points.len()]))
+        .sum();
+    if double_area < 0.0 {
+        points.reverse();
+    }
+    points
+}
+
+/// Splits a counterclockwise polygon into counterclockwise triangles by clipping ears, returning
+/// the indices of their corners. Returns [`None`] if the polygon crosses itself.
+fn triangulate_polygon(points: &[Vec2]) -> Option<Vec<u32>> {
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut indices = Vec::with_capacity(points.len().saturating_sub(2) * 3);
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let ear = (0..n).find(|&i| {
+            let corners = [
+                remaining[(i + n - 1) % n],
+                remaining[i],
+                remaining[(i + 1) % n],
+            ];
+            let [a, b, c] = corners.map(|corner| points[corner]);
+            // reflex corners can't be ears, and neither can corners with the polygon inside them
+            (b - a).perp_dot(c - b) > 0.0
+                && !remaining.iter().any(|other| {
+                    !corners.contains(other) && triangle_contains(a, b, c, points[*other])
+                })
+        })?;
+        indices.extend([
+            remaining[(ear + n - 1) % n],
+            remaining[ear],
+            remaining[(ear + 1) % n],
+        ]);
+        remaining.remove(ear);
+    }
+    // the last triangle is only clockwise if the polygon crosses itself
+    let [a, b, c] = [remaining[0], remaining[1], remaining[2]].map(|corner| points[corner]);
+    if (b - a).perp_dot(c - b) <= 0.0 {
+        return None;
+    }
+    indices.extend(remaining);
+    Some(indices.into_iter().map(|index| index as u32).collect())
+}
+
+/// Whether `point` is inside or on the edge of the counterclockwise triangle `a`, `b`, `c`.
+fn triangle_contains(a: Vec2, b: Vec2, c: Vec2, point: Vec2) -> bool {
+    (b - a).perp_dot(point - a) >= 0.0
+        && (c - b).perp_dot(point - b) >= 0.0
+        && (a - c).perp_dot(point - c) >= 0.0
+}
+
 pub fn calculate_occluder_2d_bounds(
     mut commands: Commands,
     q_light_changed: Query<(Entity, &Occluder2d), Changed<Occluder2d>>,
@@ -132,11 +429,15 @@ pub fn calculate_occluder_2d_bounds(
 
 impl ExtractComponent for Occluder2d {
     type Out = (ExtractOccluder2d, Occluder2dBounds);
-    type QueryData = (&'static GlobalTransform, &'static Occluder2d);
+    type QueryData = (
+        &'static GlobalTransform,
+        &'static Occluder2d,
+        Option<&'static CookieAnimation>,
+    );
     type QueryFilter = ();
 
     fn extract_component(
-        (transform, occluder): QueryItem<'_, Self::QueryData>,
+        (transform, occluder, animation): QueryItem<'_, Self::QueryData>,
     ) -> Option<Self::Out> {
         // FIXME: should not do calculations in extract
         let (scale, rotation, translation) = transform.to_scale_rotation_translation();
@@ -151,6 +452,9 @@ impl ExtractComponent for Occluder2d {
                 local_from_world_transpose_a: a,
                 local_from_world_transpose_b: b,
                 half_size: occluder.half_size,
+                animated: animation.is_some() as u32,
+                rotation_speed: animation.map_or(0.0, |animation| animation.rotation_speed),
+                scroll: animation.map_or(Vec2::ZERO, |animation| animation.scroll),
             },
             Occluder2dBounds {
                 transform: transform.compute_transform(),
@@ -167,6 +471,10 @@ pub struct ExtractOccluder2d {
     local_from_world_transpose_a: [Vec4; 2],
     local_from_world_transpose_b: f32,
     half_size: Vec2,
+    /// Whether the occluder has a [`CookieAnimation`]
+    animated: u32,
+    rotation_speed: f32,
+    scroll: Vec2,
 }
 
 #[derive(Component, Clone, Copy)]
@@ -189,7 +497,7 @@ impl Occluder2dBounds {
     }
 }
 
-#[derive(Clone, Copy, Pod, Zeroable)]
+#[derive(Clone, Copy, Pod, Zeroable, Debug)]
 #[repr(C)]
 pub struct Occluder2dVertex {
     position: Vec3,
@@ -248,6 +556,250 @@ impl FromWorld for Occluder2dBuffers {
     }
 }
 
+/// [`Resource`] that is bumped whenever an occluder that could be part of the [`Occluder2dBatch`]
+/// changes, so that the batch is only rebuilt when needed.
+#[derive(Resource, Default)]
+pub struct Occluder2dBatchGeneration(pub u32);
+
+/// [`System`] that bumps the [`Occluder2dBatchGeneration`] when occluders are added, removed,
+/// moved, or hidden.
+#[allow(clippy::type_complexity)]
+pub fn detect_occluder_2d_batch_changes(
+    mut generation: ResMut<Occluder2dBatchGeneration>,
+    q_changed: Query<
+        (),
+        (
+            With<Occluder2d>,
+            Or<(
+                Changed<GlobalTransform>,
+                Changed<Occluder2d>,
+                Changed<Occluder2dPolygon>,
+                Changed<Occluder2dGroups>,
+                Changed<LightDepth>,
+                Changed<InheritedVisibility>,
+                Added<Occluder2dAlphaMask>,
+            )>,
+        ),
+    >,
+    mut removed_occluders: RemovedComponents<Occluder2d>,
+    mut removed_alpha_masks: RemovedComponents<Occluder2dAlphaMask>,
+    mut removed_polygons: RemovedComponents<Occluder2dPolygon>,
+    mut removed_depths: RemovedComponents<LightDepth>,
+) {
+    let removed = removed_occluders.read().count()
+        + removed_alpha_masks.read().count()
+        + removed_polygons.read().count()
+        + removed_depths.read().count()
+        > 0;
+    if removed || !q_changed.is_empty() {
+        generation.0 = generation.0.wrapping_add(1);
+    }
+}
+
+/// Render world [`Resource`] containing the geometry of every foreground occluder that occludes
+/// all lights ([`Occluder2dGroups::ALL`]) and has no [`Occluder2dAlphaMask`], already transformed
+/// into world space. This lets each light draw the shadows of all of these occluders with a single draw call
+/// instead of one per occluder.
+#[derive(Resource)]
+pub struct Occluder2dBatch {
+    pub vertices: RawBufferVec<Occluder2dVertex>,
+    pub indices: RawBufferVec<u32>,
+    generation: Option<u32>,
+    dirty: bool,
+}
+
+impl Default for Occluder2dBatch {
+    fn default() -> Self {
+        Occluder2dBatch {
+            vertices: RawBufferVec::new(BufferUsages::VERTEX),
+            indices: RawBufferVec::new(BufferUsages::INDEX),
+            generation: None,
+            dirty: false,
+        }
+    }
+}
+
+impl Occluder2dBatch {
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.indices.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Adds the geometry of an occluder to the batch. Like in [`ExtractOccluder2d`], the scale of
+    /// the transform is ignored apart from its sign.
+    pub fn push(&mut self, transform: &GlobalTransform, half_size: Vec2) {
+        self.push_geometry(transform, &VERTICES, &INDICES, half_size);
+    }
+
+    /// Adds the geometry of an occluder with an [`Occluder2dPolygon`] to the batch.
+    pub fn push_polygon(&mut self, transform: &GlobalTransform, polygon: &Occluder2dPolygon) {
+        self.push_geometry(transform, &polygon.vertices, &polygon.indices, Vec2::ONE);
+    }
+
+    fn push_geometry(
+        &mut self,
+        transform: &GlobalTransform,
+        vertices: &[Occluder2dVertex],
+        indices: &[u32],
+        scale: Vec2,
+    ) {
+        let (transform_scale, rotation, translation) = transform.to_scale_rotation_translation();
+        let world_from_local = Affine3A::from_scale_rotation_translation(
+            transform_scale.signum(),
+            rotation,
+            translation,
+        );
+
+        let offset = self.vertices.len() as u32;
+        for vertex in vertices {
+            self.vertices.push(Occluder2dVertex::new(
+                world_from_local.transform_point3(vertex.position * scale.extend(1.0)),
+                world_from_local.transform_vector3(vertex.normal),
+            ));
+        }
+        for index in indices {
+            self.indices.push(offset + index);
+        }
+    }
+}
+
+/// [`System`] that rebuilds the [`Occluder2dBatch`] when the [`Occluder2dBatchGeneration`]
+/// changes.
+#[allow(clippy::type_complexity)]
+pub fn extract_occluder_2d_batch(
+    mut batch: ResMut<Occluder2dBatch>,
+    generation: Extract<Res<Occluder2dBatchGeneration>>,
+    q_occluders: Extract<
+        Query<
+            (
+                &GlobalTransform,
+                &Occluder2d,
+                Option<&Occluder2dPolygon>,
+                &Occluder2dGroups,
+                Option<&LightDepth>,
+                &InheritedVisibility,
+            ),
+            Without<Occluder2dAlphaMask>,
+        >,
+    >,
+) {
+    if batch.generation == Some(generation.0) {
+        return;
+    }
+    batch.generation = Some(generation.0);
+    batch.dirty = true;
+    batch.clear();
+
+    for (transform, occluder, polygon, groups, depth, visibility) in q_occluders.iter() {
+        let depth = depth.copied().unwrap_or_default();
+        if !is_occluder_2d_batched(*groups, false, depth) || !visibility.get() {
+            continue;
+        }
+        match polygon {
+            Some(polygon) => batch.push_polygon(transform, polygon),
+            None => batch.push(transform, occluder.half_size),
+        }
+    }
+}
+
+pub fn prepare_occluder_2d_batch(
+    mut batch: ResMut<Occluder2dBatch>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    if !batch.dirty {
+        return;
+    }
+    batch.dirty = false;
+    batch.vertices.write_buffer(&render_device, &render_queue);
+    batch.indices.write_buffer(&render_device, &render_queue);
+}
+
+/// Whether an occluder is drawn as part of the [`Occluder2dBatch`] instead of on its own.
+pub fn is_occluder_2d_batched(
+    groups: Occluder2dGroups,
+    alpha_masked: bool,
+    depth: LightDepth,
+) -> bool {
+    groups == Occluder2dGroups::ALL && !alpha_masked && depth == LightDepth::Foreground
+}
+
+/// How many lights each task checks against the occluders in view in [`filter_light_occluders`].
+/// Fewer lights than this are checked on the calling thread.
+const LIGHT_OCCLUDER_CHUNK_SIZE: usize = 32;
+
+/// A line light in view, as checked against the occluders in view by [`filter_light_occluders`].
+#[derive(Clone, Copy)]
+pub struct ViewLight2d {
+    pub entity: (Entity, MainEntity),
+    pub bounds: LineLight2dBounds,
+    pub groups: Occluder2dGroups,
+    pub depth: LightDepth,
+    /// Whether the light is drawn with compute shadows, which leaves only alpha masked occluders
+    /// to the stencil
+    pub compute_shadows: bool,
+}
+
+/// An occluder in view, as checked against the lights in view by [`filter_light_occluders`].
+#[derive(Clone, Copy)]
+pub struct ViewOccluder2d {
+    pub entity: (Entity, MainEntity),
+    pub bounds: Occluder2dBounds,
+    pub groups: Occluder2dGroups,
+    pub depth: LightDepth,
+    pub alpha_masked: bool,
+}
+
+impl ViewOccluder2d {
+    /// Whether the occluder's shadow from `light` is drawn on its own.
+    fn draws_shadow(&self, light: &ViewLight2d) -> bool {
+        // the rest are drawn all at once in the batch, or by the compute pass
+        let drawn_alone = !(light.compute_shadows && !self.alpha_masked)
+            && !is_occluder_2d_batched(self.groups, self.alpha_masked, self.depth);
+        drawn_alone
+            && occluder_2d_occludes(light.groups, light.depth, self.groups, self.depth)
+            && self.bounds.visible_from_line_light(&light.bounds)
+    }
+}
+
+/// The indices into `occluders` of the occluders whose shadows are drawn on their own for each of
+/// the `lights`. Every light is checked against every occluder in view, so with many lights they
+/// are split into chunks that are checked in parallel on the [`ComputeTaskPool`]. The results
+/// are in the same order as `lights` however the work was split up, so the phase items queued
+/// from them are too.
+pub fn filter_light_occluders(
+    lights: &[ViewLight2d],
+    occluders: &[ViewOccluder2d],
+) -> Vec<Vec<usize>> {
+    let filter = |light: &ViewLight2d| -> Vec<usize> {
+        if light.groups == Occluder2dGroups::NONE {
+            return vec![];
+        }
+        occluders
+            .iter()
+            .enumerate()
+            .filter(|(_, occluder)| occluder.draws_shadow(light))
+            .map(|(index, _)| index)
+            .collect()
+    };
+    if lights.len() <= LIGHT_OCCLUDER_CHUNK_SIZE {
+        return lights.iter().map(filter).collect();
+    }
+    lights
+        .par_chunk_map(
+            ComputeTaskPool::get(),
+            LIGHT_OCCLUDER_CHUNK_SIZE,
+            |_, chunk| chunk.iter().map(filter).collect::<Vec<_>>(),
+        )
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
 #[derive(Component)]
 pub struct OccluderCountTexture(pub ViewDepthTexture);
 
@@ -321,6 +873,70 @@ pub fn prepare_occluder_2d_bind_group(
     }
 }
 
+/// Render world [`Component`] holding the texture bind group of an [`Occluder2dAlphaMask`]. Kept
+/// from frame to frame, and only created again when the texture it binds changes.
+#[derive(Component)]
+pub struct Occluder2dAlphaMaskBindGroup {
+    value: BindGroup,
+    texture_view: TextureViewId,
+}
+
+/// [`System`] that creates the [`Occluder2dAlphaMaskBindGroup`] of every occluder with a loaded
+/// [`Occluder2dAlphaMask`].
+pub fn prepare_occluder_2d_alpha_mask_bind_groups(
+    mut commands: Commands,
+    q_alpha_masks: Query<(
+        Entity,
+        &Occluder2dAlphaMask,
+        Option<&Occluder2dAlphaMaskBindGroup>,
+    )>,
+    images: Res<RenderAssets<GpuImage>>,
+    pipeline: Res<Occluder2dPipeline>,
+    render_device: Res<RenderDevice>,
+) {
+    for (entity, alpha_mask, bind_group) in q_alpha_masks.iter() {
+        // skipped until the image loads, the occluder won't cast a shadow until then
+        let Some(image) = images.get(&alpha_mask.0) else {
+            continue;
+        };
+        let texture_view = image.texture_view.id();
+        if bind_group.is_some_and(|bind_group| bind_group.texture_view == texture_view) {
+            continue;
+        }
+        commands
+            .entity(entity)
+            .insert(Occluder2dAlphaMaskBindGroup {
+                value: render_device.create_bind_group(
+                    "occluder_2d_alpha_mask_bind_group",
+                    &pipeline.alpha_mask_layout,
+                    &BindGroupEntries::sequential((&image.texture_view, &image.sampler)),
+                ),
+                texture_view,
+            });
+    }
+}
+
+pub struct SetOccluder2dAlphaMaskBindGroup<const I: usize>;
+impl<P: PhaseItem, const I: usize> RenderCommand<P> for SetOccluder2dAlphaMaskBindGroup<I> {
+    type Param = ();
+    type ViewQuery = ();
+    type ItemQuery = Read<Occluder2dAlphaMaskBindGroup>;
+
+    fn render<'w>(
+        _item: &P,
+        _view: ROQueryItem<'w, Self::ViewQuery>,
+        entity: Option<ROQueryItem<'w, Self::ItemQuery>>,
+        _param: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Some(bind_group) = entity else {
+            return RenderCommandResult::Skip;
+        };
+        pass.set_bind_group(I, &bind_group.value, &[]);
+        RenderCommandResult::Success
+    }
+}
+
 pub struct SetOccluder2dBindGroup<const I: usize>;
 impl<P: PhaseItem, const I: usize> RenderCommand<P> for SetOccluder2dBindGroup<I> {
     type Param = SRes<Occluder2dBindGroup>;
@@ -369,18 +985,130 @@ impl<P: PhaseItem> RenderCommand<P> for DrawOccluder2d {
     }
 }
 
+pub struct DrawOccluder2dBatch;
+impl<P: PhaseItem> RenderCommand<P> for DrawOccluder2dBatch {
+    type Param = SRes<Occluder2dBatch>;
+    type ViewQuery = ();
+    type ItemQuery = ();
+
+    fn render<'w>(
+        _item: &P,
+        _view: ROQueryItem<'w, Self::ViewQuery>,
+        _entity: Option<ROQueryItem<'w, Self::ItemQuery>>,
+        param: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let batch = param.into_inner();
+        let (Some(vertices), Some(indices)) = (batch.vertices.buffer(), batch.indices.buffer())
+        else {
+            return RenderCommandResult::Skip;
+        };
+        if batch.is_empty() {
+            return RenderCommandResult::Skip;
+        }
+
+        pass.set_vertex_buffer(0, vertices.slice(..));
+        pass.set_index_buffer(indices.slice(..), 0, IndexFormat::Uint32);
+        pass.draw_indexed(0..batch.indices.len() as u32, 0, 0..1);
+
+        RenderCommandResult::Success
+    }
+}
+
 #[derive(Resource)]
 pub struct Occluder2dPipeline {
     pub layout: BindGroupLayout,
-    pub shadow_pipeline_id: CachedRenderPipelineId,
-    pub cutout_pipeline_id: CachedRenderPipelineId,
+    pub alpha_mask_layout: BindGroupLayout,
+    /// Pipelines that count shadows in the stencil buffer
+    pub stencil: Occluder2dPipelineIds,
+    /// Pipelines that draw shadows into the shadow mask, see
+    /// [`SoftShadows`](super::shadow_mask::SoftShadows)
+    pub shadow_mask: Occluder2dPipelineIds,
     pub reset_pipeline_id: CachedRenderPipelineId,
 }
 
+/// The pipelines used to draw the shadows of occluders into one kind of target.
+#[derive(Clone, Copy, Debug)]
+pub struct Occluder2dPipelineIds {
+    pub shadow: CachedRenderPipelineId,
+    pub alpha_mask_shadow: CachedRenderPipelineId,
+    pub cutout: CachedRenderPipelineId,
+    pub batch_shadow: CachedRenderPipelineId,
+    pub batch_cutout: CachedRenderPipelineId,
+}
+
+impl Occluder2dPipelineIds {
+    /// Queues every occluder pipeline, drawing into the shadow mask if `shadow_mask` is set and
+    /// into the stencil buffer otherwise.
+    fn queue(
+        world: &mut World,
+        layout: &BindGroupLayout,
+        alpha_mask_layout: &BindGroupLayout,
+        shadow_mask: bool,
+    ) -> Self {
+        let labels = if shadow_mask {
+            [
+                "occluder_shadow_mask_pipeline",
+                "occluder_alpha_mask_shadow_mask_pipeline",
+                "occluder_cutout_shadow_mask_pipeline",
+                "occluder_batch_shadow_mask_pipeline",
+                "occluder_batch_cutout_shadow_mask_pipeline",
+            ]
+        } else {
+            [
+                "occluder_pipeline",
+                "occluder_alpha_mask_pipeline",
+                "occluder_cutout_pipeline",
+                "occluder_batch_pipeline",
+                "occluder_batch_cutout_pipeline",
+            ]
+        };
+        let descriptors = [
+            (labels[0], false, vec![layout.clone()], vec![]),
+            (
+                labels[1],
+                false,
+                vec![layout.clone(), alpha_mask_layout.clone()],
+                vec!["OCCLUDER_ALPHA_MASK".into()],
+            ),
+            (labels[2], true, vec![layout.clone()], vec![]),
+            (labels[3], false, vec![], vec!["OCCLUDER_BATCHED".into()]),
+            (labels[4], true, vec![], vec!["OCCLUDER_BATCHED".into()]),
+        ]
+        .map(|(label, cutout, layouts, shader_defs)| {
+            build_occluder_2d_pipeline_descriptor(
+                world,
+                label,
+                cutout,
+                shadow_mask,
+                layouts,
+                shader_defs,
+            )
+        });
+
+        let pipeline_cache = world.resource_mut::<PipelineCache>();
+        let [shadow, alpha_mask_shadow, cutout, batch_shadow, batch_cutout] =
+            descriptors.map(|descriptor| pipeline_cache.queue_render_pipeline(descriptor));
+        Occluder2dPipelineIds {
+            shadow,
+            alpha_mask_shadow,
+            cutout,
+            batch_shadow,
+            batch_cutout,
+        }
+    }
+}
+
+/// Builds an occluder shadow pipeline, or a cutout pipeline if `cutout` is set. Shadows are
+/// counted in the stencil buffer, or drawn into the shadow mask if `shadow_mask` is set. `layouts`
+/// are the bind group layouts used after the line light's, starting at group 3.
 pub fn build_occluder_2d_pipeline_descriptor(
     world: &mut World,
+    label: &'static str,
     cutout: bool,
-    occluder_layout: BindGroupLayout,
+    shadow_mask: bool,
+    layouts: Vec<BindGroupLayout>,
+    mut shader_defs: Vec<ShaderDefVal>,
 ) -> RenderPipelineDescriptor {
     let render_device = world.resource::<RenderDevice>();
     let post_process_res = world.resource::<PostProcessRes>();
@@ -411,25 +1139,78 @@ pub fn build_occluder_2d_pipeline_descriptor(
         ],
     };
 
-    let mut shader_defs: Vec<ShaderDefVal> = vec![];
     if cutout {
         shader_defs.push("OCCLUDER_CUTOUT".into());
     }
+    if shadow_mask {
+        shader_defs.push("OCCLUDER_SHADOW_MASK".into());
+    }
 
-    let label = if cutout {
-        Some("occluder_cutout_pipeline".into())
+    let target = if shadow_mask {
+        // shadows are combined with max instead of counted, so overlapping occluders don't make
+        // the mask any darker than a single one, and cutouts clear the mask over occluder bodies
+        ColorTargetState {
+            format: SHADOW_MASK_FORMAT,
+            blend: Some(BlendState {
+                color: if cutout {
+                    BlendComponent::REPLACE
+                } else {
+                    BlendComponent {
+                        src_factor: BlendFactor::One,
+                        dst_factor: BlendFactor::One,
+                        operation: BlendOperation::Max,
+                    }
+                },
+                alpha: BlendComponent::REPLACE,
+            }),
+            write_mask: ColorWrites::ALL,
+        }
     } else {
-        Some("occluder_pipeline".into())
+        ColorTargetState {
+            format: ViewTarget::TEXTURE_FORMAT_HDR,
+            blend: Some(BlendState {
+                color: BlendComponent {
+                    src_factor: BlendFactor::One,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+                alpha: BlendComponent::REPLACE,
+            }),
+            write_mask: ColorWrites::ALL,
+        }
     };
+    let depth_stencil = (!shadow_mask).then(|| DepthStencilState {
+        format: TextureFormat::Stencil8,
+        depth_write_enabled: false,
+        depth_compare: CompareFunction::Always,
+        stencil: StencilState {
+            front: StencilFaceState {
+                compare: CompareFunction::Always,
+                fail_op: StencilOperation::Keep,
+                depth_fail_op: StencilOperation::Keep,
+                pass_op: if cutout {
+                    StencilOperation::Zero
+                } else {
+                    StencilOperation::IncrementClamp
+                },
+            },
+            back: StencilFaceState::default(),
+            read_mask: 0xFF,
+            write_mask: 0xFF,
+        },
+        bias: DepthBiasState::default(),
+    });
+
+    let mut layout = vec![
+        post_process_layout,
+        mesh2d_pipeline.view_layout,
+        line_light_layout,
+    ];
+    layout.extend(layouts);
 
     RenderPipelineDescriptor {
-        label,
-        layout: vec![
-            post_process_layout,
-            mesh2d_pipeline.view_layout,
-            line_light_layout,
-            occluder_layout,
-        ],
+        label: Some(label.into()),
+        layout,
         vertex: VertexState {
             shader: shader.clone(),
             shader_defs: shader_defs.clone(),
@@ -440,41 +1221,10 @@ pub fn build_occluder_2d_pipeline_descriptor(
             shader,
             shader_defs,
             entry_point: "fragment".into(),
-            targets: vec![Some(ColorTargetState {
-                format: ViewTarget::TEXTURE_FORMAT_HDR,
-                blend: Some(BlendState {
-                    color: BlendComponent {
-                        src_factor: BlendFactor::One,
-                        dst_factor: BlendFactor::One,
-                        operation: BlendOperation::Add,
-                    },
-                    alpha: BlendComponent::REPLACE,
-                }),
-                write_mask: ColorWrites::ALL,
-            })],
+            targets: vec![Some(target)],
         }),
         primitive: PrimitiveState::default(),
-        depth_stencil: Some(DepthStencilState {
-            format: TextureFormat::Stencil8,
-            depth_write_enabled: false,
-            depth_compare: CompareFunction::Always,
-            stencil: StencilState {
-                front: StencilFaceState {
-                    compare: CompareFunction::Always,
-                    fail_op: StencilOperation::Keep,
-                    depth_fail_op: StencilOperation::Keep,
-                    pass_op: if cutout {
-                        StencilOperation::Zero
-                    } else {
-                        StencilOperation::IncrementClamp
-                    },
-                },
-                back: StencilFaceState::default(),
-                read_mask: 0xFF,
-                write_mask: 0xFF,
-            },
-            bias: DepthBiasState::default(),
-        }),
+        depth_stencil,
         multisample: MultisampleState::default(),
         push_constant_ranges: vec![],
         zero_initialize_workgroup_memory: false,
@@ -493,17 +1243,23 @@ impl FromWorld for Occluder2dPipeline {
             ),
         );
 
+        let alpha_mask_layout = render_device.create_bind_group_layout(
+            "occluder_alpha_mask_bind_group_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::FRAGMENT,
+                (
+                    texture_2d(TextureSampleType::Float { filterable: true }),
+                    sampler(SamplerBindingType::Filtering),
+                ),
+            ),
+        );
+
         let reset_shader = world.load_asset("shaders/lighting/occluder_reset.wgsl");
 
-        let shadow_pipeline_descriptor =
-            build_occluder_2d_pipeline_descriptor(world, false, layout.clone());
-        let cutout_pipeline_descriptor =
-            build_occluder_2d_pipeline_descriptor(world, true, layout.clone());
+        let stencil = Occluder2dPipelineIds::queue(world, &layout, &alpha_mask_layout, false);
+        let shadow_mask = Occluder2dPipelineIds::queue(world, &layout, &alpha_mask_layout, true);
 
         let pipeline_cache = world.resource_mut::<PipelineCache>();
-        let shadow_pipeline_id = pipeline_cache.queue_render_pipeline(shadow_pipeline_descriptor);
-        let cutout_pipeline_id = pipeline_cache.queue_render_pipeline(cutout_pipeline_descriptor);
-
         let reset_pipeline_id = pipeline_cache.queue_render_pipeline(RenderPipelineDescriptor {
             label: Some("occluder_reset_pipeline".into()),
             layout: vec![],
@@ -546,8 +1302,9 @@ impl FromWorld for Occluder2dPipeline {
 
         Occluder2dPipeline {
             layout,
-            shadow_pipeline_id,
-            cutout_pipeline_id,
+            alpha_mask_layout,
+            stencil,
+            shadow_mask,
             reset_pipeline_id,
         }
     }
@@ -557,10 +1314,313 @@ impl FromWorld for Occluder2dPipeline {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::mem;
+    use bevy::tasks::TaskPool;
+    use std::{mem, time::Instant};
 
     #[test]
     fn occluder_2d_alignment() {
         assert_eq!(mem::size_of::<ExtractOccluder2d>() % 16, 0);
     }
+
+    #[test]
+    fn checkerboard_mask_casts_checkered_light() {
+        use bevy::render::render_asset::RenderAssetUsages;
+
+        // a 4x4 checkerboard, opaque where the top left texel is
+        let opaque = |x: u32, y: u32| (x + y) % 2 == 0;
+        let mask = Image::new(
+            Extent3d {
+                width: 4,
+                height: 4,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            (0..4)
+                .flat_map(|y| (0..4).map(move |x| (x, y)))
+                .flat_map(|(x, y)| [255, 255, 255, if opaque(x, y) { 255 } else { 0 }])
+                .collect(),
+            TextureFormat::Rgba8Unorm,
+            RenderAssetUsages::default(),
+        );
+        // each texel is 4 units across
+        let half_size = Vec2::splat(8.0);
+        let texel_center = |x: u32, y: u32| Vec2::new(x as f32 * 4.0 - 6.0, 6.0 - y as f32 * 4.0);
+        // a light shining straight down from above the occluder
+        let lit_from_above = |point: Vec2| {
+            !Occluder2dAlphaMask::blocks(&mask, half_size, point + Vec2::Y * 100.0, point)
+        };
+
+        // light reaches the transparent texels of the top row, and nothing past their
+        // neighbours below
+        for x in 0..4 {
+            assert_eq!(lit_from_above(texel_center(x, 0)), !opaque(x, 0));
+            assert!(!lit_from_above(texel_center(x, 1)));
+            assert!(!lit_from_above(texel_center(x, 0) - Vec2::Y * 20.0));
+        }
+        // light from the side passes through the transparent texels of the right column
+        let lit_from_right = |point: Vec2| {
+            !Occluder2dAlphaMask::blocks(&mask, half_size, point + Vec2::X * 100.0, point)
+        };
+        for y in 0..4 {
+            assert_eq!(lit_from_right(texel_center(3, y)), !opaque(3, y));
+        }
+        // rays that miss the occluder are never blocked
+        assert!(lit_from_above(Vec2::new(12.0, 0.0)));
+    }
+
+    #[test]
+    fn occluder_2d_batch_offsets_each_occluder() {
+        let mut batch = Occluder2dBatch::default();
+        for i in 0..200 {
+            let transform = GlobalTransform::from_xyz(i as f32 * 10.0, 5.0, 0.0);
+            batch.push(&transform, Vec2::new(2.0, 3.0));
+        }
+
+        assert_eq!(batch.vertices.len(), 200 * VERTICES.len());
+        assert_eq!(batch.indices.len(), 200 * INDICES.len());
+
+        // the last occluder's vertices are in world space and its indices point at them
+        let last_vertex = batch.vertices.values()[199 * VERTICES.len()];
+        assert_eq!(last_vertex.position, vec3(1988.0, 2.0, 0.0));
+        assert_eq!(
+            batch.indices.values()[199 * INDICES.len()],
+            199 * VERTICES.len() as u32
+        );
+    }
+
+    /// Pushes the vertices of `polygon` facing away from a point light at `light` like the
+    /// occluder shader does, returning the triangles of its shadow.
+    fn shadow_triangles(polygon: &Occluder2dPolygon, light: Vec2) -> Vec<[Vec2; 3]> {
+        let positions: Vec<Vec2> = polygon
+            .vertices
+            .iter()
+            .map(|vertex| {
+                let position = vertex.position.xy();
+                let point_to_light = (light - position).normalize();
+                if point_to_light.dot(vertex.normal.xy()) < 0.0 {
+                    position - 400.0 * point_to_light
+                } else {
+                    position
+                }
+            })
+            .collect();
+        polygon
+            .indices
+            .chunks(3)
+            .map(|triangle| [0, 1, 2].map(|corner| positions[triangle[corner] as usize]))
+            .collect()
+    }
+
+    /// Whether `point` is strictly inside a counterclockwise triangle of the shadow. Clockwise
+    /// triangles don't count, just like in the stencil buffer.
+    fn in_shadow(triangles: &[[Vec2; 3]], point: Vec2) -> bool {
+        triangles.iter().any(|[a, b, c]| {
+            (*b - *a).perp_dot(point - *a) > 0.0
+                && (*c - *b).perp_dot(point - *b) > 0.0
+                && (*a - *c).perp_dot(point - *c) > 0.0
+        })
+    }
+
+    #[test]
+    fn triangle_occluder_casts_angled_shadow() {
+        // a ramp going up to the right, given clockwise
+        let polygon = Occluder2dPolygon::new(&[
+            Vec2::new(-8.0, -8.0),
+            Vec2::new(8.0, 8.0),
+            Vec2::new(8.0, -8.0),
+        ])
+        .unwrap();
+        assert_eq!(polygon.half_size(), Vec2::splat(8.0));
+
+        // a light above the slope shines down along it
+        let shadow = shadow_triangles(&polygon, Vec2::new(-20.0, 20.0));
+        assert!(in_shadow(&shadow, Vec2::new(12.0, -4.0)));
+        // the edges of the shadow follow the rays through the top and bottom corners of the ramp
+        assert!(in_shadow(&shadow, Vec2::new(36.0, -8.0)));
+        assert!(!in_shadow(&shadow, Vec2::new(36.0, 0.0)));
+        assert!(in_shadow(&shadow, Vec2::new(8.0, -36.0)));
+        assert!(!in_shadow(&shadow, Vec2::new(0.0, -36.0)));
+        // the lit side of the slope
+        assert!(!in_shadow(&shadow, Vec2::new(-4.0, 4.0)));
+    }
+
+    #[test]
+    fn concave_polygons_are_triangulated() {
+        // an L shaped wall, with a repeated point and a point in the middle of an edge
+        let polygon = Occluder2dPolygon::new(&[
+            Vec2::new(0.0, 0.0),
+            Vec2::new(16.0, 0.0),
+            Vec2::new(16.0, 0.0),
+            Vec2::new(16.0, 8.0),
+            Vec2::new(8.0, 8.0),
+            Vec2::new(8.0, 16.0),
+            Vec2::new(0.0, 16.0),
+            Vec2::new(0.0, 8.0),
+        ])
+        .unwrap();
+        assert_eq!(polygon.points().len(), 6);
+
+        // the body covers the L but not the notch
+        let body = shadow_triangles(&polygon, Vec2::new(1000.0, 1000.0));
+        assert!(in_shadow(&body, Vec2::new(12.0, 4.0)));
+        assert!(in_shadow(&body, Vec2::new(4.0, 12.0)));
+        assert!(!in_shadow(&body, Vec2::new(12.0, 12.0)));
+    }
+
+    #[test]
+    fn degenerate_polygons_are_rejected() {
+        assert!(Occluder2dPolygon::new(&[]).is_none());
+        assert!(Occluder2dPolygon::new(&[Vec2::ZERO, Vec2::X]).is_none());
+        // all on one line
+        assert!(Occluder2dPolygon::new(&[Vec2::ZERO, Vec2::X, Vec2::new(2.0, 0.0)]).is_none());
+        // a bow tie crosses itself
+        assert!(Occluder2dPolygon::new(&[
+            Vec2::ZERO,
+            Vec2::new(8.0, 8.0),
+            Vec2::new(8.0, 0.0),
+            Vec2::new(0.0, 8.0),
+        ])
+        .is_none());
+    }
+
+    #[test]
+    fn foreground_walls_dont_shadow_background_lights() {
+        let all = Occluder2dGroups::ALL;
+        let (foreground, background) = (LightDepth::Foreground, LightDepth::Background);
+
+        // a background lamp behind a foreground wall and a background pillar
+        assert!(!occluder_2d_occludes(all, background, all, foreground));
+        assert!(occluder_2d_occludes(all, background, all, background));
+        // the player's light is still shadowed by the wall, but not by the pillar
+        assert!(occluder_2d_occludes(all, foreground, all, foreground));
+        assert!(!occluder_2d_occludes(all, foreground, all, background));
+
+        // the shared shadow batch only holds foreground occluders
+        assert!(is_occluder_2d_batched(all, false, foreground));
+        assert!(!is_occluder_2d_batched(all, false, background));
+    }
+
+    /// A grid of `count` lights 24 units apart, with every fourth one in the background.
+    fn grid_lights(count: usize) -> Vec<ViewLight2d> {
+        (0..count)
+            .map(|i| ViewLight2d {
+                entity: (Entity::PLACEHOLDER, MainEntity::from(Entity::PLACEHOLDER)),
+                bounds: LineLight2dBounds {
+                    transform: Transform::from_xyz(
+                        (i % 40) as f32 * 24.0,
+                        (i / 40) as f32 * 24.0,
+                        0.0,
+                    ),
+                    radius: 48.0,
+                    half_length: 0.0,
+                },
+                groups: Occluder2dGroups::ALL,
+                depth: if i % 4 == 0 {
+                    LightDepth::Background
+                } else {
+                    LightDepth::Foreground
+                },
+                compute_shadows: false,
+            })
+            .collect()
+    }
+
+    /// A grid of `count` occluders, alternating between ones that are drawn on their own and ones
+    /// that are batched or left to the compute pass.
+    fn grid_occluders(count: usize) -> Vec<ViewOccluder2d> {
+        (0..count)
+            .map(|i| ViewOccluder2d {
+                entity: (Entity::PLACEHOLDER, MainEntity::from(Entity::PLACEHOLDER)),
+                bounds: Occluder2dBounds {
+                    transform: Transform::from_xyz(
+                        (i % 20) as f32 * 48.0,
+                        (i / 20) as f32 * 48.0,
+                        0.0,
+                    ),
+                    half_size: Vec2::new(8.0, 4.0),
+                },
+                groups: Occluder2dGroups::ALL,
+                depth: if i % 3 == 0 {
+                    LightDepth::Background
+                } else {
+                    LightDepth::Foreground
+                },
+                alpha_masked: i % 2 == 0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parallel_light_occluder_filtering_keeps_light_order() {
+        ComputeTaskPool::get_or_init(TaskPool::default);
+        let lights = grid_lights(1000);
+        let occluders = grid_occluders(200);
+
+        for compute_shadows in [false, true] {
+            let mut lights = lights.clone();
+            for light in lights.iter_mut() {
+                light.compute_shadows = compute_shadows;
+            }
+            let filtered = filter_light_occluders(&lights, &occluders);
+            let expected: Vec<Vec<usize>> = lights
+                .iter()
+                .map(|light| {
+                    (0..occluders.len())
+                        .filter(|&i| occluders[i].draws_shadow(light))
+                        .collect()
+                })
+                .collect();
+            assert_eq!(filtered, expected);
+            assert!(filtered.iter().any(|occluders| !occluders.is_empty()));
+        }
+
+        // lights without groups aren't shadowed at all
+        let mut unshadowed = grid_lights(1);
+        unshadowed[0].groups = Occluder2dGroups::NONE;
+        assert_eq!(
+            filter_light_occluders(&unshadowed, &occluders),
+            vec![Vec::<usize>::new()]
+        );
+    }
+
+    /// Compares checking 1000 lights against 200 occluders on one thread with
+    /// [`filter_light_occluders`], which splits the lights over the [`ComputeTaskPool`]. Run with
+    /// `cargo test --release bench_light_occluder_filtering -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_light_occluder_filtering() {
+        const ITERATIONS: u32 = 200;
+        ComputeTaskPool::get_or_init(TaskPool::default);
+        let lights = grid_lights(1000);
+        let occluders = grid_occluders(200);
+
+        let start = Instant::now();
+        let mut single = vec![];
+        for _ in 0..ITERATIONS {
+            single = lights
+                .iter()
+                .map(|light| {
+                    (0..occluders.len())
+                        .filter(|&i| occluders[i].draws_shadow(light))
+                        .collect::<Vec<_>>()
+                })
+                .collect();
+        }
+        let single_threaded = start.elapsed() / ITERATIONS;
+
+        let start = Instant::now();
+        let mut multi = vec![];
+        for _ in 0..ITERATIONS {
+            multi = filter_light_occluders(&lights, &occluders);
+        }
+        let multi_threaded = start.elapsed() / ITERATIONS;
+
+        assert_eq!(single, multi);
+        println!(
+            "single threaded: {:?} per frame, multi threaded: {:?} per frame on {} threads",
+            single_threaded,
+            multi_threaded,
+            ComputeTaskPool::get().thread_num()
+        );
+    }
 }
@@ -9,6 +9,7 @@ use bevy::{
     math::FloatOrd,
     prelude::*,
     render::{
+        diagnostic::RecordDiagnostics,
         render_graph::{NodeRunError, RenderGraphContext, RenderLabel, ViewNode},
         render_phase::{
             CachedRenderPipelinePhaseItem, DrawFunctionId, DrawFunctions, PhaseItem,
@@ -29,13 +30,26 @@ use bevy::{
 
 use super::{
     ambient_light::{AmbientLight2dPipeline, SetAmbientLight2dBindGroup},
+    compute_shadows::{ComputeShadowBuffers, SetComputeShadowBindGroup},
+    diagnostics::LIGHT_PASS_SPAN,
+    light_buffer::{
+        composite_light_buffer, LightBufferBindGroup, LightBufferPhaseStart, LightBufferScale,
+        LightBufferTextures,
+    },
     line_light::{
         DrawLineLight2d, ExtractLineLight2d, LineLight2dBounds, LineLight2dPipeline,
         SetLineLight2dBindGroup,
     },
+    normal_map::NormalMap2dTexture,
     occluder::{
-        DrawOccluder2d, ExtractOccluder2d, Occluder2dBounds, Occluder2dGroups, Occluder2dPipeline,
-        OccluderCountTexture, SetOccluder2dBindGroup,
+        filter_light_occluders, DrawOccluder2d, DrawOccluder2dBatch, ExtractOccluder2d, LightDepth,
+        Occluder2dAlphaMask, Occluder2dBatch, Occluder2dBounds, Occluder2dGroups,
+        Occluder2dPipeline, OccluderCountTexture, SetOccluder2dAlphaMaskBindGroup,
+        SetOccluder2dBindGroup, ViewLight2d, ViewOccluder2d,
+    },
+    shadow_mask::{
+        render_shadow_mask_lights, SetShadowMaskBindGroup, ShadowMaskBindGroups, ShadowMaskLight,
+        ShadowMaskPhaseRanges, ShadowMaskTextures, SoftShadows,
     },
     AmbientLight2d, LineLight2d, Occluder2d,
 };
@@ -117,6 +131,8 @@ impl FromWorld for PostProcessRes {
                 (
                     texture_2d(TextureSampleType::Float { filterable: true }),
                     sampler(SamplerBindingType::NonFiltering),
+                    // normals drawn by the normal map node
+                    texture_2d(TextureSampleType::Float { filterable: true }),
                 ),
             ),
         );
@@ -144,12 +160,30 @@ pub fn extract_deferred_lighting_2d_camera_phases(
 
 #[allow(clippy::too_many_arguments)]
 pub fn queue_deferred_lighting(
+    mut commands: Commands,
+    soft_shadows: Res<SoftShadows>,
+    light_buffer_scale: Res<LightBufferScale>,
     deferred_lighting_draw_functions: Res<DrawFunctions<DeferredLighting2d>>,
     occluder_pipeline: Res<Occluder2dPipeline>,
+    occluder_batch: Res<Occluder2dBatch>,
+    compute_shadow_buffers: Res<ComputeShadowBuffers>,
     line_light_pipeline: Res<LineLight2dPipeline>,
     ambient_light_pipeline: Res<AmbientLight2dPipeline>,
-    q_line_lights: Query<(&LineLight2dBounds, Option<&Occluder2dGroups>), With<ExtractLineLight2d>>,
-    q_occluder: Query<(&Occluder2dBounds, Option<&Occluder2dGroups>), With<ExtractOccluder2d>>,
+    q_line_lights: Query<(
+        &ExtractLineLight2d,
+        &LineLight2dBounds,
+        Option<&Occluder2dGroups>,
+        Option<&LightDepth>,
+    )>,
+    q_occluder: Query<
+        (
+            &Occluder2dBounds,
+            Option<&Occluder2dGroups>,
+            Option<&LightDepth>,
+            Has<Occluder2dAlphaMask>,
+        ),
+        With<ExtractOccluder2d>,
+    >,
     mut deferred_lighting_phases: ResMut<ViewSortedRenderPhases<DeferredLighting2d>>,
     views: Query<(Entity, &MainEntity, &RenderVisibleEntities), With<AmbientLight2d>>,
 ) {
@@ -169,6 +203,12 @@ pub fn queue_deferred_lighting(
         let render_occluder = deferred_lighting_draw_functions
             .read()
             .id::<RenderOccluder>();
+        let render_alpha_mask_occluder = deferred_lighting_draw_functions
+            .read()
+            .id::<RenderAlphaMaskOccluder>();
+        let render_occluder_batch = deferred_lighting_draw_functions
+            .read()
+            .id::<RenderOccluder2dBatch>();
         let prepare_line_light = deferred_lighting_draw_functions
             .read()
             .id::<PrepareLineLight2d>();
@@ -178,9 +218,42 @@ pub fn queue_deferred_lighting(
         let reset_stencil_buffer = deferred_lighting_draw_functions
             .read()
             .id::<ResetOccluderStencil>();
+        let render_soft_shadow_line_light = deferred_lighting_draw_functions
+            .read()
+            .id::<RenderSoftShadowLineLight2d>();
+        let render_compute_shadow_line_light = deferred_lighting_draw_functions
+            .read()
+            .id::<RenderComputeShadowLineLight2d>();
+
+        // with soft shadows, each light's shadows are drawn into the shadow mask in their own pass
+        // instead of into the stencil buffer, see `render_shadow_mask_lights`
+        let occluder_pipelines = if soft_shadows.enabled {
+            occluder_pipeline.shadow_mask
+        } else {
+            occluder_pipeline.stencil
+        };
+        let mut shadow_mask_ranges = ShadowMaskPhaseRanges::default();
+        let light_buffer = light_buffer_scale.is_downsampled();
+        // with compute shadows, only alpha masked occluders are still drawn into the stencil
+        // buffer, the rest are checked by the light itself, see `ComputeShadowsNode`. Lights with
+        // more edges around them than the compute pass can list stay on the stencil path.
+        let compute_shadows = compute_shadow_buffers.active;
+        let line_light_pipeline_id = |compute_shadows: bool| {
+            line_light_pipeline.pipeline_id(soft_shadows.enabled, light_buffer, compute_shadows)
+        };
+        let shadow_tint_pipeline_id = line_light_pipeline.shadow_tint_pipeline_id(light_buffer);
+        let render_shadow_tint = render_line_light;
+        let render_line_light = |compute_shadows: bool| {
+            if compute_shadows {
+                render_compute_shadow_line_light
+            } else {
+                render_line_light
+            }
+        };
 
         let mut sort_key = 0.0;
 
+        // returns the index of the item in the sorted phase
         let mut add_phase_item = |pipeline: CachedRenderPipelineId,
                                   draw_function: DrawFunctionId,
                                   entity: (Entity, MainEntity)| {
@@ -193,6 +266,7 @@ pub fn queue_deferred_lighting(
                 extra_index: PhaseItemExtraIndex::NONE,
             });
             sort_key += 1.0;
+            sort_key as usize - 1
         };
 
         // Set bind group 0 - post process uniform
@@ -210,77 +284,179 @@ pub fn queue_deferred_lighting(
             (view_e, *view_me),
         );
 
+        // The light buffer is drawn in its own pass, which needs the view bind group set again
+        if light_buffer && !soft_shadows.enabled {
+            let lights_start = add_phase_item(
+                ambient_light_pipeline.pipeline_id,
+                prepare_deferred_lighting,
+                (view_e, *view_me),
+            );
+            commands
+                .entity(view_e)
+                .insert(LightBufferPhaseStart(lights_start));
+        }
+
+        // Gather the lights and occluders in view once, so the lights can be checked against the
+        // occluders in parallel. The phase items are still queued one light at a time below, in
+        // the order the lights are in view.
+        let view_occluders: Vec<ViewOccluder2d> = visible_entities
+            .iter::<With<Occluder2d>>()
+            .filter_map(|(ocl_e, ocl_me)| {
+                let (bounds, groups, depth, alpha_masked) = q_occluder.get(*ocl_e).ok()?;
+                Some(ViewOccluder2d {
+                    entity: (*ocl_e, *ocl_me),
+                    bounds: *bounds,
+                    groups: groups.copied().unwrap_or_default(),
+                    depth: depth.copied().unwrap_or_default(),
+                    alpha_masked,
+                })
+            })
+            .collect();
+        let lights: Vec<ViewLight2d> = visible_entities
+            .iter::<With<LineLight2d>>()
+            .filter_map(|(pl_e, pl_me)| {
+                let (_, bounds, groups, depth) = q_line_lights.get(*pl_e).ok()?;
+                Some(ViewLight2d {
+                    entity: (*pl_e, *pl_me),
+                    bounds: *bounds,
+                    groups: groups.copied().unwrap_or_default(),
+                    depth: depth.copied().unwrap_or_default(),
+                    compute_shadows: compute_shadows
+                        && !compute_shadow_buffers.stencil_fallback.contains(pl_e),
+                })
+            })
+            .collect();
+        let light_occluders = filter_light_occluders(&lights, &view_occluders);
+
         // Start rendering lights
-        for (pl_e, pl_me) in visible_entities.iter::<With<LineLight2d>>() {
-            let Ok((light_bounds, light_group)) = q_line_lights.get(*pl_e) else {
+        for (view_light, occluder_indices) in lights.iter().zip(light_occluders) {
+            let (pl_e, pl_me) = view_light.entity;
+            let Ok((light, ..)) = q_line_lights.get(pl_e) else {
                 continue;
             };
-            let light_group = light_group.copied().unwrap_or(Occluder2dGroups::default());
+
+            // Every shadow mask pass needs the view bind group set again
+            let shadows_start = if soft_shadows.enabled {
+                add_phase_item(
+                    ambient_light_pipeline.pipeline_id,
+                    prepare_deferred_lighting,
+                    (view_e, *view_me),
+                )
+            } else {
+                0
+            };
 
             // Set bind group 2 - line light uniform
             add_phase_item(
                 line_light_pipeline.pipeline_id,
                 prepare_line_light,
-                (*pl_e, *pl_me),
+                (pl_e, pl_me),
             );
 
             let mut is_occluded = false;
 
-            if light_group != Occluder2dGroups::NONE {
-                // filter occluders
-                let mut occluders: Vec<(Entity, MainEntity)> = vec![];
-                for (ocl_e, ocl_me) in visible_entities.iter::<With<Occluder2d>>() {
-                    let Ok((occluder_bounds, occluder_group)) = q_occluder.get(*ocl_e) else {
-                        continue;
-                    };
-                    let occluder_group = occluder_group
-                        .copied()
-                        .unwrap_or(Occluder2dGroups::default());
-                    if occluder_group.0 & light_group.0 == 0 {
-                        continue;
-                    }
-                    if !occluder_bounds.visible_from_line_light(light_bounds) {
-                        continue;
-                    }
-                    occluders.push((*ocl_e, *ocl_me));
+            if view_light.groups != Occluder2dGroups::NONE {
+                let occluders: Vec<(Entity, MainEntity, bool)> = occluder_indices
+                    .into_iter()
+                    .map(|index| {
+                        let occluder = &view_occluders[index];
+                        (occluder.entity.0, occluder.entity.1, occluder.alpha_masked)
+                    })
+                    .collect();
+                is_occluded = !occluders.is_empty();
+
+                // Batched occluders occlude every foreground light that isn't
+                // `Occluder2dGroups::NONE`
+                let draw_batch = !view_light.compute_shadows
+                    && !occluder_batch.is_empty()
+                    && view_light.depth == LightDepth::Foreground;
+                if draw_batch {
+                    add_phase_item(
+                        occluder_pipelines.batch_shadow,
+                        render_occluder_batch,
+                        (pl_e, pl_me),
+                    );
                     is_occluded = true;
                 }
 
                 // Render occluder shadows
-                for (ocl_e, ocl_me) in occluders.iter() {
-                    add_phase_item(
-                        occluder_pipeline.shadow_pipeline_id,
-                        render_occluder,
-                        (*ocl_e, *ocl_me),
-                    );
+                for (ocl_e, ocl_me, alpha_masked) in occluders.iter() {
+                    if *alpha_masked {
+                        add_phase_item(
+                            occluder_pipelines.alpha_mask_shadow,
+                            render_alpha_mask_occluder,
+                            (*ocl_e, *ocl_me),
+                        );
+                    } else {
+                        add_phase_item(
+                            occluder_pipelines.shadow,
+                            render_occluder,
+                            (*ocl_e, *ocl_me),
+                        );
+                    }
                 }
 
                 // Cutout occluder bodies
-                for (ocl_e, ocl_me) in occluders.iter() {
+                if draw_batch {
                     add_phase_item(
-                        occluder_pipeline.cutout_pipeline_id,
+                        occluder_pipelines.batch_cutout,
+                        render_occluder_batch,
+                        (pl_e, pl_me),
+                    );
+                }
+                for (ocl_e, ocl_me, _) in occluders.iter() {
+                    add_phase_item(
+                        occluder_pipelines.cutout,
                         render_occluder,
                         (*ocl_e, *ocl_me),
                     );
                 }
             }
 
+            if soft_shadows.enabled {
+                let light_start = add_phase_item(
+                    ambient_light_pipeline.pipeline_id,
+                    prepare_deferred_lighting,
+                    (view_e, *view_me),
+                );
+                let light_end = add_phase_item(
+                    line_light_pipeline_id(false),
+                    render_soft_shadow_line_light,
+                    (pl_e, pl_me),
+                ) + 1;
+                shadow_mask_ranges.lights.push(ShadowMaskLight {
+                    shadows: shadows_start..light_start,
+                    is_occluded,
+                    light: light_start..light_end,
+                });
+                continue;
+            }
+
             // Render the actual light now
             add_phase_item(
-                line_light_pipeline.pipeline_id,
-                render_line_light,
-                (*pl_e, *pl_me),
+                line_light_pipeline_id(view_light.compute_shadows),
+                render_line_light(view_light.compute_shadows),
+                (pl_e, pl_me),
             );
 
+            // Tint the parts the stencil culled
+            if is_occluded && light.has_shadow_tint() {
+                add_phase_item(shadow_tint_pipeline_id, render_shadow_tint, (pl_e, pl_me));
+            }
+
             if is_occluded {
                 // Reset the occluder
                 add_phase_item(
                     occluder_pipeline.reset_pipeline_id,
                     reset_stencil_buffer,
-                    (*pl_e, *pl_me),
+                    (pl_e, pl_me),
                 );
             }
         }
+
+        if soft_shadows.enabled {
+            commands.entity(view_e).insert(shadow_mask_ranges);
+        }
     }
 }
 
@@ -300,8 +476,31 @@ pub type RenderOccluder = (
     DrawOccluder2d,
 );
 
+pub type RenderAlphaMaskOccluder = (
+    SetItemPipeline,
+    SetOccluder2dBindGroup<3>,
+    SetOccluder2dAlphaMaskBindGroup<4>,
+    DrawOccluder2d,
+);
+
+pub type RenderOccluder2dBatch = (SetItemPipeline, DrawOccluder2dBatch);
+
 pub type RenderLineLight2d = (SetItemPipeline, SetLineLight2dBindGroup<2>, DrawLineLight2d);
 
+pub type RenderSoftShadowLineLight2d = (
+    SetItemPipeline,
+    SetLineLight2dBindGroup<2>,
+    SetShadowMaskBindGroup<3>,
+    DrawLineLight2d,
+);
+
+pub type RenderComputeShadowLineLight2d = (
+    SetItemPipeline,
+    SetLineLight2dBindGroup<2>,
+    SetComputeShadowBindGroup<3>,
+    DrawLineLight2d,
+);
+
 pub type ResetOccluderStencil = (SetItemPipeline, DrawTriangle);
 
 pub struct DrawTriangle;
@@ -333,14 +532,32 @@ impl ViewNode for DeferredLightingNode {
     type ViewQuery = (
         &'static ViewTarget,
         &'static OccluderCountTexture,
+        &'static NormalMap2dTexture,
         &'static AmbientLight2d,
+        Option<&'static ShadowMaskPhaseRanges>,
+        Option<&'static ShadowMaskTextures>,
+        Option<&'static ShadowMaskBindGroups>,
+        Option<&'static LightBufferPhaseStart>,
+        Option<&'static LightBufferTextures>,
+        Option<&'static LightBufferBindGroup>,
     );
 
     fn run<'w>(
         &self,
         graph: &mut RenderGraphContext,
         render_context: &mut RenderContext<'w>,
-        (view_target, occluder_count_texture, _ambient_lighting): QueryItem<'w, Self::ViewQuery>,
+        (
+            view_target,
+            occluder_count_texture,
+            normal_map_texture,
+            _ambient_lighting,
+            shadow_mask_ranges,
+            shadow_mask_textures,
+            shadow_mask_bind_groups,
+            light_buffer_phase_start,
+            light_buffer_textures,
+            light_buffer_bind_group,
+        ): QueryItem<'w, Self::ViewQuery>,
         world: &'w World,
     ) -> Result<(), NodeRunError> {
         let lighting_phases = world.resource::<ViewSortedRenderPhases<DeferredLighting2d>>();
@@ -354,9 +571,44 @@ impl ViewNode for DeferredLightingNode {
         let post_process_group = render_context.render_device().create_bind_group(
             "post_process_group",
             &post_process_res.layout,
-            &BindGroupEntries::sequential((post_process.source, &post_process_res.sampler)),
+            &BindGroupEntries::sequential((
+                post_process.source,
+                &post_process_res.sampler,
+                &normal_map_texture.0.default_view,
+            )),
         );
 
+        // the phase is only split into shadow mask passes while soft shadows are on
+        let shadow_mask = match (
+            world.resource::<SoftShadows>().enabled,
+            shadow_mask_ranges,
+            shadow_mask_textures,
+            shadow_mask_bind_groups,
+        ) {
+            (true, Some(ranges), Some(textures), Some(bind_groups)) => {
+                Some((ranges, textures, bind_groups))
+            }
+            _ => None,
+        };
+        // with a light buffer, only the ambient light is drawn at full resolution
+        let light_buffer = match (
+            world.resource::<LightBufferScale>().is_downsampled(),
+            light_buffer_textures,
+            light_buffer_bind_group,
+        ) {
+            (true, Some(textures), Some(bind_group)) => Some((textures, bind_group)),
+            _ => None,
+        };
+        let num_items = lighting_phase.items.len();
+        let items = match (shadow_mask, light_buffer, light_buffer_phase_start) {
+            (Some((ranges, ..)), ..) => ranges.prefix(num_items),
+            (None, Some(_), Some(start)) => 0..start.0.min(num_items),
+            _ => 0..num_items,
+        };
+
+        let diagnostics = render_context.diagnostic_recorder();
+        let time_span = diagnostics.time_span(render_context.command_encoder(), LIGHT_PASS_SPAN);
+
         let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
             label: Some("deferred_lighting_pass"),
             color_attachments: &[Some(RenderPassColorAttachment {
@@ -378,12 +630,78 @@ impl ViewNode for DeferredLightingNode {
 
         render_pass.set_bind_group(0, &post_process_group, &[]);
 
-        if !lighting_phase.items.is_empty() {
-            if let Err(err) = lighting_phase.render(&mut render_pass, world, view_entity) {
+        if !items.is_empty() {
+            if let Err(err) =
+                lighting_phase.render_range(&mut render_pass, world, view_entity, items.clone())
+            {
                 error!("Error encountered while rendering the 2d deferred lighting phase {err:?}")
             }
         }
+        drop(render_pass);
+
+        if let Some((textures, _)) = light_buffer {
+            let mut light_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+                label: Some("light_buffer_pass"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &textures.light.default_view,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Clear(LinearRgba::NONE.into()),
+                        store: StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                    view: &textures.stencil.default_view,
+                    depth_ops: None,
+                    stencil_ops: Some(Operations {
+                        load: LoadOp::Clear(0),
+                        store: StoreOp::Discard,
+                    }),
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            light_pass.set_bind_group(0, &post_process_group, &[]);
+            // soft shadowed lights are drawn into the cleared buffer by `render_shadow_mask_lights`
+            if shadow_mask.is_none() {
+                let lights = items.end..num_items;
+                if !lights.is_empty() {
+                    if let Err(err) =
+                        lighting_phase.render_range(&mut light_pass, world, view_entity, lights)
+                    {
+                        error!("Error encountered while rendering the light buffer {err:?}")
+                    }
+                }
+            }
+        }
+
+        if let Some((ranges, textures, bind_groups)) = shadow_mask {
+            render_shadow_mask_lights(
+                render_context,
+                world,
+                view_entity,
+                lighting_phase,
+                ranges,
+                textures,
+                bind_groups,
+                &post_process_group,
+                light_buffer.map_or(post_process.destination, |(textures, _)| {
+                    &textures.light.default_view
+                }),
+            );
+        }
+
+        if let Some((_, bind_group)) = light_buffer {
+            composite_light_buffer(
+                render_context,
+                world,
+                bind_group,
+                &post_process_group,
+                post_process.destination,
+            );
+        }
 
+        time_span.end(render_context.command_encoder());
         Ok(())
     }
 }
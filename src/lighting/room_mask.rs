@@ -0,0 +1,35 @@
+use bevy::prelude::*;
+
+/// The most rectangles a [`LightRoomMask`] can be made of, enough for a room made of a few parts
+/// with a few doorways into rooms of a few parts each. Rectangles past this are left out of the
+/// mask, so parts of rooms they cover stay dark. Must match `MAX_LIGHT_ROOM_RECTS` in
+/// `line_light.wgsl`.
+pub const MAX_LIGHT_ROOM_RECTS: usize = 16;
+
+/// [`Component`] for lights that only light the inside of a few rectangles in world space, like
+/// the room they are in and the rooms that room opens into, so light doesn't bleed through walls
+/// thinner than the light's shadows can be trusted with. Lights without a mask, or with an empty
+/// one, light everything in their range.
+#[derive(Component, Clone, Default, Debug, PartialEq)]
+pub struct LightRoomMask {
+    pub rects: Vec<Rect>,
+}
+
+impl LightRoomMask {
+    /// Whether the light reaches `point`. Mirrors `line_light.wgsl`.
+    pub fn contains(&self, point: Vec2) -> bool {
+        let rects = &self.rects[..self.rects.len().min(MAX_LIGHT_ROOM_RECTS)];
+        rects.is_empty() || rects.iter().any(|rect| rect.contains(point))
+    }
+
+    /// The rectangles of the mask as sent to the shader, as the min and max corner of each, and
+    /// how many of them are used.
+    pub fn shader_rects(&self) -> ([Vec4; MAX_LIGHT_ROOM_RECTS], u32) {
+        let mut rects = [Vec4::ZERO; MAX_LIGHT_ROOM_RECTS];
+        let count = self.rects.len().min(MAX_LIGHT_ROOM_RECTS);
+        for (shader_rect, rect) in rects.iter_mut().zip(&self.rects) {
+            *shader_rect = Vec4::new(rect.min.x, rect.min.y, rect.max.x, rect.max.y);
+        }
+        (rects, count as u32)
+    }
+}
@@ -0,0 +1,604 @@
+use std::ops::Range;
+
+use bevy::{
+    core_pipeline::fullscreen_vertex_shader::fullscreen_shader_vertex_state,
+    ecs::{
+        query::ROQueryItem,
+        system::{lifetimeless::Read, SystemParamItem},
+    },
+    prelude::*,
+    render::{
+        camera::ExtractedCamera,
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        render_phase::{
+            PhaseItem, RenderCommand, RenderCommandResult, SortedRenderPhase, TrackedRenderPass,
+        },
+        render_resource::{
+            binding_types::{sampler, texture_2d, uniform_buffer},
+            *,
+        },
+        renderer::{RenderContext, RenderDevice, RenderQueue},
+        texture::{CachedTexture, TextureCache},
+        Render, RenderApp, RenderSet,
+    },
+};
+use serde::Deserialize;
+
+use super::{render::DeferredLighting2d, AmbientLight2d};
+
+/// The format of the shadow mask, only the red channel is used.
+pub const SHADOW_MASK_FORMAT: TextureFormat = TextureFormat::R8Unorm;
+
+pub struct ShadowMaskPlugin;
+
+impl Plugin for ShadowMaskPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SoftShadows>()
+            .add_plugins(ExtractResourcePlugin::<SoftShadows>::default());
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .add_systems(
+                Render,
+                (prepare_shadow_mask_textures, prepare_shadow_blur_uniform)
+                    .in_set(RenderSet::PrepareResources),
+            )
+            .add_systems(
+                Render,
+                prepare_shadow_mask_bind_groups.in_set(RenderSet::PrepareBindGroups),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .init_resource::<ShadowMaskPipeline>()
+            .init_resource::<ShadowBlurUniformBuffer>();
+    }
+}
+
+/// [`Resource`] that switches lights from hard stencil shadows to soft shadows. With soft shadows,
+/// the shadows of each light are drawn into a low resolution shadow mask, which is blurred and then
+/// sampled when the light is drawn.
+///
+/// The stencil path draws every light in a single render pass. The mask path adds a mask pass,
+/// two blur passes and a light pass for every light, so with 50 lights it runs 200 small passes a
+/// frame instead of one, and the blur passes only cover `resolution_scale`² of the screen's
+/// pixels. See the `lighting_config.soft_shadows` section of `Lightborne.toml`.
+#[derive(Resource, ExtractResource, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct SoftShadows {
+    pub enabled: bool,
+    /// How far shadows are blurred, in texels of the shadow mask. Larger values make softer
+    /// shadows, and can be changed at any time.
+    pub blur_radius: f32,
+    /// The size of the shadow mask relative to the screen
+    pub resolution_scale: f32,
+}
+
+impl Default for SoftShadows {
+    fn default() -> Self {
+        SoftShadows {
+            enabled: false,
+            blur_radius: 2.0,
+            resolution_scale: 0.25,
+        }
+    }
+}
+
+/// The size of the shadow mask for a view of size `target_size`.
+pub fn shadow_mask_size(target_size: UVec2, resolution_scale: f32) -> UVec2 {
+    (target_size.as_vec2() * resolution_scale.clamp(0.0, 1.0))
+        .round()
+        .as_uvec2()
+        .max(UVec2::ONE)
+}
+
+/// Render world [`Component`] holding the two shadow mask textures of a view. Shadows are drawn
+/// into `mask`, blurred horizontally into `blurred`, and blurred vertically back into `mask`.
+#[derive(Component)]
+pub struct ShadowMaskTextures {
+    pub mask: CachedTexture,
+    pub blurred: CachedTexture,
+}
+
+pub fn prepare_shadow_mask_textures(
+    mut commands: Commands,
+    mut texture_cache: ResMut<TextureCache>,
+    render_device: Res<RenderDevice>,
+    soft_shadows: Res<SoftShadows>,
+    views: Query<(Entity, &ExtractedCamera), (With<Camera2d>, With<AmbientLight2d>)>,
+) {
+    if !soft_shadows.enabled {
+        return;
+    }
+    for (view, camera) in &views {
+        let Some(physical_target_size) = camera.physical_target_size else {
+            continue;
+        };
+        let size = shadow_mask_size(physical_target_size, soft_shadows.resolution_scale);
+
+        let mut descriptor = TextureDescriptor {
+            label: Some("shadow_mask_texture"),
+            size: Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: SHADOW_MASK_FORMAT,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        };
+        let mask = texture_cache.get(&render_device, descriptor.clone());
+        descriptor.label = Some("shadow_mask_blurred_texture");
+        let blurred = texture_cache.get(&render_device, descriptor);
+
+        commands
+            .entity(view)
+            .insert(ShadowMaskTextures { mask, blurred });
+    }
+}
+
+/// Uniform used by the shadow blur shader.
+#[derive(ShaderType, Clone, Copy, Debug, Default)]
+pub struct ShadowBlurUniform {
+    pub radius: f32,
+    _wasm_padding_a: f32,
+    _wasm_padding_b: Vec2,
+}
+
+#[derive(Resource, Default)]
+pub struct ShadowBlurUniformBuffer(pub UniformBuffer<ShadowBlurUniform>);
+
+pub fn prepare_shadow_blur_uniform(
+    mut buffer: ResMut<ShadowBlurUniformBuffer>,
+    soft_shadows: Res<SoftShadows>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    if !soft_shadows.enabled {
+        return;
+    }
+    buffer.0.set(ShadowBlurUniform {
+        radius: soft_shadows.blur_radius.max(0.0),
+        ..default()
+    });
+    buffer.0.write_buffer(&render_device, &render_queue);
+}
+
+/// Render world [`Component`] holding the bind groups used to blur a view's
+/// [`ShadowMaskTextures`] and to sample the result while drawing lights.
+#[derive(Component)]
+pub struct ShadowMaskBindGroups {
+    pub blur_horizontal: BindGroup,
+    pub blur_vertical: BindGroup,
+    pub light: BindGroup,
+}
+
+pub fn prepare_shadow_mask_bind_groups(
+    mut commands: Commands,
+    pipeline: Res<ShadowMaskPipeline>,
+    blur_uniform: Res<ShadowBlurUniformBuffer>,
+    render_device: Res<RenderDevice>,
+    views: Query<(Entity, &ShadowMaskTextures)>,
+) {
+    let Some(blur_binding) = blur_uniform.0.binding() else {
+        return;
+    };
+    for (view, textures) in &views {
+        commands.entity(view).insert(ShadowMaskBindGroups {
+            blur_horizontal: render_device.create_bind_group(
+                "shadow_blur_horizontal_bind_group",
+                &pipeline.blur_layout,
+                &BindGroupEntries::sequential((
+                    &textures.mask.default_view,
+                    &pipeline.sampler,
+                    blur_binding.clone(),
+                )),
+            ),
+            blur_vertical: render_device.create_bind_group(
+                "shadow_blur_vertical_bind_group",
+                &pipeline.blur_layout,
+                &BindGroupEntries::sequential((
+                    &textures.blurred.default_view,
+                    &pipeline.sampler,
+                    blur_binding.clone(),
+                )),
+            ),
+            light: render_device.create_bind_group(
+                "shadow_mask_bind_group",
+                &pipeline.mask_layout,
+                &BindGroupEntries::sequential((&textures.mask.default_view, &pipeline.sampler)),
+            ),
+        });
+    }
+}
+
+pub fn shadow_mask_bind_group_layout(render_device: &RenderDevice) -> BindGroupLayout {
+    render_device.create_bind_group_layout(
+        "shadow_mask_bind_group_layout",
+        &BindGroupLayoutEntries::sequential(
+            ShaderStages::FRAGMENT,
+            (
+                texture_2d(TextureSampleType::Float { filterable: true }),
+                sampler(SamplerBindingType::Filtering),
+            ),
+        ),
+    )
+}
+
+#[derive(Resource)]
+pub struct ShadowMaskPipeline {
+    pub blur_layout: BindGroupLayout,
+    pub mask_layout: BindGroupLayout,
+    pub sampler: Sampler,
+    pub blur_horizontal_pipeline_id: CachedRenderPipelineId,
+    pub blur_vertical_pipeline_id: CachedRenderPipelineId,
+}
+
+impl FromWorld for ShadowMaskPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+
+        let blur_layout = render_device.create_bind_group_layout(
+            "shadow_blur_bind_group_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::FRAGMENT,
+                (
+                    texture_2d(TextureSampleType::Float { filterable: true }),
+                    sampler(SamplerBindingType::Filtering),
+                    uniform_buffer::<ShadowBlurUniform>(false),
+                ),
+            ),
+        );
+        let mask_layout = shadow_mask_bind_group_layout(render_device);
+        // linear filtering smooths out the mask when it is stretched over the screen
+        let sampler = render_device.create_sampler(&SamplerDescriptor {
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..default()
+        });
+
+        let shader = world.load_asset("shaders/lighting/shadow_blur.wgsl");
+        let blur_pipeline_descriptor =
+            |label: &'static str, shader_defs: Vec<ShaderDefVal>| RenderPipelineDescriptor {
+                label: Some(label.into()),
+                layout: vec![blur_layout.clone()],
+                vertex: fullscreen_shader_vertex_state(),
+                fragment: Some(FragmentState {
+                    shader: shader.clone(),
+                    shader_defs,
+                    entry_point: "fragment".into(),
+                    targets: vec![Some(ColorTargetState {
+                        format: SHADOW_MASK_FORMAT,
+                        blend: None,
+                        write_mask: ColorWrites::ALL,
+                    })],
+                }),
+                primitive: PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: MultisampleState::default(),
+                push_constant_ranges: vec![],
+                zero_initialize_workgroup_memory: false,
+            };
+        let blur_horizontal_descriptor =
+            blur_pipeline_descriptor("shadow_blur_horizontal_pipeline", vec![]);
+        let blur_vertical_descriptor = blur_pipeline_descriptor(
+            "shadow_blur_vertical_pipeline",
+            vec!["BLUR_VERTICAL".into()],
+        );
+
+        let pipeline_cache = world.resource_mut::<PipelineCache>();
+        let blur_horizontal_pipeline_id =
+            pipeline_cache.queue_render_pipeline(blur_horizontal_descriptor);
+        let blur_vertical_pipeline_id =
+            pipeline_cache.queue_render_pipeline(blur_vertical_descriptor);
+
+        ShadowMaskPipeline {
+            blur_layout,
+            mask_layout,
+            sampler,
+            blur_horizontal_pipeline_id,
+            blur_vertical_pipeline_id,
+        }
+    }
+}
+
+pub struct SetShadowMaskBindGroup<const I: usize>;
+impl<P: PhaseItem, const I: usize> RenderCommand<P> for SetShadowMaskBindGroup<I> {
+    type Param = ();
+    type ViewQuery = Read<ShadowMaskBindGroups>;
+    type ItemQuery = ();
+
+    fn render<'w>(
+        _item: &P,
+        bind_groups: ROQueryItem<'w, Self::ViewQuery>,
+        _entity: Option<ROQueryItem<'w, Self::ItemQuery>>,
+        _param: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        pass.set_bind_group(I, &bind_groups.light, &[]);
+        RenderCommandResult::Success
+    }
+}
+
+/// The [`DeferredLighting2d`] phase items of a single light when drawing soft shadows.
+#[derive(Clone, Debug)]
+pub struct ShadowMaskLight {
+    /// Items drawn into the shadow mask
+    pub shadows: Range<usize>,
+    /// Whether anything is drawn into the mask, if not the blur is skipped
+    pub is_occluded: bool,
+    /// Items that draw the light itself
+    pub light: Range<usize>,
+}
+
+impl ShadowMaskLight {
+    /// The passes drawing the light, in order. Lights that aren't occluded, or drawn while the
+    /// blur pipelines are still compiling, skip the blur.
+    pub fn passes(&self, blur: bool) -> Vec<ShadowMaskPass> {
+        let mut passes = vec![ShadowMaskPass::Mask];
+        if self.is_occluded && blur {
+            passes.extend([ShadowMaskPass::BlurHorizontal, ShadowMaskPass::BlurVertical]);
+        }
+        passes.push(ShadowMaskPass::Light);
+        passes
+    }
+}
+
+/// A render pass [`render_shadow_mask_lights`] runs for a [`ShadowMaskLight`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShadowMaskPass {
+    /// Draws the light's shadows into the shadow mask
+    Mask,
+    /// Blurs the shadow mask horizontally into the blurred texture
+    BlurHorizontal,
+    /// Blurs the blurred texture vertically back into the shadow mask
+    BlurVertical,
+    /// Draws the light into the destination while sampling the shadow mask
+    Light,
+}
+
+impl ShadowMaskPass {
+    /// Whether the pass clears its target before drawing. The light pass adds the light on top of
+    /// the scene and the lights drawn before it, so overlapping lights add up instead of each
+    /// light replacing everything drawn before it.
+    pub fn clears_target(&self) -> bool {
+        *self != ShadowMaskPass::Light
+    }
+}
+
+/// Render world [`Component`] splitting a view's [`DeferredLighting2d`] phase into the passes
+/// used to draw soft shadows. Items before the first light are drawn like they are with stencil
+/// shadows.
+#[derive(Component, Clone, Debug, Default)]
+pub struct ShadowMaskPhaseRanges {
+    pub lights: Vec<ShadowMaskLight>,
+}
+
+impl ShadowMaskPhaseRanges {
+    /// The items drawn before any light, like the ambient light.
+    pub fn prefix(&self, num_items: usize) -> Range<usize> {
+        0..self
+            .lights
+            .first()
+            .map_or(num_items, |light| light.shadows.start)
+    }
+}
+
+/// Draws the lights of a view with soft shadows. For every light, its shadows are drawn into the
+/// shadow mask, the mask is blurred, and then the light is drawn into `destination` while
+/// sampling the mask. `post_process_group` is bound at group 0 of every pass, like in the stencil
+/// path.
+#[allow(clippy::too_many_arguments)]
+pub fn render_shadow_mask_lights(
+    render_context: &mut RenderContext,
+    world: &World,
+    view_entity: Entity,
+    lighting_phase: &SortedRenderPhase<DeferredLighting2d>,
+    ranges: &ShadowMaskPhaseRanges,
+    textures: &ShadowMaskTextures,
+    bind_groups: &ShadowMaskBindGroups,
+    post_process_group: &BindGroup,
+    destination: &TextureView,
+) {
+    let pipeline_cache = world.resource::<PipelineCache>();
+    let shadow_mask_pipeline = world.resource::<ShadowMaskPipeline>();
+    let blur_pipelines = pipeline_cache
+        .get_render_pipeline(shadow_mask_pipeline.blur_horizontal_pipeline_id)
+        .zip(pipeline_cache.get_render_pipeline(shadow_mask_pipeline.blur_vertical_pipeline_id));
+
+    for light in ranges.lights.iter() {
+        for pass in light.passes(blur_pipelines.is_some()) {
+            let (label, target) = match pass {
+                ShadowMaskPass::Mask => ("shadow_mask_pass", &textures.mask.default_view),
+                ShadowMaskPass::BlurHorizontal => {
+                    ("shadow_blur_pass", &textures.blurred.default_view)
+                }
+                ShadowMaskPass::BlurVertical => ("shadow_blur_pass", &textures.mask.default_view),
+                ShadowMaskPass::Light => ("shadow_mask_light_pass", destination),
+            };
+            let load = if pass.clears_target() {
+                LoadOp::Clear(LinearRgba::BLACK.into())
+            } else {
+                LoadOp::Load
+            };
+            let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+                label: Some(label),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: target,
+                    resolve_target: None,
+                    ops: Operations {
+                        load,
+                        store: StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            match (pass, blur_pipelines) {
+                (ShadowMaskPass::Mask, _) => {
+                    render_pass.set_bind_group(0, post_process_group, &[]);
+                    if light.is_occluded {
+                        if let Err(err) = lighting_phase.render_range(
+                            &mut render_pass,
+                            world,
+                            view_entity,
+                            light.shadows.clone(),
+                        ) {
+                            error!("Error encountered while rendering a shadow mask {err:?}");
+                        }
+                    }
+                }
+                (ShadowMaskPass::BlurHorizontal, Some((horizontal, _))) => {
+                    render_pass.set_render_pipeline(horizontal);
+                    render_pass.set_bind_group(0, &bind_groups.blur_horizontal, &[]);
+                    render_pass.draw(0..3, 0..1);
+                }
+                (ShadowMaskPass::BlurVertical, Some((_, vertical))) => {
+                    render_pass.set_render_pipeline(vertical);
+                    render_pass.set_bind_group(0, &bind_groups.blur_vertical, &[]);
+                    render_pass.draw(0..3, 0..1);
+                }
+                (ShadowMaskPass::Light, _) => {
+                    render_pass.set_bind_group(0, post_process_group, &[]);
+                    if let Err(err) = lighting_phase.render_range(
+                        &mut render_pass,
+                        world,
+                        view_entity,
+                        light.light.clone(),
+                    ) {
+                        error!("Error encountered while rendering a soft shadowed light {err:?}");
+                    }
+                }
+                (ShadowMaskPass::BlurHorizontal | ShadowMaskPass::BlurVertical, None) => {}
+            }
+        }
+    }
+}
+
+// WebGL2 requires thes structs be 16-byte aligned
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem;
+
+    #[test]
+    fn shadow_blur_uniform_alignment() {
+        assert_eq!(mem::size_of::<ShadowBlurUniform>() % 16, 0);
+    }
+
+    #[test]
+    fn shadow_mask_is_scaled_down_and_never_empty() {
+        assert_eq!(
+            shadow_mask_size(UVec2::new(1280, 720), 0.25),
+            UVec2::new(320, 180)
+        );
+        assert_eq!(shadow_mask_size(UVec2::new(1280, 720), 0.0), UVec2::ONE);
+        assert_eq!(
+            shadow_mask_size(UVec2::new(1280, 720), 2.0),
+            UVec2::new(1280, 720)
+        );
+    }
+
+    #[test]
+    fn prefix_ends_at_first_light() {
+        let ranges = ShadowMaskPhaseRanges {
+            lights: vec![ShadowMaskLight {
+                shadows: 2..5,
+                is_occluded: true,
+                light: 5..7,
+            }],
+        };
+        assert_eq!(ranges.prefix(7), 0..2);
+        assert_eq!(ShadowMaskPhaseRanges::default().prefix(2), 0..2);
+    }
+
+    /// Draws the passes of `lights` the way the GPU would, with each light adding its brightness
+    /// to a single pixel of the destination where they all overlap.
+    fn draw_overlapping(lights: &[(ShadowMaskLight, f32)], blur: bool) -> f32 {
+        let (mut mask, mut destination) = (0.0, 0.25);
+        for (light, brightness) in lights {
+            for pass in light.passes(blur) {
+                match pass {
+                    ShadowMaskPass::Mask => mask = if light.is_occluded { 0.5 } else { 0.0 },
+                    // blurring a uniformly shadowed pixel leaves it as is
+                    ShadowMaskPass::BlurHorizontal | ShadowMaskPass::BlurVertical => {}
+                    ShadowMaskPass::Light => {
+                        if pass.clears_target() {
+                            destination = 0.0;
+                        }
+                        destination += brightness * (1.0 - mask);
+                    }
+                }
+            }
+        }
+        destination
+    }
+
+    #[test]
+    fn overlapping_soft_shadowed_lights_add_up() {
+        let light = |start: usize, is_occluded: bool| ShadowMaskLight {
+            shadows: start..start + 2,
+            is_occluded,
+            light: start + 2..start + 3,
+        };
+        // the ambient light, a half shadowed light, and an unshadowed one on top of each other
+        let lights = [(light(1, true), 1.0), (light(4, false), 0.5)];
+        for blur in [true, false] {
+            assert_eq!(draw_overlapping(&lights, blur), 0.25 + 0.5 + 0.5);
+        }
+        assert_eq!(
+            lights[0].0.passes(true),
+            vec![
+                ShadowMaskPass::Mask,
+                ShadowMaskPass::BlurHorizontal,
+                ShadowMaskPass::BlurVertical,
+                ShadowMaskPass::Light
+            ]
+        );
+        assert_eq!(
+            lights[1].0.passes(true),
+            vec![ShadowMaskPass::Mask, ShadowMaskPass::Light]
+        );
+    }
+
+    #[test]
+    fn soft_shadows_passes_at_50_lights() {
+        let lights: Vec<ShadowMaskLight> = (0..50)
+            .map(|i| ShadowMaskLight {
+                shadows: i * 4 + 1..i * 4 + 3,
+                is_occluded: true,
+                light: i * 4 + 3..i * 4 + 5,
+            })
+            .collect();
+        let passes: Vec<ShadowMaskPass> =
+            lights.iter().flat_map(|light| light.passes(true)).collect();
+        // the stencil path draws all 50 lights in one pass
+        assert_eq!(passes.len(), 200);
+
+        // but each blur pass only covers the shadow mask, a sixteenth of the screen
+        let screen = UVec2::new(1280, 720);
+        let mask = shadow_mask_size(screen, SoftShadows::default().resolution_scale);
+        let blurs = passes
+            .iter()
+            .filter(|pass| {
+                matches!(
+                    pass,
+                    ShadowMaskPass::BlurHorizontal | ShadowMaskPass::BlurVertical
+                )
+            })
+            .count() as u32;
+        let blurred_screens = (blurs * mask.x * mask.y) as f32 / (screen.x * screen.y) as f32;
+        assert_eq!(blurred_screens, 6.25);
+    }
+}
@@ -0,0 +1,213 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use super::{
+    light_toggle::update_light_schedules, line_light::calculate_line_light_2d_bounds,
+    AmbientLight2d, LightToggle,
+};
+
+/// How far below and above the horizon the sun fades between night and day, as the sine of its
+/// angle.
+const TWILIGHT: f32 = 0.2;
+
+/// [`Plugin`] for the [`TimeOfDay`].
+pub struct TimeOfDayPlugin;
+
+impl Plugin for TimeOfDayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TimeOfDay>().add_systems(
+            PostUpdate,
+            (
+                advance_time_of_day,
+                (update_daylight, update_time_of_day_windows),
+            )
+                .chain()
+                .before(update_light_schedules)
+                .before(calculate_line_light_2d_bounds),
+        );
+    }
+}
+
+/// [`Resource`] for the time of day, from 0 to 1, where 0 is midnight and 0.5 is noon. The sun
+/// rises at 0.25 and sets at 0.75, and the [`AmbientLight2d`] of lit cameras fades between
+/// `night_ambient` and `day_ambient` with its height. Time passes with the virtual clock, so it
+/// stands still while the game is paused, and a `day_secs` of 0 stops it altogether. Lights with
+/// a [`TimeOfDayWindow`] are only on during part of the day. See the
+/// `lighting_config.time_of_day` section of `Lightborne.toml`.
+#[derive(Resource, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct TimeOfDay {
+    pub time: f32,
+    /// How long a whole day takes, in seconds
+    pub day_secs: f32,
+    /// The color of the ambient light at noon, with its intensity in the alpha
+    pub day_ambient: [f32; 4],
+    /// The color of the ambient light at midnight, with its intensity in the alpha
+    pub night_ambient: [f32; 4],
+}
+
+impl Default for TimeOfDay {
+    fn default() -> Self {
+        TimeOfDay {
+            time: 0.5,
+            day_secs: 0.0,
+            day_ambient: [1.0, 1.0, 1.0, 0.4],
+            night_ambient: [0.3, 0.35, 0.6, 0.15],
+        }
+    }
+}
+
+impl TimeOfDay {
+    pub fn advance(&mut self, delta_secs: f32) {
+        if self.day_secs > 0.0 {
+            self.time = (self.time + delta_secs / self.day_secs).rem_euclid(1.0);
+        }
+    }
+
+    /// How bright the day is, from 0 at night to 1 once the sun is up.
+    pub fn daylight(&self) -> f32 {
+        // the sine of the sun's angle above the horizon, which it crosses at 0.25 and 0.75
+        let height = ((self.time - 0.25) * TAU).sin();
+        ((height + TWILIGHT) / (2.0 * TWILIGHT)).clamp(0.0, 1.0)
+    }
+
+    pub fn ambient(&self) -> Vec4 {
+        Vec4::from(self.night_ambient).lerp(Vec4::from(self.day_ambient), self.daylight())
+    }
+}
+
+/// [`Component`] for lights that are only on between `from` and `until` in the [`TimeOfDay`],
+/// like a beam that only shines at night. A window with `from` after `until` wraps around
+/// midnight.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+#[require(LightToggle)]
+pub struct TimeOfDayWindow {
+    pub from: f32,
+    pub until: f32,
+}
+
+impl TimeOfDayWindow {
+    pub fn contains(&self, time: f32) -> bool {
+        if self.from <= self.until {
+            self.from <= time && time < self.until
+        } else {
+            time >= self.from || time < self.until
+        }
+    }
+}
+
+/// [`System`] that advances the [`TimeOfDay`].
+pub fn advance_time_of_day(mut time_of_day: ResMut<TimeOfDay>, time: Res<Time>) {
+    if time.delta_secs() > 0.0 && time_of_day.day_secs > 0.0 {
+        time_of_day.advance(time.delta_secs());
+    }
+}
+
+/// [`System`] that sets the [`AmbientLight2d`] of every lit camera from the [`TimeOfDay`].
+pub fn update_daylight(mut q_cameras: Query<&mut AmbientLight2d>, time_of_day: Res<TimeOfDay>) {
+    if !time_of_day.is_changed() {
+        return;
+    }
+    let color = time_of_day.ambient();
+    for mut ambient in q_cameras.iter_mut() {
+        ambient.color = color;
+    }
+}
+
+/// [`System`] that turns lights with a [`TimeOfDayWindow`] on during their window and off
+/// outside of it.
+pub fn update_time_of_day_windows(
+    mut q_lights: Query<(&TimeOfDayWindow, &mut LightToggle)>,
+    time_of_day: Res<TimeOfDay>,
+) {
+    for (window, mut toggle) in q_lights.iter_mut() {
+        let on = window.contains(time_of_day.time);
+        if toggle.is_on() != on {
+            toggle.set_on(on);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn daylight_follows_the_sun() {
+        let mut time_of_day = TimeOfDay {
+            time: 0.25,
+            day_secs: 100.0,
+            ..default()
+        };
+        // halfway through twilight at sunrise
+        assert!((time_of_day.daylight() - 0.5).abs() < 1e-5);
+        time_of_day.advance(25.0);
+        assert_eq!(time_of_day.daylight(), 1.0);
+        assert!(
+            time_of_day
+                .ambient()
+                .distance(Vec4::from(time_of_day.day_ambient))
+                < 1e-5
+        );
+
+        time_of_day.advance(50.0);
+        assert!(time_of_day.time.abs() < 1e-5);
+        assert_eq!(time_of_day.daylight(), 0.0);
+
+        // twilight is between day and night
+        time_of_day.time = 0.75;
+        assert!((time_of_day.daylight() - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn night_beam_shines_within_its_window() {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .insert_resource(TimeOfDay {
+                time: 0.5,
+                day_secs: 10.0,
+                ..default()
+            })
+            .add_systems(
+                Update,
+                (advance_time_of_day, update_time_of_day_windows).chain(),
+            );
+        let night_light = app
+            .world_mut()
+            .spawn(TimeOfDayWindow {
+                from: 0.8,
+                until: 0.2,
+            })
+            .id();
+        let is_on = |app: &App| app.world().get::<LightToggle>(night_light).unwrap().is_on();
+        let advance = |app: &mut App, secs: f32| {
+            app.world_mut()
+                .resource_mut::<Time>()
+                .advance_by(Duration::from_secs_f32(secs));
+            app.update();
+        };
+
+        advance(&mut app, 0.0);
+        assert!(!is_on(&app));
+        // dusk
+        advance(&mut app, 3.5);
+        assert!(is_on(&app));
+        // past midnight
+        advance(&mut app, 3.0);
+        assert!(is_on(&app));
+        // dawn
+        advance(&mut app, 1.0);
+        assert!(!is_on(&app));
+
+        // no time passes while paused
+        app.world_mut().resource_mut::<TimeOfDay>().time = 0.75;
+        advance(&mut app, 0.0);
+        advance(&mut app, 0.0);
+        assert!(!is_on(&app));
+        assert_eq!(app.world().resource::<TimeOfDay>().time, 0.75);
+    }
+}
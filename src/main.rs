@@ -1,13 +1,14 @@
 use animation::SpriteAnimationPlugin;
 use bevy::prelude::*;
-use bevy::window::PresentMode;
 use bevy::{asset::AssetMetaCheck, diagnostic::LogDiagnosticsPlugin};
 use bevy_rapier2d::prelude::*;
 
 use camera::CameraPlugin;
-use config::ConfigPlugin;
+use config::{Config, ConfigPlugin};
 use debug::DebugPlugin;
-use input::{init_cursor_world_coords, update_cursor_world_coords};
+use demo::DemoPlugin;
+use hud::HudPlugin;
+use input::{init_cursor_world_coords, menu::MenuInputPlugin, update_cursor_world_coords};
 use level::LevelManagementPlugin;
 use level_select::LevelSelectPlugin;
 use light::LightManagementPlugin;
@@ -15,13 +16,17 @@ use lighting::DeferredLightingPlugin;
 use particle::ParticlePlugin;
 use pause::PausePlugin;
 use player::PlayerManagementPlugin;
+use save::SaveSlotPlugin;
 use shared::{AnimationState, GameState, ResetLevel, UiState};
 use sound::SoundPlugin;
+use window::{primary_window, WindowSettingsPlugin};
 
 mod animation;
 mod camera;
 mod config;
 mod debug;
+mod demo;
+mod hud;
 mod input;
 mod level;
 mod level_select;
@@ -30,24 +35,21 @@ mod lighting;
 mod particle;
 mod pause;
 mod player;
+mod save;
 mod shared;
 mod sound;
+mod window;
 
 fn main() {
+    // the window is created by the WindowPlugin, before the ConfigPlugin inserts the config
+    let window_config = Config::load().window_config;
+
     App::new()
         .add_plugins(
             DefaultPlugins
                 .set(ImagePlugin::default_nearest())
                 .set(WindowPlugin {
-                    primary_window: Some(Window {
-                        title: "Lightborne".into(),
-                        name: Some("lightborne".into()),
-                        present_mode: PresentMode::AutoNoVsync,
-                        canvas: Some("#bevy-container".into()),
-                        fit_canvas_to_parent: true,
-                        prevent_default_event_handling: false,
-                        ..default()
-                    }),
+                    primary_window: Some(primary_window(&window_config)),
                     ..default()
                 })
                 .set(AssetPlugin {
@@ -58,6 +60,8 @@ fn main() {
         )
         .add_plugins(bevy_mod_debugdump::CommandLineArgs)
         .add_plugins(ConfigPlugin)
+        .add_plugins(SaveSlotPlugin)
+        .add_plugins(DemoPlugin)
         .add_plugins(LogDiagnosticsPlugin::default())
         .add_plugins(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(8.0).in_fixed_schedule())
         .add_plugins(SpriteAnimationPlugin)
@@ -67,8 +71,11 @@ fn main() {
         .add_plugins(SoundPlugin)
         .add_plugins(ParticlePlugin)
         .add_plugins(PausePlugin)
+        .add_plugins(HudPlugin)
+        .add_plugins(MenuInputPlugin)
         .add_plugins(LevelSelectPlugin)
         .add_plugins(CameraPlugin)
+        .add_plugins(WindowSettingsPlugin)
         .insert_state(GameState::Ui)
         .add_sub_state::<UiState>()
         .add_sub_state::<AnimationState>()
@@ -12,7 +12,7 @@ use crate::{
 
 use super::{ParticleBundle, ParticleOptions, ParticlePhysicsOptions};
 
-#[derive(Component, Debug)]
+#[derive(Component, Clone, Copy, PartialEq, Debug)]
 pub enum DustSurface {
     Wall,
     Wood,
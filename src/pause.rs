@@ -2,13 +2,18 @@ use bevy::{input::common_conditions::input_just_pressed, prelude::*, ui::widget:
 
 use crate::shared::GameState;
 
+/// [`Plugin`] for pausing the game with Escape. Pausing also pauses [`Time<Virtual>`], which stops
+/// [`FixedUpdate`] from running and freezes every [`Timer`] ticked in [`Update`], so that
+/// animations like the death fade pick up where they left off when the game is unpaused.
 pub struct PausePlugin;
 
 impl Plugin for PausePlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Startup, spawn_pause)
-            .add_systems(OnEnter(GameState::Paused), show_pause::<true>)
-            .add_systems(OnExit(GameState::Paused), show_pause::<false>)
+            .add_systems(OnEnter(GameState::Paused), set_paused::<true>)
+            .add_systems(OnExit(GameState::Paused), set_paused::<false>)
+            // leaving `Animating` (e.g. to open the level select) also ends a pause during it
+            .add_systems(OnExit(GameState::Animating), set_paused::<false>)
             .add_systems(
                 Update,
                 toggle_pause.run_if(input_just_pressed(KeyCode::Escape)),
@@ -40,21 +45,50 @@ fn spawn_pause(mut commands: Commands, asset_server: Res<AssetServer>) {
         ));
 }
 
-fn show_pause<const SHOW: bool>(mut query: Query<&mut Visibility, With<PauseMarker>>) {
+/// Run condition that is false while the game is paused, including pauses during animations
+/// where the [`GameState`] is left unchanged.
+pub fn not_paused(time: Res<Time<Virtual>>) -> bool {
+    !time.is_paused()
+}
+
+fn set_paused<const PAUSED: bool>(
+    mut query: Query<&mut Visibility, With<PauseMarker>>,
+    mut time: ResMut<Time<Virtual>>,
+) {
+    if PAUSED {
+        time.pause();
+    } else {
+        time.unpause();
+    }
+
     let Ok(mut pause_visibility) = query.get_single_mut() else {
         return;
     };
-    *pause_visibility = if SHOW {
+    *pause_visibility = if PAUSED {
         Visibility::Visible
     } else {
         Visibility::Hidden
     };
 }
 
-fn toggle_pause(state: Res<State<GameState>>, mut next_state: ResMut<NextState<GameState>>) {
-    next_state.set(match state.get() {
-        GameState::Paused => GameState::Playing,
-        GameState::Playing => GameState::Paused,
-        _ => state.get().clone(),
-    })
+fn toggle_pause(
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    query: Query<&mut Visibility, With<PauseMarker>>,
+    time: ResMut<Time<Virtual>>,
+) {
+    match state.get() {
+        GameState::Paused => next_state.set(GameState::Playing),
+        GameState::Playing => next_state.set(GameState::Paused),
+        // Switching to `Paused` would throw away the `AnimationState`, so animations are paused
+        // by freezing time instead
+        GameState::Animating => {
+            if time.is_paused() {
+                set_paused::<false>(query, time);
+            } else {
+                set_paused::<true>(query, time);
+            }
+        }
+        GameState::Ui => {}
+    }
 }
@@ -16,6 +16,8 @@ pub enum PlayerAnimationType {
     Jump,
     Fall,
     Land,
+    /// Held after completing a level, see [`WinPose`](super::win::WinPose)
+    Win,
 }
 
 // HAIR, LEFT, RIGHT
@@ -77,6 +79,8 @@ impl From<PlayerAnimationType> for AnimationConfig {
             PlayerAnimationType::Jump => AnimationConfig::new(15, 20, 24, false),
             PlayerAnimationType::Fall => AnimationConfig::new(21, 24, 24, false),
             PlayerAnimationType::Land => AnimationConfig::new(25, 30, 24, false),
+            // the start of the jump, arms up
+            PlayerAnimationType::Win => AnimationConfig::new(15, 16, 6, false),
         }
     }
 }
@@ -0,0 +1,160 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{level::LevelSystems, particle::dust::DustSurface};
+
+use super::{
+    movement::{Gravity, PlayerMovement},
+    PlayerMarker,
+};
+
+/// How far the player moves between two footsteps. Footsteps are spaced by distance instead of
+/// time, so running faster makes them come faster.
+const FOOTSTEP_STRIDE: f32 = 14.0;
+
+/// The slowest the player can move, in units per [`FixedUpdate`], while still taking footsteps.
+/// The player slides to a stop for a few steps after letting go of A/D, which shouldn't count.
+const FOOTSTEP_MIN_SPEED: f32 = 0.5;
+
+/// How far below the center of the player their feet are.
+const PLAYER_FOOT_OFFSET: f32 = 9.0;
+
+/// [`Plugin`] that sends [`FootstepEvent`]s while the player runs.
+pub struct PlayerFootstepPlugin;
+
+impl Plugin for PlayerFootstepPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<FootstepEvent>().add_systems(
+            FixedUpdate,
+            send_footstep_events
+                .after(PhysicsSet::Writeback)
+                .in_set(LevelSystems::Simulation),
+        );
+    }
+}
+
+/// [`Event`] sent every time the player's foot hits the ground while running, for sounds and
+/// particles that depend on what the player is running on.
+#[derive(Event, Debug)]
+pub struct FootstepEvent {
+    pub surface: DustSurface,
+    /// Where the player's feet are
+    pub position: Vec2,
+}
+
+/// [`Component`] that counts the distance the player has run since their last footstep.
+#[derive(Component, Default, Debug)]
+pub struct Footsteps {
+    distance: f32,
+    was_grounded: bool,
+}
+
+impl Footsteps {
+    /// Advances the footsteps by one [`FixedUpdate`] at `speed`, returning whether a foot hits the
+    /// ground. Standing still, being in the air and landing all start the next stride from the
+    /// beginning, so the first footstep after landing comes one full stride later.
+    pub fn step(&mut self, grounded: bool, speed: f32) -> bool {
+        let landed = grounded && !self.was_grounded;
+        self.was_grounded = grounded;
+        if !grounded || landed || speed < FOOTSTEP_MIN_SPEED {
+            self.distance = 0.0;
+            return false;
+        }
+
+        self.distance += speed;
+        if self.distance < FOOTSTEP_STRIDE {
+            return false;
+        }
+        self.distance -= FOOTSTEP_STRIDE;
+        true
+    }
+}
+
+/// [`System`] that sends a [`FootstepEvent`] whenever the player's [`Footsteps`] take a step on
+/// something with a [`DustSurface`].
+pub fn send_footstep_events(
+    mut q_player: Query<
+        (
+            &GlobalTransform,
+            &KinematicCharacterControllerOutput,
+            &PlayerMovement,
+            &mut Footsteps,
+        ),
+        With<PlayerMarker>,
+    >,
+    q_surfaces: Query<&DustSurface>,
+    gravity: Res<Gravity>,
+    mut ev_footstep: EventWriter<FootstepEvent>,
+) {
+    let Ok((transform, output, movement, mut footsteps)) = q_player.get_single_mut() else {
+        return;
+    };
+    if !footsteps.step(output.grounded, movement.velocity.x.abs()) {
+        return;
+    }
+
+    // the ground is whatever the player collided with that pushes them up, relative to gravity
+    let up = gravity.up();
+    let Some(surface) = output.collisions.iter().find_map(|collision| {
+        let is_below = collision
+            .hit
+            .details
+            .is_some_and(|detail| detail.normal2.dot(up) < 0.0);
+        if !is_below {
+            return None;
+        }
+        q_surfaces.get(collision.entity).ok()
+    }) else {
+        return;
+    };
+
+    ev_footstep.send(FootstepEvent {
+        surface: *surface,
+        position: transform.translation().xy() - up * PLAYER_FOOT_OFFSET,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs for a second of [`FixedUpdate`]s at `speed`, counting the footsteps.
+    fn footsteps_per_second(footsteps: &mut Footsteps, speed: f32) -> usize {
+        (0..64).filter(|_| footsteps.step(true, speed)).count()
+    }
+
+    #[test]
+    fn footsteps_come_faster_at_higher_speeds() {
+        let mut footsteps = Footsteps::default();
+        footsteps.step(true, 0.0);
+        // 96 units at 1.5 units per step, a footstep every 14 units
+        assert_eq!(footsteps_per_second(&mut footsteps, 1.5), 6);
+
+        let mut footsteps = Footsteps::default();
+        footsteps.step(true, 0.0);
+        assert_eq!(footsteps_per_second(&mut footsteps, 0.75), 3);
+
+        // sliding to a stop doesn't count
+        assert_eq!(footsteps_per_second(&mut footsteps, 0.3), 0);
+    }
+
+    #[test]
+    fn no_footsteps_in_the_air() {
+        let mut footsteps = Footsteps::default();
+        assert!((0..64).all(|_| !footsteps.step(false, 1.5)));
+    }
+
+    #[test]
+    fn landing_restarts_the_stride() {
+        let mut footsteps = Footsteps::default();
+        footsteps.step(true, 0.0);
+        // most of the way through a stride when the player jumps
+        (0..9).for_each(|_| assert!(!footsteps.step(true, 1.5)));
+        footsteps.step(false, 1.5);
+
+        // landing starts a full stride instead of finishing the old one
+        assert!(!footsteps.step(true, 1.5));
+        (0..9).for_each(|_| assert!(!footsteps.step(true, 1.5)));
+        assert!(footsteps.step(true, 1.5));
+    }
+}
@@ -1,39 +1,71 @@
 use std::time::Duration;
 
-use bevy::{ecs::system::SystemId, input::common_conditions::input_just_pressed, prelude::*};
+use bevy::{
+    ecs::system::SystemId,
+    input::common_conditions::{input_just_pressed, input_pressed},
+    prelude::*,
+};
 use bevy_ecs_ldtk::prelude::*;
 use bevy_rapier2d::prelude::*;
+use serde::Deserialize;
 
 use crate::{
     camera::{
-        camera_position_from_level, CameraControlType, CameraMoveEvent, CameraTransition,
-        CameraTransitionEvent,
+        camera_position_from_level_with_scale, CameraControlType, CameraMoveEvent,
+        CameraTransition, CameraTransitionEvent,
     },
+    config::Config,
     level::{
-        entity::HurtMarker, shard::reset_shard_effects_on_kill, start_flag::StartFlag,
-        CurrentLevel, LevelSystems,
+        checkpoint::Checkpoint, entity::HurtMarker, restart::RestartLevelEvent,
+        shard::reset_shard_effects_on_kill, start_flag::StartFlag, switch_level, CurrentLevel,
+        LevelSystems,
     },
     shared::{AnimationState, GameState, ResetLevel, LYRA_RESPAWN_EPSILON},
 };
 
 use super::{
     light::{AngleMarker, PlayerLightInventory},
+    lives::{GameOverEvent, PlayerLives},
     movement::PlayerMovement,
+    win::WinPose,
     PlayerHurtMarker, PlayerMarker,
 };
 
+/// How far below the bottom of the [`CurrentLevel`] the player can fall before they die.
+const OUT_OF_BOUNDS_MARGIN: f32 = 32.0;
+
 pub struct PlayerKillPlugin;
 
 impl Plugin for PlayerKillPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<KillAnimationCallbacks>()
+            .init_resource::<DeathSlowMotion>()
+            .init_resource::<RespawnDelay>()
+            .init_resource::<PendingRespawnTarget>()
+            .init_resource::<PendingRespawnGlide>()
+            .init_resource::<SpawnProtection>()
             .add_event::<KillPlayerEvent>()
-            .add_systems(Update, reset_player_on_kill.in_set(LevelSystems::Reset))
+            .add_systems(
+                Update,
+                (
+                    reset_player_on_kill,
+                    reset_death_slow_motion,
+                    reset_respawn_delay,
+                    reset_spawn_protection,
+                )
+                    .in_set(LevelSystems::Reset),
+            )
+            .add_systems(
+                Update,
+                (tick_death_slow_motion, tick_respawn_delay, glide_to_respawn),
+            )
             .add_systems(
                 Update,
                 (
                     quick_reset
                         .run_if(input_just_pressed(KeyCode::KeyR))
+                        // Shift + R restarts the whole level instead
+                        .run_if(not(input_pressed(KeyCode::ShiftLeft)))
                         .run_if(in_state(GameState::Playing)),
                     // reset player will try to preserve the current color, the calculations for
                     // which depend on proper values for the current level's allowed colors
@@ -44,37 +76,52 @@ impl Plugin for PlayerKillPlugin {
             )
             .add_systems(
                 FixedUpdate,
-                kill_player_on_hurt_intersection.in_set(LevelSystems::Simulation),
+                (
+                    update_spawn_protection.before(kill_player_on_hurt_intersection),
+                    kill_player_on_hurt_intersection,
+                    kill_player_out_of_bounds,
+                )
+                    .in_set(LevelSystems::Simulation),
             )
             .add_systems(
                 FixedUpdate,
-                start_kill_animation.run_if(on_event::<KillPlayerEvent>),
+                start_death_slow_motion
+                    .run_if(on_event::<KillPlayerEvent>)
+                    .after(switch_level),
             );
     }
 }
 
 /// [`System`] that will kill the player on press of the R key
 pub fn quick_reset(mut ev_kill_player: EventWriter<KillPlayerEvent>) {
-    ev_kill_player.send(KillPlayerEvent);
+    ev_kill_player.send(KillPlayerEvent {
+        cause: KillCause::QuickReset,
+    });
 }
 
 /// [`System`] that runs on [`GameState::Respawning`]. Will turn the state back into playing
-/// immediately.
+/// immediately, unless the respawn comes from a death that glides the player back to the
+/// checkpoint, see [`PendingRespawnGlide`].
+#[allow(clippy::too_many_arguments)]
 pub fn reset_player_on_kill(
     mut commands: Commands,
     // angle marker despawn should realistically happen in a diff system?
-    mut q_player: Query<&mut Transform, With<PlayerMarker>>,
+    mut q_player: Query<(Entity, &mut Transform), With<PlayerMarker>>,
     q_angle_marker: Query<Entity, With<AngleMarker>>,
     mut ev_reset_level: EventReader<ResetLevel>,
     q_start_flag: Query<(&StartFlag, &EntityInstance)>,
+    q_checkpoints: Query<(&Checkpoint, &EntityInstance)>,
     current_level: Res<CurrentLevel>,
     mut ev_move_camera: EventWriter<CameraMoveEvent>,
+    mut respawn_glide: ResMut<PendingRespawnGlide>,
+    config: Res<Config>,
 ) {
     // check that we recieved a ResetLevel event asking us to Respawn
     if !ev_reset_level.read().any(|x| *x == ResetLevel::Respawn) {
         return;
     }
-    let Ok(mut transform) = q_player.get_single_mut() else {
+    let glides = std::mem::take(&mut respawn_glide.0);
+    let Ok((player, mut transform)) = q_player.get_single_mut() else {
         return;
     };
 
@@ -82,23 +129,197 @@ pub fn reset_player_on_kill(
         commands.entity(angle_marker).despawn_recursive();
     }
 
-    for (flag, instance) in q_start_flag.iter() {
-        if current_level.level_iid == flag.level_iid {
-            transform.translation.x =
-                instance.world_x.expect("Lightborne uses Free world layout") as f32;
-            transform.translation.y = -instance.world_y.expect("Lightborne uses Free world layout")
-                as f32
-                + LYRA_RESPAWN_EPSILON;
-            // add small height so Lyra is not stuck into the floor
-            ev_move_camera.send(CameraMoveEvent {
-                to: camera_position_from_level(current_level.level_box, transform.translation.xy()),
-                variant: CameraControlType::Instant,
-            });
-            return;
+    let Some(checkpoint) = current_checkpoint(&q_start_flag, &q_checkpoints, &current_level) else {
+        panic!("Couldn't find start flag to respawn at");
+    };
+    let to = camera_position_from_level_with_scale(
+        current_level.level_box,
+        checkpoint,
+        current_level.camera_scale(config.camera_config.zoom),
+    );
+
+    let glide_secs = config.death_config.respawn_glide_secs;
+    if glides && glide_secs > 0.0 {
+        commands.entity(player).insert(RespawnGlide::new(
+            transform.translation.xy(),
+            checkpoint,
+            glide_secs,
+        ));
+        ev_move_camera.send(CameraMoveEvent {
+            to,
+            variant: CameraControlType::Animated {
+                duration: Duration::from_secs_f32(glide_secs),
+                ease_fn: RESPAWN_GLIDE_EASE,
+                callback: None,
+            },
+        });
+        return;
+    }
+
+    transform.translation.x = checkpoint.x;
+    transform.translation.y = checkpoint.y;
+    ev_move_camera.send(CameraMoveEvent {
+        to,
+        variant: CameraControlType::Instant,
+    });
+}
+
+/// Where the player respawns in the [`CurrentLevel`], at its active [`Checkpoint`], or at its
+/// [`StartFlag`] if none of its checkpoints are active.
+pub fn current_checkpoint(
+    q_start_flag: &Query<(&StartFlag, &EntityInstance)>,
+    q_checkpoints: &Query<(&Checkpoint, &EntityInstance)>,
+    current_level: &CurrentLevel,
+) -> Option<Vec2> {
+    let instance = q_checkpoints
+        .iter()
+        .find(|(checkpoint, _)| {
+            checkpoint.active && checkpoint.level_iid == current_level.level_iid
+        })
+        .map(|(_, instance)| instance)
+        .or_else(|| {
+            q_start_flag
+                .iter()
+                .find(|(flag, _)| flag.level_iid == current_level.level_iid)
+                .map(|(_, instance)| instance)
+        })?;
+    Some(Vec2::new(
+        instance.world_x.expect("Lightborne uses Free world layout") as f32,
+        // add small height so Lyra is not stuck into the floor
+        -instance.world_y.expect("Lightborne uses Free world layout") as f32 + LYRA_RESPAWN_EPSILON,
+    ))
+}
+
+/// When the area around the checkpoint protects the player from hazards, see
+/// [`SpawnProtection`].
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SpawnProtectionMode {
+    /// Only after respawning, until the player leaves the area for the first time
+    #[default]
+    AfterRespawn,
+    /// Whenever the player is inside of the area
+    Always,
+}
+
+/// [`Resource`] tracking whether hazards can kill the player, so a spike placed too close to a
+/// checkpoint can't kill the player the moment they respawn. The player is protected within
+/// `spawn_protection_radius` of the checkpoint, depending on the [`SpawnProtectionMode`]. See the
+/// `death_config` section of `Lightborne.toml`. Falling out of the level still kills the player.
+#[derive(Resource, Default, Debug)]
+pub struct SpawnProtection {
+    /// Whether the player has respawned and not left the area around the checkpoint since
+    since_respawn: bool,
+    /// Whether hazards can't kill the player right now
+    active: bool,
+}
+
+impl SpawnProtection {
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Protects the player until they leave the area around the checkpoint.
+    pub fn respawn(&mut self) {
+        self.since_respawn = true;
+    }
+
+    /// Updates the protection with whether the player is inside of the area around the
+    /// checkpoint.
+    pub fn update(&mut self, in_area: bool, mode: SpawnProtectionMode) {
+        if !in_area {
+            self.since_respawn = false;
         }
+        self.active = in_area
+            && match mode {
+                SpawnProtectionMode::AfterRespawn => self.since_respawn,
+                SpawnProtectionMode::Always => true,
+            };
     }
+}
 
-    panic!("Couldn't find start flag to respawn at");
+/// [`System`] that protects the player after they respawn, and takes the protection away when
+/// the level is switched.
+pub fn reset_spawn_protection(
+    mut ev_reset_level: EventReader<ResetLevel>,
+    mut spawn_protection: ResMut<SpawnProtection>,
+) {
+    *spawn_protection = SpawnProtection::default();
+    if ev_reset_level.read().any(|ev| *ev == ResetLevel::Respawn) {
+        spawn_protection.respawn();
+    }
+}
+
+/// [`System`] that updates the [`SpawnProtection`] with where the player is.
+pub fn update_spawn_protection(
+    q_player: Query<&Transform, With<PlayerMarker>>,
+    q_start_flag: Query<(&StartFlag, &EntityInstance)>,
+    q_checkpoints: Query<(&Checkpoint, &EntityInstance)>,
+    current_level: Res<CurrentLevel>,
+    mut spawn_protection: ResMut<SpawnProtection>,
+    config: Res<Config>,
+) {
+    let death_config = &config.death_config;
+    let in_area = q_player.get_single().ok().is_some_and(|transform| {
+        current_checkpoint(&q_start_flag, &q_checkpoints, &current_level).is_some_and(
+            |checkpoint| {
+                transform.translation.xy().distance(checkpoint)
+                    <= death_config.spawn_protection_radius
+            },
+        )
+    });
+    spawn_protection.update(in_area, death_config.spawn_protection);
+}
+
+const RESPAWN_GLIDE_EASE: EaseFunction = EaseFunction::SineInOut;
+
+/// [`Component`] added to the player while they glide back to the checkpoint after dying, when
+/// `respawn_glide_secs` is set in the `death_config` section of `Lightborne.toml`. The game stays
+/// in [`GameState::Animating`] until the glide is over, so the player can't move and hazards
+/// along the way are ignored.
+#[derive(Component, Debug)]
+pub struct RespawnGlide {
+    from: Vec2,
+    to: Vec2,
+    timer: Timer,
+}
+
+impl RespawnGlide {
+    pub fn new(from: Vec2, to: Vec2, secs: f32) -> Self {
+        RespawnGlide {
+            from,
+            to,
+            timer: Timer::from_seconds(secs, TimerMode::Once),
+        }
+    }
+
+    /// Where the player is along the glide.
+    pub fn position(&self) -> Vec2 {
+        let t =
+            EasingCurve::new(0.0, 1.0, RESPAWN_GLIDE_EASE).sample_clamped(self.timer.fraction());
+        self.from.lerp(self.to, t)
+    }
+}
+
+/// [`System`] that moves the player along their [`RespawnGlide`], and gives them back control
+/// once they reach the checkpoint.
+pub fn glide_to_respawn(
+    mut commands: Commands,
+    mut q_player: Query<(Entity, &mut Transform, &mut RespawnGlide), With<PlayerMarker>>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+    time: Res<Time>,
+) {
+    let Ok((player, mut transform, mut glide)) = q_player.get_single_mut() else {
+        return;
+    };
+    glide.timer.tick(time.delta());
+    let position = glide.position();
+    transform.translation.x = position.x;
+    transform.translation.y = position.y;
+    if glide.timer.finished() {
+        commands.entity(player).remove::<RespawnGlide>();
+        next_game_state.set(GameState::Playing);
+    }
 }
 
 /// Resets the player inventory and movement information on a [`LevelSwitchEvent`]
@@ -131,7 +352,11 @@ pub fn kill_player_on_hurt_intersection(
     q_hurt: Query<Entity, With<HurtMarker>>,
     mut ev_kill_player: EventWriter<KillPlayerEvent>,
     asset_server: Res<AssetServer>,
+    spawn_protection: Res<SpawnProtection>,
 ) {
+    if spawn_protection.is_active() {
+        return;
+    }
     let Ok(rapier) = rapier_context.get_single() else {
         return;
     };
@@ -141,7 +366,9 @@ pub fn kill_player_on_hurt_intersection(
 
     for hurt in q_hurt.iter() {
         if rapier.intersection_pair(player, hurt) == Some(true) {
-            ev_kill_player.send(KillPlayerEvent);
+            ev_kill_player.send(KillPlayerEvent {
+                cause: KillCause::Hazard,
+            });
             commands.entity(player).with_child((
                 AudioPlayer::new(asset_server.load("sfx/death.wav")),
                 PlaybackSettings::DESPAWN,
@@ -151,13 +378,75 @@ pub fn kill_player_on_hurt_intersection(
     }
 }
 
+/// [`System`] that kills the player when they fall out of the bottom of the [`CurrentLevel`]
+/// without entering another level.
+pub fn kill_player_out_of_bounds(
+    q_player: Query<&Transform, With<PlayerMarker>>,
+    current_level: Res<CurrentLevel>,
+    mut ev_kill_player: EventWriter<KillPlayerEvent>,
+) {
+    if current_level.level_iid.as_str().is_empty() {
+        return;
+    }
+    let Ok(transform) = q_player.get_single() else {
+        return;
+    };
+    if transform.translation.y < current_level.level_box.min.y - OUT_OF_BOUNDS_MARGIN {
+        ev_kill_player.send(KillPlayerEvent {
+            cause: KillCause::OutOfBounds,
+        });
+    }
+}
+
 /// Systems that kill the player should send this event instead of ResetLevel::Respawn, so the
 /// transition is started.
 #[derive(Event)]
-pub struct KillPlayerEvent;
+pub struct KillPlayerEvent {
+    pub cause: KillCause,
+}
+
+/// What killed the player, which decides where they respawn. See `respawn_targets` in the
+/// `death_config` section of `Lightborne.toml`.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum KillCause {
+    /// Spikes, crystals and anything else with a [`HurtMarker`]
+    Hazard,
+    /// Falling out of the bottom of the level
+    OutOfBounds,
+    /// A beam crossing a
+    /// [`ForbiddenSensor`](crate::level::forbidden_sensor::ForbiddenSensor)
+    Alarm,
+    /// Pressing R
+    QuickReset,
+}
+
+/// Where the player goes after dying.
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum RespawnTarget {
+    /// Back to the level's start flag, leaving the rest of the level as it is
+    #[default]
+    Checkpoint,
+    /// Restart the whole level from scratch, like Shift + R
+    LevelStart,
+}
+
+/// [`Resource`] holding where the player respawns once the death fade is over, decided by the
+/// [`KillCause`] of the death that started it.
+#[derive(Resource, Default, Debug)]
+pub struct PendingRespawnTarget(pub RespawnTarget);
+
+/// [`Resource`] set by [`start_kill_animation`] when the death that started it glides the player
+/// back to the checkpoint instead of fading, and consumed by the [`reset_player_on_kill`] that
+/// follows. Every other respawn, like restarting the level, still happens behind the fade.
+#[derive(Resource, Default, Debug)]
+pub struct PendingRespawnGlide(pub bool);
 
 #[derive(Resource)]
 pub struct KillAnimationCallbacks {
+    // once the death slow motion is over
+    start: SystemId,
     // once the screen is completely black
     cb1: SystemId,
     // once the screen is ready for play
@@ -167,36 +456,170 @@ pub struct KillAnimationCallbacks {
 impl FromWorld for KillAnimationCallbacks {
     fn from_world(world: &mut World) -> Self {
         KillAnimationCallbacks {
+            start: world.register_system(start_kill_animation),
             cb1: world.register_system(after_slide_to_black),
             cb2: world.register_system(after_slide_from_black),
         }
     }
 }
 
+/// [`Resource`] holding the real time left in the slow motion that plays when the player dies,
+/// before the death fade starts. See the `death_config` section of `Lightborne.toml`.
+#[derive(Resource, Default)]
+pub struct DeathSlowMotion(Option<Timer>);
+
+/// [`System`] that slows down [`Time<Virtual>`] when the player dies, or starts the death fade
+/// right away if slow motion is turned off. Deaths during the slow motion are ignored, so the
+/// slow down never stacks, and so are deaths in the same step the player completes a level.
+#[allow(clippy::too_many_arguments)]
+pub fn start_death_slow_motion(
+    mut commands: Commands,
+    mut ev_kill_player: EventReader<KillPlayerEvent>,
+    mut slow_motion: ResMut<DeathSlowMotion>,
+    win_pose: Res<WinPose>,
+    mut time: ResMut<Time<Virtual>>,
+    mut respawn_target: ResMut<PendingRespawnTarget>,
+    callbacks: Res<KillAnimationCallbacks>,
+    config: Res<Config>,
+) {
+    let Some(cause) = ev_kill_player.read().map(|ev| ev.cause).next() else {
+        return;
+    };
+    ev_kill_player.clear();
+    if slow_motion.0.is_some() || win_pose.is_active() {
+        return;
+    }
+
+    let death_config = &config.death_config;
+    respawn_target.0 = death_config.respawn_target(cause);
+    if death_config.slow_motion_secs <= 0.0 {
+        commands.run_system(callbacks.start);
+        return;
+    }
+    time.set_relative_speed(death_config.slow_motion_speed);
+    slow_motion.0 = Some(Timer::from_seconds(
+        death_config.slow_motion_secs,
+        TimerMode::Once,
+    ));
+}
+
+/// [`System`] that ends the [`DeathSlowMotion`] and starts the death fade. Uses [`Time<Real>`]
+/// so the length of the slow motion doesn't depend on its speed, but doesn't tick while paused.
+pub fn tick_death_slow_motion(
+    mut commands: Commands,
+    mut slow_motion: ResMut<DeathSlowMotion>,
+    mut time: ResMut<Time<Virtual>>,
+    real_time: Res<Time<Real>>,
+    callbacks: Res<KillAnimationCallbacks>,
+) {
+    let Some(timer) = &mut slow_motion.0 else {
+        return;
+    };
+    if time.is_paused() {
+        return;
+    }
+    if timer.tick(real_time.delta()).finished() {
+        slow_motion.0 = None;
+        time.set_relative_speed(1.0);
+        commands.run_system(callbacks.start);
+    }
+}
+
+/// [`System`] that restores the speed of time if the level resets during the
+/// [`DeathSlowMotion`], e.g. when the level is restarted.
+pub fn reset_death_slow_motion(
+    mut slow_motion: ResMut<DeathSlowMotion>,
+    mut time: ResMut<Time<Virtual>>,
+) {
+    if slow_motion.0.take().is_some() {
+        time.set_relative_speed(1.0);
+    }
+}
+
+/// Whether the player glides back to the checkpoint instead of respawning behind the death fade.
+/// Restarting the level, including after a game over, still fades.
+fn glides_to_checkpoint(
+    config: &Config,
+    respawn_target: RespawnTarget,
+    lives: &PlayerLives,
+) -> bool {
+    config.death_config.respawn_glide_secs > 0.0
+        && respawn_target == RespawnTarget::Checkpoint
+        && !lives.is_last_life()
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn start_kill_animation(
+    mut commands: Commands,
     mut ev_transition_camera: EventWriter<CameraTransitionEvent>,
     callbacks: Res<KillAnimationCallbacks>,
     cur_game_state: Res<State<GameState>>,
     mut next_game_state: ResMut<NextState<GameState>>,
     mut next_anim_state: ResMut<NextState<AnimationState>>,
+    respawn_target: Res<PendingRespawnTarget>,
+    mut respawn_glide: ResMut<PendingRespawnGlide>,
+    lives: Res<PlayerLives>,
+    config: Res<Config>,
 ) {
     if *cur_game_state.get() == GameState::Animating {
         return;
     }
+    next_game_state.set(GameState::Animating);
+    next_anim_state.set(AnimationState::Respawn);
+    respawn_glide.0 = glides_to_checkpoint(&config, respawn_target.0, &lives);
+    if respawn_glide.0 {
+        commands.run_system(callbacks.cb1);
+        return;
+    }
     ev_transition_camera.send(CameraTransitionEvent {
         duration: Duration::from_millis(400),
         ease_fn: EaseFunction::SineInOut,
         callback: Some(callbacks.cb1),
         effect: CameraTransition::SlideToBlack,
     });
-    next_game_state.set(GameState::Animating);
-    next_anim_state.set(AnimationState::Respawn);
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn after_slide_to_black(
     mut ev_transition_camera: EventWriter<CameraTransitionEvent>,
     mut ev_reset_level: EventWriter<ResetLevel>,
+    mut ev_game_over: EventWriter<GameOverEvent>,
+    mut ev_restart_level: EventWriter<RestartLevelEvent>,
+    mut lives: ResMut<PlayerLives>,
+    mut respawn_delay: ResMut<RespawnDelay>,
+    respawn_target: Res<PendingRespawnTarget>,
+    respawn_glide: Res<PendingRespawnGlide>,
     callbacks: Res<KillAnimationCallbacks>,
+    config: Res<Config>,
+) {
+    // the level restart that follows a game over takes care of respawning and the fade
+    if lives.lose_life() {
+        ev_game_over.send(GameOverEvent);
+        return;
+    }
+    // and so does restarting the level
+    if respawn_target.0 == RespawnTarget::LevelStart {
+        ev_restart_level.send(RestartLevelEvent { after_death: true });
+        return;
+    }
+    // the screen never went black, and the glide gives back control once it's over
+    if respawn_glide.0 {
+        ev_reset_level.send(ResetLevel::Respawn);
+        return;
+    }
+    let delay_secs = config.death_config.respawn_delay_secs;
+    if delay_secs > 0.0 {
+        respawn_delay.0 = Some(Timer::from_seconds(delay_secs, TimerMode::Once));
+        return;
+    }
+    respawn_from_black(&mut ev_transition_camera, &mut ev_reset_level, &callbacks);
+}
+
+/// Respawns the player while the screen is black, and fades back in.
+fn respawn_from_black(
+    ev_transition_camera: &mut EventWriter<CameraTransitionEvent>,
+    ev_reset_level: &mut EventWriter<ResetLevel>,
+    callbacks: &KillAnimationCallbacks,
 ) {
     ev_transition_camera.send(CameraTransitionEvent {
         duration: Duration::from_millis(400),
@@ -207,6 +630,643 @@ pub fn after_slide_to_black(
     ev_reset_level.send(ResetLevel::Respawn);
 }
 
+/// [`Resource`] holding the time left on the black screen after the death fade, before the
+/// player respawns. See `respawn_delay_secs` in the `death_config` section of `Lightborne.toml`.
+#[derive(Resource, Default)]
+pub struct RespawnDelay(Option<Timer>);
+
+/// [`System`] that respawns the player once the [`RespawnDelay`] is over. Any key ends it early
+/// if `respawn_delay_skippable` is set.
+pub fn tick_respawn_delay(
+    mut ev_transition_camera: EventWriter<CameraTransitionEvent>,
+    mut ev_reset_level: EventWriter<ResetLevel>,
+    mut respawn_delay: ResMut<RespawnDelay>,
+    keys: Res<ButtonInput<KeyCode>>,
+    time: Res<Time>,
+    callbacks: Res<KillAnimationCallbacks>,
+    config: Res<Config>,
+) {
+    let Some(timer) = &mut respawn_delay.0 else {
+        return;
+    };
+    let skipped =
+        config.death_config.respawn_delay_skippable && keys.get_just_pressed().next().is_some();
+    if !timer.tick(time.delta()).finished() && !skipped {
+        return;
+    }
+    respawn_delay.0 = None;
+    respawn_from_black(&mut ev_transition_camera, &mut ev_reset_level, &callbacks);
+}
+
+/// [`System`] that drops the [`RespawnDelay`] if the level resets while the screen is black, e.g.
+/// when the level is restarted, so the player doesn't respawn a second time.
+pub fn reset_respawn_delay(mut respawn_delay: ResMut<RespawnDelay>) {
+    respawn_delay.0 = None;
+}
+
 pub fn after_slide_from_black(mut next_game_state: ResMut<NextState<GameState>>) {
     next_game_state.set(GameState::Playing);
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use crate::{
+        level::{
+            rating::{count_level_run_deaths, LevelRun},
+            restart::restart_level,
+        },
+        player::lives::refill_lives_on_level_switch,
+    };
+
+    use super::*;
+
+    fn slow_motion_app() -> App {
+        let mut app = App::new();
+        app.init_resource::<Time<Virtual>>()
+            .init_resource::<Time<Real>>()
+            .insert_resource(Config::default())
+            .insert_resource(State::new(GameState::Playing))
+            .init_resource::<NextState<GameState>>()
+            .init_resource::<NextState<AnimationState>>()
+            .init_resource::<DeathSlowMotion>()
+            .init_resource::<RespawnDelay>()
+            .init_resource::<PendingRespawnTarget>()
+            .init_resource::<PendingRespawnGlide>()
+            .init_resource::<PlayerLives>()
+            .init_resource::<KillAnimationCallbacks>()
+            .init_resource::<WinPose>()
+            .add_event::<KillPlayerEvent>()
+            .add_event::<CameraTransitionEvent>()
+            .add_systems(
+                Update,
+                (
+                    start_death_slow_motion.run_if(on_event::<KillPlayerEvent>),
+                    tick_death_slow_motion,
+                )
+                    .chain(),
+            );
+        app
+    }
+
+    fn advance_real_time(app: &mut App, secs: f32) {
+        app.world_mut()
+            .resource_mut::<Time<Real>>()
+            .update_with_duration(Duration::from_secs_f32(secs));
+        app.update();
+    }
+
+    #[test]
+    fn death_slow_motion_restores_speed() {
+        let mut app = slow_motion_app();
+        let death_config = &app.world().resource::<Config>().death_config;
+        let (speed, secs) = (
+            death_config.slow_motion_speed,
+            death_config.slow_motion_secs,
+        );
+
+        app.world_mut().send_event(KillPlayerEvent {
+            cause: KillCause::Hazard,
+        });
+        advance_real_time(&mut app, 0.0);
+        assert_eq!(
+            app.world().resource::<Time<Virtual>>().relative_speed(),
+            speed
+        );
+
+        // a second death during the slow motion doesn't restart it
+        app.world_mut().send_event(KillPlayerEvent {
+            cause: KillCause::Hazard,
+        });
+        advance_real_time(&mut app, secs * 0.6);
+        app.world_mut().send_event(KillPlayerEvent {
+            cause: KillCause::Hazard,
+        });
+        advance_real_time(&mut app, secs * 0.6);
+
+        assert_eq!(
+            app.world().resource::<Time<Virtual>>().relative_speed(),
+            1.0
+        );
+        assert!(app.world().resource::<DeathSlowMotion>().0.is_none());
+        assert!(matches!(
+            app.world().resource::<NextState<GameState>>(),
+            NextState::Pending(GameState::Animating)
+        ));
+    }
+
+    #[test]
+    fn completing_a_level_beats_dying() {
+        let mut app = slow_motion_app();
+        // the player reaches the next level in the same step they die
+        app.world_mut().resource_mut::<WinPose>().start(
+            1.0,
+            CameraMoveEvent {
+                to: Vec2::ZERO,
+                variant: CameraControlType::Instant,
+            },
+        );
+        app.world_mut().send_event(KillPlayerEvent {
+            cause: KillCause::Hazard,
+        });
+        advance_real_time(&mut app, 0.0);
+        assert!(app.world().resource::<DeathSlowMotion>().0.is_none());
+        assert_eq!(
+            app.world().resource::<Time<Virtual>>().relative_speed(),
+            1.0
+        );
+        assert!(matches!(
+            app.world().resource::<NextState<AnimationState>>(),
+            NextState::Unchanged
+        ));
+    }
+
+    /// Kills the player with `cause` and finishes the death fade, returning whether the player
+    /// respawned at the checkpoint and whether the level restarted.
+    fn respawn_after(app: &mut App, cause: KillCause) -> (bool, bool) {
+        app.world_mut().send_event(KillPlayerEvent { cause });
+        app.update();
+        app.world_mut()
+            .run_system_once(after_slide_to_black)
+            .unwrap();
+
+        let respawned = app
+            .world()
+            .resource::<Events<ResetLevel>>()
+            .iter_current_update_events()
+            .any(|ev| *ev == ResetLevel::Respawn);
+        let restarted = !app
+            .world()
+            .resource::<Events<RestartLevelEvent>>()
+            .is_empty();
+        app.world_mut().resource_mut::<Events<ResetLevel>>().clear();
+        app.world_mut()
+            .resource_mut::<Events<RestartLevelEvent>>()
+            .clear();
+        // skip the rest of the slow motion
+        app.world_mut().resource_mut::<DeathSlowMotion>().0 = None;
+        (respawned, restarted)
+    }
+
+    #[test]
+    fn kill_causes_respawn_at_configured_targets() {
+        let mut app = slow_motion_app();
+        app.insert_resource(PlayerLives(None))
+            .add_event::<ResetLevel>()
+            .add_event::<GameOverEvent>()
+            .add_event::<RestartLevelEvent>();
+
+        // spikes send the player to the checkpoint, but falling out restarts the level
+        assert_eq!(respawn_after(&mut app, KillCause::Hazard), (true, false));
+        assert_eq!(
+            respawn_after(&mut app, KillCause::OutOfBounds),
+            (false, true)
+        );
+        // causes that aren't mapped use the default
+        assert_eq!(
+            respawn_after(&mut app, KillCause::QuickReset),
+            (true, false)
+        );
+
+        app.world_mut()
+            .resource_mut::<Config>()
+            .death_config
+            .default_respawn_target = RespawnTarget::LevelStart;
+        assert_eq!(
+            respawn_after(&mut app, KillCause::QuickReset),
+            (false, true)
+        );
+        assert_eq!(respawn_after(&mut app, KillCause::Hazard), (true, false));
+    }
+
+    #[test]
+    fn falling_out_keeps_lives_and_counts_the_death() {
+        let mut app = slow_motion_app();
+        app.insert_resource(PlayerLives(Some(3)))
+            .insert_resource(CurrentLevel {
+                level_iid: LevelIid::new("level"),
+                ..default()
+            })
+            .init_resource::<LevelRun>()
+            .add_event::<ResetLevel>()
+            .add_event::<GameOverEvent>()
+            .add_event::<RestartLevelEvent>()
+            .add_systems(
+                Update,
+                (
+                    restart_level.run_if(on_event::<RestartLevelEvent>),
+                    (count_level_run_deaths, refill_lives_on_level_switch)
+                        .run_if(on_event::<ResetLevel>),
+                )
+                    .chain(),
+            );
+        app.world_mut().spawn(LevelIid::new("level"));
+        app.world_mut().resource_mut::<Config>().death_config.lives = Some(3);
+
+        // enter the level
+        app.world_mut().send_event(ResetLevel::Switching);
+        app.update();
+
+        for lives in [2, 1] {
+            app.world_mut().send_event(KillPlayerEvent {
+                cause: KillCause::OutOfBounds,
+            });
+            app.update();
+            app.world_mut()
+                .run_system_once(after_slide_to_black)
+                .unwrap();
+            app.update();
+            app.world_mut().resource_mut::<DeathSlowMotion>().0 = None;
+
+            assert_eq!(
+                *app.world().resource::<PlayerLives>(),
+                PlayerLives(Some(lives))
+            );
+            assert_eq!(app.world().resource::<LevelRun>().deaths, 3 - lives);
+        }
+    }
+
+    #[test]
+    fn player_respawns_at_the_active_checkpoint() {
+        let mut world = World::new();
+        world.insert_resource(CurrentLevel {
+            level_iid: LevelIid::new("level"),
+            ..default()
+        });
+        let at = |x: i32| EntityInstance {
+            world_x: Some(x),
+            world_y: Some(0),
+            ..default()
+        };
+        world.spawn((
+            StartFlag {
+                level_iid: LevelIid::new("level"),
+            },
+            at(0),
+        ));
+        let checkpoint = world
+            .spawn((
+                Checkpoint {
+                    level_iid: LevelIid::new("level"),
+                    ..default()
+                },
+                at(100),
+            ))
+            .id();
+        world.spawn((
+            Checkpoint {
+                level_iid: LevelIid::new("other"),
+                active: true,
+                ..default()
+            },
+            at(200),
+        ));
+
+        let respawn_x = |world: &mut World| {
+            world
+                .run_system_once(
+                    |q_start_flag: Query<(&StartFlag, &EntityInstance)>,
+                     q_checkpoints: Query<(&Checkpoint, &EntityInstance)>,
+                     current_level: Res<CurrentLevel>| {
+                        current_checkpoint(&q_start_flag, &q_checkpoints, &current_level)
+                            .unwrap()
+                            .x
+                    },
+                )
+                .unwrap()
+        };
+        assert_eq!(respawn_x(&mut world), 0.0);
+        world.get_mut::<Checkpoint>(checkpoint).unwrap().active = true;
+        assert_eq!(respawn_x(&mut world), 100.0);
+    }
+
+    #[test]
+    fn player_glides_to_checkpoint() {
+        let mut config = Config::default();
+        config.death_config.respawn_glide_secs = 0.5;
+        let mut app = App::new();
+        app.insert_resource(config)
+            .init_resource::<Time>()
+            .insert_resource(CurrentLevel {
+                level_iid: LevelIid::new("level"),
+                level_box: Rect::new(-200.0, -200.0, 200.0, 200.0),
+                ..default()
+            })
+            .init_resource::<NextState<GameState>>()
+            .insert_resource(PendingRespawnGlide(true))
+            .add_event::<ResetLevel>()
+            .add_event::<CameraMoveEvent>()
+            .add_systems(Update, (reset_player_on_kill, glide_to_respawn).chain());
+        app.world_mut().spawn((
+            StartFlag {
+                level_iid: LevelIid::new("level"),
+            },
+            EntityInstance {
+                world_x: Some(100),
+                world_y: Some(-20),
+                ..default()
+            },
+        ));
+        let player = app
+            .world_mut()
+            .spawn((PlayerMarker, Transform::default()))
+            .id();
+        let checkpoint = Vec2::new(100.0, 20.0 + LYRA_RESPAWN_EPSILON);
+
+        app.world_mut().send_event(ResetLevel::Respawn);
+        app.update();
+        let position = |app: &App| {
+            app.world()
+                .get::<Transform>(player)
+                .unwrap()
+                .translation
+                .xy()
+        };
+        assert_eq!(position(&app), Vec2::ZERO);
+        assert!(app.world().get::<RespawnGlide>(player).is_some());
+
+        app.world_mut()
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_secs_f32(0.25));
+        app.update();
+        // halfway through the glide, still without control
+        assert!(position(&app).distance(checkpoint / 2.0) < 1e-3);
+        assert!(matches!(
+            app.world().resource::<NextState<GameState>>(),
+            NextState::Unchanged
+        ));
+
+        app.world_mut()
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_secs_f32(0.3));
+        app.update();
+        assert_eq!(position(&app), checkpoint);
+        assert!(app.world().get::<RespawnGlide>(player).is_none());
+        assert!(matches!(
+            app.world().resource::<NextState<GameState>>(),
+            NextState::Pending(GameState::Playing)
+        ));
+
+        // respawns that don't come from a death, like restarting the level, happen right away
+        app.world_mut()
+            .get_mut::<Transform>(player)
+            .unwrap()
+            .translation = Vec3::ZERO;
+        app.world_mut().send_event(ResetLevel::Respawn);
+        app.update();
+        assert_eq!(position(&app), checkpoint);
+        assert!(app.world().get::<RespawnGlide>(player).is_none());
+    }
+
+    #[test]
+    fn only_checkpoint_deaths_glide() {
+        let mut app = slow_motion_app();
+        app.insert_resource(PlayerLives(None))
+            .add_event::<ResetLevel>()
+            .add_event::<GameOverEvent>()
+            .add_event::<RestartLevelEvent>();
+        app.world_mut()
+            .resource_mut::<Config>()
+            .death_config
+            .respawn_glide_secs = 0.5;
+        let glides = |app: &mut App, target: RespawnTarget| {
+            app.world_mut().resource_mut::<PendingRespawnTarget>().0 = target;
+            app.insert_resource(State::new(GameState::Playing));
+            app.world_mut()
+                .run_system_once(start_kill_animation)
+                .unwrap();
+            app.world().resource::<PendingRespawnGlide>().0
+        };
+
+        assert!(glides(&mut app, RespawnTarget::Checkpoint));
+        assert!(!glides(&mut app, RespawnTarget::LevelStart));
+        app.insert_resource(PlayerLives(Some(1)));
+        assert!(!glides(&mut app, RespawnTarget::Checkpoint));
+    }
+
+    #[test]
+    fn hazards_near_checkpoint_spare_respawned_player() {
+        let mut config = Config::default();
+        config.death_config.spawn_protection_radius = 16.0;
+        let mut app = App::new();
+        app.insert_resource(config)
+            .insert_resource(CurrentLevel {
+                level_iid: LevelIid::new("level"),
+                ..default()
+            })
+            .init_resource::<SpawnProtection>()
+            .add_event::<ResetLevel>()
+            .add_systems(
+                Update,
+                (
+                    reset_spawn_protection.run_if(on_event::<ResetLevel>),
+                    update_spawn_protection,
+                )
+                    .chain(),
+            );
+        app.world_mut().spawn((
+            StartFlag {
+                level_iid: LevelIid::new("level"),
+            },
+            EntityInstance {
+                world_x: Some(0),
+                world_y: Some(0),
+                ..default()
+            },
+        ));
+        let player = app
+            .world_mut()
+            .spawn((PlayerMarker, Transform::default()))
+            .id();
+        let move_player = |app: &mut App, x: f32| {
+            app.world_mut()
+                .get_mut::<Transform>(player)
+                .unwrap()
+                .translation
+                .x = x;
+            app.update();
+            app.world().resource::<SpawnProtection>().is_active()
+        };
+
+        // walking back to the checkpoint without dying
+        assert!(!move_player(&mut app, 0.0));
+
+        app.world_mut().send_event(ResetLevel::Respawn);
+        assert!(move_player(&mut app, 0.0));
+        assert!(move_player(&mut app, 10.0));
+        // leaving the area ends the protection for good
+        assert!(!move_player(&mut app, 40.0));
+        assert!(!move_player(&mut app, 0.0));
+
+        app.world_mut()
+            .resource_mut::<Config>()
+            .death_config
+            .spawn_protection = SpawnProtectionMode::Always;
+        assert!(move_player(&mut app, 0.0));
+        assert!(!move_player(&mut app, 40.0));
+    }
+
+    #[test]
+    fn spike_inside_protection_radius_doesnt_kill_respawned_player() {
+        let mut config = Config::default();
+        config.death_config.spawn_protection_radius = 16.0;
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            AssetPlugin::default(),
+            TransformPlugin,
+            bevy::scene::ScenePlugin,
+            RapierPhysicsPlugin::<NoUserData>::default(),
+        ))
+        .init_asset::<Mesh>()
+        .init_asset::<AudioSource>()
+        .insert_resource(config)
+        .insert_resource(CurrentLevel {
+            level_iid: LevelIid::new("level"),
+            ..default()
+        })
+        .init_resource::<SpawnProtection>()
+        .add_event::<ResetLevel>()
+        .add_event::<KillPlayerEvent>()
+        .add_systems(
+            Update,
+            (
+                reset_spawn_protection.run_if(on_event::<ResetLevel>),
+                update_spawn_protection,
+                kill_player_on_hurt_intersection,
+            )
+                .chain(),
+        );
+        app.world_mut().spawn((
+            StartFlag {
+                level_iid: LevelIid::new("level"),
+            },
+            EntityInstance {
+                world_x: Some(0),
+                world_y: Some(0),
+                ..default()
+            },
+        ));
+        // the player standing on the checkpoint, with a spike right next to it
+        app.world_mut()
+            .spawn((PlayerMarker, Transform::default()))
+            .with_child((
+                Collider::cuboid(4.0, 5.0),
+                Sensor,
+                RigidBody::Dynamic,
+                GravityScale(0.0),
+                PlayerHurtMarker,
+                Transform::default(),
+            ));
+        app.world_mut().spawn((
+            HurtMarker,
+            Collider::cuboid(4.0, 4.0),
+            Sensor,
+            Transform::from_xyz(6.0, 0.0, 0.0),
+        ));
+        // whether the player was killed over the next few frames
+        let killed = |app: &mut App| {
+            for _ in 0..3 {
+                app.update();
+            }
+            app.world_mut()
+                .resource_mut::<Events<KillPlayerEvent>>()
+                .drain()
+                .count()
+                > 0
+        };
+
+        // the spike kills a player that didn't just respawn
+        assert!(killed(&mut app));
+
+        app.world_mut().send_event(ResetLevel::Respawn);
+        assert!(!killed(&mut app));
+        assert!(app.world().resource::<SpawnProtection>().is_active());
+    }
+
+    #[test]
+    fn respawn_waits_for_delay() {
+        let mut app = slow_motion_app();
+        app.insert_resource(PlayerLives(None))
+            .init_resource::<Time>()
+            .init_resource::<ButtonInput<KeyCode>>()
+            .add_event::<ResetLevel>()
+            .add_event::<GameOverEvent>()
+            .add_event::<RestartLevelEvent>()
+            .add_systems(Update, tick_respawn_delay);
+        app.world_mut()
+            .resource_mut::<Config>()
+            .death_config
+            .respawn_delay_secs = 0.5;
+
+        // whether the player respawned and the screen started fading back in since the last call
+        let respawned = |app: &mut App| {
+            let respawns = app
+                .world_mut()
+                .resource_mut::<Events<ResetLevel>>()
+                .drain()
+                .filter(|ev| *ev == ResetLevel::Respawn)
+                .count();
+            let fades = app
+                .world_mut()
+                .resource_mut::<Events<CameraTransitionEvent>>()
+                .drain()
+                .filter(|ev| matches!(ev.effect, CameraTransition::SlideFromBlack))
+                .count();
+            assert_eq!(respawns, fades);
+            respawns == 1
+        };
+        let advance = |app: &mut App, secs: f32| {
+            app.world_mut()
+                .resource_mut::<Time>()
+                .advance_by(Duration::from_secs_f32(secs));
+            app.update();
+        };
+
+        app.world_mut()
+            .run_system_once(after_slide_to_black)
+            .unwrap();
+        assert!(!respawned(&mut app));
+        advance(&mut app, 0.3);
+        assert!(!respawned(&mut app));
+        // keys don't skip the hold unless it's skippable
+        app.world_mut()
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .press(KeyCode::Space);
+        advance(&mut app, 0.1);
+        assert!(!respawned(&mut app));
+        app.world_mut()
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .clear();
+        advance(&mut app, 0.15);
+        assert!(respawned(&mut app));
+        advance(&mut app, 1.0);
+        assert!(!respawned(&mut app));
+
+        app.world_mut()
+            .resource_mut::<Config>()
+            .death_config
+            .respawn_delay_skippable = true;
+        app.world_mut()
+            .run_system_once(after_slide_to_black)
+            .unwrap();
+        advance(&mut app, 0.1);
+        assert!(!respawned(&mut app));
+        app.world_mut()
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .press(KeyCode::KeyX);
+        advance(&mut app, 0.1);
+        assert!(respawned(&mut app));
+
+        // no delay respawns right away
+        app.world_mut()
+            .resource_mut::<Config>()
+            .death_config
+            .respawn_delay_secs = 0.0;
+        app.world_mut()
+            .run_system_once(after_slide_to_black)
+            .unwrap();
+        assert!(respawned(&mut app));
+    }
+}
@@ -0,0 +1,387 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use serde::Deserialize;
+
+use crate::level::LevelSystems;
+
+use super::{
+    movement::{move_player, Gravity, PlayerMovement},
+    not_input_locked,
+    spawn::player_collision_groups,
+    PlayerMarker,
+};
+
+/// How far the sides of the player's collider are from their center, including the character
+/// controller's offset.
+const PLAYER_HALF_WIDTH: f32 = 7.0;
+/// How far above the center of the player their hands are, at the top of their collider.
+const PLAYER_HAND_HEIGHT: f32 = 5.0;
+/// How far below the center of the player their feet are.
+const PLAYER_FOOT_OFFSET: f32 = 9.0;
+
+/// [`Plugin`] that lets the player grab ledges they fall past and climb up onto them.
+pub struct PlayerLedgeGrabPlugin;
+
+impl Plugin for PlayerLedgeGrabPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LedgeGrabConfig>()
+            .add_systems(Update, reset_ledge_grabs.in_set(LevelSystems::Reset))
+            .add_systems(
+                FixedUpdate,
+                grab_ledges
+                    .run_if(not_input_locked)
+                    .after(move_player)
+                    .before(PhysicsSet::SyncBackend)
+                    .in_set(LevelSystems::Simulation),
+            );
+    }
+}
+
+/// [`Resource`] for tuning ledge grabbing, loaded from the config. Distances are in units and
+/// times in [`FixedUpdate`] steps.
+#[derive(Resource, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct LedgeGrabConfig {
+    pub enabled: bool,
+    /// How far past the side of the player a ledge can be grabbed from
+    pub reach: f32,
+    /// How far above or below the player's hands the top of a ledge can be when grabbing it
+    pub grab_window: f32,
+    /// How long climbing onto a ledge takes
+    pub climb_ticks: u32,
+    /// How long after letting go of a ledge the player falls before they can grab one again
+    pub regrab_ticks: u32,
+}
+
+impl Default for LedgeGrabConfig {
+    fn default() -> Self {
+        LedgeGrabConfig {
+            enabled: true,
+            reach: 3.0,
+            grab_window: 4.0,
+            climb_ticks: 12,
+            regrab_ticks: 10,
+        }
+    }
+}
+
+/// A ledge the player could grab, at the corner where the top of a wall meets its side.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ledge {
+    pub corner: Vec2,
+    /// 1 if the wall is to the right of the player, and -1 if it is to the left
+    pub facing: f32,
+}
+
+impl Ledge {
+    /// Where the center of the player is while hanging from the ledge by their hands.
+    pub fn hang_position(&self, up: Vec2) -> Vec2 {
+        self.corner - Vec2::X * self.facing * PLAYER_HALF_WIDTH - up * PLAYER_HAND_HEIGHT
+    }
+
+    /// Where the center of the player is while pulling themselves up, `progress` of the way
+    /// through the climb. The player first rises until their feet are above the ledge, then steps
+    /// forward onto it, so they never cut through the corner.
+    pub fn climb_position(&self, up: Vec2, progress: f32) -> Vec2 {
+        let side = Vec2::X * self.facing;
+        let hang = self.hang_position(up);
+        let above = self.corner - side * PLAYER_HALF_WIDTH + up * (PLAYER_FOOT_OFFSET + 1.0);
+        let standing = self.corner + side * PLAYER_HALF_WIDTH + up * (PLAYER_FOOT_OFFSET + 1.0);
+        if progress < 0.5 {
+            hang.lerp(above, progress * 2.0)
+        } else {
+            above.lerp(standing, (progress * 2.0 - 1.0).min(1.0))
+        }
+    }
+}
+
+/// What the player is doing this [`FixedUpdate`], as far as ledge grabbing is concerned.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct LedgeInput {
+    /// The ledge in front of the player, if they are holding towards it
+    pub ledge: Option<Ledge>,
+    pub falling: bool,
+    pub jump: bool,
+    pub down: bool,
+}
+
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub enum LedgeGrabState {
+    #[default]
+    Free,
+    Hanging {
+        ledge: Ledge,
+        /// Whether jump has been held since grabbing the ledge, so that holding jump while
+        /// falling doesn't climb right away
+        jump_held: bool,
+    },
+    Climbing {
+        ledge: Ledge,
+        ticks: u32,
+    },
+    /// Falling after letting go of a ledge
+    Released {
+        ticks: u32,
+    },
+}
+
+/// [`Component`] that tracks the player grabbing and climbing ledges.
+#[derive(Component, Default, Debug)]
+pub struct LedgeGrab {
+    pub state: LedgeGrabState,
+}
+
+impl LedgeGrab {
+    /// Advances the ledge grab by one [`FixedUpdate`]. The player grabs a ledge while falling past
+    /// it, lets go when down is pressed, and climbs up when jump is pressed.
+    pub fn update(&mut self, input: LedgeInput, config: &LedgeGrabConfig) {
+        self.state = match self.state {
+            LedgeGrabState::Free => match input.ledge {
+                Some(ledge) if input.falling => LedgeGrabState::Hanging {
+                    ledge,
+                    jump_held: input.jump,
+                },
+                _ => LedgeGrabState::Free,
+            },
+            LedgeGrabState::Hanging { .. } if input.down => LedgeGrabState::Released {
+                ticks: config.regrab_ticks,
+            },
+            LedgeGrabState::Hanging { ledge, jump_held } => match (input.jump, jump_held) {
+                (true, false) => LedgeGrabState::Climbing { ledge, ticks: 0 },
+                (jump, _) => LedgeGrabState::Hanging {
+                    ledge,
+                    jump_held: jump,
+                },
+            },
+            LedgeGrabState::Climbing { ledge, ticks } if ticks < config.climb_ticks => {
+                LedgeGrabState::Climbing {
+                    ledge,
+                    ticks: ticks + 1,
+                }
+            }
+            LedgeGrabState::Climbing { .. } => LedgeGrabState::Free,
+            LedgeGrabState::Released { ticks } if ticks > 0 => {
+                LedgeGrabState::Released { ticks: ticks - 1 }
+            }
+            LedgeGrabState::Released { .. } => LedgeGrabState::Free,
+        };
+    }
+
+    /// Where the player should be this [`FixedUpdate`], or [`None`] if they move freely.
+    pub fn target_position(&self, up: Vec2, config: &LedgeGrabConfig) -> Option<Vec2> {
+        match self.state {
+            LedgeGrabState::Hanging { ledge, .. } => Some(ledge.hang_position(up)),
+            LedgeGrabState::Climbing { ledge, ticks } => {
+                Some(ledge.climb_position(up, ticks as f32 / config.climb_ticks.max(1) as f32))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Looks for a ledge in the direction of `facing` that the player at `position` can grab, where
+/// the top of a wall is within [`LedgeGrabConfig::grab_window`] of their hands.
+pub fn find_ledge(
+    rapier_context: &RapierContext,
+    position: Vec2,
+    facing: f32,
+    up: Vec2,
+    config: &LedgeGrabConfig,
+) -> Option<Ledge> {
+    let side = Vec2::X * facing;
+    let hands = position + up * PLAYER_HAND_HEIGHT;
+    let filter = QueryFilter::new().groups(player_collision_groups());
+
+    // the side of the wall, below the lowest ledge the player can grab
+    let below_hands = hands - up * config.grab_window;
+    let (_, wall_toi) = rapier_context.cast_ray(
+        below_hands,
+        side,
+        PLAYER_HALF_WIDTH + config.reach,
+        true,
+        filter,
+    )?;
+    // the top of the wall, looking down from above the highest ledge the player can grab. Starting
+    // inside the wall means the wall is too tall to grab.
+    let above_wall = hands + up * config.grab_window + side * (wall_toi + 1.0);
+    let (_, top_toi) =
+        rapier_context.cast_ray(above_wall, -up, 2.0 * config.grab_window, true, filter)?;
+    if top_toi <= 0.0 {
+        return None;
+    }
+
+    let wall_side = below_hands + side * wall_toi;
+    let wall_top = above_wall - up * top_toi;
+    Some(Ledge {
+        corner: wall_side + up * (wall_top - wall_side).dot(up),
+        facing,
+    })
+}
+
+/// [`System`] that grabs ledges the player falls past while holding towards them, and moves the
+/// player while they hang from or climb onto a ledge. Runs after [`move_player`], overriding the
+/// movement it calculated.
+pub fn grab_ledges(
+    mut q_player: Query<
+        (
+            &GlobalTransform,
+            &mut KinematicCharacterController,
+            &KinematicCharacterControllerOutput,
+            &mut PlayerMovement,
+            &mut LedgeGrab,
+        ),
+        With<PlayerMarker>,
+    >,
+    q_rapier: Query<&RapierContext>,
+    keys: Res<ButtonInput<KeyCode>>,
+    gravity: Res<Gravity>,
+    config: Res<LedgeGrabConfig>,
+) {
+    if !config.enabled {
+        return;
+    }
+    let Ok((transform, mut controller, output, mut movement, mut ledge_grab)) =
+        q_player.get_single_mut()
+    else {
+        return;
+    };
+    let Ok(rapier_context) = q_rapier.get_single() else {
+        return;
+    };
+    let up = gravity.up();
+    let position = transform.translation().xy();
+
+    let facing = match (keys.pressed(KeyCode::KeyA), keys.pressed(KeyCode::KeyD)) {
+        (true, false) => Some(-1.0),
+        (false, true) => Some(1.0),
+        _ => None,
+    };
+    let looking = ledge_grab.state == LedgeGrabState::Free && !output.grounded;
+    let input = LedgeInput {
+        ledge: facing
+            .filter(|_| looking)
+            .and_then(|facing| find_ledge(rapier_context, position, facing, up, &config)),
+        falling: movement.velocity.dot(up) < 0.0,
+        jump: keys.any_pressed([KeyCode::Space, KeyCode::KeyW]),
+        down: keys.pressed(KeyCode::KeyS),
+    };
+    let was_climbing = matches!(ledge_grab.state, LedgeGrabState::Climbing { .. });
+    ledge_grab.update(input, &config);
+
+    let Some(target) = ledge_grab.target_position(up, &config) else {
+        return;
+    };
+    if !was_climbing && matches!(ledge_grab.state, LedgeGrabState::Climbing { .. }) {
+        // the jump that started the climb shouldn't also jump once the player is on the ledge
+        movement.cancel_jump();
+    }
+    movement.velocity = Vec2::ZERO;
+    controller.translation = Some(target - position);
+}
+
+/// [`System`] that lets go of any ledge when the level resets.
+pub fn reset_ledge_grabs(mut q_ledge_grabs: Query<&mut LedgeGrab>) {
+    for mut ledge_grab in q_ledge_grabs.iter_mut() {
+        *ledge_grab = LedgeGrab::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A platform to the right of the player whose top left corner is at the origin.
+    const LEDGE: Ledge = Ledge {
+        corner: Vec2::ZERO,
+        facing: 1.0,
+    };
+
+    fn falling_past(ledge: Option<Ledge>) -> LedgeInput {
+        LedgeInput {
+            ledge,
+            falling: true,
+            ..default()
+        }
+    }
+
+    #[test]
+    fn grabs_platform_edge_and_climbs_on_jump() {
+        let config = LedgeGrabConfig::default();
+        let mut ledge_grab = LedgeGrab::default();
+
+        // jumping up past the ledge doesn't grab it
+        ledge_grab.update(
+            LedgeInput {
+                falling: false,
+                ..falling_past(Some(LEDGE))
+            },
+            &config,
+        );
+        assert_eq!(ledge_grab.target_position(Vec2::Y, &config), None);
+
+        ledge_grab.update(falling_past(Some(LEDGE)), &config);
+        let hang = ledge_grab.target_position(Vec2::Y, &config).unwrap();
+        // hanging by the hands, beside the platform
+        assert_eq!(hang, Vec2::new(-PLAYER_HALF_WIDTH, -PLAYER_HAND_HEIGHT));
+
+        let jump = LedgeInput {
+            jump: true,
+            ..default()
+        };
+        ledge_grab.update(jump, &config);
+        let mut last = hang;
+        for _ in 0..config.climb_ticks {
+            ledge_grab.update(jump, &config);
+            let position = ledge_grab.target_position(Vec2::Y, &config).unwrap();
+            // the player never moves into the platform while their feet are below its top
+            assert!(position.x <= -PLAYER_HALF_WIDTH || position.y - PLAYER_FOOT_OFFSET > 0.0);
+            assert!(position.y >= last.y);
+            last = position;
+        }
+        // standing on top of the platform
+        assert_eq!(last, Vec2::new(PLAYER_HALF_WIDTH, PLAYER_FOOT_OFFSET + 1.0));
+        ledge_grab.update(jump, &config);
+        assert_eq!(ledge_grab.state, LedgeGrabState::Free);
+    }
+
+    #[test]
+    fn holding_jump_while_grabbing_does_not_climb() {
+        let config = LedgeGrabConfig::default();
+        let mut ledge_grab = LedgeGrab::default();
+        let held_jump = LedgeInput {
+            jump: true,
+            ..falling_past(Some(LEDGE))
+        };
+        ledge_grab.update(held_jump, &config);
+        ledge_grab.update(held_jump, &config);
+        assert!(matches!(ledge_grab.state, LedgeGrabState::Hanging { .. }));
+
+        // letting go of jump and pressing it again climbs
+        ledge_grab.update(LedgeInput::default(), &config);
+        ledge_grab.update(held_jump, &config);
+        assert!(matches!(ledge_grab.state, LedgeGrabState::Climbing { .. }));
+    }
+
+    #[test]
+    fn down_lets_go_of_ledge() {
+        let config = LedgeGrabConfig::default();
+        let mut ledge_grab = LedgeGrab::default();
+        ledge_grab.update(falling_past(Some(LEDGE)), &config);
+        ledge_grab.update(
+            LedgeInput {
+                down: true,
+                ..default()
+            },
+            &config,
+        );
+        assert_eq!(ledge_grab.target_position(Vec2::Y, &config), None);
+
+        // the player falls for a while before they can grab a ledge again
+        for _ in 0..=config.regrab_ticks {
+            ledge_grab.update(falling_past(Some(LEDGE)), &config);
+            assert_eq!(ledge_grab.target_position(Vec2::Y, &config), None);
+        }
+        ledge_grab.update(falling_past(Some(LEDGE)), &config);
+        assert!(matches!(ledge_grab.state, LedgeGrabState::Hanging { .. }));
+    }
+}
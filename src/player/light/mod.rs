@@ -14,8 +14,9 @@ use crate::{
     input::{update_cursor_world_coords, CursorWorldCoords},
     level::{CurrentLevel, LevelSystems},
     light::{
-        segments::{play_light_beam, PrevLightBeamPlayback},
-        LightBeamSource, LightColor, LightSourceZMarker,
+        fog::VolumetricFog,
+        segments::{play_light_beam, BeamMedia, PrevLightBeamPlayback},
+        BeamBounces, LightBeamDepth, LightBeamSource, LightColor, LightSourceZMarker,
     },
     lighting::LineLight2d,
 };
@@ -216,8 +217,12 @@ pub fn shoot_light(
             start_dir: ray_dir,
             time_traveled: 0.0,
             color: shoot_color,
+            width: 0.0,
+            intensity: 1.0,
+            penetration: 0.0,
+            depth: LightBeamDepth::Background,
         })
-        .insert(PrevLightBeamPlayback::from_color(shoot_color))
+        .insert(PrevLightBeamPlayback::default())
         .insert(LineLight2d::point(
             shoot_color.lighting_color().extend(1.0),
             30.0,
@@ -243,6 +248,9 @@ pub fn preview_light_path(
     q_player: Query<(&Transform, &PlayerLightInventory), With<PlayerMarker>>,
     q_cursor: Query<&CursorWorldCoords>,
     mut gizmos: Gizmos,
+    fog: Res<VolumetricFog>,
+    beam_bounces: Res<BeamBounces>,
+    media: BeamMedia,
 ) {
     let Ok(rapier_context) = q_rapier.get_single_mut() else {
         return;
@@ -267,8 +275,18 @@ pub fn preview_light_path(
         start_dir: ray_dir,
         time_traveled: 10000.0, // LOL
         color: shoot_color,
+        width: 0.0,
+        intensity: 1.0,
+        penetration: 0.0,
+        depth: LightBeamDepth::Background,
     };
-    let playback = play_light_beam(rapier_context.into_inner(), &dummy_source);
+    let playback = play_light_beam(
+        rapier_context.into_inner(),
+        &dummy_source,
+        beam_bounces.get(shoot_color),
+        &fog,
+        &media,
+    );
 
     for (a, b) in playback.iter_points(&dummy_source).tuple_windows() {
         gizmos.line_2d(a, b, shoot_color.light_beam_color().darker(0.3));
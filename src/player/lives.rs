@@ -0,0 +1,170 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::LevelIid;
+
+use crate::{
+    config::Config,
+    level::{restart::RestartLevelEvent, CurrentLevel, LevelSystems},
+    shared::ResetLevel,
+};
+
+/// [`Plugin`] for the optional lives mode, turned on by setting `lives` in the `death_config`
+/// section of `Lightborne.toml`.
+pub struct PlayerLivesPlugin;
+
+impl Plugin for PlayerLivesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PlayerLives>()
+            .add_event::<GameOverEvent>()
+            .add_systems(
+                Update,
+                refill_lives_on_level_switch.in_set(LevelSystems::Reset),
+            )
+            .add_systems(
+                Update,
+                restart_on_game_over.run_if(on_event::<GameOverEvent>),
+            );
+    }
+}
+
+/// Sent instead of [`ResetLevel::Respawn`] when the player dies without any lives left.
+#[derive(Event, Debug)]
+pub struct GameOverEvent;
+
+/// [`Resource`] holding the number of lives the player has left. `None` means lives are turned
+/// off and the player can die as many times as they want.
+#[derive(Resource, Debug, PartialEq, Eq)]
+pub struct PlayerLives(pub Option<u32>);
+
+impl FromWorld for PlayerLives {
+    fn from_world(world: &mut World) -> Self {
+        PlayerLives(world.resource::<Config>().death_config.lives)
+    }
+}
+
+impl PlayerLives {
+    /// Takes away one life, returning true if that was the player's last one.
+    pub fn lose_life(&mut self) -> bool {
+        let Some(lives) = &mut self.0 else {
+            return false;
+        };
+        *lives = lives.saturating_sub(1);
+        *lives == 0
+    }
+
+    /// Whether losing another life would be a game over.
+    pub fn is_last_life(&self) -> bool {
+        matches!(self.0, Some(lives) if lives <= 1)
+    }
+}
+
+/// [`System`] that gives the player all their lives back when they enter a new level, if
+/// `refill_lives_on_level_switch` is set. Respawning within a level or restarting it keeps the
+/// lives lost so far.
+pub fn refill_lives_on_level_switch(
+    mut ev_reset_level: EventReader<ResetLevel>,
+    current_level: Res<CurrentLevel>,
+    mut prev_level_iid: Local<Option<LevelIid>>,
+    mut lives: ResMut<PlayerLives>,
+    config: Res<Config>,
+) {
+    if !ev_reset_level.read().any(|x| *x == ResetLevel::Switching) {
+        return;
+    }
+    if prev_level_iid.as_ref() == Some(&current_level.level_iid) {
+        return;
+    }
+    *prev_level_iid = Some(current_level.level_iid.clone());
+    if config.death_config.refill_lives_on_level_switch {
+        lives.0 = config.death_config.lives;
+    }
+}
+
+/// [`System`] that restarts the current level from scratch and gives the player all their lives
+/// back after a [`GameOverEvent`].
+pub fn restart_on_game_over(
+    mut ev_game_over: EventReader<GameOverEvent>,
+    mut ev_restart_level: EventWriter<RestartLevelEvent>,
+    mut lives: ResMut<PlayerLives>,
+    config: Res<Config>,
+) {
+    ev_game_over.clear();
+    lives.0 = config.death_config.lives;
+    ev_restart_level.send_default();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lives_run_out() {
+        let mut lives = PlayerLives(Some(2));
+        assert!(!lives.lose_life());
+        assert_eq!(lives, PlayerLives(Some(1)));
+        assert!(lives.lose_life());
+        assert_eq!(lives, PlayerLives(Some(0)));
+    }
+
+    #[test]
+    fn infinite_lives_never_run_out() {
+        let mut lives = PlayerLives(None);
+        for _ in 0..10 {
+            assert!(!lives.lose_life());
+        }
+        assert_eq!(lives, PlayerLives(None));
+    }
+
+    #[test]
+    fn only_new_levels_refill_lives() {
+        let mut config = Config::default();
+        config.death_config.lives = Some(3);
+        config.death_config.refill_lives_on_level_switch = true;
+
+        let mut app = App::new();
+        app.insert_resource(config)
+            .init_resource::<PlayerLives>()
+            .init_resource::<CurrentLevel>()
+            .add_event::<ResetLevel>()
+            .add_systems(Update, refill_lives_on_level_switch);
+
+        let enter_level = |app: &mut App, level_iid: &str| {
+            app.world_mut().resource_mut::<CurrentLevel>().level_iid = LevelIid::new(level_iid);
+            app.world_mut().send_event(ResetLevel::Switching);
+            app.update();
+        };
+
+        enter_level(&mut app, "first");
+        app.world_mut().resource_mut::<PlayerLives>().0 = Some(1);
+
+        // restarting the level with Shift+R or by holding T sends the same reset
+        enter_level(&mut app, "first");
+        assert_eq!(*app.world().resource::<PlayerLives>(), PlayerLives(Some(1)));
+
+        enter_level(&mut app, "second");
+        assert_eq!(*app.world().resource::<PlayerLives>(), PlayerLives(Some(3)));
+    }
+
+    #[test]
+    fn game_over_restarts_level_with_full_lives() {
+        let mut config = Config::default();
+        config.death_config.lives = Some(3);
+
+        let mut app = App::new();
+        app.insert_resource(config)
+            .init_resource::<PlayerLives>()
+            .add_event::<GameOverEvent>()
+            .add_event::<RestartLevelEvent>()
+            .add_systems(
+                Update,
+                restart_on_game_over.run_if(on_event::<GameOverEvent>),
+            );
+
+        app.world_mut().resource_mut::<PlayerLives>().0 = Some(0);
+        app.world_mut().send_event(GameOverEvent);
+        app.update();
+
+        assert_eq!(*app.world().resource::<PlayerLives>(), PlayerLives(Some(3)));
+        let ev_restart_level = app.world().resource::<Events<RestartLevelEvent>>();
+        assert_eq!(ev_restart_level.len(), 1);
+    }
+}
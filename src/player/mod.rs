@@ -6,21 +6,29 @@ use match_player::{
     post_update_match_player_pixel, pre_update_match_player_pixel, update_match_player_z,
 };
 use strand::PlayerStrandPlugin;
+use win::PlayerWinPlugin;
 
 use crate::{animation::AnimationConfig, level::LevelSystems, lighting::LineLight2d};
 
+use footstep::{Footsteps, PlayerFootstepPlugin};
 use kill::PlayerKillPlugin;
+use ledge::{LedgeGrab, PlayerLedgeGrabPlugin};
 use light::{PlayerLightInventory, PlayerLightPlugin};
+use lives::PlayerLivesPlugin;
 use movement::{PlayerMovement, PlayerMovementPlugin};
 use spawn::{add_player_sensors, init_player_bundle};
 
 mod animation;
+pub mod footstep;
 pub mod kill;
+pub mod ledge;
 pub mod light;
+pub mod lives;
 pub mod match_player;
 pub mod movement;
-mod spawn;
+pub mod spawn;
 mod strand;
+pub mod win;
 
 /// [`Plugin`] for anything player based.
 pub struct PlayerManagementPlugin;
@@ -30,7 +38,11 @@ impl Plugin for PlayerManagementPlugin {
         app.add_plugins(PlayerLightPlugin)
             .add_plugins(PlayerMovementPlugin)
             .add_plugins(PlayerKillPlugin)
+            .add_plugins(PlayerLivesPlugin)
             .add_plugins(PlayerStrandPlugin)
+            .add_plugins(PlayerFootstepPlugin)
+            .add_plugins(PlayerLedgeGrabPlugin)
+            .add_plugins(PlayerWinPlugin)
             .add_systems(
                 PreUpdate,
                 add_player_sensors.in_set(LevelSystems::Processing),
@@ -78,6 +90,8 @@ pub struct PlayerBundle {
     friction: Friction,
     restitution: Restitution,
     player_movement: PlayerMovement,
+    footsteps: Footsteps,
+    ledge_grab: LedgeGrab,
     light_inventory: PlayerLightInventory,
     point_lighting: LineLight2d,
     animation_config: AnimationConfig,
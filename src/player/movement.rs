@@ -1,5 +1,6 @@
 use bevy::{input::common_conditions::input_just_pressed, prelude::*};
 use bevy_rapier2d::prelude::*;
+use serde::Deserialize;
 
 use crate::level::LevelSystems;
 
@@ -14,43 +15,146 @@ const JUMP_BOOST_TICKS: isize = 2;
 
 /// Max player horizontal velocity.
 const PLAYER_MAX_H_VEL: f32 = 1.5;
-/// Max player vertical velocity.
+/// Max player upward velocity. Falling is limited by [`Gravity::terminal_velocity`] instead.
 const PLAYER_MAX_Y_VEL: f32 = 5.;
 /// The positive y velocity added to the player every jump boost tick.
 const PLAYER_JUMP_VEL: f32 = 2.2;
 /// The x velocity added to the player when A/D is held.
 const PLAYER_MOVE_VEL: f32 = 0.6;
-/// The y velocity subtracted from the player due to gravity.
-const PLAYER_GRAVITY: f32 = 0.15;
 
 pub struct PlayerMovementPlugin;
 
 impl Plugin for PlayerMovementPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            FixedUpdate,
-            move_player
-                .before(PhysicsSet::SyncBackend)
-                .in_set(LevelSystems::Simulation),
-        )
-        .add_systems(
-            Update,
-            queue_jump
-                .run_if(not_input_locked)
-                .run_if(input_just_pressed(KeyCode::Space).or(input_just_pressed(KeyCode::KeyW)))
-                .before(move_player)
-                .in_set(LevelSystems::Simulation),
-        )
-        .add_systems(
-            Update,
-            crouch_player
-                .run_if(not_input_locked)
-                .before(move_player)
-                .in_set(LevelSystems::Simulation),
-        );
+        app.init_resource::<Gravity>()
+            .init_resource::<VariableJump>()
+            .add_event::<GravityFlipEvent>()
+            .add_systems(Update, reset_gravity.in_set(LevelSystems::Reset))
+            .add_systems(
+                FixedUpdate,
+                (flip_gravity, move_player)
+                    .chain()
+                    .before(PhysicsSet::SyncBackend)
+                    .in_set(LevelSystems::Simulation),
+            )
+            .add_systems(
+                Update,
+                queue_jump
+                    .run_if(not_input_locked)
+                    .run_if(
+                        input_just_pressed(KeyCode::Space).or(input_just_pressed(KeyCode::KeyW)),
+                    )
+                    .before(move_player)
+                    .in_set(LevelSystems::Simulation),
+            )
+            .add_systems(
+                Update,
+                crouch_player
+                    .run_if(not_input_locked)
+                    .before(move_player)
+                    .in_set(LevelSystems::Simulation),
+            );
     }
 }
 
+/// [`Resource`] for the gravity pulling on the player and
+/// [`PushBlock`](crate::level::push_block::PushBlock)s, loaded from the config. It is read every
+/// [`FixedUpdate`], so changing it takes effect immediately. Speeds are in units per
+/// [`FixedUpdate`].
+#[derive(Resource, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct Gravity {
+    /// The velocity subtracted every [`FixedUpdate`] while falling
+    pub gravity: f32,
+    /// The fastest anything can fall. Keeps fast falls from moving further than the thinnest
+    /// platforms in a single step.
+    pub terminal_velocity: f32,
+    /// Whether gravity pulls upwards, see [`GravityFlipEvent`]
+    #[serde(skip)]
+    pub flipped: bool,
+}
+
+impl Default for Gravity {
+    fn default() -> Self {
+        Gravity {
+            gravity: 0.15,
+            terminal_velocity: 5.0,
+            flipped: false,
+        }
+    }
+}
+
+impl Gravity {
+    /// The direction things fall away from.
+    pub fn up(&self) -> Vec2 {
+        if self.flipped {
+            Vec2::NEG_Y
+        } else {
+            Vec2::Y
+        }
+    }
+
+    /// Applies one [`FixedUpdate`] of gravity to a vertical velocity, limiting the speed of the
+    /// fall to the terminal velocity.
+    pub fn fall(&self, velocity_y: f32) -> f32 {
+        let rise = velocity_y * self.up().y - self.gravity;
+        rise.max(-self.terminal_velocity) * self.up().y
+    }
+}
+
+/// [`Resource`] for how much shorter a jump gets when the jump key is let go of early, loaded from
+/// the config.
+#[derive(Resource, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct VariableJump {
+    /// How much of the player's upward velocity is kept when the jump is cut. 0 stops the player
+    /// rising right away, and 1 makes every jump full height.
+    pub cut_multiplier: f32,
+    /// The number of [`FixedUpdate`] steps after jumping that the jump can be cut for. Letting go
+    /// after this always gives a full jump.
+    pub max_hold_ticks: isize,
+}
+
+impl Default for VariableJump {
+    fn default() -> Self {
+        VariableJump {
+            cut_multiplier: 0.0,
+            max_hold_ticks: 16,
+        }
+    }
+}
+
+impl VariableJump {
+    /// Cuts the upward velocity of a jump. Falling is left alone, so cutting the jump after its
+    /// apex does nothing.
+    pub fn cut(&self, rise: f32) -> f32 {
+        if rise <= 0.0 {
+            return rise;
+        }
+        rise * self.cut_multiplier
+    }
+}
+
+/// [`Event`] that flips the direction of [`Gravity`]. Gravity goes back to normal when the level
+/// resets.
+#[derive(Event, Debug)]
+pub struct GravityFlipEvent;
+
+/// [`System`] that flips [`Gravity`] on every [`GravityFlipEvent`].
+pub fn flip_gravity(
+    mut ev_gravity_flip: EventReader<GravityFlipEvent>,
+    mut gravity: ResMut<Gravity>,
+) {
+    for _ in ev_gravity_flip.read() {
+        gravity.flipped = !gravity.flipped;
+    }
+}
+
+/// [`System`] that points [`Gravity`] back down when the level resets.
+pub fn reset_gravity(mut gravity: ResMut<Gravity>) {
+    gravity.flipped = false;
+}
+
 /// [`Component`] that stores information about the player's movement state.
 #[derive(Component, Default)]
 pub struct PlayerMovement {
@@ -60,6 +164,14 @@ pub struct PlayerMovement {
     should_jump_ticks_remaining: isize,
     coyote_time_ticks_remaining: isize,
     jump_boost_ticks_remaining: isize,
+    jump_hold_ticks_remaining: isize,
+}
+
+impl PlayerMovement {
+    /// Forgets a jump that was pressed but hasn't happened yet.
+    pub fn cancel_jump(&mut self) {
+        self.should_jump_ticks_remaining = 0;
+    }
 }
 
 /// [`System`] that is run the frame the space bar is pressed. Allows the player to jump for the
@@ -105,12 +217,18 @@ pub fn move_player(
         With<PlayerMarker>,
     >,
     keys: Res<ButtonInput<KeyCode>>,
+    gravity: Res<Gravity>,
+    variable_jump: Res<VariableJump>,
 ) {
     let Ok((mut controller, output, mut player, movement_locked)) = q_player.get_single_mut()
     else {
         return;
     };
 
+    // vertical movement is handled relative to the direction of gravity
+    let up = gravity.up().y;
+    let mut rise = player.velocity.y * up;
+
     let check_pressed = |key: KeyCode| {
         if movement_locked.is_some() {
             return false;
@@ -126,28 +244,30 @@ pub fn move_player(
     // grounded in the past COYOTE_TIME_TICKS
     if player.should_jump_ticks_remaining > 0 && player.coyote_time_ticks_remaining > 0 {
         player.jump_boost_ticks_remaining = JUMP_BOOST_TICKS;
+        player.jump_hold_ticks_remaining = variable_jump.max_hold_ticks;
     } else if !check_pressed(KeyCode::Space)
         && !check_pressed(KeyCode::KeyW)
-        && player.velocity.y > 0.
+        && player.jump_hold_ticks_remaining > 0
     {
-        // Jump was cut
-        player.velocity.y = PLAYER_GRAVITY;
+        // Jump was cut, only once per jump so the velocity isn't cut again every step
+        rise = variable_jump.cut(rise);
         player.jump_boost_ticks_remaining = 0;
-    } else if output.desired_translation.y > 0. && output.effective_translation.y < 0.05 {
+        player.jump_hold_ticks_remaining = 0;
+    } else if output.desired_translation.y * up > 0. && output.effective_translation.y * up < 0.05 {
         // Bonked head onto wall
-        player.velocity.y = 0.;
+        rise = 0.;
         player.jump_boost_ticks_remaining = 0;
     } else if output.grounded {
-        player.velocity.y = 0.;
+        rise = 0.;
     }
 
     if player.jump_boost_ticks_remaining > 0 {
-        player.velocity.y = PLAYER_JUMP_VEL;
+        rise = PLAYER_JUMP_VEL;
     } else {
-        player.velocity.y -= PLAYER_GRAVITY;
+        rise = gravity.fall(rise * up) * up;
     }
 
-    player.velocity.y = player.velocity.y.clamp(-PLAYER_MAX_Y_VEL, PLAYER_MAX_Y_VEL);
+    player.velocity.y = rise.min(PLAYER_MAX_Y_VEL) * up;
 
     let mut moved = false;
     if check_pressed(KeyCode::KeyA) {
@@ -170,7 +290,123 @@ pub fn move_player(
 
     player.should_jump_ticks_remaining -= 1;
     player.jump_boost_ticks_remaining -= 1;
+    player.jump_hold_ticks_remaining -= 1;
     player.coyote_time_ticks_remaining -= 1;
 
+    controller.up = gravity.up();
     controller.translation = Some(player.velocity);
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    #[test]
+    fn fall_speed_clamps_to_terminal_velocity() {
+        let gravity = Gravity {
+            gravity: 1.0,
+            terminal_velocity: 3.0,
+            flipped: false,
+        };
+        assert_eq!(gravity.fall(0.0), -1.0);
+        assert_eq!(gravity.fall(-2.5), -3.0);
+        assert_eq!(gravity.fall(-10.0), -3.0);
+
+        let mut velocity = 0.0;
+        for _ in 0..100 {
+            velocity = gravity.fall(velocity);
+        }
+        assert_eq!(velocity, -3.0);
+    }
+
+    #[test]
+    fn flipped_gravity_falls_upwards() {
+        let gravity = Gravity {
+            gravity: 1.0,
+            terminal_velocity: 3.0,
+            flipped: true,
+        };
+        assert_eq!(gravity.up(), Vec2::NEG_Y);
+        assert_eq!(gravity.fall(0.0), 1.0);
+        assert_eq!(gravity.fall(10.0), 3.0);
+    }
+
+    /// The height of a jump where the jump key is let go of after `held_ticks`, running
+    /// [`queue_jump`] and [`move_player`] on a player that starts on the ground with nothing above
+    /// it.
+    fn jump_height(variable_jump: VariableJump, held_ticks: isize) -> f32 {
+        let mut world = World::new();
+        world.init_resource::<Gravity>();
+        world.insert_resource(variable_jump);
+        let mut keys = ButtonInput::<KeyCode>::default();
+        keys.press(KeyCode::Space);
+        world.insert_resource(keys);
+        let player = world
+            .spawn((
+                PlayerMarker,
+                PlayerMovement::default(),
+                KinematicCharacterController::default(),
+                KinematicCharacterControllerOutput {
+                    grounded: true,
+                    ..default()
+                },
+            ))
+            .id();
+        world.run_system_once(queue_jump).unwrap();
+
+        let mut height = 0.0;
+        for tick in 0.. {
+            if tick == held_ticks {
+                world
+                    .resource_mut::<ButtonInput<KeyCode>>()
+                    .release(KeyCode::Space);
+            }
+            world.run_system_once(move_player).unwrap();
+
+            let translation = world
+                .get::<KinematicCharacterController>(player)
+                .unwrap()
+                .translation
+                .unwrap();
+            if translation.y <= 0.0 && tick > 0 {
+                return height;
+            }
+            height += translation.y;
+            // the player moves freely through the air
+            let mut output = world
+                .get_mut::<KinematicCharacterControllerOutput>(player)
+                .unwrap();
+            output.grounded = false;
+            output.desired_translation = translation;
+            output.effective_translation = translation;
+        }
+        unreachable!()
+    }
+
+    #[test]
+    fn tapping_jump_gives_lower_jump() {
+        let variable_jump = VariableJump {
+            cut_multiplier: 0.5,
+            max_hold_ticks: 10,
+        };
+        let tap = jump_height(variable_jump, 2);
+        let hold = jump_height(variable_jump, isize::MAX);
+        assert!(tap < hold * 0.75, "tap {tap} hold {hold}");
+
+        // letting go after the max hold time is a full jump
+        assert_eq!(jump_height(variable_jump, 16), hold);
+    }
+
+    #[test]
+    fn cutting_after_apex_does_nothing() {
+        let variable_jump = VariableJump {
+            cut_multiplier: 0.5,
+            max_hold_ticks: 10,
+        };
+        assert_eq!(variable_jump.cut(1.0), 0.5);
+        assert_eq!(variable_jump.cut(0.0), 0.0);
+        assert_eq!(variable_jump.cut(-2.0), -2.0);
+    }
+}
@@ -6,20 +6,28 @@ use crate::{animation::AnimationConfig, lighting::LineLight2d, shared::GroupLabe
 
 use super::{
     animation::{PlayerAnimationType, ANIMATION_FRAMES},
+    footstep::Footsteps,
+    ledge::LedgeGrab,
     light::PlayerLightInventory,
     movement::PlayerMovement,
     PlayerBundle, PlayerHurtMarker, PlayerMarker,
 };
 
+/// The [`CollisionGroups`] of the player's collider and controller, which decide what the player
+/// stands on and runs into.
+pub fn player_collision_groups() -> CollisionGroups {
+    CollisionGroups::new(
+        GroupLabel::PLAYER_COLLIDER,
+        GroupLabel::TERRAIN | GroupLabel::PLAYER_TERRAIN,
+    )
+}
+
 /// Used by Ldtk to spawn the player correctly with all of the correct [`Component`]s.
 pub fn init_player_bundle(_: &EntityInstance) -> PlayerBundle {
     PlayerBundle {
         body: RigidBody::KinematicPositionBased,
         controller: KinematicCharacterController {
-            filter_groups: Some(CollisionGroups::new(
-                GroupLabel::PLAYER_COLLIDER,
-                GroupLabel::TERRAIN,
-            )),
+            filter_groups: Some(player_collision_groups()),
             offset: CharacterLength::Absolute(1.0),
             ..default()
         },
@@ -29,8 +37,10 @@ pub fn init_player_bundle(_: &EntityInstance) -> PlayerBundle {
             Rot::default(),
             Collider::cuboid(6.0, 7.0),
         )]),
-        collision_groups: CollisionGroups::new(GroupLabel::PLAYER_COLLIDER, GroupLabel::TERRAIN),
+        collision_groups: player_collision_groups(),
         player_movement: PlayerMovement::default(),
+        footsteps: Footsteps::default(),
+        ledge_grab: LedgeGrab::default(),
         friction: Friction {
             coefficient: 0.,
             combine_rule: CoefficientCombineRule::Min,
@@ -0,0 +1,149 @@
+use bevy::prelude::*;
+
+use crate::{animation::AnimationConfig, camera::CameraMoveEvent, shared::AnimationState};
+
+use super::{animation::PlayerAnimationType, InputLocked, PlayerMarker};
+
+/// [`Plugin`] for the pose the player strikes after completing a level, before the camera moves
+/// on to the next one.
+pub struct PlayerWinPlugin;
+
+impl Plugin for PlayerWinPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WinPose>()
+            .add_systems(OnEnter(AnimationState::Win), start_win_pose)
+            .add_systems(Update, tick_win_pose.run_if(in_state(AnimationState::Win)));
+    }
+}
+
+/// [`Resource`] holding the time left in the win pose, and the camera move that takes the player
+/// to the next level once it ends. While it is active the game is in [`AnimationState::Win`], so
+/// gameplay is frozen, input is locked and deaths are ignored. See `win_pose_secs` in the
+/// `level_config` section of `Lightborne.toml`.
+#[derive(Resource, Default, Debug)]
+pub struct WinPose(Option<(Timer, CameraMoveEvent)>);
+
+impl WinPose {
+    /// Starts the pose, holding back `advance` until it ends.
+    pub fn start(&mut self, secs: f32, advance: CameraMoveEvent) {
+        self.0 = Some((Timer::from_seconds(secs, TimerMode::Once), advance));
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+/// [`System`] that locks the player's input and puts them in the [`PlayerAnimationType::Win`]
+/// pose.
+pub fn start_win_pose(
+    mut commands: Commands,
+    mut q_player: Query<
+        (Entity, &mut PlayerAnimationType, &mut AnimationConfig),
+        With<PlayerMarker>,
+    >,
+) {
+    let Ok((player, mut animation, mut config)) = q_player.get_single_mut() else {
+        return;
+    };
+    commands.entity(player).insert(InputLocked);
+    *animation = PlayerAnimationType::Win;
+    *config = AnimationConfig::from(PlayerAnimationType::Win);
+}
+
+/// [`System`] that ends the [`WinPose`], unlocking the player's input and moving the camera to
+/// the next level.
+pub fn tick_win_pose(
+    mut commands: Commands,
+    q_player: Query<Entity, With<PlayerMarker>>,
+    mut win_pose: ResMut<WinPose>,
+    mut ev_move_camera: EventWriter<CameraMoveEvent>,
+    mut next_anim_state: ResMut<NextState<AnimationState>>,
+    time: Res<Time>,
+) {
+    let Some((timer, _)) = &mut win_pose.0 else {
+        return;
+    };
+    if !timer.tick(time.delta()).finished() {
+        return;
+    }
+
+    let (_, advance) = win_pose.0.take().unwrap();
+    ev_move_camera.send(advance);
+    next_anim_state.set(AnimationState::Switch);
+    if let Ok(player) = q_player.get_single() {
+        commands.entity(player).remove::<InputLocked>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::state::app::StatesPlugin;
+
+    use crate::{camera::CameraControlType, shared::GameState};
+
+    use super::*;
+
+    #[test]
+    fn level_advances_after_win_pose() {
+        let mut app = App::new();
+        app.add_plugins(StatesPlugin)
+            .insert_state(GameState::Playing)
+            .add_sub_state::<AnimationState>()
+            .add_event::<CameraMoveEvent>()
+            .init_resource::<Time>()
+            .add_plugins(PlayerWinPlugin);
+        let player = app
+            .world_mut()
+            .spawn((
+                PlayerMarker,
+                PlayerAnimationType::Walk,
+                AnimationConfig::from(PlayerAnimationType::Walk),
+            ))
+            .id();
+        let advances = |app: &App| app.world().resource::<Events<CameraMoveEvent>>().len();
+
+        app.world_mut().resource_mut::<WinPose>().start(
+            1.0,
+            CameraMoveEvent {
+                to: Vec2::new(100.0, 0.0),
+                variant: CameraControlType::Instant,
+            },
+        );
+        app.world_mut()
+            .resource_mut::<NextState<GameState>>()
+            .set(GameState::Animating);
+        app.world_mut()
+            .resource_mut::<NextState<AnimationState>>()
+            .set(AnimationState::Win);
+        app.update();
+        assert!(app.world().get::<InputLocked>(player).is_some());
+        assert_eq!(
+            *app.world().get::<PlayerAnimationType>(player).unwrap(),
+            PlayerAnimationType::Win
+        );
+
+        app.world_mut()
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_secs_f32(0.6));
+        app.update();
+        // still posing, the level hasn't advanced yet
+        assert!(app.world().get::<InputLocked>(player).is_some());
+        assert_eq!(advances(&app), 0);
+
+        app.world_mut()
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_secs_f32(0.6));
+        app.update();
+        assert!(app.world().get::<InputLocked>(player).is_none());
+        assert_eq!(advances(&app), 1);
+        assert!(!app.world().resource::<WinPose>().is_active());
+        app.update();
+        assert_eq!(
+            *app.world().resource::<State<AnimationState>>().get(),
+            AnimationState::Switch
+        );
+    }
+}
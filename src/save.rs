@@ -0,0 +1,465 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use bevy::{
+    input::common_conditions::{input_just_pressed, input_pressed},
+    prelude::*,
+};
+
+use crate::{
+    config::Config,
+    level::{
+        rating::{BestRatings, BestTimes},
+        torch::LitTorches,
+    },
+    shared::UiState,
+};
+
+/// The slot used when no slot is picked in the config, or the picked slot's name is invalid.
+pub const DEFAULT_SLOT: &str = "default";
+
+/// The file in a slot the [`BestRatings`] are saved to.
+const RATINGS_FILE: &str = "ratings.txt";
+
+/// The file the [`BestRatings`] were saved to before there were save slots, next to
+/// `Lightborne.toml`. It is moved into the [`DEFAULT_SLOT`], see
+/// [`SaveSlots::migrate_legacy_ratings`].
+const LEGACY_RATINGS_FILE: &str = "ratings.txt";
+
+/// The file in a slot the [`BestTimes`] are saved to.
+const BEST_TIMES_FILE: &str = "times.txt";
+
+/// The file in a slot the [`LitTorches`] are saved to.
+const LIT_TORCHES_FILE: &str = "torches.txt";
+
+/// The file in a slot with settings that override `Lightborne.toml` while the slot is played,
+/// written in the same format, see [`Config::load`].
+const SETTINGS_FILE: &str = "settings.toml";
+
+/// [`Plugin`] that loads the save slot picked in the `save_config` section of `Lightborne.toml`
+/// on startup, so several players can keep their own progress on the same computer. The slot can
+/// be changed in the level select, see [`SaveSlotPicker`].
+pub struct SaveSlotPlugin;
+
+impl Plugin for SaveSlotPlugin {
+    fn build(&self, app: &mut App) {
+        let save_config = &app.world().resource::<Config>().save_config;
+        let mut save_slots = SaveSlots::new(&save_config.dir);
+        save_slots.migrate_legacy_ratings(Path::new(LEGACY_RATINGS_FILE));
+        let slot = save_slots.load_slot(&save_config.slot);
+        info!("playing save slot {}", slot.name);
+
+        app.insert_resource(slot.ratings)
+            .insert_resource(slot.best_times)
+            .insert_resource(slot.lit_torches)
+            .insert_resource(save_slots)
+            .add_systems(OnEnter(UiState::LevelSelect), spawn_save_slot_picker)
+            .add_systems(OnExit(UiState::LevelSelect), despawn_save_slot_picker)
+            .add_systems(
+                Update,
+                (
+                    switch_save_slot.run_if(input_just_pressed(KeyCode::Tab)),
+                    delete_save_slot.run_if(
+                        input_just_pressed(KeyCode::Delete).and(input_pressed(KeyCode::ShiftLeft)),
+                    ),
+                    update_save_slot_picker.run_if(resource_changed::<SaveSlots>),
+                )
+                    .chain()
+                    .run_if(in_state(UiState::LevelSelect)),
+            );
+    }
+}
+
+/// [`Resource`] for the save slots stored in `dir`, one directory per slot, and the slot that is
+/// being played. Progress is written to the files of the active slot.
+#[derive(Resource, Debug)]
+pub struct SaveSlots {
+    dir: PathBuf,
+    active: String,
+}
+
+/// Everything saved in a slot, see [`SaveSlots::load_slot`].
+#[derive(Debug)]
+pub struct SaveSlot {
+    pub name: String,
+    pub ratings: BestRatings,
+    pub best_times: BestTimes,
+    pub lit_torches: LitTorches,
+    /// Settings that override `Lightborne.toml` while the slot is played
+    pub settings: toml::Table,
+}
+
+impl SaveSlot {
+    /// Reads the slot `name` saved in `dir`. Missing files are read as empty, so a slot that was
+    /// never played starts from scratch.
+    pub fn read(dir: &Path, name: &str) -> SaveSlot {
+        let slot_dir = dir.join(name);
+        SaveSlot {
+            name: name.to_string(),
+            ratings: BestRatings::load(&slot_dir.join(RATINGS_FILE)),
+            best_times: BestTimes::load(&slot_dir.join(BEST_TIMES_FILE)),
+            lit_torches: LitTorches::load(&slot_dir.join(LIT_TORCHES_FILE)),
+            settings: SaveSlot::read_settings(dir, name),
+        }
+    }
+
+    /// Reads the settings overrides of the slot `name` saved in `dir`. Settings that can't be
+    /// parsed are ignored.
+    pub fn read_settings(dir: &Path, name: &str) -> toml::Table {
+        let Ok(contents) = std::fs::read_to_string(dir.join(name).join(SETTINGS_FILE)) else {
+            return toml::Table::new();
+        };
+        toml::from_str(&contents).unwrap_or_else(|err| {
+            warn!(
+                "Failed to parse the settings of save slot {}: {}",
+                name, err
+            );
+            toml::Table::new()
+        })
+    }
+}
+
+impl SaveSlot {
+    /// Replaces the progress of the slot that was being played with the progress of this one.
+    pub fn insert_progress(self, commands: &mut Commands) {
+        commands.insert_resource(self.ratings);
+        commands.insert_resource(self.best_times);
+        commands.insert_resource(self.lit_torches);
+    }
+}
+
+/// Whether `name` can be used as the name of a slot. Slots are stored as directories, so names
+/// are limited to letters, numbers, `-` and `_`.
+pub fn valid_slot_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// `name` if it can be used as the name of a slot, or the [`DEFAULT_SLOT`] otherwise, so an
+/// invalid name in the config can't reach outside of the save directory.
+pub fn slot_name_or_default(name: &str) -> &str {
+    if valid_slot_name(name) {
+        name
+    } else {
+        DEFAULT_SLOT
+    }
+}
+
+impl SaveSlots {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        SaveSlots {
+            dir: dir.into(),
+            active: DEFAULT_SLOT.to_string(),
+        }
+    }
+
+    /// The name of the slot being played.
+    pub fn active(&self) -> &str {
+        &self.active
+    }
+
+    /// Writes `ratings` to the active slot, where [`SaveSlots::load_slot`] reads them from.
+    pub fn save_ratings(&self, ratings: &BestRatings) {
+        ratings.save(&self.dir.join(&self.active).join(RATINGS_FILE));
+    }
+
+    /// Writes `best_times` to the active slot, where [`SaveSlots::load_slot`] reads them from.
+    pub fn save_best_times(&self, best_times: &BestTimes) {
+        best_times.save(&self.dir.join(&self.active).join(BEST_TIMES_FILE));
+    }
+
+    /// The file the [`LitTorches`] of the active slot are saved to.
+    pub fn lit_torches_path(&self) -> PathBuf {
+        self.dir.join(&self.active).join(LIT_TORCHES_FILE)
+    }
+
+    /// The names of every saved slot, in alphabetical order.
+    pub fn list_slots(&self) -> Vec<String> {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return vec![];
+        };
+        let mut slots: Vec<String> = entries
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                if !entry.file_type().ok()?.is_dir() {
+                    return None;
+                }
+                let name = entry.file_name().into_string().ok()?;
+                valid_slot_name(&name).then_some(name)
+            })
+            .collect();
+        slots.sort();
+        slots
+    }
+
+    /// Makes `name` the active slot, creating it if it doesn't exist yet, and reads what was
+    /// saved in it. Invalid names fall back to the [`DEFAULT_SLOT`].
+    pub fn load_slot(&mut self, name: &str) -> SaveSlot {
+        if !valid_slot_name(name) {
+            warn!("Invalid save slot name {:?}, using {}", name, DEFAULT_SLOT);
+        }
+        let name = slot_name_or_default(name);
+        if let Err(err) = std::fs::create_dir_all(self.dir.join(name)) {
+            warn!("Failed to create save slot {}: {}", name, err);
+        }
+        self.active = name.to_string();
+        SaveSlot::read(&self.dir, name)
+    }
+
+    /// Moves the ratings saved at `legacy` before there were save slots into the
+    /// [`DEFAULT_SLOT`], unless it already has ratings of its own.
+    pub fn migrate_legacy_ratings(&self, legacy: &Path) {
+        let ratings_path = self.dir.join(DEFAULT_SLOT).join(RATINGS_FILE);
+        if !legacy.is_file() || ratings_path.exists() {
+            return;
+        }
+        let moved = std::fs::create_dir_all(self.dir.join(DEFAULT_SLOT))
+            .and_then(|_| std::fs::copy(legacy, &ratings_path))
+            .and_then(|_| std::fs::remove_file(legacy));
+        match moved {
+            Ok(()) => info!("moved {} into save slot {}", legacy.display(), DEFAULT_SLOT),
+            Err(err) => warn!(
+                "Failed to move {} into a save slot: {}",
+                legacy.display(),
+                err
+            ),
+        }
+    }
+
+    /// Deletes the slot `name` and everything saved in it. Deleting the active slot starts it
+    /// over as a fresh slot, which is returned so the progress that was loaded from it can be
+    /// replaced.
+    pub fn delete_slot(&mut self, name: &str) -> io::Result<Option<SaveSlot>> {
+        if !valid_slot_name(name) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid save slot name {:?}", name),
+            ));
+        }
+        std::fs::remove_dir_all(self.dir.join(name))?;
+        if name != self.active {
+            return Ok(None);
+        }
+        Ok(Some(self.load_slot(name)))
+    }
+}
+
+/// Marker [`Component`] for the text in the corner of the level select showing the active save
+/// slot. Tab switches to the next saved slot, and Shift + Delete starts the active slot over. The
+/// settings of a slot are read on startup, so they only apply once the slot is picked in
+/// `Lightborne.toml`.
+#[derive(Component)]
+pub struct SaveSlotPicker;
+
+fn save_slot_picker_text(save_slots: &SaveSlots) -> String {
+    format!(
+        "Save slot: {}  (Tab: next slot, Shift + Delete: start over)",
+        save_slots.active()
+    )
+}
+
+fn spawn_save_slot_picker(
+    mut commands: Commands,
+    save_slots: Res<SaveSlots>,
+    asset_server: Res<AssetServer>,
+) {
+    commands.spawn((
+        SaveSlotPicker,
+        Text::new(save_slot_picker_text(&save_slots)),
+        TextFont {
+            font: asset_server.load("fonts/Munro.ttf"),
+            font_size: 18.0,
+            ..default()
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(12.0),
+            bottom: Val::Px(8.0),
+            ..default()
+        },
+        // above the level select
+        GlobalZIndex(1),
+    ));
+}
+
+fn despawn_save_slot_picker(mut commands: Commands, q_picker: Query<Entity, With<SaveSlotPicker>>) {
+    for entity in q_picker.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// [`System`] that makes the saved slot after the active one, in alphabetical order, the active
+/// slot, and loads its progress.
+pub fn switch_save_slot(mut commands: Commands, mut save_slots: ResMut<SaveSlots>) {
+    let slots = save_slots.list_slots();
+    let next = slots
+        .iter()
+        .find(|name| name.as_str() > save_slots.active())
+        .or(slots.first());
+    let Some(next) = next.cloned() else {
+        return;
+    };
+    if next == save_slots.active() {
+        return;
+    }
+    let slot = save_slots.load_slot(&next);
+    info!("playing save slot {}", slot.name);
+    slot.insert_progress(&mut commands);
+}
+
+/// [`System`] that deletes everything saved in the active slot, and starts it over.
+pub fn delete_save_slot(mut commands: Commands, mut save_slots: ResMut<SaveSlots>) {
+    let active = save_slots.active().to_string();
+    match save_slots.delete_slot(&active) {
+        Ok(Some(fresh)) => {
+            info!("started save slot {} over", fresh.name);
+            fresh.insert_progress(&mut commands);
+        }
+        Ok(None) => {}
+        Err(err) => warn!("Failed to delete save slot {}: {}", active, err),
+    }
+}
+
+fn update_save_slot_picker(
+    mut q_picker: Query<&mut Text, With<SaveSlotPicker>>,
+    save_slots: Res<SaveSlots>,
+) {
+    for mut text in q_picker.iter_mut() {
+        text.0 = save_slot_picker_text(&save_slots);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, time::Duration};
+
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    #[test]
+    fn slots_keep_separate_progress() {
+        let dir =
+            std::env::temp_dir().join(format!("lightborne-save-slots-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut save_slots = SaveSlots::new(&dir);
+        assert!(save_slots.list_slots().is_empty());
+
+        for (i, name) in ["alice", "bob", "carol"].into_iter().enumerate() {
+            let mut slot = save_slots.load_slot(name);
+            assert!(slot.ratings.0.is_empty());
+            slot.ratings.record(name, i as u8 + 1);
+            save_slots.save_ratings(&slot.ratings);
+            slot.best_times
+                .record(name, Duration::from_secs(10 + i as u64));
+            save_slots.save_best_times(&slot.best_times);
+        }
+        assert_eq!(save_slots.list_slots(), vec!["alice", "bob", "carol"]);
+        std::fs::write(
+            dir.join("carol").join(SETTINGS_FILE),
+            "[hud_config]\nenabled = false\n",
+        )
+        .unwrap();
+
+        let bob = save_slots.load_slot("bob");
+        assert_eq!(save_slots.active(), "bob");
+        assert_eq!(bob.ratings.0.len(), 1);
+        assert_eq!(bob.ratings.0["bob"], 2);
+        assert_eq!(bob.best_times.0["bob"], Duration::from_secs(11));
+        assert!(bob.settings.is_empty());
+        let carol = SaveSlot::read(&dir, "carol");
+        assert_eq!(
+            carol.settings["hud_config"]["enabled"].as_bool(),
+            Some(false)
+        );
+
+        assert!(save_slots.delete_slot("alice").unwrap().is_none());
+        assert_eq!(save_slots.list_slots(), vec!["bob", "carol"]);
+        assert!(save_slots.delete_slot("alice").is_err());
+        assert!(save_slots.delete_slot("../carol").is_err());
+
+        // the active slot starts over
+        let fresh = save_slots.delete_slot("bob").unwrap().unwrap();
+        assert_eq!(fresh.name, "bob");
+        assert!(fresh.ratings.0.is_empty());
+        assert!(fresh.best_times.0.is_empty());
+        assert!(fresh.lit_torches.0.is_empty());
+        assert_eq!(save_slots.active(), "bob");
+        assert_eq!(save_slots.list_slots(), vec!["bob", "carol"]);
+
+        assert_eq!(save_slots.load_slot("../carol").name, DEFAULT_SLOT);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn picker_switches_and_starts_over_slots() {
+        let dir =
+            std::env::temp_dir().join(format!("lightborne-slot-picker-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut save_slots = SaveSlots::new(&dir);
+        for (i, name) in ["alice", "bob"].into_iter().enumerate() {
+            let mut slot = save_slots.load_slot(name);
+            slot.ratings.record(name, i as u8 + 1);
+            save_slots.save_ratings(&slot.ratings);
+        }
+        let alice = save_slots.load_slot("alice");
+
+        let mut app = App::new();
+        app.insert_resource(save_slots);
+        alice.insert_progress(&mut app.world_mut().commands());
+        app.world_mut().flush();
+        let ratings = |app: &App| app.world().resource::<BestRatings>().0.clone();
+        let active = |app: &App| app.world().resource::<SaveSlots>().active().to_string();
+
+        app.world_mut().run_system_once(switch_save_slot).unwrap();
+        assert_eq!(active(&app), "bob");
+        assert_eq!(ratings(&app), HashMap::from([("bob".to_string(), 2)]));
+        // wrapping around to the first slot
+        app.world_mut().run_system_once(switch_save_slot).unwrap();
+        assert_eq!(active(&app), "alice");
+        assert_eq!(ratings(&app)["alice"], 1);
+
+        app.world_mut().run_system_once(delete_save_slot).unwrap();
+        assert_eq!(active(&app), "alice");
+        assert!(ratings(&app).is_empty());
+        assert_eq!(
+            app.world().resource::<SaveSlots>().list_slots(),
+            vec!["alice", "bob"]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn legacy_ratings_move_into_the_default_slot() {
+        let dir =
+            std::env::temp_dir().join(format!("lightborne-legacy-ratings-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let legacy = dir.join(LEGACY_RATINGS_FILE);
+        std::fs::write(&legacy, "level-a 3\n").unwrap();
+
+        let mut save_slots = SaveSlots::new(dir.join("saves"));
+        save_slots.migrate_legacy_ratings(&legacy);
+        assert!(!legacy.exists());
+        let slot = save_slots.load_slot(DEFAULT_SLOT);
+        assert_eq!(slot.ratings.0["level-a"], 3);
+
+        // ratings already in the slot are never overwritten
+        std::fs::write(&legacy, "level-a 1\n").unwrap();
+        save_slots.migrate_legacy_ratings(&legacy);
+        assert!(legacy.exists());
+        assert_eq!(save_slots.load_slot(DEFAULT_SLOT).ratings.0["level-a"], 3);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn invalid_slot_names_use_the_default_slot() {
+        assert_eq!(slot_name_or_default("alice"), "alice");
+        assert_eq!(slot_name_or_default("../alice"), DEFAULT_SLOT);
+        assert_eq!(slot_name_or_default(""), DEFAULT_SLOT);
+    }
+}
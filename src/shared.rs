@@ -17,6 +17,17 @@ impl GroupLabel {
     pub const STRAND: Group = Group::GROUP_8;
     pub const BLUE_RAY: Group = Group::GROUP_9;
     pub const CRYSTAL_SHARD: Group = Group::GROUP_10;
+    /// Terrain only the player collides with, see [`Solidity`](crate::level::solidity::Solidity)
+    pub const PLAYER_TERRAIN: Group = Group::GROUP_11;
+    /// Terrain only light beams collide with
+    pub const BEAM_TERRAIN: Group = Group::GROUP_12;
+    /// Light sensors beams pass through instead of stopping at, see
+    /// [`is_pass_through_sensor`](crate::level::sensor::is_pass_through_sensor)
+    pub const PASS_THROUGH_SENSOR: Group = Group::GROUP_13;
+    /// The first of the groups of [`SpectralOccluder`](crate::light::spectral::SpectralOccluder)s,
+    /// one per [`LightColor`](crate::light::LightColor), see
+    /// [`LightColor::spectral_collision_group`](crate::light::LightColor::spectral_collision_group)
+    pub const SPECTRAL_OCCLUDER: Group = Group::GROUP_14;
     pub const ALL: Group = Group::from_bits_truncate(!0);
 }
 
@@ -35,6 +46,8 @@ pub enum AnimationState {
     Switch,
     Respawn,
     Shard,
+    /// The player poses after completing a level, see [`WinPose`](crate::player::win::WinPose)
+    Win,
 }
 
 #[derive(SubStates, Default, Debug, Clone, PartialEq, Eq, Hash)]
@@ -44,7 +57,7 @@ pub enum UiState {
     LevelSelect,
 }
 
-#[derive(Event, PartialEq, Eq)]
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ResetLevel {
     /// Sent to run systems that reset the player state on respawn. If you are trying to kill the
     /// player, use `KillPlayerEvent` instead
@@ -0,0 +1,147 @@
+use bevy::{
+    input::common_conditions::input_just_pressed,
+    prelude::*,
+    window::{MonitorSelection, PresentMode, PrimaryWindow, WindowMode, WindowResized},
+};
+
+use crate::config::{Config, WindowConfig, WindowModeSetting};
+
+/// The file the last window size is saved to when `window_config.remember_size` is set.
+const WINDOW_SIZE_PATH: &str = "window_size.txt";
+
+/// [`Plugin`] for changing the window while the game is running. The initial window is created
+/// from the `window_config` section of `Lightborne.toml` by [`primary_window`].
+pub struct WindowSettingsPlugin;
+
+impl Plugin for WindowSettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            toggle_fullscreen.run_if(input_just_pressed(KeyCode::F11)),
+        );
+
+        if app.world().resource::<Config>().window_config.remember_size {
+            app.init_resource::<LastWindowSize>()
+                .add_systems(Update, track_window_size)
+                .add_systems(Last, save_window_size.run_if(on_event::<AppExit>));
+        }
+    }
+}
+
+/// Creates the primary [`Window`] described by the [`WindowConfig`]. If the config asks to
+/// remember the window size, the size saved by the last run is used instead of the configured
+/// resolution.
+pub fn primary_window(window_config: &WindowConfig) -> Window {
+    let saved_size = if window_config.remember_size {
+        std::fs::read_to_string(WINDOW_SIZE_PATH)
+            .ok()
+            .and_then(|contents| parse_window_size(&contents))
+    } else {
+        None
+    };
+    let size = window_config.resolution(saved_size);
+
+    Window {
+        title: "Lightborne".into(),
+        name: Some("lightborne".into()),
+        resolution: size.into(),
+        mode: window_mode(window_config.mode),
+        present_mode: if window_config.vsync {
+            PresentMode::AutoVsync
+        } else {
+            PresentMode::AutoNoVsync
+        },
+        canvas: Some("#bevy-container".into()),
+        fit_canvas_to_parent: true,
+        prevent_default_event_handling: false,
+        ..default()
+    }
+}
+
+fn window_mode(mode: WindowModeSetting) -> WindowMode {
+    match mode {
+        WindowModeSetting::Windowed => WindowMode::Windowed,
+        WindowModeSetting::Borderless => {
+            WindowMode::BorderlessFullscreen(MonitorSelection::Current)
+        }
+        WindowModeSetting::Fullscreen => WindowMode::Fullscreen(MonitorSelection::Current),
+    }
+}
+
+/// Parses a window size saved by [`save_window_size`], written as the width and height separated
+/// by a space.
+fn parse_window_size(contents: &str) -> Option<Vec2> {
+    let mut parts = contents.split_whitespace();
+    let width = parts.next()?.parse().ok()?;
+    let height = parts.next()?.parse().ok()?;
+    Some(Vec2::new(width, height))
+}
+
+/// [`System`] that switches the window between windowed and fullscreen on F11. Goes back to
+/// the configured fullscreen mode, or borderless fullscreen if the game started windowed.
+pub fn toggle_fullscreen(
+    mut q_window: Query<&mut Window, With<PrimaryWindow>>,
+    config: Res<Config>,
+) {
+    let Ok(mut window) = q_window.get_single_mut() else {
+        return;
+    };
+    window.mode = match window.mode {
+        WindowMode::Windowed => match config.window_config.mode {
+            WindowModeSetting::Windowed => window_mode(WindowModeSetting::Borderless),
+            mode => window_mode(mode),
+        },
+        _ => WindowMode::Windowed,
+    };
+}
+
+/// [`Resource`] that holds the size of the window when it was last windowed, saved to
+/// [`WINDOW_SIZE_PATH`] when the game closes.
+#[derive(Resource, Default)]
+pub struct LastWindowSize(Option<Vec2>);
+
+/// [`System`] that stores the size of the primary window in [`LastWindowSize`]. Sizes are only
+/// stored while windowed, so fullscreen doesn't overwrite the size the window will go back to.
+pub fn track_window_size(
+    mut ev_window_resized: EventReader<WindowResized>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    mut last_window_size: ResMut<LastWindowSize>,
+) {
+    let Some(resized) = ev_window_resized.read().last() else {
+        return;
+    };
+    let Ok(window) = q_window.get(resized.window) else {
+        return;
+    };
+    if window.mode == WindowMode::Windowed {
+        last_window_size.0 = Some(Vec2::new(resized.width, resized.height));
+    }
+}
+
+/// [`System`] that writes the [`LastWindowSize`] to disk when the game is closed.
+pub fn save_window_size(last_window_size: Res<LastWindowSize>) {
+    let Some(size) = last_window_size.0 else {
+        return;
+    };
+    if let Err(err) = std::fs::write(WINDOW_SIZE_PATH, format!("{} {}", size.x, size.y)) {
+        error!(
+            "Failed to save window size to {}: {}",
+            WINDOW_SIZE_PATH, err
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_saved_window_size() {
+        assert_eq!(
+            parse_window_size("1600 900"),
+            Some(Vec2::new(1600.0, 900.0))
+        );
+        assert_eq!(parse_window_size("1600"), None);
+        assert_eq!(parse_window_size("wide tall"), None);
+    }
+}